@@ -0,0 +1,292 @@
+use arrow::{
+    array::{ArrayRef, Float64Array, Int64Array, StringArray},
+    datatypes::{DataType, Schema},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use async_trait::async_trait;
+use proboscis_anonymization::PrivacyBudgetLedger;
+use proboscis_core::{
+    data::field::{Field, TEXT_FORMAT},
+    resolver::{
+        Bind, ClientContext, ClientId, Close, Describe, Execute, Parse, ResolveError, Resolver,
+        SyncResponse,
+    },
+    sessions::{sessions, Session},
+};
+use std::sync::Arc;
+
+const SIMPLE_QUERIES_ONLY: &str = "the admin database only answers simple queries";
+
+/// Answers the queries of the admin database, which describe the proxy itself
+/// instead of being forwarded to a target
+pub struct AdminResolver {
+    users: Vec<String>,
+    ledger: Option<Arc<PrivacyBudgetLedger>>,
+}
+
+impl AdminResolver {
+    pub fn new(users: Vec<String>) -> AdminResolver {
+        AdminResolver {
+            users,
+            ledger: None,
+        }
+    }
+
+    /// Answers `SHOW BUDGET` with the balances of the ledger
+    pub fn with_budget_ledger(mut self, ledger: Arc<PrivacyBudgetLedger>) -> Self {
+        self.ledger = Some(ledger);
+        self
+    }
+}
+
+fn field(name: &str, data_type: DataType) -> arrow::datatypes::Field {
+    (&Field {
+        name: name.to_string(),
+        table_oid: 0,
+        column_number: 0,
+        data_type,
+        extension: None,
+        format: TEXT_FORMAT,
+        original_type: None,
+    })
+        .into()
+}
+
+/// One row per connected client, with the backend currently serving it
+pub fn sessions_record_batch(sessions: &[Session]) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(vec![
+        field("session", DataType::Utf8),
+        field("client_id", DataType::Utf8),
+        field("client_addr", DataType::Utf8),
+        field("user", DataType::Utf8),
+        field("backend_pid", DataType::Int64),
+    ]);
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            sessions.iter().map(|session| &session.correlation_id),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            sessions.iter().map(|session| session.client_id.to_string()),
+        )),
+        Arc::new(StringArray::from_iter_values(
+            sessions.iter().map(|session| session.address.to_string()),
+        )),
+        Arc::new(
+            sessions
+                .iter()
+                .map(|session| session.user.as_deref())
+                .collect::<StringArray>(),
+        ),
+        Arc::new(
+            sessions
+                .iter()
+                .map(|session| session.backend_pid.map(i64::from))
+                .collect::<Int64Array>(),
+        ),
+    ];
+
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+/// One row per user that spent some of the privacy budget, with the epsilon it has left
+pub fn budget_record_batch(balances: &[(String, f64)]) -> Result<RecordBatch, ArrowError> {
+    let schema = Schema::new(vec![
+        field("user", DataType::Utf8),
+        field("remaining_epsilon", DataType::Float64),
+    ]);
+
+    let columns: Vec<ArrayRef> = vec![
+        Arc::new(StringArray::from_iter_values(
+            balances.iter().map(|(user, _)| user),
+        )),
+        Arc::new(Float64Array::from_iter_values(
+            balances.iter().map(|(_, remaining)| *remaining),
+        )),
+    ];
+
+    RecordBatch::try_new(Arc::new(schema), columns)
+}
+
+/// Whether the query is `SHOW` of the name, e.g. `show sessions;`
+fn is_show(query: &str, name: &str) -> bool {
+    let words: Vec<String> = query
+        .trim_end()
+        .trim_end_matches(';')
+        .split_whitespace()
+        .map(|word| word.to_uppercase())
+        .collect();
+
+    words == ["SHOW", name]
+}
+
+#[async_trait]
+impl Resolver for AdminResolver {
+    async fn initialize(
+        &mut self,
+        _client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        match context.user() {
+            Some(user) if self.users.iter().any(|allowed| allowed == user) => Ok(()),
+            _ => Err("the user isn't allowed to connect to the admin database".into()),
+        }
+    }
+
+    async fn query(
+        &mut self,
+        _client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        if is_show(&query, "SESSIONS") {
+            return Ok(vec![sessions_record_batch(&sessions())?]);
+        }
+
+        if is_show(&query, "BUDGET") {
+            return match &self.ledger {
+                Some(ledger) => Ok(vec![budget_record_batch(&ledger.balances())?]),
+                None => Err("no privacy budget is configured".into()),
+            };
+        }
+
+        Err("the admin database only answers SHOW SESSIONS and SHOW BUDGET".into())
+    }
+
+    async fn parse(&mut self, _client_id: ClientId, _parse: Parse) -> Result<(), ResolveError> {
+        Err(SIMPLE_QUERIES_ONLY.into())
+    }
+
+    async fn describe(
+        &mut self,
+        _client_id: ClientId,
+        _describe: Describe,
+    ) -> Result<(), ResolveError> {
+        Err(SIMPLE_QUERIES_ONLY.into())
+    }
+
+    async fn bind(&mut self, _client_id: ClientId, _bind: Bind) -> Result<(), ResolveError> {
+        Err(SIMPLE_QUERIES_ONLY.into())
+    }
+
+    async fn execute(
+        &mut self,
+        _client_id: ClientId,
+        _execute: Execute,
+    ) -> Result<(), ResolveError> {
+        Err(SIMPLE_QUERIES_ONLY.into())
+    }
+
+    async fn sync(&mut self, _client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        Ok(vec![])
+    }
+
+    async fn close(&mut self, _client_id: ClientId, _close: Close) -> Result<(), ResolveError> {
+        Ok(())
+    }
+
+    async fn terminate(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Array;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_sessions_record_batch() {
+        let client_id = ClientId::parse_str("0f1e2d3c-4b5a-6978-8796-a5b4c3d2e1f0").unwrap();
+        let session = Session {
+            client_id,
+            correlation_id: "0f1e2d3c".to_string(),
+            address: "10.0.0.7:50123".parse().unwrap(),
+            user: Some("analyst".to_string()),
+            backend_pid: None,
+        };
+
+        let batch = sessions_record_batch(&[session]).unwrap();
+        assert_eq!(1, batch.num_rows());
+        assert_eq!(5, batch.num_columns());
+
+        let sessions = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!("0f1e2d3c", sessions.value(0));
+        assert!(batch.column(4).is_null(0));
+    }
+
+    #[tokio::test]
+    async fn test_admin_resolver() {
+        let mut resolver = AdminResolver::new(vec!["ops".to_string()]);
+        let client_id = ClientId::new_v4();
+        let context = |user: &str| {
+            let mut parameters = HashMap::new();
+            parameters.insert("user".to_string(), user.to_string());
+            ClientContext::new(parameters)
+        };
+
+        assert!(resolver
+            .initialize(client_id, &context("analyst"))
+            .await
+            .is_err());
+        resolver
+            .initialize(client_id, &context("ops"))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            5,
+            resolver
+                .query(client_id, "show sessions;".to_string())
+                .await
+                .unwrap()[0]
+                .num_columns()
+        );
+        assert!(resolver
+            .query(client_id, "SELECT 1".to_string())
+            .await
+            .is_err());
+        assert!(resolver
+            .query(client_id, "SHOW BUDGET".to_string())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_show_budget() {
+        let ledger = Arc::new(PrivacyBudgetLedger::new(1.0));
+        ledger.consume("bob", 0.25).unwrap();
+        ledger.consume("alice", 0.5).unwrap();
+
+        let mut resolver =
+            AdminResolver::new(vec!["ops".to_string()]).with_budget_ledger(ledger.clone());
+        let client_id = ClientId::new_v4();
+
+        let batch = resolver
+            .query(client_id, "show budget;".to_string())
+            .await
+            .unwrap()
+            .remove(0);
+        assert_eq!(2, batch.num_rows());
+
+        let users = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let remaining = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!("alice", users.value(0));
+        assert_eq!(0.5, remaining.value(0));
+        assert_eq!("bob", users.value(1));
+        assert_eq!(0.75, remaining.value(1));
+    }
+}
@@ -0,0 +1,177 @@
+use crate::config::{ColumnAnnotationsConfig, ColumnConfiguration};
+use crate::target::query_target;
+use anyhow::{anyhow, Result};
+use arrow::array::{Array, GenericStringArray};
+
+// Comments classify their column with the prefix followed by the classification, e.g.
+// `COMMENT ON COLUMN contacts.email IS 'Primary contact, pgcloak: identifier'`
+const COMMENT_PREFIX: &str = "pgcloak:";
+
+const COMMENTS_QUERY: &str = "SELECT c.relname::text, a.attname::text, d.description::text \
+    FROM pg_description d \
+    JOIN pg_class c ON c.oid = d.objoid \
+    JOIN pg_namespace n ON n.oid = c.relnamespace \
+    JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = d.objsubid \
+    WHERE d.classoid = 'pg_class'::regclass AND d.objsubid > 0 \
+    AND n.nspname NOT IN ('pg_catalog', 'information_schema')";
+
+fn security_labels_query(provider: &str) -> String {
+    format!(
+        "SELECT c.relname::text, a.attname::text, s.label::text \
+        FROM pg_seclabel s \
+        JOIN pg_class c ON c.oid = s.objoid \
+        JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum = s.objsubid \
+        WHERE s.classoid = 'pg_class'::regclass AND s.objsubid > 0 AND s.provider = '{}'",
+        provider.replace('\'', "''")
+    )
+}
+
+/// The classification of a comment, none if it doesn't contain the prefix
+pub fn comment_classification(comment: &str) -> Option<String> {
+    let comment = comment.to_lowercase();
+    let start = comment.find(COMMENT_PREFIX)? + COMMENT_PREFIX.len();
+
+    comment[start..]
+        .split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .find(|word| !word.is_empty())
+        .map(str::to_string)
+}
+
+/// The rule of a column for a classification, which are named like the `type` of `[[columns]]`
+pub fn column_configuration(name: &str, classification: &str) -> Result<ColumnConfiguration> {
+    let name = name.to_string();
+
+    match classification.trim().to_lowercase().as_str() {
+        "identifier" => Ok(ColumnConfiguration::Identifier {
+            name,
+            users: vec![],
+            roles: vec![],
+        }),
+        "pseudo_identifier" | "quasi_identifier" => Ok(ColumnConfiguration::PseudoIdentifier {
+            name,
+            numeric_aggregation: Default::default(),
+            string_aggregation: Default::default(),
+            users: vec![],
+            roles: vec![],
+        }),
+        "denied" => Ok(ColumnConfiguration::Denied {
+            name,
+            users: vec![],
+            roles: vec![],
+        }),
+        classification => Err(anyhow!(
+            "column '{}' is annotated with the unknown classification '{}'",
+            name,
+            classification
+        )),
+    }
+}
+
+/// Rows of qualified column names and their annotation
+async fn load_annotations(connection_uri: &str, query: &str) -> Result<Vec<(String, String)>> {
+    let mut annotations = vec![];
+
+    for batch in query_target(connection_uri, query).await? {
+        let text_column = |index: usize| {
+            batch
+                .column(index)
+                .as_any()
+                .downcast_ref::<GenericStringArray<i64>>()
+                .ok_or_else(|| anyhow!("unexpected type of column {} in the catalog", index))
+        };
+
+        let (tables, names, annotations_column) =
+            (text_column(0)?, text_column(1)?, text_column(2)?);
+        for row in 0..batch.num_rows() {
+            annotations.push((
+                format!("{}.{}", tables.value(row), names.value(row)),
+                annotations_column.value(row).to_string(),
+            ));
+        }
+    }
+
+    Ok(annotations)
+}
+
+/// Loads the rules of the columns classified by comments or security labels in the database
+pub async fn load_annotated_columns(
+    connection_uri: &str,
+    config: &ColumnAnnotationsConfig,
+) -> Result<Vec<ColumnConfiguration>> {
+    let mut columns = vec![];
+
+    if config.comments {
+        for (name, comment) in load_annotations(connection_uri, COMMENTS_QUERY).await? {
+            if let Some(classification) = comment_classification(&comment) {
+                columns.push(column_configuration(&name, &classification)?);
+            }
+        }
+    }
+
+    if let Some(provider) = &config.security_label_provider {
+        let query = security_labels_query(provider);
+        for (name, label) in load_annotations(connection_uri, &query).await? {
+            columns.push(column_configuration(&name, &label)?);
+        }
+    }
+
+    Ok(columns)
+}
+
+/// Adds the annotated columns to the configured ones, which take precedence
+pub fn merge_columns(
+    configured: &mut Vec<ColumnConfiguration>,
+    annotated: Vec<ColumnConfiguration>,
+) {
+    for column in annotated {
+        if !configured
+            .iter()
+            .any(|configured| configured.name() == column.name())
+        {
+            configured.push(column);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_comment_classification() {
+        assert_eq!(
+            Some("identifier".to_string()),
+            comment_classification("Primary contact, pgcloak: identifier")
+        );
+        assert_eq!(
+            Some("denied".to_string()),
+            comment_classification("PGCLOAK:denied; never shown")
+        );
+        assert_eq!(None, comment_classification("The email of the contact"));
+    }
+
+    #[test]
+    fn test_merge_columns() {
+        let mut configured = vec![column_configuration("contacts.email", "denied").unwrap()];
+
+        merge_columns(
+            &mut configured,
+            vec![
+                column_configuration("contacts.email", "identifier").unwrap(),
+                column_configuration("contacts.age", "pseudo_identifier").unwrap(),
+            ],
+        );
+
+        assert_eq!(2, configured.len());
+        assert!(matches!(
+            &configured[0],
+            ColumnConfiguration::Denied { name, .. } if name == "contacts.email"
+        ));
+        assert!(matches!(
+            &configured[1],
+            ColumnConfiguration::PseudoIdentifier { name, .. } if name == "contacts.age"
+        ));
+
+        assert!(column_configuration("contacts.age", "secret").is_err());
+    }
+}
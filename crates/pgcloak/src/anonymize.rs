@@ -0,0 +1,181 @@
+use crate::target::query_target;
+use anyhow::{anyhow, Result};
+use arrow::{
+    csv, datatypes::Schema, error::ArrowError, ipc::writer::FileWriter, record_batch::RecordBatch,
+};
+use proboscis_resolver_transformer::{
+    projection::{ProjectedOrigin, TableColumn},
+    Transformer,
+};
+use std::{fs::File, path::Path};
+
+/// Every column of the data is attributed to the table, so the column rules
+/// apply to the file as if it was the result of `SELECT * FROM table`
+fn table_origins(table: &str, schema: &Schema) -> Vec<ProjectedOrigin> {
+    schema
+        .fields()
+        .iter()
+        .map(|field| {
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: table.to_string(),
+                column: field.name().clone(),
+            })
+        })
+        .collect()
+}
+
+/// Applies the transformer to the whole data at once, like the proxy does for a result
+pub fn anonymize_batches(
+    data: &[RecordBatch],
+    table: &str,
+    transformer: &dyn Transformer,
+) -> Result<Vec<RecordBatch>> {
+    let schema = match data.first() {
+        Some(batch) => batch.schema(),
+        None => return Ok(vec![]),
+    };
+
+    Ok(transformer.transform_batches(data, &table_origins(table, &schema))?)
+}
+
+/// Reads a csv file with a header row, inferring the column types
+pub fn read_csv(path: &Path) -> Result<Vec<RecordBatch>> {
+    let reader = csv::ReaderBuilder::new()
+        .has_header(true)
+        .infer_schema(Some(1000))
+        .build(File::open(path)?)?;
+
+    Ok(reader.collect::<Result<Vec<RecordBatch>, ArrowError>>()?)
+}
+
+/// Writes the data as csv or as an arrow ipc file, depending on the extension of the path
+pub fn write_output(path: &Path, data: &[RecordBatch]) -> Result<()> {
+    let extension = path.extension().and_then(|extension| extension.to_str());
+
+    match extension {
+        Some("csv") => {
+            let mut writer = csv::Writer::new(File::create(path)?);
+            for batch in data {
+                writer.write(batch)?;
+            }
+        }
+        Some("arrow") => {
+            let schema = data
+                .first()
+                .map(|batch| batch.schema())
+                .ok_or_else(|| anyhow!("there is no data to write"))?;
+
+            let mut writer = FileWriter::try_new(File::create(path)?, &schema)?;
+            for batch in data {
+                writer.write(batch)?;
+            }
+            writer.finish()?;
+        }
+        _ => {
+            return Err(anyhow!(
+                "unsupported output format of '{}', use a .csv or .arrow file",
+                path.display()
+            ))
+        }
+    }
+
+    Ok(())
+}
+
+/// Anonymizes a csv file, or the whole table if there is no input file,
+/// and writes the result to the output file
+pub async fn anonymize(
+    connection_uri: &str,
+    table: &str,
+    input: Option<&Path>,
+    output: &Path,
+    transformer: &dyn Transformer,
+) -> Result<()> {
+    let data = match input {
+        Some(input) => read_csv(input)?,
+        None => query_target(connection_uri, &format!("SELECT * FROM {}", table)).await?,
+    };
+
+    let anonymized = anonymize_batches(&data, table, transformer)?;
+    write_output(output, &anonymized)?;
+
+    let rows: usize = anonymized.iter().map(|batch| batch.num_rows()).sum();
+    println!("wrote {} anonymized rows to {}", rows, output.display());
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::{DataType, Field};
+    use proboscis_anonymization::{
+        AnonymizationCriteria, AnonymizationTransformer, ColumnConcurrency, NumericAggregation,
+        StringAggregation,
+    };
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_anonymize_file() {
+        let directory =
+            std::env::temp_dir().join(format!("pgcloak-anonymize-test-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let input = directory.join("contacts.csv");
+        std::fs::write(
+            &input,
+            "first_name,age\nAlex,21\nMia,22\nNoah,35\nLukas,36\n",
+        )
+        .unwrap();
+
+        let transformer = AnonymizationTransformer {
+            identifier_columns: vec!["contacts.first_name".to_string()],
+            quasi_identifier_columns: vec![(
+                "contacts.age".to_string(),
+                (NumericAggregation::Range, StringAggregation::Join),
+            )]
+            .into_iter()
+            .collect(),
+            criteria: vec![AnonymizationCriteria::KAnonymous { k: 2 }],
+            table_criteria: HashMap::new(),
+            concurrency: ColumnConcurrency::sequential(),
+        };
+
+        let anonymized =
+            anonymize_batches(&read_csv(&input).unwrap(), "contacts", &transformer).unwrap();
+
+        let output = directory.join("contacts.anonymized.csv");
+        write_output(&output, &anonymized).unwrap();
+        let written = std::fs::read_to_string(&output).unwrap();
+
+        assert_eq!(5, written.lines().count());
+        assert!(!written.contains("Alex"));
+        assert!(!written.contains(",21\n"));
+
+        assert!(write_output(&directory.join("contacts.parquet"), &anonymized).is_err());
+
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn test_table_origins() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+
+        assert_eq!(
+            vec![
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: "contacts".to_string(),
+                    column: "id".to_string(),
+                }),
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: "contacts".to_string(),
+                    column: "name".to_string(),
+                }),
+            ],
+            table_origins("contacts", &schema)
+        );
+    }
+}
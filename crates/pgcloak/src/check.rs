@@ -0,0 +1,195 @@
+use crate::config::{ApplicationConfig, ColumnConfiguration, Target};
+use anyhow::{anyhow, Result};
+use proboscis_core::catalog::{Catalog, CatalogSource};
+use proboscis_resolver_postgres::{PostgresCatalogSource, TargetConfig};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Types of the target database quasi-identifiers can be aggregated for,
+/// as they are named by `format_type`
+pub const SUPPORTED_QUASI_IDENTIFIER_TYPES: &[&str] =
+    &["smallint", "integer", "bigint", "character varying", "text"];
+
+#[derive(Debug, PartialEq)]
+pub enum Problem {
+    /// Column names have to be qualified with their table, e.g. `contacts.age`
+    InvalidColumnName(String),
+    UnknownColumn(String),
+    UnsupportedType {
+        column: String,
+        data_type: String,
+    },
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Problem::InvalidColumnName(column) => write!(
+                f,
+                "column '{}' isn't qualified with its table, like 'table.column'",
+                column
+            ),
+            Problem::UnknownColumn(column) => {
+                write!(f, "column '{}' doesn't exist in the database", column)
+            }
+            Problem::UnsupportedType { column, data_type } => write!(
+                f,
+                "quasi-identifier column '{}' has the unsupported type '{}'",
+                column, data_type
+            ),
+        }
+    }
+}
+
+/// Compares the configured columns with the columns of the database, which
+/// map a qualified column name to the name of its type
+pub fn check_columns(
+    columns: &[ColumnConfiguration],
+    database_columns: &HashMap<String, String>,
+) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    for column in columns {
+        let (name, is_quasi_identifier) = match column {
+            ColumnConfiguration::Identifier { name, .. }
+            | ColumnConfiguration::Denied { name, .. } => (name, false),
+            ColumnConfiguration::PseudoIdentifier { name, .. } => (name, true),
+        };
+
+        if !name.contains('.') {
+            problems.push(Problem::InvalidColumnName(name.clone()));
+            continue;
+        }
+
+        match database_columns.get(name) {
+            None => problems.push(Problem::UnknownColumn(name.clone())),
+            Some(data_type)
+                if is_quasi_identifier
+                    && !SUPPORTED_QUASI_IDENTIFIER_TYPES.contains(&data_type.as_str()) =>
+            {
+                problems.push(Problem::UnsupportedType {
+                    column: name.clone(),
+                    data_type: data_type.clone(),
+                })
+            }
+            Some(_) => {}
+        }
+    }
+
+    problems
+}
+
+/// The columns of every table and view of the catalog, keyed by `table.column`
+pub fn database_columns(catalog: &Catalog) -> HashMap<String, String> {
+    catalog
+        .relations
+        .iter()
+        .flat_map(|relation| {
+            relation.columns.iter().map(move |column| {
+                (
+                    format!("{}.{}", relation.name, column.name),
+                    column.type_name.clone(),
+                )
+            })
+        })
+        .collect()
+}
+
+/// Loads the columns of every table and view in the target database, keyed by `table.column`
+pub async fn load_database_columns(connection_uri: &str) -> Result<HashMap<String, String>> {
+    let target_config = TargetConfig::from_uri(connection_uri).map_err(|err| anyhow!(err))?;
+
+    let catalog = PostgresCatalogSource::new(target_config)
+        .load()
+        .await
+        .map_err(|err| anyhow!("couldn't load the catalog of the database: {:?}", err))?;
+
+    Ok(database_columns(&catalog))
+}
+
+async fn check_target(target: &Target) -> Result<bool> {
+    let database_columns = load_database_columns(&target.connection_uri).await?;
+    let problems = check_columns(&target.columns, &database_columns);
+
+    let prefix = match &target.name {
+        Some(name) => format!("database '{}': ", name),
+        None => String::new(),
+    };
+
+    for problem in &problems {
+        eprintln!("error: {}{}", prefix, problem);
+    }
+
+    if problems.is_empty() {
+        println!(
+            "{}{} configured columns are valid",
+            prefix,
+            target.columns.len()
+        );
+    }
+
+    Ok(problems.is_empty())
+}
+
+/// Reports every problem of the configured columns of every database,
+/// returns whether there weren't any
+pub async fn check_config(config: &ApplicationConfig) -> Result<bool> {
+    let mut is_valid = true;
+
+    for target in config.targets() {
+        is_valid &= check_target(&target).await?;
+    }
+
+    Ok(is_valid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{NumericAggregationRef, StringAggregationRef};
+
+    #[test]
+    fn test_check_columns() {
+        let database_columns: HashMap<String, String> = vec![
+            ("contacts.first_name", "text"),
+            ("contacts.age", "integer"),
+            ("contacts.birthday", "date"),
+        ]
+        .into_iter()
+        .map(|(column, data_type)| (column.to_string(), data_type.to_string()))
+        .collect();
+
+        let quasi_identifier = |name: &str| ColumnConfiguration::PseudoIdentifier {
+            name: name.to_string(),
+            numeric_aggregation: NumericAggregationRef::default(),
+            string_aggregation: StringAggregationRef::default(),
+            users: vec![],
+            roles: vec![],
+        };
+        let identifier = |name: &str| ColumnConfiguration::Identifier {
+            name: name.to_string(),
+            users: vec![],
+            roles: vec![],
+        };
+
+        let columns = vec![
+            identifier("contacts.first_name"),
+            identifier("contacts.fist_name"),
+            identifier("last_name"),
+            quasi_identifier("contacts.age"),
+            quasi_identifier("contacts.birthday"),
+        ];
+
+        assert_eq!(
+            vec![
+                Problem::UnknownColumn("contacts.fist_name".to_string()),
+                Problem::InvalidColumnName("last_name".to_string()),
+                Problem::UnsupportedType {
+                    column: "contacts.birthday".to_string(),
+                    data_type: "date".to_string()
+                },
+            ],
+            check_columns(&columns, &database_columns)
+        );
+    }
+}
@@ -1,7 +1,14 @@
 use ::config::ConfigError;
-use proboscis_anonymization::{NumericAggregation, StringAggregation};
+use proboscis_anonymization::{
+    NumericAggregation, PseudonymDomain, SmallGroups, StringAggregation,
+};
+use proboscis_core::utils::address_filter::{AddressFilter, Cidr};
+use proboscis_core::{AuthenticationMethod, GssEncryption};
+use proboscis_resolver_cache::NegativeCaching;
+use proboscis_resolver_postgres::{PoolConfig, PoolMode};
+use proboscis_resolver_transformer::{DescribeMasking, UnparseableQueryPolicy};
 use serde::Deserialize;
-use std::path::Path;
+use std::{path::Path, time::Duration};
 
 const DEFAULT_STRING_AGG: StringAggregationRef = StringAggregationRef::Join;
 const DEFAULT_NUMERIC_AGG: NumericAggregationRef = NumericAggregationRef::Median;
@@ -10,6 +17,11 @@ const DEFAULT_NUMERIC_AGG: NumericAggregationRef = NumericAggregationRef::Median
 pub struct ListenerConfig {
     host: String,
     port: usize,
+    /// The top level `tls` applies to `listener` if it isn't set,
+    /// other listeners are plaintext without it
+    pub tls: Option<TlsConfig>,
+    /// Defaults to the top level `require_tls`
+    pub require_tls: Option<bool>,
 }
 
 impl Default for ListenerConfig {
@@ -17,6 +29,8 @@ impl Default for ListenerConfig {
         Self {
             host: String::from("localhost"),
             port: 5432,
+            tls: None,
+            require_tls: None,
         }
     }
 }
@@ -42,7 +56,7 @@ impl From<TlsConfig> for proboscis_core::TlsConfig {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum NumericAggregationRef {
     Range,
@@ -64,7 +78,7 @@ impl Default for NumericAggregationRef {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone, Copy)]
 #[serde(rename_all = "snake_case")]
 pub enum StringAggregationRef {
     Join,
@@ -86,12 +100,158 @@ impl Default for StringAggregationRef {
     }
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PoolModeRef {
+    Session,
+    Transaction,
+}
+
+impl From<PoolModeRef> for PoolMode {
+    fn from(def: PoolModeRef) -> PoolMode {
+        match def {
+            PoolModeRef::Session => PoolMode::Session,
+            PoolModeRef::Transaction => PoolMode::Transaction,
+        }
+    }
+}
+
+impl Default for PoolModeRef {
+    fn default() -> Self {
+        PoolModeRef::Session
+    }
+}
+
+/// The resolvers wrapped around the resolver of a target
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum ResolverLayerRef {
+    Anonymization,
+    Cache,
+}
+
+/// Whether groups below the minimum size are dropped or merged into an `other` group
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum SmallGroupsRef {
+    Drop,
+    Merge,
+}
+
+impl From<SmallGroupsRef> for SmallGroups {
+    fn from(def: SmallGroupsRef) -> SmallGroups {
+        match def {
+            SmallGroupsRef::Drop => SmallGroups::Drop,
+            SmallGroupsRef::Merge => SmallGroups::Merge,
+        }
+    }
+}
+
+impl Default for SmallGroupsRef {
+    fn default() -> Self {
+        SmallGroupsRef::Drop
+    }
+}
+
+/// How clients are authenticated
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthenticationMode {
+    /// With the configured credentials, upstream connections use the password of the target
+    Credentials,
+    /// By relaying the authentication to the target, every client gets its own
+    /// upstream connection, authenticated as the client
+    Passthrough,
+}
+
+impl Default for AuthenticationMode {
+    fn default() -> Self {
+        AuthenticationMode::Credentials
+    }
+}
+
+/// How requests of clients for GSSAPI encryption are answered
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum GssEncryptionRef {
+    Decline,
+    Reject,
+}
+
+impl From<GssEncryptionRef> for GssEncryption {
+    fn from(def: GssEncryptionRef) -> GssEncryption {
+        match def {
+            GssEncryptionRef::Decline => GssEncryption::Decline,
+            GssEncryptionRef::Reject => GssEncryption::Reject,
+        }
+    }
+}
+
+impl Default for GssEncryptionRef {
+    fn default() -> Self {
+        GssEncryptionRef::Decline
+    }
+}
+
+/// How clients authenticate with the configured credentials
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum PasswordEncryptionRef {
+    Md5,
+    #[serde(rename = "scram-sha-256")]
+    ScramSha256,
+}
+
+impl From<PasswordEncryptionRef> for AuthenticationMethod {
+    fn from(def: PasswordEncryptionRef) -> AuthenticationMethod {
+        match def {
+            PasswordEncryptionRef::Md5 => AuthenticationMethod::Md5,
+            PasswordEncryptionRef::ScramSha256 => AuthenticationMethod::ScramSha256,
+        }
+    }
+}
+
+impl Default for PasswordEncryptionRef {
+    fn default() -> Self {
+        PasswordEncryptionRef::Md5
+    }
+}
+
+#[derive(Debug, Deserialize, Clone, Copy)]
+#[serde(rename_all = "snake_case")]
+pub enum UnparseableQueriesRef {
+    PassthroughWithAudit,
+    Reject,
+}
+
+impl From<UnparseableQueriesRef> for UnparseableQueryPolicy {
+    fn from(def: UnparseableQueriesRef) -> UnparseableQueryPolicy {
+        match def {
+            UnparseableQueriesRef::PassthroughWithAudit => {
+                UnparseableQueryPolicy::PassthroughWithAudit
+            }
+            UnparseableQueriesRef::Reject => UnparseableQueryPolicy::Reject,
+        }
+    }
+}
+
+impl Default for UnparseableQueriesRef {
+    fn default() -> Self {
+        UnparseableQueriesRef::PassthroughWithAudit
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
 #[serde(tag = "type")]
 #[serde(rename_all = "snake_case")]
 pub enum ColumnConfiguration {
     Identifier {
         name: String,
+        /// Limits the rule to these users, in addition to `roles`
+        #[serde(default)]
+        users: Vec<String>,
+        #[serde(default)]
+        roles: Vec<String>,
     },
     PseudoIdentifier {
         name: String,
@@ -99,24 +259,738 @@ pub enum ColumnConfiguration {
         numeric_aggregation: NumericAggregationRef,
         #[serde(default)]
         string_aggregation: StringAggregationRef,
+        #[serde(default)]
+        users: Vec<String>,
+        #[serde(default)]
+        roles: Vec<String>,
+    },
+    /// Queries projecting or filtering on the column are rejected with `permission denied`
+    Denied {
+        name: String,
+        #[serde(default)]
+        users: Vec<String>,
+        #[serde(default)]
+        roles: Vec<String>,
     },
 }
 
+impl ColumnConfiguration {
+    fn scope(&self) -> (&[String], &[String]) {
+        match self {
+            ColumnConfiguration::Identifier { users, roles, .. }
+            | ColumnConfiguration::PseudoIdentifier { users, roles, .. }
+            | ColumnConfiguration::Denied { users, roles, .. } => (users, roles),
+        }
+    }
+
+    /// The roles the rule is limited to
+    pub fn roles(&self) -> &[String] {
+        self.scope().1
+    }
+
+    /// The qualified name of the column
+    pub fn name(&self) -> &str {
+        match self {
+            ColumnConfiguration::Identifier { name, .. }
+            | ColumnConfiguration::PseudoIdentifier { name, .. }
+            | ColumnConfiguration::Denied { name, .. } => name,
+        }
+    }
+
+    /// Whether the rule is limited to some users or roles
+    pub fn is_scoped(&self) -> bool {
+        let (users, roles) = self.scope();
+        !users.is_empty() || !roles.is_empty()
+    }
+
+    /// Unscoped rules apply to every user
+    pub fn applies_to(&self, credential: &Credential) -> bool {
+        let (users, roles) = self.scope();
+
+        !self.is_scoped()
+            || users.contains(&credential.username)
+            || credential.roles.iter().any(|role| roles.contains(role))
+    }
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Credential {
     pub username: String,
     pub password: String,
+    /// Roles for scoping column rules, they don't have to exist in the database. Columns denied
+    /// to a role are denied to clients switching to a database role of the same name as well.
+    #[serde(default)]
+    pub roles: Vec<String>,
+    /// Database roles clients may switch to with `SET ROLE`, any if not set
+    pub allowed_roles: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DeltaPresenceConfig {
+    pub min: f64,
+    pub max: f64,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct PopulationConfig {
+    pub path: String,
+    pub k_map: Option<usize>,
+    pub delta_presence: Option<DeltaPresenceConfig>,
+}
+
+/// Columns sharing pseudonyms derived from the key, e.g. `users.id` and `posts.author`,
+/// so that results pseudonymized separately can still be joined on them
+#[derive(Debug, Deserialize, Clone)]
+pub struct PseudonymDomainConfig {
+    pub name: String,
+    /// Secret the pseudonyms are derived with, changing it changes every pseudonym
+    pub key: String,
+    pub columns: Vec<String>,
+}
+
+impl From<PseudonymDomainConfig> for PseudonymDomain {
+    fn from(def: PseudonymDomainConfig) -> PseudonymDomain {
+        PseudonymDomain {
+            name: def.name,
+            key: def.key.into_bytes(),
+            columns: def.columns,
+        }
+    }
+}
+
+/// Columns masked by a WebAssembly module, which transforms them as Arrow IPC buffers,
+/// see the `WasmTransformation` of proboscis-anonymization for the functions it exports
+#[derive(Debug, Deserialize, Clone)]
+pub struct WasmMaskConfig {
+    /// Path of the module, either binary or in the text format
+    pub module: String,
+    pub columns: Vec<String>,
+    /// Roughly the number of instructions the module may execute per column of a result
+    pub fuel: Option<u64>,
+    /// Bytes of memory the module may use
+    pub memory_limit: Option<usize>,
+}
+
+/// The rhai script given inline, or read from the file of the path
+fn script_source(
+    script: &Option<String>,
+    script_path: &Option<String>,
+) -> Result<String, ConfigError> {
+    match (script, script_path) {
+        (Some(script), None) => Ok(script.clone()),
+        (None, Some(path)) => std::fs::read_to_string(path).map_err(|err| {
+            ConfigError::Message(format!("failed to read the script {}: {}", path, err))
+        }),
+        _ => Err(ConfigError::Message(
+            "either a script or a script_path has to be configured".to_string(),
+        )),
+    }
+}
+
+/// Columns whose values are replaced by the result of a rhai script, which reads
+/// the value from the `value` variable
+#[derive(Debug, Deserialize, Clone)]
+pub struct ScriptedColumnsConfig {
+    pub columns: Vec<String>,
+    pub script: Option<String>,
+    pub script_path: Option<String>,
+}
+
+impl ScriptedColumnsConfig {
+    pub fn script(&self) -> Result<String, ConfigError> {
+        script_source(&self.script, &self.script_path)
+    }
+}
+
+/// Clients only get the rows of results with columns of the tables for which a rhai
+/// script evaluates to true, it reads the values of the row from the `row` map
+#[derive(Debug, Deserialize, Clone)]
+pub struct RowFilterConfig {
+    pub tables: Vec<String>,
+    pub script: Option<String>,
+    pub script_path: Option<String>,
+}
+
+impl RowFilterConfig {
+    pub fn script(&self) -> Result<String, ConfigError> {
+        script_source(&self.script, &self.script_path)
+    }
+}
+
+/// Clients only get a sample of the rows of results with columns of the tables
+#[derive(Debug, Deserialize, Clone)]
+pub struct RowSamplingConfig {
+    pub tables: Vec<String>,
+    /// Share of the rows which are returned, e.g. 0.05
+    pub fraction: f64,
+    /// Every user gets the same sample as long as the seed doesn't change,
+    /// a new one is chosen on every start if not set
+    pub seed: Option<String>,
+    /// Limits the sampling to these users, in addition to `roles`
+    #[serde(default)]
+    pub users: Vec<String>,
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl RowSamplingConfig {
+    pub fn is_scoped(&self) -> bool {
+        !self.users.is_empty() || !self.roles.is_empty()
+    }
+
+    pub fn applies_to(&self, credential: &Credential) -> bool {
+        !self.is_scoped()
+            || self.users.contains(&credential.username)
+            || credential
+                .roles
+                .iter()
+                .any(|role| self.roles.contains(role))
+    }
+}
+
+/// Results of statements run by the users or roles are cut off after a number of rows,
+/// to limit how much can be exported through the proxy
+#[derive(Debug, Deserialize, Clone)]
+pub struct RowCapConfig {
+    pub max_rows: usize,
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Applies to the credentials with one of the roles, and to clients switching to one of
+    /// them with `SET ROLE`
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl RowCapConfig {
+    pub fn applies_to(&self, credential: &Credential) -> bool {
+        self.users.contains(&credential.username)
+            || credential
+                .roles
+                .iter()
+                .any(|role| self.roles.contains(role))
+    }
+}
+
+/// How columns changed by the anonymization are described to clients
+#[derive(Debug, Deserialize, Clone)]
+pub struct DescribeMaskingConfig {
+    /// Appended to the names of the columns, e.g. `_masked`
+    pub name_suffix: Option<String>,
+    /// Describes the columns with the type of their values instead of their original type
+    #[serde(default)]
+    pub declare_value_types: bool,
+}
+
+impl From<DescribeMaskingConfig> for DescribeMasking {
+    fn from(config: DescribeMaskingConfig) -> Self {
+        Self {
+            name_suffix: config.name_suffix,
+            declare_value_types: config.declare_value_types,
+        }
+    }
+}
+
+/// Aggregates of the tables are answered with noisy results, rows of them are rejected
+#[derive(Debug, Deserialize, Clone)]
+pub struct DifferentialPrivacyConfig {
+    pub tables: Vec<String>,
+    /// Spent by every query of the tables
+    pub epsilon: f64,
+    /// Groups with a smaller noisy count are suppressed, it has to be positive
+    pub threshold: f64,
+    /// The largest value a single row contributes to a sum, larger values are clamped to it.
+    /// Sums are rejected if not set
+    pub sum_sensitivity: Option<f64>,
+    /// Epsilon every user can spend in total, unlimited if not set
+    pub total_epsilon: Option<f64>,
+    /// File keeping the spent budgets across restarts
+    pub ledger_path: Option<String>,
+}
+
+/// Where the classifications of columns are read from the databases at startup,
+/// columns configured in `columns` keep their rule
+#[derive(Debug, Deserialize, Clone)]
+pub struct ColumnAnnotationsConfig {
+    /// Comments containing `pgcloak: <type>`, e.g. `pgcloak: identifier`
+    #[serde(default)]
+    pub comments: bool,
+    /// Provider of security labels naming the type of their column
+    pub security_label_provider: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct NegativeCachingConfig {
+    pub empty_result_ttl_seconds: Option<u64>,
+    /// SQLSTATE codes of errors to cache
+    #[serde(default)]
+    pub error_codes: Vec<String>,
+    #[serde(default)]
+    pub error_ttl_seconds: u64,
+}
+
+impl From<NegativeCachingConfig> for NegativeCaching {
+    fn from(config: NegativeCachingConfig) -> Self {
+        Self {
+            empty_result_ttl: config.empty_result_ttl_seconds.map(Duration::from_secs),
+            error_codes: config.error_codes,
+            error_ttl: Duration::from_secs(config.error_ttl_seconds),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CachedTableConfig {
+    pub name: String,
+    pub ttl_seconds: u64,
+    #[serde(flatten)]
+    pub negative: NegativeCachingConfig,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CachedQueryConfig {
+    pub query: String,
+    pub ttl_seconds: u64,
+    #[serde(flatten)]
+    pub negative: NegativeCachingConfig,
+}
+
+fn default_admin_database() -> String {
+    "pgcloak".to_string()
+}
+
+/// A database of the proxy itself, answering `SHOW SESSIONS` instead of forwarding queries
+#[derive(Debug, Deserialize, Clone)]
+pub struct AdminConfig {
+    #[serde(default = "default_admin_database")]
+    pub database: String,
+    /// Users allowed to connect to the admin database
+    pub users: Vec<String>,
+}
+
+/// Results of queries comparing an identifier or pseudo identifier column with a parameter
+/// of a prepared statement, e.g. `WHERE zip = $1`, which have fewer rows are suppressed
+#[derive(Debug, Deserialize, Clone)]
+pub struct ParameterizedFiltersConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// The fewest rows of such results, `k` if not set
+    pub min_rows: Option<usize>,
+}
+
+/// The tables, views and columns of each target, loaded from its system catalogs
+#[derive(Debug, Deserialize, Clone)]
+pub struct CatalogConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Channel an event trigger of the target notifies on DDL statements, which
+    /// invalidates the catalog. DDL statements of clients of the proxy always do.
+    pub ddl_channel: Option<String>,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct CacheConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Maximum memory used by cached results in bytes, unlimited if not set
+    pub memory_limit: Option<usize>,
+    /// Directory evicted results are written to, so they survive restarts
+    pub spill_directory: Option<String>,
+    /// Hex encoded 256 bit key the spill files are encrypted with
+    pub spill_encryption_key: Option<String>,
+    #[serde(default)]
+    pub tables: Vec<CachedTableConfig>,
+    #[serde(default)]
+    pub queries: Vec<CachedQueryConfig>,
+}
+
+impl CacheConfig {
+    pub fn spill_encryption_key(&self) -> Result<Option<[u8; 32]>, ConfigError> {
+        let hex = match &self.spill_encryption_key {
+            Some(hex) => hex,
+            None => return Ok(None),
+        };
+
+        let invalid = || {
+            ConfigError::Message(
+                "cache.spill_encryption_key has to consist of 64 hex characters".to_string(),
+            )
+        };
+
+        if hex.len() != 64 || !hex.is_ascii() {
+            return Err(invalid());
+        }
+
+        let mut key = [0; 32];
+        for (index, byte) in key.iter_mut().enumerate() {
+            *byte =
+                u8::from_str_radix(&hex[index * 2..index * 2 + 2], 16).map_err(|_| invalid())?;
+        }
+
+        Ok(Some(key))
+    }
+}
+
+/// Anonymization settings for results containing columns of the table,
+/// which have to hold in addition to the global `k`
+#[derive(Debug, Deserialize, Clone)]
+pub struct TableConfig {
+    pub name: String,
+    pub k: Option<usize>,
+    pub l: Option<usize>,
+    pub t: Option<f64>,
+    /// Column of the table, without the table name, which `l` and `t` apply to
+    pub sensitive_column: Option<String>,
+    /// Groups of aggregates over the table summarizing fewer rows are suppressed,
+    /// a lighter alternative to `k` for reporting queries
+    pub min_group_size: Option<usize>,
+    #[serde(default)]
+    pub small_groups: SmallGroupsRef,
+    /// Timestamp column which restricts the rows of the table to the `retention_window`
+    pub retention_column: Option<String>,
+    /// A postgres interval like `2 years`
+    pub retention_window: Option<String>,
+}
+
+const DEFAULT_SECRET_REFRESH: Duration = Duration::from_secs(300);
+
+fn default_vault_mount() -> String {
+    "secret".to_string()
+}
+
+fn default_vault_key() -> String {
+    "password".to_string()
+}
+
+/// A secret store holding the password of the user of a `connection_uri`,
+/// which is fetched again after `refresh_seconds` to pick up rotated passwords
+#[derive(Debug, Deserialize, Clone)]
+#[serde(tag = "type")]
+#[serde(rename_all = "snake_case")]
+pub enum SecretConfig {
+    Vault {
+        address: String,
+        /// Read from `VAULT_TOKEN` if not set
+        token: Option<String>,
+        #[serde(default = "default_vault_mount")]
+        mount: String,
+        path: String,
+        #[serde(default = "default_vault_key")]
+        key: String,
+        refresh_seconds: Option<u64>,
+    },
+    /// Authenticates with the keys of the `AWS_ACCESS_KEY_ID`, `AWS_SECRET_ACCESS_KEY`
+    /// and `AWS_SESSION_TOKEN` environment variables
+    AwsSecretsManager {
+        region: String,
+        secret_id: String,
+        /// Key of the password if the secret is a json object
+        key: Option<String>,
+        refresh_seconds: Option<u64>,
+    },
+}
+
+impl SecretConfig {
+    pub fn refresh_interval(&self) -> Duration {
+        let refresh_seconds = match self {
+            SecretConfig::Vault {
+                refresh_seconds, ..
+            }
+            | SecretConfig::AwsSecretsManager {
+                refresh_seconds, ..
+            } => refresh_seconds,
+        };
+
+        refresh_seconds.map_or(DEFAULT_SECRET_REFRESH, Duration::from_secs)
+    }
+}
+
+#[derive(Debug, Deserialize, Clone)]
+pub struct DatabaseConfig {
+    /// Name of the database clients connect to, which doesn't have to match the target
+    pub name: String,
+    pub connection_uri: String,
+    /// Replaces the password of the `connection_uri`
+    pub password_secret: Option<SecretConfig>,
+    /// Defaults to the top level `max_pool_size`
+    pub max_pool_size: Option<usize>,
+    /// The pool settings default to the top level ones as well
+    pub min_pool_size: Option<usize>,
+    pub pool_acquisition_timeout_seconds: Option<u64>,
+    pub pool_max_lifetime_seconds: Option<u64>,
+    pub pool_mode: Option<PoolModeRef>,
+    /// Defaults to the top level `columns`
+    pub columns: Option<Vec<ColumnConfiguration>>,
+}
+
+/// Settings of clients by the `application_name` of their startup message,
+/// which take precedence over the database they connect to
+#[derive(Debug, Deserialize, Clone)]
+pub struct ClientApplicationConfig {
+    /// The `application_name` clients set, e.g. `Metabase`
+    pub name: String,
+    /// Defaults to the top level `connection_uri`
+    pub connection_uri: Option<String>,
+    /// Replaces the password of the `connection_uri`
+    pub password_secret: Option<SecretConfig>,
+    /// Defaults to the top level `columns`
+    pub columns: Option<Vec<ColumnConfiguration>>,
+    /// Results are returned without anonymizing them, e.g. for a migration tool
+    #[serde(default)]
+    pub passthrough: bool,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct ApplicationConfig {
+    #[serde(default)]
+    pub authentication: AuthenticationMode,
+    #[serde(default)]
     pub credentials: Vec<Credential>,
+    /// Whether clients send an md5 hash of their password or take part in a SCRAM exchange,
+    /// like postgres' `password_encryption`
+    #[serde(default)]
+    pub password_encryption: PasswordEncryptionRef,
+    /// File of `username:verifier` lines, reloaded on SIGHUP
+    pub credentials_file: Option<String>,
+    #[serde(default)]
     pub columns: Vec<ColumnConfiguration>,
+    pub column_annotations: Option<ColumnAnnotationsConfig>,
     pub tls: Option<TlsConfig>,
+    /// Rejects clients which don't use tls, instead of serving them in plaintext
+    #[serde(default)]
+    pub require_tls: bool,
     pub listener: ListenerConfig,
+    /// Further addresses clients can connect to
+    #[serde(default)]
+    pub listeners: Vec<ListenerConfig>,
+    /// Ranges of client addresses in CIDR notation, every address is allowed if empty
+    #[serde(default)]
+    pub allowed_addresses: Vec<String>,
+    /// Ranges of client addresses which are denied, even if they are allowed
+    #[serde(default)]
+    pub denied_addresses: Vec<String>,
+    /// Whether clients requesting GSSAPI encryption fall back to tls or plaintext
+    #[serde(default)]
+    pub gss_encryption: GssEncryptionRef,
+    /// Time clients have to send their startup message, 60 seconds if not set
+    pub startup_timeout_seconds: Option<u64>,
+    /// Time clients have to authenticate, 60 seconds if not set
+    pub authentication_timeout_seconds: Option<u64>,
     pub max_pool_size: usize,
-    pub connection_uri: String,
+    /// Connections opened on startup
+    #[serde(default)]
+    pub min_pool_size: usize,
+    /// Maximum time to wait for a connection of the pool, unlimited if not set
+    pub pool_acquisition_timeout_seconds: Option<u64>,
+    /// Connections older than this are closed instead of being reused
+    pub pool_max_lifetime_seconds: Option<u64>,
+    /// Whether clients keep their connection until they disconnect or until their transaction ends
+    #[serde(default)]
+    pub pool_mode: PoolModeRef,
+    /// Target of clients connecting to a database that isn't listed in `databases`
+    pub connection_uri: Option<String>,
+    /// Replaces the password of the top level `connection_uri`
+    pub password_secret: Option<SecretConfig>,
+    #[serde(default)]
+    pub databases: Vec<DatabaseConfig>,
+    #[serde(default)]
+    pub applications: Vec<ClientApplicationConfig>,
     pub k: usize,
+    /// Results with a larger value in bytes are rejected, e.g. a huge bytea,
+    /// without holding the value in memory
+    pub max_value_size: Option<usize>,
+    #[serde(default)]
+    pub tables: Vec<TableConfig>,
+    pub population: Option<PopulationConfig>,
+    pub differential_privacy: Option<DifferentialPrivacyConfig>,
+    #[serde(default)]
+    pub pseudonym_domains: Vec<PseudonymDomainConfig>,
+    #[serde(default)]
+    pub wasm_masks: Vec<WasmMaskConfig>,
+    #[serde(default)]
+    pub scripted_columns: Vec<ScriptedColumnsConfig>,
+    #[serde(default)]
+    pub row_filters: Vec<RowFilterConfig>,
+    pub describe_masking: Option<DescribeMaskingConfig>,
+    /// Whether results of queries which can't be parsed are returned untransformed
+    #[serde(default)]
+    pub unparseable_queries: UnparseableQueriesRef,
+    #[serde(default)]
+    pub row_sampling: Vec<RowSamplingConfig>,
+    #[serde(default)]
+    pub row_caps: Vec<RowCapConfig>,
+    pub parameterized_filters: Option<ParameterizedFiltersConfig>,
+    /// Columns of a result transformed in parallel, one at a time if not set
+    pub column_concurrency: Option<usize>,
+    pub cache: Option<CacheConfig>,
+    /// Traces wildcards and views of queries with the catalog of the target
+    pub catalog: Option<CatalogConfig>,
+    /// The order of the resolvers around each target, outermost first. By default the
+    /// anonymized results are cached, so repeated queries skip the anonymization as well
+    pub resolver_layers: Option<Vec<ResolverLayerRef>>,
+    /// Address of the http server answering health probes and metrics scrapes
+    pub health: Option<ListenerConfig>,
+    pub admin: Option<AdminConfig>,
+    /// Address of the Arrow Flight server answering queries of the default target
+    pub flight: Option<ListenerConfig>,
+}
+
+/// A database behind the proxy, with the columns to anonymize in it
+pub struct Target {
+    /// The default target, used for every unlisted database, has no name
+    pub name: Option<String>,
+    pub connection_uri: String,
+    pub password_secret: Option<SecretConfig>,
+    pub pool_config: PoolConfig,
+    pub columns: Vec<ColumnConfiguration>,
+    /// Whether results are returned without anonymizing them
+    pub passthrough: bool,
+    pub max_value_size: Option<usize>,
+}
+
+fn parse_cidrs(key: &str, inputs: &[String]) -> Result<Vec<Cidr>, ConfigError> {
+    inputs
+        .iter()
+        .map(|input| {
+            input
+                .parse()
+                .map_err(|err| ConfigError::Message(format!("{}: {}", key, err)))
+        })
+        .collect()
+}
+
+impl ApplicationConfig {
+    pub fn resolver_layers(&self) -> Result<Vec<ResolverLayerRef>, ConfigError> {
+        let layers = match &self.resolver_layers {
+            Some(layers) => layers.clone(),
+            None => {
+                return Ok(vec![
+                    ResolverLayerRef::Cache,
+                    ResolverLayerRef::Anonymization,
+                ])
+            }
+        };
+
+        let count = |layer| layers.iter().filter(|other| **other == layer).count();
+
+        if count(ResolverLayerRef::Anonymization) != 1 {
+            return Err(ConfigError::Message(
+                "resolver_layers has to contain anonymization exactly once".to_string(),
+            ));
+        }
+
+        let cache_enabled = self.cache.as_ref().map_or(false, |cache| cache.enabled);
+        match count(ResolverLayerRef::Cache) {
+            0 if cache_enabled => Err(ConfigError::Message(
+                "the cache is enabled, but resolver_layers doesn't contain it".to_string(),
+            )),
+            0 | 1 => Ok(layers),
+            _ => Err(ConfigError::Message(
+                "resolver_layers can contain the cache only once".to_string(),
+            )),
+        }
+    }
+
+    pub fn address_filter(&self) -> Result<AddressFilter, ConfigError> {
+        Ok(AddressFilter {
+            allow: parse_cidrs("allowed_addresses", &self.allowed_addresses)?,
+            deny: parse_cidrs("denied_addresses", &self.denied_addresses)?,
+        })
+    }
+
+    fn pool_config(&self) -> PoolConfig {
+        PoolConfig {
+            max_size: self.max_pool_size,
+            min_size: self.min_pool_size,
+            acquisition_timeout: self
+                .pool_acquisition_timeout_seconds
+                .map(Duration::from_secs),
+            max_lifetime: self.pool_max_lifetime_seconds.map(Duration::from_secs),
+            mode: self.pool_mode.into(),
+        }
+    }
+
+    pub fn targets(&self) -> Vec<Target> {
+        let default_target = self.connection_uri.as_ref().map(|connection_uri| Target {
+            name: None,
+            connection_uri: connection_uri.clone(),
+            password_secret: self.password_secret.clone(),
+            pool_config: self.pool_config(),
+            columns: self.columns.clone(),
+            passthrough: false,
+            max_value_size: self.max_value_size,
+        });
+
+        default_target
+            .into_iter()
+            .chain(self.databases.iter().map(|database| {
+                Target {
+                    name: Some(database.name.clone()),
+                    connection_uri: database.connection_uri.clone(),
+                    password_secret: database.password_secret.clone(),
+                    pool_config: PoolConfig {
+                        max_size: database.max_pool_size.unwrap_or(self.max_pool_size),
+                        min_size: database.min_pool_size.unwrap_or(self.min_pool_size),
+                        acquisition_timeout: database
+                            .pool_acquisition_timeout_seconds
+                            .or(self.pool_acquisition_timeout_seconds)
+                            .map(Duration::from_secs),
+                        max_lifetime: database
+                            .pool_max_lifetime_seconds
+                            .or(self.pool_max_lifetime_seconds)
+                            .map(Duration::from_secs),
+                        mode: database.pool_mode.unwrap_or(self.pool_mode).into(),
+                    },
+                    columns: database
+                        .columns
+                        .clone()
+                        .unwrap_or_else(|| self.columns.clone()),
+                    passthrough: false,
+                    max_value_size: self.max_value_size,
+                }
+            }))
+            .collect()
+    }
+
+    /// The targets of the configured applications with their `application_name`,
+    /// each gets its own pool with the top level pool settings
+    pub fn application_targets(&self) -> Result<Vec<(String, Target)>, ConfigError> {
+        self.applications
+            .iter()
+            .map(|application| {
+                // The password secret belongs to the connection_uri it replaces the password of
+                let (connection_uri, password_secret) = match &application.connection_uri {
+                    Some(connection_uri) => {
+                        (connection_uri.clone(), application.password_secret.clone())
+                    }
+                    None => (
+                        self.connection_uri.clone().ok_or_else(|| {
+                            ConfigError::Message(format!(
+                                "application '{}' needs a connection_uri, as there is no top level one",
+                                application.name
+                            ))
+                        })?,
+                        self.password_secret.clone(),
+                    ),
+                };
+
+                Ok((
+                    application.name.clone(),
+                    Target {
+                        name: None,
+                        connection_uri,
+                        password_secret,
+                        pool_config: self.pool_config(),
+                        columns: application
+                            .columns
+                            .clone()
+                            .unwrap_or_else(|| self.columns.clone()),
+                        passthrough: application.passthrough,
+                        max_value_size: self.max_value_size,
+                    },
+                ))
+            })
+            .collect()
+    }
 }
 
 pub fn load_config(path: &Path) -> Result<ApplicationConfig, ConfigError> {
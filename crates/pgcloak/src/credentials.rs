@@ -0,0 +1,108 @@
+use crate::config::Credential;
+use anyhow::{anyhow, Result};
+use proboscis_core::{
+    utils::{password::is_md5_password_verifier, scram::is_scram_password_verifier},
+    SharedCredentials,
+};
+use std::{collections::HashMap, path::Path};
+
+/// Parses a credentials file with a `username:verifier` pair per line, like an htpasswd file.
+/// The verifiers are md5 or SCRAM verifiers as stored by postgres, e.g. `md5` followed by the hex
+/// digest of the password and username. Empty lines and lines starting with `#` are skipped.
+/// Users with a SCRAM verifier authenticate with SCRAM-SHA-256 regardless of `password_encryption`.
+pub fn parse_credentials_file(contents: &str) -> Result<HashMap<String, String>> {
+    let mut credentials = HashMap::new();
+
+    for (index, line) in contents.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (username, verifier) = line
+            .split_once(':')
+            .ok_or_else(|| anyhow!("line {} isn't of the form username:verifier", index + 1))?;
+
+        if !is_md5_password_verifier(verifier) && !is_scram_password_verifier(verifier) {
+            return Err(anyhow!(
+                "line {} doesn't contain an md5 or scram verifier",
+                index + 1
+            ));
+        }
+
+        credentials.insert(username.to_string(), verifier.to_string());
+    }
+
+    Ok(credentials)
+}
+
+/// The credentials of the config, with those of the credentials file taking precedence
+pub fn load_credentials(
+    credentials: &[Credential],
+    credentials_file: Option<&Path>,
+) -> Result<HashMap<String, String>> {
+    let mut loaded: HashMap<String, String> = credentials
+        .iter()
+        .map(|credential| (credential.username.clone(), credential.password.clone()))
+        .collect();
+
+    if let Some(credentials_file) = credentials_file {
+        let contents = std::fs::read_to_string(credentials_file)?;
+        loaded.extend(parse_credentials_file(&contents)?);
+    }
+
+    Ok(loaded)
+}
+
+/// Reloads the credentials whenever the process receives SIGHUP.
+/// Credentials that fail to load are logged and the previous ones are kept.
+#[cfg(unix)]
+pub async fn reload_on_hangup(
+    credentials: Vec<Credential>,
+    credentials_file: Option<std::path::PathBuf>,
+    shared_credentials: SharedCredentials,
+) -> Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut hangup = signal(SignalKind::hangup())?;
+
+    while hangup.recv().await.is_some() {
+        match load_credentials(&credentials, credentials_file.as_deref()) {
+            Ok(loaded) => {
+                tracing::info!(users = loaded.len(), "reloaded credentials");
+                *shared_credentials
+                    .write()
+                    .expect("Credentials lock poisoned") = loaded;
+            }
+            Err(err) => tracing::warn!(error = %err, "couldn't reload credentials"),
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_credentials_file() {
+        let credentials =
+            parse_credentials_file("# analysts\n\nanalyst:md532e12f215ba27cb750c9e093ce4b5127\n")
+                .unwrap();
+
+        assert_eq!(
+            Some(&"md532e12f215ba27cb750c9e093ce4b5127".to_string()),
+            credentials.get("analyst")
+        );
+        assert_eq!(1, credentials.len());
+
+        assert!(parse_credentials_file("analyst:password").is_err());
+        assert!(parse_credentials_file("analyst").is_err());
+        assert!(parse_credentials_file("analyst:SCRAM-SHA-256$4096:salt$key").is_err());
+
+        let verifier = "SCRAM-SHA-256$4096:W22ZaJ0SNY7soEsUEjb6gQ==$WG5d8oPm3OtcPnkdi4Uo7BkeZkBFzpcXkuLmtbsT4qY=:wfPLwcE6nTWhTAmQ7tl2KeoiWGPlZqQxSrmfPwDl2dU=";
+        let credentials = parse_credentials_file(&format!("analyst:{}", verifier)).unwrap();
+        assert_eq!(Some(&verifier.to_string()), credentials.get("analyst"));
+    }
+}
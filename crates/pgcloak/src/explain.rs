@@ -0,0 +1,47 @@
+use crate::target::query_target;
+use anyhow::{anyhow, Result};
+use proboscis_resolver_transformer::{
+    describe_origin, explain_query, ColumnExplanation, Transformer,
+};
+
+fn format_explanation(explanation: &ColumnExplanation) -> String {
+    format!(
+        "{} (from {}): {}",
+        explanation.name,
+        describe_origin(&explanation.origin),
+        explanation.describe_transformations()
+    )
+}
+
+/// Prints how the transformer would change each column of the query's result.
+/// The target only describes the result, no rows are read.
+pub async fn explain(
+    connection_uri: &str,
+    query: &str,
+    transformer: &dyn Transformer,
+) -> Result<()> {
+    let query = query.trim().trim_end_matches(';');
+
+    let data = query_target(
+        connection_uri,
+        &format!("SELECT * FROM ({}) AS explained LIMIT 0", query),
+    )
+    .await?;
+
+    let schema = data
+        .first()
+        .ok_or_else(|| anyhow!("the database didn't describe the result"))?
+        .schema();
+
+    match explain_query(query, &schema, &[transformer]) {
+        Ok(explanations) => {
+            for explanation in &explanations {
+                println!("{}", format_explanation(explanation));
+            }
+        }
+        // The proxy forwards results of queries it can't trace without any changes
+        Err(err) => println!("{}, the result would be returned unchanged", err),
+    }
+
+    Ok(())
+}
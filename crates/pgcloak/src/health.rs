@@ -0,0 +1,338 @@
+use anyhow::Result;
+use proboscis_core::{
+    metrics::{stage_latency, truncated_results, Stage},
+    sessions::sessions,
+    ProxyMetrics,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(1);
+
+// Requests of probes and scrapers are tiny, anything larger is cut off
+const MAX_REQUEST_SIZE: usize = 8192;
+
+/// A database behind the proxy, which has to be reachable for the proxy to be ready
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    pub host: String,
+    pub port: u16,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub status: &'static str,
+    pub content_type: &'static str,
+    pub body: String,
+}
+
+impl Response {
+    fn text(status: &'static str, body: String) -> Response {
+        Response {
+            status,
+            content_type: "text/plain; charset=utf-8",
+            body,
+        }
+    }
+}
+
+async fn is_reachable(upstream: &Upstream) -> bool {
+    matches!(
+        timeout(
+            CONNECT_TIMEOUT,
+            TcpStream::connect((upstream.host.as_str(), upstream.port))
+        )
+        .await,
+        Ok(Ok(_))
+    )
+}
+
+/// Renders the metrics in the prometheus text format
+pub fn render_metrics(metrics: &ProxyMetrics) -> String {
+    let counters = [
+        (
+            "pgcloak_connections_total",
+            "Connections accepted from clients",
+            metrics.connections(),
+        ),
+        (
+            "pgcloak_rejected_connections_total",
+            "Connections closed during their startup, e.g. as the requested database isn't configured",
+            metrics.rejected_connections(),
+        ),
+        (
+            "pgcloak_timed_out_connections_total",
+            "Connections closed as the client didn't finish its startup or authentication in time",
+            metrics.timed_out_connections(),
+        ),
+        (
+            "pgcloak_denied_connections_total",
+            "Connections closed as the address of the client isn't allowed",
+            metrics.denied_connections(),
+        ),
+        (
+            "pgcloak_truncated_results_total",
+            "Results cut off at the row cap of their client",
+            truncated_results(),
+        ),
+    ];
+
+    let mut rendered: String = counters
+        .iter()
+        .map(|(name, help, value)| {
+            format!(
+                "# HELP {name} {help}\n# TYPE {name} counter\n{name} {value}\n",
+                name = name,
+                help = help,
+                value = value
+            )
+        })
+        .collect();
+
+    rendered.push_str(&render_stage_latencies());
+    rendered.push_str(&render_sessions());
+
+    let query_stats = metrics.query_stats();
+    if query_stats.is_empty() {
+        return rendered;
+    }
+
+    let mut durations = String::from(
+        "# HELP pgcloak_query_duration_seconds Time spent resolving queries, by fingerprint\n\
+         # TYPE pgcloak_query_duration_seconds summary\n",
+    );
+    let mut rows = String::from(
+        "# HELP pgcloak_query_rows_total Rows returned by queries, by fingerprint\n\
+         # TYPE pgcloak_query_rows_total counter\n",
+    );
+
+    for (fingerprint, stats) in query_stats {
+        let labels = format!(
+            "fingerprint=\"{}\",query=\"{}\"",
+            fingerprint,
+            escape_label_value(&stats.normalized_query)
+        );
+
+        durations.push_str(&format!(
+            "pgcloak_query_duration_seconds_sum{{{labels}}} {sum}\n\
+             pgcloak_query_duration_seconds_count{{{labels}}} {count}\n",
+            labels = labels,
+            sum = stats.total_duration.as_secs_f64(),
+            count = stats.executions
+        ));
+        rows.push_str(&format!(
+            "pgcloak_query_rows_total{{{}}} {}\n",
+            labels, stats.rows
+        ));
+    }
+
+    rendered.push_str(&durations);
+    rendered.push_str(&rows);
+    rendered
+}
+
+/// Renders the latency histograms of the stages of answering queries
+fn render_stage_latencies() -> String {
+    let mut rendered = String::from(
+        "# HELP pgcloak_stage_duration_seconds Time spent in each stage of answering queries\n\
+         # TYPE pgcloak_stage_duration_seconds histogram\n",
+    );
+
+    for stage in Stage::ALL.iter() {
+        let latency = stage_latency(*stage);
+
+        for (bound, count) in latency.buckets {
+            rendered.push_str(&format!(
+                "pgcloak_stage_duration_seconds_bucket{{stage=\"{}\",le=\"{}\"}} {}\n",
+                stage.name(),
+                bound,
+                count
+            ));
+        }
+
+        rendered.push_str(&format!(
+            "pgcloak_stage_duration_seconds_bucket{{stage=\"{stage}\",le=\"+Inf\"}} {count}\n\
+             pgcloak_stage_duration_seconds_sum{{stage=\"{stage}\"}} {sum}\n\
+             pgcloak_stage_duration_seconds_count{{stage=\"{stage}\"}} {count}\n",
+            stage = stage.name(),
+            count = latency.count,
+            sum = latency.sum.as_secs_f64()
+        ));
+    }
+
+    rendered
+}
+
+/// Renders the connected clients as an info metric, labeled with the session and the backend
+/// serving it, so metrics and logs of the proxy and the target can be joined
+fn render_sessions() -> String {
+    let mut rendered = String::from(
+        "# HELP pgcloak_session_info Connected clients and the backend currently serving them\n\
+         # TYPE pgcloak_session_info gauge\n",
+    );
+
+    for session in sessions() {
+        rendered.push_str(&format!(
+            "pgcloak_session_info{{session=\"{}\",client_id=\"{}\",user=\"{}\",backend_pid=\"{}\"}} 1\n",
+            session.correlation_id,
+            session.client_id,
+            escape_label_value(session.user.as_deref().unwrap_or_default()),
+            session
+                .backend_pid
+                .map(|backend_pid| backend_pid.to_string())
+                .unwrap_or_default()
+        ));
+    }
+
+    rendered
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+pub async fn respond(path: &str, upstreams: &[Upstream], metrics: &ProxyMetrics) -> Response {
+    match path {
+        "/healthz" => Response::text("200 OK", "ok\n".to_string()),
+        "/readyz" => {
+            for upstream in upstreams {
+                if !is_reachable(upstream).await {
+                    return Response::text(
+                        "503 Service Unavailable",
+                        format!("{}:{} is unreachable\n", upstream.host, upstream.port),
+                    );
+                }
+            }
+
+            Response::text("200 OK", "ready\n".to_string())
+        }
+        "/metrics" => Response {
+            status: "200 OK",
+            content_type: "text/plain; version=0.0.4",
+            body: render_metrics(metrics),
+        },
+        _ => Response::text("404 Not Found", "not found\n".to_string()),
+    }
+}
+
+async fn handle_request(
+    mut stream: TcpStream,
+    upstreams: &[Upstream],
+    metrics: &ProxyMetrics,
+) -> Result<()> {
+    let mut request = vec![];
+    let mut buffer = [0; 1024];
+
+    while !request.windows(4).any(|window| window == b"\r\n\r\n")
+        && request.len() < MAX_REQUEST_SIZE
+    {
+        let read = stream.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        request.extend_from_slice(&buffer[..read]);
+    }
+
+    let request = String::from_utf8_lossy(&request);
+    let mut request_line = request
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .split_whitespace();
+
+    let response = match (request_line.next(), request_line.next()) {
+        (Some("GET"), Some(target)) => {
+            let path = target.split('?').next().unwrap_or_default();
+            respond(path, upstreams, metrics).await
+        }
+        _ => Response::text("405 Method Not Allowed", "method not allowed\n".to_string()),
+    };
+
+    stream
+        .write_all(
+            format!(
+                "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                response.status,
+                response.content_type,
+                response.body.len(),
+                response.body
+            )
+            .as_bytes(),
+        )
+        .await?;
+
+    Ok(())
+}
+
+/// Answers health and readiness probes and metrics scrapes over http
+pub async fn serve(
+    listener: TcpListener,
+    upstreams: Vec<Upstream>,
+    metrics: Arc<ProxyMetrics>,
+) -> Result<()> {
+    let upstreams = Arc::new(upstreams);
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let upstreams = upstreams.clone();
+        let metrics = metrics.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = handle_request(stream, &upstreams, &metrics).await {
+                tracing::debug!(error = %err, "failed to answer health request");
+            }
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::Ordering;
+
+    #[tokio::test]
+    async fn test_respond() {
+        let metrics = ProxyMetrics::default();
+        metrics.connections.fetch_add(3, Ordering::Relaxed);
+        metrics.record_query(
+            "select \"name\" from contacts where id = 1",
+            Duration::from_millis(500),
+            1,
+        );
+
+        assert_eq!("200 OK", respond("/healthz", &[], &metrics).await.status);
+        assert_eq!("200 OK", respond("/readyz", &[], &metrics).await.status);
+        assert_eq!(
+            "404 Not Found",
+            respond("/unknown", &[], &metrics).await.status
+        );
+
+        let metrics_response = respond("/metrics", &[], &metrics).await;
+        assert!(metrics_response
+            .body
+            .contains("\npgcloak_connections_total 3\n"));
+        assert!(metrics_response.body.contains(
+            "\npgcloak_stage_duration_seconds_bucket{stage=\"anonymization\",le=\"+Inf\"} "
+        ));
+        assert!(metrics_response
+            .body
+            .contains("query=\"select \\\"name\\\" from contacts where id = ?\"} 0.5\n"));
+
+        // Nothing listens on the port, connecting is refused right away
+        let unreachable = Upstream {
+            host: "127.0.0.1".to_string(),
+            port: 1,
+        };
+        assert_eq!(
+            "503 Service Unavailable",
+            respond("/readyz", &[unreachable], &metrics).await.status
+        );
+    }
+}
@@ -0,0 +1,164 @@
+use crate::check::{load_database_columns, SUPPORTED_QUASI_IDENTIFIER_TYPES};
+use anyhow::Result;
+use std::fmt::Write;
+
+// Words of column names that usually hold directly identifying values
+const IDENTIFIER_WORDS: &[&str] = &[
+    "name",
+    "firstname",
+    "lastname",
+    "surname",
+    "fullname",
+    "username",
+    "email",
+    "mail",
+    "ssn",
+    "phone",
+    "telephone",
+    "mobile",
+];
+
+// Words of column names that usually hold values identifying a person in combination
+const QUASI_IDENTIFIER_WORDS: &[&str] = &[
+    "age", "zip", "zipcode", "postal", "postcode", "gender", "sex", "city",
+];
+
+#[derive(Debug, PartialEq)]
+pub enum Suggestion {
+    Identifier,
+    QuasiIdentifier,
+    None,
+}
+
+/// Guesses from its name whether a column identifies a person, e.g. `email` or `last_name`
+pub fn suggest(column_name: &str, data_type: &str) -> Suggestion {
+    let column_name = column_name.to_lowercase();
+    let words: Vec<&str> = column_name
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .collect();
+
+    let matches_any = |candidates: &[&str]| words.iter().any(|word| candidates.contains(word));
+
+    if matches_any(IDENTIFIER_WORDS)
+        || column_name.contains("email")
+        || column_name.contains("phone")
+    {
+        Suggestion::Identifier
+    } else if matches_any(QUASI_IDENTIFIER_WORDS)
+        && SUPPORTED_QUASI_IDENTIFIER_TYPES.contains(&data_type)
+    {
+        Suggestion::QuasiIdentifier
+    } else {
+        Suggestion::None
+    }
+}
+
+/// Renders a starter config for the database, the columns are pairs of
+/// a qualified column name and the name of its type
+pub fn render_config(connection_uri: &str, columns: &[(String, String)]) -> String {
+    let mut config = String::new();
+
+    // Writing to a string can't fail
+    let _ = writeln!(
+        config,
+        "# Generated by `pgcloak init`, the suggested columns are guesses based on\n\
+         # their names, review them before using the proxy\n\
+         \n\
+         max_pool_size = 10\n\
+         k = 3\n\
+         connection_uri = \"{}\"\n\
+         \n\
+         [listener]\n\
+         host = \"0.0.0.0\"\n\
+         port = \"6432\"\n\
+         \n\
+         # Change these before exposing the proxy\n\
+         [[credentials]]\n\
+         username = \"admin\"\n\
+         password = \"password\"",
+        connection_uri
+    );
+
+    let mut unflagged = vec![];
+
+    for (name, data_type) in columns {
+        match suggest(name.split('.').last().unwrap_or(name), data_type) {
+            Suggestion::Identifier => {
+                let _ = writeln!(
+                    config,
+                    "\n[[columns]]\ntype = \"identifier\"\nname = \"{}\"",
+                    name
+                );
+            }
+            Suggestion::QuasiIdentifier => {
+                let _ = writeln!(
+                    config,
+                    "\n[[columns]]\ntype = \"pseudo_identifier\"\nname = \"{}\"",
+                    name
+                );
+            }
+            Suggestion::None => unflagged.push(format!("{} ({})", name, data_type)),
+        }
+    }
+
+    if !unflagged.is_empty() {
+        let _ = writeln!(
+            config,
+            "\n# Columns that weren't recognized, they are returned unchanged:"
+        );
+        for column in unflagged {
+            let _ = writeln!(config, "#   {}", column);
+        }
+    }
+
+    config
+}
+
+/// Prints a starter config for the tables of the database
+pub async fn init(connection_uri: &str) -> Result<()> {
+    let mut columns: Vec<(String, String)> = load_database_columns(connection_uri)
+        .await?
+        .into_iter()
+        .collect();
+    columns.sort();
+
+    print!("{}", render_config(connection_uri, &columns));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_suggest() {
+        assert_eq!(Suggestion::Identifier, suggest("last_name", "text"));
+        assert_eq!(Suggestion::Identifier, suggest("EmailAddress", "text"));
+        assert_eq!(Suggestion::Identifier, suggest("ssn", "character varying"));
+        assert_eq!(Suggestion::Identifier, suggest("mobile_phone", "text"));
+        assert_eq!(Suggestion::QuasiIdentifier, suggest("age", "integer"));
+        assert_eq!(Suggestion::None, suggest("birth_date", "date"));
+        assert_eq!(Suggestion::None, suggest("business_number", "text"));
+        assert_eq!(Suggestion::None, suggest("zip", "point"));
+    }
+
+    #[test]
+    fn test_render_config() {
+        let columns: Vec<(String, String)> = vec![
+            ("contacts.age", "integer"),
+            ("contacts.email", "text"),
+            ("contacts.id", "integer"),
+        ]
+        .into_iter()
+        .map(|(name, data_type)| (name.to_string(), data_type.to_string()))
+        .collect();
+
+        let config = render_config("postgresql://localhost/postgres", &columns);
+
+        assert!(config.contains("connection_uri = \"postgresql://localhost/postgres\""));
+        assert!(config.contains("type = \"pseudo_identifier\"\nname = \"contacts.age\""));
+        assert!(config.contains("type = \"identifier\"\nname = \"contacts.email\""));
+        assert!(config.contains("#   contacts.id (integer)"));
+    }
+}
@@ -1,17 +1,658 @@
-use crate::config::ColumnConfiguration;
-use anyhow::Result;
-use clap::{App, Arg};
+use crate::config::{
+    AuthenticationMode, CacheConfig, CatalogConfig, ColumnConfiguration, Credential,
+    DeltaPresenceConfig, DifferentialPrivacyConfig, PseudonymDomainConfig, ResolverLayerRef,
+    RowCapConfig, RowFilterConfig, RowSamplingConfig, ScriptedColumnsConfig, TableConfig, Target,
+    WasmMaskConfig,
+};
+use anyhow::{anyhow, Context, Result};
+use clap::{App, Arg, SubCommand};
 use proboscis_anonymization::{
-    AnonymizationCriteria, AnonymizationTransformer, NumericAggregation, StringAggregation,
+    AnonymizationCriteria, AnonymizationTransformer, ColumnConcurrency,
+    DifferentialPrivacyTransformer, GroupSizeTransformer, MaskedColumns, MaskingTransformer,
+    MinGroupSize, NumericAggregation, Population, PrivacyBudgetLedger, PseudonymizationTransformer,
+    RowSamplingTransformer, StringAggregation, UserBudget,
+};
+use proboscis_core::{
+    catalog::CatalogCache,
+    flight::FlightServer,
+    resolver::{ResolveError, Resolver, ResolverStack, SharedExtensions},
+    Listener, Proxy,
+};
+use proboscis_resolver_cache::{CacheRule, CachingResolver};
+use proboscis_resolver_postgres::{
+    listen_for_catalog_changes, PostgresCatalogSource, PostgresResolver, RefreshingCredentials,
+    TargetConfig,
+};
+use proboscis_resolver_transformer::{
+    DescribeMasking, RetentionRule, Transformer, TransformingResolver, UnparseableQueryPolicy,
+};
+use std::{
+    collections::{
+        hash_map::{DefaultHasher, RandomState},
+        HashMap,
+    },
+    hash::{BuildHasher, Hash, Hasher},
+    path::{Path, PathBuf},
+    str::FromStr,
+    sync::Arc,
+    time::Duration,
 };
-use proboscis_core::Proxy;
-use proboscis_resolver_postgres::{PostgresResolver, TargetConfig};
-use proboscis_resolver_transformer::TransformingResolver;
-use std::{collections::HashMap, path::Path, str::FromStr};
 use tokio::net::TcpListener;
 use tracing::{subscriber::set_global_default, Level};
 
+mod admin;
+mod annotations;
+mod anonymize;
+mod bench;
+mod check;
 mod config;
+mod credentials;
+mod explain;
+mod health;
+mod init;
+mod scan;
+mod secrets;
+mod target;
+mod views;
+
+fn caching_resolver(resolver: Box<dyn Resolver>, config: CacheConfig) -> Result<CachingResolver> {
+    let mut caching_resolver = CachingResolver::new(resolver);
+
+    if let Some(memory_limit) = config.memory_limit {
+        caching_resolver = caching_resolver.with_memory_limit(memory_limit);
+    }
+
+    // The key has to be known before the spill files are loaded
+    if let Some(key) = config.spill_encryption_key()? {
+        caching_resolver = caching_resolver.with_spill_encryption_key(key);
+    }
+
+    if let Some(spill_directory) = config.spill_directory {
+        caching_resolver = caching_resolver.with_spill_directory(PathBuf::from(spill_directory))?;
+    }
+
+    for table in config.tables {
+        caching_resolver = caching_resolver.add_rule(CacheRule::Table {
+            table: table.name,
+            ttl: Duration::from_secs(table.ttl_seconds),
+            negative: table.negative.into(),
+        });
+    }
+
+    for query in config.queries {
+        caching_resolver = caching_resolver.add_rule(CacheRule::Query {
+            query: query.query,
+            ttl: Duration::from_secs(query.ttl_seconds),
+            negative: query.negative.into(),
+        });
+    }
+
+    Ok(caching_resolver)
+}
+
+/// The target of the configured database, or the default target without a name
+fn find_target(targets: Vec<Target>, database: Option<&str>) -> Result<Target> {
+    targets
+        .into_iter()
+        .find(|target| target.name.as_deref() == database)
+        .ok_or_else(|| match database {
+            Some(database) => anyhow!("the database '{}' isn't configured", database),
+            None => anyhow!("no connection_uri is configured, use --database"),
+        })
+}
+
+/// The connection settings of a target, the error names the database of an invalid connection_uri
+fn target_config(target: &Target) -> Result<TargetConfig> {
+    TargetConfig::from_uri(&target.connection_uri).map_err(|err| match &target.name {
+        Some(database) => anyhow!(
+            "the connection_uri of the database '{}' is invalid: {}",
+            database,
+            err
+        ),
+        None => anyhow!("the connection_uri is invalid: {}", err),
+    })
+}
+
+type TableCriteria = HashMap<String, Vec<AnonymizationCriteria>>;
+
+fn table_criteria(tables: &[TableConfig]) -> Result<TableCriteria> {
+    let mut table_criteria: TableCriteria = HashMap::new();
+
+    for table in tables {
+        let criteria = table_criteria.entry(table.name.clone()).or_default();
+
+        if let Some(k) = table.k {
+            criteria.push(AnonymizationCriteria::KAnonymous { k });
+        }
+
+        if table.l.is_some() || table.t.is_some() {
+            let sensitive_column = table.sensitive_column.clone().ok_or_else(|| {
+                anyhow!(
+                    "table '{}' needs a sensitive_column for l and t",
+                    table.name
+                )
+            })?;
+
+            if let Some(l) = table.l {
+                criteria.push(AnonymizationCriteria::LDiverse {
+                    l,
+                    sensitive_column: sensitive_column.clone(),
+                });
+            }
+
+            if let Some(t) = table.t {
+                criteria.push(AnonymizationCriteria::TClose {
+                    t,
+                    sensitive_column,
+                });
+            }
+        }
+    }
+
+    Ok(table_criteria)
+}
+
+fn min_group_sizes(tables: &[TableConfig]) -> HashMap<String, MinGroupSize> {
+    tables
+        .iter()
+        .filter_map(|table| {
+            table.min_group_size.map(|size| {
+                (
+                    table.name.clone(),
+                    MinGroupSize {
+                        size,
+                        small_groups: table.small_groups.into(),
+                    },
+                )
+            })
+        })
+        .collect()
+}
+
+fn retention_rules(tables: &[TableConfig]) -> Result<Vec<RetentionRule>> {
+    tables
+        .iter()
+        .filter_map(
+            |table| match (&table.retention_column, &table.retention_window) {
+                (Some(column), Some(window)) => Some(Ok(RetentionRule {
+                    table: table.name.clone(),
+                    column: column.clone(),
+                    window: window.clone(),
+                })),
+                (None, None) => None,
+                _ => Some(Err(anyhow!(
+                    "table '{}' needs both a retention_column and a retention_window",
+                    table.name
+                ))),
+            },
+        )
+        .collect()
+}
+
+/// Compiles the modules of the masks once, every result is transformed by new instances
+#[cfg(feature = "wasm")]
+fn wasm_masks(masks: &[WasmMaskConfig]) -> Result<Vec<MaskedColumns>> {
+    use proboscis_anonymization::{WasmLimits, WasmTransformation};
+
+    masks
+        .iter()
+        .map(|mask| {
+            let defaults = WasmLimits::default();
+            let limits = WasmLimits {
+                fuel: mask.fuel.unwrap_or(defaults.fuel),
+                memory_bytes: mask.memory_limit.unwrap_or(defaults.memory_bytes),
+            };
+
+            let transformation = WasmTransformation::from_file(Path::new(&mask.module), limits)
+                .map_err(|err| anyhow!("failed to load the mask '{}': {}", mask.module, err))?;
+
+            Ok(MaskedColumns {
+                columns: mask.columns.clone(),
+                transformation: Arc::new(transformation),
+            })
+        })
+        .collect()
+}
+
+#[cfg(not(feature = "wasm"))]
+fn wasm_masks(masks: &[WasmMaskConfig]) -> Result<Vec<MaskedColumns>> {
+    match masks.is_empty() {
+        true => Ok(vec![]),
+        false => Err(anyhow!(
+            "wasm_masks are configured, but pgcloak was built without the wasm feature"
+        )),
+    }
+}
+
+#[cfg(feature = "scripting")]
+fn scripted_columns(configs: &[ScriptedColumnsConfig]) -> Result<Vec<MaskedColumns>> {
+    use proboscis_anonymization::ScriptTransformation;
+
+    configs
+        .iter()
+        .map(|config| {
+            Ok(MaskedColumns {
+                columns: config.columns.clone(),
+                transformation: Arc::new(ScriptTransformation::new(&config.script()?)?),
+            })
+        })
+        .collect()
+}
+
+#[cfg(feature = "scripting")]
+fn row_filter(config: &RowFilterConfig) -> Result<Box<dyn Transformer>> {
+    Ok(Box::new(proboscis_anonymization::ScriptedRowFilter::new(
+        config.tables.clone(),
+        &config.script()?,
+    )?))
+}
+
+#[cfg(not(feature = "scripting"))]
+fn scripted_columns(configs: &[ScriptedColumnsConfig]) -> Result<Vec<MaskedColumns>> {
+    match configs.is_empty() {
+        true => Ok(vec![]),
+        false => Err(anyhow!(
+            "scripted_columns are configured, but pgcloak was built without the scripting feature"
+        )),
+    }
+}
+
+#[cfg(not(feature = "scripting"))]
+fn row_filter(_config: &RowFilterConfig) -> Result<Box<dyn Transformer>> {
+    Err(anyhow!(
+        "row_filters are configured, but pgcloak was built without the scripting feature"
+    ))
+}
+
+fn anonymization_transformer(
+    columns: Vec<ColumnConfiguration>,
+    criteria: Vec<AnonymizationCriteria>,
+    table_criteria: TableCriteria,
+    concurrency: ColumnConcurrency,
+) -> AnonymizationTransformer {
+    let mut identifier_columns = vec![];
+    let mut quasi_identifier_columns: HashMap<String, (NumericAggregation, StringAggregation)> =
+        HashMap::new();
+
+    for column in columns {
+        match column {
+            ColumnConfiguration::Identifier { name, .. } => identifier_columns.push(name),
+            ColumnConfiguration::PseudoIdentifier {
+                name,
+                string_aggregation,
+                numeric_aggregation,
+                ..
+            } => {
+                quasi_identifier_columns.insert(
+                    name,
+                    (numeric_aggregation.into(), string_aggregation.into()),
+                );
+            }
+            // Results never contain denied columns
+            ColumnConfiguration::Denied { .. } => {}
+        }
+    }
+
+    AnonymizationTransformer {
+        identifier_columns,
+        quasi_identifier_columns,
+        criteria,
+        table_criteria,
+        concurrency,
+    }
+}
+
+/// The differential privacy settings, with the ledger of the budgets spent on every target
+type DifferentialPrivacy = (DifferentialPrivacyConfig, Option<Arc<PrivacyBudgetLedger>>);
+
+fn differential_privacy_transformer(
+    (config, ledger): &DifferentialPrivacy,
+    user: Option<&str>,
+) -> DifferentialPrivacyTransformer {
+    DifferentialPrivacyTransformer {
+        protected_tables: config.tables.clone(),
+        epsilon: config.epsilon,
+        threshold: config.threshold,
+        sum_sensitivity: config.sum_sensitivity,
+        budget: ledger.clone().zip(user).map(|(ledger, user)| UserBudget {
+            user: user.to_string(),
+            ledger,
+        }),
+    }
+}
+
+/// The seed of a sampling rule, which is random without a configured seed
+fn sampling_seed(config: &RowSamplingConfig) -> u64 {
+    match &config.seed {
+        Some(seed) => {
+            let mut hasher = DefaultHasher::new();
+            seed.hash(&mut hasher);
+            hasher.finish()
+        }
+        None => RandomState::new().build_hasher().finish(),
+    }
+}
+
+/// Every user gets a sample of its own, which stays the same across its sessions
+fn user_sampling_seed(seed: u64, user: &str) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    seed.hash(&mut hasher);
+    user.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// The rules the results of every target are transformed with
+struct Policies {
+    criteria: Vec<AnonymizationCriteria>,
+    table_criteria: TableCriteria,
+    min_group_sizes: HashMap<String, MinGroupSize>,
+    differential_privacy: Option<DifferentialPrivacy>,
+    pseudonym_domains: Vec<PseudonymDomainConfig>,
+    masks: Vec<MaskedColumns>,
+    row_filters: Vec<RowFilterConfig>,
+    retention_rules: Vec<RetentionRule>,
+    describe_masking: Option<DescribeMasking>,
+    unparseable_queries: UnparseableQueryPolicy,
+    /// The sampling rules with their seeds
+    row_sampling: Vec<(RowSamplingConfig, u64)>,
+    row_caps: Vec<RowCapConfig>,
+    /// The fewest rows of results filtering anonymized columns by parameters
+    parameterized_min_rows: Option<usize>,
+    column_concurrency: ColumnConcurrency,
+    resolver_layers: Vec<ResolverLayerRef>,
+    catalog: Option<CatalogConfig>,
+}
+
+// Queries starting with the comment return how their columns would be anonymized
+const EXPLAIN_PREFIX: &str = "/*pgcloak:explain*/";
+
+/// Wraps the resolver of a target in the anonymization of its results
+fn transforming_resolver(
+    resolver: Box<dyn Resolver>,
+    columns: Vec<ColumnConfiguration>,
+    policies: &Policies,
+    credentials: &[Credential],
+    catalog: Option<Arc<CatalogCache>>,
+) -> Result<TransformingResolver> {
+    let mut transforming_resolver = TransformingResolver::new(resolver)
+        .with_explain_prefix(EXPLAIN_PREFIX)
+        .with_unparseable_query_policy(policies.unparseable_queries);
+
+    if let Some(catalog) = catalog {
+        transforming_resolver = transforming_resolver.with_catalog(catalog);
+    }
+
+    if let Some(describe_masking) = &policies.describe_masking {
+        transforming_resolver =
+            transforming_resolver.with_describe_masking(describe_masking.clone());
+    }
+
+    for column in &columns {
+        let (table, name) = match column {
+            ColumnConfiguration::Denied { name, .. } => name
+                .rsplit_once('.')
+                .ok_or_else(|| anyhow!("the denied column '{}' has no table", name))?,
+            _ => continue,
+        };
+
+        if column.is_scoped() {
+            for credential in credentials.iter().filter(|c| column.applies_to(c)) {
+                transforming_resolver =
+                    transforming_resolver.deny_user_column(&credential.username, table, name);
+            }
+
+            for role in column.roles() {
+                transforming_resolver = transforming_resolver.deny_role_column(role, table, name);
+            }
+        } else {
+            transforming_resolver = transforming_resolver.deny_column(table, name);
+        }
+    }
+
+    for credential in credentials {
+        if let Some(allowed_roles) = &credential.allowed_roles {
+            let allowed_roles: Vec<&str> = allowed_roles.iter().map(String::as_str).collect();
+            transforming_resolver =
+                transforming_resolver.restrict_roles(&credential.username, &allowed_roles);
+        }
+    }
+
+    for cap in &policies.row_caps {
+        for credential in credentials.iter().filter(|c| cap.applies_to(c)) {
+            transforming_resolver =
+                transforming_resolver.limit_user_rows(&credential.username, cap.max_rows);
+        }
+
+        for role in &cap.roles {
+            transforming_resolver = transforming_resolver.limit_role_rows(role, cap.max_rows);
+        }
+    }
+
+    // Scoped rules don't anonymize the column for everyone, only the unscoped ones require
+    // a minimum size of the population a parameter selects
+    if let Some(min_rows) = policies.parameterized_min_rows {
+        for column in columns.iter().filter(|column| !column.is_scoped()) {
+            let name = match column {
+                ColumnConfiguration::Identifier { name, .. }
+                | ColumnConfiguration::PseudoIdentifier { name, .. } => name,
+                ColumnConfiguration::Denied { .. } => continue,
+            };
+
+            if let Some((table, name)) = name.rsplit_once('.') {
+                transforming_resolver =
+                    transforming_resolver.require_parameterized_rows(table, name, min_rows);
+            }
+        }
+    }
+
+    for rule in &policies.retention_rules {
+        transforming_resolver = transforming_resolver.add_retention_rule(rule.clone());
+    }
+
+    // Rows are filtered by their original values, before any of them are transformed
+    for filter in &policies.row_filters {
+        transforming_resolver = transforming_resolver.add_transformer(row_filter(filter)?);
+    }
+
+    // Small groups are suppressed before the remaining rows are anonymized
+    if !policies.min_group_sizes.is_empty() {
+        transforming_resolver =
+            transforming_resolver.add_transformer(Box::new(GroupSizeTransformer {
+                tables: policies.min_group_sizes.clone(),
+            }));
+    }
+
+    if !policies.pseudonym_domains.is_empty() {
+        transforming_resolver =
+            transforming_resolver.add_transformer(Box::new(PseudonymizationTransformer {
+                domains: policies
+                    .pseudonym_domains
+                    .iter()
+                    .cloned()
+                    .map(|domain| domain.into())
+                    .collect(),
+                concurrency: policies.column_concurrency.clone(),
+            }));
+    }
+
+    if !policies.masks.is_empty() {
+        transforming_resolver =
+            transforming_resolver.add_transformer(Box::new(MaskingTransformer {
+                masks: policies.masks.clone(),
+                concurrency: policies.column_concurrency.clone(),
+            }));
+    }
+
+    for (sampling, seed) in &policies.row_sampling {
+        let transformer = |seed| RowSamplingTransformer {
+            tables: sampling.tables.clone(),
+            fraction: sampling.fraction,
+            seed,
+        };
+
+        if sampling.is_scoped() {
+            for credential in credentials.iter().filter(|c| sampling.applies_to(c)) {
+                transforming_resolver = transforming_resolver.add_user_transformer(
+                    &credential.username,
+                    Box::new(transformer(user_sampling_seed(*seed, &credential.username))),
+                );
+            }
+        } else {
+            transforming_resolver =
+                transforming_resolver.add_transformer(Box::new(transformer(*seed)));
+        }
+    }
+
+    if columns.iter().any(ColumnConfiguration::is_scoped) {
+        // Every user gets a transformer with the rules that apply to it
+        for credential in credentials {
+            let columns = columns
+                .iter()
+                .filter(|column| column.applies_to(credential))
+                .cloned()
+                .collect();
+
+            transforming_resolver = transforming_resolver.add_user_transformer(
+                &credential.username,
+                Box::new(anonymization_transformer(
+                    columns,
+                    policies.criteria.clone(),
+                    policies.table_criteria.clone(),
+                    policies.column_concurrency.clone(),
+                )),
+            );
+        }
+    } else {
+        transforming_resolver =
+            transforming_resolver.add_transformer(Box::new(anonymization_transformer(
+                columns,
+                policies.criteria.clone(),
+                policies.table_criteria.clone(),
+                policies.column_concurrency.clone(),
+            )));
+    }
+
+    if let Some(differential_privacy) = &policies.differential_privacy {
+        match differential_privacy.1 {
+            // Every user spends its own budget
+            Some(_) => {
+                for credential in credentials {
+                    transforming_resolver = transforming_resolver.add_user_transformer(
+                        &credential.username,
+                        Box::new(differential_privacy_transformer(
+                            differential_privacy,
+                            Some(&credential.username),
+                        )),
+                    );
+                }
+            }
+            None => {
+                transforming_resolver = transforming_resolver.add_transformer(Box::new(
+                    differential_privacy_transformer(differential_privacy, None),
+                ));
+            }
+        }
+    }
+
+    Ok(transforming_resolver)
+}
+
+/// Builds the resolvers for a single target, every target gets its own pool and cache
+async fn target_resolver(
+    target: Target,
+    policies: &Policies,
+    credentials: &[Credential],
+    cache_config: Option<CacheConfig>,
+) -> Result<Box<dyn Resolver>> {
+    let mut target_config = target_config(&target)?;
+
+    if let Some(password_secret) = target.password_secret {
+        let refresh_interval = password_secret.refresh_interval();
+        target_config =
+            target_config.with_credentials_provider(Arc::new(RefreshingCredentials::new(
+                crate::secrets::credentials_provider(password_secret)?,
+                refresh_interval,
+            )));
+    }
+
+    // Every anonymized target has its own catalog, shared by its clients
+    let catalog_config = policies
+        .catalog
+        .as_ref()
+        .filter(|catalog| catalog.enabled && !target.passthrough);
+    let catalog = match catalog_config {
+        Some(catalog_config) => {
+            let catalog = Arc::new(CatalogCache::new(Box::new(PostgresCatalogSource::new(
+                target_config.clone(),
+            ))));
+
+            if let Some(channel) = &catalog_config.ddl_channel {
+                listen_for_catalog_changes(target_config.clone(), channel.clone(), catalog.clone());
+            }
+
+            Some(catalog)
+        }
+        None => None,
+    };
+
+    let database = target.name.clone();
+    let mut postgres_resolver =
+        PostgresResolver::create_with_pool_config(target_config, target.pool_config)
+            .await
+            .with_context(|| match &database {
+                Some(database) => format!(
+                    "couldn't create the connection pool of the database '{}'",
+                    database
+                ),
+                None => "couldn't create the connection pool".to_string(),
+            })?
+            .with_application_name_prefix("pgcloak");
+
+    if let Some(max_value_size) = target.max_value_size {
+        postgres_resolver = postgres_resolver.with_max_value_size(max_value_size);
+    }
+
+    if target.passthrough {
+        return Ok(Box::new(postgres_resolver));
+    }
+
+    let mut cache_config = cache_config.filter(|cache_config| cache_config.enabled);
+    let mut columns = Some(target.columns);
+
+    let mut stack = ResolverStack::new();
+    for layer in &policies.resolver_layers {
+        stack = match layer {
+            ResolverLayerRef::Anonymization => match (columns.take(), catalog.clone()) {
+                (Some(columns), catalog) => stack.layer(
+                    move |inner: Box<dyn Resolver>,
+                          _: &SharedExtensions|
+                          -> Result<Box<dyn Resolver>, ResolveError> {
+                        Ok(Box::new(transforming_resolver(
+                            inner,
+                            columns,
+                            policies,
+                            credentials,
+                            catalog,
+                        )?))
+                    },
+                ),
+                (None, _) => stack,
+            },
+            ResolverLayerRef::Cache => match cache_config.take() {
+                Some(cache_config) => stack.layer(
+                    move |inner: Box<dyn Resolver>,
+                          _: &SharedExtensions|
+                          -> Result<Box<dyn Resolver>, ResolveError> {
+                        Ok(Box::new(caching_resolver(inner, cache_config)?))
+                    },
+                ),
+                None => stack,
+            },
+        };
+    }
+
+    Ok(stack.build(Box::new(postgres_resolver))?)
+}
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -31,6 +672,154 @@ async fn main() -> Result<()> {
                 .help("Sets the level of verbosity"),
         )
         .arg(Arg::with_name("database").help("Connection uri for the database"))
+        .subcommand(
+            SubCommand::with_name("check")
+                .about("Checks that the configured columns exist in the database"),
+        )
+        .subcommand(
+            SubCommand::with_name("init")
+                .about("Prints a starter config, suggesting columns to anonymize from the schema")
+                .arg(
+                    Arg::with_name("connection_uri")
+                        .required(true)
+                        .help("Connection uri for the database"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("scan")
+                .about("Samples the tables of a database and suggests columns to anonymize")
+                .arg(
+                    Arg::with_name("connection_uri")
+                        .required(true)
+                        .help("Connection uri for the database"),
+                )
+                .arg(
+                    Arg::with_name("sample_size")
+                        .long("sample-size")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .help("Number of rows to sample from every table"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("bench")
+                .about("Measures the latency of queries against the target and the proxy")
+                .arg(
+                    Arg::with_name("endpoint")
+                        .long("endpoint")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .required(true)
+                        .help(
+                            "A database to measure like 'proxy=postgresql://...', \
+                             the overhead is relative to the first one",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("query")
+                        .long("query")
+                        .takes_value(true)
+                        .multiple(true)
+                        .number_of_values(1)
+                        .help("A query of the workload"),
+                )
+                .arg(
+                    Arg::with_name("workload")
+                        .long("workload")
+                        .takes_value(true)
+                        .help("A file with further queries of the workload, one per line"),
+                )
+                .arg(
+                    Arg::with_name("iterations")
+                        .long("iterations")
+                        .takes_value(true)
+                        .default_value("1000")
+                        .help("Number of queries to run against every endpoint"),
+                )
+                .arg(
+                    Arg::with_name("concurrency")
+                        .long("concurrency")
+                        .takes_value(true)
+                        .default_value("1")
+                        .help("Number of connections running queries at the same time"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("explain")
+                .about("Shows how the columns of a query's result would be anonymized")
+                .arg(
+                    Arg::with_name("query")
+                        .required(true)
+                        .help("The query to explain"),
+                )
+                .arg(
+                    Arg::with_name("database")
+                        .long("database")
+                        .takes_value(true)
+                        .help("Name of the configured database to run the query against"),
+                )
+                .arg(
+                    Arg::with_name("user")
+                        .long("user")
+                        .takes_value(true)
+                        .help("Only applies the column rules of this user"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("generate-views")
+                .about("Prints views masking the columns of the tables like the proxy does")
+                .arg(
+                    Arg::with_name("schema")
+                        .long("schema")
+                        .takes_value(true)
+                        .default_value("masked")
+                        .help("The schema to create the views in"),
+                )
+                .arg(
+                    Arg::with_name("database")
+                        .long("database")
+                        .takes_value(true)
+                        .help("Name of the configured database to create the views for"),
+                )
+                .arg(
+                    Arg::with_name("user").long("user").takes_value(true).help(
+                        "Applies the column rules of this user, instead of the unscoped ones",
+                    ),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("anonymize")
+                .about("Anonymizes a csv file or a whole table with the configured rules")
+                .arg(
+                    Arg::with_name("table")
+                        .long("table")
+                        .takes_value(true)
+                        .required(true)
+                        .help(
+                            "The table to export, or the table the columns of the input belong to",
+                        ),
+                )
+                .arg(
+                    Arg::with_name("input")
+                        .long("input")
+                        .takes_value(true)
+                        .help("A csv file with a header row, instead of the table's data"),
+                )
+                .arg(
+                    Arg::with_name("output")
+                        .long("output")
+                        .takes_value(true)
+                        .required(true)
+                        .help("The .csv or .arrow file to write"),
+                )
+                .arg(
+                    Arg::with_name("database")
+                        .long("database")
+                        .takes_value(true)
+                        .help("Name of the configured database to export the table from"),
+                ),
+        )
         .get_matches();
 
     let tracing_level = Level::from_str(
@@ -45,6 +834,62 @@ async fn main() -> Result<()> {
 
     set_global_default(collector)?;
 
+    // There is no config to load yet when creating one
+    if let Some(init_matches) = matches.subcommand_matches("init") {
+        let connection_uri = init_matches
+            .value_of("connection_uri")
+            .expect("Missing value for 'connection_uri' argument");
+        crate::init::init(connection_uri).await?;
+        return Ok(());
+    }
+
+    if let Some(scan_matches) = matches.subcommand_matches("scan") {
+        let connection_uri = scan_matches
+            .value_of("connection_uri")
+            .expect("Missing value for 'connection_uri' argument");
+        let sample_size = scan_matches
+            .value_of("sample_size")
+            .expect("Missing value for 'sample_size' argument")
+            .parse()?;
+        crate::scan::scan(connection_uri, sample_size).await?;
+        return Ok(());
+    }
+
+    if let Some(bench_matches) = matches.subcommand_matches("bench") {
+        let endpoints = bench_matches
+            .values_of("endpoint")
+            .expect("Missing value for 'endpoint' argument")
+            .map(str::parse)
+            .collect::<Result<Vec<crate::bench::Endpoint>>>()?;
+
+        let mut queries: Vec<String> = bench_matches
+            .values_of("query")
+            .into_iter()
+            .flatten()
+            .map(str::to_string)
+            .collect();
+        if let Some(workload) = bench_matches.value_of("workload") {
+            queries.extend(crate::bench::Workload::parse_queries(
+                &std::fs::read_to_string(workload)?,
+            ));
+        }
+
+        let workload = crate::bench::Workload {
+            queries,
+            iterations: bench_matches
+                .value_of("iterations")
+                .expect("Missing value for 'iterations' argument")
+                .parse()?,
+            concurrency: bench_matches
+                .value_of("concurrency")
+                .expect("Missing value for 'concurrency' argument")
+                .parse()?,
+        };
+
+        crate::bench::bench(&endpoints, &workload).await?;
+        return Ok(());
+    }
+
     let config_file_path = Path::new(
         matches
             .value_of("config")
@@ -54,60 +899,389 @@ async fn main() -> Result<()> {
     let config_file_path = std::env::current_dir()?.join(config_file_path);
     let config = crate::config::load_config(&config_file_path)?;
 
-    let mut identifier_columns = vec![];
-    let mut quasi_identifier_columns: HashMap<String, (NumericAggregation, StringAggregation)> =
-        HashMap::new();
+    if matches.subcommand_matches("check").is_some() {
+        let is_valid = crate::check::check_config(&config).await?;
+        std::process::exit(if is_valid { 0 } else { 1 });
+    }
 
-    for column in config.columns {
-        match column {
-            ColumnConfiguration::Identifier { name } => identifier_columns.push(name),
-            ColumnConfiguration::PseudoIdentifier {
-                name,
-                string_aggregation,
-                numeric_aggregation,
-            } => {
-                quasi_identifier_columns.insert(
-                    name,
-                    (numeric_aggregation.into(), string_aggregation.into()),
-                );
+    let mut targets = config.targets();
+    let mut application_targets = config.application_targets()?;
+    let address_filter = config.address_filter()?;
+
+    if let Some(annotations_config) = &config.column_annotations {
+        for target in targets
+            .iter_mut()
+            .chain(application_targets.iter_mut().map(|(_, target)| target))
+        {
+            let annotated = crate::annotations::load_annotated_columns(
+                &target.connection_uri,
+                annotations_config,
+            )
+            .await?;
+            crate::annotations::merge_columns(&mut target.columns, annotated);
+        }
+    }
+
+    if let Some(views_matches) = matches.subcommand_matches("generate-views") {
+        let schema = views_matches
+            .value_of("schema")
+            .expect("Missing value for 'schema' argument");
+        let target = find_target(targets, views_matches.value_of("database"))?;
+
+        let columns: Vec<ColumnConfiguration> = match views_matches.value_of("user") {
+            Some(user) => {
+                let credential = config
+                    .credentials
+                    .iter()
+                    .find(|credential| credential.username == user)
+                    .ok_or_else(|| anyhow!("the user '{}' isn't configured", user))?;
+
+                target
+                    .columns
+                    .into_iter()
+                    .filter(|column| column.applies_to(credential))
+                    .collect()
             }
+            None => target
+                .columns
+                .into_iter()
+                .filter(|column| !column.is_scoped())
+                .collect(),
+        };
+
+        crate::views::print_views(&target.connection_uri, &columns, schema).await?;
+        return Ok(());
+    }
+
+    // Clients bring their own upstream connections, the pools may lack the credentials to prewarm
+    if config.authentication == AuthenticationMode::Passthrough {
+        for target in targets
+            .iter_mut()
+            .chain(application_targets.iter_mut().map(|(_, target)| target))
+        {
+            target.pool_config.min_size = 0;
         }
     }
 
-    let credentials = config
-        .credentials
-        .iter()
-        .cloned()
-        .map(|credential| (credential.username, credential.password))
-        .collect();
+    let credentials_file = config.credentials_file.as_ref().map(PathBuf::from);
+    let credentials =
+        crate::credentials::load_credentials(&config.credentials, credentials_file.as_deref())?;
 
     let tls_config: Option<proboscis_core::TlsConfig> = config.tls.map(|config| config.into());
 
-    let mut proxy = Proxy::new(
-        proboscis_core::Config {
-            credentials,
-            tls_config,
-        },
-        Box::new(
-            TransformingResolver::new(Box::new(
-                PostgresResolver::create(
-                    TargetConfig::from_uri(&config.connection_uri).unwrap(),
-                    config.max_pool_size,
-                )
-                .await
-                .unwrap(),
-            ))
-            .add_transformer(Box::new(AnonymizationTransformer {
-                identifier_columns,
-                quasi_identifier_columns,
-                criteria: AnonymizationCriteria::KAnonymous { k: config.k },
-            })),
-        ),
-    );
+    let mut criteria = vec![AnonymizationCriteria::KAnonymous { k: config.k }];
+
+    if let Some(population_config) = config.population {
+        let population = Arc::new(Population::from_csv(Path::new(&population_config.path))?);
+
+        if let Some(k) = population_config.k_map {
+            criteria.push(AnonymizationCriteria::KMap {
+                k,
+                population: population.clone(),
+            });
+        }
+
+        if let Some(DeltaPresenceConfig { min, max }) = population_config.delta_presence {
+            criteria.push(AnonymizationCriteria::DeltaPresent {
+                min,
+                max,
+                population,
+            });
+        }
+    }
+
+    let table_criteria = table_criteria(&config.tables)?;
+    let min_group_sizes = min_group_sizes(&config.tables);
+    let retention_rules = retention_rules(&config.tables)?;
+    let column_concurrency = ColumnConcurrency::new(config.column_concurrency.unwrap_or(1))?;
+
+    let differential_privacy = config
+        .differential_privacy
+        .clone()
+        .map(|differential_privacy| -> Result<DifferentialPrivacy> {
+            // Group keys are released as they are, only the threshold keeps rare ones back
+            if !differential_privacy.tables.is_empty() && differential_privacy.threshold <= 0.0 {
+                return Err(anyhow!(
+                    "differential_privacy requires a threshold above 0 for its tables"
+                ));
+            }
+
+            let ledger = match differential_privacy.total_epsilon {
+                // Budgets are spent by users, which aren't known with passthrough authentication
+                Some(_) if config.credentials.is_empty() => {
+                    return Err(anyhow!(
+                        "a total_epsilon requires credentials to be configured"
+                    ))
+                }
+                Some(total_epsilon) => Some(Arc::new(match &differential_privacy.ledger_path {
+                    Some(path) => {
+                        PrivacyBudgetLedger::with_persistence(total_epsilon, Path::new(path))?
+                    }
+                    None => PrivacyBudgetLedger::new(total_epsilon),
+                })),
+                None => None,
+            };
+
+            Ok((differential_privacy, ledger))
+        })
+        .transpose()?;
+
+    if let Some(explain_matches) = matches.subcommand_matches("explain") {
+        let query = explain_matches
+            .value_of("query")
+            .expect("Missing value for 'query' argument");
+        let target = find_target(targets, explain_matches.value_of("database"))?;
+
+        let columns = match explain_matches.value_of("user") {
+            Some(user) => {
+                let credential = config
+                    .credentials
+                    .iter()
+                    .find(|credential| credential.username == user)
+                    .ok_or_else(|| anyhow!("the user '{}' isn't configured", user))?;
+
+                target
+                    .columns
+                    .into_iter()
+                    .filter(|column| column.applies_to(credential))
+                    .collect()
+            }
+            None => target.columns,
+        };
+
+        let transformer =
+            anonymization_transformer(columns, criteria, table_criteria, column_concurrency);
+        crate::explain::explain(&target.connection_uri, query, &transformer).await?;
+        return Ok(());
+    }
+
+    if let Some(anonymize_matches) = matches.subcommand_matches("anonymize") {
+        let table = anonymize_matches
+            .value_of("table")
+            .expect("Missing value for 'table' argument");
+        let output = anonymize_matches
+            .value_of("output")
+            .expect("Missing value for 'output' argument");
+
+        let target = find_target(targets, anonymize_matches.value_of("database"))?;
+        let transformer =
+            anonymization_transformer(target.columns, criteria, table_criteria, column_concurrency);
+
+        crate::anonymize::anonymize(
+            &target.connection_uri,
+            table,
+            anonymize_matches.value_of("input").map(Path::new),
+            Path::new(output),
+            &transformer,
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let row_sampling = config
+        .row_sampling
+        .iter()
+        .map(|sampling| {
+            if !(0.0..=1.0).contains(&sampling.fraction) {
+                return Err(anyhow!(
+                    "the sampled fraction of {} has to be between 0 and 1",
+                    sampling.tables.join(", ")
+                ));
+            }
+
+            Ok((sampling.clone(), sampling_seed(sampling)))
+        })
+        .collect::<Result<Vec<_>>>()?;
 
-    let listener = TcpListener::bind(config.listener.to_address()).await?;
+    if let Some(cap) = config
+        .row_caps
+        .iter()
+        .find(|cap| cap.users.is_empty() && cap.roles.is_empty())
+    {
+        return Err(anyhow!(
+            "the row cap of {} rows applies to no users or roles",
+            cap.max_rows
+        ));
+    }
+
+    let policies = Policies {
+        criteria,
+        table_criteria,
+        min_group_sizes,
+        differential_privacy,
+        pseudonym_domains: config.pseudonym_domains.clone(),
+        masks: [
+            wasm_masks(&config.wasm_masks)?,
+            scripted_columns(&config.scripted_columns)?,
+        ]
+        .concat(),
+        row_filters: config.row_filters.clone(),
+        retention_rules,
+        describe_masking: config
+            .describe_masking
+            .clone()
+            .map(|masking| masking.into()),
+        row_sampling,
+        row_caps: config.row_caps.clone(),
+        parameterized_min_rows: config
+            .parameterized_filters
+            .as_ref()
+            .filter(|filters| filters.enabled)
+            .map(|filters| filters.min_rows.unwrap_or(config.k)),
+        column_concurrency,
+        unparseable_queries: config.unparseable_queries.into(),
+        resolver_layers: config.resolver_layers()?,
+        catalog: config.catalog.clone(),
+    };
+
+    if targets.is_empty() && application_targets.is_empty() {
+        return Err(anyhow!(
+            "either a connection_uri or at least one database has to be configured"
+        ));
+    }
+
+    let upstreams = targets
+        .iter()
+        .map(target_config)
+        .chain(
+            application_targets
+                .iter()
+                .map(|(application_name, target)| {
+                    target_config(target).with_context(|| {
+                        format!(
+                            "can't connect to the target of the application '{}'",
+                            application_name
+                        )
+                    })
+                }),
+        )
+        .map(|target_config| {
+            target_config.map(|target_config| crate::health::Upstream {
+                host: target_config.host,
+                port: target_config.port,
+            })
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let proxy_config = proboscis_core::Config {
+        credentials,
+        tls_config: tls_config.clone(),
+    };
+
+    // Clients requesting a database that isn't configured by name use the default target
+    let mut proxy = match targets.iter().position(|target| target.name.is_none()) {
+        Some(index) => {
+            let target = targets.remove(index);
+            Proxy::new(
+                proxy_config,
+                target_resolver(target, &policies, &config.credentials, config.cache.clone())
+                    .await?,
+            )
+        }
+        None => Proxy::with_databases(proxy_config, HashMap::new()),
+    };
+
+    for target in targets {
+        let name = target
+            .name
+            .clone()
+            .expect("Only the default target is unnamed");
+        proxy = proxy.add_database(
+            &name,
+            target_resolver(target, &policies, &config.credentials, config.cache.clone()).await?,
+        );
+    }
+
+    for (application_name, target) in application_targets {
+        proxy = proxy.add_application(
+            &application_name,
+            target_resolver(target, &policies, &config.credentials, config.cache.clone()).await?,
+        );
+    }
+
+    if let Some(admin_config) = &config.admin {
+        let mut admin = crate::admin::AdminResolver::new(admin_config.users.clone());
+        if let Some((_, Some(ledger))) = &policies.differential_privacy {
+            admin = admin.with_budget_ledger(ledger.clone());
+        }
+
+        proxy = proxy.add_database(&admin_config.database, Box::new(admin));
+    }
+
+    if config.authentication == AuthenticationMode::Passthrough {
+        proxy = proxy.with_authentication_passthrough();
+    }
+
+    proxy = proxy
+        .with_address_filter(address_filter)
+        .with_gss_encryption(config.gss_encryption.into())
+        .with_authentication_method(config.password_encryption.into());
+
+    if let Some(startup_timeout_seconds) = config.startup_timeout_seconds {
+        proxy = proxy.with_startup_timeout(Duration::from_secs(startup_timeout_seconds));
+    }
+
+    if let Some(authentication_timeout_seconds) = config.authentication_timeout_seconds {
+        proxy =
+            proxy.with_authentication_timeout(Duration::from_secs(authentication_timeout_seconds));
+    }
+
+    if let Some(flight_config) = &config.flight {
+        let target = config
+            .targets()
+            .into_iter()
+            .find(|target| target.name.is_none())
+            .ok_or_else(|| anyhow!("the flight server requires a top level connection_uri"))?;
+        let resolver =
+            target_resolver(target, &policies, &config.credentials, config.cache.clone()).await?;
+        let address = tokio::net::lookup_host(flight_config.to_address())
+            .await?
+            .next()
+            .ok_or_else(|| anyhow!("couldn't resolve {}", flight_config.to_address()))?;
+
+        let flight_server = FlightServer::new(resolver, proxy.credentials());
+        tokio::spawn(async move {
+            if let Err(err) = flight_server.serve(address).await {
+                tracing::error!("flight server failed: {}", err);
+            }
+        });
+    }
+
+    #[cfg(unix)]
+    tokio::spawn(crate::credentials::reload_on_hangup(
+        config.credentials.clone(),
+        credentials_file,
+        proxy.credentials(),
+    ));
+
+    if let Some(health_config) = config.health {
+        let health_listener = TcpListener::bind(health_config.to_address()).await?;
+        tokio::spawn(crate::health::serve(
+            health_listener,
+            upstreams,
+            proxy.metrics(),
+        ));
+    }
+
+    let mut listeners = vec![Listener {
+        listener: TcpListener::bind(config.listener.to_address()).await?,
+        tls_config: config
+            .listener
+            .tls
+            .map(|config| config.into())
+            .or(tls_config),
+        require_tls: config.listener.require_tls.unwrap_or(config.require_tls),
+    }];
+
+    for listener_config in config.listeners {
+        listeners.push(Listener {
+            listener: TcpListener::bind(listener_config.to_address()).await?,
+            tls_config: listener_config.tls.map(|config| config.into()),
+            require_tls: listener_config.require_tls.unwrap_or(config.require_tls),
+        });
+    }
 
-    proxy.listen(listener).await?;
+    proxy.listen_all(listeners).await?;
 
     Ok(())
 }
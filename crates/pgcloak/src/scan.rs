@@ -0,0 +1,372 @@
+use crate::check::{load_database_columns, SUPPORTED_QUASI_IDENTIFIER_TYPES};
+use crate::init::{suggest, Suggestion};
+use crate::target::query_target;
+use anyhow::{anyhow, Result};
+use arrow::array::{Array, GenericStringArray};
+use regex::Regex;
+use std::collections::{BTreeMap, HashMap};
+use std::fmt::Write;
+
+// Values of these types are matched against the patterns, numbers would look like phone numbers
+const TEXT_TYPES: &[&str] = &["text", "character varying", "character"];
+
+const PATTERNS: &[(&str, &str)] = &[
+    ("email addresses", r"^[^@\s]+@[^@\s]+\.[A-Za-z]{2,}$"),
+    ("social security numbers", r"^\d{3}-\d{2}-\d{4}$"),
+    ("phone numbers", r"^\+?\d[\d ()./-]{5,18}\d$"),
+];
+
+// Share of the sampled values a pattern has to match
+const PATTERN_MATCH_RATIO: f64 = 0.8;
+
+// Columns with a larger share of distinct values single out rows by themselves
+const IDENTIFIER_UNIQUENESS: f64 = 0.95;
+
+// Columns with a larger share of values occurring only once single out rows in combination
+const QUASI_IDENTIFIER_SINGLETONS: f64 = 0.2;
+
+// Smaller samples say nothing about the uniqueness of a column
+const MIN_UNIQUENESS_SAMPLE: usize = 20;
+
+/// The sampled values of a column, null values are left out
+#[derive(Debug)]
+pub struct ColumnSample {
+    /// Qualified name, like `contacts.email`
+    pub name: String,
+    pub data_type: String,
+    pub values: Vec<String>,
+}
+
+/// A suggested classification of a column and the evidence for it
+#[derive(Debug, PartialEq)]
+pub struct Finding {
+    pub column: String,
+    pub suggestion: Suggestion,
+    pub reasons: Vec<String>,
+}
+
+fn ratio(count: usize, total: usize) -> f64 {
+    count as f64 / total as f64
+}
+
+// Keys are unique but only identify a person through the rows they refer to
+fn is_key(column_name: &str) -> bool {
+    let column_name = column_name.to_lowercase();
+    column_name == "id" || column_name.ends_with("_id")
+}
+
+pub struct Scanner {
+    patterns: Vec<(&'static str, Regex)>,
+}
+
+impl Default for Scanner {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scanner {
+    pub fn new() -> Self {
+        Scanner {
+            patterns: PATTERNS
+                .iter()
+                .map(|(description, pattern)| {
+                    (*description, Regex::new(pattern).expect("Invalid pattern"))
+                })
+                .collect(),
+        }
+    }
+
+    /// Classifies a column by its name, the patterns its values match and their uniqueness,
+    /// none if nothing suggests it identifies a person
+    pub fn classify(&self, sample: &ColumnSample) -> Option<Finding> {
+        let column_name = sample.name.split('.').last().unwrap_or(&sample.name);
+        let total = sample.values.len();
+
+        let mut is_identifier = false;
+        let mut is_quasi_identifier = false;
+        let mut reasons = vec![];
+
+        match suggest(column_name, &sample.data_type) {
+            Suggestion::Identifier => {
+                is_identifier = true;
+                reasons.push("its name suggests an identifier".to_string());
+            }
+            Suggestion::QuasiIdentifier => {
+                is_quasi_identifier = true;
+                reasons.push("its name suggests a quasi-identifier".to_string());
+            }
+            Suggestion::None => {}
+        }
+
+        if total > 0 && TEXT_TYPES.contains(&sample.data_type.as_str()) {
+            let matching_pattern = self.patterns.iter().find_map(|(description, pattern)| {
+                let matches = sample
+                    .values
+                    .iter()
+                    .filter(|value| pattern.is_match(value.trim()))
+                    .count();
+
+                Some((description, ratio(matches, total)))
+                    .filter(|(_, share)| *share >= PATTERN_MATCH_RATIO)
+            });
+
+            if let Some((description, share)) = matching_pattern {
+                is_identifier = true;
+                reasons.push(format!(
+                    "{:.0}% of {} sampled values look like {}",
+                    share * 100.0,
+                    total,
+                    description
+                ));
+            }
+        }
+
+        if total >= MIN_UNIQUENESS_SAMPLE && !is_key(column_name) {
+            let mut occurrences: HashMap<&str, usize> = HashMap::new();
+            for value in &sample.values {
+                *occurrences.entry(value.as_str()).or_default() += 1;
+            }
+
+            let uniqueness = ratio(occurrences.len(), total);
+            let singletons = ratio(
+                occurrences.values().filter(|count| **count == 1).count(),
+                total,
+            );
+
+            if uniqueness >= IDENTIFIER_UNIQUENESS {
+                is_identifier = true;
+                reasons.push(format!(
+                    "{:.0}% of {} sampled values are distinct",
+                    uniqueness * 100.0,
+                    total
+                ));
+            } else if singletons >= QUASI_IDENTIFIER_SINGLETONS
+                && SUPPORTED_QUASI_IDENTIFIER_TYPES.contains(&sample.data_type.as_str())
+            {
+                is_quasi_identifier = true;
+                reasons.push(format!(
+                    "{:.0}% of {} sampled values occur only once",
+                    singletons * 100.0,
+                    total
+                ));
+            }
+        }
+
+        let suggestion = if is_identifier {
+            Suggestion::Identifier
+        } else if is_quasi_identifier {
+            Suggestion::QuasiIdentifier
+        } else {
+            return None;
+        };
+
+        Some(Finding {
+            column: sample.name.clone(),
+            suggestion,
+            reasons,
+        })
+    }
+}
+
+/// Renders the findings as `[[columns]]` entries, to be merged into a config
+pub fn render_findings(sample_size: usize, findings: &[Finding]) -> String {
+    let mut config = String::new();
+
+    // Writing to a string can't fail
+    let _ = writeln!(
+        config,
+        "# Generated by `pgcloak scan` from up to {} rows of every table, review\n\
+         # the suggested columns before merging them into the config",
+        sample_size
+    );
+
+    for finding in findings {
+        let column_type = match finding.suggestion {
+            Suggestion::Identifier => "identifier",
+            Suggestion::QuasiIdentifier => "pseudo_identifier",
+            Suggestion::None => continue,
+        };
+
+        let _ = writeln!(config);
+        for reason in &finding.reasons {
+            let _ = writeln!(config, "# {}", reason);
+        }
+        let _ = writeln!(
+            config,
+            "[[columns]]\ntype = \"{}\"\nname = \"{}\"",
+            column_type, finding.column
+        );
+    }
+
+    config
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Samples the values of the columns of a table, as text
+async fn sample_table(
+    connection_uri: &str,
+    table: &str,
+    columns: &[(&str, &str)],
+    sample_size: usize,
+) -> Result<Vec<ColumnSample>> {
+    let query = format!(
+        "SELECT {} FROM {} LIMIT {}",
+        columns
+            .iter()
+            .map(|(column, _)| format!("{}::text", quote_identifier(column)))
+            .collect::<Vec<String>>()
+            .join(", "),
+        quote_identifier(table),
+        sample_size
+    );
+
+    let mut samples: Vec<ColumnSample> = columns
+        .iter()
+        .map(|(column, data_type)| ColumnSample {
+            name: format!("{}.{}", table, column),
+            data_type: data_type.to_string(),
+            values: vec![],
+        })
+        .collect();
+
+    for batch in query_target(connection_uri, &query).await? {
+        for (index, sample) in samples.iter_mut().enumerate() {
+            let values = batch
+                .column(index)
+                .as_any()
+                .downcast_ref::<GenericStringArray<i64>>()
+                .ok_or_else(|| anyhow!("unexpected type of column {}", sample.name))?;
+
+            sample
+                .values
+                .extend(values.iter().flatten().map(str::to_string));
+        }
+    }
+
+    Ok(samples)
+}
+
+/// Prints suggested column classifications for the tables of the database,
+/// based on a sample of their rows
+pub async fn scan(connection_uri: &str, sample_size: usize) -> Result<()> {
+    let database_columns = load_database_columns(connection_uri).await?;
+
+    let mut tables: BTreeMap<&str, Vec<(&str, &str)>> = BTreeMap::new();
+    for (name, data_type) in &database_columns {
+        if let Some((table, column)) = name.split_once('.') {
+            tables
+                .entry(table)
+                .or_default()
+                .push((column, data_type.as_str()));
+        }
+    }
+
+    let scanner = Scanner::new();
+    let mut findings = vec![];
+
+    for (table, mut columns) in tables {
+        columns.sort();
+
+        for sample in sample_table(connection_uri, table, &columns, sample_size).await? {
+            findings.extend(scanner.classify(&sample));
+        }
+    }
+
+    print!("{}", render_findings(sample_size, &findings));
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample(name: &str, data_type: &str, values: Vec<String>) -> ColumnSample {
+        ColumnSample {
+            name: name.to_string(),
+            data_type: data_type.to_string(),
+            values,
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let scanner = Scanner::new();
+        let classify = |sample: ColumnSample| {
+            scanner
+                .classify(&sample)
+                .map(|finding| finding.suggestion)
+                .unwrap_or(Suggestion::None)
+        };
+
+        let emails = (0..10)
+            .map(|i| format!("user{}@example.com", i % 3))
+            .collect();
+        assert_eq!(
+            Suggestion::Identifier,
+            classify(sample("contacts.contact", "text", emails))
+        );
+
+        let ssns = vec!["123-45-6789".to_string(), "987-65-4321".to_string()];
+        assert_eq!(
+            Suggestion::Identifier,
+            classify(sample("contacts.tax", "character varying", ssns))
+        );
+
+        let phones = vec!["+1 (555) 123-4567".to_string(), "555.987.6543".to_string()];
+        assert_eq!(
+            Suggestion::Identifier,
+            classify(sample("contacts.contact", "text", phones))
+        );
+
+        // Distinct values single out rows, unless they are keys
+        let unique: Vec<String> = (0..50).map(|i| format!("code {}", i)).collect();
+        assert_eq!(
+            Suggestion::Identifier,
+            classify(sample("contacts.code", "text", unique.clone()))
+        );
+        assert_eq!(
+            Suggestion::None,
+            classify(sample("contacts.code_id", "text", unique))
+        );
+
+        let mostly_unique = (0..50).map(|i| (i % 30).to_string()).collect();
+        assert_eq!(
+            Suggestion::QuasiIdentifier,
+            classify(sample("contacts.weight", "integer", mostly_unique))
+        );
+
+        let categories = (0..50).map(|i| (i % 5).to_string()).collect();
+        assert_eq!(
+            Suggestion::None,
+            classify(sample("contacts.category", "integer", categories))
+        );
+
+        // Numbers aren't matched against the patterns
+        let numbers = vec!["5551234567".to_string(), "5559876543".to_string()];
+        assert_eq!(
+            Suggestion::None,
+            classify(sample("contacts.amount", "bigint", numbers))
+        );
+    }
+
+    #[test]
+    fn test_render_findings() {
+        let findings = vec![Finding {
+            column: "contacts.email".to_string(),
+            suggestion: Suggestion::Identifier,
+            reasons: vec!["90% of 10 sampled values look like email addresses".to_string()],
+        }];
+
+        let config = render_findings(1000, &findings);
+
+        assert!(config.contains(
+            "# 90% of 10 sampled values look like email addresses\n\
+             [[columns]]\ntype = \"identifier\"\nname = \"contacts.email\""
+        ));
+    }
+}
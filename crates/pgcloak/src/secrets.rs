@@ -0,0 +1,306 @@
+use crate::config::SecretConfig;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use hyper::{body::to_bytes, client::HttpConnector, Body, Client, Request};
+use hyper_openssl::HttpsConnector;
+use openssl::{hash::MessageDigest, pkey::PKey, sha::sha256, sign::Signer};
+use proboscis_core::resolver::ResolveError;
+use proboscis_resolver_postgres::CredentialsProvider;
+use serde_json::Value;
+use std::fmt;
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn https_client() -> Result<Client<HttpsConnector<HttpConnector>>> {
+    Ok(Client::builder().build(HttpsConnector::new()?))
+}
+
+async fn send(request: Request<Body>) -> Result<Value> {
+    let response = https_client()?.request(request).await?;
+    let status = response.status();
+    let body = to_bytes(response.into_body()).await?;
+
+    if !status.is_success() {
+        return Err(anyhow!(
+            "secret store responded with {}: {}",
+            status,
+            String::from_utf8_lossy(&body)
+        ));
+    }
+
+    Ok(serde_json::from_slice(&body)?)
+}
+
+/// Reads the password from a key of a secret in the kv version 2 engine of HashiCorp Vault
+pub struct VaultSecret {
+    pub address: String,
+    pub token: String,
+    pub mount: String,
+    pub path: String,
+    pub key: String,
+}
+
+// The token isn't printed
+impl fmt::Debug for VaultSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VaultSecret")
+            .field("address", &self.address)
+            .field("mount", &self.mount)
+            .field("path", &self.path)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+impl VaultSecret {
+    async fn fetch(&self) -> Result<String> {
+        let request = Request::get(format!(
+            "{}/v1/{}/data/{}",
+            self.address.trim_end_matches('/'),
+            self.mount,
+            self.path
+        ))
+        .header("X-Vault-Token", &self.token)
+        .body(Body::empty())?;
+
+        let response = send(request).await?;
+
+        response["data"]["data"][&self.key]
+            .as_str()
+            .map(str::to_string)
+            .ok_or_else(|| anyhow!("vault secret {} has no key {}", self.path, self.key))
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for VaultSecret {
+    async fn password(&self) -> Result<String, ResolveError> {
+        self.fetch().await.map_err(ResolveError::Other)
+    }
+}
+
+/// Reads the password from a secret of AWS Secrets Manager, either the whole secret string
+/// or a key of it, if it is a json object like the secrets of RDS databases
+pub struct AwsSecret {
+    pub region: String,
+    pub secret_id: String,
+    pub key: Option<String>,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+    pub session_token: Option<String>,
+}
+
+// The keys aren't printed
+impl fmt::Debug for AwsSecret {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AwsSecret")
+            .field("region", &self.region)
+            .field("secret_id", &self.secret_id)
+            .field("key", &self.key)
+            .finish()
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &str) -> Result<Vec<u8>> {
+    let key = PKey::hmac(key)?;
+    let mut signer = Signer::new(MessageDigest::sha256(), &key)?;
+    signer.update(data.as_bytes())?;
+    Ok(signer.sign_to_vec()?)
+}
+
+/// Derives the key of the day, region and service for signing requests with signature version 4
+fn signing_key(
+    secret_access_key: &str,
+    date: &str,
+    region: &str,
+    service: &str,
+) -> Result<Vec<u8>> {
+    let key = hmac_sha256(format!("AWS4{}", secret_access_key).as_bytes(), date)?;
+    let key = hmac_sha256(&key, region)?;
+    let key = hmac_sha256(&key, service)?;
+    hmac_sha256(&key, "aws4_request")
+}
+
+const SECRETS_MANAGER_TARGET: &str = "secretsmanager.GetSecretValue";
+const SECRETS_MANAGER_CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+impl AwsSecret {
+    fn host(&self) -> String {
+        format!("secretsmanager.{}.amazonaws.com", self.region)
+    }
+
+    /// The value of the authorization header of a request with the body at the time,
+    /// which is formatted like `20240102T030405Z`
+    fn authorization(&self, body: &str, amz_date: &str) -> Result<String> {
+        let date = &amz_date[..8];
+
+        let mut headers = vec![
+            ("content-type", SECRETS_MANAGER_CONTENT_TYPE.to_string()),
+            ("host", self.host()),
+            ("x-amz-date", amz_date.to_string()),
+            ("x-amz-target", SECRETS_MANAGER_TARGET.to_string()),
+        ];
+        if let Some(session_token) = &self.session_token {
+            headers.push(("x-amz-security-token", session_token.clone()));
+        }
+        headers.sort();
+
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| *name)
+            .collect::<Vec<&str>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "POST\n/\n\n{}\n{}\n{}",
+            canonical_headers,
+            signed_headers,
+            to_hex(&sha256(body.as_bytes()))
+        );
+
+        let scope = format!("{}/{}/secretsmanager/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            to_hex(&sha256(canonical_request.as_bytes()))
+        );
+
+        let key = signing_key(
+            &self.secret_access_key,
+            date,
+            &self.region,
+            "secretsmanager",
+        )?;
+        let signature = to_hex(&hmac_sha256(&key, &string_to_sign)?);
+
+        Ok(format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key_id, scope, signed_headers, signature
+        ))
+    }
+
+    async fn fetch(&self) -> Result<String> {
+        let body = serde_json::json!({ "SecretId": self.secret_id }).to_string();
+        let amz_date = chrono::Utc::now().format("%Y%m%dT%H%M%SZ").to_string();
+
+        let mut request = Request::post(format!("https://{}/", self.host()))
+            .header("Content-Type", SECRETS_MANAGER_CONTENT_TYPE)
+            .header("X-Amz-Date", &amz_date)
+            .header("X-Amz-Target", SECRETS_MANAGER_TARGET)
+            .header("Authorization", self.authorization(&body, &amz_date)?);
+        if let Some(session_token) = &self.session_token {
+            request = request.header("X-Amz-Security-Token", session_token);
+        }
+
+        let response = send(request.body(Body::from(body))?).await?;
+        let secret = response["SecretString"]
+            .as_str()
+            .ok_or_else(|| anyhow!("aws secret {} has no secret string", self.secret_id))?;
+
+        match &self.key {
+            Some(key) => serde_json::from_str::<Value>(secret)?[key]
+                .as_str()
+                .map(str::to_string)
+                .ok_or_else(|| anyhow!("aws secret {} has no key {}", self.secret_id, key)),
+            None => Ok(secret.to_string()),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for AwsSecret {
+    async fn password(&self) -> Result<String, ResolveError> {
+        self.fetch().await.map_err(ResolveError::Other)
+    }
+}
+
+fn env_var(name: &str) -> Result<String> {
+    std::env::var(name).map_err(|_| anyhow!("the environment variable {} isn't set", name))
+}
+
+/// Creates the provider of the configured secret, tokens and keys
+/// which aren't configured are read from the environment
+pub fn credentials_provider(config: SecretConfig) -> Result<Box<dyn CredentialsProvider>> {
+    Ok(match config {
+        SecretConfig::Vault {
+            address,
+            token,
+            mount,
+            path,
+            key,
+            refresh_seconds: _,
+        } => Box::new(VaultSecret {
+            address,
+            token: match token {
+                Some(token) => token,
+                None => env_var("VAULT_TOKEN")?,
+            },
+            mount,
+            path,
+            key,
+        }),
+        SecretConfig::AwsSecretsManager {
+            region,
+            secret_id,
+            key,
+            refresh_seconds: _,
+        } => Box::new(AwsSecret {
+            region,
+            secret_id,
+            key,
+            access_key_id: env_var("AWS_ACCESS_KEY_ID")?,
+            secret_access_key: env_var("AWS_SECRET_ACCESS_KEY")?,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        }),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signing_key() {
+        // Example of the aws documentation
+        let key = signing_key(
+            "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY",
+            "20120215",
+            "us-east-1",
+            "iam",
+        )
+        .unwrap();
+
+        assert_eq!(
+            "f4780e2d9f65fa895f9c67b32ce1baf0b0d8a43505a000a1a9e090d414db404d",
+            to_hex(&key)
+        );
+    }
+
+    #[test]
+    fn test_authorization() {
+        let secret = AwsSecret {
+            region: "eu-central-1".to_string(),
+            secret_id: "analytics".to_string(),
+            key: None,
+            access_key_id: "AKIDEXAMPLE".to_string(),
+            secret_access_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+        };
+
+        assert_eq!(
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20240102/eu-central-1/secretsmanager/aws4_request, \
+             SignedHeaders=content-type;host;x-amz-date;x-amz-target, \
+             Signature=4dff458ec08655735f6bc304dd4947c85446a202a00c3247a2cda65c525087b1",
+            secret
+                .authorization("{\"SecretId\":\"analytics\"}", "20240102T030405Z")
+                .unwrap()
+        );
+    }
+}
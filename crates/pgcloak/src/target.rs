@@ -0,0 +1,22 @@
+use anyhow::{anyhow, Result};
+use arrow::record_batch::RecordBatch;
+use proboscis_core::resolver::{ClientContext, ClientId, Resolver};
+use proboscis_resolver_postgres::{PostgresResolver, TargetConfig};
+
+/// Runs a single query against the target database, outside of any client connection
+pub async fn query_target(connection_uri: &str, query: &str) -> Result<Vec<RecordBatch>> {
+    let target_config = TargetConfig::from_uri(connection_uri).map_err(|err| anyhow!(err))?;
+
+    let mut resolver = PostgresResolver::create(target_config, 1)
+        .await
+        .map_err(|err| anyhow!("couldn't connect to the database: {:?}", err))?;
+
+    let client_id = ClientId::new_v4();
+    resolver
+        .initialize(client_id, &ClientContext::default())
+        .await?;
+    let data = resolver.query(client_id, query.to_string()).await?;
+    resolver.terminate(client_id).await?;
+
+    Ok(data)
+}
@@ -0,0 +1,272 @@
+use crate::config::{ColumnConfiguration, NumericAggregationRef, StringAggregationRef};
+use anyhow::{anyhow, Result};
+use proboscis_core::catalog::{Catalog, CatalogColumn, CatalogRelation, CatalogSource};
+use proboscis_resolver_postgres::{PostgresCatalogSource, TargetConfig};
+use std::collections::BTreeMap;
+
+const NUMERIC_TYPES: &[&str] = &["smallint", "integer", "bigint"];
+const STRING_TYPES: &[&str] = &["character varying", "text"];
+
+// Identifiers are replaced by random strings of the length the proxy uses
+const RANDOM_STRING: &str = "left(md5(random()::text) || md5(random()::text), 30)";
+
+const HEADER: &str = "\
+-- Generated by `pgcloak generate-views` from the column rules, regenerate the views when they change.
+-- Identifiers are replaced by random values and denied columns are left out. Quasi-identifiers are
+-- generalized over the whole table, which is at least as coarse as the groups formed by the proxy.
+-- Transformations other than the column rules, e.g. pseudonyms or row filters, aren't applied.";
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// The expression replacing the values of the column in the view, none if it's left out
+fn masked_expression(
+    rule: Option<&ColumnConfiguration>,
+    column: &CatalogColumn,
+    table: &str,
+) -> Result<Option<String>> {
+    let name = quote_identifier(&column.name);
+
+    let expression = match rule {
+        None => name,
+        Some(ColumnConfiguration::Denied { .. }) => return Ok(None),
+        Some(ColumnConfiguration::Identifier { .. }) => {
+            match STRING_TYPES.contains(&column.type_name.as_str()) {
+                true => format!(
+                    "CASE WHEN {name} IS NOT NULL THEN {random} END AS {name}",
+                    name = name,
+                    random = RANDOM_STRING
+                ),
+                // Values of other types can't be randomized, none of them is revealed
+                false => format!("NULL::{} AS {}", column.type_name, name),
+            }
+        }
+        Some(ColumnConfiguration::PseudoIdentifier {
+            numeric_aggregation,
+            string_aggregation,
+            ..
+        }) => {
+            let aggregation = if NUMERIC_TYPES.contains(&column.type_name.as_str()) {
+                match numeric_aggregation {
+                    NumericAggregationRef::Median => format!(
+                        "div(sum({name}), count(*))::{data_type}",
+                        name = name,
+                        data_type = column.type_name
+                    ),
+                    NumericAggregationRef::Range => format!(
+                        "CASE WHEN min({name}) = max({name}) THEN min({name})::text \
+                         ELSE min({name}) || ' - ' || max({name}) END",
+                        name = name
+                    ),
+                }
+            } else if STRING_TYPES.contains(&column.type_name.as_str()) {
+                match string_aggregation {
+                    StringAggregationRef::Join => {
+                        format!("string_agg(DISTINCT {}::text, ', ')", name)
+                    }
+                    // The common prefix of all values is the one of the lowest and highest value
+                    // in bytewise order, it is empty if any value is null
+                    StringAggregationRef::Substring => {
+                        return Ok(Some(format!(
+                            "(SELECT CASE WHEN nulls THEN '' ELSE left(first, (\
+                             SELECT coalesce(min(i) - 1, least(length(first), length(last))) \
+                             FROM generate_series(1, least(length(first), length(last))) AS i \
+                             WHERE substr(first, i, 1) <> substr(last, i, 1))) END \
+                             FROM (SELECT min({name}::text COLLATE \"C\") AS first, \
+                             max({name}::text COLLATE \"C\") AS last, \
+                             count(*) > count({name}) AS nulls FROM {table}) AS bounds) AS {name}",
+                            name = name,
+                            table = table
+                        )))
+                    }
+                }
+            } else {
+                return Err(anyhow!(
+                    "quasi-identifier column '{}' has the unsupported type '{}'",
+                    column.name,
+                    column.type_name
+                ));
+            };
+
+            format!("(SELECT {} FROM {}) AS {}", aggregation, table, name)
+        }
+    };
+
+    Ok(Some(expression))
+}
+
+fn view_definition(
+    relation: &CatalogRelation,
+    rules: &[&ColumnConfiguration],
+    schema: &str,
+) -> Result<String> {
+    let table = format!(
+        "{}.{}",
+        quote_identifier(&relation.schema),
+        quote_identifier(&relation.name)
+    );
+
+    let mut expressions = vec![];
+    for column in &relation.columns {
+        let rule = rules
+            .iter()
+            .find(|rule| rule.name().rsplit('.').next() == Some(column.name.as_str()));
+
+        if let Some(expression) = masked_expression(rule.copied(), column, &table)? {
+            expressions.push(expression);
+        }
+    }
+
+    Ok(format!(
+        "CREATE OR REPLACE VIEW {}.{} AS\nSELECT\n    {}\nFROM {};\n",
+        quote_identifier(schema),
+        quote_identifier(&relation.name),
+        expressions.join(",\n    "),
+        table
+    ))
+}
+
+/// The statements creating a view in the schema for every table with column rules,
+/// which masks the columns of the table like the proxy does
+pub fn generate_views(
+    catalog: &Catalog,
+    columns: &[ColumnConfiguration],
+    schema: &str,
+) -> Result<String> {
+    let mut tables: BTreeMap<&str, Vec<&ColumnConfiguration>> = BTreeMap::new();
+    for column in columns {
+        let (table, _) = column.name().rsplit_once('.').ok_or_else(|| {
+            anyhow!(
+                "column '{}' isn't qualified with its table, like 'table.column'",
+                column.name()
+            )
+        })?;
+
+        tables.entry(table).or_default().push(column);
+    }
+
+    let mut statements = vec![
+        HEADER.to_string(),
+        format!(
+            "CREATE SCHEMA IF NOT EXISTS {};\n",
+            quote_identifier(schema)
+        ),
+    ];
+
+    for (table, rules) in tables {
+        let relation = catalog
+            .relation(table)
+            .ok_or_else(|| anyhow!("table '{}' doesn't exist in the database", table))?;
+
+        for rule in &rules {
+            let column = rule.name().rsplit('.').next().unwrap_or_default();
+            if relation.column(column).is_none() {
+                return Err(anyhow!(
+                    "column '{}' doesn't exist in the database",
+                    rule.name()
+                ));
+            }
+        }
+
+        statements.push(view_definition(relation, &rules, schema)?);
+    }
+
+    Ok(statements.join("\n"))
+}
+
+/// Prints the statements creating the masked views of the tables of the target
+pub async fn print_views(
+    connection_uri: &str,
+    columns: &[ColumnConfiguration],
+    schema: &str,
+) -> Result<()> {
+    let target_config = TargetConfig::from_uri(connection_uri).map_err(|err| anyhow!(err))?;
+
+    let catalog = PostgresCatalogSource::new(target_config)
+        .load()
+        .await
+        .map_err(|err| anyhow!("couldn't load the catalog of the database: {:?}", err))?;
+
+    println!("{}", generate_views(&catalog, columns, schema)?);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proboscis_core::catalog::RelationKind;
+
+    fn column(name: &str, number: i16, type_name: &str) -> CatalogColumn {
+        CatalogColumn {
+            name: name.to_string(),
+            number,
+            type_oid: 0,
+            type_name: type_name.to_string(),
+        }
+    }
+
+    fn catalog() -> Catalog {
+        Catalog {
+            relations: vec![CatalogRelation {
+                oid: 16384,
+                schema: "public".to_string(),
+                name: "contacts".to_string(),
+                kind: RelationKind::Table,
+                columns: vec![
+                    column("id", 1, "integer"),
+                    column("email", 2, "text"),
+                    column("age", 3, "integer"),
+                    column("ssn", 4, "text"),
+                ],
+                definition: None,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_generate_views() {
+        let columns = vec![
+            ColumnConfiguration::Identifier {
+                name: "contacts.email".to_string(),
+                users: vec![],
+                roles: vec![],
+            },
+            ColumnConfiguration::PseudoIdentifier {
+                name: "contacts.age".to_string(),
+                numeric_aggregation: NumericAggregationRef::Range,
+                string_aggregation: StringAggregationRef::Join,
+                users: vec![],
+                roles: vec![],
+            },
+            ColumnConfiguration::Denied {
+                name: "contacts.ssn".to_string(),
+                users: vec![],
+                roles: vec![],
+            },
+        ];
+
+        let views = generate_views(&catalog(), &columns, "masked").unwrap();
+
+        assert!(views.contains("CREATE SCHEMA IF NOT EXISTS \"masked\";"));
+        assert!(views.contains("CREATE OR REPLACE VIEW \"masked\".\"contacts\" AS"));
+        assert!(views.contains("    \"id\",\n"));
+        assert!(views.contains(&format!(
+            "CASE WHEN \"email\" IS NOT NULL THEN {} END AS \"email\"",
+            RANDOM_STRING
+        )));
+        assert!(views.contains("FROM \"public\".\"contacts\") AS \"age\""));
+        assert!(!views.contains("\"ssn\""));
+    }
+
+    #[test]
+    fn test_generate_views_of_unknown_columns() {
+        let columns = vec![ColumnConfiguration::Identifier {
+            name: "contacts.phone".to_string(),
+            users: vec![],
+            roles: vec![],
+        }];
+
+        assert!(generate_views(&catalog(), &columns, "masked").is_err());
+    }
+}
@@ -0,0 +1,104 @@
+use arrow::{
+    array::{ArrayRef, Int32Array, StringArray},
+    datatypes::{DataType, Field, Schema},
+    record_batch::RecordBatch,
+};
+use criterion::{criterion_group, criterion_main, Criterion};
+use proboscis_anonymization::{
+    AnonymizationCriteria, AnonymizationTransformer, ColumnConcurrency, NumericAggregation,
+    PseudonymDomain, PseudonymizationTransformer, StringAggregation,
+};
+use proboscis_resolver_transformer::{
+    projection::{ProjectedOrigin, TableColumn},
+    Transformer,
+};
+use std::{collections::HashMap, sync::Arc};
+
+const ROWS: usize = 10_000;
+const STRING_COLUMNS: usize = 16;
+
+fn origin(column: &str) -> ProjectedOrigin {
+    ProjectedOrigin::TableColumn(TableColumn {
+        table: "contacts".to_string(),
+        column: column.to_string(),
+    })
+}
+
+// A wide result of string columns and an age column
+fn wide_batch(rows: usize) -> (RecordBatch, Vec<ProjectedOrigin>) {
+    let mut fields = vec![Field::new("age", DataType::Int32, false)];
+    let mut columns: Vec<ArrayRef> = vec![Arc::new(Int32Array::from(
+        (0..rows)
+            .map(|row| 18 + (row % 60) as i32)
+            .collect::<Vec<i32>>(),
+    ))];
+    let mut origins = vec![origin("age")];
+
+    for column in 0..STRING_COLUMNS {
+        let name = format!("column_{}", column);
+        fields.push(Field::new(&name, DataType::Utf8, false));
+        columns.push(Arc::new(StringArray::from(
+            (0..rows)
+                .map(|row| format!("value {} of {}", row % 100, name))
+                .collect::<Vec<String>>(),
+        )));
+        origins.push(origin(&name));
+    }
+
+    let batch = RecordBatch::try_new(Arc::new(Schema::new(fields)), columns).unwrap();
+    (batch, origins)
+}
+
+fn pseudonymization(c: &mut Criterion) {
+    let (batch, origins) = wide_batch(ROWS);
+
+    let transformer = |concurrency| PseudonymizationTransformer {
+        domains: vec![PseudonymDomain {
+            name: "contacts".to_string(),
+            key: b"secret".to_vec(),
+            columns: (0..STRING_COLUMNS)
+                .map(|column| format!("contacts.column_{}", column))
+                .collect(),
+        }],
+        concurrency,
+    };
+
+    let sequential = transformer(ColumnConcurrency::sequential());
+    c.bench_function("pseudonymize wide result", |b| {
+        b.iter(|| sequential.transform_records(&batch, &origins).unwrap())
+    });
+
+    let parallel = transformer(ColumnConcurrency::new(4).unwrap());
+    c.bench_function("pseudonymize wide result on 4 threads", |b| {
+        b.iter(|| parallel.transform_records(&batch, &origins).unwrap())
+    });
+}
+
+fn aggregation(c: &mut Criterion) {
+    let (batch, origins) = wide_batch(ROWS / 10);
+
+    let mut quasi_identifier_columns = HashMap::new();
+    quasi_identifier_columns.insert(
+        "contacts.age".to_string(),
+        (NumericAggregation::Range, StringAggregation::Join),
+    );
+    quasi_identifier_columns.insert(
+        "contacts.column_0".to_string(),
+        (NumericAggregation::Range, StringAggregation::Substring),
+    );
+
+    let transformer = AnonymizationTransformer {
+        identifier_columns: vec!["contacts.column_1".to_string()],
+        quasi_identifier_columns,
+        criteria: vec![AnonymizationCriteria::KAnonymous { k: 10 }],
+        table_criteria: HashMap::new(),
+        concurrency: ColumnConcurrency::sequential(),
+    };
+
+    c.bench_function("aggregate wide result", |b| {
+        b.iter(|| transformer.transform_records(&batch, &origins).unwrap())
+    });
+}
+
+criterion_group!(benches, pseudonymization, aggregation);
+criterion_main!(benches);
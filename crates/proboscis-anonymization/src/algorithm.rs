@@ -1,4 +1,6 @@
 use crate::column_transformations::{ColumnTransformation, ColumnTransformationError};
+use crate::concurrency::ColumnConcurrency;
+use crate::population::Population;
 use arrow::array::Array;
 use itertools::Itertools;
 use polars::prelude::{ChunkCompare, DataFrame, NamedFrom, Series, UInt32Chunked};
@@ -7,6 +9,7 @@ use proboscis_resolver_transformer::TransformerError;
 use std::collections::{HashMap, HashSet, VecDeque};
 use std::convert::TryFrom;
 use std::ops::Deref;
+use std::sync::Arc;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -26,16 +29,23 @@ pub enum AnonymizationError {
 
 impl From<AnonymizationError> for TransformerError {
     fn from(error: AnonymizationError) -> Self {
-        TransformerError::Other(anyhow::anyhow!(error))
+        match error {
+            AnonymizationError::Arrow(err) => TransformerError::Arrow(err),
+            AnonymizationError::UnsupportedType(_) => {
+                TransformerError::Unsupported(error.to_string())
+            }
+            _ => TransformerError::Other(anyhow::anyhow!(error)),
+        }
     }
 }
 
-fn get_span(series: &Series) -> Result<Option<i64>, PolarsError> {
+fn get_span(series: &Series) -> Result<Option<i64>, AnonymizationError> {
     match series.dtype() {
         &polars::prelude::DataType::UInt8
         | &polars::prelude::DataType::UInt16
         | &polars::prelude::DataType::UInt32
         | &polars::prelude::DataType::UInt64
+        | &polars::prelude::DataType::Int8
         | &polars::prelude::DataType::Int16
         | &polars::prelude::DataType::Int32
         | &polars::prelude::DataType::Int64 => Ok({
@@ -56,11 +66,14 @@ fn get_span(series: &Series) -> Result<Option<i64>, PolarsError> {
         &polars::prelude::DataType::Utf8 => {
             Ok(series.arg_unique().map(|x| Some(x.len() as i64))?)
         }
-        _ => todo!(),
+        data_type => Err(AnonymizationError::UnsupportedType(data_type.clone())),
     }
 }
 
-fn get_spans(series: &[&Series], partition: &[u32]) -> Result<Vec<Option<i64>>, PolarsError> {
+fn get_spans(
+    series: &[&Series],
+    partition: &[u32],
+) -> Result<Vec<Option<i64>>, AnonymizationError> {
     let mut spans = vec![];
 
     for series in series {
@@ -84,7 +97,7 @@ fn split(
     df: &DataFrame,
     partition: &[u32],
     column_index: usize,
-) -> Result<(Vec<u32>, Vec<u32>), PolarsError> {
+) -> Result<(Vec<u32>, Vec<u32>), AnonymizationError> {
     let dfp = df[column_index].take(&UInt32Chunked::new_from_slice("idx", partition))?;
 
     match dfp.dtype() {
@@ -92,6 +105,7 @@ fn split(
         | &polars::prelude::DataType::UInt16
         | &polars::prelude::DataType::UInt32
         | &polars::prelude::DataType::UInt64
+        | &polars::prelude::DataType::Int8
         | &polars::prelude::DataType::Int16
         | &polars::prelude::DataType::Int32
         | &polars::prelude::DataType::Int64 => match dfp.median() {
@@ -135,15 +149,15 @@ fn split(
 
             Ok((left_indices, right_indices))
         }
-        _ => todo!(),
+        data_type => Err(AnonymizationError::UnsupportedType(data_type.clone())),
     }
 }
 
 pub fn partition_dataset(
     df: &DataFrame,
     quasi_identifiers: &[&str],
-    is_valid: &dyn Fn(&DataFrame, &[u32]) -> Result<bool, PolarsError>,
-) -> Result<Vec<Vec<u32>>, PolarsError> {
+    is_valid: &dyn Fn(&DataFrame, &[u32]) -> Result<bool, AnonymizationError>,
+) -> Result<Vec<Vec<u32>>, AnonymizationError> {
     let mut partitions: VecDeque<Vec<u32>> =
         vec![(0..df[0].len()).map(|i| i as u32).collect::<Vec<u32>>()].into();
 
@@ -255,6 +269,8 @@ fn deidentify_column(series: &Series) -> Result<Series, AnonymizationError> {
     apply_column_transformation_to_series(series, &crate::column_transformations::Randomize {})
 }
 
+/// Aggregates a single quasi-identifier column of a partition, picking the numeric
+/// or string aggregation configured for this specific column based on its type.
 fn agg_column(
     series: &Series,
     numeric_aggregation: &NumericAggregation,
@@ -262,6 +278,10 @@ fn agg_column(
 ) -> Result<Series, AnonymizationError> {
     match series.dtype() {
         polars::prelude::DataType::UInt8
+        | polars::prelude::DataType::UInt16
+        | polars::prelude::DataType::UInt32
+        | polars::prelude::DataType::UInt64
+        | polars::prelude::DataType::Int8
         | polars::prelude::DataType::Int16
         | polars::prelude::DataType::Int32
         | polars::prelude::DataType::Int64 => apply_column_transformation_to_series(
@@ -294,35 +314,178 @@ pub fn is_l_diverse(
         >= l)
 }
 
+fn value_distribution(series: &Series) -> HashMap<String, f64> {
+    let mut distribution: HashMap<String, f64> = HashMap::new();
+    for index in 0..series.len() {
+        *distribution
+            .entry(format!("{:?}", series.get(index)))
+            .or_default() += 1.0;
+    }
+
+    let total = series.len() as f64;
+    for share in distribution.values_mut() {
+        *share /= total;
+    }
+
+    distribution
+}
+
+/// The distribution of the sensitive values within the partition differs from their
+/// distribution in the whole dataset by at most t. The difference is measured as the
+/// earth mover's distance with equal ground distances, i.e. the variational distance.
+pub fn is_t_close(
+    df: &DataFrame,
+    partition: &[u32],
+    sensitive_column: &str,
+    t: f64,
+) -> Result<bool, PolarsError> {
+    let column = df.column(sensitive_column)?;
+
+    let overall = value_distribution(column);
+    let within =
+        value_distribution(&column.take(&UInt32Chunked::new_from_slice("idx", partition))?);
+
+    // Every value of the partition is also part of the whole dataset
+    let distance: f64 = overall
+        .iter()
+        .map(|(value, share)| (share - within.get(value).unwrap_or(&0.0)).abs())
+        .sum::<f64>()
+        / 2.0;
+
+    Ok(distance <= t)
+}
+
+/// Every group of individuals in the population sharing the generalized
+/// quasi-identifier values of the partition contains at least k individuals
+pub fn is_k_map(
+    df: &DataFrame,
+    partition: &[u32],
+    quasi_identifiers: &[&str],
+    population: &Population,
+    k: usize,
+) -> Result<bool, AnonymizationError> {
+    Ok(population.count_matching(df, partition, quasi_identifiers)? >= k)
+}
+
+/// The probability of an individual of the population being contained
+/// in the partition lies within [min, max]
+pub fn is_delta_present(
+    df: &DataFrame,
+    partition: &[u32],
+    quasi_identifiers: &[&str],
+    population: &Population,
+    min: f64,
+    max: f64,
+) -> Result<bool, AnonymizationError> {
+    let matching = population.count_matching(df, partition, quasi_identifiers)?;
+
+    if matching == 0 {
+        // The partition isn't part of the population, so its presence can't be bounded
+        return Ok(false);
+    }
+
+    let delta = partition.len() as f64 / matching as f64;
+
+    Ok(min <= delta && delta <= max)
+}
+
 #[derive(Clone)]
 pub enum AnonymizationCriteria {
-    KAnonymous { k: usize },
-    LDiverse { l: usize, sensitive_column: String },
+    KAnonymous {
+        k: usize,
+    },
+    LDiverse {
+        l: usize,
+        sensitive_column: String,
+    },
+    TClose {
+        t: f64,
+        sensitive_column: String,
+    },
+    KMap {
+        k: usize,
+        population: Arc<Population>,
+    },
+    DeltaPresent {
+        min: f64,
+        max: f64,
+        population: Arc<Population>,
+    },
 }
 
 impl AnonymizationCriteria {
-    fn is_anonymous(&self, df: &DataFrame, partition: &[u32]) -> Result<bool, PolarsError> {
+    fn is_anonymous(
+        &self,
+        df: &DataFrame,
+        partition: &[u32],
+        quasi_identifiers: &[&str],
+    ) -> Result<bool, AnonymizationError> {
         match self {
             Self::KAnonymous { k } => Ok(is_k_anonymous(partition, *k)),
             Self::LDiverse {
                 l,
                 sensitive_column,
-            } => is_l_diverse(df, partition, sensitive_column, *l),
+            } => Ok(is_l_diverse(df, partition, sensitive_column, *l)?),
+            Self::TClose {
+                t,
+                sensitive_column,
+            } => Ok(is_t_close(df, partition, sensitive_column, *t)?),
+            Self::KMap { k, population } => {
+                is_k_map(df, partition, quasi_identifiers, population, *k)
+            }
+            Self::DeltaPresent {
+                min,
+                max,
+                population,
+            } => is_delta_present(df, partition, quasi_identifiers, population, *min, *max),
         }
     }
 }
 
+/// The values of the column, with those of the identifiers replaced and those of the
+/// quasi identifiers aggregated per partition, ordered like the partitions
+fn anonymize_column(
+    column: &Series,
+    partitions: &[Vec<u32>],
+    identifiers: &[&str],
+    quasi_identifiers: &HashMap<String, (NumericAggregation, StringAggregation)>,
+) -> Result<Series, AnonymizationError> {
+    let is_quasi_identifier = quasi_identifiers.contains_key(column.name());
+    let is_identifier = identifiers.contains(&column.name());
+
+    let mut updated = column.take(&UInt32Chunked::new_from_slice("idx", &[]))?;
+
+    for partition in partitions {
+        let data = column.take(&UInt32Chunked::new_from_slice("idx", partition))?;
+
+        let new_data: Series = if is_quasi_identifier {
+            let (numeric_aggregation, string_aggregation) =
+                quasi_identifiers.get(column.name()).unwrap();
+            agg_column(&data, numeric_aggregation, string_aggregation)?
+        } else if is_identifier {
+            deidentify_column(&data)?
+        } else {
+            data
+        };
+
+        updated.append(&new_data)?;
+    }
+
+    Ok(updated)
+}
+
 pub fn anonymize(
     df: &DataFrame,
     identifiers: &[&str],
     quasi_identifiers: &HashMap<String, (NumericAggregation, StringAggregation)>,
     criteria: &[AnonymizationCriteria],
+    concurrency: &ColumnConcurrency,
 ) -> Result<DataFrame, AnonymizationError> {
     let quasi_identifier_strs: Vec<&str> = quasi_identifiers.keys().map(|k| k.as_str()).collect();
 
     let partitions = partition_dataset(df, &quasi_identifier_strs, &|_, partition| {
         for criterium in criteria {
-            if !criterium.is_anonymous(df, partition)? {
+            if !criterium.is_anonymous(df, partition, &quasi_identifier_strs)? {
                 return Ok(false);
             }
         }
@@ -330,33 +493,9 @@ pub fn anonymize(
         Ok(true)
     })?;
 
-    let mut updated: Vec<Series> = vec![];
-
-    for partition in &partitions {
-        for (index, column) in df.get_columns().iter().enumerate() {
-            let is_quasi_identifier = quasi_identifier_strs.contains(&column.name());
-            let is_identifier = identifiers.contains(&column.name());
-
-            let data = column.take(&UInt32Chunked::new_from_slice("idx", partition))?;
-
-            let new_data: Series = if is_quasi_identifier {
-                let (numeric_aggregation, string_aggregation) =
-                    quasi_identifiers.get(column.name()).unwrap();
-                agg_column(&data, numeric_aggregation, string_aggregation)?
-            } else if is_identifier {
-                deidentify_column(&data)?
-            } else {
-                data
-            };
-
-            let entry = updated.get_mut(index);
-
-            match entry {
-                Some(s) => updated[index] = s.append(&new_data)?.clone(),
-                None => updated.push(new_data),
-            };
-        }
-    }
+    let updated = concurrency.try_map(df.get_columns(), |column| {
+        anonymize_column(column, &partitions, identifiers, quasi_identifiers)
+    })?;
 
     let original_indices_of_updated_rows: Vec<u32> = partitions.concat();
 
@@ -468,6 +607,7 @@ mod tests {
             &identifiers,
             &quasi_identifiers,
             &[AnonymizationCriteria::KAnonymous { k: 2 }],
+            &ColumnConcurrency::sequential(),
         )
         .unwrap();
 
@@ -483,4 +623,71 @@ mod tests {
         assert_eq!(AnyValue::Int32(8), id_column.get(7));
         assert_eq!(AnyValue::Int32(9), id_column.get(8));
     }
+
+    #[test]
+    fn per_column_aggregation() {
+        let age_array = Int32Array::from(vec![18, 19, 40, 41]);
+        let zip_array = StringArray::from(vec!["10115", "10117", "80331", "80333"]);
+
+        let schema = Schema::new(vec![
+            Field::new("age", DataType::Int32, false),
+            Field::new("zip", DataType::Utf8, false),
+        ]);
+
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(age_array), Arc::new(zip_array)],
+        )
+        .unwrap();
+
+        let df = record_batch_to_data_frame(&batch).unwrap();
+
+        let quasi_identifiers = vec![
+            (
+                "age".to_string(),
+                (NumericAggregation::Range, StringAggregation::Join),
+            ),
+            (
+                "zip".to_string(),
+                (NumericAggregation::Median, StringAggregation::Substring),
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let anonymized = anonymize(
+            &df,
+            &[],
+            &quasi_identifiers,
+            &[AnonymizationCriteria::KAnonymous { k: 2 }],
+            &ColumnConcurrency::new(2).unwrap(),
+        )
+        .unwrap();
+
+        let age_column = anonymized.column("age").unwrap();
+        let zip_column = anonymized.column("zip").unwrap();
+
+        assert_eq!(AnyValue::Utf8("18 - 19"), age_column.get(0));
+        assert_eq!(AnyValue::Utf8("1011*"), zip_column.get(0));
+        assert_eq!(AnyValue::Utf8("40 - 41"), age_column.get(3));
+        assert_eq!(AnyValue::Utf8("8033*"), zip_column.get(3));
+    }
+
+    #[test]
+    fn t_closeness() {
+        let schema = Schema::new(vec![Field::new("diagnosis", DataType::Utf8, false)]);
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(StringArray::from(vec![
+                "Flu", "Flu", "Cancer", "Cancer",
+            ]))],
+        )
+        .unwrap();
+        let df = record_batch_to_data_frame(&batch).unwrap();
+
+        assert!(is_t_close(&df, &[0, 2], "diagnosis", 0.0).unwrap());
+        assert!(!is_t_close(&df, &[0, 1], "diagnosis", 0.4).unwrap());
+        assert!(is_t_close(&df, &[0, 1], "diagnosis", 0.5).unwrap());
+    }
 }
@@ -0,0 +1,211 @@
+use proboscis_resolver_transformer::TransformerError;
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum BudgetError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(
+        "privacy budget of user {user} exhausted (requested {requested}, remaining {remaining})"
+    )]
+    Exhausted {
+        user: String,
+        requested: f64,
+        remaining: f64,
+    },
+
+    #[error("invalid epsilon: {0}")]
+    InvalidEpsilon(f64),
+
+    #[error("invalid ledger entry: {0}")]
+    InvalidEntry(String),
+}
+
+impl From<BudgetError> for TransformerError {
+    fn from(error: BudgetError) -> Self {
+        TransformerError::Other(anyhow::anyhow!(error))
+    }
+}
+
+/// Tracks the epsilon each user has spent on differentially private queries.
+///
+/// Every user starts with the same total budget. Queries consume a part of it
+/// and are rejected once the remaining budget doesn't cover their epsilon.
+/// If a persistence path is configured, the consumed budgets are written
+/// to it after every change and restored on creation.
+#[derive(Debug)]
+pub struct PrivacyBudgetLedger {
+    total_epsilon: f64,
+    consumed: Mutex<HashMap<String, f64>>,
+    persistence_path: Option<PathBuf>,
+}
+
+impl PrivacyBudgetLedger {
+    pub fn new(total_epsilon: f64) -> PrivacyBudgetLedger {
+        PrivacyBudgetLedger {
+            total_epsilon,
+            consumed: Mutex::new(HashMap::new()),
+            persistence_path: None,
+        }
+    }
+
+    /// Creates a ledger persisted to the given file, loading previously consumed budgets if it exists
+    pub fn with_persistence(
+        total_epsilon: f64,
+        path: &Path,
+    ) -> Result<PrivacyBudgetLedger, BudgetError> {
+        let consumed = if path.exists() {
+            read_ledger_file(path)?
+        } else {
+            HashMap::new()
+        };
+
+        Ok(PrivacyBudgetLedger {
+            total_epsilon,
+            consumed: Mutex::new(consumed),
+            persistence_path: Some(path.to_path_buf()),
+        })
+    }
+
+    pub fn total(&self) -> f64 {
+        self.total_epsilon
+    }
+
+    pub fn remaining(&self, user: &str) -> f64 {
+        let consumed = self.consumed.lock().unwrap();
+        self.total_epsilon - consumed.get(user).cloned().unwrap_or(0.0)
+    }
+
+    /// The remaining budget of every user that consumed some of it, sorted by user
+    pub fn balances(&self) -> Vec<(String, f64)> {
+        let consumed = self.consumed.lock().unwrap();
+
+        let mut balances: Vec<(String, f64)> = consumed
+            .iter()
+            .map(|(user, consumed)| (user.clone(), self.total_epsilon - consumed))
+            .collect();
+
+        balances.sort_by(|(a, _), (b, _)| a.cmp(b));
+        balances
+    }
+
+    /// Deducts epsilon from the budget of the user, returning the remaining budget
+    pub fn consume(&self, user: &str, epsilon: f64) -> Result<f64, BudgetError> {
+        if !epsilon.is_finite() || epsilon <= 0.0 {
+            return Err(BudgetError::InvalidEpsilon(epsilon));
+        }
+
+        let mut consumed = self.consumed.lock().unwrap();
+
+        let already_consumed = consumed.get(user).cloned().unwrap_or(0.0);
+        let remaining = self.total_epsilon - already_consumed;
+
+        if epsilon > remaining {
+            return Err(BudgetError::Exhausted {
+                user: user.to_string(),
+                requested: epsilon,
+                remaining,
+            });
+        }
+
+        consumed.insert(user.to_string(), already_consumed + epsilon);
+
+        if let Some(path) = &self.persistence_path {
+            write_ledger_file(path, &consumed)?;
+        }
+
+        Ok(remaining - epsilon)
+    }
+
+    /// Restores the full budget of the user
+    pub fn reset(&self, user: &str) -> Result<(), BudgetError> {
+        let mut consumed = self.consumed.lock().unwrap();
+        consumed.remove(user);
+
+        if let Some(path) = &self.persistence_path {
+            write_ledger_file(path, &consumed)?;
+        }
+
+        Ok(())
+    }
+}
+
+// The ledger file contains one `<user>\t<consumed epsilon>` entry per line
+fn read_ledger_file(path: &Path) -> Result<HashMap<String, f64>, BudgetError> {
+    let mut consumed = HashMap::new();
+
+    for line in fs::read_to_string(path)?.lines() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let (user, epsilon) = line
+            .rsplit_once('\t')
+            .ok_or_else(|| BudgetError::InvalidEntry(line.to_string()))?;
+
+        let epsilon: f64 = epsilon
+            .parse()
+            .map_err(|_| BudgetError::InvalidEntry(line.to_string()))?;
+
+        consumed.insert(user.to_string(), epsilon);
+    }
+
+    Ok(consumed)
+}
+
+fn write_ledger_file(path: &Path, consumed: &HashMap<String, f64>) -> Result<(), BudgetError> {
+    let contents: String = consumed
+        .iter()
+        .map(|(user, epsilon)| format!("{}\t{}\n", user, epsilon))
+        .collect();
+
+    // Write to a temporary file first so a crash can't leave a truncated ledger behind
+    let temporary_path = path.with_extension("tmp");
+    fs::write(&temporary_path, contents)?;
+    fs::rename(&temporary_path, path)?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rejects_queries_once_exhausted() {
+        let ledger = PrivacyBudgetLedger::new(1.0);
+
+        assert_eq!(0.5, ledger.consume("analyst", 0.5).unwrap());
+        assert_eq!(0.25, ledger.consume("analyst", 0.25).unwrap());
+        assert!(matches!(
+            ledger.consume("analyst", 0.5),
+            Err(BudgetError::Exhausted { .. })
+        ));
+
+        assert_eq!(0.25, ledger.remaining("analyst"));
+        assert_eq!(1.0, ledger.remaining("admin"));
+    }
+
+    #[test]
+    fn persists_consumed_budget() {
+        let path =
+            std::env::temp_dir().join(format!("proboscis-budget-{}.ledger", rand::random::<u64>()));
+
+        {
+            let ledger = PrivacyBudgetLedger::with_persistence(2.0, &path).unwrap();
+            ledger.consume("analyst", 0.5).unwrap();
+        }
+
+        let ledger = PrivacyBudgetLedger::with_persistence(2.0, &path).unwrap();
+        assert_eq!(1.5, ledger.remaining("analyst"));
+
+        fs::remove_file(&path).unwrap();
+    }
+}
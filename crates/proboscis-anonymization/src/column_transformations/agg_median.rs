@@ -1,4 +1,6 @@
-use super::{ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult};
+use super::{
+    repeat_primitive, ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult,
+};
 use arrow::{
     array::{ArrayRef, PrimitiveArray},
     datatypes::{
@@ -6,7 +8,7 @@ use arrow::{
         UInt16Type, UInt32Type, UInt64Type, UInt8Type,
     },
 };
-use std::{ops::Add, sync::Arc};
+use std::ops::Add;
 
 fn median<T>(array: &PrimitiveArray<T>) -> Option<T::Native>
 where
@@ -27,11 +29,7 @@ where
         .downcast_ref::<PrimitiveArray<T>>()
         .ok_or(super::ColumnTransformationError::DowncastFailed)?;
 
-    Ok(Arc::new(
-        vec![median(array); array.len()]
-            .into_iter()
-            .collect::<PrimitiveArray<T>>(),
-    ))
+    repeat_primitive::<T>(median(array), array.len())
 }
 
 pub struct AggMedian;
@@ -80,6 +78,7 @@ impl ColumnTransformation for AggMedian {
 mod tests {
     use super::*;
     use arrow::array::Int32Array;
+    use std::sync::Arc;
 
     #[test]
     fn test_number_agg_range_equal() {
@@ -1,26 +1,27 @@
-use super::{ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult};
+use super::{
+    repeat_string, ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult,
+};
 use arrow::{
-    array::{ArrayRef, PrimitiveArray, StringArray},
+    array::{Array, ArrayRef, PrimitiveArray},
+    compute::kernels::aggregate,
     datatypes::{
-        ArrowPrimitiveType, DataType, Int16Type, Int32Type, Int64Type, Int8Type, UInt16Type,
-        UInt32Type, UInt64Type, UInt8Type,
+        ArrowNumericType, ArrowPrimitiveType, DataType, Int16Type, Int32Type, Int64Type, Int8Type,
+        UInt16Type, UInt32Type, UInt64Type, UInt8Type,
     },
 };
-use std::{fmt::Display, sync::Arc};
+use std::fmt::Display;
 
 fn aggregated_value<T>(array: &PrimitiveArray<T>) -> Option<String>
 where
-    T: ArrowPrimitiveType,
-    <T as ArrowPrimitiveType>::Native: Ord + Display,
+    T: ArrowNumericType,
+    <T as ArrowPrimitiveType>::Native: PartialEq + Display,
 {
-    if array.is_empty() {
+    // A range including unknown values is unknown as well
+    if array.null_count() > 0 {
         return None;
     }
 
-    let min = array.iter().min().unwrap();
-    let max = array.iter().max().unwrap();
-
-    match (min, max) {
+    match (aggregate::min(array), aggregate::max(array)) {
         (Some(min), Some(max)) => {
             let agg = if max == min {
                 format!("{}", max)
@@ -36,19 +37,15 @@ where
 
 fn agg_numeric_array<T>(input: ArrayRef) -> ColumnTransformationResult<ArrayRef>
 where
-    T: ArrowPrimitiveType,
-    <T as ArrowPrimitiveType>::Native: Ord + Display,
+    T: ArrowNumericType,
+    <T as ArrowPrimitiveType>::Native: PartialEq + Display,
 {
     let array = input
         .as_any()
         .downcast_ref::<PrimitiveArray<T>>()
         .ok_or(super::ColumnTransformationError::DowncastFailed)?;
 
-    Ok(Arc::new(
-        vec![aggregated_value(array); array.len()]
-            .into_iter()
-            .collect::<StringArray>(),
-    ))
+    repeat_string::<i32>(aggregated_value(array).as_deref(), array.len())
 }
 
 pub struct AggRange;
@@ -84,7 +81,8 @@ impl ColumnTransformation for AggRange {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use arrow::array::Int32Array;
+    use arrow::array::{Int32Array, StringArray};
+    use std::sync::Arc;
 
     #[test]
     fn test_number_agg_range_equal() {
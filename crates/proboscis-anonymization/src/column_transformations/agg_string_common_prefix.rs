@@ -1,50 +1,54 @@
-use super::{ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult};
+use super::{
+    repeat_string, ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult,
+};
 use arrow::{
-    array::{ArrayRef, GenericStringArray},
+    array::{Array, ArrayRef, GenericStringArray},
     datatypes::DataType,
 };
-use std::{cmp, sync::Arc};
 
-pub fn longest_common_prefix(strings: Vec<&str>) -> &str {
-    if strings.is_empty() {
+// The common prefix of the values of the array, which is empty if any of them is null
+fn array_common_prefix<T: arrow::array::StringOffsetSizeTrait>(
+    array: &GenericStringArray<T>,
+) -> &str {
+    if array.is_empty() || array.null_count() > 0 {
         return "";
     }
-    let str0 = strings[0];
-    let str0bytes = str0.as_bytes();
-    let mut len = str0.len();
-    for str in &strings[1..] {
-        len = cmp::min(
-            len,
-            str.as_bytes()
-                .iter()
-                .zip(str0bytes)
-                .take_while(|&(a, b)| a == b)
-                .count(),
-        );
+
+    let first = array.value(0);
+    let mut len = first.len();
+    for index in 1..array.len() {
+        if len == 0 {
+            break;
+        }
+
+        len = array
+            .value(index)
+            .as_bytes()
+            .iter()
+            .zip(&first.as_bytes()[..len])
+            .take_while(|&(a, b)| a == b)
+            .count();
+    }
+
+    // The prefix may end within a multi-byte character
+    while !first.is_char_boundary(len) {
+        len -= 1;
     }
-    &strings[0][..len]
+
+    &first[..len]
 }
 
 fn agg_string_array<T: arrow::array::StringOffsetSizeTrait>(
     input: ArrayRef,
 ) -> ColumnTransformationResult<ArrayRef> {
-    let longest_common_prefix = longest_common_prefix(
-        input
-            .as_any()
-            .downcast_ref::<GenericStringArray<T>>()
-            .ok_or(super::ColumnTransformationError::DowncastFailed)?
-            .iter()
-            .map(|s| s.map_or("", |s| s))
-            .collect(),
-    );
-
-    Ok(Arc::new(GenericStringArray::<T>::from(vec![
-        format!(
-            "{}*",
-            longest_common_prefix
-        );
-        input.len()
-    ])))
+    let array = input
+        .as_any()
+        .downcast_ref::<GenericStringArray<T>>()
+        .ok_or(super::ColumnTransformationError::DowncastFailed)?;
+
+    let aggregated = format!("{}*", array_common_prefix(array));
+
+    repeat_string::<T>(Some(&aggregated), array.len())
 }
 
 pub struct AggStringCommonPrefix {}
@@ -62,12 +66,17 @@ impl ColumnTransformation for AggStringCommonPrefix {
 
     fn output_format(
         &self,
-        _input: &DataType,
+        input: &DataType,
     ) -> super::ColumnTransformationResult<ColumnTransformationOutput> {
-        Ok(ColumnTransformationOutput {
-            data_type: DataType::Utf8,
-            nullable: false,
-        })
+        match input {
+            DataType::Utf8 | DataType::LargeUtf8 => Ok(ColumnTransformationOutput {
+                data_type: input.clone(),
+                nullable: false,
+            }),
+            _ => Err(super::ColumnTransformationError::UnsupportedType(
+                input.clone(),
+            )),
+        }
     }
 }
 
@@ -75,6 +84,7 @@ impl ColumnTransformation for AggStringCommonPrefix {
 mod tests {
     use super::*;
     use arrow::array::StringArray;
+    use std::sync::Arc;
 
     #[test]
     fn test_string_aggregation() {
@@ -94,4 +104,16 @@ mod tests {
                 .collect::<Vec<Option<&str>>>()
         );
     }
+
+    #[test]
+    fn test_string_aggregation_multi_byte_prefix() {
+        let array = StringArray::from(vec!["Müller", "Mü"]);
+        assert_eq!("Mü", array_common_prefix(&array));
+
+        let array = StringArray::from(vec!["Mäx", "Möx"]);
+        assert_eq!("M", array_common_prefix(&array));
+
+        let array = StringArray::from(vec![Some("Berlin"), None]);
+        assert_eq!("", array_common_prefix(&array));
+    }
 }
@@ -1,30 +1,27 @@
-use super::{ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult};
+use super::{
+    repeat_string, ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult,
+};
 use arrow::{
     array::{ArrayRef, GenericStringArray},
     datatypes::DataType,
 };
 use itertools::Itertools;
-use std::sync::Arc;
+use std::collections::BTreeSet;
 
 fn agg_string_array<T: arrow::array::StringOffsetSizeTrait>(
     input: ArrayRef,
 ) -> ColumnTransformationResult<ArrayRef> {
-    let unique_strings: Vec<&str> = input
+    let unique_strings: BTreeSet<&str> = input
         .as_any()
         .downcast_ref::<GenericStringArray<T>>()
         .ok_or(super::ColumnTransformationError::DowncastFailed)?
         .iter()
-        .unique()
         .map(|v| v.map_or("None", |v| v))
-        .sorted()
         .collect();
 
-    let new_string = unique_strings.join(", ");
+    let new_string = unique_strings.iter().join(", ");
 
-    Ok(Arc::new(GenericStringArray::<T>::from(vec![
-        new_string;
-        input.len()
-    ])))
+    repeat_string::<T>(Some(&new_string), input.len())
 }
 
 pub struct AggStringJoinUnique;
@@ -42,12 +39,17 @@ impl ColumnTransformation for AggStringJoinUnique {
 
     fn output_format(
         &self,
-        _input: &DataType,
+        input: &DataType,
     ) -> super::ColumnTransformationResult<ColumnTransformationOutput> {
-        Ok(ColumnTransformationOutput {
-            data_type: DataType::Utf8,
-            nullable: false,
-        })
+        match input {
+            DataType::Utf8 | DataType::LargeUtf8 => Ok(ColumnTransformationOutput {
+                data_type: input.clone(),
+                nullable: false,
+            }),
+            _ => Err(super::ColumnTransformationError::UnsupportedType(
+                input.clone(),
+            )),
+        }
     }
 }
 
@@ -55,6 +57,7 @@ impl ColumnTransformation for AggStringJoinUnique {
 mod tests {
     use super::*;
     use arrow::array::StringArray;
+    use std::sync::Arc;
 
     #[test]
     fn test_string_aggregation() {
@@ -3,6 +3,8 @@ mod agg_range;
 mod agg_string_common_prefix;
 mod agg_string_join_unique;
 mod randomize;
+#[cfg(feature = "wasm")]
+mod wasm;
 
 pub use agg_median::AggMedian;
 pub use agg_range::AggRange;
@@ -10,8 +12,15 @@ pub use agg_string_common_prefix::AggStringCommonPrefix;
 pub use agg_string_join_unique::AggStringJoinUnique;
 use proboscis_resolver_transformer::TransformerError;
 pub use randomize::Randomize;
+#[cfg(feature = "wasm")]
+pub use wasm::{WasmLimits, WasmTransformation};
 
-use arrow::{array::ArrayRef, datatypes::DataType};
+use arrow::{
+    array::{ArrayRef, GenericStringBuilder, PrimitiveBuilder, StringOffsetSizeTrait},
+    datatypes::{ArrowPrimitiveType, DataType},
+    error::ArrowError,
+};
+use std::sync::Arc;
 use thiserror::Error;
 
 pub struct ColumnTransformationOutput {
@@ -28,6 +37,17 @@ pub enum ColumnTransformationError {
 
     #[error("downcast failed")]
     DowncastFailed,
+
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+
+    #[cfg(feature = "wasm")]
+    #[error("wasm module failed: {0}")]
+    Wasm(anyhow::Error),
+
+    #[cfg(feature = "scripting")]
+    #[error(transparent)]
+    Script(#[from] crate::scripting::ScriptError),
 }
 
 impl From<ColumnTransformationError> for TransformerError {
@@ -43,3 +63,40 @@ pub trait ColumnTransformation: Send + Sync {
         input: &DataType,
     ) -> ColumnTransformationResult<ColumnTransformationOutput>;
 }
+
+/// An array of `len` times the aggregated string, built without a string per row
+fn repeat_string<T: StringOffsetSizeTrait>(
+    value: Option<&str>,
+    len: usize,
+) -> ColumnTransformationResult<ArrayRef> {
+    let data_capacity = value.map_or(0, |value| value.len() * len);
+    let mut builder = GenericStringBuilder::<T>::with_capacity(len, data_capacity);
+
+    for _ in 0..len {
+        match value {
+            Some(value) => builder.append_value(value)?,
+            None => builder.append_null()?,
+        }
+    }
+
+    Ok(Arc::new(builder.finish()))
+}
+
+/// An array of `len` times the aggregated value
+fn repeat_primitive<T: ArrowPrimitiveType>(
+    value: Option<T::Native>,
+    len: usize,
+) -> ColumnTransformationResult<ArrayRef> {
+    let mut builder = PrimitiveBuilder::<T>::new(len);
+
+    match value {
+        Some(value) => builder.append_slice(&vec![value; len])?,
+        None => {
+            for _ in 0..len {
+                builder.append_null()?;
+            }
+        }
+    }
+
+    Ok(Arc::new(builder.finish()))
+}
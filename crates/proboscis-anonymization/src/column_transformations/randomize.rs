@@ -1,31 +1,43 @@
 use super::{ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult};
 use arrow::{
-    array::{ArrayRef, GenericStringArray},
+    array::{Array, ArrayRef, GenericStringArray, GenericStringBuilder},
     datatypes::DataType,
 };
 use rand::{distributions::Alphanumeric, thread_rng, Rng};
 use std::sync::Arc;
 
+const RANDOM_STRING_LENGTH: usize = 30;
+
 fn randomize_string_array<T: arrow::array::StringOffsetSizeTrait>(
     input: ArrayRef,
 ) -> ColumnTransformationResult<ArrayRef> {
-    Ok(Arc::new(
-        input
-            .as_any()
-            .downcast_ref::<GenericStringArray<T>>()
-            .ok_or(super::ColumnTransformationError::DowncastFailed)?
-            .iter()
-            .map(|v| {
-                v.map(|_| {
-                    thread_rng()
-                        .sample_iter(&Alphanumeric)
-                        .take(30)
-                        .map(char::from)
-                        .collect::<String>()
-                })
-            })
-            .collect::<GenericStringArray<T>>(),
-    ))
+    let array = input
+        .as_any()
+        .downcast_ref::<GenericStringArray<T>>()
+        .ok_or(super::ColumnTransformationError::DowncastFailed)?;
+
+    let mut rng = thread_rng();
+    let mut value = String::with_capacity(RANDOM_STRING_LENGTH);
+    let mut builder =
+        GenericStringBuilder::<T>::with_capacity(array.len(), array.len() * RANDOM_STRING_LENGTH);
+
+    for index in 0..array.len() {
+        if array.is_null(index) {
+            builder.append_null()?;
+            continue;
+        }
+
+        value.clear();
+        value.extend(
+            (&mut rng)
+                .sample_iter(&Alphanumeric)
+                .take(RANDOM_STRING_LENGTH)
+                .map(char::from),
+        );
+        builder.append_value(&value)?;
+    }
+
+    Ok(Arc::new(builder.finish()))
 }
 
 pub struct Randomize;
@@ -0,0 +1,204 @@
+use super::{
+    ColumnTransformation, ColumnTransformationError, ColumnTransformationOutput,
+    ColumnTransformationResult,
+};
+use anyhow::anyhow;
+use arrow::{
+    array::{Array, ArrayRef},
+    datatypes::{DataType, Field, Schema},
+    ipc::{reader::StreamReader, writer::StreamWriter},
+    record_batch::RecordBatch,
+};
+use std::{convert::TryFrom, io::Cursor, path::Path, sync::Arc};
+use wasmtime::{Config, Engine, Instance, Module, Store, StoreLimitsBuilder};
+
+// The single column of the record batches exchanged with the module
+const COLUMN_NAME: &str = "value";
+
+/// What a single call of a module may use
+#[derive(Clone, Copy, Debug)]
+pub struct WasmLimits {
+    /// Roughly the number of instructions the module may execute
+    pub fuel: u64,
+    pub memory_bytes: usize,
+}
+
+impl Default for WasmLimits {
+    fn default() -> Self {
+        WasmLimits {
+            fuel: 1_000_000_000,
+            memory_bytes: 256 * 1024 * 1024,
+        }
+    }
+}
+
+/// A transformation implemented by a WebAssembly module, so custom masking logic can be
+/// loaded at runtime. The module can't import anything and has to export
+///
+/// - `memory`
+/// - `alloc(len: i32) -> i32`, reserving `len` bytes of its memory for the input
+/// - `transform(ptr: i32, len: i32) -> i64`, receiving an Arrow IPC stream of a batch with
+///   a single column named `value`, and returning the offset (upper 32 bits) and length
+///   (lower 32 bits) of a stream of the same form with the transformed column
+///
+/// The transformed column has to keep the type and length of the input. Every column is
+/// transformed by a new instance of the module, nothing is kept between calls.
+pub struct WasmTransformation {
+    engine: Engine,
+    module: Module,
+    limits: WasmLimits,
+}
+
+fn wasm_error(error: anyhow::Error) -> ColumnTransformationError {
+    ColumnTransformationError::Wasm(error)
+}
+
+impl WasmTransformation {
+    /// Compiles the module, either binary or in the text format
+    pub fn new(module: &[u8], limits: WasmLimits) -> ColumnTransformationResult<Self> {
+        let mut config = Config::new();
+        config.consume_fuel(true);
+
+        let engine = Engine::new(&config).map_err(wasm_error)?;
+        let module = Module::new(&engine, module).map_err(wasm_error)?;
+
+        Ok(WasmTransformation {
+            engine,
+            module,
+            limits,
+        })
+    }
+
+    pub fn from_file(path: &Path, limits: WasmLimits) -> ColumnTransformationResult<Self> {
+        let module = std::fs::read(path)
+            .map_err(|err| wasm_error(anyhow!("failed to read {}: {}", path.display(), err)))?;
+        WasmTransformation::new(&module, limits)
+    }
+
+    /// Passes the input to the transform function of a new instance and returns its output
+    fn call(&self, input: &[u8]) -> anyhow::Result<Vec<u8>> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.memory_bytes)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.add_fuel(self.limits.fuel)?;
+
+        let instance = Instance::new(&mut store, &self.module, &[])?;
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| anyhow!("the module doesn't export its memory"))?;
+        let alloc = instance.get_typed_func::<i32, i32, _>(&mut store, "alloc")?;
+        let transform = instance.get_typed_func::<(i32, i32), i64, _>(&mut store, "transform")?;
+
+        let len = i32::try_from(input.len())?;
+        let ptr = alloc.call(&mut store, len)?;
+        memory.write(&mut store, ptr as u32 as usize, input)?;
+
+        let output = transform.call(&mut store, (ptr, len))? as u64;
+        let mut buffer = vec![0; (output & 0xffff_ffff) as usize];
+        memory.read(&store, (output >> 32) as usize, &mut buffer)?;
+
+        Ok(buffer)
+    }
+}
+
+impl ColumnTransformation for WasmTransformation {
+    fn transform_data(&self, data: ArrayRef) -> ColumnTransformationResult<ArrayRef> {
+        let schema = Schema::new(vec![Field::new(
+            COLUMN_NAME,
+            data.data_type().clone(),
+            true,
+        )]);
+        let batch = RecordBatch::try_new(Arc::new(schema.clone()), vec![data.clone()])?;
+
+        let mut input = vec![];
+        {
+            let mut writer = StreamWriter::try_new(&mut input, &schema)?;
+            writer.write(&batch)?;
+            writer.finish()?;
+        }
+
+        let output = self.call(&input).map_err(wasm_error)?;
+
+        let transformed = StreamReader::try_new(Cursor::new(output))?
+            .next()
+            .ok_or_else(|| wasm_error(anyhow!("the module returned no record batch")))??;
+
+        match transformed.columns() {
+            [column] if column.data_type() == data.data_type() && column.len() == data.len() => {
+                Ok(column.clone())
+            }
+            _ => Err(wasm_error(anyhow!(
+                "the module has to return a single column of the same type and length"
+            ))),
+        }
+    }
+
+    fn output_format(
+        &self,
+        input: &DataType,
+    ) -> ColumnTransformationResult<ColumnTransformationOutput> {
+        Ok(ColumnTransformationOutput {
+            data_type: input.clone(),
+            nullable: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::StringArray;
+
+    // Returns its input, which it reads from the start of its memory
+    const IDENTITY_MODULE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 0)
+            (func (export "transform") (param i32 i32) (result i64)
+                local.get 0
+                i64.extend_i32_u
+                i64.const 32
+                i64.shl
+                local.get 1
+                i64.extend_i32_u
+                i64.or))
+    "#;
+
+    const LOOPING_MODULE: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "alloc") (param i32) (result i32) i32.const 0)
+            (func (export "transform") (param i32 i32) (result i64)
+                (loop br 0)
+                i64.const 0))
+    "#;
+
+    fn names() -> ArrayRef {
+        Arc::new(StringArray::from(vec![Some("Alex"), None, Some("Mia")]))
+    }
+
+    #[test]
+    fn test_wasm_transformation() {
+        let transformation =
+            WasmTransformation::new(IDENTITY_MODULE.as_bytes(), WasmLimits::default()).unwrap();
+
+        let transformed = transformation.transform_data(names()).unwrap();
+        assert_eq!(names().data(), transformed.data());
+    }
+
+    #[test]
+    fn test_wasm_transformation_runs_out_of_fuel() {
+        let limits = WasmLimits {
+            fuel: 10_000,
+            ..WasmLimits::default()
+        };
+        let transformation = WasmTransformation::new(LOOPING_MODULE.as_bytes(), limits).unwrap();
+
+        assert!(matches!(
+            transformation.transform_data(names()),
+            Err(ColumnTransformationError::Wasm(_))
+        ));
+    }
+}
@@ -0,0 +1,22 @@
+use arrow::{array::Array, error::ArrowError, record_batch::RecordBatch};
+use std::ops::Deref;
+
+/// Combines record batches sharing a schema into a single one
+pub fn concat_record_batches(batches: &[RecordBatch]) -> Result<RecordBatch, ArrowError> {
+    let schema = batches
+        .first()
+        .ok_or_else(|| ArrowError::InvalidArgumentError("no batches to combine".to_string()))?
+        .schema();
+
+    let mut columns = vec![];
+    for index in 0..schema.fields().len() {
+        let arrays: Vec<&dyn Array> = batches
+            .iter()
+            .map(|batch| batch.column(index).deref())
+            .collect();
+
+        columns.push(arrow::compute::kernels::concat::concat(&arrays)?);
+    }
+
+    RecordBatch::try_new(schema, columns)
+}
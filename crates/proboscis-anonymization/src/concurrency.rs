@@ -0,0 +1,80 @@
+use rayon::{prelude::*, ThreadPool, ThreadPoolBuildError, ThreadPoolBuilder};
+use std::sync::Arc;
+
+/// How many columns of a result are transformed at the same time. Transformers sharing
+/// it share its threads, so the limit holds across all of them.
+#[derive(Clone, Default)]
+pub struct ColumnConcurrency {
+    // Columns are transformed on the calling thread without a pool
+    pool: Option<Arc<ThreadPool>>,
+}
+
+impl ColumnConcurrency {
+    /// Transforms up to `limit` columns in parallel, one is the same as sequentially
+    pub fn new(limit: usize) -> Result<Self, ThreadPoolBuildError> {
+        if limit <= 1 {
+            return Ok(Self::sequential());
+        }
+
+        let pool = ThreadPoolBuilder::new()
+            .num_threads(limit)
+            .thread_name(|index| format!("column-transformation-{}", index))
+            .build()?;
+
+        Ok(Self {
+            pool: Some(Arc::new(pool)),
+        })
+    }
+
+    pub fn sequential() -> Self {
+        Self { pool: None }
+    }
+
+    /// Applies the transformation to every column, keeping their order
+    pub(crate) fn try_map<T, R, E, F>(&self, columns: &[T], transformation: F) -> Result<Vec<R>, E>
+    where
+        T: Sync,
+        R: Send,
+        E: Send,
+        F: Fn(&T) -> Result<R, E> + Sync + Send,
+    {
+        match &self.pool {
+            Some(pool) if columns.len() > 1 => {
+                pool.install(|| columns.par_iter().map(transformation).collect())
+            }
+            _ => columns.iter().map(transformation).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_try_map_keeps_order() {
+        let columns: Vec<usize> = (0..64).collect();
+
+        for concurrency in &[
+            ColumnConcurrency::sequential(),
+            ColumnConcurrency::new(4).unwrap(),
+        ] {
+            let result: Result<Vec<usize>, ()> =
+                concurrency.try_map(&columns, |column| Ok(column * 2));
+            assert_eq!(
+                columns
+                    .iter()
+                    .map(|column| column * 2)
+                    .collect::<Vec<usize>>(),
+                result.unwrap()
+            );
+
+            let result: Result<Vec<usize>, usize> =
+                concurrency.try_map(&columns, |column| match column {
+                    10 => Err(*column),
+                    _ => Ok(*column),
+                });
+            assert_eq!(Err(10), result);
+        }
+    }
+}
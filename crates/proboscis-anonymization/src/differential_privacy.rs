@@ -0,0 +1,682 @@
+use crate::budget::PrivacyBudgetLedger;
+use arrow::{
+    array::{ArrayRef, BooleanArray, Float32Array, Float64Array, Int32Array, Int64Array},
+    compute::filter_record_batch,
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+};
+use proboscis_core::utils::sql::{table_references, unqualified_table_name};
+use proboscis_resolver_transformer::{
+    projection::{Aggregate, ProjectedOrigin, TableColumn},
+    Transformer, TransformerError,
+};
+use rand::Rng;
+use sqlparser::{
+    ast::{Expr, Function, Query, SelectItem, SetExpr, Statement, TableFactor, TableWithJoins},
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+use std::sync::Arc;
+
+/// The budget a user spends on the queries answered by the transformer
+pub struct UserBudget {
+    pub user: String,
+    pub ledger: Arc<PrivacyBudgetLedger>,
+}
+
+/// Answers aggregate queries over protected tables, like
+/// `SELECT city, count(*) FROM contacts GROUP BY city`, with noisy results.
+///
+/// Counts and sums receive Laplace noise and groups whose noisy count is below the
+/// threshold are suppressed. Results exposing rows of protected tables are rejected.
+/// The values of sums are clamped to the sensitivity by the query the target runs.
+pub struct DifferentialPrivacyTransformer {
+    pub protected_tables: Vec<String>,
+    /// Spent by every query, split evenly among its noisy columns
+    pub epsilon: f64,
+    /// Groups with a smaller noisy count are removed from results. Results of protected tables
+    /// can't be grouped unless it is positive.
+    pub threshold: f64,
+    /// The largest absolute value a single row contributes to a sum, larger values are
+    /// clamped to it. Sums can't be answered without it.
+    pub sum_sensitivity: Option<f64>,
+    pub budget: Option<UserBudget>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Perturbation {
+    Unchanged,
+    Count,
+    Sum(f64),
+}
+
+impl DifferentialPrivacyTransformer {
+    fn is_protected(&self, origin: &ProjectedOrigin) -> bool {
+        let is_protected_table = |table: &String| self.protected_tables.contains(table);
+
+        match origin {
+            ProjectedOrigin::TableColumn(TableColumn { table, column: _ }) => {
+                is_protected_table(table)
+            }
+            ProjectedOrigin::AmbiguousTableColumn(candidates) => candidates
+                .iter()
+                .any(|candidate| is_protected_table(&candidate.table)),
+            ProjectedOrigin::Aggregate(Aggregate {
+                function: _,
+                tables,
+            }) => tables.iter().any(is_protected_table),
+            _ => false,
+        }
+    }
+
+    // Whether the statement may read one of the protected tables, tables in subqueries of
+    // expressions are only found by their name
+    fn reads_protected_table(&self, statement: &Statement) -> bool {
+        let references = table_references(statement);
+        let protected: Vec<String> = self
+            .protected_tables
+            .iter()
+            .map(|table| unqualified_table_name(table))
+            .collect();
+
+        let referenced = references
+            .tables
+            .iter()
+            .any(|table| protected.contains(&unqualified_table_name(table)));
+        if referenced || references.complete {
+            return referenced;
+        }
+
+        let statement = statement.to_string().to_lowercase();
+        protected.iter().any(|table| statement.contains(table))
+    }
+
+    /// How each column of the result is perturbed, none if it doesn't touch protected tables
+    fn perturbations(
+        &self,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Option<Vec<Perturbation>>, TransformerError> {
+        if !origins.iter().any(|origin| self.is_protected(origin)) {
+            return Ok(None);
+        }
+
+        let aggregates: Vec<&Aggregate> = origins
+            .iter()
+            .filter(|origin| self.is_protected(origin))
+            .filter_map(|origin| match origin {
+                ProjectedOrigin::Aggregate(aggregate) => Some(aggregate),
+                _ => None,
+            })
+            .collect();
+
+        if aggregates.is_empty() {
+            return Err(anyhow::anyhow!(
+                "protected tables can only be queried with aggregates like count(*)"
+            )
+            .into());
+        }
+
+        let perturbations = origins
+            .iter()
+            .map(|origin| match origin {
+                ProjectedOrigin::Aggregate(aggregate) if self.is_protected(origin) => {
+                    match (aggregate.function.as_str(), self.sum_sensitivity) {
+                        ("count", _) => Ok(Perturbation::Count),
+                        ("sum", Some(sensitivity)) => Ok(Perturbation::Sum(sensitivity)),
+                        (function, _) => Err(anyhow::anyhow!(
+                            "{}() isn't supported for protected tables",
+                            function
+                        )),
+                    }
+                }
+                // Group keys are released as they are, the threshold suppresses rare ones
+                _ => Ok(Perturbation::Unchanged),
+            })
+            .collect::<Result<Vec<Perturbation>, anyhow::Error>>()?;
+
+        // Without a threshold every group key would be released, e.g. every value of an identifier
+        if self.threshold <= 0.0 && perturbations.contains(&Perturbation::Unchanged) {
+            return Err(anyhow::anyhow!(
+                "protected tables can only be grouped with a threshold suppressing small groups"
+            )
+            .into());
+        }
+
+        if self.threshold > 0.0 && !perturbations.contains(&Perturbation::Count) {
+            return Err(anyhow::anyhow!(
+                "aggregates of protected tables have to include count(*) to suppress small groups"
+            )
+            .into());
+        }
+
+        Ok(Some(perturbations))
+    }
+
+    fn perturb(
+        &self,
+        data: &RecordBatch,
+        perturbations: &[Perturbation],
+    ) -> Result<RecordBatch, TransformerError> {
+        let noisy_columns = perturbations
+            .iter()
+            .filter(|perturbation| **perturbation != Perturbation::Unchanged)
+            .count();
+        // Sequential composition, the epsilons of the noisy columns add up to the one of the query
+        let column_epsilon = self.epsilon / noisy_columns as f64;
+
+        let mut rng = rand::thread_rng();
+        let mut group_counts: Option<ArrayRef> = None;
+
+        let columns = data
+            .columns()
+            .iter()
+            .zip(perturbations)
+            .map(|(column, perturbation)| match perturbation {
+                Perturbation::Unchanged => Ok(column.clone()),
+                Perturbation::Count => {
+                    let noisy = add_laplace_noise(column, 1.0 / column_epsilon, true, &mut rng)?;
+                    group_counts.get_or_insert_with(|| noisy.clone());
+                    Ok(noisy)
+                }
+                Perturbation::Sum(sensitivity) => {
+                    add_laplace_noise(column, sensitivity / column_epsilon, false, &mut rng)
+                }
+            })
+            .collect::<Result<Vec<ArrayRef>, TransformerError>>()?;
+
+        let noisy = RecordBatch::try_new(data.schema(), columns)?;
+
+        match group_counts {
+            Some(counts) if self.threshold > 0.0 => {
+                let keep = to_f64(&counts)?
+                    .iter()
+                    .map(|count| Some(count.map_or(false, |count| count >= self.threshold)))
+                    .collect::<BooleanArray>();
+
+                Ok(filter_record_batch(&noisy, &keep)?)
+            }
+            _ => Ok(noisy),
+        }
+    }
+}
+
+// Samples the Laplace distribution centered at zero by inverting its cumulative distribution.
+// The uniform sample has to be within the open interval, -0.5 would take the logarithm of zero.
+fn laplace<R: Rng>(scale: f64, rng: &mut R) -> f64 {
+    let uniform = loop {
+        let uniform: f64 = rng.gen_range(-0.5..0.5);
+        if uniform.abs() < 0.5 {
+            break uniform;
+        }
+    };
+
+    -scale * uniform.signum() * (1.0 - 2.0 * uniform.abs()).ln()
+}
+
+fn add_laplace_noise<R: Rng>(
+    array: &ArrayRef,
+    scale: f64,
+    non_negative: bool,
+    rng: &mut R,
+) -> Result<ArrayRef, TransformerError> {
+    let mut noisy = |value: f64| {
+        let value = value + laplace(scale, rng);
+        if non_negative {
+            value.max(0.0)
+        } else {
+            value
+        }
+    };
+
+    let result: ArrayRef = match array.data_type() {
+        DataType::Int64 => Arc::new(
+            downcast::<Int64Array>(array)?
+                .iter()
+                .map(|value| value.map(|value| noisy(value as f64).round() as i64))
+                .collect::<Int64Array>(),
+        ),
+        DataType::Int32 => Arc::new(
+            downcast::<Int32Array>(array)?
+                .iter()
+                .map(|value| value.map(|value| noisy(value as f64).round() as i32))
+                .collect::<Int32Array>(),
+        ),
+        DataType::Float64 => Arc::new(
+            downcast::<Float64Array>(array)?
+                .iter()
+                .map(|value| value.map(&mut noisy))
+                .collect::<Float64Array>(),
+        ),
+        DataType::Float32 => Arc::new(
+            downcast::<Float32Array>(array)?
+                .iter()
+                .map(|value| value.map(|value| noisy(value as f64) as f32))
+                .collect::<Float32Array>(),
+        ),
+        data_type => {
+            return Err(TransformerError::Unsupported(format!(
+                "can't add noise to values of type {:?}",
+                data_type
+            )))
+        }
+    };
+
+    Ok(result)
+}
+
+fn to_f64(array: &ArrayRef) -> Result<Vec<Option<f64>>, TransformerError> {
+    Ok(match array.data_type() {
+        DataType::Int64 => downcast::<Int64Array>(array)?
+            .iter()
+            .map(|value| value.map(|value| value as f64))
+            .collect(),
+        DataType::Int32 => downcast::<Int32Array>(array)?
+            .iter()
+            .map(|value| value.map(|value| value as f64))
+            .collect(),
+        DataType::Float64 => downcast::<Float64Array>(array)?.iter().collect(),
+        DataType::Float32 => downcast::<Float32Array>(array)?
+            .iter()
+            .map(|value| value.map(|value| value as f64))
+            .collect(),
+        data_type => {
+            return Err(TransformerError::Unsupported(format!(
+                "counts of type {:?} aren't supported",
+                data_type
+            )))
+        }
+    })
+}
+
+fn downcast<T: 'static>(array: &ArrayRef) -> Result<&T, TransformerError> {
+    array
+        .as_any()
+        .downcast_ref::<T>()
+        .ok_or_else(|| anyhow::anyhow!("unexpected array of type {:?}", array.data_type()).into())
+}
+
+// Clamps the values of the sums of the query and its subqueries to the sensitivity
+fn clamp_query_sums(query: &mut Query, sensitivity: f64) -> Result<bool, TransformerError> {
+    let mut clamped = false;
+
+    if let Some(with) = &mut query.with {
+        for cte in &mut with.cte_tables {
+            clamped |= clamp_query_sums(&mut cte.query, sensitivity)?;
+        }
+    }
+
+    Ok(clamp_set_expr_sums(&mut query.body, sensitivity)? | clamped)
+}
+
+fn clamp_set_expr_sums(body: &mut SetExpr, sensitivity: f64) -> Result<bool, TransformerError> {
+    match body {
+        SetExpr::Select(select) => {
+            let mut clamped = false;
+
+            for item in &mut select.projection {
+                if let SelectItem::UnnamedExpr(expr) | SelectItem::ExprWithAlias { expr, .. } = item
+                {
+                    clamped |= clamp_expr_sums(expr, sensitivity)?;
+                }
+            }
+
+            // Groups can be filtered by their sums as well
+            if let Some(having) = &mut select.having {
+                clamped |= clamp_expr_sums(having, sensitivity)?;
+            }
+
+            for table in &mut select.from {
+                clamped |= clamp_table_sums(table, sensitivity)?;
+            }
+
+            Ok(clamped)
+        }
+        SetExpr::Query(query) => clamp_query_sums(query, sensitivity),
+        SetExpr::SetOperation { left, right, .. } => {
+            Ok(clamp_set_expr_sums(left, sensitivity)? | clamp_set_expr_sums(right, sensitivity)?)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn clamp_table_sums(
+    table: &mut TableWithJoins,
+    sensitivity: f64,
+) -> Result<bool, TransformerError> {
+    let mut clamped = false;
+
+    let factors = std::iter::once(&mut table.relation)
+        .chain(table.joins.iter_mut().map(|join| &mut join.relation));
+    for factor in factors {
+        clamped |= match factor {
+            TableFactor::Derived { subquery, .. } => clamp_query_sums(subquery, sensitivity)?,
+            TableFactor::NestedJoin(table) => clamp_table_sums(table, sensitivity)?,
+            _ => false,
+        };
+    }
+
+    Ok(clamped)
+}
+
+fn clamp_expr_sums(expr: &mut Expr, sensitivity: f64) -> Result<bool, TransformerError> {
+    match expr {
+        Expr::Function(function) if function.name.to_string().eq_ignore_ascii_case("sum") => {
+            let clamped = clamped_sum(function, sensitivity)?;
+            *expr = clamped;
+            Ok(true)
+        }
+        Expr::Nested(expr) | Expr::UnaryOp { expr, .. } | Expr::Cast { expr, .. } => {
+            clamp_expr_sums(expr, sensitivity)
+        }
+        Expr::BinaryOp { left, right, .. } => {
+            Ok(clamp_expr_sums(left, sensitivity)? | clamp_expr_sums(right, sensitivity)?)
+        }
+        _ => Ok(false),
+    }
+}
+
+// `sum(x)` as `sum(least(greatest(x, -sensitivity), sensitivity))`, whole sensitivities are
+// written as integers so that sums of integers keep their type
+fn clamped_sum(function: &Function, sensitivity: f64) -> Result<Expr, TransformerError> {
+    let args = function
+        .args
+        .iter()
+        .map(|arg| arg.to_string())
+        .collect::<Vec<String>>()
+        .join(", ");
+    let query = format!(
+        "SELECT sum(least(greatest({}, -{sensitivity}), {sensitivity}))",
+        args,
+        sensitivity = sensitivity.abs()
+    );
+
+    let clamped = match Parser::parse_sql(&PostgreSqlDialect {}, &query)
+        .map_err(|err| anyhow::anyhow!("couldn't clamp the sum: {}", err))?
+        .pop()
+    {
+        Some(Statement::Query(query)) => match query.body {
+            SetExpr::Select(select) => select.projection.into_iter().next(),
+            _ => None,
+        },
+        _ => None,
+    };
+
+    match clamped {
+        Some(SelectItem::UnnamedExpr(Expr::Function(clamped))) => Ok(Expr::Function(Function {
+            distinct: function.distinct,
+            over: function.over.clone(),
+            ..clamped
+        })),
+        _ => Err(anyhow::anyhow!("couldn't clamp the sum {}", function).into()),
+    }
+}
+
+impl Transformer for DifferentialPrivacyTransformer {
+    /// Clamps the values of the sums of queries reading protected tables, so a single row
+    /// can't change a sum by more than the noise accounts for
+    fn rewrite_query(&self, query: &str) -> Result<Option<String>, TransformerError> {
+        let sensitivity = match self.sum_sensitivity {
+            Some(sensitivity) => sensitivity,
+            None => return Ok(None),
+        };
+
+        // Queries which can't be parsed can't be traced to their aggregates either
+        let mut statements = match Parser::parse_sql(&PostgreSqlDialect {}, query) {
+            Ok(statements) => statements,
+            Err(_) => return Ok(None),
+        };
+
+        let mut clamped = false;
+        for statement in &mut statements {
+            if !self.reads_protected_table(statement) {
+                continue;
+            }
+
+            if let Statement::Query(query) = statement {
+                clamped |= clamp_query_sums(query, sensitivity)?;
+            }
+        }
+
+        if !clamped {
+            return Ok(None);
+        }
+
+        Ok(Some(
+            statements
+                .iter()
+                .map(|statement| statement.to_string())
+                .collect::<Vec<String>>()
+                .join("; "),
+        ))
+    }
+
+    fn transform_schema(
+        &self,
+        schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Schema, TransformerError> {
+        // Rejects unsupported queries before their rows are read
+        self.perturbations(origins)?;
+
+        Ok(schema.clone())
+    }
+
+    fn transform_records(
+        &self,
+        data: &RecordBatch,
+        origins: &[ProjectedOrigin],
+    ) -> Result<RecordBatch, TransformerError> {
+        let mut batches = self.transform_batches(&[data.clone()], origins)?;
+        Ok(batches.remove(0))
+    }
+
+    fn transform_batches(
+        &self,
+        data: &[RecordBatch],
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<RecordBatch>, TransformerError> {
+        let perturbations = match self.perturbations(origins)? {
+            Some(perturbations) => perturbations,
+            None => return Ok(data.to_vec()),
+        };
+
+        // The whole result is released at once, so it is paid for once
+        if let Some(UserBudget { user, ledger }) = &self.budget {
+            ledger.consume(user, self.epsilon)?;
+        }
+
+        data.iter()
+            .map(|batch| self.perturb(batch, &perturbations))
+            .collect()
+    }
+
+    fn explain_schema(
+        &self,
+        schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        let perturbations = match self.perturbations(origins)? {
+            Some(perturbations) => perturbations,
+            None => return Ok(vec![None; schema.fields().len()]),
+        };
+
+        Ok(perturbations
+            .iter()
+            .map(|perturbation| match perturbation {
+                Perturbation::Unchanged => None,
+                Perturbation::Count if self.threshold > 0.0 => Some(format!(
+                    "Laplace noise added (epsilon {}), groups below {} suppressed",
+                    self.epsilon, self.threshold
+                )),
+                Perturbation::Count | Perturbation::Sum(_) => {
+                    Some(format!("Laplace noise added (epsilon {})", self.epsilon))
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Array, StringArray},
+        datatypes::{DataType, Field},
+    };
+    use rand::rngs::mock::StepRng;
+
+    #[test]
+    fn test_laplace_bounds() {
+        // The first sample is the lower bound -0.5, it is drawn again
+        let mut rng = StepRng::new(0, 1 << 63);
+        assert!(laplace(1.0, &mut rng).is_finite());
+    }
+
+    #[test]
+    fn test_clamps_sums() {
+        let transformer = DifferentialPrivacyTransformer {
+            sum_sensitivity: Some(1000.0),
+            ..transformer(10.0)
+        };
+
+        let rewritten = transformer
+            .rewrite_query("SELECT city, count(*), sum(donation) FROM contacts GROUP BY city")
+            .unwrap()
+            .unwrap();
+        assert!(rewritten.contains("sum(least(greatest(donation, "));
+        assert!(rewritten.contains("1000), 1000)) FROM contacts"));
+
+        let rewritten = transformer
+            .rewrite_query(
+                "SELECT count(*) FROM contacts GROUP BY city HAVING sum(c.donation) > 100",
+            )
+            .unwrap()
+            .unwrap();
+        assert!(rewritten.contains("HAVING sum(least(greatest(c.donation, "));
+
+        assert_eq!(
+            None,
+            transformer
+                .rewrite_query("SELECT sum(amount) FROM orders")
+                .unwrap()
+        );
+    }
+
+    fn transformer(threshold: f64) -> DifferentialPrivacyTransformer {
+        DifferentialPrivacyTransformer {
+            protected_tables: vec!["contacts".to_string()],
+            // Practically no noise, so that the results are predictable
+            epsilon: 1e9,
+            threshold,
+            sum_sensitivity: None,
+            budget: None,
+        }
+    }
+
+    fn grouped_counts() -> (RecordBatch, Vec<ProjectedOrigin>) {
+        let schema = Schema::new(vec![
+            Field::new("city", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, false),
+        ]);
+
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from(vec!["Berlin", "Hamburg", "Bremen"])),
+                Arc::new(Int64Array::from(vec![120, 45, 2])),
+            ],
+        )
+        .unwrap();
+
+        let origins = vec![
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: "contacts".to_string(),
+                column: "city".to_string(),
+            }),
+            ProjectedOrigin::Aggregate(Aggregate {
+                function: "count".to_string(),
+                tables: vec!["contacts".to_string()],
+            }),
+        ];
+
+        (batch, origins)
+    }
+
+    #[test]
+    fn test_suppresses_small_groups() {
+        let (batch, origins) = grouped_counts();
+
+        let result = transformer(10.0)
+            .transform_batches(&[batch], &origins)
+            .unwrap();
+
+        let cities = result[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(2, cities.len());
+        assert_eq!("Berlin", cities.value(0));
+        assert_eq!("Hamburg", cities.value(1));
+
+        let counts = result[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(120, counts.value(0));
+        assert_eq!(45, counts.value(1));
+    }
+
+    #[test]
+    fn test_rejects_rows_of_protected_tables() {
+        let origins = vec![ProjectedOrigin::TableColumn(TableColumn {
+            table: "contacts".to_string(),
+            column: "city".to_string(),
+        })];
+        let schema = Schema::new(vec![Field::new("city", DataType::Utf8, false)]);
+
+        assert!(transformer(0.0)
+            .transform_schema(&schema, &origins)
+            .is_err());
+
+        let other_table = vec![ProjectedOrigin::TableColumn(TableColumn {
+            table: "cities".to_string(),
+            column: "name".to_string(),
+        })];
+        assert!(transformer(0.0)
+            .transform_schema(&schema, &other_table)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_rejects_groups_without_threshold() {
+        let (batch, origins) = grouped_counts();
+
+        assert!(transformer(0.0)
+            .transform_batches(&[batch], &origins)
+            .is_err());
+    }
+
+    #[test]
+    fn test_consumes_budget() {
+        let (batch, origins) = grouped_counts();
+        let ledger = Arc::new(PrivacyBudgetLedger::new(1.0));
+
+        let transformer = DifferentialPrivacyTransformer {
+            epsilon: 0.6,
+            budget: Some(UserBudget {
+                user: "analyst".to_string(),
+                ledger: ledger.clone(),
+            }),
+            ..transformer(1.0)
+        };
+
+        assert!(transformer
+            .transform_batches(&[batch.clone()], &origins)
+            .is_ok());
+        assert!((ledger.remaining("analyst") - 0.4).abs() < 1e-9);
+
+        assert!(transformer.transform_batches(&[batch], &origins).is_err());
+    }
+}
@@ -0,0 +1,414 @@
+use crate::concat::concat_record_batches;
+use arrow::{
+    array::{
+        new_null_array, ArrayRef, BooleanArray, Float32Array, Float64Array, Int32Array, Int64Array,
+        StringArray,
+    },
+    compute::{concat, filter_record_batch},
+    datatypes::{DataType, Schema},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use proboscis_resolver_transformer::{
+    projection::{Aggregate, ProjectedOrigin},
+    Transformer, TransformerError,
+};
+use std::{collections::HashMap, sync::Arc};
+
+/// The value of the group keys of the group merging all small groups
+pub const OTHER_GROUP: &str = "other";
+
+/// What happens to groups with fewer rows than the minimum
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SmallGroups {
+    Drop,
+    /// Merged into a single group with `other` as its string keys, which is
+    /// dropped as well if it is still too small
+    Merge,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MinGroupSize {
+    pub size: usize,
+    pub small_groups: SmallGroups,
+}
+
+/// Suppresses the groups of aggregate results over the tables, e.g. of
+/// `SELECT city, count(*) FROM contacts GROUP BY city`, which summarize fewer rows than
+/// the minimum of the table. The sizes of the groups are taken from the `count(*)` column,
+/// aggregates without one are rejected.
+pub struct GroupSizeTransformer {
+    pub tables: HashMap<String, MinGroupSize>,
+}
+
+impl GroupSizeTransformer {
+    /// The strictest minimum of the tables the aggregates of the result summarize
+    fn min_group_size(&self, origins: &[ProjectedOrigin]) -> Option<MinGroupSize> {
+        origins
+            .iter()
+            .filter_map(|origin| match origin {
+                ProjectedOrigin::Aggregate(Aggregate {
+                    function: _,
+                    tables,
+                }) => Some(tables),
+                _ => None,
+            })
+            .flatten()
+            .filter_map(|table| self.tables.get(table))
+            .fold(None, |strictest: Option<MinGroupSize>, min| {
+                Some(match strictest {
+                    None => *min,
+                    Some(strictest) => MinGroupSize {
+                        size: strictest.size.max(min.size),
+                        small_groups: match (strictest.small_groups, min.small_groups) {
+                            (SmallGroups::Merge, SmallGroups::Merge) => SmallGroups::Merge,
+                            _ => SmallGroups::Drop,
+                        },
+                    },
+                })
+            })
+    }
+
+    fn count_column(origins: &[ProjectedOrigin]) -> Result<usize, TransformerError> {
+        origins
+            .iter()
+            .position(|origin| match origin {
+                ProjectedOrigin::Aggregate(Aggregate {
+                    function,
+                    tables: _,
+                }) => function == "count",
+                _ => false,
+            })
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "aggregates of the tables have to include count(*) to enforce their minimum group size"
+                )
+                .into()
+            })
+    }
+}
+
+fn numeric_values(array: &ArrayRef) -> Option<Vec<Option<f64>>> {
+    let as_any = array.as_any();
+
+    match array.data_type() {
+        DataType::Int64 => as_any
+            .downcast_ref::<Int64Array>()
+            .map(|array| array.iter().map(|v| v.map(|v| v as f64)).collect()),
+        DataType::Int32 => as_any
+            .downcast_ref::<Int32Array>()
+            .map(|array| array.iter().map(|v| v.map(|v| v as f64)).collect()),
+        DataType::Float64 => as_any
+            .downcast_ref::<Float64Array>()
+            .map(|array| array.iter().collect()),
+        DataType::Float32 => as_any
+            .downcast_ref::<Float32Array>()
+            .map(|array| array.iter().map(|v| v.map(|v| v as f64)).collect()),
+        _ => None,
+    }
+}
+
+/// A single value of the type, null if the type isn't numeric
+fn numeric_value(data_type: &DataType, value: Option<f64>) -> ArrayRef {
+    match data_type {
+        DataType::Int64 => Arc::new(Int64Array::from(vec![value.map(|v| v.round() as i64)])),
+        DataType::Int32 => Arc::new(Int32Array::from(vec![value.map(|v| v.round() as i32)])),
+        DataType::Float64 => Arc::new(Float64Array::from(vec![value])),
+        DataType::Float32 => Arc::new(Float32Array::from(vec![value.map(|v| v as f32)])),
+        data_type => new_null_array(data_type, 1),
+    }
+}
+
+/// Merges the rows of the groups into a single row, combining each aggregate
+/// with the function it was computed with
+fn merge_groups(
+    groups: &RecordBatch,
+    origins: &[ProjectedOrigin],
+    counts: &[f64],
+) -> Result<RecordBatch, ArrowError> {
+    let total_count: f64 = counts.iter().sum();
+
+    let columns = groups
+        .columns()
+        .iter()
+        .zip(origins)
+        .map(|(column, origin)| {
+            let data_type = column.data_type();
+            let function = match origin {
+                ProjectedOrigin::Aggregate(Aggregate {
+                    function,
+                    tables: _,
+                }) => function.as_str(),
+                _ => {
+                    return match data_type {
+                        DataType::Utf8 => {
+                            Arc::new(StringArray::from(vec![OTHER_GROUP])) as ArrayRef
+                        }
+                        data_type => new_null_array(data_type, 1),
+                    }
+                }
+            };
+
+            let values = match numeric_values(column) {
+                Some(values) => values,
+                None => return new_null_array(data_type, 1),
+            };
+            let present = values.iter().flatten().cloned();
+
+            let merged = match function {
+                "count" | "sum" => Some(present.sum()),
+                "min" => present.fold(None, |min: Option<f64>, v| {
+                    Some(min.map_or(v, |m| m.min(v)))
+                }),
+                "max" => present.fold(None, |max: Option<f64>, v| {
+                    Some(max.map_or(v, |m| m.max(v)))
+                }),
+                // Weighted by the number of rows of each group
+                "avg" if total_count > 0.0 => Some(
+                    values
+                        .iter()
+                        .zip(counts)
+                        .filter_map(|(value, count)| value.map(|value| value * count))
+                        .sum::<f64>()
+                        / total_count,
+                ),
+                _ => None,
+            };
+
+            numeric_value(data_type, merged)
+        })
+        .collect();
+
+    RecordBatch::try_new(groups.schema(), columns)
+}
+
+impl Transformer for GroupSizeTransformer {
+    fn transform_schema(
+        &self,
+        schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Schema, TransformerError> {
+        // Rejects aggregates without counts before their rows are read
+        if self.min_group_size(origins).is_some() {
+            Self::count_column(origins)?;
+        }
+
+        Ok(schema.clone())
+    }
+
+    fn transform_records(
+        &self,
+        data: &RecordBatch,
+        origins: &[ProjectedOrigin],
+    ) -> Result<RecordBatch, TransformerError> {
+        let min = match self.min_group_size(origins) {
+            Some(min) => min,
+            None => return Ok(data.clone()),
+        };
+
+        let count_column = data.column(Self::count_column(origins)?);
+        let counts: Vec<f64> = numeric_values(count_column)
+            .ok_or_else(|| {
+                anyhow::anyhow!(
+                    "counts of type {:?} aren't supported",
+                    count_column.data_type()
+                )
+            })?
+            .into_iter()
+            .map(|count| count.unwrap_or(0.0))
+            .collect();
+
+        let is_large = |count: &f64| *count >= min.size as f64;
+        let large_groups = filter_record_batch(
+            data,
+            &counts
+                .iter()
+                .map(|count| Some(is_large(count)))
+                .collect::<BooleanArray>(),
+        )?;
+
+        if min.small_groups == SmallGroups::Drop || counts.iter().all(is_large) {
+            return Ok(large_groups);
+        }
+
+        let small_groups = filter_record_batch(
+            data,
+            &counts
+                .iter()
+                .map(|count| Some(!is_large(count)))
+                .collect::<BooleanArray>(),
+        )?;
+        let small_counts: Vec<f64> = counts.iter().cloned().filter(|c| !is_large(c)).collect();
+
+        if !is_large(&small_counts.iter().sum()) {
+            return Ok(large_groups);
+        }
+
+        let merged = merge_groups(&small_groups, origins, &small_counts)?;
+        let columns = large_groups
+            .columns()
+            .iter()
+            .zip(merged.columns())
+            .map(|(large, merged)| concat(&[large.as_ref(), merged.as_ref()]))
+            .collect::<Result<Vec<ArrayRef>, ArrowError>>()?;
+
+        Ok(RecordBatch::try_new(data.schema(), columns)?)
+    }
+
+    fn transform_batches(
+        &self,
+        data: &[RecordBatch],
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<RecordBatch>, TransformerError> {
+        if data.len() <= 1 || self.min_group_size(origins).is_none() {
+            return data
+                .iter()
+                .map(|batch| self.transform_records(batch, origins))
+                .collect();
+        }
+
+        // Small groups of every batch are merged into the same group
+        let combined = concat_record_batches(data)?;
+
+        Ok(vec![self.transform_records(&combined, origins)?])
+    }
+
+    fn explain_schema(
+        &self,
+        schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        let min = match self.min_group_size(origins) {
+            Some(min) => min,
+            None => return Ok(vec![None; schema.fields().len()]),
+        };
+        let count_column = Self::count_column(origins)?;
+
+        Ok((0..schema.fields().len())
+            .map(|index| {
+                if index != count_column {
+                    None
+                } else if min.small_groups == SmallGroups::Merge {
+                    Some(format!(
+                        "groups below {} rows merged into '{}'",
+                        min.size, OTHER_GROUP
+                    ))
+                } else {
+                    Some(format!("groups below {} rows dropped", min.size))
+                }
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::Array,
+        datatypes::{DataType, Field},
+    };
+    use proboscis_resolver_transformer::projection::TableColumn;
+
+    fn aggregate(function: &str) -> ProjectedOrigin {
+        ProjectedOrigin::Aggregate(Aggregate {
+            function: function.to_string(),
+            tables: vec!["contacts".to_string()],
+        })
+    }
+
+    fn transformer(small_groups: SmallGroups) -> GroupSizeTransformer {
+        GroupSizeTransformer {
+            tables: vec![(
+                "contacts".to_string(),
+                MinGroupSize {
+                    size: 10,
+                    small_groups,
+                },
+            )]
+            .into_iter()
+            .collect(),
+        }
+    }
+
+    fn grouped_result() -> (RecordBatch, Vec<ProjectedOrigin>) {
+        let schema = Schema::new(vec![
+            Field::new("city", DataType::Utf8, false),
+            Field::new("count", DataType::Int64, false),
+            Field::new("max", DataType::Int32, true),
+        ]);
+
+        let batch = RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(StringArray::from(vec!["Berlin", "Bremen", "Kiel", "Ulm"])),
+                Arc::new(Int64Array::from(vec![120, 4, 7, 3])),
+                Arc::new(Int32Array::from(vec![Some(90), Some(60), None, Some(75)])),
+            ],
+        )
+        .unwrap();
+
+        let origins = vec![
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: "contacts".to_string(),
+                column: "city".to_string(),
+            }),
+            aggregate("count"),
+            aggregate("max"),
+        ];
+
+        (batch, origins)
+    }
+
+    #[test]
+    fn test_drops_small_groups() {
+        let (batch, origins) = grouped_result();
+
+        let result = transformer(SmallGroups::Drop)
+            .transform_records(&batch, &origins)
+            .unwrap();
+
+        assert_eq!(1, result.num_rows());
+    }
+
+    #[test]
+    fn test_merges_small_groups() {
+        let (batch, origins) = grouped_result();
+
+        let result = transformer(SmallGroups::Merge)
+            .transform_records(&batch, &origins)
+            .unwrap();
+
+        let cities = result
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(2, cities.len());
+        assert_eq!("Berlin", cities.value(0));
+        assert_eq!(OTHER_GROUP, cities.value(1));
+
+        let counts = result
+            .column(1)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(14, counts.value(1));
+
+        let maxima = result
+            .column(2)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        assert_eq!(75, maxima.value(1));
+    }
+
+    #[test]
+    fn test_requires_count() {
+        let schema = Schema::new(vec![Field::new("max", DataType::Int32, true)]);
+
+        assert!(transformer(SmallGroups::Drop)
+            .transform_schema(&schema, &[aggregate("max")])
+            .is_err());
+    }
+}
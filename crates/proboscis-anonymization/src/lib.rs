@@ -1,9 +1,50 @@
+#[cfg(feature = "k-anonymity")]
 mod algorithm;
+mod budget;
+#[cfg(feature = "k-anonymity")]
 mod column_transformations;
+mod concat;
+mod concurrency;
+#[cfg(feature = "k-anonymity")]
 mod conversion;
+mod differential_privacy;
+mod group_size;
+#[cfg(feature = "k-anonymity")]
+mod masking;
+mod origin;
+#[cfg(feature = "k-anonymity")]
+mod population;
+mod pseudonymization;
+mod sampling;
+#[cfg(feature = "scripting")]
+mod scripting;
+#[cfg(feature = "k-anonymity")]
 mod transformer;
 
+#[cfg(feature = "k-anonymity")]
 pub use algorithm::AnonymizationCriteria;
+#[cfg(feature = "k-anonymity")]
 pub use algorithm::NumericAggregation;
+#[cfg(feature = "k-anonymity")]
 pub use algorithm::StringAggregation;
+pub use budget::BudgetError;
+pub use budget::PrivacyBudgetLedger;
+#[cfg(feature = "k-anonymity")]
+pub use column_transformations::{
+    ColumnTransformation, ColumnTransformationError, ColumnTransformationOutput,
+};
+#[cfg(feature = "wasm")]
+pub use column_transformations::{WasmLimits, WasmTransformation};
+pub use concurrency::ColumnConcurrency;
+pub use differential_privacy::{DifferentialPrivacyTransformer, UserBudget};
+pub use group_size::{GroupSizeTransformer, MinGroupSize, SmallGroups};
+#[cfg(feature = "k-anonymity")]
+pub use masking::{MaskedColumns, MaskingTransformer};
+#[cfg(feature = "k-anonymity")]
+pub use population::Population;
+pub use pseudonymization::{PseudonymDomain, PseudonymizationTransformer};
+pub use sampling::RowSamplingTransformer;
+#[cfg(feature = "scripting")]
+pub use scripting::{ScriptError, ScriptTransformation, ScriptedRowFilter};
+#[cfg(feature = "k-anonymity")]
 pub use transformer::AnonymizationTransformer;
@@ -0,0 +1,154 @@
+use crate::{
+    column_transformations::ColumnTransformation, concurrency::ColumnConcurrency,
+    origin::normalized_column_names,
+};
+use arrow::{
+    array::ArrayRef,
+    datatypes::{Field, Schema},
+    record_batch::RecordBatch,
+};
+use proboscis_resolver_transformer::{projection::ProjectedOrigin, Transformer, TransformerError};
+use std::sync::Arc;
+
+/// Columns whose values are replaced by the transformation, e.g. a custom one
+/// loaded from a wasm module
+#[derive(Clone)]
+pub struct MaskedColumns {
+    /// Qualified names of the columns
+    pub columns: Vec<String>,
+    pub transformation: Arc<dyn ColumnTransformation>,
+}
+
+/// Applies the transformation of the masked columns to each column of a result
+/// originating from one of them, the first matching one if there are several
+pub struct MaskingTransformer {
+    pub masks: Vec<MaskedColumns>,
+    pub concurrency: ColumnConcurrency,
+}
+
+impl MaskingTransformer {
+    fn column_masks(&self, origins: &[ProjectedOrigin]) -> Vec<Option<&MaskedColumns>> {
+        origins
+            .iter()
+            .map(|origin| {
+                let names = normalized_column_names(origin);
+
+                self.masks
+                    .iter()
+                    .find(|mask| names.iter().any(|name| mask.columns.contains(name)))
+            })
+            .collect()
+    }
+}
+
+impl Transformer for MaskingTransformer {
+    fn transform_schema(
+        &self,
+        schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Schema, TransformerError> {
+        let fields = schema
+            .fields()
+            .iter()
+            .zip(self.column_masks(origins))
+            .map(|(field, mask)| match mask {
+                Some(mask) => {
+                    let output = mask.transformation.output_format(field.data_type())?;
+                    Ok(Field::new(field.name(), output.data_type, output.nullable))
+                }
+                None => Ok(field.clone()),
+            })
+            .collect::<Result<Vec<Field>, TransformerError>>()?;
+
+        Ok(Schema::new(fields))
+    }
+
+    fn transform_records(
+        &self,
+        data: &RecordBatch,
+        origins: &[ProjectedOrigin],
+    ) -> Result<RecordBatch, TransformerError> {
+        let masks = self.column_masks(origins);
+
+        if masks.iter().all(Option::is_none) {
+            return Ok(data.clone());
+        }
+
+        let columns: Vec<(&ArrayRef, Option<&MaskedColumns>)> =
+            data.columns().iter().zip(masks).collect();
+
+        let columns = self
+            .concurrency
+            .try_map(&columns, |(column, mask)| match mask {
+                Some(mask) => Ok(mask.transformation.transform_data((*column).clone())?),
+                None => Ok::<ArrayRef, TransformerError>((*column).clone()),
+            })?;
+
+        let schema = self.transform_schema(&data.schema(), origins)?;
+        Ok(RecordBatch::try_new(Arc::new(schema), columns)?)
+    }
+
+    fn explain_schema(
+        &self,
+        _schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        Ok(self
+            .column_masks(origins)
+            .into_iter()
+            .map(|mask| mask.map(|_| "masked".to_string()))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::column_transformations::Randomize;
+    use arrow::{array::StringArray, datatypes::DataType};
+    use proboscis_resolver_transformer::projection::TableColumn;
+
+    #[test]
+    fn test_masking_transformer() {
+        let transformer = MaskingTransformer {
+            masks: vec![MaskedColumns {
+                columns: vec!["contacts.email".to_string()],
+                transformation: Arc::new(Randomize),
+            }],
+            concurrency: ColumnConcurrency::sequential(),
+        };
+
+        let origins: Vec<ProjectedOrigin> = ["email", "name"]
+            .iter()
+            .map(|column| {
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: "contacts".to_string(),
+                    column: column.to_string(),
+                })
+            })
+            .collect();
+
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("email", DataType::Utf8, false),
+                Field::new("name", DataType::Utf8, false),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec!["alex@example.com"])),
+                Arc::new(StringArray::from(vec!["Alex"])),
+            ],
+        )
+        .unwrap();
+
+        let masked = transformer.transform_records(&batch, &origins).unwrap();
+        assert_ne!(batch.column(0).data(), masked.column(0).data());
+        assert_eq!(batch.column(1).data(), masked.column(1).data());
+
+        assert_eq!(
+            vec![Some("masked".to_string()), None],
+            transformer
+                .explain_schema(&batch.schema(), &origins)
+                .unwrap()
+        );
+    }
+}
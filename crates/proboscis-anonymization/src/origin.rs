@@ -0,0 +1,20 @@
+use proboscis_resolver_transformer::projection::{ProjectedOrigin, TableColumn};
+
+/// The configured names (`table.column`) a projected column could originate from.
+/// Unqualified columns of joins are attributed to every joined table they might belong
+/// to, so that the quasi-identifier set spans all tables involved in the query.
+pub(crate) fn normalized_column_names(origin: &ProjectedOrigin) -> Vec<String> {
+    match origin {
+        ProjectedOrigin::Function => vec![],
+        ProjectedOrigin::Value => vec![],
+        // Aggregates don't disclose the values of single rows
+        ProjectedOrigin::Aggregate(_) => vec![],
+        ProjectedOrigin::TableColumn(TableColumn { table, column }) => {
+            vec![format!("{}.{}", table, column)]
+        }
+        ProjectedOrigin::AmbiguousTableColumn(candidates) => candidates
+            .iter()
+            .map(|TableColumn { table, column }| format!("{}.{}", table, column))
+            .collect(),
+    }
+}
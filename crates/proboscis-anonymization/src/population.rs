@@ -0,0 +1,205 @@
+use crate::algorithm::AnonymizationError;
+use arrow::{
+    array::{ArrayRef, GenericStringArray, Int64Array},
+    compute::kernels::cast::cast,
+    datatypes::DataType,
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use polars::prelude::{DataFrame, NewChunkedArray, UInt32Chunked};
+use std::{collections::HashSet, fs::File, path::Path};
+
+/// The generalized values a partition takes for a single quasi-identifier column
+enum ColumnBounds {
+    Numeric { min: i64, max: i64 },
+    Strings(HashSet<String>),
+    Empty,
+}
+
+impl ColumnBounds {
+    fn contains_numeric(&self, value: Option<i64>) -> bool {
+        match (self, value) {
+            (Self::Numeric { min, max }, Some(value)) => *min <= value && value <= *max,
+            (Self::Empty, None) => true,
+            _ => false,
+        }
+    }
+
+    fn contains_string(&self, value: Option<&str>) -> bool {
+        match (self, value) {
+            (Self::Strings(values), Some(value)) => values.contains(value),
+            (Self::Empty, None) => true,
+            _ => false,
+        }
+    }
+}
+
+/// A reference dataset the queried data is a sample of.
+///
+/// Used by population based criteria (k-map, δ-presence) to determine how many
+/// individuals of the whole population share the generalized quasi-identifier
+/// values of a partition. Columns are matched to quasi-identifiers by name,
+/// quasi-identifiers not contained in the population are ignored.
+#[derive(Debug)]
+pub struct Population {
+    data: Vec<RecordBatch>,
+}
+
+impl Population {
+    pub fn new(data: Vec<RecordBatch>) -> Population {
+        Population { data }
+    }
+
+    /// Loads a population from a csv file with a header row, inferring the column types
+    pub fn from_csv(path: &Path) -> Result<Population, ArrowError> {
+        let file = File::open(path)?;
+
+        let reader = arrow::csv::ReaderBuilder::new()
+            .has_header(true)
+            .infer_schema(Some(1000))
+            .build(file)?;
+
+        let data = reader.collect::<Result<Vec<RecordBatch>, ArrowError>>()?;
+
+        Ok(Population { data })
+    }
+
+    pub fn len(&self) -> usize {
+        self.data.iter().map(|batch| batch.num_rows()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Counts the individuals in the population whose quasi-identifier values
+    /// fall within the values taken by the given partition
+    pub fn count_matching(
+        &self,
+        df: &DataFrame,
+        partition: &[u32],
+        quasi_identifiers: &[&str],
+    ) -> Result<usize, AnonymizationError> {
+        let mut bounds = vec![];
+        for quasi_identifier in quasi_identifiers {
+            let series = df
+                .column(quasi_identifier)?
+                .take(&UInt32Chunked::new_from_slice("idx", partition))?;
+
+            let column_bounds = match series.dtype() {
+                polars::prelude::DataType::Utf8 => {
+                    let values: HashSet<String> = series
+                        .utf8()?
+                        .into_iter()
+                        .flatten()
+                        .map(|value| value.to_string())
+                        .collect();
+
+                    if values.is_empty() {
+                        ColumnBounds::Empty
+                    } else {
+                        ColumnBounds::Strings(values)
+                    }
+                }
+                _ => match (series.min::<i64>(), series.max::<i64>()) {
+                    (Some(min), Some(max)) => ColumnBounds::Numeric { min, max },
+                    _ => ColumnBounds::Empty,
+                },
+            };
+
+            bounds.push((quasi_identifier.to_string(), column_bounds));
+        }
+
+        let mut count = 0;
+        for batch in &self.data {
+            let mut matches = vec![true; batch.num_rows()];
+
+            for (name, column_bounds) in &bounds {
+                let index = match batch.schema().index_of(name) {
+                    Ok(index) => index,
+                    Err(_) => continue,
+                };
+
+                let column: &ArrayRef = batch.column(index);
+
+                match column.data_type() {
+                    DataType::Utf8 => {
+                        let values = column
+                            .as_any()
+                            .downcast_ref::<GenericStringArray<i32>>()
+                            .ok_or_else(|| {
+                                ArrowError::CastError(format!("invalid column {}", name))
+                            })?;
+
+                        for (row, value) in values.iter().enumerate() {
+                            matches[row] &= column_bounds.contains_string(value);
+                        }
+                    }
+                    _ => {
+                        let casted = cast(column, &DataType::Int64)?;
+                        let values =
+                            casted
+                                .as_any()
+                                .downcast_ref::<Int64Array>()
+                                .ok_or_else(|| {
+                                    ArrowError::CastError(format!("invalid column {}", name))
+                                })?;
+
+                        for (row, value) in values.iter().enumerate() {
+                            matches[row] &= column_bounds.contains_numeric(value);
+                        }
+                    }
+                }
+            }
+
+            count += matches.iter().filter(|m| **m).count();
+        }
+
+        Ok(count)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::record_batch_to_data_frame;
+    use arrow::{
+        array::{Int32Array, StringArray},
+        datatypes::{Field, Schema},
+    };
+    use std::sync::Arc;
+
+    fn batch(ages: Vec<i32>, zips: Vec<&str>) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("age", DataType::Int32, false),
+            Field::new("zip", DataType::Utf8, false),
+        ]);
+
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(ages)),
+                Arc::new(StringArray::from(zips)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn count_matching_population() {
+        let population = Population::new(vec![batch(
+            vec![18, 19, 20, 35, 40],
+            vec!["10115", "10117", "10115", "10115", "80331"],
+        )]);
+
+        let sample =
+            record_batch_to_data_frame(&batch(vec![18, 20], vec!["10115", "10115"])).unwrap();
+
+        let count = population
+            .count_matching(&sample, &[0, 1], &["age", "zip"])
+            .unwrap();
+
+        // 18 & 20 from the sample, 19 lies within the range but has another zip
+        assert_eq!(2, count);
+    }
+}
@@ -0,0 +1,277 @@
+use crate::{concurrency::ColumnConcurrency, origin::normalized_column_names};
+use arrow::{
+    array::{
+        Array, ArrayRef, GenericStringArray, GenericStringBuilder, Int32Array, Int32Builder,
+        Int64Array, Int64Builder, StringOffsetSizeTrait,
+    },
+    datatypes::{DataType, Schema},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use openssl::{
+    error::ErrorStack,
+    hash::MessageDigest,
+    pkey::{PKey, Private},
+    sign::Signer,
+};
+use proboscis_resolver_transformer::{projection::ProjectedOrigin, Transformer, TransformerError};
+use std::{convert::TryInto, sync::Arc};
+
+/// Columns sharing pseudonyms, e.g. `users.id` and `posts.author`, so results
+/// pseudonymized separately can still be joined on them
+pub struct PseudonymDomain {
+    pub name: String,
+    /// Secret of the keyed hash, every domain should have its own so
+    /// pseudonyms of different domains can't be linked
+    pub key: Vec<u8>,
+    /// Qualified names of the columns, which should share their type
+    pub columns: Vec<String>,
+}
+
+/// Replaces the values of the columns of the domains with pseudonyms, which are derived
+/// from a keyed hash of the value. Equal values get the same pseudonym in every column of
+/// a domain and across queries, as long as the key doesn't change.
+pub struct PseudonymizationTransformer {
+    pub domains: Vec<PseudonymDomain>,
+    pub concurrency: ColumnConcurrency,
+}
+
+// Strings are replaced by the first bytes of the hash in hex
+const STRING_PSEUDONYM_BYTES: usize = 16;
+
+fn keyed_hash(key: &PKey<Private>, value: &str) -> Result<[u8; 32], ErrorStack> {
+    let mut signer = Signer::new(MessageDigest::sha256(), key)?;
+    signer.update(value.as_bytes())?;
+
+    let mut hash = [0; 32];
+    signer.sign(&mut hash)?;
+    Ok(hash)
+}
+
+const HEX_DIGITS: &[u8; 16] = b"0123456789abcdef";
+
+fn pseudonymize_string_array<T: StringOffsetSizeTrait>(
+    key: &PKey<Private>,
+    data: &ArrayRef,
+) -> Result<ArrayRef, TransformerError> {
+    let array = downcast::<GenericStringArray<T>>(data)?;
+
+    let mut builder = GenericStringBuilder::<T>::with_capacity(
+        array.len(),
+        array.len() * STRING_PSEUDONYM_BYTES * 2,
+    );
+    let mut pseudonym = String::with_capacity(STRING_PSEUDONYM_BYTES * 2);
+
+    for index in 0..array.len() {
+        if array.is_null(index) {
+            builder.append_null()?;
+            continue;
+        }
+
+        let hash = keyed_hash(key, array.value(index)).map_err(|err| anyhow::anyhow!(err))?;
+
+        pseudonym.clear();
+        for byte in &hash[..STRING_PSEUDONYM_BYTES] {
+            pseudonym.push(HEX_DIGITS[(byte >> 4) as usize] as char);
+            pseudonym.push(HEX_DIGITS[(byte & 0xf) as usize] as char);
+        }
+        builder.append_value(&pseudonym)?;
+    }
+
+    Ok(Arc::new(builder.finish()))
+}
+
+// Integers are hashed by their decimal representation and replaced by a positive integer
+fn integer_pseudonym(key: &PKey<Private>, value: i64) -> Result<i64, TransformerError> {
+    let hash = keyed_hash(key, &value.to_string()).map_err(|err| anyhow::anyhow!(err))?;
+    let bytes: [u8; 8] = hash[..8].try_into().unwrap();
+    Ok(i64::from_be_bytes(bytes) & i64::MAX)
+}
+
+fn downcast<T: 'static>(data: &ArrayRef) -> Result<&T, TransformerError> {
+    data.as_any().downcast_ref::<T>().ok_or_else(|| {
+        ArrowError::CastError(format!("unexpected array of type {:?}", data.data_type())).into()
+    })
+}
+
+fn pseudonymize(key: &PKey<Private>, data: &ArrayRef) -> Result<ArrayRef, TransformerError> {
+    let result: ArrayRef = match data.data_type() {
+        DataType::Utf8 => pseudonymize_string_array::<i32>(key, data)?,
+        DataType::LargeUtf8 => pseudonymize_string_array::<i64>(key, data)?,
+        DataType::Int64 => {
+            let array = downcast::<Int64Array>(data)?;
+            let mut builder = Int64Builder::new(array.len());
+            for index in 0..array.len() {
+                match array.is_null(index) {
+                    true => builder.append_null()?,
+                    false => builder.append_value(integer_pseudonym(key, array.value(index))?)?,
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        DataType::Int32 => {
+            let array = downcast::<Int32Array>(data)?;
+            let mut builder = Int32Builder::new(array.len());
+            for index in 0..array.len() {
+                match array.is_null(index) {
+                    true => builder.append_null()?,
+                    false => {
+                        let pseudonym = integer_pseudonym(key, array.value(index) as i64)?;
+                        builder.append_value((pseudonym & i32::MAX as i64) as i32)?
+                    }
+                }
+            }
+            Arc::new(builder.finish())
+        }
+        data_type => {
+            return Err(TransformerError::Unsupported(format!(
+                "values of type {:?} can't be pseudonymized",
+                data_type
+            )))
+        }
+    };
+
+    Ok(result)
+}
+
+impl PseudonymizationTransformer {
+    /// The domain of each column of the result, if it has one
+    fn column_domains(&self, origins: &[ProjectedOrigin]) -> Vec<Option<&PseudonymDomain>> {
+        origins
+            .iter()
+            .map(|origin| {
+                let names = normalized_column_names(origin);
+
+                self.domains
+                    .iter()
+                    .find(|domain| names.iter().any(|name| domain.columns.contains(name)))
+            })
+            .collect()
+    }
+}
+
+impl Transformer for PseudonymizationTransformer {
+    fn transform_schema(
+        &self,
+        schema: &Schema,
+        _origins: &[ProjectedOrigin],
+    ) -> Result<Schema, TransformerError> {
+        // Pseudonyms have the type of the values they replace
+        Ok(schema.clone())
+    }
+
+    fn transform_records(
+        &self,
+        data: &RecordBatch,
+        origins: &[ProjectedOrigin],
+    ) -> Result<RecordBatch, TransformerError> {
+        let domains = self.column_domains(origins);
+
+        if domains.iter().all(Option::is_none) {
+            return Ok(data.clone());
+        }
+
+        let columns: Vec<(&ArrayRef, Option<&PseudonymDomain>)> =
+            data.columns().iter().zip(domains).collect();
+
+        let columns = self
+            .concurrency
+            .try_map(&columns, |(column, domain)| match domain {
+                Some(domain) => {
+                    let key = PKey::hmac(&domain.key).map_err(|err| anyhow::anyhow!(err))?;
+                    pseudonymize(&key, column)
+                }
+                None => Ok((*column).clone()),
+            })?;
+
+        Ok(RecordBatch::try_new(data.schema(), columns)?)
+    }
+
+    fn explain_schema(
+        &self,
+        _schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        Ok(self
+            .column_domains(origins)
+            .into_iter()
+            .map(|domain| domain.map(|domain| format!("pseudonymized in domain {}", domain.name)))
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{array::StringArray, datatypes::Field};
+    use proboscis_resolver_transformer::projection::TableColumn;
+
+    fn origin(table: &str, column: &str) -> ProjectedOrigin {
+        ProjectedOrigin::TableColumn(TableColumn {
+            table: table.to_string(),
+            column: column.to_string(),
+        })
+    }
+
+    fn pseudonymized_ids(
+        transformer: &PseudonymizationTransformer,
+        origin: ProjectedOrigin,
+    ) -> Vec<i64> {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 1]))],
+        )
+        .unwrap();
+
+        let result = transformer.transform_records(&batch, &[origin]).unwrap();
+        let ids = result
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+
+        (0..ids.len()).map(|index| ids.value(index)).collect()
+    }
+
+    #[test]
+    fn test_domains_share_pseudonyms() {
+        let transformer = PseudonymizationTransformer {
+            domains: vec![
+                PseudonymDomain {
+                    name: "users".to_string(),
+                    key: b"users secret".to_vec(),
+                    columns: vec!["users.id".to_string(), "posts.author".to_string()],
+                },
+                PseudonymDomain {
+                    name: "posts".to_string(),
+                    key: b"posts secret".to_vec(),
+                    columns: vec!["posts.id".to_string()],
+                },
+            ],
+            concurrency: ColumnConcurrency::sequential(),
+        };
+
+        let user_ids = pseudonymized_ids(&transformer, origin("users", "id"));
+        assert_ne!(vec![1, 2, 1], user_ids);
+        assert_eq!(user_ids[0], user_ids[2]);
+        assert_ne!(user_ids[0], user_ids[1]);
+
+        let authors = pseudonymized_ids(&transformer, origin("posts", "author"));
+        assert_eq!(user_ids, authors);
+
+        let post_ids = pseudonymized_ids(&transformer, origin("posts", "id"));
+        assert_ne!(user_ids, post_ids);
+    }
+
+    #[test]
+    fn test_pseudonymize_strings() {
+        let key = PKey::hmac(b"secret").unwrap();
+        let data: ArrayRef = Arc::new(StringArray::from(vec![Some("alice"), None]));
+
+        let result = pseudonymize(&key, &data).unwrap();
+        let result = result.as_any().downcast_ref::<StringArray>().unwrap();
+
+        assert_eq!(STRING_PSEUDONYM_BYTES * 2, result.value(0).len());
+        assert!(result.is_null(1));
+    }
+}
@@ -0,0 +1,145 @@
+use crate::origin::normalized_column_names;
+use arrow::{
+    array::BooleanArray, compute::filter_record_batch, datatypes::Schema,
+    record_batch::RecordBatch, util::display::array_value_to_string,
+};
+use proboscis_resolver_transformer::{projection::ProjectedOrigin, Transformer, TransformerError};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+/// Returns a fraction of the rows of results with columns of the tables, e.g. 5% of them.
+/// Whether a row is part of the sample depends on its values and the seed only, so
+/// repeating a query returns the same rows instead of another sample. Aggregates are
+/// computed by the target and aren't sampled.
+pub struct RowSamplingTransformer {
+    pub tables: Vec<String>,
+    /// Share of the rows which are returned, between 0 and 1
+    pub fraction: f64,
+    /// Transformers with different seeds return different samples
+    pub seed: u64,
+}
+
+impl RowSamplingTransformer {
+    fn is_sampled(&self, origins: &[ProjectedOrigin]) -> bool {
+        origins
+            .iter()
+            .flat_map(normalized_column_names)
+            .any(|name| {
+                let table = name
+                    .rsplit_once('.')
+                    .map_or(name.as_str(), |(table, _)| table);
+                self.tables.iter().any(|sampled| sampled == table)
+            })
+    }
+
+    fn is_row_in_sample(&self, data: &RecordBatch, row: usize) -> Result<bool, TransformerError> {
+        let mut hasher = DefaultHasher::new();
+        self.seed.hash(&mut hasher);
+
+        for column in data.columns() {
+            array_value_to_string(column, row)?.hash(&mut hasher);
+        }
+
+        Ok((hasher.finish() as f64 / u64::MAX as f64) < self.fraction)
+    }
+}
+
+impl Transformer for RowSamplingTransformer {
+    fn transform_schema(
+        &self,
+        schema: &Schema,
+        _origins: &[ProjectedOrigin],
+    ) -> Result<Schema, TransformerError> {
+        Ok(schema.clone())
+    }
+
+    fn transform_records(
+        &self,
+        data: &RecordBatch,
+        origins: &[ProjectedOrigin],
+    ) -> Result<RecordBatch, TransformerError> {
+        if !self.is_sampled(origins) {
+            return Ok(data.clone());
+        }
+
+        let keep = (0..data.num_rows())
+            .map(|row| self.is_row_in_sample(data, row).map(Some))
+            .collect::<Result<BooleanArray, TransformerError>>()?;
+
+        Ok(filter_record_batch(data, &keep)?)
+    }
+
+    fn explain_schema(
+        &self,
+        schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        let description = Some(format!("{}% of the rows sampled", self.fraction * 100.0))
+            .filter(|_| self.is_sampled(origins));
+
+        Ok(vec![description; schema.fields().len()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::Int64Array,
+        datatypes::{DataType, Field},
+    };
+    use proboscis_resolver_transformer::projection::TableColumn;
+    use std::sync::Arc;
+
+    fn events(rows: i64) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("id", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from((0..rows).collect::<Vec<i64>>()))],
+        )
+        .unwrap()
+    }
+
+    fn origin(table: &str) -> ProjectedOrigin {
+        ProjectedOrigin::TableColumn(TableColumn {
+            table: table.to_string(),
+            column: "id".to_string(),
+        })
+    }
+
+    #[test]
+    fn test_sample_rows() {
+        let transformer = RowSamplingTransformer {
+            tables: vec!["events".to_string()],
+            fraction: 0.1,
+            seed: 42,
+        };
+        let data = events(1000);
+
+        let sample = transformer
+            .transform_records(&data, &[origin("events")])
+            .unwrap();
+        assert!(sample.num_rows() > 50 && sample.num_rows() < 150);
+
+        // The same rows are sampled again
+        let repeated = transformer
+            .transform_records(&data, &[origin("events")])
+            .unwrap();
+        assert_eq!(sample.column(0), repeated.column(0));
+
+        let other_seed = RowSamplingTransformer {
+            seed: 7,
+            ..transformer
+        };
+        let other_sample = other_seed
+            .transform_records(&data, &[origin("events")])
+            .unwrap();
+        assert_ne!(sample.column(0), other_sample.column(0));
+
+        let unsampled = other_seed
+            .transform_records(&data, &[origin("users")])
+            .unwrap();
+        assert_eq!(1000, unsampled.num_rows());
+    }
+}
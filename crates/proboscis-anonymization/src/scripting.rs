@@ -0,0 +1,364 @@
+use crate::{
+    column_transformations::{
+        ColumnTransformation, ColumnTransformationOutput, ColumnTransformationResult,
+    },
+    origin::normalized_column_names,
+};
+use arrow::{
+    array::{Array, ArrayRef, BooleanArray, Float64Array, Int64Array, StringArray},
+    compute::{cast, filter_record_batch},
+    datatypes::{DataType, Schema},
+    error::ArrowError,
+    record_batch::RecordBatch,
+};
+use proboscis_resolver_transformer::{projection::ProjectedOrigin, Transformer, TransformerError};
+use rhai::{module_resolvers::DummyModuleResolver, Dynamic, Engine, Map, Scope, AST};
+use std::sync::Arc;
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ScriptError {
+    #[error("failed to compile the script: {0}")]
+    Compile(#[from] rhai::ParseError),
+
+    #[error("the script failed: {0}")]
+    Evaluation(#[from] Box<rhai::EvalAltResult>),
+
+    #[error("the script returned a {0} for a column of type {1}")]
+    UnexpectedValue(String, DataType),
+
+    #[error(transparent)]
+    Arrow(#[from] ArrowError),
+}
+
+impl From<ScriptError> for TransformerError {
+    fn from(error: ScriptError) -> Self {
+        TransformerError::Other(anyhow::anyhow!(error))
+    }
+}
+
+/// An engine without access to anything outside of the script, which stops
+/// runaway scripts instead of stalling the result
+fn restricted_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine.set_max_operations(100_000);
+    engine.set_max_call_levels(16);
+    engine.set_max_string_size(64 * 1024);
+    engine.set_max_array_size(10_000);
+    engine.set_max_map_size(10_000);
+
+    engine.set_module_resolver(DummyModuleResolver::new());
+    engine.disable_symbol("eval");
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+
+    engine
+}
+
+/// Scripts see integers as `i64`, floats as `f64` and values of other types as strings
+fn script_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Int8
+        | DataType::Int16
+        | DataType::Int32
+        | DataType::Int64
+        | DataType::UInt8
+        | DataType::UInt16
+        | DataType::UInt32 => DataType::Int64,
+        DataType::Float32 | DataType::Float64 => DataType::Float64,
+        DataType::Boolean => DataType::Boolean,
+        _ => DataType::Utf8,
+    }
+}
+
+fn collect_values<A, F>(array: &ArrayRef, value: F) -> Result<Vec<Dynamic>, ScriptError>
+where
+    A: Array + 'static,
+    F: Fn(&A, usize) -> Dynamic,
+{
+    let array = array.as_any().downcast_ref::<A>().ok_or_else(|| {
+        ArrowError::CastError(format!("unexpected array of type {:?}", array.data_type()))
+    })?;
+
+    Ok((0..array.len())
+        .map(|index| match array.is_null(index) {
+            true => Dynamic::UNIT,
+            false => value(array, index),
+        })
+        .collect())
+}
+
+/// The values of the array as scripts see them, nulls are `()`
+fn script_values(array: &ArrayRef) -> Result<Vec<Dynamic>, ScriptError> {
+    let array = cast(array, &script_type(array.data_type()))?;
+
+    match array.data_type() {
+        DataType::Int64 => collect_values(&array, |array: &Int64Array, index| {
+            array.value(index).into()
+        }),
+        DataType::Float64 => collect_values(&array, |array: &Float64Array, index| {
+            array.value(index).into()
+        }),
+        DataType::Boolean => collect_values(&array, |array: &BooleanArray, index| {
+            array.value(index).into()
+        }),
+        _ => collect_values(&array, |array: &StringArray, index| {
+            array.value(index).into()
+        }),
+    }
+}
+
+fn typed_values<T>(
+    values: Vec<Dynamic>,
+    data_type: &DataType,
+    convert: fn(Dynamic) -> Result<T, &'static str>,
+) -> Result<Vec<Option<T>>, ScriptError> {
+    values
+        .into_iter()
+        .map(|value| {
+            if value.is::<()>() {
+                return Ok(None);
+            }
+
+            let type_name = value.type_name().to_string();
+            convert(value)
+                .map(Some)
+                .map_err(|_| ScriptError::UnexpectedValue(type_name, data_type.clone()))
+        })
+        .collect()
+}
+
+/// An array of the type from the values scripts returned, `()` becomes null
+fn array_of(values: Vec<Dynamic>, data_type: &DataType) -> Result<ArrayRef, ScriptError> {
+    let array: ArrayRef = match script_type(data_type) {
+        DataType::Int64 => Arc::new(Int64Array::from(typed_values(
+            values,
+            data_type,
+            |value| value.as_int(),
+        )?)),
+        DataType::Float64 => Arc::new(Float64Array::from(typed_values(
+            values,
+            data_type,
+            |value| value.as_float(),
+        )?)),
+        DataType::Boolean => Arc::new(BooleanArray::from(typed_values(
+            values,
+            data_type,
+            |value| value.as_bool(),
+        )?)),
+        _ => Arc::new(
+            typed_values(values, data_type, |value| value.into_string())?
+                .into_iter()
+                .collect::<StringArray>(),
+        ),
+    };
+
+    Ok(cast(&array, data_type)?)
+}
+
+/// Replaces every value of a column by the result of a rhai script, which reads the value
+/// from the `value` variable, e.g. `value.sub_string(0, 1) + "***"`. Nulls aren't passed
+/// to the script, a script returning `()` nulls the value.
+pub struct ScriptTransformation {
+    engine: Engine,
+    script: AST,
+}
+
+impl ScriptTransformation {
+    pub fn new(script: &str) -> Result<Self, ScriptError> {
+        let engine = restricted_engine();
+        let script = engine.compile(script)?;
+        Ok(ScriptTransformation { engine, script })
+    }
+
+    fn transform_values(&self, values: Vec<Dynamic>) -> Result<Vec<Dynamic>, ScriptError> {
+        values
+            .into_iter()
+            .map(|value| {
+                if value.is::<()>() {
+                    return Ok(value);
+                }
+
+                let mut scope = Scope::new();
+                scope.push("value", value);
+                Ok(self
+                    .engine
+                    .eval_ast_with_scope::<Dynamic>(&mut scope, &self.script)?)
+            })
+            .collect()
+    }
+}
+
+impl ColumnTransformation for ScriptTransformation {
+    fn transform_data(&self, data: ArrayRef) -> ColumnTransformationResult<ArrayRef> {
+        let values = self.transform_values(script_values(&data)?)?;
+        Ok(array_of(values, data.data_type())?)
+    }
+
+    fn output_format(
+        &self,
+        input: &DataType,
+    ) -> ColumnTransformationResult<ColumnTransformationOutput> {
+        Ok(ColumnTransformationOutput {
+            data_type: input.clone(),
+            nullable: true,
+        })
+    }
+}
+
+/// Only returns the rows of results with columns of the tables for which a rhai script
+/// evaluates to true. The script reads the values of the row by the names of the columns
+/// from the `row` map, e.g. `row.country == "DE" && row.age >= 18`.
+pub struct ScriptedRowFilter {
+    pub tables: Vec<String>,
+    engine: Engine,
+    script: AST,
+}
+
+impl ScriptedRowFilter {
+    pub fn new(tables: Vec<String>, script: &str) -> Result<Self, ScriptError> {
+        let engine = restricted_engine();
+        let script = engine.compile(script)?;
+        Ok(ScriptedRowFilter {
+            tables,
+            engine,
+            script,
+        })
+    }
+
+    fn is_filtered(&self, origins: &[ProjectedOrigin]) -> bool {
+        origins
+            .iter()
+            .flat_map(normalized_column_names)
+            .any(|name| {
+                let table = name
+                    .rsplit_once('.')
+                    .map_or(name.as_str(), |(table, _)| table);
+                self.tables.iter().any(|filtered| filtered == table)
+            })
+    }
+
+    fn kept_rows(&self, data: &RecordBatch) -> Result<BooleanArray, ScriptError> {
+        let schema = data.schema();
+        let columns = data
+            .columns()
+            .iter()
+            .map(script_values)
+            .collect::<Result<Vec<_>, ScriptError>>()?;
+
+        (0..data.num_rows())
+            .map(|index| {
+                let mut row = Map::new();
+                for (field, values) in schema.fields().iter().zip(&columns) {
+                    row.insert(field.name().as_str().into(), values[index].clone());
+                }
+
+                let mut scope = Scope::new();
+                scope.push("row", row);
+                Ok(Some(
+                    self.engine
+                        .eval_ast_with_scope::<bool>(&mut scope, &self.script)?,
+                ))
+            })
+            .collect()
+    }
+}
+
+impl Transformer for ScriptedRowFilter {
+    fn transform_schema(
+        &self,
+        schema: &Schema,
+        _origins: &[ProjectedOrigin],
+    ) -> Result<Schema, TransformerError> {
+        Ok(schema.clone())
+    }
+
+    fn transform_records(
+        &self,
+        data: &RecordBatch,
+        origins: &[ProjectedOrigin],
+    ) -> Result<RecordBatch, TransformerError> {
+        if !self.is_filtered(origins) {
+            return Ok(data.clone());
+        }
+
+        Ok(filter_record_batch(data, &self.kept_rows(data)?)?)
+    }
+
+    fn explain_schema(
+        &self,
+        schema: &Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        let description =
+            Some("rows filtered by a script".to_string()).filter(|_| self.is_filtered(origins));
+
+        Ok(vec![description; schema.fields().len()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::Field;
+    use proboscis_resolver_transformer::projection::TableColumn;
+
+    fn contacts() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("name", DataType::Utf8, true),
+                Field::new("age", DataType::Int32, false),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec![Some("Alex"), None, Some("Mia")])),
+                Arc::new(arrow::array::Int32Array::from(vec![17, 35, 22])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_script_transformation() {
+        let transformation =
+            ScriptTransformation::new(r#"value.sub_string(0, 1) + "***""#).unwrap();
+
+        let masked = transformation
+            .transform_data(contacts().column(0).clone())
+            .unwrap();
+        let masked = masked.as_any().downcast_ref::<StringArray>().unwrap();
+        assert_eq!("A***", masked.value(0));
+        assert!(masked.is_null(1));
+        assert_eq!("M***", masked.value(2));
+
+        let rounded = ScriptTransformation::new("value / 10 * 10")
+            .unwrap()
+            .transform_data(contacts().column(1).clone())
+            .unwrap();
+        assert_eq!(&DataType::Int32, rounded.data_type());
+
+        assert!(ScriptTransformation::new("loop {}")
+            .unwrap()
+            .transform_data(contacts().column(1).clone())
+            .is_err());
+    }
+
+    #[test]
+    fn test_scripted_row_filter() {
+        let filter = ScriptedRowFilter::new(vec!["contacts".to_string()], "row.age >= 18").unwrap();
+
+        let origins: Vec<ProjectedOrigin> = ["name", "age"]
+            .iter()
+            .map(|column| {
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: "contacts".to_string(),
+                    column: column.to_string(),
+                })
+            })
+            .collect();
+
+        let filtered = filter.transform_records(&contacts(), &origins).unwrap();
+        assert_eq!(2, filtered.num_rows());
+
+        assert!(ScriptedRowFilter::new(vec![], "row.age >=").is_err());
+    }
+}
@@ -1,19 +1,24 @@
 use super::AnonymizationCriteria;
 use crate::{
     algorithm::{anonymize, NumericAggregation, StringAggregation},
+    concat::concat_record_batches,
+    concurrency::ColumnConcurrency,
     conversion::{data_frame_to_record_batch, record_batch_to_data_frame},
+    origin::normalized_column_names,
 };
 use arrow::record_batch::RecordBatch;
-use proboscis_resolver_transformer::{
-    projection::{ProjectedOrigin, TableColumn},
-    Transformer, TransformerError,
-};
+use itertools::Itertools;
+use proboscis_resolver_transformer::{projection::ProjectedOrigin, Transformer, TransformerError};
 use std::collections::HashMap;
 
 pub struct AnonymizationTransformer {
     pub identifier_columns: Vec<String>,
     pub quasi_identifier_columns: HashMap<String, (NumericAggregation, StringAggregation)>,
-    pub criteria: AnonymizationCriteria,
+    pub criteria: Vec<AnonymizationCriteria>,
+    /// Criteria which additionally have to hold for results containing columns of the table.
+    /// Sensitive columns of these criteria are named without their table.
+    pub table_criteria: HashMap<String, Vec<AnonymizationCriteria>>,
+    pub concurrency: ColumnConcurrency,
 }
 
 // The identifier & pseudo identifiers contained in the query
@@ -23,6 +28,67 @@ type RelevantColumns = (
 );
 
 impl AnonymizationTransformer {
+    /// The criteria for a result, including those of every table its columns originate from
+    fn get_criteria(
+        &self,
+        origins: &[ProjectedOrigin],
+        schema: &arrow::datatypes::Schema,
+    ) -> Vec<AnonymizationCriteria> {
+        let mut criteria = self.criteria.clone();
+
+        let tables: Vec<String> = origins
+            .iter()
+            .flat_map(normalized_column_names)
+            .filter_map(|name| name.split('.').next().map(|table| table.to_string()))
+            .unique()
+            .collect();
+
+        for table in tables {
+            let table_criteria = match self.table_criteria.get(&table) {
+                Some(table_criteria) => table_criteria,
+                None => continue,
+            };
+
+            // The sensitive column is looked up among the projected columns
+            let projected_name = |column: &str| {
+                let name = format!("{}.{}", table, column);
+                origins
+                    .iter()
+                    .position(|origin| normalized_column_names(origin).contains(&name))
+                    .map(|idx| schema.field(idx).name().to_string())
+            };
+
+            for criterium in table_criteria {
+                let resolved = match criterium {
+                    AnonymizationCriteria::LDiverse {
+                        l,
+                        sensitive_column,
+                    } => projected_name(sensitive_column).map(|sensitive_column| {
+                        AnonymizationCriteria::LDiverse {
+                            l: *l,
+                            sensitive_column,
+                        }
+                    }),
+                    AnonymizationCriteria::TClose {
+                        t,
+                        sensitive_column,
+                    } => projected_name(sensitive_column).map(|sensitive_column| {
+                        AnonymizationCriteria::TClose {
+                            t: *t,
+                            sensitive_column,
+                        }
+                    }),
+                    _ => Some(criterium.clone()),
+                };
+
+                // Sensitive columns which aren't part of the result can't be disclosed by it
+                criteria.extend(resolved);
+            }
+        }
+
+        criteria
+    }
+
     fn get_relevant_columns(
         &self,
         origins: &[ProjectedOrigin],
@@ -32,38 +98,27 @@ impl AnonymizationTransformer {
             origins
                 .iter()
                 .enumerate()
-                .filter_map(|(idx, origin)| match origin {
-                    ProjectedOrigin::Function => None,
-                    ProjectedOrigin::Value => None,
-                    ProjectedOrigin::TableColumn(TableColumn { table, column }) => {
-                        let normalized_column_name = &format!("{}.{}", table, column);
-
-                        self.quasi_identifier_columns
-                            .get(normalized_column_name)
-                            .map(|aggregations| {
-                                (schema.field(idx).name().to_string(), *aggregations)
-                            })
-                    }
+                .filter_map(|(idx, origin)| {
+                    normalized_column_names(origin)
+                        .iter()
+                        .find_map(|name| self.quasi_identifier_columns.get(name))
+                        .map(|aggregations| (schema.field(idx).name().to_string(), *aggregations))
                 })
                 .collect();
 
         let identifier_columns: Vec<String> = origins
             .iter()
             .enumerate()
-            .filter_map(|(idx, origin)| match origin {
-                ProjectedOrigin::Function => None,
-                ProjectedOrigin::Value => None,
-                ProjectedOrigin::TableColumn(TableColumn { table, column }) => {
-                    let normalized_column_name = &format!("{}.{}", table, column);
-
-                    let is_identifier = self.identifier_columns.contains(normalized_column_name);
-
-                    if !is_identifier {
-                        return None;
-                    }
+            .filter_map(|(idx, origin)| {
+                let is_identifier = normalized_column_names(origin)
+                    .iter()
+                    .any(|name| self.identifier_columns.contains(name));
 
-                    Some(schema.field(idx).name().to_string())
+                if !is_identifier {
+                    return None;
                 }
+
+                Some(schema.field(idx).name().to_string())
             })
             .collect();
 
@@ -85,7 +140,16 @@ impl Transformer for AnonymizationTransformer {
 
         let mut updated_fields = vec![];
         for field in schema.fields() {
-            let updated_field = match field.data_type() {
+            let (numeric_aggregation, string_aggregation) =
+                match quasi_identifiers.get(field.name()) {
+                    Some(aggregations) => aggregations,
+                    None => {
+                        updated_fields.push(field.clone());
+                        continue;
+                    }
+                };
+
+            let transformation = match field.data_type() {
                 arrow::datatypes::DataType::UInt8
                 | arrow::datatypes::DataType::UInt16
                 | arrow::datatypes::DataType::UInt32
@@ -93,24 +157,23 @@ impl Transformer for AnonymizationTransformer {
                 | arrow::datatypes::DataType::Int8
                 | arrow::datatypes::DataType::Int16
                 | arrow::datatypes::DataType::Int32
-                | arrow::datatypes::DataType::Int64 => match quasi_identifiers.get(field.name()) {
-                    Some((numeric_aggregation, _)) => {
-                        let output_format = numeric_aggregation
-                            .transformation()
-                            .output_format(field.data_type())?;
-
-                        arrow::datatypes::Field::new(
-                            field.name(),
-                            output_format.data_type,
-                            output_format.nullable,
-                        )
-                    }
-                    None => field.clone(),
-                },
-                _ => field.clone(),
+                | arrow::datatypes::DataType::Int64 => numeric_aggregation.transformation(),
+                arrow::datatypes::DataType::Utf8 | arrow::datatypes::DataType::LargeUtf8 => {
+                    string_aggregation.transformation()
+                }
+                _ => {
+                    updated_fields.push(field.clone());
+                    continue;
+                }
             };
 
-            updated_fields.push(updated_field);
+            let output_format = transformation.output_format(field.data_type())?;
+
+            updated_fields.push(arrow::datatypes::Field::new(
+                field.name(),
+                output_format.data_type,
+                output_format.nullable,
+            ));
         }
 
         let updated_schema = arrow::datatypes::Schema::new(updated_fields);
@@ -140,7 +203,8 @@ impl Transformer for AnonymizationTransformer {
             &dataframe,
             &identifier_columns_strs,
             &quasi_identifiers,
-            &[self.criteria.clone()],
+            &self.get_criteria(origins, &data.schema()),
+            &self.concurrency,
         )?;
 
         let updated_schema = self.transform_schema(&data.schema(), origins)?;
@@ -149,6 +213,76 @@ impl Transformer for AnonymizationTransformer {
 
         Ok(result)
     }
+
+    fn transform_batches(
+        &self,
+        data: &[RecordBatch],
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<RecordBatch>, TransformerError> {
+        if data.len() <= 1 {
+            return data
+                .iter()
+                .map(|batch| self.transform_records(batch, origins))
+                .collect();
+        }
+
+        // The anonymization criteria have to hold for the whole result, not every single batch
+        let combined = concat_record_batches(data)?;
+
+        Ok(vec![self.transform_records(&combined, origins)?])
+    }
+
+    fn explain_schema(
+        &self,
+        schema: &arrow::datatypes::Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        let explanations = schema
+            .fields()
+            .iter()
+            .zip(origins)
+            .map(|(field, origin)| {
+                let names = normalized_column_names(origin);
+
+                if let Some((name, (numeric_aggregation, string_aggregation))) = names
+                    .iter()
+                    .find_map(|name| self.quasi_identifier_columns.get_key_value(name))
+                {
+                    return Some(match field.data_type() {
+                        arrow::datatypes::DataType::UInt8
+                        | arrow::datatypes::DataType::UInt16
+                        | arrow::datatypes::DataType::UInt32
+                        | arrow::datatypes::DataType::UInt64
+                        | arrow::datatypes::DataType::Int8
+                        | arrow::datatypes::DataType::Int16
+                        | arrow::datatypes::DataType::Int32
+                        | arrow::datatypes::DataType::Int64 => format!(
+                            "aggregated ({:?}) as quasi-identifier {}",
+                            numeric_aggregation, name
+                        ),
+                        arrow::datatypes::DataType::Utf8
+                        | arrow::datatypes::DataType::LargeUtf8 => {
+                            format!(
+                                "aggregated ({:?}) as quasi-identifier {}",
+                                string_aggregation, name
+                            )
+                        }
+                        data_type => format!(
+                            "unsupported type {:?} of quasi-identifier {}",
+                            data_type, name
+                        ),
+                    });
+                }
+
+                names
+                    .iter()
+                    .find(|name| self.identifier_columns.contains(name))
+                    .map(|name| format!("de-identified as identifier {}", name))
+            })
+            .collect();
+
+        Ok(explanations)
+    }
 }
 
 #[cfg(test)]
@@ -159,8 +293,114 @@ mod tests {
         datatypes::{DataType, Field, Schema},
     };
     use itertools::Itertools;
+    use proboscis_resolver_transformer::projection::TableColumn;
     use std::sync::Arc;
 
+    #[test]
+    fn test_explain_schema() {
+        let transformer = AnonymizationTransformer {
+            identifier_columns: vec!["contacts.first_name".to_string()],
+            quasi_identifier_columns: vec![(
+                "contacts.age".to_string(),
+                (NumericAggregation::Range, StringAggregation::Join),
+            )]
+            .into_iter()
+            .collect(),
+            criteria: vec![],
+            table_criteria: HashMap::new(),
+            concurrency: ColumnConcurrency::sequential(),
+        };
+
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("first_name", DataType::Utf8, false),
+            Field::new("age", DataType::Int32, false),
+        ]);
+
+        let origins: Vec<ProjectedOrigin> = vec!["id", "first_name", "age"]
+            .into_iter()
+            .map(|column| {
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: "contacts".to_string(),
+                    column: column.to_string(),
+                })
+            })
+            .collect();
+
+        assert_eq!(
+            vec![
+                None,
+                Some("de-identified as identifier contacts.first_name".to_string()),
+                Some("aggregated (Range) as quasi-identifier contacts.age".to_string()),
+            ],
+            transformer.explain_schema(&schema, &origins).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_table_criteria() {
+        let transformer = AnonymizationTransformer {
+            identifier_columns: vec![],
+            quasi_identifier_columns: HashMap::new(),
+            criteria: vec![AnonymizationCriteria::KAnonymous { k: 2 }],
+            table_criteria: vec![
+                (
+                    "contacts".to_string(),
+                    vec![
+                        AnonymizationCriteria::KAnonymous { k: 5 },
+                        AnonymizationCriteria::LDiverse {
+                            l: 2,
+                            sensitive_column: "profession".to_string(),
+                        },
+                        AnonymizationCriteria::TClose {
+                            t: 0.2,
+                            sensitive_column: "salary".to_string(),
+                        },
+                    ],
+                ),
+                (
+                    "orders".to_string(),
+                    vec![AnonymizationCriteria::KAnonymous { k: 10 }],
+                ),
+            ]
+            .into_iter()
+            .collect(),
+            concurrency: ColumnConcurrency::sequential(),
+        };
+
+        let schema = Schema::new(vec![
+            Field::new("age", DataType::Int32, false),
+            Field::new("job", DataType::Utf8, false),
+        ]);
+
+        let origins: Vec<ProjectedOrigin> = vec!["age", "profession"]
+            .into_iter()
+            .map(|column| {
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: "contacts".to_string(),
+                    column: column.to_string(),
+                })
+            })
+            .collect();
+
+        let criteria = transformer.get_criteria(&origins, &schema);
+
+        // The salary isn't part of the result, the orders table isn't queried
+        assert_eq!(3, criteria.len());
+        assert!(matches!(
+            criteria[0],
+            AnonymizationCriteria::KAnonymous { k: 2 }
+        ));
+        assert!(matches!(
+            criteria[1],
+            AnonymizationCriteria::KAnonymous { k: 5 }
+        ));
+        assert!(matches!(
+            &criteria[2],
+            AnonymizationCriteria::LDiverse { l: 2, sensitive_column } if sensitive_column == "job"
+        ));
+    }
+
     #[test]
     fn with_median_aggregation() {
         let id_array = Int32Array::from(vec![1, 2, 3, 4, 5, 6, 7, 8, 9]);
@@ -218,7 +458,9 @@ mod tests {
         let transformer = AnonymizationTransformer {
             quasi_identifier_columns,
             identifier_columns: vec![],
-            criteria: AnonymizationCriteria::KAnonymous { k: 2 },
+            criteria: vec![AnonymizationCriteria::KAnonymous { k: 2 }],
+            table_criteria: HashMap::new(),
+            concurrency: ColumnConcurrency::sequential(),
         };
 
         let origins = vec![
@@ -316,7 +558,9 @@ mod tests {
         let transformer = AnonymizationTransformer {
             quasi_identifier_columns,
             identifier_columns: vec![],
-            criteria: AnonymizationCriteria::KAnonymous { k: 2 },
+            criteria: vec![AnonymizationCriteria::KAnonymous { k: 2 }],
+            table_criteria: HashMap::new(),
+            concurrency: ColumnConcurrency::sequential(),
         };
 
         let origins = vec![
@@ -356,4 +600,65 @@ mod tests {
                 .collect_vec()
         );
     }
+
+    #[test]
+    fn quasi_identifiers_across_joined_tables() {
+        let schema = Schema::new(vec![
+            Field::new("age", DataType::Int32, false),
+            Field::new("city", DataType::Utf8, false),
+            Field::new("title", DataType::Utf8, false),
+        ]);
+
+        let quasi_identifier_columns = vec![
+            (
+                "users.age".to_string(),
+                (NumericAggregation::Range, StringAggregation::Join),
+            ),
+            (
+                "events.city".to_string(),
+                (NumericAggregation::Range, StringAggregation::Substring),
+            ),
+        ]
+        .iter()
+        .cloned()
+        .collect();
+
+        let transformer = AnonymizationTransformer {
+            quasi_identifier_columns,
+            identifier_columns: vec![],
+            criteria: vec![AnonymizationCriteria::KAnonymous { k: 2 }],
+            table_criteria: HashMap::new(),
+            concurrency: ColumnConcurrency::sequential(),
+        };
+
+        let origins = vec![
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: String::from("users"),
+                column: String::from("age"),
+            }),
+            ProjectedOrigin::AmbiguousTableColumn(vec![
+                TableColumn {
+                    table: String::from("users"),
+                    column: String::from("city"),
+                },
+                TableColumn {
+                    table: String::from("events"),
+                    column: String::from("city"),
+                },
+            ]),
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: String::from("events"),
+                column: String::from("title"),
+            }),
+        ];
+
+        let (identifiers, quasi_identifiers) =
+            transformer.get_relevant_columns(&origins, &schema).unwrap();
+
+        assert!(identifiers.is_empty());
+        assert_eq!(
+            vec!["age", "city"],
+            quasi_identifiers.keys().sorted().collect_vec()
+        );
+    }
 }
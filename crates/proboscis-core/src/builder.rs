@@ -0,0 +1,283 @@
+use crate::{
+    hooks::ProxyHook,
+    metrics::ProxyMetrics,
+    proxy::{AuthenticationMethod, Config, GssEncryption, Proxy, TlsConfig},
+    resolver::Resolver,
+    utils::{address_filter::AddressFilter, tls::ReloadingTlsAcceptor},
+    ProboscisError,
+};
+use std::{collections::HashMap, sync::Arc, time::Duration};
+use thiserror::Error;
+
+#[derive(Error, Debug)]
+pub enum ProxyBuildError {
+    #[error(
+        "no resolver is configured, neither a default one nor one of a database or application"
+    )]
+    MissingResolver,
+
+    #[error("no credentials are configured, every client would be rejected")]
+    MissingCredentials,
+
+    #[error("credentials aren't used with authentication passthrough")]
+    CredentialsWithPassthrough,
+
+    #[error("the resolver stack is wrapped, but there is no default resolver to wrap")]
+    NothingToWrap,
+
+    #[error("the {0} timeout must not be zero")]
+    ZeroTimeout(&'static str),
+
+    #[error("the tls certificate can't be loaded: {0}")]
+    Tls(ProboscisError),
+}
+
+type ResolverWrapper = Box<dyn FnOnce(Box<dyn Resolver>) -> Box<dyn Resolver>>;
+
+/// Assembles a proxy step by step, checking the settings fit together once it is built
+///
+/// ```ignore
+/// let proxy = ProxyBuilder::new()
+///     .credential("admin", "password")
+///     .resolver(Box::new(PostgresResolver::create(target_config, 10).await?))
+///     .wrap_resolver(|resolver| Box::new(TransformingResolver::new(resolver)))
+///     .build()?;
+/// ```
+#[derive(Default)]
+pub struct ProxyBuilder {
+    credentials: HashMap<String, String>,
+    tls_config: Option<TlsConfig>,
+    resolver: Option<Box<dyn Resolver>>,
+    // Applied to the default resolver in order, so the last one is the outermost
+    wrappers: Vec<ResolverWrapper>,
+    database_resolvers: HashMap<String, Box<dyn Resolver>>,
+    application_resolvers: HashMap<String, Box<dyn Resolver>>,
+    metrics: Option<Arc<ProxyMetrics>>,
+    authentication_passthrough: bool,
+    address_filter: Option<AddressFilter>,
+    gss_encryption: Option<GssEncryption>,
+    authentication_method: Option<AuthenticationMethod>,
+    startup_timeout: Option<Duration>,
+    authentication_timeout: Option<Duration>,
+    hooks: Vec<Arc<dyn ProxyHook>>,
+}
+
+impl ProxyBuilder {
+    pub fn new() -> ProxyBuilder {
+        ProxyBuilder::default()
+    }
+
+    /// Allows the user to connect with the password, or the md5 verifier of the password
+    pub fn credential(mut self, user: &str, password: &str) -> ProxyBuilder {
+        self.credentials
+            .insert(user.to_string(), password.to_string());
+        self
+    }
+
+    pub fn credentials(mut self, credentials: HashMap<String, String>) -> ProxyBuilder {
+        self.credentials.extend(credentials);
+        self
+    }
+
+    pub fn tls(mut self, tls_config: TlsConfig) -> ProxyBuilder {
+        self.tls_config = Some(tls_config);
+        self
+    }
+
+    /// The resolver of clients connecting to databases without a resolver of their own
+    pub fn resolver(mut self, resolver: Box<dyn Resolver>) -> ProxyBuilder {
+        self.resolver = Some(resolver);
+        self
+    }
+
+    /// Wraps the default resolver, e.g. in a transforming or caching resolver.
+    /// Every call adds another layer around the previous ones.
+    pub fn wrap_resolver<F>(mut self, wrapper: F) -> ProxyBuilder
+    where
+        F: FnOnce(Box<dyn Resolver>) -> Box<dyn Resolver> + 'static,
+    {
+        self.wrappers.push(Box::new(wrapper));
+        self
+    }
+
+    pub fn database(mut self, database: &str, resolver: Box<dyn Resolver>) -> ProxyBuilder {
+        self.database_resolvers
+            .insert(database.to_string(), resolver);
+        self
+    }
+
+    pub fn application(
+        mut self,
+        application_name: &str,
+        resolver: Box<dyn Resolver>,
+    ) -> ProxyBuilder {
+        self.application_resolvers
+            .insert(application_name.to_string(), resolver);
+        self
+    }
+
+    /// Counts into the metrics, e.g. to share them with a server exposing them
+    pub fn metrics(mut self, metrics: Arc<ProxyMetrics>) -> ProxyBuilder {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Called at the stages of every client's lifecycle, in the order hooks are added
+    pub fn hook(mut self, hook: Arc<dyn ProxyHook>) -> ProxyBuilder {
+        self.hooks.push(hook);
+        self
+    }
+
+    pub fn authentication_passthrough(mut self) -> ProxyBuilder {
+        self.authentication_passthrough = true;
+        self
+    }
+
+    pub fn address_filter(mut self, address_filter: AddressFilter) -> ProxyBuilder {
+        self.address_filter = Some(address_filter);
+        self
+    }
+
+    /// Authenticates clients with md5 by default
+    pub fn authentication_method(
+        mut self,
+        authentication_method: AuthenticationMethod,
+    ) -> ProxyBuilder {
+        self.authentication_method = Some(authentication_method);
+        self
+    }
+
+    pub fn gss_encryption(mut self, gss_encryption: GssEncryption) -> ProxyBuilder {
+        self.gss_encryption = Some(gss_encryption);
+        self
+    }
+
+    pub fn startup_timeout(mut self, startup_timeout: Duration) -> ProxyBuilder {
+        self.startup_timeout = Some(startup_timeout);
+        self
+    }
+
+    pub fn authentication_timeout(mut self, authentication_timeout: Duration) -> ProxyBuilder {
+        self.authentication_timeout = Some(authentication_timeout);
+        self
+    }
+
+    fn validate(&self) -> Result<(), ProxyBuildError> {
+        if self.resolver.is_none() {
+            if !self.wrappers.is_empty() {
+                return Err(ProxyBuildError::NothingToWrap);
+            }
+
+            if self.database_resolvers.is_empty() && self.application_resolvers.is_empty() {
+                return Err(ProxyBuildError::MissingResolver);
+            }
+        }
+
+        match (self.authentication_passthrough, self.credentials.is_empty()) {
+            (true, false) => return Err(ProxyBuildError::CredentialsWithPassthrough),
+            (false, true) => return Err(ProxyBuildError::MissingCredentials),
+            _ => {}
+        }
+
+        for (name, timeout) in &[
+            ("startup", self.startup_timeout),
+            ("authentication", self.authentication_timeout),
+        ] {
+            if *timeout == Some(Duration::from_secs(0)) {
+                return Err(ProxyBuildError::ZeroTimeout(*name));
+            }
+        }
+
+        // The certificate is loaded again once the proxy listens
+        if let Some(tls_config) = &self.tls_config {
+            ReloadingTlsAcceptor::load(&tls_config.pcks_path, &tls_config.password)
+                .map_err(ProxyBuildError::Tls)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<Proxy, ProxyBuildError> {
+        self.validate()?;
+
+        let config = Config {
+            tls_config: self.tls_config,
+            credentials: self.credentials,
+        };
+
+        let wrappers = self.wrappers;
+        let resolver = self.resolver.map(|resolver| {
+            wrappers
+                .into_iter()
+                .fold(resolver, |resolver, wrap| wrap(resolver))
+        });
+
+        let mut proxy = match resolver {
+            Some(resolver) => Proxy::new(config, resolver),
+            None => Proxy::with_databases(config, HashMap::new()),
+        };
+
+        for (database, resolver) in self.database_resolvers {
+            proxy = proxy.add_database(&database, resolver);
+        }
+
+        for (application_name, resolver) in self.application_resolvers {
+            proxy = proxy.add_application(&application_name, resolver);
+        }
+
+        if let Some(metrics) = self.metrics {
+            proxy = proxy.with_metrics(metrics);
+        }
+
+        if self.authentication_passthrough {
+            proxy = proxy.with_authentication_passthrough();
+        }
+
+        if let Some(address_filter) = self.address_filter {
+            proxy = proxy.with_address_filter(address_filter);
+        }
+
+        if let Some(authentication_method) = self.authentication_method {
+            proxy = proxy.with_authentication_method(authentication_method);
+        }
+
+        if let Some(gss_encryption) = self.gss_encryption {
+            proxy = proxy.with_gss_encryption(gss_encryption);
+        }
+
+        if let Some(startup_timeout) = self.startup_timeout {
+            proxy = proxy.with_startup_timeout(startup_timeout);
+        }
+
+        if let Some(authentication_timeout) = self.authentication_timeout {
+            proxy = proxy.with_authentication_timeout(authentication_timeout);
+        }
+
+        for hook in self.hooks {
+            proxy = proxy.with_hook(hook);
+        }
+
+        Ok(proxy)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_build_without_resolver() {
+        assert!(matches!(
+            ProxyBuilder::new().credential("admin", "password").build(),
+            Err(ProxyBuildError::MissingResolver)
+        ));
+
+        assert!(matches!(
+            ProxyBuilder::new()
+                .credential("admin", "password")
+                .wrap_resolver(|resolver| resolver)
+                .build(),
+            Err(ProxyBuildError::NothingToWrap)
+        ));
+    }
+}
@@ -0,0 +1,226 @@
+use crate::resolver::ResolveError;
+use async_trait::async_trait;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc, RwLock,
+};
+use tokio::sync::Mutex;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RelationKind {
+    Table,
+    View,
+    MaterializedView,
+    ForeignTable,
+    PartitionedTable,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CatalogColumn {
+    pub name: String,
+    /// The position of the column within its relation, starting at 1
+    pub number: i16,
+    pub type_oid: u32,
+    /// The name of the type without modifiers, e.g. `character varying`
+    pub type_name: String,
+}
+
+/// A table, view or other relation of the upstream with its columns
+#[derive(Clone, Debug, PartialEq)]
+pub struct CatalogRelation {
+    pub oid: u32,
+    pub schema: String,
+    pub name: String,
+    pub kind: RelationKind,
+    pub columns: Vec<CatalogColumn>,
+    /// The query defining a view
+    pub definition: Option<String>,
+}
+
+impl CatalogRelation {
+    pub fn qualified_name(&self) -> String {
+        format!("{}.{}", self.schema, self.name)
+    }
+
+    /// Whether the name, qualified with the schema or not, refers to the relation
+    pub fn matches(&self, name: &str) -> bool {
+        match name.split_once('.') {
+            Some((schema, name)) => schema == self.schema && name == self.name,
+            None => name == self.name,
+        }
+    }
+
+    pub fn column(&self, name: &str) -> Option<&CatalogColumn> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+}
+
+/// A snapshot of the relations of the upstream
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Catalog {
+    pub relations: Vec<CatalogRelation>,
+}
+
+impl Catalog {
+    pub fn relation_by_oid(&self, oid: u32) -> Option<&CatalogRelation> {
+        self.relations.iter().find(|relation| relation.oid == oid)
+    }
+
+    /// The relation the name refers to. Unqualified names prefer relations of
+    /// the `public` schema, like with the default search path.
+    pub fn relation(&self, name: &str) -> Option<&CatalogRelation> {
+        let mut candidates = self
+            .relations
+            .iter()
+            .filter(|relation| relation.matches(name));
+
+        let first = candidates.next()?;
+        if first.schema == "public" {
+            return Some(first);
+        }
+
+        Some(
+            candidates
+                .find(|relation| relation.schema == "public")
+                .unwrap_or(first),
+        )
+    }
+}
+
+/// Loads the catalog, e.g. from the system catalogs of the upstream
+#[async_trait]
+pub trait CatalogSource: Send + Sync {
+    async fn load(&self) -> Result<Catalog, ResolveError>;
+}
+
+/// The catalog shared by every client, loaded on first use and reloaded once it
+/// has been invalidated, e.g. because a DDL statement changed the schema
+pub struct CatalogCache {
+    source: Box<dyn CatalogSource>,
+    catalog: RwLock<Option<Arc<Catalog>>>,
+    // Incremented on every invalidation, so loads which started before one aren't stored
+    generation: AtomicU64,
+    // Clients waiting for the catalog share a single load
+    loading: Mutex<()>,
+}
+
+impl CatalogCache {
+    pub fn new(source: Box<dyn CatalogSource>) -> CatalogCache {
+        CatalogCache {
+            source,
+            catalog: RwLock::new(None),
+            generation: AtomicU64::new(0),
+            loading: Mutex::new(()),
+        }
+    }
+
+    /// The cached catalog without loading it
+    pub fn current(&self) -> Option<Arc<Catalog>> {
+        self.catalog.read().unwrap().clone()
+    }
+
+    /// The cached catalog, loading it if it isn't cached
+    pub async fn get(&self) -> Result<Arc<Catalog>, ResolveError> {
+        if let Some(catalog) = self.current() {
+            return Ok(catalog);
+        }
+
+        let _loading = self.loading.lock().await;
+        if let Some(catalog) = self.current() {
+            return Ok(catalog);
+        }
+
+        let generation = self.generation.load(Ordering::SeqCst);
+        let catalog = Arc::new(self.source.load().await?);
+
+        let mut cached = self.catalog.write().unwrap();
+        if self.generation.load(Ordering::SeqCst) == generation {
+            *cached = Some(catalog.clone());
+        }
+
+        Ok(catalog)
+    }
+
+    /// Drops the cached catalog, the next access loads it again
+    pub fn invalidate(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        *self.catalog.write().unwrap() = None;
+        tracing::debug!("invalidated the catalog cache");
+    }
+}
+
+// First keywords of statements which change the schema
+const DDL_KEYWORDS: &[&str] = &["ALTER", "COMMENT", "CREATE", "DROP", "IMPORT", "TRUNCATE"];
+
+/// Whether the query contains a statement that might change relations or their columns.
+/// Every statement of a batch is checked, so e.g. `BEGIN; ALTER TABLE ...` counts as well.
+pub fn is_ddl(query: &str) -> bool {
+    query.split(';').any(|statement| {
+        let keyword = statement
+            .lines()
+            .map(str::trim_start)
+            .find(|line| !line.is_empty() && !line.starts_with("--"))
+            .and_then(|line| line.split_whitespace().next())
+            .unwrap_or("");
+
+        DDL_KEYWORDS
+            .iter()
+            .any(|ddl| keyword.eq_ignore_ascii_case(ddl))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+
+    struct CountingSource {
+        loads: Arc<AtomicUsize>,
+    }
+
+    #[async_trait]
+    impl CatalogSource for CountingSource {
+        async fn load(&self) -> Result<Catalog, ResolveError> {
+            self.loads.fetch_add(1, Ordering::SeqCst);
+            Ok(Catalog {
+                relations: vec![CatalogRelation {
+                    oid: 16384,
+                    schema: "public".to_string(),
+                    name: "contacts".to_string(),
+                    kind: RelationKind::Table,
+                    columns: vec![],
+                    definition: None,
+                }],
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_catalog_cache_invalidation() {
+        let loads = Arc::new(AtomicUsize::new(0));
+        let cache = CatalogCache::new(Box::new(CountingSource {
+            loads: loads.clone(),
+        }));
+
+        assert!(cache.current().is_none());
+
+        let catalog = cache.get().await.unwrap();
+        assert_eq!(Some(16384), catalog.relation("contacts").map(|r| r.oid));
+        cache.get().await.unwrap();
+        assert_eq!(1, loads.load(Ordering::SeqCst));
+
+        cache.invalidate();
+        assert!(cache.current().is_none());
+        cache.get().await.unwrap();
+        assert_eq!(2, loads.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_is_ddl() {
+        assert!(is_ddl("ALTER TABLE contacts ADD COLUMN ssn text"));
+        assert!(is_ddl("BEGIN; drop view adults; COMMIT"));
+        assert!(is_ddl("-- add the view\nCREATE VIEW adults AS SELECT 1"));
+        assert!(!is_ddl("SELECT created FROM contacts"));
+        assert!(!is_ddl("SET search_path = public"));
+    }
+}
@@ -0,0 +1,218 @@
+use std::io::{Error, ErrorKind};
+
+fn invalid(text: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, format!("invalid array: {}", text))
+}
+
+/// Parses a one-dimensional array in text format (`{a,"b c",NULL}`) into its elements
+pub fn parse_array_text(text: &str) -> std::io::Result<Vec<Option<String>>> {
+    let inner = text
+        .strip_prefix('{')
+        .and_then(|text| text.strip_suffix('}'))
+        .ok_or_else(|| invalid(text))?;
+
+    let mut elements = vec![];
+    if inner.is_empty() {
+        return Ok(elements);
+    }
+
+    let mut chars = inner.chars().peekable();
+    loop {
+        let element = if chars.peek() == Some(&'"') {
+            chars.next();
+
+            let mut element = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => element.push(chars.next().ok_or_else(|| invalid(text))?),
+                    Some(c) => element.push(c),
+                    None => return Err(invalid(text)),
+                }
+            }
+
+            Some(element)
+        } else {
+            let mut element = String::new();
+            while let Some(c) = chars.peek() {
+                match c {
+                    ',' => break,
+                    // Nested arrays are not supported
+                    '{' | '}' | '"' => return Err(invalid(text)),
+                    _ => element.push(*c),
+                }
+                chars.next();
+            }
+
+            if element.is_empty() {
+                return Err(invalid(text));
+            }
+
+            if element.eq_ignore_ascii_case("NULL") {
+                None
+            } else {
+                Some(element)
+            }
+        };
+
+        elements.push(element);
+
+        match chars.next() {
+            Some(',') => continue,
+            None => break,
+            Some(_) => return Err(invalid(text)),
+        }
+    }
+
+    Ok(elements)
+}
+
+fn needs_quotes(element: &str) -> bool {
+    element.is_empty()
+        || element.eq_ignore_ascii_case("NULL")
+        || element
+            .chars()
+            .any(|c| matches!(c, '{' | '}' | ',' | '"' | '\\') || c.is_whitespace())
+}
+
+/// Formats elements as a one-dimensional array in text format
+pub fn format_array_text(elements: &[Option<String>]) -> String {
+    let elements: Vec<String> = elements
+        .iter()
+        .map(|element| match element {
+            None => "NULL".to_string(),
+            Some(element) if needs_quotes(element) => {
+                format!("\"{}\"", element.replace('\\', "\\\\").replace('"', "\\\""))
+            }
+            Some(element) => element.clone(),
+        })
+        .collect();
+
+    format!("{{{}}}", elements.join(","))
+}
+
+fn read_i32(bytes: &[u8], offset: usize) -> std::io::Result<i32> {
+    bytes
+        .get(offset..offset + 4)
+        .map(|bytes| i32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid binary array"))
+}
+
+/// Parses a one-dimensional array in binary format into the binary representations of its elements
+pub fn parse_array_binary(bytes: &[u8]) -> std::io::Result<Vec<Option<Vec<u8>>>> {
+    let dimensions = read_i32(bytes, 0)?;
+    match dimensions {
+        0 => return Ok(vec![]),
+        1 => {}
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidData,
+                "multidimensional arrays are not supported",
+            ))
+        }
+    }
+
+    // Skip the null flag, element type and lower bound
+    let length = read_i32(bytes, 12)?;
+    let mut offset = 20;
+
+    let mut elements = vec![];
+    for _ in 0..length {
+        let element_length = read_i32(bytes, offset)?;
+        offset += 4;
+
+        if element_length < 0 {
+            elements.push(None);
+            continue;
+        }
+
+        let end = offset + element_length as usize;
+        let element = bytes
+            .get(offset..end)
+            .ok_or_else(|| Error::new(ErrorKind::InvalidData, "invalid binary array"))?;
+        elements.push(Some(element.to_vec()));
+        offset = end;
+    }
+
+    Ok(elements)
+}
+
+/// Formats the binary representations of elements as a one-dimensional array in binary format
+pub fn format_array_binary(elements: &[Option<Vec<u8>>], element_type_oid: u32) -> Vec<u8> {
+    let mut bytes = vec![];
+
+    let dimensions: i32 = if elements.is_empty() { 0 } else { 1 };
+    let has_nulls = elements.iter().any(|element| element.is_none()) as i32;
+
+    bytes.extend_from_slice(&dimensions.to_be_bytes());
+    bytes.extend_from_slice(&has_nulls.to_be_bytes());
+    bytes.extend_from_slice(&element_type_oid.to_be_bytes());
+
+    if elements.is_empty() {
+        return bytes;
+    }
+
+    bytes.extend_from_slice(&(elements.len() as i32).to_be_bytes());
+    // Lower bound, postgres arrays start at 1 by default
+    bytes.extend_from_slice(&1_i32.to_be_bytes());
+
+    for element in elements {
+        match element {
+            Some(element) => {
+                bytes.extend_from_slice(&(element.len() as i32).to_be_bytes());
+                bytes.extend_from_slice(element);
+            }
+            None => bytes.extend_from_slice(&(-1_i32).to_be_bytes()),
+        }
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_array() {
+        assert_eq!(
+            vec![Some("1".to_string()), None, Some("3".to_string())],
+            parse_array_text("{1,NULL,3}").unwrap()
+        );
+        assert_eq!(
+            vec![Some("a b".to_string()), Some("say \"hi\"".to_string())],
+            parse_array_text(r#"{"a b","say \"hi\""}"#).unwrap()
+        );
+        assert!(parse_array_text("{}").unwrap().is_empty());
+        assert!(parse_array_text("{{1,2},{3,4}}").is_err());
+        assert!(parse_array_text("1,2").is_err());
+    }
+
+    #[test]
+    fn test_format_array() {
+        let elements = vec![
+            Some("plain".to_string()),
+            Some("a b".to_string()),
+            Some("NULL".to_string()),
+            Some("".to_string()),
+            None,
+        ];
+
+        let text = format_array_text(&elements);
+        assert_eq!(r#"{plain,"a b","NULL","",NULL}"#, text);
+        assert_eq!(elements, parse_array_text(&text).unwrap());
+        assert_eq!("{}", format_array_text(&[]));
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let elements = vec![Some(vec![0, 0, 0, 1]), None, Some(vec![0, 0, 0, 3])];
+
+        let bytes = format_array_binary(&elements, 23);
+        assert_eq!(elements, parse_array_binary(&bytes).unwrap());
+
+        let empty = format_array_binary(&[], 23);
+        assert_eq!(12, empty.len());
+        assert!(parse_array_binary(&empty).unwrap().is_empty());
+    }
+}
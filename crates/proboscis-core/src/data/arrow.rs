@@ -1,18 +1,45 @@
+use crate::data::array::{
+    format_array_binary, format_array_text, parse_array_binary, parse_array_text,
+};
+use crate::data::bytes::{format_bytea_text, format_uuid_text, parse_bytea_text, parse_uuid_text};
+use crate::data::field::{
+    extension_of_field, format_of_field, is_numeric_text_field, postgres_type_for_arrow_type,
+    ARRAY_ELEMENT_FIELD_NAME, BINARY_FORMAT, TEXT_FORMAT,
+};
+use crate::data::numeric::{
+    format_numeric_binary, format_numeric_text, numeric_binary_to_text, numeric_text_to_binary,
+    parse_numeric_binary, parse_numeric_text,
+};
+use crate::data::primitive::{
+    format_bool_text, format_float_text, parse_bool_text, parse_float_text,
+};
+use crate::data::registry::{mapping_for_field, TypeMapping};
+use crate::data::temporal::{
+    format_date_binary, format_date_text, format_time_binary, format_time_text,
+    format_timestamp_binary, format_timestamp_text, format_timestamptz_text, parse_date_binary,
+    parse_date_text, parse_time_binary, parse_time_text, parse_timestamp_binary,
+    parse_timestamp_text, parse_timestamptz_text,
+};
 use arrow::array::{
-    as_primitive_array, make_array, ArrayData, BooleanArray, FixedSizeBinaryArray, Float32Array,
-    Float64Array, ListArray, UInt16Array, UInt32Array, UInt64Array,
+    as_primitive_array, make_array, ArrayData, BinaryArray, BooleanArray, Date32Array,
+    DecimalArray, DecimalBuilder, FixedSizeBinaryArray, Float32Array, Float64Array, ListArray,
+    Time64MicrosecondArray, TimestampMicrosecondArray, UInt16Array, UInt32Array, UInt64Array,
 };
 use arrow::array::{Array, GenericListArray, UInt8Array};
 use arrow::array::{ArrayRef, GenericStringArray, Int16Array, Int32Array, Int64Array, Int8Array};
 use arrow::buffer::{Buffer, MutableBuffer};
-use arrow::datatypes::{DataType, Schema, ToByteSlice, UInt8Type};
+use arrow::datatypes::{DataType, Schema, TimeUnit, ToByteSlice, UInt8Type};
 use arrow::error::ArrowError;
 use arrow::record_batch::RecordBatch;
-use byteorder::{BigEndian, ByteOrder, ReadBytesExt, WriteBytesExt};
-use proboscis_postgres_protocol::message::{DataRow, RowDescription};
+use arrow::util::bit_util;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+use proboscis_postgres_protocol::message::{BindParameter, DataRow, RowDescription};
 use std::convert::TryFrom;
 use std::{sync::Arc, vec};
 
+/// Number of rows resolvers put into a single record batch by default
+pub const DEFAULT_BATCH_SIZE: usize = 1024;
+
 macro_rules! create_numerical_column_data_to_array_function {
     ($func_name:ident, $Array:ident, $type:ty, $byte_count:literal, $read_closure:tt) => {
         #[allow(clippy::redundant_closure_call)]
@@ -40,22 +67,6 @@ macro_rules! create_numerical_column_data_to_array_function {
     };
 }
 
-create_numerical_column_data_to_array_function!(
-    column_data_to_array_f32,
-    Float32Array,
-    f32,
-    4,
-    (|buffer: &[u8]| -> std::io::Result<f32> { Ok(BigEndian::read_f32(buffer)) })
-);
-
-create_numerical_column_data_to_array_function!(
-    column_data_to_array_f64,
-    Float64Array,
-    f64,
-    8,
-    (|buffer: &[u8]| -> std::io::Result<f64> { Ok(BigEndian::read_f64(buffer)) })
-);
-
 create_numerical_column_data_to_array_function!(
     column_data_to_array_i8,
     Int8Array,
@@ -115,35 +126,266 @@ create_numerical_column_data_to_array_function!(
     (|mut buffer: &[u8]| -> std::io::Result<u64> { buffer.read_u64::<BigEndian>() })
 );
 
+create_numerical_column_data_to_array_function!(
+    column_data_to_array_f32,
+    Float32Array,
+    f32,
+    4,
+    (|mut buffer: &[u8]| -> std::io::Result<f32> { buffer.read_f32::<BigEndian>() })
+);
+create_numerical_column_data_to_array_function!(
+    column_data_to_array_f64,
+    Float64Array,
+    f64,
+    8,
+    (|mut buffer: &[u8]| -> std::io::Result<f64> { buffer.read_f64::<BigEndian>() })
+);
+
+fn arrow_to_io_error(err: ArrowError) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
+
+fn invalid_data<E>(err: E) -> std::io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    std::io::Error::new(std::io::ErrorKind::InvalidData, err)
+}
+
+fn parse_text_column<T>(
+    data: &[Option<Vec<u8>>],
+    parse: impl Fn(&str) -> std::io::Result<T>,
+) -> std::io::Result<Vec<Option<T>>> {
+    data.iter()
+        .map(|d| {
+            d.as_ref()
+                .map(|d| parse(std::str::from_utf8(d).map_err(invalid_data)?))
+                .transpose()
+        })
+        .collect()
+}
+
+fn parse_binary_column<T>(
+    data: &[Option<Vec<u8>>],
+    parse: impl Fn(&[u8]) -> std::io::Result<T>,
+) -> std::io::Result<Vec<Option<T>>> {
+    data.iter()
+        .map(|d| d.as_ref().map(|d| parse(d)).transpose())
+        .collect()
+}
+
+fn parse_column<T>(
+    data: &[Option<Vec<u8>>],
+    format: i16,
+    parse_text: impl Fn(&str) -> std::io::Result<T>,
+    parse_binary: impl Fn(&[u8]) -> std::io::Result<T>,
+) -> std::io::Result<Vec<Option<T>>> {
+    match format {
+        BINARY_FORMAT => parse_binary_column(data, parse_binary),
+        _ => parse_text_column(data, parse_text),
+    }
+}
+
+fn parse_integer_text<T: std::str::FromStr>(text: &str) -> std::io::Result<T> {
+    text.parse()
+        .map_err(|_| invalid_data(format!("invalid integer: {}", text)))
+}
+
+fn column_data_to_array_decimal(
+    data: &[Option<Vec<u8>>],
+    precision: usize,
+    scale: usize,
+    format: i16,
+) -> std::io::Result<ArrayRef> {
+    let values = parse_column(
+        data,
+        format,
+        |text| parse_numeric_text(text, scale),
+        |bytes| parse_numeric_binary(bytes, scale),
+    )?;
+
+    let mut builder = DecimalBuilder::new(data.len(), precision, scale);
+    for value in values {
+        match value {
+            Some(value) => builder.append_value(value).map_err(arrow_to_io_error)?,
+            None => builder.append_null().map_err(arrow_to_io_error)?,
+        }
+    }
+
+    Ok(Arc::new(builder.finish()))
+}
+
+/// Numerics that don't fit into a decimal are kept in their text representation
+fn column_data_to_numeric_text_array(
+    data: &[Option<Vec<u8>>],
+    format: i16,
+) -> std::io::Result<ArrayRef> {
+    let values = parse_column(
+        data,
+        format,
+        |text| Ok(text.to_string()),
+        numeric_binary_to_text,
+    )?;
+
+    Ok(Arc::new(
+        values.into_iter().collect::<GenericStringArray<i64>>(),
+    ))
+}
+
+fn empty_fixed_size_binary_array(size: i32) -> FixedSizeBinaryArray {
+    let array_data = ArrayData::new(
+        DataType::FixedSizeBinary(size),
+        0,
+        None,
+        Some(MutableBuffer::from_len_zeroed(0).into()),
+        0,
+        vec![MutableBuffer::from_len_zeroed(0).into()],
+        vec![],
+    );
+
+    FixedSizeBinaryArray::from(array_data)
+}
+
+fn column_data_to_list_array(
+    data: &[Option<Vec<u8>>],
+    field: &arrow::datatypes::Field,
+    format: i16,
+) -> std::io::Result<ArrayRef> {
+    let arrays = parse_column(
+        data,
+        format,
+        |text| {
+            Ok(parse_array_text(text)?
+                .into_iter()
+                .map(|element| element.map(String::into_bytes))
+                .collect())
+        },
+        parse_array_binary,
+    )?;
+
+    let mut offsets: Vec<i32> = vec![0];
+    let mut null_buffer = MutableBuffer::new_null(data.len());
+    let mut elements = vec![];
+
+    for (index, array) in arrays.into_iter().enumerate() {
+        if let Some(array) = array {
+            bit_util::set_bit(null_buffer.as_slice_mut(), index);
+            elements.extend(array);
+        }
+
+        offsets.push(elements.len() as i32);
+    }
+
+    // Elements are in the same representation as the array itself
+    let values = if is_numeric_text_field(field) {
+        column_data_to_numeric_text_array(&elements, format)?
+    } else {
+        column_data_to_array(&elements, field.data_type(), format)?
+    };
+
+    let list_data = ArrayData::builder(DataType::List(Box::new(field.clone())))
+        .len(data.len())
+        .add_buffer(Buffer::from(offsets.to_byte_slice()))
+        .add_child_data(values.data().clone())
+        .null_bit_buffer(null_buffer.into())
+        .build();
+
+    Ok(make_array(list_data))
+}
+
 fn column_data_to_array(
     data: &[Option<Vec<u8>>],
     data_type: &DataType,
+    format: i16,
 ) -> std::io::Result<ArrayRef> {
-    match data_type {
-        DataType::Int8 => column_data_to_array_i8(data),
-        DataType::Int16 => column_data_to_array_i16(data),
-        DataType::Int32 => column_data_to_array_i32(data),
-        DataType::Int64 => column_data_to_array_i64(data),
+    match (data_type, format) {
+        // "char" is a single byte in both representations
+        (DataType::Int8, _) => column_data_to_array_i8(data),
+        (DataType::UInt8, _) => column_data_to_array_u8(data),
+
+        (DataType::Int16, BINARY_FORMAT) => column_data_to_array_i16(data),
+        (DataType::Int32, BINARY_FORMAT) => column_data_to_array_i32(data),
+        (DataType::Int64, BINARY_FORMAT) => column_data_to_array_i64(data),
+        (DataType::UInt16, BINARY_FORMAT) => column_data_to_array_u16(data),
+        (DataType::UInt32, BINARY_FORMAT) => column_data_to_array_u32(data),
+        (DataType::UInt64, BINARY_FORMAT) => column_data_to_array_u64(data),
+        (DataType::Float32, BINARY_FORMAT) => column_data_to_array_f32(data),
+        (DataType::Float64, BINARY_FORMAT) => column_data_to_array_f64(data),
 
-        DataType::UInt8 => column_data_to_array_u8(data),
-        DataType::UInt16 => column_data_to_array_u16(data),
-        DataType::UInt32 => column_data_to_array_u32(data),
-        DataType::UInt64 => column_data_to_array_u64(data),
+        (DataType::Int16, _) => Ok(Arc::new(Int16Array::from(parse_text_column(
+            data,
+            parse_integer_text,
+        )?))),
+        (DataType::Int32, _) => Ok(Arc::new(Int32Array::from(parse_text_column(
+            data,
+            parse_integer_text,
+        )?))),
+        (DataType::Int64, _) => Ok(Arc::new(Int64Array::from(parse_text_column(
+            data,
+            parse_integer_text,
+        )?))),
+        (DataType::UInt16, _) => Ok(Arc::new(UInt16Array::from(parse_text_column(
+            data,
+            parse_integer_text,
+        )?))),
+        (DataType::UInt32, _) => Ok(Arc::new(UInt32Array::from(parse_text_column(
+            data,
+            parse_integer_text,
+        )?))),
+        (DataType::UInt64, _) => Ok(Arc::new(UInt64Array::from(parse_text_column(
+            data,
+            parse_integer_text,
+        )?))),
+        (DataType::Float32, _) => Ok(Arc::new(Float32Array::from(parse_text_column(
+            data,
+            |text| Ok(parse_float_text(text)? as f32),
+        )?))),
+        (DataType::Float64, _) => Ok(Arc::new(Float64Array::from(parse_text_column(
+            data,
+            parse_float_text,
+        )?))),
 
-        DataType::Float32 => column_data_to_array_f32(data),
-        DataType::Float64 => column_data_to_array_f64(data),
+        (DataType::Decimal(precision, scale), _) => {
+            column_data_to_array_decimal(data, *precision, *scale, format)
+        }
 
-        DataType::Utf8 => Ok(Arc::new(
+        (DataType::Date32, _) => Ok(Arc::new(Date32Array::from(parse_column(
+            data,
+            format,
+            parse_date_text,
+            parse_date_binary,
+        )?))),
+        (DataType::Time64(TimeUnit::Microsecond), _) => Ok(Arc::new(Time64MicrosecondArray::from(
+            parse_column(data, format, parse_time_text, parse_time_binary)?,
+        ))),
+        (DataType::Timestamp(TimeUnit::Microsecond, None), _) => {
+            Ok(Arc::new(TimestampMicrosecondArray::from_opt_vec(
+                parse_column(data, format, parse_timestamp_text, parse_timestamp_binary)?,
+                None,
+            )))
+        }
+        (DataType::Timestamp(TimeUnit::Microsecond, Some(timezone)), _) => {
+            Ok(Arc::new(TimestampMicrosecondArray::from_opt_vec(
+                parse_column(data, format, parse_timestamptz_text, parse_timestamp_binary)?,
+                Some(timezone.clone()),
+            )))
+        }
+
+        // Text is sent as is in both representations
+        (DataType::Utf8, _) => Ok(Arc::new(
             data.iter()
                 .map(|d| d.as_ref().map(|d| String::from_utf8(d.to_vec()).unwrap()))
                 .collect::<GenericStringArray<i32>>(),
         )),
-        DataType::LargeUtf8 => Ok(Arc::new(
+        (DataType::LargeUtf8, _) => Ok(Arc::new(
             data.iter()
                 .map(|d| d.as_ref().map(|d| String::from_utf8(d.to_vec()).unwrap()))
                 .collect::<GenericStringArray<i64>>(),
         )),
-        DataType::List(field) => {
+        (DataType::List(field), _) if field.name() == ARRAY_ELEMENT_FIELD_NAME => {
+            column_data_to_list_array(data, field, format)
+        }
+        (DataType::List(field), _) => {
             let data_array: Vec<Option<Vec<Option<u8>>>> = data
                 .iter()
                 .map(|d| d.as_ref().map(|d| d.iter().map(|d| Some(*d)).collect()))
@@ -161,24 +403,27 @@ fn column_data_to_array(
 
             Ok(make_array(list_data))
         }
-        DataType::Boolean => Ok(Arc::new(BooleanArray::from(
-            data.iter()
-                .map(|d| d.as_ref().map(|d| d == &[0]))
-                .collect::<Vec<Option<bool>>>(),
-        ))),
-        DataType::FixedSizeBinary(size) => {
+        (DataType::Boolean, _) => Ok(Arc::new(BooleanArray::from(parse_column(
+            data,
+            format,
+            parse_bool_text,
+            |bytes| Ok(bytes.first().map_or(false, |byte| *byte != 0)),
+        )?))),
+        (DataType::FixedSizeBinary(16), _) => {
             if data.is_empty() {
-                let array_data = ArrayData::new(
-                    DataType::FixedSizeBinary(*size as i32),
-                    0,
-                    None,
-                    Some(MutableBuffer::from_len_zeroed(0).into()),
-                    0,
-                    vec![MutableBuffer::from_len_zeroed(0).into()],
-                    vec![],
-                );
+                return Ok(Arc::new(empty_fixed_size_binary_array(16)));
+            }
+
+            let values = parse_column(data, format, parse_uuid_text, |bytes| Ok(bytes.to_vec()))?;
 
-                return Ok(Arc::new(FixedSizeBinaryArray::from(array_data)));
+            Ok(Arc::new(
+                FixedSizeBinaryArray::try_from_sparse_iter(values.into_iter())
+                    .map_err(arrow_to_io_error)?,
+            ))
+        }
+        (DataType::FixedSizeBinary(size), _) => {
+            if data.is_empty() {
+                return Ok(Arc::new(empty_fixed_size_binary_array(*size)));
             }
 
             return Ok(Arc::new(
@@ -192,23 +437,51 @@ fn column_data_to_array(
                 .unwrap(),
             ));
         }
+        (DataType::Binary, _) => {
+            let values = parse_column(data, format, parse_bytea_text, |bytes| Ok(bytes.to_vec()))?;
+
+            Ok(Arc::new(BinaryArray::from_opt_vec(
+                values
+                    .iter()
+                    .map(|value| value.as_deref())
+                    .collect::<Vec<Option<&[u8]>>>(),
+            )))
+        }
         _ => todo!("{}", data_type),
     }
 }
 
+/// Converts the value of a bind parameter into a single element arrow array
+pub fn bind_parameter_to_array(
+    parameter: &BindParameter,
+    data_type: &DataType,
+) -> std::io::Result<ArrayRef> {
+    match parameter {
+        BindParameter::Text(text) => {
+            column_data_to_array(&[Some(text.as_bytes().to_vec())], data_type, TEXT_FORMAT)
+        }
+        BindParameter::Binary(bytes) => {
+            column_data_to_array(&[Some(bytes.clone())], data_type, BINARY_FORMAT)
+        }
+    }
+}
+
 fn protocol_rows_to_arrow_columns(
     schema: &Schema,
     rows: Vec<Vec<Option<Vec<u8>>>>,
+) -> std::io::Result<Vec<ArrayRef>> {
+    let formats: Vec<i16> = schema.fields().iter().map(format_of_field).collect();
+    decode_rows(schema, rows, &formats)
+}
+
+fn decode_rows(
+    schema: &Schema,
+    rows: Vec<Vec<Option<Vec<u8>>>>,
+    formats: &[i16],
 ) -> std::io::Result<Vec<ArrayRef>> {
     let mut columns_data: Vec<Vec<Option<Vec<u8>>>> =
         schema.fields().iter().map(|_| vec![]).collect();
 
-    let columns_data_types: Vec<DataType> = schema
-        .fields()
-        .iter()
-        .map(|f| f.data_type().clone())
-        .collect();
-
     for row in rows {
         for (index, field) in row.iter().enumerate() {
             columns_data[index].push(field.clone());
@@ -216,13 +489,35 @@ fn protocol_rows_to_arrow_columns(
     }
 
     let mut result = vec![];
-    for (column_data, data_type) in columns_data.iter().zip(columns_data_types.iter()) {
-        result.push(column_data_to_array(column_data, data_type)?)
+    for ((column_data, field), format) in columns_data
+        .iter()
+        .zip(schema.fields().iter())
+        .zip(formats.iter().copied())
+    {
+        let column = match mapping_for_field(field) {
+            Some(mapping) => mapping.decode(column_data, format)?,
+            None if is_numeric_text_field(field) => {
+                column_data_to_numeric_text_array(column_data, format)?
+            }
+            None => column_data_to_array(column_data, field.data_type(), format)?,
+        };
+
+        result.push(column)
     }
 
     Ok(result)
 }
 
+/// The rows with every value in text format as a batch of the schema, regardless of the
+/// formats of its fields
+pub fn text_rows_to_record_batch(
+    schema: Arc<Schema>,
+    rows: Vec<Vec<Option<Vec<u8>>>>,
+) -> Result<RecordBatch, ArrowError> {
+    let columns = decode_rows(&schema, rows, &vec![TEXT_FORMAT; schema.fields().len()])?;
+    RecordBatch::try_new(schema, columns)
+}
+
 pub fn protocol_fields_to_schema(
     fields: &[proboscis_postgres_protocol::message::Field],
 ) -> Result<Schema, &str> {
@@ -253,107 +548,367 @@ pub fn simple_query_response_to_record_batch(
     RecordBatch::try_new(Arc::new(schema), columns)
 }
 
+/// Converts the rows of a query response into record batches of at most `batch_size` rows.
+/// The result always contains at least one, possibly empty, batch, so the schema is known.
+pub fn simple_query_response_to_record_batches(
+    fields: &[proboscis_postgres_protocol::message::Field],
+    data: &[DataRow],
+    batch_size: usize,
+) -> Result<Vec<RecordBatch>, ArrowError> {
+    let schema = Arc::new(
+        protocol_fields_to_schema(fields).map_err(|err| ArrowError::CastError(err.to_string()))?,
+    );
+
+    if data.is_empty() {
+        let columns = protocol_rows_to_arrow_columns(&schema, vec![])?;
+        return Ok(vec![RecordBatch::try_new(schema, columns)?]);
+    }
+
+    let mut batches = vec![];
+    for chunk in data.chunks(batch_size.max(1)) {
+        let protocol_row_data = chunk
+            .iter()
+            .map(|DataRow { field_data }| field_data.clone())
+            .collect();
+
+        let columns = protocol_rows_to_arrow_columns(&schema, protocol_row_data)?;
+        batches.push(RecordBatch::try_new(schema.clone(), columns)?);
+    }
+
+    Ok(batches)
+}
+
+fn serialize_text_cell(column: &ArrayRef, row_index: usize) -> std::io::Result<Vec<u8>> {
+    let text = match column.data_type() {
+        DataType::Int16 => {
+            let values: &Int16Array = as_primitive_array(column);
+            values.value(row_index).to_string()
+        }
+        DataType::Int32 => {
+            let values: &Int32Array = as_primitive_array(column);
+            values.value(row_index).to_string()
+        }
+        DataType::Int64 => {
+            let values: &Int64Array = as_primitive_array(column);
+            values.value(row_index).to_string()
+        }
+        DataType::UInt16 => {
+            let values: &UInt16Array = as_primitive_array(column);
+            values.value(row_index).to_string()
+        }
+        DataType::UInt32 => {
+            let values: &UInt32Array = as_primitive_array(column);
+            values.value(row_index).to_string()
+        }
+        DataType::UInt64 => {
+            let values: &UInt64Array = as_primitive_array(column);
+            values.value(row_index).to_string()
+        }
+        DataType::Float32 => {
+            let values: &Float32Array = as_primitive_array(column);
+            format_float_text(values.value(row_index))
+        }
+        DataType::Float64 => {
+            let values: &Float64Array = as_primitive_array(column);
+            format_float_text(values.value(row_index))
+        }
+        DataType::Boolean => {
+            let values = column.as_any().downcast_ref::<BooleanArray>().unwrap();
+            format_bool_text(values.value(row_index)).to_string()
+        }
+        DataType::Decimal(_, scale) => {
+            let values = column.as_any().downcast_ref::<DecimalArray>().unwrap();
+            format_numeric_text(values.value(row_index), *scale)
+        }
+        DataType::Date32 => {
+            let values: &Date32Array = as_primitive_array(column);
+            format_date_text(values.value(row_index))
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            let values: &Time64MicrosecondArray = as_primitive_array(column);
+            format_time_text(values.value(row_index))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, timezone) => {
+            let values: &TimestampMicrosecondArray = as_primitive_array(column);
+            match timezone {
+                Some(_) => format_timestamptz_text(values.value(row_index)),
+                None => format_timestamp_text(values.value(row_index)),
+            }
+        }
+        DataType::FixedSizeBinary(16) => {
+            let values = column
+                .as_any()
+                .downcast_ref::<FixedSizeBinaryArray>()
+                .unwrap();
+            format_uuid_text(values.value(row_index))
+        }
+        DataType::Binary => {
+            let values = column.as_any().downcast_ref::<BinaryArray>().unwrap();
+            format_bytea_text(values.value(row_index))
+        }
+        DataType::List(field) if field.name() == ARRAY_ELEMENT_FIELD_NAME => {
+            let values = column
+                .as_any()
+                .downcast_ref::<GenericListArray<i32>>()
+                .unwrap();
+
+            let row_value = values.value(row_index);
+
+            let mut elements = vec![];
+            for element_index in 0..row_value.len() {
+                let element = serialize_cell(&row_value, element_index, TEXT_FORMAT)?
+                    .map(String::from_utf8)
+                    .transpose()
+                    .map_err(invalid_data)?;
+                elements.push(element);
+            }
+
+            format_array_text(&elements)
+        }
+        _ => return serialize_raw_cell(column, row_index),
+    };
+
+    Ok(text.into_bytes())
+}
+
+fn serialize_binary_cell(column: &ArrayRef, row_index: usize) -> std::io::Result<Vec<u8>> {
+    let mut cell: Vec<u8> = vec![];
+
+    match column.data_type() {
+        DataType::Int16 => {
+            let values: &Int16Array = as_primitive_array(column);
+            cell.write_i16::<BigEndian>(values.value(row_index))?
+        }
+        DataType::Int32 => {
+            let values: &Int32Array = as_primitive_array(column);
+            cell.write_i32::<BigEndian>(values.value(row_index))?
+        }
+        DataType::Int64 => {
+            let values: &Int64Array = as_primitive_array(column);
+            cell.write_i64::<BigEndian>(values.value(row_index))?
+        }
+        DataType::UInt16 => {
+            let values: &UInt16Array = as_primitive_array(column);
+            cell.write_u16::<BigEndian>(values.value(row_index))?
+        }
+        DataType::UInt32 => {
+            let values: &UInt32Array = as_primitive_array(column);
+            cell.write_u32::<BigEndian>(values.value(row_index))?
+        }
+        DataType::UInt64 => {
+            let values: &UInt64Array = as_primitive_array(column);
+            cell.write_u64::<BigEndian>(values.value(row_index))?
+        }
+        DataType::Float32 => {
+            let values: &Float32Array = as_primitive_array(column);
+            cell.write_f32::<BigEndian>(values.value(row_index))?
+        }
+        DataType::Float64 => {
+            let values: &Float64Array = as_primitive_array(column);
+            cell.write_f64::<BigEndian>(values.value(row_index))?
+        }
+        DataType::Boolean => {
+            let values = column.as_any().downcast_ref::<BooleanArray>().unwrap();
+            cell.write_u8(values.value(row_index) as u8)?
+        }
+        DataType::Decimal(_, scale) => {
+            let values = column.as_any().downcast_ref::<DecimalArray>().unwrap();
+            cell = format_numeric_binary(values.value(row_index), *scale)
+        }
+        DataType::Date32 => {
+            let values: &Date32Array = as_primitive_array(column);
+            cell = format_date_binary(values.value(row_index))
+        }
+        DataType::Time64(TimeUnit::Microsecond) => {
+            let values: &Time64MicrosecondArray = as_primitive_array(column);
+            cell = format_time_binary(values.value(row_index))
+        }
+        DataType::Timestamp(TimeUnit::Microsecond, _) => {
+            let values: &TimestampMicrosecondArray = as_primitive_array(column);
+            cell = format_timestamp_binary(values.value(row_index))
+        }
+        DataType::FixedSizeBinary(16) => {
+            let values = column
+                .as_any()
+                .downcast_ref::<FixedSizeBinaryArray>()
+                .unwrap();
+            cell.extend_from_slice(values.value(row_index))
+        }
+        DataType::Binary => {
+            let values = column.as_any().downcast_ref::<BinaryArray>().unwrap();
+            cell.extend_from_slice(values.value(row_index))
+        }
+        DataType::List(field) if field.name() == ARRAY_ELEMENT_FIELD_NAME => {
+            let values = column
+                .as_any()
+                .downcast_ref::<GenericListArray<i32>>()
+                .unwrap();
+
+            let row_value = values.value(row_index);
+
+            let mut elements = vec![];
+            for element_index in 0..row_value.len() {
+                let element = if is_numeric_text_field(field) {
+                    serialize_numeric_text_cell(&row_value, element_index, BINARY_FORMAT)?
+                } else {
+                    serialize_cell(&row_value, element_index, BINARY_FORMAT)?
+                };
+                elements.push(element);
+            }
+
+            let element_type =
+                postgres_type_for_arrow_type(field.data_type(), extension_of_field(field))
+                    .ok_or_else(|| {
+                        invalid_data(format!(
+                            "arrays of {} can't be serialized",
+                            field.data_type()
+                        ))
+                    })?;
+            cell = format_array_binary(&elements, element_type.oid())
+        }
+        _ => return serialize_raw_cell(column, row_index),
+    }
+
+    Ok(cell)
+}
+
+/// Serializes types whose representation doesn't depend on the format
+fn serialize_raw_cell(column: &ArrayRef, row_index: usize) -> std::io::Result<Vec<u8>> {
+    let mut cell: Vec<u8> = vec![];
+
+    match column.data_type() {
+        DataType::Int8 => {
+            let values: &Int8Array = as_primitive_array(column);
+            cell.write_i8(values.value(row_index))?
+        }
+        DataType::UInt8 => {
+            let values: &UInt8Array = as_primitive_array(column);
+            cell.write_u8(values.value(row_index))?
+        }
+        DataType::LargeUtf8 => {
+            let values = &column
+                .as_any()
+                .downcast_ref::<GenericStringArray<i64>>()
+                .unwrap();
+            cell.extend_from_slice(values.value(row_index).as_bytes())
+        }
+        DataType::Utf8 => {
+            let values = &column
+                .as_any()
+                .downcast_ref::<GenericStringArray<i32>>()
+                .unwrap();
+            cell.extend_from_slice(values.value(row_index).as_bytes())
+        }
+        DataType::List(_) => {
+            let values = &column
+                .as_any()
+                .downcast_ref::<GenericListArray<i32>>()
+                .unwrap();
+
+            let row_value = values.value(row_index);
+
+            let value = row_value
+                .as_any()
+                .downcast_ref::<UInt8Array>()
+                .unwrap()
+                .values();
+
+            cell.extend_from_slice(value)
+        }
+        DataType::FixedSizeBinary(_) => {
+            let values = &column
+                .as_any()
+                .downcast_ref::<FixedSizeBinaryArray>()
+                .unwrap();
+
+            let mut row_value = values.value(row_index).to_vec();
+            row_value.reverse();
+            let end = row_value.iter_mut().take_while(|p| **p == 0).count();
+            row_value.reverse();
+
+            cell.extend_from_slice(&row_value[0..row_value.len() - end])
+        }
+        _ => todo!("{:?}", column.data_type()),
+    }
+
+    Ok(cell)
+}
+
+fn serialize_cell(
+    column: &ArrayRef,
+    row_index: usize,
+    format: i16,
+) -> std::io::Result<Option<Vec<u8>>> {
+    if column.is_null(row_index) {
+        return Ok(None);
+    }
+
+    let cell = match format {
+        BINARY_FORMAT => serialize_binary_cell(column, row_index)?,
+        _ => serialize_text_cell(column, row_index)?,
+    };
+
+    Ok(Some(cell))
+}
+
+/// Serializes numerics kept in their text representation, converting them if the binary
+/// representation is requested
+fn serialize_numeric_text_cell(
+    column: &ArrayRef,
+    row_index: usize,
+    format: i16,
+) -> std::io::Result<Option<Vec<u8>>> {
+    let cell = serialize_cell(column, row_index, TEXT_FORMAT)?;
+
+    match format {
+        BINARY_FORMAT => cell
+            .map(|text| numeric_text_to_binary(std::str::from_utf8(&text).map_err(invalid_data)?))
+            .transpose(),
+        _ => Ok(cell),
+    }
+}
+
 pub fn serialize_record_batch_to_data_rows(batch: &RecordBatch) -> std::io::Result<Vec<DataRow>> {
+    let formats: Vec<i16> = batch
+        .schema()
+        .fields()
+        .iter()
+        .map(format_of_field)
+        .collect();
+    serialize_rows(batch, &formats)
+}
+
+/// The rows with every value in text format, regardless of the formats of the fields
+pub fn serialize_record_batch_to_text_rows(batch: &RecordBatch) -> std::io::Result<Vec<DataRow>> {
+    serialize_rows(batch, &vec![TEXT_FORMAT; batch.num_columns()])
+}
+
+fn serialize_rows(batch: &RecordBatch, formats: &[i16]) -> std::io::Result<Vec<DataRow>> {
+    let schema = batch.schema();
+    let mappings: Vec<Option<Arc<dyn TypeMapping>>> =
+        schema.fields().iter().map(mapping_for_field).collect();
+    let numeric_text: Vec<bool> = schema.fields().iter().map(is_numeric_text_field).collect();
+
     let mut result = vec![];
 
     for row_index in 0..batch.num_rows() {
         let mut row_data = vec![];
 
-        for column in batch.columns() {
-            let mut cell: Vec<u8> = vec![];
-            match column.data_type() {
-                DataType::Int8 => {
-                    let values: &Int8Array = as_primitive_array(column);
-                    cell.write_i8(values.value(row_index))?
-                }
-                DataType::Int16 => {
-                    let values: &Int16Array = as_primitive_array(column);
-                    cell.write_i16::<BigEndian>(values.value(row_index))?
-                }
-                DataType::Int32 => {
-                    let values: &Int32Array = as_primitive_array(column);
-                    cell.write_i32::<BigEndian>(values.value(row_index))?
-                }
-                DataType::Int64 => {
-                    let values: &Int64Array = as_primitive_array(column);
-                    cell.write_i64::<BigEndian>(values.value(row_index))?
-                }
-                DataType::UInt8 => {
-                    let values: &UInt8Array = as_primitive_array(column);
-                    cell.write_u8(values.value(row_index))?
-                }
-                DataType::UInt16 => {
-                    let values: &UInt16Array = as_primitive_array(column);
-                    cell.write_u16::<BigEndian>(values.value(row_index))?
-                }
-                DataType::UInt32 => {
-                    let values: &UInt32Array = as_primitive_array(column);
-                    cell.write_u32::<BigEndian>(values.value(row_index))?
-                }
-                DataType::UInt64 => {
-                    let values: &UInt64Array = as_primitive_array(column);
-                    cell.write_u64::<BigEndian>(values.value(row_index))?
-                }
-                DataType::Float32 => {
-                    let values: &Float32Array = as_primitive_array(column);
-                    cell.write_f32::<BigEndian>(values.value(row_index))?
-                }
-                DataType::Float64 => {
-                    let values: &Float64Array = as_primitive_array(column);
-                    cell.write_f64::<BigEndian>(values.value(row_index))?
-                }
-                DataType::LargeUtf8 => {
-                    let values = &column
-                        .as_any()
-                        .downcast_ref::<GenericStringArray<i64>>()
-                        .unwrap();
-                    cell.extend_from_slice(values.value(row_index).as_bytes())
-                }
-                DataType::Utf8 => {
-                    let values = &column
-                        .as_any()
-                        .downcast_ref::<GenericStringArray<i32>>()
-                        .unwrap();
-                    cell.extend_from_slice(values.value(row_index).as_bytes())
-                }
-                DataType::List(_) => {
-                    let values = &column
-                        .as_any()
-                        .downcast_ref::<GenericListArray<i32>>()
-                        .unwrap();
-
-                    let row_value = values.value(row_index);
-
-                    let value = row_value
-                        .as_any()
-                        .downcast_ref::<UInt8Array>()
-                        .unwrap()
-                        .values();
-
-                    cell.extend_from_slice(value)
-                }
-                DataType::Boolean => {
-                    let values = &column.as_any().downcast_ref::<BooleanArray>().unwrap();
-                    let boolean_value = values.value(row_index);
-                    let byte_value = if boolean_value { 1 } else { 0 };
-                    cell.extend_from_slice(&[byte_value])
-                }
-                DataType::FixedSizeBinary(_) => {
-                    let values = &column
-                        .as_any()
-                        .downcast_ref::<FixedSizeBinaryArray>()
-                        .unwrap();
-
-                    let mut row_value = values.value(row_index).to_vec();
-                    row_value.reverse();
-                    let end = row_value.iter_mut().take_while(|p| **p == 0).count();
-                    row_value.reverse();
-
-                    cell.extend_from_slice(&row_value[0..row_value.len() - end])
-                }
-                _ => todo!("{:?}", column.data_type()),
-            }
-            row_data.push(Some(cell))
+        for (((column, format), mapping), numeric_text) in batch
+            .columns()
+            .iter()
+            .zip(formats.iter())
+            .zip(mappings.iter())
+            .zip(numeric_text.iter())
+        {
+            let cell = match mapping {
+                Some(_) if column.is_null(row_index) => None,
+                Some(mapping) => Some(mapping.encode(column, row_index, *format)?),
+                None if *numeric_text => serialize_numeric_text_cell(column, row_index, *format)?,
+                None => serialize_cell(column, row_index, *format)?,
+            };
+
+            row_data.push(cell)
         }
 
         result.push(DataRow {
@@ -422,7 +977,7 @@ mod tests {
                 type_oid: 23,
                 type_length: 4,
                 type_modifier: -1,
-                format: 0,
+                format: 1,
             },
             proboscis_postgres_protocol::message::Field {
                 name: "a".to_string(),
@@ -462,4 +1017,481 @@ mod tests {
         assert_eq!(fields, deserialized_row_description.fields);
         assert_eq!(data, deserialized_data);
     }
+
+    #[test]
+    fn test_numeric_round_trip() {
+        let fields = vec![
+            proboscis_postgres_protocol::message::Field {
+                name: "price".to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid: 1700,
+                type_length: -1,
+                type_modifier: 655366,
+                format: 0,
+            },
+            proboscis_postgres_protocol::message::Field {
+                name: "ratio".to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid: 1700,
+                type_length: -1,
+                type_modifier: -1,
+                format: 0,
+            },
+        ];
+
+        let data = vec![
+            DataRow {
+                field_data: vec![Some(b"123.45".to_vec()), Some(b"0.125".to_vec())],
+            },
+            DataRow {
+                field_data: vec![Some(b"-0.50".to_vec()), Some(b"-42".to_vec())],
+            },
+        ];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        assert_eq!(
+            &DataType::Decimal(10, 2),
+            batch.schema().field(0).data_type()
+        );
+        assert_eq!(&DataType::LargeUtf8, batch.schema().field(1).data_type());
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+
+    #[test]
+    fn test_unconstrained_numeric_round_trip() {
+        let field = |name: &str, type_oid, format| proboscis_postgres_protocol::message::Field {
+            name: name.to_string(),
+            table_oid: 0,
+            column_number: 0,
+            type_oid,
+            type_length: -1,
+            type_modifier: -1,
+            format,
+        };
+
+        let fields = vec![
+            field("ratio", 1700, 0),
+            field("ratio", 1700, 1),
+            field("ratios", 1231, 1),
+        ];
+
+        let large = "123456789012345678901234567890.1234567890123456789";
+        let data = vec![
+            DataRow {
+                field_data: vec![
+                    Some(large.as_bytes().to_vec()),
+                    Some(numeric_text_to_binary(large).unwrap()),
+                    Some(format_array_binary(
+                        &[Some(numeric_text_to_binary("0.125").unwrap()), None],
+                        1700,
+                    )),
+                ],
+            },
+            DataRow {
+                field_data: vec![
+                    Some(b"NaN".to_vec()),
+                    Some(numeric_text_to_binary("-Infinity").unwrap()),
+                    None,
+                ],
+            },
+        ];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        let ratios = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<GenericStringArray<i64>>()
+            .unwrap();
+        assert_eq!(large, ratios.value(0));
+        assert_eq!("-Infinity", ratios.value(1));
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+
+    #[test]
+    fn test_temporal_round_trip() {
+        let field =
+            |name: &str, type_oid, type_length| proboscis_postgres_protocol::message::Field {
+                name: name.to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid,
+                type_length,
+                type_modifier: -1,
+                format: 0,
+            };
+
+        let fields = vec![
+            field("date", 1082, 4),
+            field("time", 1083, 8),
+            field("timestamp", 1114, 8),
+            field("timestamptz", 1184, 8),
+        ];
+
+        let data = vec![DataRow {
+            field_data: vec![
+                Some(b"2021-03-04".to_vec()),
+                Some(b"13:45:06.5".to_vec()),
+                Some(b"2021-03-04 13:45:06.123".to_vec()),
+                Some(b"2021-03-04 13:45:06+00".to_vec()),
+            ],
+        }];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+
+    #[test]
+    fn test_uuid_json_bytea_round_trip() {
+        let field =
+            |name: &str, type_oid, type_length| proboscis_postgres_protocol::message::Field {
+                name: name.to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid,
+                type_length,
+                type_modifier: -1,
+                format: 0,
+            };
+
+        let fields = vec![
+            field("id", 2950, 16),
+            field("document", 114, -1),
+            field("attributes", 3802, -1),
+            field("avatar", 17, -1),
+        ];
+
+        let data = vec![DataRow {
+            field_data: vec![
+                Some(b"a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11".to_vec()),
+                Some(br#"{"name": "Max"}"#.to_vec()),
+                Some(br#"{"age": 42}"#.to_vec()),
+                Some(b"\\xdeadbeef".to_vec()),
+            ],
+        }];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        assert_eq!(&DataType::Utf8, batch.schema().field(1).data_type());
+        assert_eq!(&DataType::Binary, batch.schema().field(3).data_type());
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+
+    #[test]
+    fn test_bool_float_round_trip() {
+        let field =
+            |name: &str, type_oid, type_length| proboscis_postgres_protocol::message::Field {
+                name: name.to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid,
+                type_length,
+                type_modifier: -1,
+                format: 0,
+            };
+
+        let fields = vec![
+            field("active", 16, -1),
+            field("ratio", 700, 4),
+            field("score", 701, 8),
+        ];
+
+        let data = vec![
+            DataRow {
+                field_data: vec![
+                    Some(b"t".to_vec()),
+                    Some(b"1.1".to_vec()),
+                    Some(b"-0.25".to_vec()),
+                ],
+            },
+            DataRow {
+                field_data: vec![Some(b"f".to_vec()), None, Some(b"Infinity".to_vec())],
+            },
+        ];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+
+    #[test]
+    fn test_array_round_trip() {
+        let field = |name: &str, type_oid| proboscis_postgres_protocol::message::Field {
+            name: name.to_string(),
+            table_oid: 0,
+            column_number: 0,
+            type_oid,
+            type_length: -1,
+            type_modifier: -1,
+            format: 0,
+        };
+
+        let fields = vec![
+            field("scores", 1007),
+            field("tags", 1009),
+            field("ids", 2951),
+        ];
+
+        let data = vec![
+            DataRow {
+                field_data: vec![
+                    Some(b"{1,NULL,3}".to_vec()),
+                    Some(br#"{a,"b c"}"#.to_vec()),
+                    Some(b"{a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11}".to_vec()),
+                ],
+            },
+            DataRow {
+                field_data: vec![Some(b"{}".to_vec()), None, Some(b"{}".to_vec())],
+            },
+        ];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        let scores = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<GenericListArray<i32>>()
+            .unwrap()
+            .value(0);
+        assert_eq!(3, scores.len());
+        assert_eq!(
+            3,
+            as_primitive_array::<arrow::datatypes::Int32Type>(&scores).value(2)
+        );
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+
+    #[test]
+    fn test_text_fallback_round_trip() {
+        let fields = vec![
+            proboscis_postgres_protocol::message::Field {
+                name: "code".to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid: 1042,
+                type_length: -1,
+                type_modifier: 14,
+                format: 0,
+            },
+            proboscis_postgres_protocol::message::Field {
+                name: "duration".to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid: 1186,
+                type_length: 16,
+                type_modifier: -1,
+                format: 0,
+            },
+        ];
+
+        let data = vec![DataRow {
+            field_data: vec![
+                Some(b"AB        ".to_vec()),
+                Some(b"1 day 02:00:00".to_vec()),
+            ],
+        }];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+        assert_eq!(&DataType::LargeUtf8, batch.schema().field(0).data_type());
+        assert_eq!(&DataType::LargeUtf8, batch.schema().field(1).data_type());
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+
+        // Their binary representation isn't known
+        let binary_fields = vec![proboscis_postgres_protocol::message::Field {
+            format: 1,
+            ..fields[1].clone()
+        }];
+        assert!(simple_query_response_to_record_batch(&binary_fields, &[]).is_err());
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let field = |name: &str, type_oid, type_length, type_modifier| {
+            proboscis_postgres_protocol::message::Field {
+                name: name.to_string(),
+                table_oid: 0,
+                column_number: 0,
+                type_oid,
+                type_length,
+                type_modifier,
+                format: 1,
+            }
+        };
+
+        let fields = vec![
+            field("id", 20, 8, -1),
+            field("score", 701, 8, -1),
+            field("active", 16, -1, -1),
+            field("price", 1700, -1, 655366),
+            field("created_at", 1114, 8, -1),
+            field("uuid", 2950, 16, -1),
+            field("scores", 1007, -1, -1),
+        ];
+
+        let data = vec![
+            DataRow {
+                field_data: vec![
+                    Some(42_i64.to_be_bytes().to_vec()),
+                    Some((-0.25_f64).to_be_bytes().to_vec()),
+                    Some(vec![1]),
+                    Some(format_numeric_binary(12345, 2)),
+                    Some(format_timestamp_binary(668_094_306_000_000)),
+                    Some(parse_uuid_text("a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11").unwrap()),
+                    Some(format_array_binary(
+                        &[Some(1_i32.to_be_bytes().to_vec()), None],
+                        23,
+                    )),
+                ],
+            },
+            DataRow {
+                field_data: vec![None, None, Some(vec![0]), None, None, None, None],
+            },
+        ];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        let prices = batch
+            .column(3)
+            .as_any()
+            .downcast_ref::<DecimalArray>()
+            .unwrap();
+        assert_eq!(12345, prices.value(0));
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+
+    #[test]
+    fn test_row_description_metadata_preserved() {
+        let fields = vec![
+            proboscis_postgres_protocol::message::Field {
+                name: "name".to_string(),
+                table_oid: 16394,
+                column_number: 2,
+                type_oid: 1043,
+                type_length: -1,
+                // varchar(255)
+                type_modifier: 259,
+                format: 0,
+            },
+            proboscis_postgres_protocol::message::Field {
+                name: "age".to_string(),
+                table_oid: 16394,
+                column_number: 3,
+                type_oid: 23,
+                type_length: 4,
+                type_modifier: -1,
+                format: 0,
+            },
+        ];
+
+        let batch = simple_query_response_to_record_batch(&fields, &[]).unwrap();
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        assert_eq!(fields, deserialized_row_description.fields);
+
+        // A transformation changing the type of a column must not report the original type
+        let mut transformed_age = Field::new("age", DataType::Utf8, false);
+        transformed_age.set_metadata(batch.schema().field(1).metadata().clone());
+        let transformed_schema =
+            Schema::new(vec![batch.schema().field(0).clone(), transformed_age]);
+
+        let transformed_row_description =
+            serialize_record_batch_schema_to_row_description(&transformed_schema);
+
+        assert_eq!(fields[0], transformed_row_description.fields[0]);
+        assert_eq!(1043, transformed_row_description.fields[1].type_oid);
+        assert_eq!(16394, transformed_row_description.fields[1].table_oid);
+        assert_eq!(3, transformed_row_description.fields[1].column_number);
+    }
+
+    #[test]
+    fn test_chunked_batches() {
+        let fields = vec![proboscis_postgres_protocol::message::Field {
+            name: "id".to_string(),
+            table_oid: 0,
+            column_number: 0,
+            type_oid: 23,
+            type_length: 4,
+            type_modifier: -1,
+            format: 0,
+        }];
+
+        let data: Vec<DataRow> = (0..5)
+            .map(|id| DataRow {
+                field_data: vec![Some(id.to_string().into_bytes())],
+            })
+            .collect();
+
+        let batches = simple_query_response_to_record_batches(&fields, &data, 2).unwrap();
+        assert_eq!(
+            vec![2, 2, 1],
+            batches
+                .iter()
+                .map(|batch| batch.num_rows())
+                .collect::<Vec<_>>()
+        );
+
+        let empty = simple_query_response_to_record_batches(&fields, &[], 2).unwrap();
+        assert_eq!(1, empty.len());
+        assert_eq!(0, empty[0].num_rows());
+    }
 }
@@ -0,0 +1,81 @@
+use std::io::{Error, ErrorKind};
+
+fn invalid(kind: &str, text: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid {}: {}", kind, text),
+    )
+}
+
+fn parse_hex(text: &str) -> Option<Vec<u8>> {
+    if text.len() % 2 != 0 || !text.is_ascii() {
+        return None;
+    }
+
+    (0..text.len())
+        .step_by(2)
+        .map(|index| u8::from_str_radix(&text[index..index + 2], 16).ok())
+        .collect()
+}
+
+fn format_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+/// Parses the canonical text representation of a uuid into its 16 bytes
+pub fn parse_uuid_text(text: &str) -> std::io::Result<Vec<u8>> {
+    let hex: String = text.chars().filter(|c| *c != '-').collect();
+
+    match parse_hex(&hex) {
+        Some(bytes) if bytes.len() == 16 => Ok(bytes),
+        _ => Err(invalid("uuid", text)),
+    }
+}
+
+pub fn format_uuid_text(bytes: &[u8]) -> String {
+    let hex = format_hex(bytes);
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[0..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..32]
+    )
+}
+
+/// Parses a bytea in hex format (`\x...`), which postgres uses by default
+pub fn parse_bytea_text(text: &str) -> std::io::Result<Vec<u8>> {
+    text.strip_prefix("\\x")
+        .and_then(parse_hex)
+        .ok_or_else(|| invalid("bytea", text))
+}
+
+pub fn format_bytea_text(bytes: &[u8]) -> String {
+    format!("\\x{}", format_hex(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_uuid() {
+        let text = "a0eebc99-9c0b-4ef8-bb6d-6bb9bd380a11";
+        let bytes = parse_uuid_text(text).unwrap();
+
+        assert_eq!(16, bytes.len());
+        assert_eq!(0xa0, bytes[0]);
+        assert_eq!(text, format_uuid_text(&bytes));
+        assert!(parse_uuid_text("a0eebc99").is_err());
+    }
+
+    #[test]
+    fn test_bytea() {
+        let bytes = parse_bytea_text("\\xdeadbeef").unwrap();
+
+        assert_eq!(vec![0xde, 0xad, 0xbe, 0xef], bytes);
+        assert_eq!("\\xdeadbeef", format_bytea_text(&bytes));
+        assert!(parse_bytea_text("deadbeef").is_err());
+    }
+}
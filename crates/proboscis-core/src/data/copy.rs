@@ -0,0 +1,269 @@
+//! The text and csv formats of `COPY`, which have a line per row with the values separated by
+//! a delimiter
+use crate::data::arrow::serialize_record_batch_to_text_rows;
+use arrow::{datatypes::Schema, record_batch::RecordBatch};
+
+/// How the rows of a `COPY` are written
+#[derive(Debug, Clone, PartialEq)]
+pub struct CopyOptions {
+    /// Whether the csv format is used instead of the text format
+    pub csv: bool,
+    pub delimiter: u8,
+    /// The string written for nulls
+    pub null: String,
+    /// Whether the first line has the names of the columns
+    pub header: bool,
+    /// The quote and the escape of quotes of the csv format
+    pub quote: u8,
+    pub escape: u8,
+}
+
+impl CopyOptions {
+    /// The defaults of the text format, a tab between values and `\N` for nulls
+    pub fn text() -> CopyOptions {
+        CopyOptions {
+            csv: false,
+            delimiter: b'\t',
+            null: "\\N".to_string(),
+            header: false,
+            quote: b'"',
+            escape: b'"',
+        }
+    }
+
+    /// The defaults of the csv format, a comma between values and nothing for nulls
+    pub fn csv() -> CopyOptions {
+        CopyOptions {
+            csv: true,
+            delimiter: b',',
+            null: "".to_string(),
+            ..CopyOptions::text()
+        }
+    }
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        CopyOptions::text()
+    }
+}
+
+/// Appends the value with backslashes and the characters separating values and rows escaped
+fn put_escaped(line: &mut Vec<u8>, value: &[u8], delimiter: u8) {
+    for byte in value {
+        match byte {
+            b'\\' => line.extend_from_slice(b"\\\\"),
+            b'\t' => line.extend_from_slice(b"\\t"),
+            b'\n' => line.extend_from_slice(b"\\n"),
+            b'\r' => line.extend_from_slice(b"\\r"),
+            byte if *byte == delimiter => line.extend_from_slice(&[b'\\', *byte]),
+            byte => line.push(*byte),
+        }
+    }
+}
+
+/// Appends the value, quoted if it could be mistaken for a null or contains a delimiter,
+/// quote or line break
+fn put_quoted(line: &mut Vec<u8>, value: &[u8], options: &CopyOptions) {
+    let quoted = value == options.null.as_bytes()
+        || value.iter().any(|byte| {
+            *byte == options.delimiter || *byte == options.quote || *byte == b'\n' || *byte == b'\r'
+        });
+
+    if !quoted {
+        line.extend_from_slice(value);
+        return;
+    }
+
+    line.push(options.quote);
+    for byte in value {
+        if *byte == options.quote || *byte == options.escape {
+            line.push(options.escape);
+        }
+        line.push(*byte);
+    }
+    line.push(options.quote);
+}
+
+fn serialize_line(values: &[Option<Vec<u8>>], options: &CopyOptions) -> Vec<u8> {
+    let mut line = vec![];
+    for (index, value) in values.iter().enumerate() {
+        if index > 0 {
+            line.push(options.delimiter);
+        }
+        match value {
+            Some(value) if options.csv => put_quoted(&mut line, value, options),
+            Some(value) => put_escaped(&mut line, value, options.delimiter),
+            None => line.extend_from_slice(options.null.as_bytes()),
+        }
+    }
+    line.push(b'\n');
+    line
+}
+
+/// The line with the names of the columns, which starts a `COPY` with a header
+pub fn serialize_copy_header(schema: &Schema, options: &CopyOptions) -> Vec<u8> {
+    let names: Vec<Option<Vec<u8>>> = schema
+        .fields()
+        .iter()
+        .map(|field| Some(field.name().as_bytes().to_vec()))
+        .collect();
+    serialize_line(&names, options)
+}
+
+/// The rows of the batch as lines of a `COPY` with the options, without a header
+pub fn serialize_record_batch_to_copy(
+    batch: &RecordBatch,
+    options: &CopyOptions,
+) -> std::io::Result<Vec<Vec<u8>>> {
+    let lines = serialize_record_batch_to_text_rows(batch)?
+        .iter()
+        .map(|row| serialize_line(&row.field_data, options))
+        .collect();
+
+    Ok(lines)
+}
+
+/// The rows of the batch as lines of the text format of `COPY`, nulls are written as `\N`
+pub fn serialize_record_batch_to_copy_text(batch: &RecordBatch) -> std::io::Result<Vec<Vec<u8>>> {
+    serialize_record_batch_to_copy(batch, &CopyOptions::text())
+}
+
+/// The value with the escape sequences of the text format of `COPY` replaced by the bytes they
+/// stand for
+fn unescape(value: &[u8]) -> Vec<u8> {
+    let mut result = vec![];
+    let mut index = 0;
+    while index < value.len() {
+        if value[index] != b'\\' || index + 1 == value.len() {
+            result.push(value[index]);
+            index += 1;
+            continue;
+        }
+
+        let escaped = value[index + 1];
+        index += 2;
+        match escaped {
+            b'b' => result.push(8),
+            b'f' => result.push(12),
+            b'n' => result.push(b'\n'),
+            b'r' => result.push(b'\r'),
+            b't' => result.push(b'\t'),
+            b'v' => result.push(11),
+            b'0'..=b'7' => {
+                let mut byte = escaped - b'0';
+                let end = (index + 2).min(value.len());
+                while index < end && (b'0'..=b'7').contains(&value[index]) {
+                    byte = byte.wrapping_mul(8).wrapping_add(value[index] - b'0');
+                    index += 1;
+                }
+                result.push(byte);
+            }
+            b'x' if index < value.len() && value[index].is_ascii_hexdigit() => {
+                let end = (index + 2).min(value.len());
+                let mut byte = 0u8;
+                while index < end && value[index].is_ascii_hexdigit() {
+                    byte = byte * 16 + (value[index] as char).to_digit(16).unwrap_or(0) as u8;
+                    index += 1;
+                }
+                result.push(byte);
+            }
+            escaped => result.push(escaped),
+        }
+    }
+    result
+}
+
+/// The rows of data in the default text format of `COPY`, with tabs between values and `\N`
+/// for nulls
+pub fn parse_copy_text(data: &[u8]) -> Vec<Vec<Option<Vec<u8>>>> {
+    data.split(|byte| *byte == b'\n')
+        .filter(|line| !line.is_empty())
+        .map(|line| {
+            line.split(|byte| *byte == b'\t')
+                .map(|value| match value {
+                    b"\\N" => None,
+                    value => Some(unescape(value)),
+                })
+                .collect()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Int32Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use std::sync::Arc;
+
+    #[test]
+    fn test_serialize_record_batch_to_copy_text() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("Max\tMustermann\\"), None])),
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            vec![b"1\tMax\\tMustermann\\\\\n".to_vec(), b"2\t\\N\n".to_vec()],
+            serialize_record_batch_to_copy_text(&batch).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_serialize_record_batch_to_copy_csv() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec![
+                    Some("Max \"Mustermann\", Jr."),
+                    Some(""),
+                    None,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let options = CopyOptions {
+            header: true,
+            ..CopyOptions::csv()
+        };
+        assert_eq!(
+            b"id,name\n".to_vec(),
+            serialize_copy_header(&batch.schema(), &options)
+        );
+        assert_eq!(
+            vec![
+                b"1,\"Max \"\"Mustermann\"\", Jr.\"\n".to_vec(),
+                b"2,\"\"\n".to_vec(),
+                b"3,\n".to_vec()
+            ],
+            serialize_record_batch_to_copy(&batch, &options).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_parse_copy_text() {
+        assert_eq!(
+            vec![
+                vec![Some(b"1".to_vec()), Some(b"Max\tMustermann\\".to_vec())],
+                vec![Some(b"2".to_vec()), None],
+                vec![Some(b"3".to_vec()), Some(b"\n\x01a".to_vec())],
+            ],
+            parse_copy_text(b"1\tMax\\tMustermann\\\\\n2\t\\N\n3\t\\n\\001\\x61\n")
+        );
+    }
+}
@@ -1,8 +1,67 @@
-use arrow::datatypes::DataType;
+use crate::data::numeric::{
+    numeric_precision_and_scale, numeric_type_modifier, MAX_DECIMAL_PRECISION,
+};
+use crate::data::registry::{mapping_for_extension_name, mapping_for_oid};
+use arrow::datatypes::{DataType, Schema, TimeUnit};
 use std::{collections::BTreeMap, convert::TryFrom};
 
-fn postgres_type_for_arrow_type(arrow_type: &DataType) -> postgres::types::Type {
-    match arrow_type {
+/// Metadata key arrow uses to mark fields of extension types
+pub const EXTENSION_NAME_METADATA_KEY: &str = "ARROW:extension:name";
+
+/// Extension of Utf8 fields containing postgres json
+pub const JSON_EXTENSION_NAME: &str = "postgres.json";
+
+/// Extension of Utf8 fields containing postgres jsonb
+pub const JSONB_EXTENSION_NAME: &str = "postgres.jsonb";
+
+/// Extension of LargeUtf8 fields containing postgres numerics that don't fit into a decimal
+pub const NUMERIC_EXTENSION_NAME: &str = "postgres.numeric";
+
+/// Format code of values in text representation
+pub const TEXT_FORMAT: i16 = 0;
+
+/// Format code of values in binary representation
+pub const BINARY_FORMAT: i16 = 1;
+
+/// Name of the element field of lists representing postgres arrays
+pub const ARRAY_ELEMENT_FIELD_NAME: &str = "item";
+
+fn array_type_for_element_type(
+    element_type: &postgres::types::Type,
+) -> Option<postgres::types::Type> {
+    match *element_type {
+        postgres::types::Type::BOOL => Some(postgres::types::Type::BOOL_ARRAY),
+        postgres::types::Type::INT2 => Some(postgres::types::Type::INT2_ARRAY),
+        postgres::types::Type::INT4 => Some(postgres::types::Type::INT4_ARRAY),
+        postgres::types::Type::INT8 => Some(postgres::types::Type::INT8_ARRAY),
+        postgres::types::Type::FLOAT4 => Some(postgres::types::Type::FLOAT4_ARRAY),
+        postgres::types::Type::FLOAT8 => Some(postgres::types::Type::FLOAT8_ARRAY),
+        postgres::types::Type::NUMERIC => Some(postgres::types::Type::NUMERIC_ARRAY),
+        postgres::types::Type::TEXT => Some(postgres::types::Type::TEXT_ARRAY),
+        postgres::types::Type::VARCHAR => Some(postgres::types::Type::VARCHAR_ARRAY),
+        postgres::types::Type::UUID => Some(postgres::types::Type::UUID_ARRAY),
+        postgres::types::Type::DATE => Some(postgres::types::Type::DATE_ARRAY),
+        postgres::types::Type::TIMESTAMP => Some(postgres::types::Type::TIMESTAMP_ARRAY),
+        postgres::types::Type::TIMESTAMPTZ => Some(postgres::types::Type::TIMESTAMPTZ_ARRAY),
+        _ => None,
+    }
+}
+
+/// The postgres type values of the arrow type are sent as, if there is one
+pub(crate) fn postgres_type_for_arrow_type(
+    arrow_type: &DataType,
+    extension: Option<&str>,
+) -> Option<postgres::types::Type> {
+    let postgres_type = match arrow_type {
+        DataType::Utf8 | DataType::LargeUtf8 if extension == Some(JSON_EXTENSION_NAME) => {
+            postgres::types::Type::JSON
+        }
+        DataType::Utf8 | DataType::LargeUtf8 if extension == Some(JSONB_EXTENSION_NAME) => {
+            postgres::types::Type::JSONB
+        }
+        DataType::Utf8 | DataType::LargeUtf8 if extension == Some(NUMERIC_EXTENSION_NAME) => {
+            postgres::types::Type::NUMERIC
+        }
         DataType::Boolean => postgres::types::Type::BOOL,
         DataType::Int8 => postgres::types::Type::CHAR,
         DataType::Int16 => postgres::types::Type::INT2,
@@ -14,54 +73,129 @@ fn postgres_type_for_arrow_type(arrow_type: &DataType) -> postgres::types::Type
         DataType::LargeUtf8 => postgres::types::Type::TEXT,
         DataType::Utf8 => postgres::types::Type::VARCHAR,
         DataType::FixedSizeBinary(64) => postgres::types::Type::NAME,
+        DataType::FixedSizeBinary(16) => postgres::types::Type::UUID,
+        DataType::Binary | DataType::LargeBinary => postgres::types::Type::BYTEA,
+        DataType::Decimal(_, _) => postgres::types::Type::NUMERIC,
+        DataType::Date32 => postgres::types::Type::DATE,
+        DataType::Time64(_) => postgres::types::Type::TIME,
+        DataType::Timestamp(_, None) => postgres::types::Type::TIMESTAMP,
+        DataType::Timestamp(_, Some(_)) => postgres::types::Type::TIMESTAMPTZ,
         DataType::List(field) => match field.name().as_str() {
             "unnamed_oid_vector" => postgres::types::Type::OID_VECTOR,
             "unnamed_name_array" => postgres::types::Type::NAME_ARRAY,
-            "unnamed_text_array" => postgres::types::Type::TEXT_ARRAY,
             "unnamed_char_array" => postgres::types::Type::CHAR_ARRAY,
             "unnamed_oid_array" => postgres::types::Type::OID_ARRAY,
-            _ => todo!("{}", arrow_type),
+            ARRAY_ELEMENT_FIELD_NAME => {
+                let element_type =
+                    postgres_type_for_arrow_type(field.data_type(), extension_of_field(field))?;
+                return array_type_for_element_type(&element_type);
+            }
+            _ => return None,
         },
-        _ => todo!("{}", arrow_type),
+        _ => return None,
+    };
+
+    Some(postgres_type)
+}
+
+/// Whether the oid belongs to one of the types built into postgres that are converted into
+/// arrow types. Other types, like enums and domains, have to be resolved through the catalog
+/// of the target, the rest is kept in its text representation.
+pub fn is_builtin_type_oid(type_oid: u32) -> bool {
+    arrow_type_for_oid(type_oid, -1).is_some()
+}
+
+/// The arrow type values of the type are converted into, if there is a conversion.
+/// Types without one, like bpchar or interval, are kept in their text representation.
+fn arrow_type_for_oid(type_oid: u32, type_modifier: i32) -> Option<DataType> {
+    postgres::types::Type::from_oid(type_oid)
+        .and_then(|postgres_type| arrow_type_for_postgres_type(&postgres_type, type_modifier))
+}
+
+fn arrow_type_for_postgres_type(
+    postgres_type: &postgres::types::Type,
+    type_modifier: i32,
+) -> Option<DataType> {
+    let data_type =
+        match *postgres_type {
+            postgres::types::Type::BOOL => DataType::Boolean,
+            postgres::types::Type::CHAR => DataType::Int8,
+            postgres::types::Type::INT2 => DataType::Int16,
+            postgres::types::Type::INT4 => DataType::Int32,
+            postgres::types::Type::INT8 => DataType::Int64,
+            postgres::types::Type::FLOAT4 => DataType::Float32,
+            postgres::types::Type::FLOAT8 => DataType::Float64,
+            postgres::types::Type::TEXT => DataType::LargeUtf8,
+            postgres::types::Type::VARCHAR => DataType::Utf8,
+            postgres::types::Type::NAME => DataType::FixedSizeBinary(64),
+            postgres::types::Type::OID => DataType::UInt16,
+            postgres::types::Type::NUMERIC => arrow_type_for_numeric(type_modifier),
+            postgres::types::Type::UUID => DataType::FixedSizeBinary(16),
+            postgres::types::Type::JSON => DataType::Utf8,
+            postgres::types::Type::JSONB => DataType::Utf8,
+            postgres::types::Type::BYTEA => DataType::Binary,
+            postgres::types::Type::DATE => DataType::Date32,
+            postgres::types::Type::TIME => DataType::Time64(TimeUnit::Microsecond),
+            postgres::types::Type::TIMESTAMP => DataType::Timestamp(TimeUnit::Microsecond, None),
+            postgres::types::Type::TIMESTAMPTZ => {
+                DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".to_string()))
+            }
+            postgres::types::Type::OID_VECTOR => DataType::List(Box::new(
+                arrow::datatypes::Field::new("unnamed_oid_vector", DataType::UInt8, true),
+            )),
+            postgres::types::Type::CHAR_ARRAY => DataType::List(Box::new(
+                arrow::datatypes::Field::new("unnamed_char_array", DataType::UInt8, true),
+            )),
+            postgres::types::Type::NAME_ARRAY => DataType::List(Box::new(
+                arrow::datatypes::Field::new("unnamed_name_array", DataType::UInt8, true),
+            )),
+            postgres::types::Type::OID_ARRAY => DataType::List(Box::new(
+                arrow::datatypes::Field::new("unnamed_oid_array", DataType::UInt8, true),
+            )),
+            _ => match postgres_type.kind() {
+                postgres::types::Kind::Array(element_type) => {
+                    DataType::List(Box::new(array_element_field(element_type, type_modifier)?))
+                }
+                _ => return None,
+            },
+        };
+
+    Some(data_type)
+}
+
+/// Numerics are converted into decimals, unless they have no precision or one beyond that of
+/// decimals. Those are kept in their text representation instead of being rounded to some scale.
+fn arrow_type_for_numeric(type_modifier: i32) -> DataType {
+    match numeric_precision_and_scale(type_modifier) {
+        Some((precision, scale)) if precision <= MAX_DECIMAL_PRECISION => {
+            DataType::Decimal(precision, scale)
+        }
+        _ => DataType::LargeUtf8,
     }
 }
 
-fn arrow_type_for_postgres_type(postgres_type: &postgres::types::Type) -> DataType {
-    match *postgres_type {
-        postgres::types::Type::BOOL => DataType::Boolean,
-        postgres::types::Type::CHAR => DataType::Int8,
-        postgres::types::Type::INT2 => DataType::Int16,
-        postgres::types::Type::INT4 => DataType::Int32,
-        postgres::types::Type::INT8 => DataType::Int64,
-        postgres::types::Type::FLOAT4 => DataType::Float32,
-        postgres::types::Type::FLOAT8 => DataType::Float64,
-        postgres::types::Type::TEXT => DataType::LargeUtf8,
-        postgres::types::Type::VARCHAR => DataType::Utf8,
-        postgres::types::Type::NAME => DataType::FixedSizeBinary(64),
-        postgres::types::Type::OID => DataType::UInt16,
-        postgres::types::Type::OID_VECTOR => DataType::List(Box::new(
-            arrow::datatypes::Field::new("unnamed_oid_vector", DataType::UInt8, true),
-        )),
-        postgres::types::Type::CHAR_ARRAY => DataType::List(Box::new(
-            arrow::datatypes::Field::new("unnamed_char_array", DataType::UInt8, true),
-        )),
-        postgres::types::Type::TEXT_ARRAY => DataType::List(Box::new(
-            arrow::datatypes::Field::new("unnamed_text_array", DataType::UInt8, true),
-        )),
-        postgres::types::Type::NAME_ARRAY => DataType::List(Box::new(
-            arrow::datatypes::Field::new("unnamed_name_array", DataType::UInt8, true),
-        )),
-        postgres::types::Type::OID_ARRAY => DataType::List(Box::new(arrow::datatypes::Field::new(
-            "unnamed_oid_array",
-            DataType::UInt8,
-            true,
-        ))),
-        _ => todo!("{}", postgres_type),
+fn array_element_field(
+    element_type: &postgres::types::Type,
+    type_modifier: i32,
+) -> Option<arrow::datatypes::Field> {
+    let data_type = arrow_type_for_postgres_type(element_type, type_modifier)?;
+    // Elements only keep the extension of numerics, whose binary representation differs from
+    // the one of their arrow type
+    let extension = extension_for_postgres_type(element_type, &data_type)
+        .filter(|extension| extension == NUMERIC_EXTENSION_NAME);
+
+    let mut field = arrow::datatypes::Field::new(ARRAY_ELEMENT_FIELD_NAME, data_type, true);
+    if let Some(extension) = extension {
+        let mut metadata = BTreeMap::new();
+        metadata.insert(EXTENSION_NAME_METADATA_KEY.to_string(), extension);
+        field.set_metadata(Some(metadata));
     }
+
+    Some(field)
 }
 
-fn typelen_for_postgres_type(postgres_type: &postgres::types::Type) -> i16 {
-    match *postgres_type {
+fn typelen_for_postgres_type(postgres_type: &postgres::types::Type) -> Option<i16> {
+    let length = match *postgres_type {
         postgres::types::Type::BOOL => -1,
         postgres::types::Type::CHAR => 1,
         postgres::types::Type::INT2 => 2,
@@ -73,17 +207,84 @@ fn typelen_for_postgres_type(postgres_type: &postgres::types::Type) -> i16 {
         postgres::types::Type::VARCHAR => -1,
         postgres::types::Type::NAME => 64,
         postgres::types::Type::OID => 2,
+        postgres::types::Type::NUMERIC => -1,
+        postgres::types::Type::UUID => 16,
+        postgres::types::Type::JSON => -1,
+        postgres::types::Type::JSONB => -1,
+        postgres::types::Type::BYTEA => -1,
+        postgres::types::Type::DATE => 4,
+        postgres::types::Type::TIME => 8,
+        postgres::types::Type::TIMESTAMP => 8,
+        postgres::types::Type::TIMESTAMPTZ => 8,
         postgres::types::Type::OID_VECTOR => -1,
         postgres::types::Type::TEXT_ARRAY => -1,
         postgres::types::Type::NAME_ARRAY => -1,
         postgres::types::Type::CHAR_ARRAY => -1,
         postgres::types::Type::OID_ARRAY => -1,
-        _ => todo!("{}", postgres_type),
+        _ => match postgres_type.kind() {
+            postgres::types::Kind::Array(_) => -1,
+            _ => return None,
+        },
+    };
+
+    Some(length)
+}
+
+fn extension_for_postgres_type(
+    postgres_type: &postgres::types::Type,
+    data_type: &DataType,
+) -> Option<String> {
+    match (postgres_type, data_type) {
+        (&postgres::types::Type::JSON, _) => Some(JSON_EXTENSION_NAME.to_string()),
+        (&postgres::types::Type::JSONB, _) => Some(JSONB_EXTENSION_NAME.to_string()),
+        (&postgres::types::Type::NUMERIC, DataType::LargeUtf8) => {
+            Some(NUMERIC_EXTENSION_NAME.to_string())
+        }
+        _ => None,
     }
 }
 
-fn format_for_postgres_type(_postgres_type: &postgres::types::Type) -> i16 {
-    0
+/// Name of the arrow extension type of the field, if it has one
+pub fn extension_of_field(field: &arrow::datatypes::Field) -> Option<&str> {
+    field
+        .metadata()
+        .as_ref()
+        .and_then(|metadata| metadata.get(EXTENSION_NAME_METADATA_KEY))
+        .map(String::as_str)
+}
+
+/// Whether the field contains numerics in their text representation, whose binary
+/// representation has to be converted
+pub fn is_numeric_text_field(field: &arrow::datatypes::Field) -> bool {
+    extension_of_field(field) == Some(NUMERIC_EXTENSION_NAME)
+}
+
+fn type_modifier_for_arrow_type(arrow_type: &DataType) -> i32 {
+    match arrow_type {
+        DataType::Decimal(precision, scale) => numeric_type_modifier(*precision, *scale),
+        // The type modifier of an array column applies to its elements
+        DataType::List(field) => type_modifier_for_arrow_type(field.data_type()),
+        _ => -1,
+    }
+}
+
+/// Type of a field as described by the server
+#[derive(Clone, Debug, PartialEq)]
+pub struct PostgresTypeInfo {
+    pub oid: u32,
+    pub length: i16,
+    pub modifier: i32,
+}
+
+impl PostgresTypeInfo {
+    /// Whether the arrow type is still the one the postgres type is converted into
+    fn matches(&self, data_type: &DataType) -> bool {
+        if let Some(mapping) = mapping_for_oid(self.oid) {
+            return mapping.data_type(self.modifier) == *data_type;
+        }
+
+        arrow_type_for_oid(self.oid, self.modifier).unwrap_or(DataType::LargeUtf8) == *data_type
+    }
 }
 
 pub struct Field {
@@ -91,15 +292,58 @@ pub struct Field {
     pub table_oid: i32,
     pub column_number: i16,
     pub data_type: DataType,
+    /// Name of the arrow extension type, used for postgres types without a native arrow equivalent
+    pub extension: Option<String>,
+    /// Whether values of the field are in text or binary representation
+    pub format: i16,
+    /// Type the server described the field with, restored as long as the data type is unchanged
+    pub original_type: Option<PostgresTypeInfo>,
 }
 
 impl TryFrom<&Field> for proboscis_postgres_protocol::message::Field {
     type Error = &'static str;
 
     fn try_from(value: &Field) -> Result<Self, Self::Error> {
-        let postgres_type = postgres_type_for_arrow_type(&value.data_type);
-        let type_length = typelen_for_postgres_type(&postgres_type);
-        let format = format_for_postgres_type(&postgres_type);
+        let original_type = value
+            .original_type
+            .as_ref()
+            .filter(|original_type| original_type.matches(&value.data_type));
+
+        if let Some(original_type) = original_type {
+            return Ok(proboscis_postgres_protocol::message::Field {
+                name: value.name.clone(),
+                table_oid: value.table_oid,
+                column_number: value.column_number,
+                type_oid: original_type.oid,
+                type_length: original_type.length,
+                type_modifier: original_type.modifier,
+                format: value.format,
+            });
+        }
+
+        let registered = value
+            .extension
+            .as_deref()
+            .and_then(mapping_for_extension_name);
+
+        if let Some((type_oid, mapping)) = registered {
+            return Ok(proboscis_postgres_protocol::message::Field {
+                name: value.name.clone(),
+                table_oid: value.table_oid,
+                column_number: value.column_number,
+                type_oid,
+                type_length: mapping.type_length(),
+                type_modifier: mapping.type_modifier(&value.data_type),
+                format: value.format,
+            });
+        }
+
+        let postgres_type =
+            postgres_type_for_arrow_type(&value.data_type, value.extension.as_deref())
+                .ok_or("couldn't match data type with postgres type")?;
+        let type_length = typelen_for_postgres_type(&postgres_type)
+            .ok_or("couldn't determine length of postgres type")?;
+        let type_modifier = type_modifier_for_arrow_type(&value.data_type);
 
         Ok(proboscis_postgres_protocol::message::Field {
             name: value.name.clone(),
@@ -107,8 +351,8 @@ impl TryFrom<&Field> for proboscis_postgres_protocol::message::Field {
             column_number: value.column_number,
             type_oid: postgres_type.oid(),
             type_length,
-            type_modifier: -1,
-            format,
+            type_modifier,
+            format: value.format,
         })
     }
 }
@@ -117,15 +361,47 @@ impl TryFrom<&proboscis_postgres_protocol::message::Field> for Field {
     type Error = &'static str;
 
     fn try_from(value: &proboscis_postgres_protocol::message::Field) -> Result<Self, Self::Error> {
-        let postgres_type = postgres::types::Type::from_oid(value.type_oid)
-            .ok_or("couldn't match oid with type")?;
-        let data_type = arrow_type_for_postgres_type(&postgres_type);
+        let original_type = Some(PostgresTypeInfo {
+            oid: value.type_oid,
+            length: value.type_length,
+            modifier: value.type_modifier,
+        });
+
+        if let Some(mapping) = mapping_for_oid(value.type_oid) {
+            return Ok(Field {
+                name: value.name.clone(),
+                data_type: mapping.data_type(value.type_modifier),
+                extension: Some(mapping.extension_name().to_string()),
+                table_oid: value.table_oid,
+                column_number: value.column_number,
+                format: value.format,
+                original_type,
+            });
+        }
+
+        let postgres_type = postgres::types::Type::from_oid(value.type_oid);
+        let data_type = arrow_type_for_oid(value.type_oid, value.type_modifier);
+
+        let (data_type, extension) = match (postgres_type, data_type) {
+            (Some(postgres_type), Some(data_type)) => {
+                let extension = extension_for_postgres_type(&postgres_type, &data_type);
+                (data_type, extension)
+            }
+            // The text of other types is passed on, their binary representation is unknown
+            _ if value.format == BINARY_FORMAT => {
+                return Err("values of the type can't be converted in binary format")
+            }
+            _ => (DataType::LargeUtf8, None),
+        };
 
         Ok(Field {
             name: value.name.clone(),
             data_type,
+            extension,
             table_oid: value.table_oid,
             column_number: value.column_number,
+            format: value.format,
+            original_type,
         })
     }
 }
@@ -135,6 +411,18 @@ impl From<&Field> for arrow::datatypes::Field {
         let mut metadata = BTreeMap::new();
         metadata.insert("table_oid".to_string(), value.table_oid.to_string());
         metadata.insert("column_number".to_string(), value.column_number.to_string());
+        metadata.insert("format".to_string(), value.format.to_string());
+        if let Some(extension) = &value.extension {
+            metadata.insert(EXTENSION_NAME_METADATA_KEY.to_string(), extension.clone());
+        }
+        if let Some(original_type) = &value.original_type {
+            metadata.insert("type_oid".to_string(), original_type.oid.to_string());
+            metadata.insert("type_length".to_string(), original_type.length.to_string());
+            metadata.insert(
+                "type_modifier".to_string(),
+                original_type.modifier.to_string(),
+            );
+        }
 
         let mut field = arrow::datatypes::Field::new(&value.name, value.data_type.clone(), false);
         field.set_metadata(Some(metadata));
@@ -161,11 +449,67 @@ impl TryFrom<&arrow::datatypes::Field> for Field {
             .parse()
             .map_err(|_| "parse error")?;
 
+        let format = format_of_field(value);
+        let extension = metadata.get(EXTENSION_NAME_METADATA_KEY).cloned();
+
+        let original_type = match (
+            metadata.get("type_oid"),
+            metadata.get("type_length"),
+            metadata.get("type_modifier"),
+        ) {
+            (Some(oid), Some(length), Some(modifier)) => Some(PostgresTypeInfo {
+                oid: oid.parse().map_err(|_| "parse error")?,
+                length: length.parse().map_err(|_| "parse error")?,
+                modifier: modifier.parse().map_err(|_| "parse error")?,
+            }),
+            _ => None,
+        };
+
         Ok(Field {
             data_type: value.data_type().clone(),
+            extension,
             name: value.name().clone(),
             table_oid,
             column_number,
+            format,
+            original_type,
         })
     }
 }
+
+/// Adds the metadata the proxy needs to describe the columns to clients, to fields without it,
+/// e.g. to the results of resolvers which don't query postgres
+pub fn with_field_metadata(schema: &Schema) -> Schema {
+    Schema::new(
+        schema
+            .fields()
+            .iter()
+            .map(|field| {
+                if Field::try_from(field).is_ok() {
+                    return field.clone();
+                }
+
+                (&Field {
+                    name: field.name().clone(),
+                    table_oid: 0,
+                    column_number: 0,
+                    data_type: field.data_type().clone(),
+                    extension: None,
+                    format: TEXT_FORMAT,
+                    original_type: None,
+                })
+                    .into()
+            })
+            .collect(),
+    )
+}
+
+/// The format of the values of a field, defaulting to text if it isn't specified
+pub fn format_of_field(field: &arrow::datatypes::Field) -> i16 {
+    field
+        .metadata()
+        .as_ref()
+        .and_then(|metadata| metadata.get("format"))
+        .and_then(|format| format.parse().ok())
+        .unwrap_or(TEXT_FORMAT)
+}
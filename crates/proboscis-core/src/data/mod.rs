@@ -1,2 +1,9 @@
+pub mod array;
 pub mod arrow;
+pub mod bytes;
+pub mod copy;
 pub mod field;
+pub mod numeric;
+pub mod primitive;
+pub mod registry;
+pub mod temporal;
@@ -0,0 +1,400 @@
+use std::{
+    convert::TryFrom,
+    io::{Error, ErrorKind},
+};
+
+/// Largest precision of the decimals numeric columns are converted into.
+///
+/// Numerics without a type modifier keep the scale of every single value and
+/// can exceed any precision, they are kept in their text representation instead.
+pub const MAX_DECIMAL_PRECISION: usize = 38;
+
+// Postgres adds the size of the varlena header to every type modifier
+const VARHDRSZ: i32 = 4;
+
+/// Extracts precision and scale from the type modifier of a numeric column,
+/// unconstrained numerics have neither
+pub fn numeric_precision_and_scale(type_modifier: i32) -> Option<(usize, usize)> {
+    if type_modifier < VARHDRSZ {
+        return None;
+    }
+
+    let modifier = type_modifier - VARHDRSZ;
+    let precision = ((modifier >> 16) & 0xffff) as usize;
+    let scale = (modifier & 0xffff) as usize;
+
+    Some((precision, scale))
+}
+
+/// Computes the type modifier of a numeric column with the given precision and scale
+pub fn numeric_type_modifier(precision: usize, scale: usize) -> i32 {
+    (((precision as i32) << 16) | scale as i32) + VARHDRSZ
+}
+
+/// Parses the text representation of a numeric into an integer with the given scale.
+/// Surplus fractional digits are rounded half away from zero, like postgres does.
+pub fn parse_numeric_text(text: &str, scale: usize) -> std::io::Result<i128> {
+    let invalid = || Error::new(ErrorKind::InvalidData, format!("invalid numeric: {}", text));
+
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let (integer_part, fractional_part) = match digits.split_once('.') {
+        Some((integer_part, fractional_part)) => (integer_part, fractional_part),
+        None => (digits, ""),
+    };
+
+    if integer_part.is_empty() && fractional_part.is_empty() {
+        return Err(invalid());
+    }
+
+    if !integer_part
+        .chars()
+        .chain(fractional_part.chars())
+        .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let mut value: i128 = 0;
+    let fractional_digits = fractional_part.chars().chain(std::iter::repeat('0'));
+    for digit in integer_part.chars().chain(fractional_digits.take(scale)) {
+        value = value
+            .checked_mul(10)
+            .and_then(|value| value.checked_add(digit.to_digit(10).unwrap() as i128))
+            .ok_or_else(invalid)?;
+    }
+
+    if let Some(next_digit) = fractional_part.chars().nth(scale) {
+        if next_digit >= '5' {
+            value = value.checked_add(1).ok_or_else(invalid)?;
+        }
+    }
+
+    Ok(if negative { -value } else { value })
+}
+
+/// Formats an integer with the given scale as the text representation of a numeric
+pub fn format_numeric_text(value: i128, scale: usize) -> String {
+    let digits = value.unsigned_abs().to_string();
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+
+    let (integer_part, fractional_part) = digits.split_at(digits.len() - scale);
+
+    let sign = if value < 0 { "-" } else { "" };
+
+    if fractional_part.is_empty() {
+        format!("{}{}", sign, integer_part)
+    } else {
+        format!("{}{}.{}", sign, integer_part, fractional_part)
+    }
+}
+
+// Sign markers of the binary numeric format
+const NUMERIC_POSITIVE: u16 = 0x0000;
+const NUMERIC_NEGATIVE: u16 = 0x4000;
+const NUMERIC_NAN: u16 = 0xC000;
+const NUMERIC_POSITIVE_INFINITY: u16 = 0xD000;
+const NUMERIC_NEGATIVE_INFINITY: u16 = 0xF000;
+
+fn read_numeric_header(bytes: &[u8]) -> std::io::Result<(Vec<u16>, i32, u16, usize)> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "invalid binary numeric");
+
+    let read_u16 = |offset: usize| -> std::io::Result<u16> {
+        bytes
+            .get(offset..offset + 2)
+            .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+            .ok_or_else(invalid)
+    };
+
+    let digit_count = read_u16(0)? as usize;
+    let weight = read_u16(2)? as i16 as i32;
+    let sign = read_u16(4)?;
+    let display_scale = read_u16(6)? as usize;
+    let digits = (0..digit_count)
+        .map(|index| read_u16(8 + index * 2))
+        .collect::<std::io::Result<Vec<u16>>>()?;
+
+    Ok((digits, weight, sign, display_scale))
+}
+
+/// Parses the binary representation of a numeric (base 10000 digits) into an integer with the given scale
+pub fn parse_numeric_binary(bytes: &[u8], scale: usize) -> std::io::Result<i128> {
+    let invalid = || Error::new(ErrorKind::InvalidData, "invalid binary numeric");
+
+    let (digits, weight, sign, _) = read_numeric_header(bytes)?;
+
+    if sign != NUMERIC_POSITIVE && sign != NUMERIC_NEGATIVE {
+        return Err(Error::new(
+            ErrorKind::InvalidData,
+            "numeric NaN and infinity are not supported in decimals",
+        ));
+    }
+
+    let mut value: i128 = 0;
+    for digit in &digits {
+        value = value
+            .checked_mul(10_000)
+            .and_then(|value| value.checked_add(*digit as i128))
+            .ok_or_else(invalid)?;
+    }
+
+    // The last digit has the weight `weight - digit_count + 1` in base 10000
+    let exponent = 4 * (weight - digits.len() as i32 + 1) + scale as i32;
+    if exponent >= 0 {
+        value = value
+            .checked_mul(10_i128.checked_pow(exponent as u32).ok_or_else(invalid)?)
+            .ok_or_else(invalid)?;
+    } else {
+        let divisor = 10_i128
+            .checked_pow((-exponent) as u32)
+            .ok_or_else(invalid)?;
+        let remainder = value % divisor;
+        value /= divisor;
+        if remainder * 2 >= divisor {
+            value += 1;
+        }
+    }
+
+    Ok(if sign == NUMERIC_NEGATIVE {
+        -value
+    } else {
+        value
+    })
+}
+
+/// Formats an integer with the given scale as the binary representation of a numeric
+pub fn format_numeric_binary(value: i128, scale: usize) -> Vec<u8> {
+    let digits = value.unsigned_abs().to_string();
+    let digits = format!("{:0>width$}", digits, width = scale + 1);
+    let (integer_part, fractional_part) = digits.split_at(digits.len() - scale);
+
+    format_numeric_digits_binary(value < 0, integer_part, fractional_part)
+}
+
+/// Converts the binary representation of a numeric into its text representation,
+/// regardless of its precision
+pub fn numeric_binary_to_text(bytes: &[u8]) -> std::io::Result<String> {
+    let (digits, weight, sign, display_scale) = read_numeric_header(bytes)?;
+
+    match sign {
+        NUMERIC_NAN => return Ok("NaN".to_string()),
+        NUMERIC_POSITIVE_INFINITY => return Ok("Infinity".to_string()),
+        NUMERIC_NEGATIVE_INFINITY => return Ok("-Infinity".to_string()),
+        NUMERIC_POSITIVE | NUMERIC_NEGATIVE => {}
+        _ => return Err(Error::new(ErrorKind::InvalidData, "invalid binary numeric")),
+    }
+
+    // Digits outside of the ones sent are zero
+    let digit = |position: i32| -> u16 {
+        usize::try_from(position)
+            .ok()
+            .and_then(|position| digits.get(position).copied())
+            .unwrap_or(0)
+    };
+
+    let mut integer_part = String::new();
+    for position in 0..=weight {
+        integer_part.push_str(&format!("{:04}", digit(position)));
+    }
+    let integer_part = integer_part.trim_start_matches('0');
+
+    let mut fractional_part = String::new();
+    let mut position = weight + 1;
+    while fractional_part.len() < display_scale {
+        fractional_part.push_str(&format!("{:04}", digit(position)));
+        position += 1;
+    }
+    fractional_part.truncate(display_scale);
+
+    let sign = if sign == NUMERIC_NEGATIVE { "-" } else { "" };
+    let integer_part = if integer_part.is_empty() {
+        "0"
+    } else {
+        integer_part
+    };
+
+    if fractional_part.is_empty() {
+        Ok(format!("{}{}", sign, integer_part))
+    } else {
+        Ok(format!("{}{}.{}", sign, integer_part, fractional_part))
+    }
+}
+
+/// Converts the text representation of a numeric into its binary representation,
+/// regardless of its precision
+pub fn numeric_text_to_binary(text: &str) -> std::io::Result<Vec<u8>> {
+    let invalid = || Error::new(ErrorKind::InvalidData, format!("invalid numeric: {}", text));
+
+    let special = |sign: u16| -> Vec<u8> {
+        [0, 0, sign, 0]
+            .iter()
+            .flat_map(|value: &u16| value.to_be_bytes().to_vec())
+            .collect()
+    };
+
+    if text.eq_ignore_ascii_case("nan") {
+        return Ok(special(NUMERIC_NAN));
+    }
+    if text.eq_ignore_ascii_case("infinity") || text.eq_ignore_ascii_case("+infinity") {
+        return Ok(special(NUMERIC_POSITIVE_INFINITY));
+    }
+    if text.eq_ignore_ascii_case("-infinity") {
+        return Ok(special(NUMERIC_NEGATIVE_INFINITY));
+    }
+
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text.strip_prefix('+').unwrap_or(text)),
+    };
+
+    let (integer_part, fractional_part) = match digits.split_once('.') {
+        Some((integer_part, fractional_part)) => (integer_part, fractional_part),
+        None => (digits, ""),
+    };
+
+    if (integer_part.is_empty() && fractional_part.is_empty())
+        || !integer_part
+            .chars()
+            .chain(fractional_part.chars())
+            .all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let is_zero = integer_part
+        .chars()
+        .chain(fractional_part.chars())
+        .all(|c| c == '0');
+
+    Ok(format_numeric_digits_binary(
+        negative && !is_zero,
+        integer_part,
+        fractional_part,
+    ))
+}
+
+/// Formats the decimal digits before and after the decimal point as the binary representation
+/// of a numeric, with the number of digits after the decimal point as its display scale
+fn format_numeric_digits_binary(
+    negative: bool,
+    integer_part: &str,
+    fractional_part: &str,
+) -> Vec<u8> {
+    let scale = fractional_part.len();
+
+    // Align both parts to groups of four decimal digits around the decimal point
+    let integer_padding = (4 - integer_part.len() % 4) % 4;
+    let fractional_padding = (4 - fractional_part.len() % 4) % 4;
+    let aligned = format!(
+        "{}{}{}{}",
+        "0".repeat(integer_padding),
+        integer_part,
+        fractional_part,
+        "0".repeat(fractional_padding)
+    );
+
+    let mut groups: Vec<u16> = aligned
+        .as_bytes()
+        .chunks(4)
+        .map(|chunk| std::str::from_utf8(chunk).unwrap().parse().unwrap())
+        .collect();
+    let mut weight = ((integer_padding + integer_part.len()) / 4) as i16 - 1;
+
+    while groups.first() == Some(&0) {
+        groups.remove(0);
+        weight -= 1;
+    }
+    while groups.last() == Some(&0) {
+        groups.pop();
+    }
+    if groups.is_empty() {
+        weight = 0;
+    }
+
+    let sign = if negative {
+        NUMERIC_NEGATIVE
+    } else {
+        NUMERIC_POSITIVE
+    };
+
+    let mut bytes = vec![];
+    bytes.extend_from_slice(&(groups.len() as u16).to_be_bytes());
+    bytes.extend_from_slice(&weight.to_be_bytes());
+    bytes.extend_from_slice(&sign.to_be_bytes());
+    bytes.extend_from_slice(&(scale as u16).to_be_bytes());
+    for group in groups {
+        bytes.extend_from_slice(&group.to_be_bytes());
+    }
+
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_type_modifier() {
+        // numeric(10, 2)
+        assert_eq!(655366, numeric_type_modifier(10, 2));
+
+        assert_eq!(Some((10, 2)), numeric_precision_and_scale(655366));
+        assert_eq!(None, numeric_precision_and_scale(-1));
+    }
+
+    #[test]
+    fn test_text_round_trip() {
+        assert_eq!(12345, parse_numeric_text("123.45", 2).unwrap());
+        assert_eq!(-5, parse_numeric_text("-0.05", 2).unwrap());
+        assert_eq!(12300, parse_numeric_text("123", 2).unwrap());
+        assert_eq!(124, parse_numeric_text("123.5", 0).unwrap());
+        assert!(parse_numeric_text("NaN", 2).is_err());
+
+        assert_eq!("123.45", format_numeric_text(12345, 2));
+        assert_eq!("-0.05", format_numeric_text(-5, 2));
+        assert_eq!("123.00", format_numeric_text(12300, 2));
+        assert_eq!("7", format_numeric_text(7, 0));
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        // 123.45 is sent as the base 10000 digits [123, 4500] with weight 0
+        let binary = format_numeric_binary(12345, 2);
+        assert_eq!(vec![0, 2, 0, 0, 0, 0, 0, 2, 0, 123, 0x11, 0x94], binary);
+        assert_eq!(12345, parse_numeric_binary(&binary, 2).unwrap());
+
+        for (value, scale) in &[(-5, 2), (0, 3), (1_000_000, 0), (123_456_789, 4)] {
+            let binary = format_numeric_binary(*value, *scale);
+            assert_eq!(*value, parse_numeric_binary(&binary, *scale).unwrap());
+        }
+
+        // Rounds surplus digits when reading into a smaller scale
+        assert_eq!(1235, parse_numeric_binary(&binary, 1).unwrap());
+    }
+
+    #[test]
+    fn test_unconstrained_round_trip() {
+        let binary = numeric_text_to_binary("123.45").unwrap();
+        assert_eq!(format_numeric_binary(12345, 2), binary);
+
+        for text in &[
+            "0",
+            "0.000",
+            "-0.0500",
+            "1000000",
+            "12345678901234567890123456789012345678901234567890.123456789012345678901",
+            "NaN",
+            "Infinity",
+            "-Infinity",
+        ] {
+            let binary = numeric_text_to_binary(text).unwrap();
+            assert_eq!(*text, numeric_binary_to_text(&binary).unwrap());
+        }
+
+        assert!(numeric_text_to_binary("1e5").is_err());
+        assert!(parse_numeric_binary(&numeric_text_to_binary("NaN").unwrap(), 2).is_err());
+    }
+}
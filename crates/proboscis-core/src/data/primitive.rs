@@ -0,0 +1,76 @@
+use std::io::{Error, ErrorKind};
+
+fn invalid(kind: &str, text: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid {}: {}", kind, text),
+    )
+}
+
+pub fn parse_bool_text(text: &str) -> std::io::Result<bool> {
+    match text {
+        "t" => Ok(true),
+        "f" => Ok(false),
+        _ => Err(invalid("boolean", text)),
+    }
+}
+
+pub fn format_bool_text(value: bool) -> &'static str {
+    if value {
+        "t"
+    } else {
+        "f"
+    }
+}
+
+/// Parses a float8 in text format, including the special values postgres emits
+pub fn parse_float_text(text: &str) -> std::io::Result<f64> {
+    match text {
+        "NaN" => Ok(f64::NAN),
+        "Infinity" => Ok(f64::INFINITY),
+        "-Infinity" => Ok(f64::NEG_INFINITY),
+        _ => text.parse().map_err(|_| invalid("float", text)),
+    }
+}
+
+/// Formats a float4 or float8 in text format, using the shortest representation that round-trips
+pub fn format_float_text<T: Into<f64> + ToString + Copy>(value: T) -> String {
+    let float: f64 = value.into();
+
+    if float.is_nan() {
+        "NaN".to_string()
+    } else if float.is_infinite() && float > 0.0 {
+        "Infinity".to_string()
+    } else if float.is_infinite() {
+        "-Infinity".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bool() {
+        assert!(parse_bool_text("t").unwrap());
+        assert!(!parse_bool_text("f").unwrap());
+        assert!(parse_bool_text("yes").is_err());
+        assert_eq!("t", format_bool_text(true));
+    }
+
+    #[test]
+    fn test_float() {
+        assert_eq!(1.5, parse_float_text("1.5").unwrap());
+        assert_eq!(1e20, parse_float_text("1e+20").unwrap());
+        assert!(parse_float_text("NaN").unwrap().is_nan());
+        assert_eq!(f64::NEG_INFINITY, parse_float_text("-Infinity").unwrap());
+
+        assert_eq!("1.5", format_float_text(1.5));
+        assert_eq!("-0.25", format_float_text(-0.25));
+        assert_eq!("Infinity", format_float_text(f64::INFINITY));
+        assert_eq!("NaN", format_float_text(f64::NAN));
+        assert_eq!("1.1", format_float_text(1.1f32));
+    }
+}
@@ -0,0 +1,183 @@
+use crate::data::field::{BINARY_FORMAT, EXTENSION_NAME_METADATA_KEY};
+use arrow::array::{Array, ArrayRef, GenericStringArray};
+use arrow::datatypes::DataType;
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+
+/// Conversion between values of a postgres type and an arrow array, for types
+/// that are not supported by proboscis itself (e.g. postgis geometry or hstore).
+///
+/// Fields of a registered type are tagged with the extension name of the
+/// mapping, which is used to find the postgres type again during serialization.
+pub trait TypeMapping: Send + Sync {
+    /// Name of the arrow extension type, should be unique across all mappings
+    fn extension_name(&self) -> &str;
+
+    /// The arrow type values of the postgres type are converted into
+    fn data_type(&self, type_modifier: i32) -> DataType;
+
+    fn type_length(&self) -> i16 {
+        -1
+    }
+
+    fn type_modifier(&self, _data_type: &DataType) -> i32 {
+        -1
+    }
+
+    /// Converts the values of a column, in the given format, into an arrow array
+    fn decode(&self, data: &[Option<Vec<u8>>], format: i16) -> std::io::Result<ArrayRef>;
+
+    /// Converts a single non-null value of an arrow array into the given format
+    fn encode(&self, column: &ArrayRef, row_index: usize, format: i16) -> std::io::Result<Vec<u8>>;
+}
+
+/// Mapping for types whose values can be handled as plain text, like citext
+pub struct TextTypeMapping {
+    extension_name: String,
+}
+
+impl TextTypeMapping {
+    pub fn new(extension_name: &str) -> TextTypeMapping {
+        TextTypeMapping {
+            extension_name: extension_name.to_string(),
+        }
+    }
+}
+
+impl TypeMapping for TextTypeMapping {
+    fn extension_name(&self) -> &str {
+        &self.extension_name
+    }
+
+    fn data_type(&self, _type_modifier: i32) -> DataType {
+        DataType::Utf8
+    }
+
+    fn decode(&self, data: &[Option<Vec<u8>>], format: i16) -> std::io::Result<ArrayRef> {
+        if format == BINARY_FORMAT {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("{} doesn't support the binary format", self.extension_name),
+            ));
+        }
+
+        let values = data
+            .iter()
+            .map(|d| {
+                d.as_ref()
+                    .map(|d| String::from_utf8(d.to_vec()))
+                    .transpose()
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+            })
+            .collect::<std::io::Result<Vec<Option<String>>>>()?;
+
+        Ok(Arc::new(
+            values.into_iter().collect::<GenericStringArray<i32>>(),
+        ))
+    }
+
+    fn encode(
+        &self,
+        column: &ArrayRef,
+        row_index: usize,
+        _format: i16,
+    ) -> std::io::Result<Vec<u8>> {
+        let values = column
+            .as_any()
+            .downcast_ref::<GenericStringArray<i32>>()
+            .ok_or_else(|| {
+                std::io::Error::new(std::io::ErrorKind::InvalidData, "expected a string array")
+            })?;
+
+        Ok(values.value(row_index).as_bytes().to_vec())
+    }
+}
+
+#[derive(Default)]
+struct TypeRegistry {
+    by_oid: HashMap<u32, Arc<dyn TypeMapping>>,
+    by_extension_name: HashMap<String, (u32, Arc<dyn TypeMapping>)>,
+}
+
+static REGISTRY: Lazy<RwLock<TypeRegistry>> = Lazy::new(|| RwLock::new(TypeRegistry::default()));
+
+/// Registers a conversion for the postgres type with the given oid.
+/// A later registration for the same oid replaces the previous one.
+pub fn register_type_mapping<M: TypeMapping + 'static>(type_oid: u32, mapping: M) {
+    let mapping: Arc<dyn TypeMapping> = Arc::new(mapping);
+
+    let mut registry = REGISTRY.write().unwrap();
+    if let Some(previous) = registry.by_oid.insert(type_oid, mapping.clone()) {
+        registry.by_extension_name.remove(previous.extension_name());
+    }
+    registry
+        .by_extension_name
+        .insert(mapping.extension_name().to_string(), (type_oid, mapping));
+}
+
+pub fn mapping_for_oid(type_oid: u32) -> Option<Arc<dyn TypeMapping>> {
+    REGISTRY.read().unwrap().by_oid.get(&type_oid).cloned()
+}
+
+/// Looks up the oid and mapping of a registered type by the extension name of the mapping
+pub fn mapping_for_extension_name(extension_name: &str) -> Option<(u32, Arc<dyn TypeMapping>)> {
+    REGISTRY
+        .read()
+        .unwrap()
+        .by_extension_name
+        .get(extension_name)
+        .cloned()
+}
+
+pub fn mapping_for_field(field: &arrow::datatypes::Field) -> Option<Arc<dyn TypeMapping>> {
+    field
+        .metadata()
+        .as_ref()
+        .and_then(|metadata| metadata.get(EXTENSION_NAME_METADATA_KEY))
+        .and_then(|extension_name| mapping_for_extension_name(extension_name))
+        .map(|(_, mapping)| mapping)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::data::arrow::{
+        serialize_record_batch_schema_to_row_description, serialize_record_batch_to_data_rows,
+        simple_query_response_to_record_batch,
+    };
+    use proboscis_postgres_protocol::message::DataRow;
+
+    #[test]
+    fn test_registered_type_round_trip() {
+        // Extension types get their oid when the extension is created
+        let citext_oid = 90001;
+        register_type_mapping(citext_oid, TextTypeMapping::new("postgres.citext"));
+
+        let fields = vec![proboscis_postgres_protocol::message::Field {
+            name: "email".to_string(),
+            table_oid: 0,
+            column_number: 0,
+            type_oid: citext_oid,
+            type_length: -1,
+            type_modifier: -1,
+            format: 0,
+        }];
+
+        let data = vec![DataRow {
+            field_data: vec![Some(b"Max@Example.com".to_vec())],
+        }];
+
+        let batch = simple_query_response_to_record_batch(&fields, &data).unwrap();
+
+        assert_eq!(&DataType::Utf8, batch.schema().field(0).data_type());
+
+        let deserialized_row_description =
+            serialize_record_batch_schema_to_row_description(&batch.schema());
+
+        let deserialized_data = serialize_record_batch_to_data_rows(&batch).unwrap();
+
+        assert_eq!(fields, deserialized_row_description.fields);
+        assert_eq!(data, deserialized_data);
+    }
+}
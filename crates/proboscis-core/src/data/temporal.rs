@@ -0,0 +1,397 @@
+use chrono::{Datelike, NaiveDate, NaiveDateTime, NaiveTime, Timelike};
+use std::io::{Error, ErrorKind};
+
+// Postgres counts binary dates and timestamps from 2000-01-01 instead of the unix epoch
+const POSTGRES_EPOCH_DAYS: i32 = 10_957;
+const POSTGRES_EPOCH_MICROSECONDS: i64 = 946_684_800_000_000;
+
+const MICROSECONDS_PER_SECOND: i64 = 1_000_000;
+
+// Postgres encodes infinity and -infinity as the extremes of the binary representation,
+// which are kept as the extremes of the unix based values
+const INFINITY: &str = "infinity";
+const NEGATIVE_INFINITY: &str = "-infinity";
+
+fn invalid(kind: &str, text: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid {}: {}", kind, text),
+    )
+}
+
+fn unix_epoch() -> NaiveDate {
+    NaiveDate::from_ymd(1970, 1, 1)
+}
+
+fn days_to_naive_date(days: i32) -> Option<NaiveDate> {
+    unix_epoch().checked_add_signed(chrono::Duration::days(days as i64))
+}
+
+/// Rewrites a date the way postgres renders it, with years beyond 9999 or followed by ` BC`,
+/// into one with a signed year, which chrono parses without limiting it to four digits
+fn normalize_year(text: &str) -> Option<String> {
+    let (text, before_christ) = match text.strip_suffix(" BC") {
+        Some(text) => (text, true),
+        None => (text, false),
+    };
+
+    let year_end = text.find('-')?;
+    let year: i32 = text[..year_end].parse().ok()?;
+    // There is no year 0, 1 BC is year 0 and 2 BC year -1
+    let year = if before_christ { 1 - year } else { year };
+
+    Some(format!("{:+}{}", year, &text[year_end..]))
+}
+
+/// Formats the year the way postgres does, years before 1 AD get a ` BC` suffix
+fn format_year(date: &NaiveDate) -> (String, &'static str) {
+    match date.year() {
+        year if year > 0 => (format!("{:04}", year), ""),
+        year => (format!("{:04}", 1 - year), " BC"),
+    }
+}
+
+fn read_i32(bytes: &[u8]) -> std::io::Result<i32> {
+    let mut buffer = [0; 4];
+    if bytes.len() != buffer.len() {
+        return Err(Error::new(ErrorKind::InvalidData, "expected 4 bytes"));
+    }
+    buffer.copy_from_slice(bytes);
+    Ok(i32::from_be_bytes(buffer))
+}
+
+fn read_i64(bytes: &[u8]) -> std::io::Result<i64> {
+    let mut buffer = [0; 8];
+    if bytes.len() != buffer.len() {
+        return Err(Error::new(ErrorKind::InvalidData, "expected 8 bytes"));
+    }
+    buffer.copy_from_slice(bytes);
+    Ok(i64::from_be_bytes(buffer))
+}
+
+/// Formats microseconds the way postgres does, omitting trailing zeros
+fn format_fraction(microseconds: u32) -> String {
+    if microseconds == 0 {
+        return String::new();
+    }
+
+    let fraction = format!("{:06}", microseconds);
+    format!(".{}", fraction.trim_end_matches('0'))
+}
+
+fn naive_date_time_to_microseconds(value: &NaiveDateTime) -> i64 {
+    value.timestamp() * MICROSECONDS_PER_SECOND + value.timestamp_subsec_micros() as i64
+}
+
+fn microseconds_to_naive_date_time(microseconds: i64) -> Option<NaiveDateTime> {
+    let seconds = microseconds.div_euclid(MICROSECONDS_PER_SECOND);
+    let subsec_microseconds = microseconds.rem_euclid(MICROSECONDS_PER_SECOND);
+    NaiveDateTime::from_timestamp_opt(seconds, subsec_microseconds as u32 * 1000)
+}
+
+/// Formats a timestamp, returning the era suffix separately as it follows a time zone offset
+fn format_naive_date_time(value: &NaiveDateTime) -> (String, &'static str) {
+    let (year, era) = format_year(&value.date());
+    let formatted = format!(
+        "{}-{}{}",
+        year,
+        value.format("%m-%d %H:%M:%S"),
+        format_fraction(value.nanosecond() / 1000)
+    );
+    (formatted, era)
+}
+
+/// Parses a date in ISO format into days since the unix epoch
+pub fn parse_date_text(text: &str) -> std::io::Result<i32> {
+    match text {
+        INFINITY => return Ok(i32::MAX),
+        NEGATIVE_INFINITY => return Ok(i32::MIN),
+        _ => {}
+    }
+
+    let date = normalize_year(text)
+        .and_then(|normalized| NaiveDate::parse_from_str(&normalized, "%Y-%m-%d").ok())
+        .ok_or_else(|| invalid("date", text))?;
+    Ok(date.signed_duration_since(unix_epoch()).num_days() as i32)
+}
+
+/// Formats a date, days beyond the range of dates are formatted as infinite
+pub fn format_date_text(days: i32) -> String {
+    match days_to_naive_date(days) {
+        Some(date) if days != i32::MAX && days != i32::MIN => {
+            let (year, era) = format_year(&date);
+            format!("{}-{}{}", year, date.format("%m-%d"), era)
+        }
+        _ if days < 0 => NEGATIVE_INFINITY.to_string(),
+        _ => INFINITY.to_string(),
+    }
+}
+
+pub fn parse_date_binary(bytes: &[u8]) -> std::io::Result<i32> {
+    let days = read_i32(bytes)?;
+    if days == i32::MAX || days == i32::MIN {
+        return Ok(days);
+    }
+
+    days.checked_add(POSTGRES_EPOCH_DAYS)
+        .filter(|days| days_to_naive_date(*days).is_some())
+        .ok_or_else(|| invalid("date", &days.to_string()))
+}
+
+pub fn format_date_binary(days: i32) -> Vec<u8> {
+    let days = match days {
+        i32::MAX | i32::MIN => days,
+        _ => days.saturating_sub(POSTGRES_EPOCH_DAYS),
+    };
+    days.to_be_bytes().to_vec()
+}
+
+/// Parses a time of day into microseconds since midnight
+pub fn parse_time_text(text: &str) -> std::io::Result<i64> {
+    let time = NaiveTime::parse_from_str(text, "%H:%M:%S%.f").map_err(|_| invalid("time", text))?;
+    Ok(
+        time.num_seconds_from_midnight() as i64 * MICROSECONDS_PER_SECOND
+            + (time.nanosecond() / 1000) as i64,
+    )
+}
+
+pub fn format_time_text(microseconds: i64) -> String {
+    let seconds = microseconds.div_euclid(MICROSECONDS_PER_SECOND);
+    let subsec_microseconds = microseconds.rem_euclid(MICROSECONDS_PER_SECOND) as u32;
+
+    format!(
+        "{:02}:{:02}:{:02}{}",
+        seconds / 3600,
+        (seconds / 60) % 60,
+        seconds % 60,
+        format_fraction(subsec_microseconds)
+    )
+}
+
+pub fn parse_time_binary(bytes: &[u8]) -> std::io::Result<i64> {
+    read_i64(bytes)
+}
+
+pub fn format_time_binary(microseconds: i64) -> Vec<u8> {
+    microseconds.to_be_bytes().to_vec()
+}
+
+/// Parses a timestamp without time zone into microseconds since the unix epoch
+pub fn parse_timestamp_text(text: &str) -> std::io::Result<i64> {
+    match text {
+        INFINITY => return Ok(i64::MAX),
+        NEGATIVE_INFINITY => return Ok(i64::MIN),
+        _ => {}
+    }
+
+    let value = normalize_year(text)
+        .and_then(|normalized| {
+            NaiveDateTime::parse_from_str(&normalized, "%Y-%m-%d %H:%M:%S%.f").ok()
+        })
+        .ok_or_else(|| invalid("timestamp", text))?;
+    Ok(naive_date_time_to_microseconds(&value))
+}
+
+/// Formats a timestamp, microseconds beyond the range of timestamps are formatted as infinite
+pub fn format_timestamp_text(microseconds: i64) -> String {
+    match format_timestamp_parts(microseconds) {
+        Ok((formatted, era)) => format!("{}{}", formatted, era),
+        Err(infinity) => infinity.to_string(),
+    }
+}
+
+fn format_timestamp_parts(microseconds: i64) -> Result<(String, &'static str), &'static str> {
+    match microseconds_to_naive_date_time(microseconds) {
+        Some(value) if microseconds != i64::MAX && microseconds != i64::MIN => {
+            Ok(format_naive_date_time(&value))
+        }
+        _ if microseconds < 0 => Err(NEGATIVE_INFINITY),
+        _ => Err(INFINITY),
+    }
+}
+
+/// Parses a timestamp with time zone into microseconds since the unix epoch (UTC).
+///
+/// Postgres renders these in the time zone of the session, followed by
+/// an offset of the form `+HH`, `+HH:MM` or `+HH:MM:SS`.
+pub fn parse_timestamptz_text(text: &str) -> std::io::Result<i64> {
+    match text {
+        INFINITY => return Ok(i64::MAX),
+        NEGATIVE_INFINITY => return Ok(i64::MIN),
+        _ => {}
+    }
+
+    // The era follows the offset, but belongs to the date
+    let (text, era) = match text.strip_suffix(" BC") {
+        Some(text) => (text, " BC"),
+        None => (text, ""),
+    };
+
+    // Skip the date part, which contains dashes as well
+    let offset_start = text
+        .char_indices()
+        .skip(10)
+        .find(|(_, c)| *c == '+' || *c == '-')
+        .map(|(index, _)| index)
+        .ok_or_else(|| invalid("timestamptz", text))?;
+
+    let (timestamp, offset) = text.split_at(offset_start);
+
+    let sign = if offset.starts_with('-') { -1 } else { 1 };
+    let mut offset_seconds = 0;
+    for (index, component) in offset[1..].split(':').enumerate() {
+        let component: i64 = component
+            .parse()
+            .map_err(|_| invalid("timestamptz", text))?;
+        offset_seconds += component
+            * [3600, 60, 1]
+                .get(index)
+                .ok_or_else(|| invalid("timestamptz", text))?;
+    }
+
+    let local = parse_timestamp_text(&format!("{}{}", timestamp, era))?;
+    Ok(local - sign * offset_seconds * MICROSECONDS_PER_SECOND)
+}
+
+/// Formats a timestamp with time zone, always rendering it in UTC
+pub fn format_timestamptz_text(microseconds: i64) -> String {
+    match format_timestamp_parts(microseconds) {
+        Ok((formatted, era)) => format!("{}+00{}", formatted, era),
+        Err(infinity) => infinity.to_string(),
+    }
+}
+
+/// Parses a binary timestamp (with or without time zone) into microseconds since the unix epoch
+pub fn parse_timestamp_binary(bytes: &[u8]) -> std::io::Result<i64> {
+    let microseconds = read_i64(bytes)?;
+    if microseconds == i64::MAX || microseconds == i64::MIN {
+        return Ok(microseconds);
+    }
+
+    microseconds
+        .checked_add(POSTGRES_EPOCH_MICROSECONDS)
+        .filter(|microseconds| microseconds_to_naive_date_time(*microseconds).is_some())
+        .ok_or_else(|| invalid("timestamp", &microseconds.to_string()))
+}
+
+pub fn format_timestamp_binary(microseconds: i64) -> Vec<u8> {
+    let microseconds = match microseconds {
+        i64::MAX | i64::MIN => microseconds,
+        _ => microseconds.saturating_sub(POSTGRES_EPOCH_MICROSECONDS),
+    };
+    microseconds.to_be_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_date() {
+        assert_eq!(18690, parse_date_text("2021-03-04").unwrap());
+        assert_eq!("2021-03-04", format_date_text(18690));
+        assert_eq!("1969-12-31", format_date_text(-1));
+
+        let binary = format_date_binary(18690);
+        assert_eq!(vec![0, 0, 30, 53], binary);
+        assert_eq!(18690, parse_date_binary(&binary).unwrap());
+    }
+
+    #[test]
+    fn test_time() {
+        let microseconds = parse_time_text("13:45:06.5").unwrap();
+        assert_eq!(49_506_500_000, microseconds);
+        assert_eq!("13:45:06.5", format_time_text(microseconds));
+        assert_eq!("00:00:00", format_time_text(0));
+    }
+
+    #[test]
+    fn test_timestamp() {
+        let microseconds = parse_timestamp_text("2021-03-04 13:45:06.123456").unwrap();
+        assert_eq!(1_614_865_506_123_456, microseconds);
+        assert_eq!(
+            "2021-03-04 13:45:06.123456",
+            format_timestamp_text(microseconds)
+        );
+
+        let binary = format_timestamp_binary(microseconds);
+        assert_eq!(microseconds, parse_timestamp_binary(&binary).unwrap());
+        assert_eq!(
+            POSTGRES_EPOCH_MICROSECONDS,
+            parse_timestamp_binary(&[0; 8]).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_timestamptz() {
+        let utc = parse_timestamptz_text("2021-03-04 13:45:06+00").unwrap();
+        assert_eq!(
+            utc,
+            parse_timestamptz_text("2021-03-04 15:15:06+01:30").unwrap()
+        );
+        assert_eq!(
+            utc,
+            parse_timestamptz_text("2021-03-04 08:45:06-05").unwrap()
+        );
+        assert_eq!("2021-03-04 13:45:06+00", format_timestamptz_text(utc));
+    }
+
+    #[test]
+    fn test_infinity() {
+        for (text, days) in [("infinity", i32::MAX), ("-infinity", i32::MIN)].iter() {
+            assert_eq!(*days, parse_date_text(text).unwrap());
+            assert_eq!(*text, format_date_text(*days));
+            assert_eq!(days.to_be_bytes().to_vec(), format_date_binary(*days));
+            assert_eq!(*days, parse_date_binary(&days.to_be_bytes()).unwrap());
+        }
+
+        for (text, microseconds) in [("infinity", i64::MAX), ("-infinity", i64::MIN)].iter() {
+            assert_eq!(*microseconds, parse_timestamp_text(text).unwrap());
+            assert_eq!(*microseconds, parse_timestamptz_text(text).unwrap());
+            assert_eq!(*text, format_timestamp_text(*microseconds));
+            assert_eq!(*text, format_timestamptz_text(*microseconds));
+
+            let binary = microseconds.to_be_bytes();
+            assert_eq!(binary.to_vec(), format_timestamp_binary(*microseconds));
+            assert_eq!(*microseconds, parse_timestamp_binary(&binary).unwrap());
+        }
+
+        assert!(parse_date_binary(&(i32::MAX - 1).to_be_bytes()).is_err());
+        assert!(parse_timestamp_binary(&(i64::MAX - 1).to_be_bytes()).is_err());
+    }
+
+    #[test]
+    fn test_years_beyond_four_digits() {
+        for text in ["0044-03-15 BC", "0001-01-01 BC", "12345-06-07"].iter() {
+            assert_eq!(*text, format_date_text(parse_date_text(text).unwrap()));
+        }
+        assert_eq!(
+            parse_date_text("0001-12-31 BC").unwrap() + 1,
+            parse_date_text("0001-01-01").unwrap()
+        );
+
+        let microseconds = parse_timestamp_text("0044-03-15 12:00:00.5 BC").unwrap();
+        assert_eq!(
+            "0044-03-15 12:00:00.5 BC",
+            format_timestamp_text(microseconds)
+        );
+        assert_eq!(
+            microseconds,
+            parse_timestamp_binary(&format_timestamp_binary(microseconds)).unwrap()
+        );
+        assert_eq!(
+            microseconds,
+            parse_timestamptz_text("0044-03-15 13:00:00.5+01 BC").unwrap()
+        );
+        assert_eq!(
+            "0044-03-15 12:00:00.5+00 BC",
+            format_timestamptz_text(microseconds)
+        );
+
+        let microseconds = parse_timestamptz_text("12345-06-07 08:09:10+00").unwrap();
+        assert_eq!(
+            "12345-06-07 08:09:10+00",
+            format_timestamptz_text(microseconds)
+        );
+    }
+}
@@ -1,13 +1,32 @@
+use crate::sqlstate;
 use thiserror::Error;
 
+/// Why a client couldn't authenticate
+#[derive(Error, Debug)]
+pub enum AuthError {
+    #[error("incorrect password of user {0}")]
+    IncorrectPassword(String),
+
+    #[error("missing password for user {0} in config")]
+    UnknownUser(String),
+
+    #[error(transparent)]
+    Scram(#[from] crate::utils::scram::ScramError),
+}
+
 #[derive(Error, Debug)]
 pub enum ProboscisError {
     #[error(transparent)]
     Io(#[from] tokio::io::Error),
 
+    #[cfg(feature = "tls")]
     #[error(transparent)]
     TLS(#[from] native_tls::Error),
 
+    #[cfg(not(feature = "tls"))]
+    #[error("tls is configured, but proboscis was built without the tls feature")]
+    TlsDisabled,
+
     #[error(transparent)]
     Arrow(#[from] arrow::error::ArrowError),
 
@@ -17,15 +36,51 @@ pub enum ProboscisError {
     #[error(transparent)]
     Resolve(#[from] crate::resolver::ResolveError),
 
+    #[error(transparent)]
+    Auth(#[from] AuthError),
+
     #[error("expected message: {0}")]
     ExpectedMessage(&'static str),
 
-    #[error("incorrect password")]
-    IncorrectPassword,
+    #[error("frontend connected without tls, which the listener requires")]
+    PlaintextConnectionDenied,
 
-    #[error("frontend requested tls, but tls is not configured")]
-    FrontendRequestedTLS,
+    #[error("frontend requested gssapi encryption, which is rejected")]
+    FrontendRequestedGssEncryption,
 
-    #[error("missing password for user {0} in config")]
-    MissingPasswordInConfig(String),
+    #[error("unsupported client encoding {0}")]
+    UnsupportedClientEncoding(String),
+}
+
+impl ProboscisError {
+    /// The code of the ErrorResponse a client receives for the error
+    pub fn sqlstate(&self) -> &str {
+        match self {
+            ProboscisError::Io(_) => sqlstate::CONNECTION_FAILURE,
+            #[cfg(feature = "tls")]
+            ProboscisError::TLS(_) => sqlstate::CONNECTION_FAILURE,
+            #[cfg(not(feature = "tls"))]
+            ProboscisError::TlsDisabled => sqlstate::FEATURE_NOT_SUPPORTED,
+            ProboscisError::Arrow(_) => sqlstate::INTERNAL_ERROR,
+            ProboscisError::Parse(_) | ProboscisError::ExpectedMessage(_) => {
+                sqlstate::PROTOCOL_VIOLATION
+            }
+            ProboscisError::Resolve(err) => err.sqlstate(),
+            ProboscisError::Auth(_) => sqlstate::INVALID_PASSWORD,
+            ProboscisError::PlaintextConnectionDenied => {
+                sqlstate::INVALID_AUTHORIZATION_SPECIFICATION
+            }
+            ProboscisError::FrontendRequestedGssEncryption
+            | ProboscisError::UnsupportedClientEncoding(_) => sqlstate::FEATURE_NOT_SUPPORTED,
+        }
+    }
+
+    /// The ErrorResponse ending the connection of a client for the error, errors of the target
+    /// are passed on with their details
+    pub fn to_error_response(&self) -> proboscis_postgres_protocol::message::Error {
+        match self {
+            ProboscisError::Resolve(err) => err.to_error_response().with_severity(sqlstate::FATAL),
+            _ => sqlstate::error_response(sqlstate::FATAL, self.sqlstate(), self.to_string()),
+        }
+    }
 }
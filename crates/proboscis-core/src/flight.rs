@@ -0,0 +1,205 @@
+use crate::{
+    resolver::{ClientContext, ClientId, Resolver},
+    utils::password::verify_password,
+    SharedCredentials,
+};
+use arrow::{datatypes::Schema, ipc::writer::IpcWriteOptions};
+use arrow_flight::{
+    flight_service_server::{FlightService, FlightServiceServer},
+    utils::flight_data_from_arrow_batch,
+    Action, ActionType, Criteria, Empty, FlightData, FlightDescriptor, FlightInfo,
+    HandshakeRequest, HandshakeResponse, PutResult, SchemaAsIpc, SchemaResult, Ticket,
+};
+use futures::Stream;
+use std::{net::SocketAddr, pin::Pin, sync::Arc};
+use tokio::sync::Mutex;
+use tonic::{metadata::MetadataMap, transport::Server, Request, Response, Status, Streaming};
+use tracing::info;
+use uuid::Uuid;
+
+type FlightStream<T> = Pin<Box<dyn Stream<Item = Result<T, Status>> + Send + Sync + 'static>>;
+
+/// Serves queries over Arrow Flight, so clients can fetch large results as arrow batches
+/// instead of postgres rows. The ticket of `DoGet` is the query, clients authenticate with
+/// an `authorization: Basic ...` header of the credentials they'd use with the proxy.
+/// Queries pass through the same resolver stack, including its transformers,
+/// but are answered one at a time.
+pub struct FlightServer {
+    resolver: Arc<Mutex<Box<dyn Resolver>>>,
+    credentials: SharedCredentials,
+}
+
+impl FlightServer {
+    pub fn new(resolver: Box<dyn Resolver>, credentials: SharedCredentials) -> FlightServer {
+        FlightServer {
+            resolver: Arc::new(Mutex::new(resolver)),
+            credentials,
+        }
+    }
+
+    pub async fn serve(self, address: SocketAddr) -> Result<(), tonic::transport::Error> {
+        info!("flight server listening on {}", address);
+
+        Server::builder()
+            .add_service(FlightServiceServer::new(self))
+            .serve(address)
+            .await
+    }
+
+    fn authenticate(&self, metadata: &MetadataMap) -> Result<String, Status> {
+        let (user, password) = basic_credentials(metadata)
+            .ok_or_else(|| Status::unauthenticated("missing basic authorization header"))?;
+
+        let credentials = self.credentials.read().expect("Credentials lock poisoned");
+
+        match credentials.get(&user) {
+            Some(stored) if verify_password(&user, &password, stored) => Ok(user),
+            _ => Err(Status::unauthenticated("incorrect user or password")),
+        }
+    }
+}
+
+/// The user and password of an `authorization: Basic <base64 of user:password>` header
+fn basic_credentials(metadata: &MetadataMap) -> Option<(String, String)> {
+    let header = metadata.get("authorization")?.to_str().ok()?;
+    let encoded = header.strip_prefix("Basic ")?;
+    let decoded = String::from_utf8(base64::decode(encoded.trim()).ok()?).ok()?;
+    let (user, password) = decoded.split_once(':')?;
+
+    Some((user.to_string(), password.to_string()))
+}
+
+#[tonic::async_trait]
+impl FlightService for FlightServer {
+    type HandshakeStream = FlightStream<HandshakeResponse>;
+    type ListFlightsStream = FlightStream<FlightInfo>;
+    type DoGetStream = FlightStream<FlightData>;
+    type DoPutStream = FlightStream<PutResult>;
+    type DoActionStream = FlightStream<arrow_flight::Result>;
+    type ListActionsStream = FlightStream<ActionType>;
+    type DoExchangeStream = FlightStream<FlightData>;
+
+    async fn do_get(
+        &self,
+        request: Request<Ticket>,
+    ) -> Result<Response<Self::DoGetStream>, Status> {
+        let user = self.authenticate(request.metadata())?;
+        let query = String::from_utf8(request.into_inner().ticket)
+            .map_err(|_| Status::invalid_argument("the ticket has to be a utf-8 query"))?;
+
+        let client_id: ClientId = Uuid::new_v4();
+        let context = ClientContext::new(vec![("user".to_string(), user)].into_iter().collect());
+
+        let mut resolver = self.resolver.lock().await;
+        let result = match resolver.initialize(client_id, &context).await {
+            Ok(()) => resolver.query(client_id, query).await,
+            Err(err) => Err(err),
+        };
+        resolver
+            .terminate(client_id)
+            .await
+            .map_err(|err| Status::internal(err.to_string()))?;
+        drop(resolver);
+
+        let batches = result.map_err(|err| Status::internal(err.to_string()))?;
+
+        let options = IpcWriteOptions::default();
+        let schema = batches
+            .first()
+            .map(|batch| batch.schema())
+            .unwrap_or_else(|| Arc::new(Schema::empty()));
+
+        let mut flight_data: Vec<Result<FlightData, Status>> =
+            vec![Ok(SchemaAsIpc::new(&schema, &options).into())];
+        for batch in &batches {
+            let (dictionaries, batch) = flight_data_from_arrow_batch(batch, &options);
+            flight_data.extend(dictionaries.into_iter().map(Ok));
+            flight_data.push(Ok(batch));
+        }
+
+        Ok(Response::new(Box::pin(futures::stream::iter(flight_data))))
+    }
+
+    async fn handshake(
+        &self,
+        _request: Request<Streaming<HandshakeRequest>>,
+    ) -> Result<Response<Self::HandshakeStream>, Status> {
+        Err(Status::unimplemented(
+            "authenticate every request with a basic authorization header",
+        ))
+    }
+
+    async fn list_flights(
+        &self,
+        _request: Request<Criteria>,
+    ) -> Result<Response<Self::ListFlightsStream>, Status> {
+        Err(Status::unimplemented("list_flights"))
+    }
+
+    async fn get_flight_info(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<FlightInfo>, Status> {
+        Err(Status::unimplemented("get_flight_info"))
+    }
+
+    async fn get_schema(
+        &self,
+        _request: Request<FlightDescriptor>,
+    ) -> Result<Response<SchemaResult>, Status> {
+        Err(Status::unimplemented("get_schema"))
+    }
+
+    async fn do_put(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoPutStream>, Status> {
+        Err(Status::unimplemented("do_put"))
+    }
+
+    async fn do_action(
+        &self,
+        _request: Request<Action>,
+    ) -> Result<Response<Self::DoActionStream>, Status> {
+        Err(Status::unimplemented("do_action"))
+    }
+
+    async fn list_actions(
+        &self,
+        _request: Request<Empty>,
+    ) -> Result<Response<Self::ListActionsStream>, Status> {
+        Err(Status::unimplemented("list_actions"))
+    }
+
+    async fn do_exchange(
+        &self,
+        _request: Request<Streaming<FlightData>>,
+    ) -> Result<Response<Self::DoExchangeStream>, Status> {
+        Err(Status::unimplemented("do_exchange"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_basic_credentials() {
+        let mut metadata = MetadataMap::new();
+        assert_eq!(None, basic_credentials(&metadata));
+
+        metadata.insert(
+            "authorization",
+            format!("Basic {}", base64::encode("admin:pass:word"))
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            Some(("admin".to_string(), "pass:word".to_string())),
+            basic_credentials(&metadata)
+        );
+
+        metadata.insert("authorization", "Bearer token".parse().unwrap());
+        assert_eq!(None, basic_credentials(&metadata));
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{resolver::ClientContext, sqlstate, ProboscisError};
+use async_trait::async_trait;
+use proboscis_postgres_protocol::message::Error;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use uuid::Uuid;
+
+/// A connection the proxy accepted, before the client sent its startup message
+#[derive(Debug, Clone)]
+pub struct ConnectionInfo {
+    pub client_id: Uuid,
+    pub address: SocketAddr,
+}
+
+impl ConnectionInfo {
+    /// The short id of the client's session, see `sessions::correlation_id`
+    pub fn correlation_id(&self) -> String {
+        crate::sessions::correlation_id(&self.client_id)
+    }
+}
+
+/// An authenticated client
+#[derive(Debug, Clone)]
+pub struct SessionInfo {
+    pub connection: ConnectionInfo,
+    /// The parameters of the client's startup message, e.g. `user` and `database`
+    pub context: ClientContext,
+}
+
+/// A query a client ran
+#[derive(Debug, Clone)]
+pub struct QueryOutcome {
+    pub query: String,
+    pub rows: u64,
+    pub duration: Duration,
+}
+
+/// Refuses a client or query, the client receives an ErrorResponse with the code and message
+#[derive(Debug, Clone, PartialEq)]
+pub struct HookRejection {
+    pub code: String,
+    pub message: String,
+}
+
+impl HookRejection {
+    pub fn new(code: &str, message: &str) -> HookRejection {
+        HookRejection {
+            code: code.to_string(),
+            message: message.to_string(),
+        }
+    }
+
+    /// Rejects for lack of privilege, like postgres does for denied relations
+    pub fn denied(message: &str) -> HookRejection {
+        HookRejection::new(sqlstate::INSUFFICIENT_PRIVILEGE, message)
+    }
+
+    pub(crate) fn to_error_response(&self, severity: &str) -> Error {
+        sqlstate::error_response(severity, &self.code, self.message.clone())
+    }
+}
+
+/// Called at the stages of a client's lifecycle, e.g. to audit or limit what clients do
+/// without wrapping resolvers. Every method does nothing by default.
+///
+/// Hooks run on the task of the client, a slow hook delays the client it is called for.
+#[async_trait]
+pub trait ProxyHook: Send + Sync {
+    /// A connection was accepted, the client didn't send its startup message yet
+    async fn on_client_connect(&self, _connection: &ConnectionInfo) {}
+
+    /// The client was authenticated, a rejection closes its connection
+    async fn on_authenticated(&self, _session: &SessionInfo) -> Result<(), HookRejection> {
+        Ok(())
+    }
+
+    /// Called before a simple query or the parse of a prepared statement is resolved,
+    /// a rejected query fails without reaching the resolver
+    async fn on_query(&self, _session: &SessionInfo, _query: &str) -> Result<(), HookRejection> {
+        Ok(())
+    }
+
+    /// A query was answered
+    async fn on_result(&self, _session: &SessionInfo, _outcome: &QueryOutcome) {}
+
+    /// Answering a request of the client failed, the connection is closed unless
+    /// the error is a recoverable one of the resolver
+    async fn on_error(&self, _connection: &ConnectionInfo, _error: &ProboscisError) {}
+
+    /// The connection was closed, after the client was served for the duration
+    async fn on_disconnect(&self, _connection: &ConnectionInfo, _duration: Duration) {}
+}
+
+/// The hooks of a proxy, called in the order they were registered
+#[derive(Clone, Default)]
+pub struct Hooks {
+    hooks: Vec<Arc<dyn ProxyHook>>,
+}
+
+impl Hooks {
+    pub fn register(&mut self, hook: Arc<dyn ProxyHook>) {
+        self.hooks.push(hook);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    pub(crate) async fn on_client_connect(&self, connection: &ConnectionInfo) {
+        for hook in &self.hooks {
+            hook.on_client_connect(connection).await;
+        }
+    }
+
+    /// The first rejection, the remaining hooks aren't called then
+    pub(crate) async fn on_authenticated(
+        &self,
+        session: &SessionInfo,
+    ) -> Result<(), HookRejection> {
+        for hook in &self.hooks {
+            hook.on_authenticated(session).await?;
+        }
+        Ok(())
+    }
+
+    /// The first rejection, the remaining hooks aren't called then
+    pub(crate) async fn on_query(
+        &self,
+        session: &SessionInfo,
+        query: &str,
+    ) -> Result<(), HookRejection> {
+        for hook in &self.hooks {
+            hook.on_query(session, query).await?;
+        }
+        Ok(())
+    }
+
+    pub(crate) async fn on_result(&self, session: &SessionInfo, outcome: &QueryOutcome) {
+        for hook in &self.hooks {
+            hook.on_result(session, outcome).await;
+        }
+    }
+
+    pub(crate) async fn on_error(&self, connection: &ConnectionInfo, error: &ProboscisError) {
+        for hook in &self.hooks {
+            hook.on_error(connection, error).await;
+        }
+    }
+
+    pub(crate) async fn on_disconnect(&self, connection: &ConnectionInfo, duration: Duration) {
+        for hook in &self.hooks {
+            hook.on_disconnect(connection, duration).await;
+        }
+    }
+}
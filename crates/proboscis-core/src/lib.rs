@@ -1,10 +1,30 @@
+mod builder;
+pub mod catalog;
 pub mod data;
 mod error;
+#[cfg(feature = "flight")]
+pub mod flight;
+pub mod hooks;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(not(feature = "metrics"))]
+mod no_metrics;
+#[cfg(not(feature = "metrics"))]
+pub use no_metrics as metrics;
 mod proxy;
 pub mod resolver;
+pub mod sessions;
+pub mod sqlstate;
 pub mod utils;
 
-pub use crate::error::ProboscisError;
+pub use crate::builder::{ProxyBuildError, ProxyBuilder};
+pub use crate::error::{AuthError, ProboscisError};
+pub use crate::hooks::{HookRejection, ProxyHook};
+pub use crate::metrics::{ProxyMetrics, QueryStats};
+pub use crate::proxy::AuthenticationMethod;
 pub use crate::proxy::Config;
+pub use crate::proxy::GssEncryption;
+pub use crate::proxy::Listener;
 pub use crate::proxy::Proxy;
+pub use crate::proxy::SharedCredentials;
 pub use crate::proxy::TlsConfig;
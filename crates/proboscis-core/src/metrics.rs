@@ -0,0 +1,255 @@
+use crate::utils::fingerprint::{fingerprint, normalize_query};
+use once_cell::sync::Lazy;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// Upper bounds of the latency histogram buckets in seconds
+pub const LATENCY_BUCKETS: [f64; 10] =
+    [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Parts of answering a query, which are timed separately,
+/// so the time spent in the proxy can be told apart from the time spent in the database
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    /// Parsing the query and tracing the origins of its columns
+    Parse,
+    TransformSchema,
+    /// From sending a request to the database until its response is read
+    Upstream,
+    /// Applying the transformers to the records
+    Anonymization,
+    /// Writing the records to the client
+    Serialization,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Parse,
+        Stage::TransformSchema,
+        Stage::Upstream,
+        Stage::Anonymization,
+        Stage::Serialization,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Parse => "parse",
+            Stage::TransformSchema => "transform_schema",
+            Stage::Upstream => "upstream",
+            Stage::Anonymization => "anonymization",
+            Stage::Serialization => "serialization",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Histogram {
+    // Observations per bucket of `LATENCY_BUCKETS`, the last one counts those above every bound
+    buckets: [AtomicU64; LATENCY_BUCKETS.len() + 1],
+    sum_micros: AtomicU64,
+}
+
+/// Observations of a histogram, with the counts of the buckets being cumulative
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<(f64, u64)>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+impl Histogram {
+    pub fn observe(&self, duration: Duration) {
+        let seconds = duration.as_secs_f64();
+        let bucket = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+
+        self.buckets[bucket].fetch_add(1, Ordering::Relaxed);
+        self.sum_micros
+            .fetch_add(duration.as_micros() as u64, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> HistogramSnapshot {
+        let mut count = 0;
+        let mut buckets = vec![];
+
+        for (bound, bucket) in LATENCY_BUCKETS.iter().zip(self.buckets.iter()) {
+            count += bucket.load(Ordering::Relaxed);
+            buckets.push((*bound, count));
+        }
+        count += self.buckets[LATENCY_BUCKETS.len()].load(Ordering::Relaxed);
+
+        HistogramSnapshot {
+            buckets,
+            count,
+            sum: Duration::from_micros(self.sum_micros.load(Ordering::Relaxed)),
+        }
+    }
+}
+
+// The stages run in different resolvers, which report to the same histograms
+static STAGE_LATENCIES: Lazy<[Histogram; 5]> = Lazy::new(Default::default);
+
+fn stage_histogram(stage: Stage) -> &'static Histogram {
+    let index = Stage::ALL
+        .iter()
+        .position(|candidate| *candidate == stage)
+        .expect("Stage is missing in Stage::ALL");
+
+    &STAGE_LATENCIES[index]
+}
+
+pub fn observe_stage(stage: Stage, duration: Duration) {
+    stage_histogram(stage).observe(duration);
+}
+
+/// Runs the function and adds its duration to the histogram of the stage
+pub fn time_stage<T, F: FnOnce() -> T>(stage: Stage, f: F) -> T {
+    let started = Instant::now();
+    let result = f();
+    observe_stage(stage, started.elapsed());
+    result
+}
+
+pub fn stage_latency(stage: Stage) -> HistogramSnapshot {
+    stage_histogram(stage).snapshot()
+}
+
+// Results are capped in the resolvers, which don't know the metrics of their proxy
+static TRUNCATED_RESULTS: AtomicU64 = AtomicU64::new(0);
+
+/// Counts a result that was cut off at the row cap of its client
+pub fn record_truncation() {
+    TRUNCATED_RESULTS.fetch_add(1, Ordering::Relaxed);
+}
+
+pub fn truncated_results() -> u64 {
+    TRUNCATED_RESULTS.load(Ordering::Relaxed)
+}
+
+/// Aggregated executions of the queries sharing a fingerprint
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryStats {
+    pub normalized_query: String,
+    pub executions: u64,
+    pub total_duration: Duration,
+    pub rows: u64,
+}
+
+/// Counters of a proxy, shared with whatever reports them while the proxy is listening
+#[derive(Debug, Default)]
+pub struct ProxyMetrics {
+    /// Connections accepted from clients
+    pub connections: AtomicU64,
+    /// Connections closed during their startup, e.g. as no resolver serves the requested database
+    pub rejected_connections: AtomicU64,
+    /// Connections closed as the client didn't finish its startup or authentication in time
+    pub timed_out_connections: AtomicU64,
+    /// Connections closed as the address of the client isn't allowed
+    pub denied_connections: AtomicU64,
+    /// Maps query fingerprints to their stats
+    queries: Mutex<HashMap<String, QueryStats>>,
+}
+
+impl ProxyMetrics {
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn timed_out_connections(&self) -> u64 {
+        self.timed_out_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn denied_connections(&self) -> u64 {
+        self.denied_connections.load(Ordering::Relaxed)
+    }
+
+    /// Adds an execution of the query to the stats of its fingerprint
+    pub fn record_query(&self, query: &str, duration: Duration, rows: u64) {
+        let mut queries = self.queries.lock().expect("Query metrics lock poisoned");
+
+        let stats = queries
+            .entry(fingerprint(query))
+            .or_insert_with(|| QueryStats {
+                normalized_query: normalize_query(query),
+                ..QueryStats::default()
+            });
+
+        stats.executions += 1;
+        stats.total_duration += duration;
+        stats.rows += rows;
+    }
+
+    /// The stats of every fingerprint, ordered by fingerprint
+    pub fn query_stats(&self) -> Vec<(String, QueryStats)> {
+        let queries = self.queries.lock().expect("Query metrics lock poisoned");
+
+        let mut stats: Vec<(String, QueryStats)> = queries
+            .iter()
+            .map(|(fingerprint, stats)| (fingerprint.clone(), stats.clone()))
+            .collect();
+        stats.sort_by(|(a, _), (b, _)| a.cmp(b));
+
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_histogram() {
+        let histogram = Histogram::default();
+
+        histogram.observe(Duration::from_micros(50));
+        histogram.observe(Duration::from_millis(3));
+        histogram.observe(Duration::from_secs(10));
+
+        let snapshot = histogram.snapshot();
+        assert_eq!(3, snapshot.count);
+        assert_eq!((0.0001, 1), snapshot.buckets[0]);
+        assert_eq!((0.005, 2), snapshot.buckets[3]);
+        assert_eq!((5.0, 2), snapshot.buckets[9]);
+        assert_eq!(Duration::from_micros(10_003_050), snapshot.sum);
+    }
+
+    #[test]
+    fn test_record_query() {
+        let metrics = ProxyMetrics::default();
+
+        metrics.record_query(
+            "SELECT * FROM contacts WHERE id = 1",
+            Duration::from_millis(3),
+            1,
+        );
+        metrics.record_query(
+            "select * from contacts where id = 2",
+            Duration::from_millis(5),
+            0,
+        );
+
+        let stats = metrics.query_stats();
+        assert_eq!(1, stats.len());
+
+        let (_, stats) = &stats[0];
+        assert_eq!(
+            "select * from contacts where id = ?",
+            stats.normalized_query
+        );
+        assert_eq!(2, stats.executions);
+        assert_eq!(Duration::from_millis(8), stats.total_duration);
+        assert_eq!(1, stats.rows);
+    }
+}
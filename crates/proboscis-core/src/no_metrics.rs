@@ -0,0 +1,110 @@
+//! Stands in for the metrics module without the `metrics` feature, the latencies of stages
+//! and the stats of queries aren't recorded
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Upper bounds of the latency histogram buckets in seconds
+pub const LATENCY_BUCKETS: [f64; 10] =
+    [0.0001, 0.0005, 0.001, 0.005, 0.01, 0.05, 0.1, 0.5, 1.0, 5.0];
+
+/// Parts of answering a query, which would be timed separately
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Parse,
+    TransformSchema,
+    Upstream,
+    Anonymization,
+    Serialization,
+}
+
+impl Stage {
+    pub const ALL: [Stage; 5] = [
+        Stage::Parse,
+        Stage::TransformSchema,
+        Stage::Upstream,
+        Stage::Anonymization,
+        Stage::Serialization,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Stage::Parse => "parse",
+            Stage::TransformSchema => "transform_schema",
+            Stage::Upstream => "upstream",
+            Stage::Anonymization => "anonymization",
+            Stage::Serialization => "serialization",
+        }
+    }
+}
+
+/// Observations of a histogram, always empty
+#[derive(Debug, Clone, PartialEq)]
+pub struct HistogramSnapshot {
+    pub buckets: Vec<(f64, u64)>,
+    pub count: u64,
+    pub sum: Duration,
+}
+
+pub fn observe_stage(_stage: Stage, _duration: Duration) {}
+
+/// Runs the function
+pub fn time_stage<T, F: FnOnce() -> T>(_stage: Stage, f: F) -> T {
+    f()
+}
+
+pub fn stage_latency(_stage: Stage) -> HistogramSnapshot {
+    HistogramSnapshot {
+        buckets: LATENCY_BUCKETS.iter().map(|bound| (*bound, 0)).collect(),
+        count: 0,
+        sum: Duration::default(),
+    }
+}
+
+pub fn record_truncation() {}
+
+pub fn truncated_results() -> u64 {
+    0
+}
+
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct QueryStats {
+    pub normalized_query: String,
+    pub executions: u64,
+    pub total_duration: Duration,
+    pub rows: u64,
+}
+
+/// The connection counters of a proxy, which are kept without the feature as well
+#[derive(Debug, Default)]
+pub struct ProxyMetrics {
+    pub connections: AtomicU64,
+    pub rejected_connections: AtomicU64,
+    pub timed_out_connections: AtomicU64,
+    pub denied_connections: AtomicU64,
+}
+
+impl ProxyMetrics {
+    pub fn connections(&self) -> u64 {
+        self.connections.load(Ordering::Relaxed)
+    }
+
+    pub fn rejected_connections(&self) -> u64 {
+        self.rejected_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn timed_out_connections(&self) -> u64 {
+        self.timed_out_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn denied_connections(&self) -> u64 {
+        self.denied_connections.load(Ordering::Relaxed)
+    }
+
+    pub fn record_query(&self, _query: &str, _duration: Duration, _rows: u64) {}
+
+    pub fn query_stats(&self) -> Vec<(String, QueryStats)> {
+        vec![]
+    }
+}
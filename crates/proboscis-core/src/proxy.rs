@@ -1,21 +1,44 @@
 use crate::{
-    resolver::Resolver,
+    hooks::{ConnectionInfo, Hooks, ProxyHook, QueryOutcome, SessionInfo},
+    metrics::{observe_stage, ProxyMetrics, Stage},
+    resolver::{sync_error, ClientContext, CopyOutMessage, ResolveError, Resolver, SyncResponse},
+    sessions, sqlstate,
+    utils::address_filter::AddressFilter,
     utils::connection::{Connection, MaybeTlsStream},
-    utils::password::encode_md5_password_hash,
-    ProboscisError,
+    utils::copy::{parse_copy_statement, CopyDirection},
+    utils::encoding::{is_supported_client_encoding, SUPPORTED_CLIENT_ENCODING},
+    utils::fingerprint::fingerprint,
+    utils::password::{
+        encode_md5_password_hash, encode_md5_verifier_hash, is_md5_password_verifier,
+    },
+    utils::scram::{ScramError, ScramServer, ScramVerifier, SCRAM_SHA_256},
+    utils::tls::{ReloadingTlsAcceptor, TlsAcceptor},
+    utils::transaction::TransactionState,
+    AuthError, ProboscisError,
 };
-use native_tls::Identity;
+use futures::{FutureExt, StreamExt};
 use proboscis_postgres_protocol::{
     message::{
-        BackendMessage, CommandCompleteTag, FrontendMessage, MD5Hash, MD5Salt,
-        ReadyForQueryTransactionStatus,
+        BackendMessage, CloseKind, CommandCompleteTag, Error, FrontendMessage, MD5Hash, MD5Salt,
+        Message, ParameterStatus, ReadyForQueryTransactionStatus, SASLInitialResponse,
     },
     StartupMessage,
 };
 use rand::Rng;
-use std::{collections::HashMap, fs::File, io::Read};
-use tokio::{io::AsyncWriteExt, net::TcpListener};
-use tracing::{info, trace_span, Instrument};
+use std::{
+    any::Any,
+    collections::HashMap,
+    panic::AssertUnwindSafe,
+    sync::{atomic::Ordering, Arc, RwLock},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::AsyncWriteExt,
+    net::TcpListener,
+    sync::{mpsc, Mutex},
+    time::{sleep, timeout},
+};
+use tracing::{error, info, info_span, warn, Instrument};
 use uuid::Uuid;
 
 #[derive(Clone)]
@@ -27,69 +50,577 @@ pub struct TlsConfig {
 #[derive(Clone)]
 pub struct Config {
     pub tls_config: Option<TlsConfig>,
+    /// Maps usernames to their passwords, or to md5 verifiers of their passwords
     pub credentials: HashMap<String, String>,
 }
 
+/// A socket the proxy accepts clients on, with the tls settings for its clients
+pub struct Listener {
+    pub listener: TcpListener,
+    pub tls_config: Option<TlsConfig>,
+    /// Rejects clients which don't use tls, instead of serving them in plaintext
+    pub require_tls: bool,
+}
+
+/// How requests of clients for GSSAPI encryption are answered, which the proxy doesn't support
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum GssEncryption {
+    /// Declines the request and logs it, so the client falls back to tls or plaintext
+    Decline,
+    /// Closes the connection with an error
+    Reject,
+}
+
+impl Default for GssEncryption {
+    fn default() -> Self {
+        GssEncryption::Decline
+    }
+}
+
+/// How the proxy authenticates clients with the configured credentials
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AuthenticationMethod {
+    /// Requests an md5 hash of the password, users whose credential is a SCRAM verifier
+    /// are authenticated with SCRAM-SHA-256
+    Md5,
+    /// Requests a SCRAM-SHA-256 exchange, like postgres with `password_encryption = scram-sha-256`.
+    /// Users whose credential is an md5 verifier can't take part in it and fall back to md5.
+    ScramSha256,
+}
+
+impl Default for AuthenticationMethod {
+    fn default() -> Self {
+        AuthenticationMethod::Md5
+    }
+}
+
+const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(60);
+const DEFAULT_AUTHENTICATION_TIMEOUT: Duration = Duration::from_secs(60);
+// How often messages the target sends to idle clients are polled for
+const IDLE_POLL_INTERVAL: Duration = Duration::from_millis(100);
+// How long a listener waits after failing to accept a connection, e.g. as it ran out of file descriptors
+const ACCEPT_ERROR_BACKOFF: Duration = Duration::from_millis(100);
+
+/// A resolver shared by the tasks of the clients it serves, one request at a time
+type SharedResolver = Arc<Mutex<Box<dyn Resolver>>>;
+
+/// Credentials which can be replaced while the proxy is listening
+pub type SharedCredentials = Arc<RwLock<HashMap<String, String>>>;
+
 pub struct Proxy {
     config: Config,
-    resolver: Box<dyn Resolver>,
+    resolver: Option<SharedResolver>,
+    // Resolvers of specific databases, chosen by the database a client connects to
+    database_resolvers: HashMap<String, SharedResolver>,
+    // Resolvers chosen by the application_name of a client, before its database
+    application_resolvers: HashMap<String, SharedResolver>,
+    metrics: Arc<ProxyMetrics>,
+    credentials: SharedCredentials,
+    // Whether resolvers authenticate clients with their upstream, instead of the credentials
+    authentication_passthrough: bool,
+    authentication_method: AuthenticationMethod,
+    address_filter: AddressFilter,
+    gss_encryption: GssEncryption,
+    // Limits of the phases of a connection before it is served, so clients which stop
+    // responding don't hold on to their task, or the resolver they connect to
+    startup_timeout: Duration,
+    authentication_timeout: Duration,
+    hooks: Hooks,
 }
 
 impl Proxy {
     pub async fn listen(&mut self, listener: TcpListener) -> Result<(), ProboscisError> {
-        info!("Listening on: {}", &listener.local_addr()?);
+        let tls_config = self.config.tls_config.clone();
+        self.listen_all(vec![Listener {
+            listener,
+            tls_config,
+            require_tls: false,
+        }])
+        .await
+    }
 
-        let tls_acceptor: Option<tokio_native_tls::TlsAcceptor> = match &self.config.tls_config {
-            Some(tls_config) => {
-                let mut file = File::open(tls_config.pcks_path.clone())?;
-                let mut identity = vec![];
-                file.read_to_end(&mut identity)?;
+    /// Serves the clients of all listeners, each with its own tls settings,
+    /// e.g. a plaintext listener on localhost and a tls listener on a public address
+    pub async fn listen_all(&mut self, listeners: Vec<Listener>) -> Result<(), ProboscisError> {
+        let (sender, mut receiver) = mpsc::channel(listeners.len().max(1));
+        let mut tls_acceptors = vec![];
+        let mut require_tls = vec![];
 
-                let certificate = Identity::from_pkcs12(&identity, tls_config.password.as_str())?;
-                let acceptor = tokio_native_tls::TlsAcceptor::from(
-                    native_tls::TlsAcceptor::builder(certificate).build()?,
-                );
+        for (
+            index,
+            Listener {
+                listener,
+                tls_config,
+                require_tls: listener_requires_tls,
+            },
+        ) in listeners.into_iter().enumerate()
+        {
+            info!("Listening on: {}", &listener.local_addr()?);
 
-                Some(acceptor)
+            if listener_requires_tls && tls_config.is_none() {
+                warn!("tls is required, but not configured, every client will be rejected");
+            }
+            require_tls.push(listener_requires_tls);
+
+            tls_acceptors.push(match tls_config {
+                Some(tls_config) => Some(ReloadingTlsAcceptor::load(
+                    &tls_config.pcks_path,
+                    &tls_config.password,
+                )?),
+                _ => None,
+            });
+
+            // Every listener accepts on its own task
+            let sender = sender.clone();
+            tokio::spawn(async move {
+                loop {
+                    match listener.accept().await {
+                        Ok(accepted) => {
+                            if sender.send((index, accepted)).await.is_err() {
+                                break;
+                            }
+                        }
+                        // Failing to accept one connection doesn't stop the listener
+                        Err(err) => {
+                            error!(error = %err, "failed to accept a connection");
+                            sleep(ACCEPT_ERROR_BACKOFF).await;
+                        }
+                    }
+                }
+            });
+        }
+
+        drop(sender);
+
+        let settings = Arc::new(ClientSettings {
+            resolver: self.resolver.clone(),
+            database_resolvers: self.database_resolvers.clone(),
+            application_resolvers: self.application_resolvers.clone(),
+            metrics: self.metrics.clone(),
+            credentials: self.credentials.clone(),
+            authentication_passthrough: self.authentication_passthrough,
+            authentication_method: self.authentication_method,
+            gss_encryption: self.gss_encryption,
+            startup_timeout: self.startup_timeout,
+            authentication_timeout: self.authentication_timeout,
+            hooks: self.hooks.clone(),
+        });
+
+        while let Some((index, (stream, client_addr))) = receiver.recv().await {
+            // Dropping the stream closes it before the client could send anything
+            if !self.address_filter.is_allowed(client_addr.ip()) {
+                warn!(client.addr = %client_addr, "connection from a denied address");
+                self.metrics
+                    .denied_connections
+                    .fetch_add(1, Ordering::Relaxed);
+                continue;
             }
-            _ => None,
-        };
 
-        loop {
-            let (stream, client_addr) = listener.accept().await?;
             let client_id = Uuid::new_v4();
 
-            let span =
-                trace_span!("connection", client.addr = %client_addr, client.id = %client_id);
+            let connection = ConnectionInfo {
+                client_id,
+                address: client_addr,
+            };
+
+            // Every line logged for the client carries the session, like the upstream
+            // connections of the client carry it in their application name
+            let span = info_span!(
+                "connection",
+                session = %connection.correlation_id(),
+                client.addr = %client_addr,
+                client.id = %client_id
+            );
 
             info!(parent: &span, "connection established");
+            self.metrics.connections.fetch_add(1, Ordering::Relaxed);
+            sessions::register(&connection);
 
-            let mut frontend_connection = accept_frontend_connection(stream, &tls_acceptor)
-                .instrument(tracing::info_span!(
-                    parent: &span,
-                    "accept_frontend_connection"
-                ))
-                .await?;
+            // The identity may have been renewed since the last connection
+            let current_tls_acceptor = tls_acceptors[index]
+                .as_mut()
+                .map(|acceptor| acceptor.acceptor());
+
+            // Every client is served by its own task, a failing client doesn't affect the others
+            let settings = settings.clone();
+            let require_tls = require_tls[index];
+            tokio::spawn(async move {
+                let connected = Instant::now();
+                settings.hooks.on_client_connect(&connection).await;
+
+                let served = serve_client(
+                    &connection,
+                    stream,
+                    current_tls_acceptor,
+                    require_tls,
+                    &settings,
+                    &span,
+                )
+                .await;
+
+                if let Err(err) = served {
+                    warn!(parent: &span, error = %err, "connection failed");
+                    settings.hooks.on_error(&connection, &err).await;
+                }
+
+                sessions::unregister(&connection.client_id);
+                settings
+                    .hooks
+                    .on_disconnect(&connection, connected.elapsed())
+                    .await;
+            });
+        }
+
+        Ok(())
+    }
+
+    pub fn new(config: Config, resolver: Box<dyn Resolver>) -> Proxy {
+        Proxy {
+            credentials: Arc::new(RwLock::new(config.credentials.clone())),
+            config,
+            resolver: Some(Arc::new(Mutex::new(resolver))),
+            database_resolvers: HashMap::new(),
+            application_resolvers: HashMap::new(),
+            metrics: Arc::default(),
+            authentication_passthrough: false,
+            authentication_method: AuthenticationMethod::default(),
+            address_filter: AddressFilter::default(),
+            gss_encryption: GssEncryption::default(),
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            authentication_timeout: DEFAULT_AUTHENTICATION_TIMEOUT,
+            hooks: Hooks::default(),
+        }
+    }
+
+    /// Routes clients connecting to the database to the resolver, instead of the default one
+    pub fn add_database(mut self, database: &str, resolver: Box<dyn Resolver>) -> Proxy {
+        self.database_resolvers
+            .insert(database.to_string(), Arc::new(Mutex::new(resolver)));
+        self
+    }
+
+    /// Routes clients setting the application_name in their startup message to the resolver,
+    /// regardless of the database they connect to
+    pub fn add_application(mut self, application_name: &str, resolver: Box<dyn Resolver>) -> Proxy {
+        self.application_resolvers
+            .insert(application_name.to_string(), Arc::new(Mutex::new(resolver)));
+        self
+    }
+
+    /// Relays the authentication of clients to the upstream of the resolver they connect to,
+    /// e.g. a SCRAM exchange, so the proxy doesn't need to know the passwords of the clients.
+    /// The credentials of the config aren't used then.
+    pub fn with_authentication_passthrough(mut self) -> Proxy {
+        self.authentication_passthrough = true;
+        self
+    }
+
+    /// Authenticates clients with md5 by default
+    pub fn with_authentication_method(
+        mut self,
+        authentication_method: AuthenticationMethod,
+    ) -> Proxy {
+        self.authentication_method = authentication_method;
+        self
+    }
+
+    /// Closes connections of clients whose address the filter doesn't allow
+    pub fn with_address_filter(mut self, address_filter: AddressFilter) -> Proxy {
+        self.address_filter = address_filter;
+        self
+    }
+
+    /// Limits the time from accepting a connection until the client sent its startup message,
+    /// including the tls handshake
+    pub fn with_startup_timeout(mut self, startup_timeout: Duration) -> Proxy {
+        self.startup_timeout = startup_timeout;
+        self
+    }
+
+    /// Limits the time a client has to authenticate, like postgres' authentication_timeout
+    pub fn with_authentication_timeout(mut self, authentication_timeout: Duration) -> Proxy {
+        self.authentication_timeout = authentication_timeout;
+        self
+    }
+
+    /// Declines GSSAPI encryption requests by default
+    pub fn with_gss_encryption(mut self, gss_encryption: GssEncryption) -> Proxy {
+        self.gss_encryption = gss_encryption;
+        self
+    }
 
-            handle_authentication(&mut frontend_connection, &self.config.credentials)
-                .instrument(tracing::info_span!(parent: &span, "handle_authentication"))
+    /// The credentials clients are authenticated with, new connections use
+    /// the credentials written to the handle while the proxy is listening
+    pub fn credentials(&self) -> SharedCredentials {
+        self.credentials.clone()
+    }
+
+    /// Counts into the metrics instead of ones of its own
+    pub fn with_metrics(mut self, metrics: Arc<ProxyMetrics>) -> Proxy {
+        self.metrics = metrics;
+        self
+    }
+
+    /// Calls the hook at the stages of every client's lifecycle, after the hooks added before
+    pub fn with_hook(mut self, hook: Arc<dyn ProxyHook>) -> Proxy {
+        self.hooks.register(hook);
+        self
+    }
+
+    /// Counters of the proxy, which can be read while it is listening
+    pub fn metrics(&self) -> Arc<ProxyMetrics> {
+        self.metrics.clone()
+    }
+
+    /// Creates a proxy that routes every client to the resolver of the database it connects to.
+    /// Clients connecting to any other database are rejected.
+    pub fn with_databases(
+        config: Config,
+        database_resolvers: HashMap<String, Box<dyn Resolver>>,
+    ) -> Proxy {
+        Proxy {
+            credentials: Arc::new(RwLock::new(config.credentials.clone())),
+            config,
+            resolver: None,
+            database_resolvers: database_resolvers
+                .into_iter()
+                .map(|(database, resolver)| (database, Arc::new(Mutex::new(resolver))))
+                .collect(),
+            application_resolvers: HashMap::new(),
+            metrics: Arc::default(),
+            authentication_passthrough: false,
+            authentication_method: AuthenticationMethod::default(),
+            address_filter: AddressFilter::default(),
+            gss_encryption: GssEncryption::default(),
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            authentication_timeout: DEFAULT_AUTHENTICATION_TIMEOUT,
+            hooks: Hooks::default(),
+        }
+    }
+}
+
+/// The settings of a listening proxy, which the task of every client holds on to
+struct ClientSettings {
+    resolver: Option<SharedResolver>,
+    database_resolvers: HashMap<String, SharedResolver>,
+    application_resolvers: HashMap<String, SharedResolver>,
+    metrics: Arc<ProxyMetrics>,
+    credentials: SharedCredentials,
+    authentication_passthrough: bool,
+    authentication_method: AuthenticationMethod,
+    gss_encryption: GssEncryption,
+    startup_timeout: Duration,
+    authentication_timeout: Duration,
+    hooks: Hooks,
+}
+
+async fn serve_client(
+    connection: &ConnectionInfo,
+    stream: tokio::net::TcpStream,
+    tls_acceptor: Option<TlsAcceptor>,
+    require_tls: bool,
+    settings: &ClientSettings,
+    span: &tracing::Span,
+) -> Result<(), ProboscisError> {
+    let client_id = connection.client_id;
+    let metrics = &settings.metrics;
+
+    let accepted = timeout(
+        settings.startup_timeout,
+        accept_frontend_connection(stream, &tls_acceptor, settings.gss_encryption, require_tls),
+    )
+    .instrument(tracing::info_span!(
+        parent: span,
+        "accept_frontend_connection"
+    ))
+    .await;
+
+    let mut frontend_connection = match accepted {
+        Ok(Ok(frontend_connection)) => frontend_connection,
+        Ok(Err(err)) => {
+            warn!(parent: span, error = %err, "startup failed");
+            metrics.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+        Err(_) => {
+            warn!(parent: span, "startup timed out");
+            metrics
+                .timed_out_connections
+                .fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+    };
+
+    if let Err(err) = check_client_encoding(&mut frontend_connection).await {
+        warn!(parent: span, error = %err, "client requested an unsupported encoding");
+        metrics.rejected_connections.fetch_add(1, Ordering::Relaxed);
+        return Ok(());
+    }
+
+    if !settings.authentication_passthrough {
+        // The lock isn't held while waiting for the client
+        let credentials = settings
+            .credentials
+            .read()
+            .expect("Credentials lock poisoned")
+            .clone();
+
+        let authenticated = timeout(
+            settings.authentication_timeout,
+            handle_authentication(
+                &mut frontend_connection,
+                &credentials,
+                settings.authentication_method,
+            ),
+        )
+        .instrument(tracing::info_span!(parent: span, "handle_authentication"))
+        .await;
+
+        match authenticated {
+            Ok(result) => result?,
+            Err(_) => {
+                warn!(parent: span, "authentication timed out");
+                metrics
+                    .timed_out_connections
+                    .fetch_add(1, Ordering::Relaxed);
+                return Ok(());
+            }
+        }
+    }
+
+    // Like postgres, the database defaults to the name of the user
+    let database = frontend_connection
+        .parameters
+        .get("database")
+        .or_else(|| frontend_connection.parameters.get("user"))
+        .cloned()
+        .unwrap_or_default();
+
+    let application_name = frontend_connection
+        .parameters
+        .get("application_name")
+        .cloned()
+        .unwrap_or_default();
+
+    let resolver = match settings
+        .application_resolvers
+        .get(&application_name)
+        .or_else(|| settings.database_resolvers.get(&database))
+        .or_else(|| settings.resolver.as_ref())
+    {
+        Some(resolver) => resolver,
+        None => {
+            warn!(parent: span, database = %database, "client requested an unknown database");
+            metrics.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            return Ok(());
+        }
+    };
+
+    let served = AssertUnwindSafe(async {
+        if settings.authentication_passthrough {
+            let authenticated = timeout(settings.authentication_timeout, async {
+                resolver
+                    .lock()
+                    .await
+                    .authenticate(client_id, &mut frontend_connection)
+                    .await
+            })
+            .instrument(tracing::info_span!(parent: span, "authenticate"))
+            .await;
+
+            match authenticated {
+                Ok(result) => result?,
+                Err(_) => {
+                    warn!(parent: span, "authentication timed out");
+                    metrics
+                        .timed_out_connections
+                        .fetch_add(1, Ordering::Relaxed);
+                    return Ok(());
+                }
+            }
+        }
+
+        let session = SessionInfo {
+            connection: connection.clone(),
+            context: ClientContext::new(frontend_connection.parameters.clone()),
+        };
+        sessions::set_user(&client_id, session.context.user());
+
+        if let Err(rejection) = settings.hooks.on_authenticated(&session).await {
+            warn!(parent: span, reason = %rejection.message, "a hook rejected the client");
+            metrics.rejected_connections.fetch_add(1, Ordering::Relaxed);
+            frontend_connection
+                .write_message(
+                    BackendMessage::Error(rejection.to_error_response(sqlstate::FATAL)).into(),
+                )
                 .await?;
+            return Ok(());
+        }
 
-            handle_connection(client_id, &mut frontend_connection, &mut self.resolver)
-                .instrument(span)
+        handle_connection(
+            &session,
+            &mut frontend_connection,
+            resolver,
+            metrics,
+            &settings.hooks,
+        )
+        .instrument(span.clone())
+        .await
+    })
+    .catch_unwind()
+    .await;
+
+    match served {
+        Ok(Err(err)) => {
+            // The client learns why the connection ends, unless it is already broken
+            let _ = frontend_connection
+                .write_message(BackendMessage::Error(err.to_error_response()).into())
+                .await;
+
+            Err(err)
+        }
+        Ok(result) => result,
+        Err(panic) => {
+            error!(
+                parent: span,
+                panic = panic_message(&*panic),
+                message = ?frontend_connection.last_frontend_message(),
+                "serving the client panicked"
+            );
+
+            // The resolver may still hold state of the client, which panicking again can't break
+            let _ = AssertUnwindSafe(async { resolver.lock().await.terminate(client_id).await })
+                .catch_unwind()
+                .await;
+
+            frontend_connection
+                .write_message(fatal_error(
+                    sqlstate::INTERNAL_ERROR,
+                    "the proxy failed to handle the request".to_string(),
+                ))
                 .await?;
+
+            Ok(())
         }
     }
+}
 
-    pub fn new(config: Config, resolver: Box<dyn Resolver>) -> Proxy {
-        Proxy { config, resolver }
+fn panic_message(panic: &(dyn Any + Send)) -> &str {
+    match panic.downcast_ref::<&str>() {
+        Some(message) => message,
+        None => panic
+            .downcast_ref::<String>()
+            .map(String::as_str)
+            .unwrap_or("unknown panic"),
     }
 }
 
-pub async fn handle_authentication(
+/// Requests an md5 hash of the password, returns why the client couldn't authenticate
+async fn authenticate_md5(
     frontend: &mut Connection,
-    credentials: &HashMap<String, String>,
-) -> Result<(), ProboscisError> {
+    user: &str,
+    password: Option<&String>,
+) -> Result<Option<AuthError>, ProboscisError> {
     let salt = rand::thread_rng().gen::<[u8; 4]>().to_vec();
 
     frontend
@@ -105,26 +636,130 @@ pub async fn handle_authentication(
         _ => return Err(ProboscisError::ExpectedMessage("MD5HashedPassword")),
     };
 
+    Ok(match password {
+        None => Some(AuthError::UnknownUser(user.to_string())),
+        Some(password) => {
+            let actual_hash = if is_md5_password_verifier(password) {
+                encode_md5_verifier_hash(password, &salt[..])
+            } else {
+                encode_md5_password_hash(user, password, &salt[..])
+            };
+
+            (received_hash != actual_hash).then(|| AuthError::IncorrectPassword(user.to_string()))
+        }
+    })
+}
+
+/// Runs a SCRAM-SHA-256 exchange with the keys of the verifier, returns why the client
+/// couldn't authenticate
+async fn authenticate_scram(
+    frontend: &mut Connection,
+    user: &str,
+    verifier: ScramVerifier,
+) -> Result<Option<AuthError>, ProboscisError> {
+    let mut server = ScramServer::new(verifier);
+
+    frontend
+        .write_message(BackendMessage::AuthenticationSASL(vec![SCRAM_SHA_256.to_string()]).into())
+        .await?;
+
+    let client_first = match frontend.read_sasl_initial_response().await? {
+        FrontendMessage::SASLInitialResponse(SASLInitialResponse {
+            mechanism,
+            data: Some(data),
+        }) if mechanism == SCRAM_SHA_256 => data,
+        _ => return Err(ProboscisError::ExpectedMessage("SASLInitialResponse")),
+    };
+
+    let server_first = match server.handle_client_first(&client_first) {
+        Ok(server_first) => server_first,
+        Err(err) => return Ok(Some(err.into())),
+    };
+
+    frontend
+        .write_message(BackendMessage::AuthenticationSASLContinue(server_first).into())
+        .await?;
+
+    let client_final = match frontend.read_sasl_response().await? {
+        FrontendMessage::SASLResponse(data) => data,
+        _ => return Err(ProboscisError::ExpectedMessage("SASLResponse")),
+    };
+
+    match server.handle_client_final(&client_final) {
+        Ok(server_final) => {
+            frontend
+                .write_message(BackendMessage::AuthenticationSASLFinal(server_final).into())
+                .await?;
+
+            Ok(None)
+        }
+        Err(ScramError::InvalidProof) => Ok(Some(AuthError::IncorrectPassword(user.to_string()))),
+        Err(err) => Ok(Some(err.into())),
+    }
+}
+
+pub async fn handle_authentication(
+    frontend: &mut Connection,
+    credentials: &HashMap<String, String>,
+    method: AuthenticationMethod,
+) -> Result<(), ProboscisError> {
     let user = frontend
         .parameters
         .get("user")
         .expect("Missing user parameter")
         .clone();
+    let password = credentials.get(&user);
+
+    // Unknown users go through the same exchange as known ones, with keys no password derives
+    let scram_verifier = match password {
+        Some(password) if is_md5_password_verifier(password) => None,
+        Some(password) => ScramVerifier::parse(password).or_else(|| {
+            (method == AuthenticationMethod::ScramSha256)
+                .then(|| ScramVerifier::from_password(password))
+        }),
+        None => (method == AuthenticationMethod::ScramSha256)
+            .then(|| ScramVerifier::from_password(&Uuid::new_v4().to_string())),
+    };
 
-    let password = credentials
-        .get(&user.clone())
-        .ok_or_else(|| ProboscisError::MissingPasswordInConfig(user.clone()))?;
+    let error = match scram_verifier {
+        Some(verifier) => authenticate_scram(frontend, &user, verifier)
+            .await?
+            .map(|error| match password {
+                None => AuthError::UnknownUser(user.clone()),
+                Some(_) => error,
+            }),
+        None => authenticate_md5(frontend, &user, password).await?,
+    };
 
-    let actual_hash = encode_md5_password_hash(&user, password, &salt[..]);
+    // Like postgres, clients can't tell unknown users from incorrect passwords
+    if let Some(error) = error {
+        frontend
+            .write_message(fatal_error(
+                sqlstate::INVALID_PASSWORD,
+                format!("password authentication failed for user \"{}\"", user),
+            ))
+            .await?;
 
-    if received_hash != actual_hash {
-        return Err(ProboscisError::IncorrectPassword);
+        return Err(error.into());
     }
 
     frontend
         .write_message(BackendMessage::AuthenticationOk.into())
         .await?;
 
+    // Clients like JDBC refuse to continue without knowing the encoding
+    for key in ["client_encoding", "server_encoding"] {
+        frontend
+            .write_message(
+                BackendMessage::ParameterStatus(ParameterStatus {
+                    key: key.to_string(),
+                    value: SUPPORTED_CLIENT_ENCODING.to_string(),
+                })
+                .into(),
+            )
+            .await?;
+    }
+
     frontend
         .write_message(
             BackendMessage::ReadyForQuery(ReadyForQueryTransactionStatus::NotInTransaction).into(),
@@ -134,20 +769,74 @@ pub async fn handle_authentication(
     Ok(())
 }
 
+/// Rejects clients requesting an encoding other than UTF8 with an ErrorResponse,
+/// as strings are passed through without converting them
+pub async fn check_client_encoding(frontend: &mut Connection) -> Result<(), ProboscisError> {
+    let encoding = match frontend.parameters.get("client_encoding") {
+        Some(encoding) if !is_supported_client_encoding(encoding) => encoding.clone(),
+        _ => return Ok(()),
+    };
+
+    frontend
+        .write_message(fatal_error(
+            sqlstate::FEATURE_NOT_SUPPORTED,
+            format!(
+                "conversion between {} and {} is not supported",
+                encoding, SUPPORTED_CLIENT_ENCODING
+            ),
+        ))
+        .await?;
+
+    Err(ProboscisError::UnsupportedClientEncoding(encoding))
+}
+
+/// An ErrorResponse ending the connection, the startup can't be continued after it
+fn fatal_error(code: &str, message: String) -> Message {
+    BackendMessage::Error(sqlstate::error_response(sqlstate::FATAL, code, message)).into()
+}
+
+/// Negotiates the encryption of a client's connection and reads its startup message.
+/// Without a tls acceptor, requests for tls are declined and the client continues in plaintext,
+/// unless tls is required.
 pub async fn accept_frontend_connection(
     mut frontend_stream: tokio::net::TcpStream,
-    tls_acceptor: &Option<tokio_native_tls::TlsAcceptor>,
+    tls_acceptor: &Option<TlsAcceptor>,
+    gss_encryption: GssEncryption,
+    require_tls: bool,
 ) -> Result<Connection, ProboscisError> {
     let mut startup_message = StartupMessage::read(&mut frontend_stream).await?;
 
+    // Clients with kerberos credentials ask for GSSAPI encryption before tls
+    if let StartupMessage::GssEncRequest = startup_message {
+        match gss_encryption {
+            GssEncryption::Decline => {
+                info!("declined GSSAPI encryption");
+                frontend_stream.write_all(&[b'N']).await?;
+                startup_message = StartupMessage::read(&mut frontend_stream).await?;
+            }
+            GssEncryption::Reject => {
+                warn!("rejected a client requesting GSSAPI encryption");
+                fatal_error(
+                    sqlstate::FEATURE_NOT_SUPPORTED,
+                    "GSSAPI encryption is not supported".to_string(),
+                )
+                .write(&mut frontend_stream)
+                .await?;
+                return Err(ProboscisError::FrontendRequestedGssEncryption);
+            }
+        }
+    }
+
     let mut frontend: MaybeTlsStream;
     match startup_message {
         StartupMessage::SslRequest => {
             match tls_acceptor {
                 None => {
                     // TLS not supported
-                    frontend_stream.write(&[b'N']).await?;
-                    return Err(ProboscisError::FrontendRequestedTLS);
+                    frontend_stream.write_all(&[b'N']).await?;
+
+                    frontend = MaybeTlsStream::Left(frontend_stream);
+                    startup_message = StartupMessage::read(&mut frontend).await?;
                 }
                 Some(tls_acceptor) => {
                     let tls_acceptor = tls_acceptor;
@@ -166,155 +855,1416 @@ pub async fn accept_frontend_connection(
 
     let frontend_params = match startup_message {
         StartupMessage::Startup { params } => params,
-        _ => panic!(""),
+        _ => return Err(ProboscisError::ExpectedMessage("StartupMessage")),
     };
 
+    if require_tls {
+        if let MaybeTlsStream::Left(stream) = &mut frontend {
+            fatal_error(
+                sqlstate::INVALID_AUTHORIZATION_SPECIFICATION,
+                "the connection requires TLS".to_string(),
+            )
+            .write(stream)
+            .await?;
+            return Err(ProboscisError::PlaintextConnectionDenied);
+        }
+    }
+
     let frontend = Connection::new(frontend, frontend_params);
 
     Ok(frontend)
 }
 
-pub async fn handle_connection(
-    client_id: Uuid,
+/// Writes the result of the query to the client while it is resolved, returns its number of rows.
+/// A failing batch ends the result, the rows before it may already have been written.
+async fn write_query_result(
     frontend: &mut Connection,
     resolver: &mut Box<dyn Resolver>,
-) -> Result<(), ProboscisError> {
-    resolver.initialize(client_id).await?;
-
-    loop {
-        let request = frontend.read_frontend_message().await?;
-
-        match request {
-            FrontendMessage::Terminate => {
-                async {
-                    resolver
-                        .terminate(client_id)
-                        .instrument(tracing::trace_span!("resolver"))
-                        .await?;
+    client_id: Uuid,
+    query: &str,
+) -> Result<u64, ProboscisError> {
+    let mut batches = resolver
+        .query_stream(client_id, query.to_string())
+        .instrument(tracing::trace_span!("resolver"))
+        .await?;
 
-                    Ok::<(), ProboscisError>(())
-                }
-                .instrument(tracing::trace_span!("terminate"))
-                .await?;
+    let mut rows = 0;
+    let mut described = false;
+    let mut serialization = Duration::default();
+    while let Some(batch) = batches.next().await {
+        let batch = batch?;
+        rows += batch.num_rows() as u64;
 
-                break;
-            }
-            FrontendMessage::SimpleQuery(query) => {
-                async {
-                    let result = resolver
-                        .query(client_id, query)
-                        .instrument(tracing::trace_span!("resolver"))
-                        .await?;
+        let serialization_started = Instant::now();
+        if !described {
+            frontend.write_row_description(&batch.schema());
+            described = true;
+        }
+        frontend.write_data_rows(&batch).await?;
+        serialization += serialization_started.elapsed();
+    }
 
-                    frontend.write_data(result).await?;
+    frontend.flush_data().await?;
+    observe_stage(Stage::Serialization, serialization);
 
-                    // TODO: Fix the command complete tag
-                    frontend
-                        .write_message(
-                            BackendMessage::CommandComplete(CommandCompleteTag("C".to_string()))
-                                .into(),
-                        )
-                        .await?;
+    Ok(rows)
+}
 
-                    frontend
-                        .write_message(
-                            BackendMessage::ReadyForQuery(
-                                ReadyForQueryTransactionStatus::NotInTransaction,
-                            )
-                            .into(),
-                        )
-                        .await?;
+/// Relays the data of a `COPY ... FROM STDIN` from the client to the resolver, returns the
+/// tag of its command complete
+async fn read_copy_in(
+    frontend: &mut Connection,
+    resolver: &Mutex<Box<dyn Resolver>>,
+    client_id: Uuid,
+    query: &str,
+) -> Result<CommandCompleteTag, ProboscisError> {
+    // The resolver is locked for each message, the client may take its time sending the data
+    let response = resolver
+        .lock()
+        .await
+        .copy_in(client_id, query.to_string())
+        .instrument(tracing::trace_span!("resolver"))
+        .await?;
+    frontend
+        .write_message(BackendMessage::CopyInResponse(response).into())
+        .await?;
 
-                    Ok::<(), ProboscisError>(())
+    loop {
+        match frontend.read_frontend_message().await? {
+            FrontendMessage::CopyData(data) => {
+                let mut resolver = resolver.lock().await;
+                if let Err(err) = resolver.copy_data(client_id, data).await {
+                    // The target has to leave the copy as well, the client's remaining data
+                    // is ignored once it received the error
+                    if err.is_recoverable() {
+                        resolver.copy_fail(client_id, err.to_string()).await.ok();
+                    }
+                    return Err(err.into());
                 }
-                .instrument(tracing::trace_span!("query"))
-                .await?;
             }
-            FrontendMessage::Parse(parse) => {
-                async {
-                    resolver
-                        .parse(client_id, parse)
-                        .instrument(tracing::trace_span!("resolver"))
-                        .await?;
-
-                    Ok::<(), ProboscisError>(())
-                }
-                .instrument(tracing::trace_span!("parse"))
-                .await?;
+            FrontendMessage::CopyDone => {
+                return Ok(resolver.lock().await.copy_done(client_id).await?)
             }
-            FrontendMessage::Describe(describe) => {
-                async {
-                    resolver
-                        .describe(client_id, describe)
-                        .instrument(tracing::trace_span!("resolver"))
-                        .await?;
+            FrontendMessage::CopyFail(message) => {
+                resolver
+                    .lock()
+                    .await
+                    .copy_fail(client_id, message.clone())
+                    .await?;
 
-                    Ok::<(), ProboscisError>(())
-                }
-                .instrument(tracing::trace_span!("describe"))
-                .await?;
+                return Err(ResolveError::Target(sqlstate::error_response(
+                    sqlstate::ERROR,
+                    sqlstate::QUERY_CANCELED,
+                    format!("COPY from stdin failed: {}", message),
+                ))
+                .into());
             }
-            FrontendMessage::Bind(bind) => {
-                async {
-                    resolver
-                        .bind(client_id, bind)
-                        .instrument(tracing::trace_span!("resolver"))
-                        .await?;
+            // Like postgres, flushes and syncs are accepted but meaningless during a copy
+            FrontendMessage::Flush | FrontendMessage::Sync => {}
+            _ => return Err(ProboscisError::ExpectedMessage("CopyData")),
+        }
+    }
+}
 
-                    Ok::<(), ProboscisError>(())
-                }
-                .instrument(tracing::trace_span!("bind"))
-                .await?;
-            }
-            FrontendMessage::Execute(execute) => {
-                async {
-                    resolver
-                        .execute(client_id, execute)
-                        .instrument(tracing::trace_span!("resolver"))
+/// Writes the data of a `COPY ... TO STDOUT` to the client while it is resolved, returns the
+/// tag of its command complete
+async fn write_copy_out(
+    frontend: &mut Connection,
+    resolver: &mut Box<dyn Resolver>,
+    client_id: Uuid,
+    query: &str,
+) -> Result<CommandCompleteTag, ProboscisError> {
+    let (response, mut messages) = resolver
+        .copy_out(client_id, query.to_string())
+        .instrument(tracing::trace_span!("resolver"))
+        .await?;
+    frontend
+        .write_message(BackendMessage::CopyOutResponse(response).into())
+        .await?;
+
+    while let Some(message) = messages.next().await {
+        match message? {
+            CopyOutMessage::Data(data) => frontend.write_copy_data(data).await?,
+            // Writes the buffered data along with it
+            CopyOutMessage::Complete(tag) => {
+                frontend
+                    .write_message(BackendMessage::CopyDone.into())
+                    .await?;
+                return Ok(tag);
+            }
+        }
+    }
+
+    frontend.flush_data().await?;
+    Err(ResolveError::from("the copy ended without completing").into())
+}
+
+/// The number of rows of a `COPY n` tag
+fn copied_rows(tag: &CommandCompleteTag) -> u64 {
+    tag.0
+        .strip_prefix("COPY ")
+        .and_then(|rows| rows.parse().ok())
+        .unwrap_or(0)
+}
+
+/// The messages the resolver raised for the client besides its responses, i.e. the parameters
+/// the target reported as changed, notices and notifications
+fn take_notices(resolver: &mut Box<dyn Resolver>, client_id: Uuid) -> Vec<BackendMessage> {
+    let parameter_changes = resolver
+        .take_parameter_changes(client_id)
+        .into_iter()
+        .map(BackendMessage::ParameterStatus);
+
+    let notices = resolver
+        .take_notices(client_id)
+        .into_iter()
+        .map(BackendMessage::NoticeResponse);
+
+    let notifications = resolver
+        .take_notifications(client_id)
+        .into_iter()
+        .map(BackendMessage::NotificationResponse);
+
+    parameter_changes
+        .chain(notices)
+        .chain(notifications)
+        .collect()
+}
+
+async fn write_notices(
+    frontend: &mut Connection,
+    notices: Vec<BackendMessage>,
+) -> Result<(), ProboscisError> {
+    for notice in notices {
+        frontend.write_message(notice.into()).await?;
+    }
+
+    Ok(())
+}
+
+/// Writes the responses, with the notices of the resolver before the end of the batch
+async fn write_responses(
+    frontend: &mut Connection,
+    responses: Vec<SyncResponse>,
+    mut notices: Vec<BackendMessage>,
+    transaction: &TransactionState,
+) -> Result<(), ProboscisError> {
+    let serialization_started = Instant::now();
+    for response in responses {
+        // Resolvers don't know the transaction state of the client
+        if let SyncResponse::ReadyForQuery = response {
+            write_notices(frontend, std::mem::take(&mut notices)).await?;
+            frontend
+                .write_message(BackendMessage::ReadyForQuery(transaction.status()).into())
+                .await?;
+            continue;
+        }
+
+        for message in response.as_messages() {
+            frontend.write_message(message.into()).await?;
+        }
+    }
+    write_notices(frontend, notices).await?;
+    observe_stage(Stage::Serialization, serialization_started.elapsed());
+
+    Ok(())
+}
+
+/// The responses of a sync or flush. A failure the client can recover from is answered with its
+/// ErrorResponse like an error of the target, followed by ReadyForQuery for a sync.
+async fn recover_responses(
+    result: Result<Vec<SyncResponse>, ResolveError>,
+    sync: bool,
+    session: &SessionInfo,
+    hooks: &Hooks,
+) -> Result<Vec<SyncResponse>, ProboscisError> {
+    let err = match result {
+        Ok(responses) => {
+            if let Some(error) = sync_error(&responses) {
+                let err = ProboscisError::Resolve(ResolveError::Target(error.clone()));
+                hooks.on_error(&session.connection, &err).await;
+            }
+            return Ok(responses);
+        }
+        Err(err) if err.is_recoverable() => err,
+        Err(err) => return Err(err.into()),
+    };
+
+    let mut responses = vec![SyncResponse::Error(err.to_error_response())];
+    if sync {
+        responses.push(SyncResponse::ReadyForQuery);
+    }
+    hooks
+        .on_error(&session.connection, &ProboscisError::Resolve(err))
+        .await;
+
+    Ok(responses)
+}
+
+/// Keeps the failure of an operation the client can recover from, it is answered at the next
+/// sync and the operations until then are skipped
+async fn defer_failure(
+    result: Result<(), ResolveError>,
+    failed: &mut Option<Error>,
+    session: &SessionInfo,
+    hooks: &Hooks,
+) -> Result<(), ProboscisError> {
+    match result {
+        Ok(()) => Ok(()),
+        Err(err) if err.is_recoverable() => {
+            *failed = Some(err.to_error_response());
+            hooks
+                .on_error(&session.connection, &ProboscisError::Resolve(err))
+                .await;
+            Ok(())
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+/// Updates the transaction with the executed queries the responses complete. The execution an
+/// error answers and the ones after it didn't take effect, the error fails the transaction.
+fn apply_executed(
+    transaction: &mut TransactionState,
+    executed: &mut Vec<String>,
+    responses: &[SyncResponse],
+) {
+    let completed = responses
+        .iter()
+        .take_while(|response| !matches!(response, SyncResponse::Error(_)))
+        .filter(|response| {
+            matches!(
+                response,
+                SyncResponse::CommandComplete(_)
+                    | SyncResponse::EmptyQueryResponse
+                    | SyncResponse::PortalSuspended
+            )
+        })
+        .count();
+
+    for query in executed.drain(..).take(completed) {
+        transaction.apply(&query);
+    }
+
+    if sync_error(responses).is_some() {
+        transaction.fail();
+    }
+}
+
+async fn record_results(
+    responses: &[SyncResponse],
+    duration: Duration,
+    metrics: &ProxyMetrics,
+    session: &SessionInfo,
+    hooks: &Hooks,
+) {
+    for response in responses {
+        if let SyncResponse::Records { data, query } = response {
+            let rows = data.iter().map(|batch| batch.num_rows() as u64).sum();
+            metrics.record_query(query, duration, rows);
+
+            let outcome = QueryOutcome {
+                query: query.clone(),
+                rows,
+                duration,
+            };
+            hooks.on_result(session, &outcome).await;
+        }
+    }
+}
+
+/// Serves the requests of the client. The resolver is locked for each request, so it serves
+/// other clients while this one is idle.
+pub async fn handle_connection(
+    session: &SessionInfo,
+    frontend: &mut Connection,
+    resolver: &Mutex<Box<dyn Resolver>>,
+    metrics: &ProxyMetrics,
+    hooks: &Hooks,
+) -> Result<(), ProboscisError> {
+    let client_id = session.connection.client_id;
+    resolver
+        .lock()
+        .await
+        .initialize(client_id, &session.context)
+        .await?;
+
+    // Whether operations were sent since the last sync or flush, their responses have to
+    // be written before the response of a close
+    let mut outstanding = false;
+
+    // Transactions and savepoints are tracked by the statements the client runs,
+    // prepared ones by the query of each executed portal
+    let mut transaction = TransactionState::default();
+    let mut statements: HashMap<String, String> = HashMap::new();
+    let mut portals: HashMap<String, String> = HashMap::new();
+    let mut executed: Vec<String> = vec![];
+
+    // An operation of the extended protocol failed or a hook rejected the parse of a statement,
+    // the messages up to the next sync are ignored like postgres does after an error.
+    // The failure is answered at the sync, unless a flush answered it already.
+    let mut failed: Option<Error> = None;
+    let mut skipping = false;
+
+    loop {
+        // Messages the target sends while the client is idle are forwarded as they are polled,
+        // e.g. notifications of the channels it listens on. Waiting for them would hold the lock.
+        tokio::select! {
+            waited = frontend.wait_for_message() => waited?,
+            _ = sleep(IDLE_POLL_INTERVAL) => {
+                let notices = {
+                    let mut resolver = resolver.lock().await;
+                    match resolver.wait_for_idle_message(client_id).now_or_never() {
+                        Some(waited) => {
+                            waited?;
+                            resolver.read_idle_messages(client_id).await?;
+                            take_notices(&mut resolver, client_id)
+                        }
+                        None => vec![],
+                    }
+                };
+                write_notices(frontend, notices).await?;
+                continue;
+            }
+        }
+
+        let request = frontend.read_frontend_message().await?;
+
+        if (failed.is_some() || skipping)
+            && !matches!(request, FrontendMessage::Sync | FrontendMessage::Terminate)
+        {
+            continue;
+        }
+
+        match request {
+            FrontendMessage::Terminate => {
+                async {
+                    resolver
+                        .lock()
+                        .await
+                        .terminate(client_id)
+                        .instrument(tracing::trace_span!("resolver"))
                         .await?;
 
                     Ok::<(), ProboscisError>(())
                 }
-                .instrument(tracing::trace_span!("execute"))
+                .instrument(tracing::trace_span!("terminate"))
                 .await?;
+
+                break;
             }
-            FrontendMessage::Sync => {
+            FrontendMessage::SimpleQuery(query) => {
+                let span = tracing::trace_span!("query", fingerprint = %fingerprint(&query));
+
                 async {
-                    let responses = resolver
-                        .sync(client_id)
-                        .instrument(tracing::trace_span!("resolver"))
-                        .await?;
+                    if let Err(rejection) = hooks.on_query(session, &query).await {
+                        transaction.fail();
+                        frontend
+                            .write_message(
+                                BackendMessage::Error(rejection.to_error_response(sqlstate::ERROR))
+                                    .into(),
+                            )
+                            .await?;
+                        frontend
+                            .write_message(
+                                BackendMessage::ReadyForQuery(transaction.status()).into(),
+                            )
+                            .await?;
 
-                    for response in responses {
-                        for message in response.as_messages() {
-                            frontend.write_message(message.into()).await?;
-                        }
+                        return Ok(());
                     }
 
+                    let started = Instant::now();
+                    let result = match parse_copy_statement(&query) {
+                        Some(CopyDirection::In) => {
+                            read_copy_in(frontend, resolver, client_id, &query)
+                                .await
+                                .map(|tag| (copied_rows(&tag), tag))
+                        }
+                        // The results are streamed while the resolver is locked
+                        Some(CopyDirection::Out) => {
+                            write_copy_out(frontend, &mut resolver.lock().await, client_id, &query)
+                                .await
+                                .map(|tag| (copied_rows(&tag), tag))
+                        }
+                        // TODO: Fix the command complete tag
+                        None => write_query_result(
+                            frontend,
+                            &mut resolver.lock().await,
+                            client_id,
+                            &query,
+                        )
+                        .await
+                        .map(|rows| (rows, CommandCompleteTag("C".to_string()))),
+                    };
+
+                    let (rows, tag) = match result {
+                        Ok(result) => result,
+                        // The query was rejected, the client can continue with the next one
+                        Err(ProboscisError::Resolve(err)) if err.is_recoverable() => {
+                            let response = err.to_error_response();
+                            hooks
+                                .on_error(&session.connection, &ProboscisError::Resolve(err))
+                                .await;
+
+                            transaction.fail();
+                            frontend
+                                .write_message(BackendMessage::Error(response).into())
+                                .await?;
+                            frontend
+                                .write_message(
+                                    BackendMessage::ReadyForQuery(transaction.status()).into(),
+                                )
+                                .await?;
+
+                            return Ok(());
+                        }
+                        Err(err) => return Err(err),
+                    };
+
+                    // The duration includes writing the result, which is streamed to the client
+                    let duration = started.elapsed();
+                    metrics.record_query(&query, duration, rows);
+                    transaction.apply(&query);
+
+                    let outcome = QueryOutcome {
+                        query: query.clone(),
+                        rows,
+                        duration,
+                    };
+                    hooks.on_result(session, &outcome).await;
+
+                    let notices = take_notices(&mut resolver.lock().await, client_id);
+                    write_notices(frontend, notices).await?;
+
+                    frontend
+                        .write_message(BackendMessage::CommandComplete(tag).into())
+                        .await?;
+
+                    frontend
+                        .write_message(BackendMessage::ReadyForQuery(transaction.status()).into())
+                        .await?;
+
                     Ok::<(), ProboscisError>(())
                 }
+                .instrument(span)
+                .await?;
+            }
+            FrontendMessage::Parse(parse) => {
+                if let Err(rejection) = hooks.on_query(session, &parse.query).await {
+                    failed = Some(rejection.to_error_response(sqlstate::ERROR));
+                    continue;
+                }
+
+                statements.insert(parse.statement_name.clone(), parse.query.clone());
+
+                let result = resolver
+                    .lock()
+                    .await
+                    .parse(client_id, parse)
+                    .instrument(tracing::trace_span!("resolver"))
+                    .instrument(tracing::trace_span!("parse"))
+                    .await;
+                defer_failure(result, &mut failed, session, hooks).await?;
+
+                outstanding = true;
+            }
+            FrontendMessage::Describe(describe) => {
+                let result = resolver
+                    .lock()
+                    .await
+                    .describe(client_id, describe)
+                    .instrument(tracing::trace_span!("resolver"))
+                    .instrument(tracing::trace_span!("describe"))
+                    .await;
+                defer_failure(result, &mut failed, session, hooks).await?;
+
+                outstanding = true;
+            }
+            FrontendMessage::Bind(bind) => {
+                let query = statements.get(&bind.statement).cloned();
+                portals.insert(bind.portal.clone(), query.unwrap_or_default());
+
+                let result = resolver
+                    .lock()
+                    .await
+                    .bind(client_id, bind)
+                    .instrument(tracing::trace_span!("resolver"))
+                    .instrument(tracing::trace_span!("bind"))
+                    .await;
+                defer_failure(result, &mut failed, session, hooks).await?;
+
+                outstanding = true;
+            }
+            FrontendMessage::Execute(execute) => {
+                executed.extend(portals.get(&execute.portal).cloned());
+
+                let result = resolver
+                    .lock()
+                    .await
+                    .execute(client_id, execute)
+                    .instrument(tracing::trace_span!("resolver"))
+                    .instrument(tracing::trace_span!("execute"))
+                    .await;
+                defer_failure(result, &mut failed, session, hooks).await?;
+
+                outstanding = true;
+            }
+            FrontendMessage::Sync => {
+                async {
+                    let started = Instant::now();
+                    let (result, notices) = {
+                        let mut resolver = resolver.lock().await;
+                        let result = resolver
+                            .sync(client_id)
+                            .instrument(tracing::trace_span!("resolver"))
+                            .await;
+                        (result, take_notices(&mut resolver, client_id))
+                    };
+                    let responses = recover_responses(result, true, session, hooks).await?;
+
+                    // The portals of a sync are resolved together, each is attributed the whole duration
+                    record_results(&responses, started.elapsed(), metrics, session, hooks).await;
+
+                    apply_executed(&mut transaction, &mut executed, &responses);
+
+                    // The operations after an error were skipped, including the failed one
+                    let failure = failed.take();
+                    skipping = false;
+                    if sync_error(&responses).is_some() {
+                        return write_responses(frontend, responses, notices, &transaction).await;
+                    }
+
+                    let failure = match failure {
+                        Some(failure) => failure,
+                        None => {
+                            return write_responses(frontend, responses, notices, &transaction)
+                                .await
+                        }
+                    };
+
+                    // The operations before the failed one are answered, then its error
+                    transaction.fail();
+                    let (ready, responses): (Vec<_>, Vec<_>) = responses
+                        .into_iter()
+                        .partition(|response| matches!(response, SyncResponse::ReadyForQuery));
+                    write_responses(frontend, responses, notices, &transaction).await?;
+
+                    frontend
+                        .write_message(BackendMessage::Error(failure).into())
+                        .await?;
+
+                    write_responses(frontend, ready, vec![], &transaction).await
+                }
                 .instrument(tracing::trace_span!("sync"))
                 .await?;
+
+                outstanding = false;
             }
-            FrontendMessage::Close(close) => {
+            FrontendMessage::Flush => {
                 async {
-                    resolver
+                    let started = Instant::now();
+                    let (result, notices) = {
+                        let mut resolver = resolver.lock().await;
+                        let result = resolver
+                            .flush(client_id)
+                            .instrument(tracing::trace_span!("resolver"))
+                            .await;
+                        (result, take_notices(&mut resolver, client_id))
+                    };
+                    let responses = recover_responses(result, false, session, hooks).await?;
+
+                    record_results(&responses, started.elapsed(), metrics, session, hooks).await;
+
+                    apply_executed(&mut transaction, &mut executed, &responses);
+
+                    if sync_error(&responses).is_some() {
+                        skipping = true;
+                    }
+
+                    write_responses(frontend, responses, notices, &transaction).await
+                }
+                .instrument(tracing::trace_span!("flush"))
+                .await?;
+
+                outstanding = false;
+            }
+            FrontendMessage::Close(close) => {
+                match close.kind {
+                    CloseKind::Statement => statements.remove(&close.name),
+                    CloseKind::Portal => portals.remove(&close.name),
+                };
+
+                let closed = async {
+                    if outstanding {
+                        let (result, notices) = {
+                            let mut resolver = resolver.lock().await;
+                            let result = resolver
+                                .flush(client_id)
+                                .instrument(tracing::trace_span!("resolver"))
+                                .await;
+                            (result, take_notices(&mut resolver, client_id))
+                        };
+                        let responses = recover_responses(result, false, session, hooks).await?;
+
+                        apply_executed(&mut transaction, &mut executed, &responses);
+
+                        // The target skips the close as well
+                        let errored = sync_error(&responses).is_some();
+
+                        write_responses(frontend, responses, notices, &transaction).await?;
+
+                        if errored {
+                            skipping = true;
+                            return Ok(false);
+                        }
+                    }
+
+                    let result = resolver
+                        .lock()
+                        .await
                         .close(client_id, close)
                         .instrument(tracing::trace_span!("resolver"))
-                        .await?;
+                        .await;
 
-                    frontend
-                        .write_message(BackendMessage::CloseComplete.into())
-                        .await?;
+                    let closed = result.is_ok();
+                    defer_failure(result, &mut failed, session, hooks).await?;
 
-                    Ok::<(), ProboscisError>(())
+                    Ok::<bool, ProboscisError>(closed)
                 }
                 .instrument(tracing::trace_span!("close"))
                 .await?;
+
+                if closed {
+                    frontend
+                        .write_message(BackendMessage::CloseComplete.into())
+                        .await?;
+                }
+
+                outstanding = false;
             }
+            // The rest of a copy in the client sends after it failed, postgres ignores it as well
+            FrontendMessage::CopyData(_)
+            | FrontendMessage::CopyDone
+            | FrontendMessage::CopyFail(_) => {}
             _ => unimplemented!(),
         }
     }
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        hooks::HookRejection,
+        resolver::{ClientId, ResolveError},
+    };
+    use arrow::record_batch::RecordBatch;
+    use async_trait::async_trait;
+    use proboscis_postgres_protocol::message::{
+        Bind, BindParameter, Close, Describe, Execute, NotificationResponse, Parse,
+    };
+    use tokio::{io::AsyncReadExt, net::TcpStream, task::JoinHandle};
+
+    /// Accepts every client, but panics on queries
+    struct PanickingResolver;
+
+    #[async_trait]
+    impl Resolver for PanickingResolver {
+        async fn authenticate(
+            &mut self,
+            _client_id: ClientId,
+            frontend: &mut Connection,
+        ) -> Result<(), ResolveError> {
+            frontend
+                .write_message(BackendMessage::AuthenticationOk.into())
+                .await?;
+            frontend
+                .write_message(
+                    BackendMessage::ReadyForQuery(ReadyForQueryTransactionStatus::NotInTransaction)
+                        .into(),
+                )
+                .await?;
+            Ok(())
+        }
+
+        async fn initialize(
+            &mut self,
+            _client_id: ClientId,
+            _context: &ClientContext,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn query(
+            &mut self,
+            _client_id: ClientId,
+            _query: String,
+        ) -> Result<Vec<RecordBatch>, ResolveError> {
+            panic!("the resolver panicked")
+        }
+
+        async fn parse(&mut self, _client_id: ClientId, _parse: Parse) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn describe(
+            &mut self,
+            _client_id: ClientId,
+            _describe: Describe,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn bind(&mut self, _client_id: ClientId, _bind: Bind) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn execute(
+            &mut self,
+            _client_id: ClientId,
+            _execute: Execute,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn sync(&mut self, _client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+            Ok(vec![])
+        }
+
+        async fn close(&mut self, _client_id: ClientId, _close: Close) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn terminate(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+            Ok(())
+        }
+    }
+
+    /// Answers queries without rows and notifies idle clients of what is sent to it
+    struct NotifyingResolver {
+        notifications: mpsc::UnboundedReceiver<NotificationResponse>,
+        received: Vec<NotificationResponse>,
+    }
+
+    #[async_trait]
+    impl Resolver for NotifyingResolver {
+        async fn authenticate(
+            &mut self,
+            client_id: ClientId,
+            frontend: &mut Connection,
+        ) -> Result<(), ResolveError> {
+            PanickingResolver.authenticate(client_id, frontend).await
+        }
+
+        async fn initialize(
+            &mut self,
+            _client_id: ClientId,
+            _context: &ClientContext,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn query(
+            &mut self,
+            _client_id: ClientId,
+            _query: String,
+        ) -> Result<Vec<RecordBatch>, ResolveError> {
+            Ok(vec![])
+        }
+
+        async fn parse(&mut self, _client_id: ClientId, _parse: Parse) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn describe(
+            &mut self,
+            _client_id: ClientId,
+            _describe: Describe,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn bind(&mut self, _client_id: ClientId, _bind: Bind) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn execute(
+            &mut self,
+            _client_id: ClientId,
+            _execute: Execute,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn sync(&mut self, _client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+            Ok(vec![])
+        }
+
+        async fn close(&mut self, _client_id: ClientId, _close: Close) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        fn take_notifications(&mut self, _client_id: ClientId) -> Vec<NotificationResponse> {
+            std::mem::take(&mut self.received)
+        }
+
+        // Receiving from the channel can be cancelled without losing a notification
+        async fn wait_for_idle_message(
+            &mut self,
+            _client_id: ClientId,
+        ) -> Result<(), ResolveError> {
+            match self.notifications.recv().await {
+                Some(notification) => {
+                    self.received.push(notification);
+                    Ok(())
+                }
+                None => futures::future::pending().await,
+            }
+        }
+
+        async fn terminate(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+            Ok(())
+        }
+    }
+
+    /// Answers prepared inserts, the first violates a unique constraint and the second fails
+    /// in the resolver
+    struct ConflictingResolver {
+        syncs: usize,
+    }
+
+    #[async_trait]
+    impl Resolver for ConflictingResolver {
+        async fn authenticate(
+            &mut self,
+            client_id: ClientId,
+            frontend: &mut Connection,
+        ) -> Result<(), ResolveError> {
+            PanickingResolver.authenticate(client_id, frontend).await
+        }
+
+        async fn initialize(
+            &mut self,
+            _client_id: ClientId,
+            _context: &ClientContext,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn query(
+            &mut self,
+            _client_id: ClientId,
+            _query: String,
+        ) -> Result<Vec<RecordBatch>, ResolveError> {
+            Ok(vec![])
+        }
+
+        async fn parse(&mut self, _client_id: ClientId, _parse: Parse) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn describe(
+            &mut self,
+            _client_id: ClientId,
+            _describe: Describe,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn bind(&mut self, _client_id: ClientId, _bind: Bind) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn execute(
+            &mut self,
+            _client_id: ClientId,
+            _execute: Execute,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn sync(&mut self, _client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+            self.syncs += 1;
+            match self.syncs {
+                1 => Ok(vec![
+                    SyncResponse::ParseComplete,
+                    SyncResponse::BindComplete,
+                    SyncResponse::Error(
+                        sqlstate::error_response(
+                            sqlstate::ERROR,
+                            "23505",
+                            "duplicate key value violates unique constraint".to_string(),
+                        )
+                        .with_detail("Key (id)=(1) already exists.".to_string()),
+                    ),
+                    SyncResponse::ReadyForQuery,
+                ]),
+                2 => Err(ResolveError::Other(
+                    anyhow::anyhow!("the source is unreachable")
+                        .context("the query can't be planned"),
+                )),
+                _ => Ok(vec![
+                    SyncResponse::ParseComplete,
+                    SyncResponse::BindComplete,
+                    SyncResponse::CommandComplete(CommandCompleteTag("INSERT 0 1".to_string())),
+                    SyncResponse::ReadyForQuery,
+                ]),
+            }
+        }
+
+        async fn close(&mut self, _client_id: ClientId, _close: Close) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn terminate(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+            Ok(())
+        }
+    }
+
+    /// Rejects queries mentioning secrets and reports the clients which disconnected
+    struct SecretHook {
+        disconnects: mpsc::UnboundedSender<Uuid>,
+    }
+
+    #[async_trait]
+    impl ProxyHook for SecretHook {
+        async fn on_query(&self, _session: &SessionInfo, query: &str) -> Result<(), HookRejection> {
+            match query.contains("secret") {
+                true => Err(HookRejection::denied("secrets can't be queried")),
+                false => Ok(()),
+            }
+        }
+
+        async fn on_disconnect(&self, connection: &ConnectionInfo, _duration: Duration) {
+            let _ = self.disconnects.send(connection.client_id);
+        }
+    }
+
+    /// Sends the first message of a client and accepts the connection without a tls acceptor
+    async fn start_handshake(
+        message: StartupMessage,
+        gss_encryption: GssEncryption,
+        require_tls: bool,
+    ) -> (JoinHandle<Result<Connection, ProboscisError>>, TcpStream) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let mut client = TcpStream::connect(listener.local_addr().unwrap())
+            .await
+            .unwrap();
+        let (stream, _) = listener.accept().await.unwrap();
+
+        message.write(&mut client).await.unwrap();
+
+        let accepted = tokio::spawn(async move {
+            accept_frontend_connection(stream, &None, gss_encryption, require_tls).await
+        });
+
+        (accepted, client)
+    }
+
+    fn startup_message() -> StartupMessage {
+        let mut params = HashMap::new();
+        params.insert("user".to_string(), "admin".to_string());
+        StartupMessage::Startup { params }
+    }
+
+    #[tokio::test]
+    async fn test_declined_gss_encryption() {
+        let (accepted, mut client) =
+            start_handshake(StartupMessage::GssEncRequest, GssEncryption::Decline, false).await;
+        assert_eq!(b'N', client.read_u8().await.unwrap());
+
+        // The client falls back to a plaintext connection
+        startup_message().write(&mut client).await.unwrap();
+
+        let frontend = accepted.await.unwrap().unwrap();
+        assert_eq!(
+            Some("admin"),
+            frontend.parameters.get("user").map(String::as_str)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rejected_gss_encryption() {
+        let (accepted, mut client) =
+            start_handshake(StartupMessage::GssEncRequest, GssEncryption::Reject, false).await;
+
+        assert!(matches!(
+            accepted.await.unwrap(),
+            Err(ProboscisError::FrontendRequestedGssEncryption)
+        ));
+        assert!(matches!(
+            BackendMessage::read(&mut client).await,
+            Ok(BackendMessage::Error(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_declined_tls() {
+        let (accepted, mut client) =
+            start_handshake(StartupMessage::SslRequest, GssEncryption::Decline, false).await;
+        assert_eq!(b'N', client.read_u8().await.unwrap());
+
+        startup_message().write(&mut client).await.unwrap();
+
+        assert!(accepted.await.unwrap().is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_required_tls() {
+        let (accepted, mut client) =
+            start_handshake(startup_message(), GssEncryption::Decline, true).await;
+
+        assert!(matches!(
+            accepted.await.unwrap(),
+            Err(ProboscisError::PlaintextConnectionDenied)
+        ));
+        assert!(matches!(
+            BackendMessage::read(&mut client).await,
+            Ok(BackendMessage::Error(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_panicking_resolver() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut proxy = Proxy::new(
+            Config {
+                tls_config: None,
+                credentials: HashMap::new(),
+            },
+            Box::new(PanickingResolver),
+        )
+        .with_authentication_passthrough();
+        tokio::spawn(async move { proxy.listen(listener).await });
+
+        // The proxy keeps serving clients after one of them made it panic
+        for _ in 0..2 {
+            let mut client = TcpStream::connect(address).await.unwrap();
+            startup_message().write(&mut client).await.unwrap();
+
+            assert!(matches!(
+                BackendMessage::read(&mut client).await,
+                Ok(BackendMessage::AuthenticationOk)
+            ));
+            assert!(matches!(
+                BackendMessage::read(&mut client).await,
+                Ok(BackendMessage::ReadyForQuery(_))
+            ));
+
+            Message::from(FrontendMessage::SimpleQuery("SELECT 1".to_string()))
+                .write(&mut client)
+                .await
+                .unwrap();
+
+            assert!(matches!(
+                BackendMessage::read(&mut client).await,
+                Ok(BackendMessage::Error(_))
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_hooks() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (disconnects, mut disconnected) = mpsc::unbounded_channel();
+
+        let mut proxy = Proxy::new(
+            Config {
+                tls_config: None,
+                credentials: HashMap::new(),
+            },
+            Box::new(PanickingResolver),
+        )
+        .with_authentication_passthrough()
+        .with_hook(Arc::new(SecretHook { disconnects }));
+        tokio::spawn(async move { proxy.listen(listener).await });
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        startup_message().write(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+
+        // The rejected query doesn't reach the resolver, which would panic
+        Message::from(FrontendMessage::SimpleQuery(
+            "SELECT secret FROM vault".to_string(),
+        ))
+        .write(&mut client)
+        .await
+        .unwrap();
+
+        match BackendMessage::read(&mut client).await {
+            Ok(BackendMessage::Error(error)) => assert_eq!(
+                Some(sqlstate::INSUFFICIENT_PRIVILEGE),
+                sqlstate::code_of(&error)
+            ),
+            message => panic!("expected an error, got {:?}", message),
+        }
+        assert!(matches!(
+            BackendMessage::read(&mut client).await,
+            Ok(BackendMessage::ReadyForQuery(_))
+        ));
+
+        Message::from(FrontendMessage::Terminate)
+            .write(&mut client)
+            .await
+            .unwrap();
+        assert!(disconnected.recv().await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_idle_notifications() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (notify, notifications) = mpsc::unbounded_channel();
+
+        let mut proxy = Proxy::new(
+            Config {
+                tls_config: None,
+                credentials: HashMap::new(),
+            },
+            Box::new(NotifyingResolver {
+                notifications,
+                received: vec![],
+            }),
+        )
+        .with_authentication_passthrough();
+        tokio::spawn(async move { proxy.listen(listener).await });
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        startup_message().write(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+
+        let notification = NotificationResponse {
+            process_id: 42,
+            channel: "contacts".to_string(),
+            payload: "inserted".to_string(),
+        };
+        notify.send(notification.clone()).unwrap();
+
+        // The client is notified without sending a request
+        match BackendMessage::read(&mut client).await {
+            Ok(BackendMessage::NotificationResponse(received)) => {
+                assert_eq!(notification, received)
+            }
+            message => panic!("expected a notification, got {:?}", message),
+        }
+
+        // Requests are still answered after waiting for notifications
+        Message::from(FrontendMessage::SimpleQuery("LISTEN contacts".to_string()))
+            .write(&mut client)
+            .await
+            .unwrap();
+        loop {
+            match BackendMessage::read(&mut client).await {
+                Ok(BackendMessage::ReadyForQuery(_)) => break,
+                Ok(_) => {}
+                message => panic!("expected the end of the query, got {:?}", message),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_idle_client_releases_resolver() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        let (_notify, notifications) = mpsc::unbounded_channel();
+
+        let mut proxy = Proxy::new(
+            Config {
+                tls_config: None,
+                credentials: HashMap::new(),
+            },
+            Box::new(NotifyingResolver {
+                notifications,
+                received: vec![],
+            }),
+        )
+        .with_authentication_passthrough();
+        tokio::spawn(async move { proxy.listen(listener).await });
+
+        let mut idle = TcpStream::connect(address).await.unwrap();
+        startup_message().write(&mut idle).await.unwrap();
+        BackendMessage::read(&mut idle).await.unwrap();
+        BackendMessage::read(&mut idle).await.unwrap();
+
+        // The second client shares the resolver, it is served while the first one is idle
+        let served = timeout(Duration::from_secs(5), async {
+            let mut client = TcpStream::connect(address).await.unwrap();
+            startup_message().write(&mut client).await.unwrap();
+            BackendMessage::read(&mut client).await.unwrap();
+            BackendMessage::read(&mut client).await.unwrap();
+
+            simple_query(&mut client, "SELECT 1").await
+        })
+        .await
+        .expect("the second client wasn't served");
+
+        assert!(matches!(
+            served.last(),
+            Some(BackendMessage::ReadyForQuery(_))
+        ));
+    }
+
+    /// Runs a prepared insert and reads the messages up to ReadyForQuery
+    async fn execute_prepared(client: &mut TcpStream, query: &str) -> Vec<BackendMessage> {
+        let messages = vec![
+            FrontendMessage::Parse(Parse {
+                statement_name: "".to_string(),
+                query: query.to_string(),
+                param_types: vec![],
+            }),
+            FrontendMessage::Bind(Bind {
+                statement: "".to_string(),
+                portal: "".to_string(),
+                params: vec![BindParameter::Text("1".to_string())],
+                results: vec![],
+            }),
+            FrontendMessage::Execute(Execute {
+                portal: "".to_string(),
+                row_limit: 0,
+            }),
+            FrontendMessage::Sync,
+        ];
+        for message in messages {
+            Message::from(message).write(client).await.unwrap();
+        }
+
+        read_until_ready(client).await
+    }
+
+    async fn simple_query(client: &mut TcpStream, query: &str) -> Vec<BackendMessage> {
+        Message::from(FrontendMessage::SimpleQuery(query.to_string()))
+            .write(client)
+            .await
+            .unwrap();
+
+        read_until_ready(client).await
+    }
+
+    async fn read_until_ready(client: &mut TcpStream) -> Vec<BackendMessage> {
+        let mut received = vec![];
+        loop {
+            let message = BackendMessage::read(client).await.unwrap();
+            let ready = matches!(message, BackendMessage::ReadyForQuery(_));
+            received.push(message);
+            if ready {
+                return received;
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extended_query_errors() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut proxy = Proxy::new(
+            Config {
+                tls_config: None,
+                credentials: HashMap::new(),
+            },
+            Box::new(ConflictingResolver { syncs: 0 }),
+        )
+        .with_authentication_passthrough();
+        tokio::spawn(async move { proxy.listen(listener).await });
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        startup_message().write(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+
+        // The responses before the error are written, the connection stays usable
+        let messages = execute_prepared(&mut client, "INSERT INTO contacts (id) VALUES ($1)").await;
+        assert_eq!(4, messages.len());
+        assert!(matches!(messages[0], BackendMessage::ParseComplete));
+        assert!(matches!(messages[1], BackendMessage::BindComplete));
+        match &messages[2] {
+            BackendMessage::Error(error) => {
+                assert_eq!(Some("23505"), error.code());
+                assert_eq!(Some(sqlstate::ERROR), error.severity());
+                assert_eq!(Some("Key (id)=(1) already exists."), error.detail());
+            }
+            message => panic!("expected an error, got {:?}", message),
+        }
+        assert!(matches!(
+            messages[3],
+            BackendMessage::ReadyForQuery(ReadyForQueryTransactionStatus::NotInTransaction)
+        ));
+
+        // A failure of the resolver is answered like an error of the target, with its causes
+        let messages = execute_prepared(&mut client, "INSERT INTO contacts (id) VALUES ($1)").await;
+        assert_eq!(2, messages.len());
+        match &messages[0] {
+            BackendMessage::Error(error) => {
+                assert_eq!(Some(sqlstate::INTERNAL_ERROR), error.code());
+                assert_eq!(Some(sqlstate::ERROR), error.severity());
+                assert_eq!(Some("the query can't be planned"), error.message());
+                assert_eq!(Some("the source is unreachable"), error.detail());
+            }
+            message => panic!("expected an error, got {:?}", message),
+        }
+
+        let messages = execute_prepared(&mut client, "INSERT INTO contacts (id) VALUES ($1)").await;
+        assert!(matches!(
+            &messages[2],
+            BackendMessage::CommandComplete(CommandCompleteTag(tag)) if tag == "INSERT 0 1"
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_transaction_status_after_failed_execute() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+
+        let mut proxy = Proxy::new(
+            Config {
+                tls_config: None,
+                credentials: HashMap::new(),
+            },
+            Box::new(ConflictingResolver { syncs: 0 }),
+        )
+        .with_authentication_passthrough();
+        tokio::spawn(async move { proxy.listen(listener).await });
+
+        let mut client = TcpStream::connect(address).await.unwrap();
+        startup_message().write(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+        BackendMessage::read(&mut client).await.unwrap();
+
+        let status = |messages: Vec<BackendMessage>| match messages.last() {
+            Some(BackendMessage::ReadyForQuery(status)) => status.clone(),
+            message => panic!("expected ready for query, got {:?}", message),
+        };
+
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InTransaction,
+            status(simple_query(&mut client, "BEGIN").await)
+        );
+
+        // The failed commit didn't end the transaction
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InFailedTransaction,
+            status(execute_prepared(&mut client, "COMMIT").await)
+        );
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InFailedTransaction,
+            status(execute_prepared(&mut client, "ROLLBACK").await)
+        );
+
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InTransaction,
+            status(execute_prepared(&mut client, "ROLLBACK AND CHAIN").await)
+        );
+        assert_eq!(
+            ReadyForQueryTransactionStatus::NotInTransaction,
+            status(simple_query(&mut client, "COMMIT").await)
+        );
+    }
+}
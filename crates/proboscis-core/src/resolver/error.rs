@@ -1,3 +1,4 @@
+use crate::sqlstate;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -11,12 +12,128 @@ pub enum ResolveError {
     #[error(transparent)]
     Arrow(#[from] arrow::error::ArrowError),
 
+    #[error("the target server returned an error: {0:?}")]
+    Target(proboscis_postgres_protocol::message::Error),
+
+    #[error("the result couldn't be transformed: {0}")]
+    Transform(anyhow::Error),
+
+    #[error("unsupported: {0}")]
+    Unsupported(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl ResolveError {
+    /// The code of the ErrorResponse a client receives for the error
+    pub fn sqlstate(&self) -> &str {
+        match self {
+            ResolveError::Io(_) => sqlstate::CONNECTION_FAILURE,
+            ResolveError::Parse(_) => sqlstate::PROTOCOL_VIOLATION,
+            ResolveError::Target(error) => {
+                sqlstate::code_of(error).unwrap_or(sqlstate::INTERNAL_ERROR)
+            }
+            ResolveError::Unsupported(_) => sqlstate::FEATURE_NOT_SUPPORTED,
+            ResolveError::Arrow(_) | ResolveError::Transform(_) | ResolveError::Other(_) => {
+                sqlstate::INTERNAL_ERROR
+            }
+        }
+    }
+
+    /// Whether the client can continue after the error, e.g. the connection to the
+    /// target broke for io errors and the protocol is out of sync for parse errors
+    pub fn is_recoverable(&self) -> bool {
+        !matches!(self, ResolveError::Io(_) | ResolveError::Parse(_))
+    }
+
+    /// The causes of the error, e.g. why a value couldn't be transformed
+    pub fn detail(&self) -> Option<String> {
+        let causes: Vec<String> = match self {
+            ResolveError::Transform(err) | ResolveError::Other(err) => {
+                err.chain().skip(1).map(ToString::to_string).collect()
+            }
+            _ => vec![],
+        };
+
+        match causes.is_empty() {
+            true => None,
+            false => Some(causes.join(": ")),
+        }
+    }
+
+    /// The ErrorResponse a client receives for the error, the target's one is passed on
+    pub fn to_error_response(&self) -> proboscis_postgres_protocol::message::Error {
+        if let ResolveError::Target(error) = self {
+            return error.clone();
+        }
+
+        let response = sqlstate::error_response(
+            match self.is_recoverable() {
+                true => sqlstate::ERROR,
+                false => sqlstate::FATAL,
+            },
+            self.sqlstate(),
+            self.to_string(),
+        );
+
+        match self.detail() {
+            Some(detail) => response.with_detail(detail),
+            None => response,
+        }
+    }
+}
+
 impl From<&str> for ResolveError {
     fn from(error: &str) -> Self {
         ResolveError::Other(anyhow::anyhow!(String::from(error)))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sqlstate() {
+        let denied = ResolveError::Target(sqlstate::error_response(
+            sqlstate::ERROR,
+            sqlstate::INSUFFICIENT_PRIVILEGE,
+            "permission denied".to_string(),
+        ));
+        assert_eq!(sqlstate::INSUFFICIENT_PRIVILEGE, denied.sqlstate());
+        assert!(denied.is_recoverable());
+
+        let unsupported = ResolveError::Unsupported("couldn't trace the query".to_string());
+        let response = unsupported.to_error_response();
+        assert_eq!(
+            Some(sqlstate::FEATURE_NOT_SUPPORTED),
+            sqlstate::code_of(&response)
+        );
+        assert!(response
+            .messages
+            .contains(&(b'S', sqlstate::ERROR.to_string())));
+
+        let broken = ResolveError::Io(std::io::ErrorKind::BrokenPipe.into());
+        assert!(!broken.is_recoverable());
+        assert_eq!(sqlstate::CONNECTION_FAILURE, broken.sqlstate());
+        assert_eq!(Some(sqlstate::FATAL), broken.to_error_response().severity());
+    }
+
+    #[test]
+    fn test_error_response_detail() {
+        let failed = ResolveError::Transform(
+            anyhow::anyhow!("the value isn't a date")
+                .context("couldn't generalize column 'birthday'"),
+        );
+        let response = failed.to_error_response();
+        assert_eq!(
+            Some("the result couldn't be transformed: couldn't generalize column 'birthday'"),
+            response.message()
+        );
+        assert_eq!(Some("the value isn't a date"), response.detail());
+
+        let unsupported = ResolveError::Unsupported("couldn't trace the query".to_string());
+        assert_eq!(None, unsupported.to_error_response().detail());
+    }
+}
@@ -1,20 +1,125 @@
 use super::{error::ResolveError, response::SyncResponse};
+use crate::utils::connection::Connection;
 use arrow::record_batch::RecordBatch;
 use async_trait::async_trait;
+use futures::{
+    future,
+    stream::{self, BoxStream, StreamExt},
+};
+use std::collections::HashMap;
 use uuid::Uuid;
 
-pub use proboscis_postgres_protocol::message::{Bind, Close, Describe, Execute, Parse};
+pub use proboscis_postgres_protocol::message::{
+    Bind, Close, CommandCompleteTag, CopyResponse, Describe, Execute, Parse,
+};
+use proboscis_postgres_protocol::message::{Error, NotificationResponse, ParameterStatus};
 
 pub type ClientId = Uuid;
 
+/// The batches of a result, a failing one ends it
+pub type RecordBatchStream<'a> = BoxStream<'a, Result<RecordBatch, ResolveError>>;
+
+/// A message of a `COPY ... TO STDOUT`
+#[derive(Debug, PartialEq, Clone)]
+pub enum CopyOutMessage {
+    /// A chunk of the data, usually a row
+    Data(Vec<u8>),
+    /// The copy is done, with the tag of its command complete, e.g. `COPY 3`
+    Complete(CommandCompleteTag),
+}
+
+/// The messages of a `COPY ... TO STDOUT`, ending with `CopyOutMessage::Complete`.
+/// A failing message ends it
+pub type CopyOutStream<'a> = BoxStream<'a, Result<CopyOutMessage, ResolveError>>;
+
+/// A copy out of messages known upfront, for resolvers which don't stream them
+pub fn copy_out_messages<'a>(messages: Vec<CopyOutMessage>) -> CopyOutStream<'a> {
+    stream::iter(messages.into_iter().map(Ok)).boxed()
+}
+
+/// Reads the messages of a copy out to its end, e.g. to process all of its data at once
+pub async fn collect_copy_out(
+    mut messages: CopyOutStream<'_>,
+) -> Result<Vec<CopyOutMessage>, ResolveError> {
+    let mut collected = vec![];
+    while let Some(message) = messages.next().await {
+        collected.push(message?);
+    }
+    Ok(collected)
+}
+
+/// Waits until any of the resolvers has a message for the idle client, for resolvers
+/// combining others
+pub async fn wait_for_any_idle_message<'a, I>(
+    resolvers: I,
+    client_id: ClientId,
+) -> Result<(), ResolveError>
+where
+    I: IntoIterator<Item = &'a mut Box<dyn Resolver>>,
+{
+    let waits: Vec<_> = resolvers
+        .into_iter()
+        .map(|resolver| resolver.wait_for_idle_message(client_id))
+        .collect();
+
+    if waits.is_empty() {
+        return future::pending().await;
+    }
+
+    future::select_all(waits).await.0
+}
+
+/// What is known about a client once it is authenticated
+#[derive(Debug, Clone, Default)]
+pub struct ClientContext {
+    /// The parameters of the client's startup message, e.g. `user` and `database`
+    pub parameters: HashMap<String, String>,
+}
+
+impl ClientContext {
+    pub fn new(parameters: HashMap<String, String>) -> ClientContext {
+        ClientContext { parameters }
+    }
+
+    /// The name the client authenticated with
+    pub fn user(&self) -> Option<&str> {
+        self.parameters.get("user").map(String::as_str)
+    }
+}
+
 #[async_trait]
 pub trait Resolver: Sync + Send {
-    async fn initialize(&mut self, client_id: ClientId) -> Result<(), ResolveError>;
+    /// Authenticates the client with the resolver's upstream by relaying the authentication
+    /// exchange, for proxies that don't authenticate clients themselves.
+    /// Called before `initialize`, the client has received `ReadyForQuery` once it returns.
+    async fn authenticate(
+        &mut self,
+        _client_id: ClientId,
+        _frontend: &mut Connection,
+    ) -> Result<(), ResolveError> {
+        Err("the resolver doesn't support authentication passthrough".into())
+    }
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError>;
     async fn query(
         &mut self,
         client_id: ClientId,
         query: String,
-    ) -> Result<RecordBatch, ResolveError>;
+    ) -> Result<Vec<RecordBatch>, ResolveError>;
+    /// The result of the query as it is produced, so it can be processed without holding all
+    /// of it in memory. The client's next request must wait until the stream is consumed or
+    /// dropped. Resolvers that don't stream return the batches of `query`.
+    async fn query_stream<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<RecordBatchStream<'a>, ResolveError> {
+        let batches = self.query(client_id, query).await?;
+        Ok(stream::iter(batches.into_iter().map(Ok)).boxed())
+    }
     async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError>;
     async fn describe(
         &mut self,
@@ -24,6 +129,75 @@ pub trait Resolver: Sync + Send {
     async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError>;
     async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError>;
     async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError>;
+    /// The responses to the operations since the last sync or flush, without ending the batch.
+    /// Resolvers holding operations back until the sync answer them there instead.
+    async fn flush(&mut self, _client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        Ok(vec![])
+    }
     async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError>;
+    /// Starts a `COPY ... FROM STDIN`. Once it returns, the client's data is passed to
+    /// `copy_data` until it ends the copy with `copy_done` or aborts it with `copy_fail`.
+    async fn copy_in(
+        &mut self,
+        _client_id: ClientId,
+        _query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        Err(ResolveError::Unsupported("COPY FROM STDIN".to_string()))
+    }
+    async fn copy_data(
+        &mut self,
+        _client_id: ClientId,
+        _data: Vec<u8>,
+    ) -> Result<(), ResolveError> {
+        Err(ResolveError::Unsupported("COPY FROM STDIN".to_string()))
+    }
+    /// Ends the copy in, returning the tag of its command complete
+    async fn copy_done(
+        &mut self,
+        _client_id: ClientId,
+    ) -> Result<CommandCompleteTag, ResolveError> {
+        Err(ResolveError::Unsupported("COPY FROM STDIN".to_string()))
+    }
+    /// Aborts the copy in, the target's error about it is returned as `ResolveError::Target`
+    async fn copy_fail(
+        &mut self,
+        _client_id: ClientId,
+        _message: String,
+    ) -> Result<(), ResolveError> {
+        Err(ResolveError::Unsupported("COPY FROM STDIN".to_string()))
+    }
+    /// Runs a `COPY ... TO STDOUT`, the data is streamed like the result of `query_stream`
+    async fn copy_out<'a>(
+        &'a mut self,
+        _client_id: ClientId,
+        _query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        Err(ResolveError::Unsupported("COPY TO STDOUT".to_string()))
+    }
+    /// Notices for the client raised while answering its last request, e.g. warnings about
+    /// truncated results. The proxy sends them before the request is completed.
+    fn take_notices(&mut self, _client_id: ClientId) -> Vec<Error> {
+        vec![]
+    }
+    /// Parameters the target reported as changed while answering the client's last request,
+    /// e.g. `TimeZone` after a `SET`. The proxy sends them along with the notices.
+    fn take_parameter_changes(&mut self, _client_id: ClientId) -> Vec<ParameterStatus> {
+        vec![]
+    }
+    /// Notifications of the channels the client listens on, which arrived while answering its
+    /// last request or while it was idle. The proxy sends them along with the notices.
+    fn take_notifications(&mut self, _client_id: ClientId) -> Vec<NotificationResponse> {
+        vec![]
+    }
+    /// Waits until the target sends the idle client a message, e.g. a notification, which is
+    /// read with `read_idle_messages` then. The proxy polls it while the client is idle,
+    /// so it must not lose any data when cancelled. Never returns by default.
+    async fn wait_for_idle_message(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+        future::pending().await
+    }
+    /// Reads the messages `wait_for_idle_message` waited for, without waiting for others
+    async fn read_idle_messages(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+        Ok(())
+    }
     async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError>;
 }
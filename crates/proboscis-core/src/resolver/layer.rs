@@ -0,0 +1,331 @@
+use super::{
+    error::ResolveError,
+    interface::{
+        Bind, ClientContext, ClientId, Close, CommandCompleteTag, CopyOutStream, CopyResponse,
+        Describe, Execute, Parse, RecordBatchStream, Resolver,
+    },
+    response::SyncResponse,
+};
+use crate::utils::{connection::Connection, fingerprint::fingerprint};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use proboscis_postgres_protocol::message::{Error, NotificationResponse, ParameterStatus};
+use std::{
+    any::{Any, TypeId},
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+/// Values of any type, at most one of each
+#[derive(Default)]
+pub struct Extensions {
+    values: HashMap<TypeId, Box<dyn Any + Send + Sync>>,
+}
+
+impl Extensions {
+    pub fn new() -> Extensions {
+        Extensions::default()
+    }
+
+    /// Returns the previous value of the type
+    pub fn insert<T: Any + Send + Sync>(&mut self, value: T) -> Option<T> {
+        self.values
+            .insert(TypeId::of::<T>(), Box::new(value))
+            .and_then(|previous| previous.downcast().ok().map(|previous| *previous))
+    }
+
+    pub fn get<T: Any + Send + Sync>(&self) -> Option<&T> {
+        self.values
+            .get(&TypeId::of::<T>())
+            .and_then(|value| value.downcast_ref())
+    }
+
+    pub fn remove<T: Any + Send + Sync>(&mut self) -> Option<T> {
+        self.values
+            .remove(&TypeId::of::<T>())
+            .and_then(|value| value.downcast().ok().map(|value| *value))
+    }
+}
+
+impl fmt::Debug for Extensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Extensions")
+            .field("len", &self.values.len())
+            .finish()
+    }
+}
+
+/// The extensions of every client, shared by the layers of a stack,
+/// so a layer can read what another one learned about the client
+#[derive(Clone, Default, Debug)]
+pub struct SharedExtensions {
+    clients: Arc<Mutex<HashMap<ClientId, Extensions>>>,
+}
+
+impl SharedExtensions {
+    pub fn insert<T: Any + Send + Sync>(&self, client_id: ClientId, value: T) {
+        self.clients
+            .lock()
+            .unwrap()
+            .entry(client_id)
+            .or_default()
+            .insert(value);
+    }
+
+    pub fn get<T: Any + Send + Sync + Clone>(&self, client_id: ClientId) -> Option<T> {
+        self.clients
+            .lock()
+            .unwrap()
+            .get(&client_id)
+            .and_then(|extensions| extensions.get::<T>())
+            .cloned()
+    }
+
+    fn remove_client(&self, client_id: ClientId) {
+        self.clients.lock().unwrap().remove(&client_id);
+    }
+}
+
+/// Who the client is, recorded once it is initialized
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClientIdentity {
+    pub user: Option<String>,
+    pub database: Option<String>,
+    pub application_name: Option<String>,
+}
+
+/// The fingerprint of the last query the client sent, recorded before any layer handles it
+#[derive(Debug, Clone, PartialEq)]
+pub struct QueryFingerprint(pub String);
+
+/// Wraps a resolver in another one, e.g. a transforming or caching resolver
+pub trait ResolverLayer {
+    fn layer(
+        self: Box<Self>,
+        inner: Box<dyn Resolver>,
+        extensions: &SharedExtensions,
+    ) -> Result<Box<dyn Resolver>, ResolveError>;
+}
+
+impl<F> ResolverLayer for F
+where
+    F: FnOnce(Box<dyn Resolver>, &SharedExtensions) -> Result<Box<dyn Resolver>, ResolveError>,
+{
+    fn layer(
+        self: Box<Self>,
+        inner: Box<dyn Resolver>,
+        extensions: &SharedExtensions,
+    ) -> Result<Box<dyn Resolver>, ResolveError> {
+        (*self)(inner, extensions)
+    }
+}
+
+/// Layers around a resolver, the first one added is the outermost, which sees requests first
+#[derive(Default)]
+pub struct ResolverStack<'a> {
+    layers: Vec<Box<dyn ResolverLayer + 'a>>,
+}
+
+impl<'a> ResolverStack<'a> {
+    pub fn new() -> ResolverStack<'a> {
+        ResolverStack { layers: vec![] }
+    }
+
+    pub fn layer<L: ResolverLayer + 'a>(mut self, layer: L) -> ResolverStack<'a> {
+        self.layers.push(Box::new(layer));
+        self
+    }
+
+    /// Wraps the resolver in the layers. The extensions of a client are filled in before
+    /// any layer handles its requests and are dropped once it terminates.
+    pub fn build(self, resolver: Box<dyn Resolver>) -> Result<Box<dyn Resolver>, ResolveError> {
+        let extensions = SharedExtensions::default();
+
+        let mut resolver = resolver;
+        for layer in self.layers.into_iter().rev() {
+            resolver = layer.layer(resolver, &extensions)?;
+        }
+
+        Ok(Box::new(ContextResolver {
+            resolver,
+            extensions,
+        }))
+    }
+}
+
+/// Records the context of the requests in the extensions, before passing them on
+struct ContextResolver {
+    resolver: Box<dyn Resolver>,
+    extensions: SharedExtensions,
+}
+
+#[async_trait]
+impl Resolver for ContextResolver {
+    async fn authenticate(
+        &mut self,
+        client_id: ClientId,
+        frontend: &mut Connection,
+    ) -> Result<(), ResolveError> {
+        self.resolver.authenticate(client_id, frontend).await
+    }
+
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        let parameter = |name: &str| context.parameters.get(name).cloned();
+        self.extensions.insert(
+            client_id,
+            ClientIdentity {
+                user: parameter("user"),
+                database: parameter("database"),
+                application_name: parameter("application_name"),
+            },
+        );
+
+        self.resolver.initialize(client_id, context).await
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        self.extensions
+            .insert(client_id, QueryFingerprint(fingerprint(&query)));
+        self.resolver.query(client_id, query).await
+    }
+
+    async fn query_stream<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<RecordBatchStream<'a>, ResolveError> {
+        self.extensions
+            .insert(client_id, QueryFingerprint(fingerprint(&query)));
+        self.resolver.query_stream(client_id, query).await
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        self.extensions
+            .insert(client_id, QueryFingerprint(fingerprint(&parse.query)));
+        self.resolver.parse(client_id, parse).await
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        self.resolver.describe(client_id, describe).await
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        self.resolver.bind(client_id, bind).await
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        self.resolver.execute(client_id, execute).await
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        self.resolver.sync(client_id).await
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        self.resolver.flush(client_id).await
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        self.resolver.close(client_id, close).await
+    }
+
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        self.extensions
+            .insert(client_id, QueryFingerprint(fingerprint(&query)));
+        self.resolver.copy_in(client_id, query).await
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        self.resolver.copy_data(client_id, data).await
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        self.resolver.copy_done(client_id).await
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        self.resolver.copy_fail(client_id, message).await
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        self.extensions
+            .insert(client_id, QueryFingerprint(fingerprint(&query)));
+        self.resolver.copy_out(client_id, query).await
+    }
+
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        self.resolver.take_notices(client_id)
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.resolver.take_parameter_changes(client_id)
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.resolver.take_notifications(client_id)
+    }
+
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.wait_for_idle_message(client_id).await
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.read_idle_messages(client_id).await
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        let result = self.resolver.terminate(client_id).await;
+        self.extensions.remove_client(client_id);
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_shared_extensions() {
+        let extensions = SharedExtensions::default();
+        let client_id = Uuid::new_v4();
+
+        extensions.insert(client_id, QueryFingerprint("a".to_string()));
+        extensions.insert(client_id, QueryFingerprint("b".to_string()));
+
+        assert_eq!(
+            Some(QueryFingerprint("b".to_string())),
+            extensions.get(client_id)
+        );
+        assert_eq!(None, extensions.get::<ClientIdentity>(client_id));
+        assert_eq!(None, extensions.get::<QueryFingerprint>(Uuid::new_v4()));
+
+        extensions.remove_client(client_id);
+        assert_eq!(None, extensions.get::<QueryFingerprint>(client_id));
+    }
+}
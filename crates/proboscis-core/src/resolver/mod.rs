@@ -1,7 +1,13 @@
 mod error;
 mod interface;
+mod layer;
+mod operations;
 mod response;
 
 pub use error::ResolveError;
 pub use interface::*;
-pub use response::SyncResponse;
+pub use layer::{
+    ClientIdentity, Extensions, QueryFingerprint, ResolverLayer, ResolverStack, SharedExtensions,
+};
+pub use operations::{answer_operations, AnswerOperations, Operation};
+pub use response::{sync_error, SyncResponse};
@@ -0,0 +1,68 @@
+use super::{ClientId, Describe, Execute, ResolveError, SyncResponse};
+use async_trait::async_trait;
+
+/// An operation of the extended protocol, queued by resolvers which answer them at the next
+/// sync or flush instead of forwarding them
+pub enum Operation {
+    Parse,
+    Bind,
+    Describe(Describe),
+    Execute(Execute),
+}
+
+/// Answers the describes and executes of queued operations
+#[async_trait]
+pub trait AnswerOperations: Send {
+    async fn answer_describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError>;
+
+    async fn answer_execute(
+        &mut self,
+        client_id: ClientId,
+        execute: Execute,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError>;
+}
+
+/// The responses of the operations in order. Like a target, the operations after an error
+/// are skipped and the error is answered in their place.
+pub async fn answer_operations<A: AnswerOperations>(
+    answerer: &mut A,
+    client_id: ClientId,
+    operations: Vec<Operation>,
+) -> Vec<SyncResponse> {
+    let mut responses = vec![];
+    for operation in operations {
+        let answered = match operation {
+            Operation::Parse => {
+                responses.push(SyncResponse::ParseComplete);
+                Ok(())
+            }
+            Operation::Bind => {
+                responses.push(SyncResponse::BindComplete);
+                Ok(())
+            }
+            Operation::Describe(describe) => {
+                answerer
+                    .answer_describe(client_id, describe, &mut responses)
+                    .await
+            }
+            Operation::Execute(execute) => {
+                answerer
+                    .answer_execute(client_id, execute, &mut responses)
+                    .await
+            }
+        };
+
+        if let Err(err) = answered {
+            responses.push(SyncResponse::Error(err.to_error_response()));
+            break;
+        }
+    }
+
+    responses
+}
@@ -3,12 +3,18 @@ use crate::data::arrow::{
 };
 use arrow::{datatypes::Schema, record_batch::RecordBatch};
 use proboscis_postgres_protocol::message::{
-    BackendMessage, CommandCompleteTag, ParameterDescription, ReadyForQueryTransactionStatus,
+    BackendMessage, CommandCompleteTag, Error, ParameterDescription, ReadyForQueryTransactionStatus,
 };
 
 pub enum SyncResponse {
-    Schema { schema: Schema, query: String },
-    Records { data: RecordBatch, query: String },
+    Schema {
+        schema: Schema,
+        query: String,
+    },
+    Records {
+        data: Vec<RecordBatch>,
+        query: String,
+    },
     CommandComplete(CommandCompleteTag),
     BindComplete,
     ParseComplete,
@@ -17,6 +23,8 @@ pub enum SyncResponse {
     NoData,
     EmptyQueryResponse,
     PortalSuspended,
+    /// An operation failed, the target skips the operations after it until the next sync
+    Error(Error),
 }
 
 impl SyncResponse {
@@ -26,15 +34,11 @@ impl SyncResponse {
                 let row_description = serialize_record_batch_schema_to_row_description(&schema);
                 vec![BackendMessage::RowDescription(row_description)]
             }
-            SyncResponse::Records { data, query: _ } => {
-                let messages = serialize_record_batch_to_data_rows(&data)
-                    .unwrap()
-                    .iter()
-                    .map(|data_row| BackendMessage::DataRow(data_row.clone()))
-                    .collect();
-
-                messages
-            }
+            SyncResponse::Records { data, query: _ } => data
+                .iter()
+                .flat_map(|batch| serialize_record_batch_to_data_rows(batch).unwrap())
+                .map(BackendMessage::DataRow)
+                .collect(),
             SyncResponse::CommandComplete(tag) => vec![BackendMessage::CommandComplete(tag)],
             SyncResponse::ParameterDescription(parameter_description) => {
                 vec![BackendMessage::ParameterDescription(parameter_description)]
@@ -47,6 +51,15 @@ impl SyncResponse {
             SyncResponse::NoData => vec![BackendMessage::NoData],
             SyncResponse::EmptyQueryResponse => vec![BackendMessage::EmptyQueryResponse],
             SyncResponse::PortalSuspended => vec![BackendMessage::PortalSuspended],
+            SyncResponse::Error(error) => vec![BackendMessage::Error(error)],
         }
     }
 }
+
+/// The error an operation among the responses failed with, if any
+pub fn sync_error(responses: &[SyncResponse]) -> Option<&Error> {
+    responses.iter().find_map(|response| match response {
+        SyncResponse::Error(error) => Some(error),
+        _ => None,
+    })
+}
@@ -0,0 +1,118 @@
+use crate::{hooks::ConnectionInfo, resolver::ClientId};
+use once_cell::sync::Lazy;
+use std::{collections::HashMap, net::SocketAddr, sync::Mutex};
+
+/// A connected client and the backend of the target serving it, so the log lines of the
+/// proxy can be matched with those of the target
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub client_id: ClientId,
+    /// Short id of the session, logged as `session` and part of the application name
+    /// of its connections to the target
+    pub correlation_id: String,
+    pub address: SocketAddr,
+    /// Set once the client is authenticated
+    pub user: Option<String>,
+    /// Process id of the postgres backend the session currently uses, none while
+    /// it holds no connection, e.g. between transactions of a pool in transaction mode
+    pub backend_pid: Option<u32>,
+}
+
+static SESSIONS: Lazy<Mutex<HashMap<ClientId, Session>>> = Lazy::new(Mutex::default);
+
+/// The short id of the client's session, the start of its client id
+pub fn correlation_id(client_id: &ClientId) -> String {
+    client_id.to_string()[..8].to_string()
+}
+
+pub fn register(connection: &ConnectionInfo) {
+    let session = Session {
+        client_id: connection.client_id,
+        correlation_id: correlation_id(&connection.client_id),
+        address: connection.address,
+        user: None,
+        backend_pid: None,
+    };
+
+    SESSIONS
+        .lock()
+        .expect("Sessions lock poisoned")
+        .insert(connection.client_id, session);
+}
+
+pub fn set_user(client_id: &ClientId, user: Option<&str>) {
+    if let Some(session) = SESSIONS
+        .lock()
+        .expect("Sessions lock poisoned")
+        .get_mut(client_id)
+    {
+        session.user = user.map(str::to_string);
+    }
+}
+
+/// Records the backend serving the client, sessions of other proxies than the one of the
+/// process, e.g. resolvers used on their own, aren't registered and ignored
+pub fn set_backend_pid(client_id: &ClientId, backend_pid: Option<u32>) {
+    if let Some(session) = SESSIONS
+        .lock()
+        .expect("Sessions lock poisoned")
+        .get_mut(client_id)
+    {
+        session.backend_pid = backend_pid;
+    }
+}
+
+pub fn unregister(client_id: &ClientId) {
+    SESSIONS
+        .lock()
+        .expect("Sessions lock poisoned")
+        .remove(client_id);
+}
+
+/// The sessions of the connected clients, ordered by their correlation id
+pub fn sessions() -> Vec<Session> {
+    let mut sessions: Vec<Session> = SESSIONS
+        .lock()
+        .expect("Sessions lock poisoned")
+        .values()
+        .cloned()
+        .collect();
+
+    sessions.sort_by(|a, b| a.correlation_id.cmp(&b.correlation_id));
+    sessions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_session_registry() {
+        let connection = ConnectionInfo {
+            client_id: Uuid::new_v4(),
+            address: "127.0.0.1:54321".parse().unwrap(),
+        };
+        let find = || {
+            sessions()
+                .into_iter()
+                .find(|session| session.client_id == connection.client_id)
+        };
+
+        register(&connection);
+        set_user(&connection.client_id, Some("analyst"));
+        set_backend_pid(&connection.client_id, Some(4242));
+
+        let session = find().unwrap();
+        assert_eq!(8, session.correlation_id.len());
+        assert!(connection
+            .client_id
+            .to_string()
+            .starts_with(&session.correlation_id));
+        assert_eq!(Some("analyst".to_string()), session.user);
+        assert_eq!(Some(4242), session.backend_pid);
+
+        unregister(&connection.client_id);
+        assert_eq!(None, find());
+    }
+}
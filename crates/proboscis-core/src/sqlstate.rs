@@ -0,0 +1,39 @@
+//! The SQLSTATE codes of the errors the proxy sends to clients
+use proboscis_postgres_protocol::message::Error;
+
+pub const WARNING: &str = "01000";
+pub const FEATURE_NOT_SUPPORTED: &str = "0A000";
+pub const CONNECTION_FAILURE: &str = "08006";
+pub const PROTOCOL_VIOLATION: &str = "08P01";
+pub const INVALID_PARAMETER_VALUE: &str = "22023";
+pub const INVALID_AUTHORIZATION_SPECIFICATION: &str = "28000";
+pub const INVALID_PASSWORD: &str = "28P01";
+pub const INSUFFICIENT_PRIVILEGE: &str = "42501";
+pub const QUERY_CANCELED: &str = "57014";
+pub const INTERNAL_ERROR: &str = "XX000";
+
+/// Severities of errors, fatal ones end the connection, warnings are sent as notices
+pub const WARNING_SEVERITY: &str = "WARNING";
+pub const ERROR: &str = "ERROR";
+pub const FATAL: &str = "FATAL";
+
+/// The fields of an ErrorResponse
+pub fn error_response(severity: &str, code: &str, message: String) -> Error {
+    Error {
+        messages: vec![
+            (b'S', severity.to_string()),
+            (b'V', severity.to_string()),
+            (b'C', code.to_string()),
+            (b'M', message),
+        ],
+    }
+}
+
+/// The code of an ErrorResponse, e.g. one a target sent
+pub fn code_of(error: &Error) -> Option<&str> {
+    error
+        .messages
+        .iter()
+        .find(|(field, _)| *field == b'C')
+        .map(|(_, code)| code.as_str())
+}
@@ -0,0 +1,150 @@
+use std::{
+    net::{IpAddr, Ipv4Addr},
+    str::FromStr,
+};
+
+/// A range of addresses in CIDR notation, e.g. `10.0.0.0/8` or `fd00::/8`.
+/// A single address without a prefix length matches only itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Cidr {
+    network: IpAddr,
+    prefix_length: u8,
+}
+
+// Clients connecting over IPv6 to a dual stack socket have IPv4 mapped addresses like `::ffff:10.0.0.1`
+fn unmap(address: IpAddr) -> IpAddr {
+    match address {
+        IpAddr::V6(v6) => match v6.octets() {
+            [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, a, b, c, d] => {
+                IpAddr::V4(Ipv4Addr::new(a, b, c, d))
+            }
+            _ => address,
+        },
+        _ => address,
+    }
+}
+
+fn leading_bits_match(a: &[u8], b: &[u8], bits: u8) -> bool {
+    let full_bytes = (bits / 8) as usize;
+    let remaining_bits = bits % 8;
+
+    if a[..full_bytes] != b[..full_bytes] {
+        return false;
+    }
+
+    if remaining_bits == 0 {
+        return true;
+    }
+
+    let mask = 0xff_u8 << (8 - remaining_bits);
+    a[full_bytes] & mask == b[full_bytes] & mask
+}
+
+impl Cidr {
+    pub fn contains(&self, address: IpAddr) -> bool {
+        match (self.network, unmap(address)) {
+            (IpAddr::V4(network), IpAddr::V4(address)) => {
+                leading_bits_match(&network.octets(), &address.octets(), self.prefix_length)
+            }
+            (IpAddr::V6(network), IpAddr::V6(address)) => {
+                leading_bits_match(&network.octets(), &address.octets(), self.prefix_length)
+            }
+            _ => false,
+        }
+    }
+}
+
+impl FromStr for Cidr {
+    type Err = String;
+
+    fn from_str(input: &str) -> Result<Cidr, String> {
+        let (address, prefix_length) = match input.split_once('/') {
+            Some((address, prefix_length)) => (address, Some(prefix_length)),
+            None => (input, None),
+        };
+
+        let network = unmap(
+            address
+                .trim()
+                .parse::<IpAddr>()
+                .map_err(|err| format!("invalid address '{}': {}", address, err))?,
+        );
+
+        let max_prefix_length = if network.is_ipv4() { 32 } else { 128 };
+        let prefix_length = match prefix_length {
+            Some(prefix_length) => prefix_length
+                .trim()
+                .parse::<u8>()
+                .ok()
+                .filter(|prefix_length| *prefix_length <= max_prefix_length)
+                .ok_or_else(|| format!("invalid prefix length in '{}'", input))?,
+            None => max_prefix_length,
+        };
+
+        Ok(Cidr {
+            network,
+            prefix_length,
+        })
+    }
+}
+
+/// Decides which client addresses may connect, before their startup message is read.
+/// Denied ranges take precedence, an empty allowlist allows every address that isn't denied.
+#[derive(Debug, Clone, Default)]
+pub struct AddressFilter {
+    pub allow: Vec<Cidr>,
+    pub deny: Vec<Cidr>,
+}
+
+impl AddressFilter {
+    pub fn is_allowed(&self, address: IpAddr) -> bool {
+        if self.deny.iter().any(|cidr| cidr.contains(address)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|cidr| cidr.contains(address))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cidr(input: &str) -> Cidr {
+        input.parse().unwrap()
+    }
+
+    fn address(input: &str) -> IpAddr {
+        input.parse().unwrap()
+    }
+
+    #[test]
+    fn test_cidr_contains() {
+        assert!(cidr("10.0.0.0/8").contains(address("10.1.2.3")));
+        assert!(!cidr("10.0.0.0/8").contains(address("11.0.0.1")));
+        assert!(cidr("192.168.1.0/23").contains(address("192.168.0.255")));
+        assert!(!cidr("192.168.1.0/24").contains(address("192.168.0.255")));
+        assert!(cidr("0.0.0.0/0").contains(address("8.8.8.8")));
+        assert!(cidr("127.0.0.1").contains(address("::ffff:127.0.0.1")));
+        assert!(!cidr("127.0.0.1").contains(address("127.0.0.2")));
+        assert!(cidr("fd00::/8").contains(address("fd12:3456::1")));
+        assert!(!cidr("fd00::/8").contains(address("10.0.0.1")));
+
+        assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+        assert!("localhost".parse::<Cidr>().is_err());
+    }
+
+    #[test]
+    fn test_address_filter() {
+        let filter = AddressFilter {
+            allow: vec![cidr("10.0.0.0/8")],
+            deny: vec![cidr("10.0.0.0/24")],
+        };
+
+        assert!(filter.is_allowed(address("10.1.0.1")));
+        assert!(!filter.is_allowed(address("10.0.0.1")));
+        assert!(!filter.is_allowed(address("192.168.0.1")));
+
+        assert!(AddressFilter::default().is_allowed(address("192.168.0.1")));
+    }
+}
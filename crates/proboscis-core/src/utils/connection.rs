@@ -1,53 +1,142 @@
-use crate::data::arrow::{
-    serialize_record_batch_schema_to_row_description, serialize_record_batch_to_data_rows,
+use crate::{
+    data::arrow::{
+        serialize_record_batch_schema_to_row_description, serialize_record_batch_to_data_rows,
+    },
+    utils::tls::TlsStream,
 };
-use arrow::record_batch::RecordBatch;
+use arrow::{datatypes::Schema, record_batch::RecordBatch};
 use proboscis_postgres_protocol::{
     message::{BackendMessage, FrontendMessage},
     Message, ParseError, StartupMessage,
 };
 use std::collections::HashMap;
-use tokio::io::{AsyncWriteExt, BufWriter};
+use tokio::io::{AsyncReadExt, AsyncWriteExt, BufWriter, Chain};
 use tracing::debug;
 
-pub type MaybeTlsStream = tokio_util::either::Either<
-    tokio::net::TcpStream,
-    tokio_native_tls::TlsStream<tokio::net::TcpStream>,
->;
+pub type MaybeTlsStream = tokio_util::either::Either<tokio::net::TcpStream, TlsStream>;
+
+// Encoded messages are written out once the buffer grows beyond this size
+const WRITE_BUFFER_FLUSH_SIZE: usize = 64 * 1024;
+
+// The buffer keeps at most this capacity after writing a large result
+const WRITE_BUFFER_RETAINED_CAPACITY: usize = 1024 * 1024;
 
 #[derive(Debug)]
 pub struct Connection {
     stream: BufWriter<MaybeTlsStream>,
+    // Messages are encoded into it, reused for every message of the connection
+    write_buffer: Vec<u8>,
     pub parameters: HashMap<String, String>,
+    /// Process id of the postgres backend at the other end, for connections to a target
+    pub backend_pid: Option<u32>,
+    // Logged if handling the message fails unexpectedly
+    last_frontend_message: Option<FrontendMessage>,
+    // The first byte of the next message, read while waiting for it
+    peeked: Option<u8>,
 }
 
 impl Connection {
     pub fn new(stream: MaybeTlsStream, parameters: HashMap<String, String>) -> Connection {
         Connection {
             stream: BufWriter::new(stream),
+            write_buffer: Vec::with_capacity(WRITE_BUFFER_FLUSH_SIZE),
             parameters,
+            backend_pid: None,
+            last_frontend_message: None,
+            peeked: None,
+        }
+    }
+
+    // Reads start with the byte read while waiting for the message
+    fn reader(&mut self) -> Chain<std::io::Cursor<Vec<u8>>, &mut BufWriter<MaybeTlsStream>> {
+        let peeked = self.peeked.take().into_iter().collect();
+        std::io::Cursor::new(peeked).chain(&mut self.stream)
+    }
+
+    /// Waits until the next message arrives without reading it. Unlike reading a message, it
+    /// can be cancelled without losing any data, e.g. to wait for two connections at once.
+    pub async fn wait_for_message(&mut self) -> Result<(), std::io::Error> {
+        if self.peeked.is_some() {
+            return Ok(());
+        }
+
+        let mut byte = [0; 1];
+        if self.stream.read(&mut byte).await? == 0 {
+            return Err(std::io::ErrorKind::UnexpectedEof.into());
         }
+        self.peeked = Some(byte[0]);
+
+        Ok(())
     }
 
-    pub async fn write_data(&mut self, data: RecordBatch) -> Result<(), std::io::Error> {
-        let row_description = serialize_record_batch_schema_to_row_description(&data.schema());
+    /// Whether a message arrived that wasn't read yet, as far as `wait_for_message` knows
+    pub fn has_pending_message(&self) -> bool {
+        self.peeked.is_some()
+    }
 
-        self.write_message(BackendMessage::RowDescription(row_description).into())
-            .await?;
+    pub async fn write_data(&mut self, data: &[RecordBatch]) -> Result<(), std::io::Error> {
+        let schema = match data.first() {
+            Some(batch) => batch.schema(),
+            None => return Ok(()),
+        };
+
+        self.write_row_description(&schema);
+        for batch in data {
+            self.write_data_rows(batch).await?;
+        }
+        self.flush_data().await
+    }
+
+    /// Starts a result, its rows follow with `write_data_rows`
+    pub fn write_row_description(&mut self, schema: &Schema) {
+        let row_description = serialize_record_batch_schema_to_row_description(schema);
+        BackendMessage::RowDescription(row_description).encode(&mut self.write_buffer);
+    }
+
+    /// The rows are written in chunks and flushed once with `flush_data`,
+    /// instead of after every row
+    pub async fn write_data_rows(&mut self, batch: &RecordBatch) -> Result<(), std::io::Error> {
+        for message in serialize_record_batch_to_data_rows(batch)? {
+            BackendMessage::DataRow(message).encode(&mut self.write_buffer);
+
+            if self.write_buffer.len() >= WRITE_BUFFER_FLUSH_SIZE {
+                self.write_buffered().await?;
+            }
+        }
+
+        Ok(())
+    }
 
-        let data_rows = serialize_record_batch_to_data_rows(&data)?;
+    /// A chunk of the data of a copy out, flushed with `flush_data` like the rows of a result
+    pub async fn write_copy_data(&mut self, data: Vec<u8>) -> Result<(), std::io::Error> {
+        BackendMessage::CopyData(data).encode(&mut self.write_buffer);
 
-        for message in data_rows {
-            self.write_message(BackendMessage::DataRow(message).into())
-                .await?;
+        if self.write_buffer.len() >= WRITE_BUFFER_FLUSH_SIZE {
+            self.write_buffered().await?;
         }
 
         Ok(())
     }
 
+    pub async fn flush_data(&mut self) -> Result<(), std::io::Error> {
+        self.write_buffered().await?;
+        self.stream.flush().await
+    }
+
+    /// Writes the encoded messages to the stream, keeping the buffer for the next ones
+    async fn write_buffered(&mut self) -> Result<(), std::io::Error> {
+        let result = self.stream.write_all(&self.write_buffer).await;
+
+        self.write_buffer.clear();
+        self.write_buffer.shrink_to(WRITE_BUFFER_RETAINED_CAPACITY);
+
+        result
+    }
+
     pub async fn write_message(&mut self, message: Message) -> Result<(), std::io::Error> {
         debug!(message = ?message, "writing message");
-        message.write(&mut self.stream).await?;
+        message.encode(&mut self.write_buffer);
+        self.write_buffered().await?;
         self.stream.flush().await
     }
 
@@ -61,19 +150,54 @@ impl Connection {
     }
 
     pub async fn read_frontend_message(&mut self) -> Result<FrontendMessage, ParseError> {
-        let message = FrontendMessage::read(&mut self.stream).await;
+        let message = FrontendMessage::read(&mut self.reader()).await;
         debug!(message = ?message, "read frontend message");
+        if let Ok(message) = &message {
+            self.last_frontend_message = Some(message.clone());
+        }
+        message
+    }
+
+    /// The message the frontend sent last
+    pub fn last_frontend_message(&self) -> Option<&FrontendMessage> {
+        self.last_frontend_message.as_ref()
+    }
+
+    pub async fn read_sasl_initial_response(&mut self) -> Result<FrontendMessage, ParseError> {
+        let message = FrontendMessage::read_sasl_initial_response(&mut self.reader()).await;
+        debug!(message = ?message, "read sasl initial response");
+        message
+    }
+
+    pub async fn read_sasl_response(&mut self) -> Result<FrontendMessage, ParseError> {
+        let message = FrontendMessage::read_sasl_response(&mut self.reader()).await;
+        debug!(message = ?message, "read sasl response");
         message
     }
 
     pub async fn read_backend_message(&mut self) -> Result<BackendMessage, ParseError> {
-        let message = BackendMessage::read(&mut self.stream).await;
+        let message = BackendMessage::read(&mut self.reader()).await;
+        debug!(message = ?message, "read backend message");
+        message
+    }
+
+    /// Reads a message, failing on data rows with a field longer than the limit without buffering it
+    pub async fn read_backend_message_with_field_limit(
+        &mut self,
+        field_limit: Option<usize>,
+    ) -> Result<BackendMessage, ParseError> {
+        let message = match field_limit {
+            Some(field_limit) => {
+                BackendMessage::read_with_field_limit(&mut self.reader(), field_limit).await
+            }
+            None => BackendMessage::read(&mut self.reader()).await,
+        };
         debug!(message = ?message, "read backend message");
         message
     }
 
     pub async fn read_startup_message(&mut self) -> Result<StartupMessage, ParseError> {
-        let message = StartupMessage::read(&mut self.stream).await;
+        let message = StartupMessage::read(&mut self.reader()).await;
         debug!(message = ?message, "read startup message");
         message
     }
@@ -0,0 +1,59 @@
+/// The direction of a `COPY` streaming its data through the connection
+#[derive(Debug, PartialEq)]
+pub enum CopyDirection {
+    /// `COPY ... FROM STDIN`, the client sends the data
+    In,
+    /// `COPY ... TO STDOUT`, the client receives the data
+    Out,
+}
+
+/// Recognizes `COPY` statements exchanging their data with the client by their keywords,
+/// other statements, including copies from and to files, return none
+pub fn parse_copy_statement(statement: &str) -> Option<CopyDirection> {
+    let statement = statement.trim().trim_end_matches(';');
+    let words: Vec<String> = statement
+        .split(|c: char| c.is_whitespace() || c == '(' || c == ')')
+        .filter(|word| !word.is_empty())
+        .map(str::to_uppercase)
+        .collect();
+
+    if words.first().map(String::as_str) != Some("COPY") {
+        return None;
+    }
+
+    // The options come last, the query of a copy out may contain the keywords as well
+    words
+        .windows(2)
+        .rev()
+        .find_map(|pair| match (pair[0].as_str(), pair[1].as_str()) {
+            ("FROM", "STDIN") => Some(CopyDirection::In),
+            ("TO", "STDOUT") => Some(CopyDirection::Out),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_copy_statement() {
+        assert_eq!(
+            Some(CopyDirection::In),
+            parse_copy_statement("COPY contacts (id, name) FROM STDIN;")
+        );
+        assert_eq!(
+            Some(CopyDirection::Out),
+            parse_copy_statement("copy (SELECT * FROM contacts) to stdout WITH (FORMAT csv)")
+        );
+        assert_eq!(
+            Some(CopyDirection::Out),
+            parse_copy_statement("COPY (SELECT 1 FROM STDIN) TO STDOUT")
+        );
+        assert_eq!(
+            None,
+            parse_copy_statement("COPY contacts TO '/tmp/contacts'")
+        );
+        assert_eq!(None, parse_copy_statement("SELECT 'COPY t FROM STDIN'"));
+    }
+}
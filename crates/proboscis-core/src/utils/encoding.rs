@@ -0,0 +1,30 @@
+/// The only encoding the proxy speaks, upstream connections request it as well
+pub const SUPPORTED_CLIENT_ENCODING: &str = "UTF8";
+
+// Like postgres, names are compared ignoring case and non alphanumeric characters
+fn normalize(name: &str) -> String {
+    name.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// Whether a client requesting the encoding can be served without converting strings,
+/// `UNICODE` is an alias of `UTF8`
+pub fn is_supported_client_encoding(name: &str) -> bool {
+    matches!(normalize(name).as_str(), "UTF8" | "UNICODE")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_supported_client_encoding() {
+        assert!(is_supported_client_encoding("UTF8"));
+        assert!(is_supported_client_encoding("utf-8"));
+        assert!(is_supported_client_encoding("Unicode"));
+        assert!(!is_supported_client_encoding("LATIN1"));
+        assert!(!is_supported_client_encoding("SQL_ASCII"));
+    }
+}
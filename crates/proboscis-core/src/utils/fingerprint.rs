@@ -0,0 +1,230 @@
+use md5::{Digest, Md5};
+
+const LITERAL: &str = "?";
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '$'
+}
+
+fn is_operator_char(c: char) -> bool {
+    "+-*/<>=~!@#%^&|`?:".contains(c)
+}
+
+/// Reads until the closing quote, a doubled quote is part of the quoted text
+fn skip_quoted(chars: &[char], start: usize, quote: char) -> usize {
+    let mut index = start + 1;
+
+    while index < chars.len() {
+        if chars[index] == quote {
+            if chars.get(index + 1) == Some(&quote) {
+                index += 2;
+                continue;
+            }
+            return index + 1;
+        }
+        index += 1;
+    }
+
+    index
+}
+
+/// Reads a dollar quoted string like `$tag$text$tag$`, if one starts at the index
+fn skip_dollar_quoted(chars: &[char], start: usize) -> Option<usize> {
+    let tag_end = chars[start + 1..]
+        .iter()
+        .position(|c| !(c.is_alphanumeric() || *c == '_'))
+        .map(|position| start + 1 + position)?;
+
+    if chars[tag_end] != '$' {
+        return None;
+    }
+
+    let tag = &chars[start..=tag_end];
+    let end = (tag_end + 1..=chars.len().saturating_sub(tag.len()))
+        .find(|index| &chars[*index..*index + tag.len()] == tag)
+        .map(|index| index + tag.len())
+        .unwrap_or_else(|| chars.len());
+
+    Some(end)
+}
+
+fn tokenize(query: &str) -> Vec<String> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut tokens = vec![];
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        let next = chars.get(index + 1).copied();
+
+        if c.is_whitespace() {
+            index += 1;
+        } else if c == '-' && next == Some('-') {
+            while index < chars.len() && chars[index] != '\n' {
+                index += 1;
+            }
+        } else if c == '/' && next == Some('*') {
+            index = (index + 2..chars.len())
+                .find(|index| chars[*index - 1] == '*' && chars[*index] == '/')
+                .map(|index| index + 1)
+                .unwrap_or_else(|| chars.len());
+        } else if c == '\'' {
+            index = skip_quoted(&chars, index, '\'');
+            tokens.push(LITERAL.to_string());
+        } else if c == '"' {
+            let end = skip_quoted(&chars, index, '"');
+            tokens.push(chars[index..end].iter().collect());
+            index = end;
+        } else if c == '$' && next.map_or(false, |next| next.is_ascii_digit()) {
+            // Parameters of prepared statements stand for literals as well
+            index += 1;
+            while index < chars.len() && chars[index].is_ascii_digit() {
+                index += 1;
+            }
+            tokens.push(LITERAL.to_string());
+        } else if let Some(end) = (c == '$')
+            .then(|| skip_dollar_quoted(&chars, index))
+            .flatten()
+        {
+            index = end;
+            tokens.push(LITERAL.to_string());
+        } else if c.is_ascii_digit() || (c == '.' && next.map_or(false, |c| c.is_ascii_digit())) {
+            while index < chars.len()
+                && (chars[index].is_ascii_alphanumeric() || chars[index] == '.')
+            {
+                // Exponents can be signed, e.g. 1e-3
+                if (chars[index] == 'e' || chars[index] == 'E')
+                    && matches!(chars.get(index + 1), Some('+') | Some('-'))
+                {
+                    index += 1;
+                }
+                index += 1;
+            }
+            tokens.push(LITERAL.to_string());
+        } else if is_word_char(c) {
+            let start = index;
+            while index < chars.len() && is_word_char(chars[index]) {
+                index += 1;
+            }
+            // Like postgres, unquoted identifiers and keywords are case insensitive
+            let word: String = chars[start..index].iter().collect();
+            tokens.push(word.to_lowercase());
+        } else if is_operator_char(c) {
+            let start = index;
+            while index < chars.len() && is_operator_char(chars[index]) {
+                index += 1;
+            }
+            tokens.push(chars[start..index].iter().collect());
+        } else {
+            tokens.push(c.to_string());
+            index += 1;
+        }
+    }
+
+    tokens
+}
+
+/// Replaces lists of literals after `IN`, e.g. `in (?, ?, ?)` with `in (?)`,
+/// so queries differing only in the number of values share a fingerprint
+fn collapse_in_lists(tokens: Vec<String>) -> Vec<String> {
+    let mut collapsed: Vec<String> = vec![];
+    let mut index = 0;
+
+    while index < tokens.len() {
+        collapsed.push(tokens[index].clone());
+
+        let follows_in =
+            tokens[index] == "(" && collapsed.len() >= 2 && collapsed[collapsed.len() - 2] == "in";
+
+        if follows_in {
+            let mut end = index + 1;
+            while tokens.get(end).map(String::as_str) == Some(LITERAL) {
+                match tokens.get(end + 1).map(String::as_str) {
+                    Some(",") => end += 2,
+                    Some(")") => {
+                        collapsed.push(LITERAL.to_string());
+                        index = end;
+                        break;
+                    }
+                    _ => break,
+                }
+            }
+        }
+
+        index += 1;
+    }
+
+    collapsed
+}
+
+/// Normalizes the query, so queries which only differ in their literals,
+/// formatting, comments or the length of `IN` lists are the same
+pub fn normalize_query(query: &str) -> String {
+    let tokens = collapse_in_lists(tokenize(query));
+    let mut normalized = String::new();
+
+    for (index, token) in tokens.iter().enumerate() {
+        let previous = index.checked_sub(1).map(|index| tokens[index].as_str());
+        let no_space = matches!(previous, None | Some("(") | Some("."))
+            || matches!(token.as_str(), ")" | "," | "." | ";");
+
+        if !no_space {
+            normalized.push(' ');
+        }
+        normalized.push_str(token);
+    }
+
+    normalized
+}
+
+/// A short, stable identifier of the normalized query, for logs and metric labels
+pub fn fingerprint(query: &str) -> String {
+    let hash = Md5::digest(normalize_query(query).as_bytes());
+
+    hash[..8]
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_query() {
+        assert_eq!(
+            "select * from contacts where id in (?) and name = ?",
+            normalize_query(
+                "SELECT *\n  FROM contacts WHERE id IN (1, 2, 3) AND name = 'O''Brien'"
+            )
+        );
+        assert_eq!(
+            "select \"First Name\", age from contacts where age >= ? limit ?",
+            normalize_query(
+                "select \"First Name\", age from contacts /* adults */ where age >= 18 -- only\nlimit $1"
+            )
+        );
+        assert_eq!(
+            "select a.price * ? from a where b = ?",
+            normalize_query("select a.price * 1.5e-3 from a where b = $tag$it's$tag$")
+        );
+        assert_eq!(
+            "select * from contacts where id in (select id from deleted)",
+            normalize_query("select * from contacts where id in (select id from deleted)")
+        );
+    }
+
+    #[test]
+    fn test_fingerprint() {
+        assert_eq!(
+            fingerprint("select * from contacts where id in (1, 2)"),
+            fingerprint("SELECT * FROM contacts WHERE id IN (7)")
+        );
+        assert_ne!(
+            fingerprint("select * from contacts"),
+            fingerprint("select * from accounts")
+        );
+        assert_eq!(16, fingerprint("select 1").len());
+    }
+}
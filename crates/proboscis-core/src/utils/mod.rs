@@ -1,2 +1,16 @@
+pub mod address_filter;
 pub mod connection;
+pub mod copy;
+pub mod encoding;
+pub mod fingerprint;
+#[cfg(not(feature = "tls"))]
+mod no_tls;
 pub mod password;
+pub mod role;
+pub mod scram;
+pub mod sql;
+#[cfg(feature = "tls")]
+pub mod tls;
+#[cfg(not(feature = "tls"))]
+pub use no_tls as tls;
+pub mod transaction;
@@ -0,0 +1,65 @@
+//! Stands in for the tls module without the `tls` feature, identities can't be loaded
+//! so every client is served in plaintext
+use crate::ProboscisError;
+use std::{
+    io,
+    pin::Pin,
+    task::{Context, Poll},
+};
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+/// No value of it exists, as no tls acceptor can be loaded
+#[derive(Clone, Debug)]
+pub enum NoTls {}
+
+pub type TlsAcceptor = NoTls;
+pub type TlsStream = NoTls;
+
+pub struct ReloadingTlsAcceptor(NoTls);
+
+impl ReloadingTlsAcceptor {
+    pub fn load(_path: &str, _password: &str) -> Result<ReloadingTlsAcceptor, ProboscisError> {
+        Err(ProboscisError::TlsDisabled)
+    }
+
+    pub fn acceptor(&mut self) -> TlsAcceptor {
+        match self.0 {}
+    }
+}
+
+impl NoTls {
+    pub async fn accept(
+        &self,
+        _stream: tokio::net::TcpStream,
+    ) -> Result<TlsStream, ProboscisError> {
+        match *self {}
+    }
+}
+
+impl AsyncRead for NoTls {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        match *self {}
+    }
+}
+
+impl AsyncWrite for NoTls {
+    fn poll_write(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+        _buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        match *self {}
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match *self {}
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        match *self {}
+    }
+}
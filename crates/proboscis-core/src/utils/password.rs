@@ -1,11 +1,80 @@
+use super::scram::ScramVerifier;
 use md5::{Digest, Md5};
 
-pub fn encode_md5_password_hash(username: &str, password: &str, salt: &[u8]) -> String {
+const MD5_PREFIX: &str = "md5";
+
+/// The md5 verifier of a password, as postgres stores it in `pg_authid`
+pub fn md5_password_verifier(username: &str, password: &str) -> String {
     let mut md5 = Md5::new();
     md5.update(password.as_bytes());
     md5.update(username.as_bytes());
-    let output = md5.finalize_reset();
-    md5.update(format!("{:x}", output));
+    format!("{}{:x}", MD5_PREFIX, md5.finalize())
+}
+
+/// Like postgres, passwords of the form `md5` followed by 32 hex digits are
+/// treated as md5 verifiers instead of plaintext passwords
+pub fn is_md5_password_verifier(password: &str) -> bool {
+    password.len() == MD5_PREFIX.len() + 32
+        && password.starts_with(MD5_PREFIX)
+        && password[MD5_PREFIX.len()..]
+            .chars()
+            .all(|c| c.is_ascii_hexdigit())
+}
+
+/// The response to an md5 authentication request, computed from the verifier of the password
+pub fn encode_md5_verifier_hash(verifier: &str, salt: &[u8]) -> String {
+    let mut md5 = Md5::new();
+    md5.update(verifier.strip_prefix(MD5_PREFIX).unwrap_or(verifier));
     md5.update(&salt);
-    format!("md5{:x}", md5.finalize())
+    format!("{}{:x}", MD5_PREFIX, md5.finalize())
+}
+
+pub fn encode_md5_password_hash(username: &str, password: &str, salt: &[u8]) -> String {
+    encode_md5_verifier_hash(&md5_password_verifier(username, password), salt)
+}
+
+/// Whether the plaintext password matches the stored password, or md5 or SCRAM verifier of the user
+pub fn verify_password(username: &str, password: &str, stored: &str) -> bool {
+    if is_md5_password_verifier(stored) {
+        md5_password_verifier(username, password) == stored
+    } else if let Some(verifier) = ScramVerifier::parse(stored) {
+        verifier.verify_password(password)
+    } else {
+        password == stored
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_md5_password_verifier() {
+        let verifier = md5_password_verifier("admin", "password");
+
+        assert!(is_md5_password_verifier(&verifier));
+        assert!(!is_md5_password_verifier("password"));
+        assert!(!is_md5_password_verifier(
+            "md5zzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz"
+        ));
+
+        assert_eq!(
+            encode_md5_password_hash("admin", "password", &[1, 2, 3, 4]),
+            encode_md5_verifier_hash(&verifier, &[1, 2, 3, 4])
+        );
+    }
+
+    #[test]
+    fn test_verify_password() {
+        let verifier = md5_password_verifier("admin", "password");
+
+        assert!(verify_password("admin", "password", &verifier));
+        assert!(!verify_password("other", "password", &verifier));
+        assert!(verify_password("admin", "password", "password"));
+        assert!(!verify_password("admin", "secret", "password"));
+
+        let verifier = ScramVerifier::from_password("password").to_string();
+        assert!(verify_password("admin", "password", &verifier));
+        assert!(!verify_password("admin", "secret", &verifier));
+    }
 }
@@ -0,0 +1,131 @@
+use sqlparser::{
+    dialect::PostgreSqlDialect,
+    tokenizer::{Token, Tokenizer},
+};
+
+/// A statement changing the role the statements of a session run as
+#[derive(Clone, Debug, PartialEq)]
+pub enum RoleChange {
+    /// `SET ROLE` or `SET SESSION AUTHORIZATION` to the role
+    Set(String),
+    /// Back to the role the session authenticated as, e.g. `RESET ROLE`, `SET ROLE NONE`
+    /// or `DISCARD ALL`
+    Reset,
+    /// A change to a role that isn't known without running the query, e.g. by `set_config`
+    Opaque,
+}
+
+/// The role changes of the statements of the query in their order. `SET LOCAL ROLE` is
+/// treated like `SET ROLE`, the role is kept after its transaction ends.
+pub fn parse_role_changes(query: &str) -> Vec<RoleChange> {
+    let tokens = match Tokenizer::new(&PostgreSqlDialect {}, query).tokenize() {
+        Ok(tokens) => tokens,
+        Err(_) => return vec![],
+    };
+
+    let mut changes: Vec<RoleChange> = tokens
+        .split(|token| token == &Token::SemiColon)
+        .filter_map(parse_role_change)
+        .collect();
+
+    if calls_set_config_of_role(&tokens) {
+        changes.push(RoleChange::Opaque);
+    }
+
+    changes
+}
+
+// `set_config('role', ...)` changes the role like `SET ROLE`
+fn calls_set_config_of_role(tokens: &[Token]) -> bool {
+    let tokens: Vec<&Token> = tokens
+        .iter()
+        .filter(|token| !matches!(token, Token::Whitespace(_)))
+        .collect();
+
+    tokens.windows(3).any(|window| match window {
+        [Token::Word(function), Token::LParen, Token::SingleQuotedString(setting)] => {
+            function.value.eq_ignore_ascii_case("set_config")
+                && (setting.eq_ignore_ascii_case("role")
+                    || setting.eq_ignore_ascii_case("session_authorization"))
+        }
+        _ => false,
+    })
+}
+
+fn parse_role_change(tokens: &[Token]) -> Option<RoleChange> {
+    let words: Vec<(String, bool)> = tokens
+        .iter()
+        .filter(|token| !matches!(token, Token::Whitespace(_)))
+        .map(|token| match token {
+            Token::Word(word) => match word.quote_style {
+                Some(_) => Some((word.value.clone(), true)),
+                None => Some((word.value.to_lowercase(), false)),
+            },
+            Token::SingleQuotedString(value) => Some((value.clone(), true)),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+
+    let keywords: Vec<&str> = words
+        .iter()
+        .map(|(word, quoted)| if *quoted { "" } else { word.as_str() })
+        .collect();
+
+    let role = |index: usize| -> Option<RoleChange> {
+        match (words.get(index), words.len() == index + 1) {
+            (Some((word, false)), true) if word == "none" || word == "default" => {
+                Some(RoleChange::Reset)
+            }
+            (Some((word, _)), true) => Some(RoleChange::Set(word.clone())),
+            _ => None,
+        }
+    };
+
+    match keywords.as_slice() {
+        ["reset", "role"]
+        | ["reset", "session", "authorization"]
+        | ["reset", "all"]
+        | ["discard", "all"] => Some(RoleChange::Reset),
+        ["set", "role", ..] => role(2),
+        ["set", "session" | "local", "role", ..] => role(3),
+        ["set", "session", "authorization", ..] => role(3),
+        ["set", "session" | "local", "session", "authorization", ..] => role(4),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_role_changes() {
+        let set = |role: &str| vec![RoleChange::Set(role.to_string())];
+
+        assert_eq!(set("analyst"), parse_role_changes("SET ROLE Analyst"));
+        assert_eq!(set("Analyst"), parse_role_changes("set role \"Analyst\";"));
+        assert_eq!(set("admin"), parse_role_changes("SET LOCAL ROLE 'admin'"));
+        assert_eq!(
+            set("admin"),
+            parse_role_changes("SET SESSION AUTHORIZATION admin")
+        );
+        assert_eq!(
+            set("admin"),
+            parse_role_changes("SELECT 1; /* switch */ SET ROLE admin")
+        );
+        assert_eq!(
+            vec![RoleChange::Reset],
+            parse_role_changes("SET SESSION AUTHORIZATION DEFAULT")
+        );
+        assert_eq!(
+            vec![RoleChange::Set("admin".to_string()), RoleChange::Reset],
+            parse_role_changes("SET ROLE admin; RESET ROLE")
+        );
+        assert!(parse_role_changes("SET search_path = public").is_empty());
+        assert!(parse_role_changes("SELECT 'SET ROLE admin'").is_empty());
+        assert_eq!(
+            vec![RoleChange::Opaque],
+            parse_role_changes("SELECT set_config('role', current_user, false)")
+        );
+    }
+}
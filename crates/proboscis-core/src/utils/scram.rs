@@ -0,0 +1,400 @@
+use hmac::{Hmac, Mac, NewMac};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use thiserror::Error;
+
+pub const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+
+const VERIFIER_PREFIX: &str = "SCRAM-SHA-256$";
+// The iterations postgres derives the keys of new passwords with
+const DEFAULT_ITERATIONS: u32 = 4096;
+const NONCE_LENGTH: usize = 18;
+const SALT_LENGTH: usize = 16;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum ScramError {
+    #[error("malformed SCRAM message: {0}")]
+    MalformedMessage(&'static str),
+
+    #[error("channel binding isn't supported")]
+    UnsupportedChannelBinding,
+
+    #[error("the nonce of the client doesn't match")]
+    NonceMismatch,
+
+    #[error("the proof of the client doesn't match")]
+    InvalidProof,
+
+    #[error("the signature of the server doesn't match")]
+    InvalidSignature,
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn xor(a: &[u8], b: &[u8]) -> Vec<u8> {
+    a.iter().zip(b).map(|(a, b)| a ^ b).collect()
+}
+
+// Compares in constant time, so the time of a failed attempt doesn't leak the key
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && xor(a, b).iter().fold(0, |acc, byte| acc | byte) == 0
+}
+
+/// The key the password derives with the salt, PBKDF2 with HMAC-SHA-256 of a single block
+fn salted_password(password: &str, salt: &[u8], iterations: u32) -> Vec<u8> {
+    let mut message = salt.to_vec();
+    message.extend_from_slice(&1u32.to_be_bytes());
+
+    let mut previous = hmac(password.as_bytes(), &message);
+    let mut result = previous.clone();
+    for _ in 1..iterations {
+        previous = hmac(password.as_bytes(), &previous);
+        result = xor(&result, &previous);
+    }
+
+    result
+}
+
+/// The keys of a password postgres stores in `pg_authid` with `password_encryption = scram-sha-256`,
+/// of the form `SCRAM-SHA-256$<iterations>:<salt>$<StoredKey>:<ServerKey>`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScramVerifier {
+    pub iterations: u32,
+    pub salt: Vec<u8>,
+    pub stored_key: Vec<u8>,
+    pub server_key: Vec<u8>,
+}
+
+impl ScramVerifier {
+    /// Derives the keys of the password with a random salt
+    pub fn from_password(password: &str) -> ScramVerifier {
+        let salt = rand::thread_rng().gen::<[u8; SALT_LENGTH]>();
+        ScramVerifier::with_salt(password, &salt, DEFAULT_ITERATIONS)
+    }
+
+    pub fn with_salt(password: &str, salt: &[u8], iterations: u32) -> ScramVerifier {
+        let salted_password = salted_password(password, salt, iterations);
+
+        ScramVerifier {
+            iterations,
+            salt: salt.to_vec(),
+            stored_key: Sha256::digest(&hmac(&salted_password, b"Client Key")).to_vec(),
+            server_key: hmac(&salted_password, b"Server Key"),
+        }
+    }
+
+    /// Parses a stored verifier, none for other passwords
+    pub fn parse(verifier: &str) -> Option<ScramVerifier> {
+        let (parameters, keys) = verifier.strip_prefix(VERIFIER_PREFIX)?.split_once('$')?;
+        let (iterations, salt) = parameters.split_once(':')?;
+        let (stored_key, server_key) = keys.split_once(':')?;
+
+        Some(ScramVerifier {
+            iterations: iterations.parse().ok()?,
+            salt: base64::decode(salt).ok()?,
+            stored_key: base64::decode(stored_key).ok()?,
+            server_key: base64::decode(server_key).ok()?,
+        })
+    }
+
+    /// Whether the plaintext password derives the keys
+    pub fn verify_password(&self, password: &str) -> bool {
+        let derived = ScramVerifier::with_salt(password, &self.salt, self.iterations);
+        equal(&derived.stored_key, &self.stored_key)
+    }
+}
+
+impl std::fmt::Display for ScramVerifier {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}{}:{}${}:{}",
+            VERIFIER_PREFIX,
+            self.iterations,
+            base64::encode(&self.salt),
+            base64::encode(&self.stored_key),
+            base64::encode(&self.server_key)
+        )
+    }
+}
+
+/// Whether the password is stored as a SCRAM verifier instead of in plaintext
+pub fn is_scram_password_verifier(password: &str) -> bool {
+    ScramVerifier::parse(password).is_some()
+}
+
+fn random_nonce() -> String {
+    base64::encode(rand::thread_rng().gen::<[u8; NONCE_LENGTH]>())
+}
+
+fn attribute<'a>(attributes: &[&'a str], name: char) -> Option<&'a str> {
+    attributes.iter().find_map(|attribute| {
+        attribute
+            .strip_prefix(name)
+            .and_then(|rest| rest.strip_prefix('='))
+    })
+}
+
+/// The server side of a SCRAM-SHA-256 exchange (RFC 5802, RFC 7677) without channel binding.
+/// Like postgres, the username of the messages is ignored in favor of the one of the startup
+/// message, and passwords aren't normalized with SASLprep.
+pub struct ScramServer {
+    verifier: ScramVerifier,
+    server_nonce: String,
+    // The messages so far, the proofs of both sides sign
+    client_first_bare: Option<String>,
+    server_first: Option<String>,
+    gs2_header: Option<String>,
+    nonce: Option<String>,
+}
+
+impl ScramServer {
+    pub fn new(verifier: ScramVerifier) -> ScramServer {
+        ScramServer::with_server_nonce(verifier, random_nonce())
+    }
+
+    fn with_server_nonce(verifier: ScramVerifier, server_nonce: String) -> ScramServer {
+        ScramServer {
+            verifier,
+            server_nonce,
+            client_first_bare: None,
+            server_first: None,
+            gs2_header: None,
+            nonce: None,
+        }
+    }
+
+    /// Answers the data of the client's `SASLInitialResponse` with the server-first-message
+    pub fn handle_client_first(&mut self, message: &[u8]) -> Result<Vec<u8>, ScramError> {
+        let message = std::str::from_utf8(message)
+            .map_err(|_| ScramError::MalformedMessage("client-first-message isn't utf-8"))?;
+
+        // The gs2 header consists of the channel binding flag and an optional authzid
+        let mut parts = message.splitn(3, ',');
+        let (flag, authzid, bare) = match (parts.next(), parts.next(), parts.next()) {
+            (Some(flag), Some(authzid), Some(bare)) => (flag, authzid, bare),
+            _ => return Err(ScramError::MalformedMessage("missing gs2 header")),
+        };
+
+        match flag {
+            "n" | "y" => {}
+            flag if flag.starts_with("p=") => return Err(ScramError::UnsupportedChannelBinding),
+            _ => return Err(ScramError::MalformedMessage("invalid channel binding flag")),
+        }
+
+        let attributes: Vec<&str> = bare.split(',').collect();
+        if attribute(&attributes, 'm').is_some() {
+            return Err(ScramError::MalformedMessage(
+                "unsupported mandatory extension",
+            ));
+        }
+
+        let client_nonce = attribute(&attributes, 'r')
+            .filter(|nonce| !nonce.is_empty())
+            .ok_or(ScramError::MalformedMessage("missing nonce"))?;
+
+        let nonce = format!("{}{}", client_nonce, self.server_nonce);
+        let server_first = format!(
+            "r={},s={},i={}",
+            nonce,
+            base64::encode(&self.verifier.salt),
+            self.verifier.iterations
+        );
+
+        self.gs2_header = Some(format!("{},{},", flag, authzid));
+        self.client_first_bare = Some(bare.to_string());
+        self.server_first = Some(server_first.clone());
+        self.nonce = Some(nonce);
+
+        Ok(server_first.into_bytes())
+    }
+
+    /// Verifies the proof of the client's client-final-message, answered with the
+    /// server-final-message proving the server knows the keys as well
+    pub fn handle_client_final(&mut self, message: &[u8]) -> Result<Vec<u8>, ScramError> {
+        let (client_first_bare, server_first, gs2_header, nonce) = match (
+            &self.client_first_bare,
+            &self.server_first,
+            &self.gs2_header,
+            &self.nonce,
+        ) {
+            (Some(bare), Some(server_first), Some(gs2_header), Some(nonce)) => {
+                (bare, server_first, gs2_header, nonce)
+            }
+            _ => {
+                return Err(ScramError::MalformedMessage(
+                    "unexpected client-final-message",
+                ))
+            }
+        };
+
+        let message = std::str::from_utf8(message)
+            .map_err(|_| ScramError::MalformedMessage("client-final-message isn't utf-8"))?;
+        let (without_proof, proof) = message
+            .rsplit_once(",p=")
+            .ok_or(ScramError::MalformedMessage("missing proof"))?;
+
+        let attributes: Vec<&str> = without_proof.split(',').collect();
+        let channel_binding = attribute(&attributes, 'c')
+            .and_then(|binding| base64::decode(binding).ok())
+            .ok_or(ScramError::MalformedMessage("missing channel binding"))?;
+        if channel_binding != gs2_header.as_bytes() {
+            return Err(ScramError::UnsupportedChannelBinding);
+        }
+
+        if attribute(&attributes, 'r') != Some(nonce.as_str()) {
+            return Err(ScramError::NonceMismatch);
+        }
+
+        let proof =
+            base64::decode(proof).map_err(|_| ScramError::MalformedMessage("invalid proof"))?;
+        let auth_message = format!("{},{},{}", client_first_bare, server_first, without_proof);
+
+        let client_signature = hmac(&self.verifier.stored_key, auth_message.as_bytes());
+        let client_key = xor(&proof, &client_signature);
+        if proof.len() != client_signature.len()
+            || !equal(&Sha256::digest(&client_key), &self.verifier.stored_key)
+        {
+            return Err(ScramError::InvalidProof);
+        }
+
+        let server_signature = hmac(&self.verifier.server_key, auth_message.as_bytes());
+        Ok(format!("v={}", base64::encode(server_signature)).into_bytes())
+    }
+}
+
+/// The client side of a SCRAM-SHA-256 exchange without channel binding
+pub struct ScramClient {
+    password: String,
+    client_nonce: String,
+    client_first_bare: String,
+    server_signature: Option<Vec<u8>>,
+}
+
+impl ScramClient {
+    pub fn new(password: &str) -> ScramClient {
+        let client_nonce = random_nonce();
+
+        ScramClient {
+            password: password.to_string(),
+            client_first_bare: format!("n=,r={}", client_nonce),
+            client_nonce,
+            server_signature: None,
+        }
+    }
+
+    /// The data of the `SASLInitialResponse`
+    pub fn client_first(&self) -> Vec<u8> {
+        format!("n,,{}", self.client_first_bare).into_bytes()
+    }
+
+    /// Answers the server-first-message with the client-final-message
+    pub fn handle_server_first(&mut self, message: &[u8]) -> Result<Vec<u8>, ScramError> {
+        let message = std::str::from_utf8(message)
+            .map_err(|_| ScramError::MalformedMessage("server-first-message isn't utf-8"))?;
+        let attributes: Vec<&str> = message.split(',').collect();
+
+        let nonce = attribute(&attributes, 'r')
+            .filter(|nonce| nonce.starts_with(&self.client_nonce))
+            .ok_or(ScramError::NonceMismatch)?;
+        let salt = attribute(&attributes, 's')
+            .and_then(|salt| base64::decode(salt).ok())
+            .ok_or(ScramError::MalformedMessage("missing salt"))?;
+        let iterations = attribute(&attributes, 'i')
+            .and_then(|iterations| iterations.parse().ok())
+            .ok_or(ScramError::MalformedMessage("missing iteration count"))?;
+
+        let salted_password = salted_password(&self.password, &salt, iterations);
+        let client_key = hmac(&salted_password, b"Client Key");
+        let stored_key = Sha256::digest(&client_key);
+
+        // "biws" is the encoded gs2 header "n,,"
+        let without_proof = format!("c=biws,r={}", nonce);
+        let auth_message = format!("{},{},{}", self.client_first_bare, message, without_proof);
+        let proof = xor(&client_key, &hmac(&stored_key, auth_message.as_bytes()));
+
+        let server_key = hmac(&salted_password, b"Server Key");
+        self.server_signature = Some(hmac(&server_key, auth_message.as_bytes()));
+
+        Ok(format!("{},p={}", without_proof, base64::encode(proof)).into_bytes())
+    }
+
+    /// Checks the server-final-message proves the server knows the keys of the password
+    pub fn verify_server_final(&self, message: &[u8]) -> Result<(), ScramError> {
+        let signature = std::str::from_utf8(message)
+            .ok()
+            .and_then(|message| message.strip_prefix("v="))
+            .and_then(|signature| base64::decode(signature).ok())
+            .ok_or(ScramError::MalformedMessage("missing server signature"))?;
+
+        match &self.server_signature {
+            Some(expected) if equal(expected, &signature) => Ok(()),
+            _ => Err(ScramError::InvalidSignature),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The example exchange of RFC 7677
+    #[test]
+    fn test_rfc_7677_exchange() {
+        let salt = base64::decode("W22ZaJ0SNY7soEsUEjb6gQ==").unwrap();
+        let verifier = ScramVerifier::with_salt("pencil", &salt, 4096);
+        let mut server =
+            ScramServer::with_server_nonce(verifier, "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0".to_string());
+
+        assert_eq!(
+            "r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096"
+                .as_bytes(),
+            &server
+                .handle_client_first(b"n,,n=user,r=rOprNGfwEbeRWgbNEkqO")
+                .unwrap()[..]
+        );
+        assert_eq!(
+            b"v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4=",
+            &server
+                .handle_client_final(b"c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=")
+                .unwrap()[..]
+        );
+    }
+
+    #[test]
+    fn test_client_and_server() {
+        let verifier = ScramVerifier::from_password("password");
+        assert_eq!(
+            Some(verifier.clone()),
+            ScramVerifier::parse(&verifier.to_string())
+        );
+        assert!(verifier.verify_password("password"));
+        assert!(!verifier.verify_password("secret"));
+
+        let mut server = ScramServer::new(verifier.clone());
+        let mut client = ScramClient::new("password");
+        let server_first = server.handle_client_first(&client.client_first()).unwrap();
+        let client_final = client.handle_server_first(&server_first).unwrap();
+        let server_final = server.handle_client_final(&client_final).unwrap();
+        assert_eq!(Ok(()), client.verify_server_final(&server_final));
+
+        let mut server = ScramServer::new(verifier);
+        let mut client = ScramClient::new("secret");
+        let server_first = server.handle_client_first(&client.client_first()).unwrap();
+        let client_final = client.handle_server_first(&server_first).unwrap();
+        assert_eq!(
+            Err(ScramError::InvalidProof),
+            server.handle_client_final(&client_final)
+        );
+
+        assert_eq!(
+            Err(ScramError::UnsupportedChannelBinding),
+            ScramServer::new(ScramVerifier::from_password("password"))
+                .handle_client_first(b"p=tls-server-end-point,,n=,r=abc")
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use crate::utils::transaction::split_statements;
+use sqlparser::{
+    ast::{Query, SetExpr, Statement, TableFactor, TableWithJoins},
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+
+/// The first keyword of the statement in uppercase, e.g. `SELECT` for `(SELECT 1)`
+pub fn first_keyword(query: &str) -> String {
+    query
+        .trim_start_matches(|c: char| c.is_whitespace() || c == '(')
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .next()
+        .unwrap_or_default()
+        .to_uppercase()
+}
+
+/// The highest parameter like `$2` in the query, outside of string literals
+pub fn parameter_count(query: &str) -> usize {
+    let chars: Vec<char> = query.chars().collect();
+    let mut count = 0;
+    let mut in_literal = false;
+
+    for (index, c) in chars.iter().enumerate() {
+        match c {
+            '\'' => in_literal = !in_literal,
+            '$' if !in_literal => {
+                let number: String = chars[index + 1..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+                count = count.max(number.parse().unwrap_or(0));
+            }
+            _ => {}
+        }
+    }
+
+    count
+}
+
+// Statements the parser doesn't know which only describe the database, like `SHOW TABLES`
+fn only_describes(statement: &str) -> bool {
+    match first_keyword(statement).as_str() {
+        "SHOW" | "DESCRIBE" | "DESC" | "EXISTS" => true,
+        // EXPLAIN ANALYZE runs the statement
+        "EXPLAIN" => !statement.split(|c: char| !c.is_alphanumeric()).any(|word| {
+            word.eq_ignore_ascii_case("ANALYZE") || word.eq_ignore_ascii_case("ANALYSE")
+        }),
+        _ => false,
+    }
+}
+
+/// Whether every statement of the query only reads, so running it on another target has no effect
+pub fn is_read_only(query: &str) -> bool {
+    let statements = split_statements(query);
+
+    !statements.is_empty()
+        && statements.into_iter().all(|statement| {
+            match Parser::parse_sql(&PostgreSqlDialect {}, statement) {
+                Ok(parsed) => matches!(parsed.as_slice(), [Statement::Query(_)]),
+                Err(_) => only_describes(statement),
+            }
+        })
+}
+
+/// Name of a table without its schema and quotes, as names in queries may or may not be qualified
+pub fn unqualified_table_name(name: &str) -> String {
+    name.rsplit('.')
+        .next()
+        .unwrap_or(name)
+        .trim_matches('"')
+        .to_lowercase()
+}
+
+/// The tables in the FROM clauses of a query, as they are written
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct TableReferences {
+    pub tables: Vec<String>,
+    /// Whether the query reads no other tables. It may if it has common table expressions, whose
+    /// names would be mistaken for tables, subqueries within expressions, which aren't traversed,
+    /// or other sources like table functions.
+    pub complete: bool,
+}
+
+/// Collects the tables in the FROM clauses of a query, including those of subqueries in them
+/// and of common table expressions. Statements which aren't queries reference no tables.
+pub fn table_references(statement: &Statement) -> TableReferences {
+    let query = match statement {
+        Statement::Query(query) => query,
+        _ => return TableReferences::default(),
+    };
+
+    let mut references = TableReferences {
+        tables: vec![],
+        complete: true,
+    };
+    let mut select_count = 0;
+    collect_query_tables(query, &mut references, &mut select_count);
+
+    // Every subquery adds a SELECT keyword to the query, if there are more than the
+    // visited selects, some are hidden in expressions which aren't traversed
+    let keyword_count = query
+        .to_string()
+        .split(|c: char| !c.is_alphanumeric() && c != '_')
+        .filter(|word| word.eq_ignore_ascii_case("select"))
+        .count();
+
+    if keyword_count != select_count {
+        references.complete = false;
+    }
+
+    references
+}
+
+fn collect_query_tables(query: &Query, references: &mut TableReferences, select_count: &mut usize) {
+    if let Some(with) = &query.with {
+        references.complete = false;
+        for cte in &with.cte_tables {
+            collect_query_tables(&cte.query, references, select_count);
+        }
+    }
+
+    collect_set_expr_tables(&query.body, references, select_count);
+}
+
+fn collect_set_expr_tables(
+    set_expr: &SetExpr,
+    references: &mut TableReferences,
+    select_count: &mut usize,
+) {
+    match set_expr {
+        SetExpr::Select(select) => {
+            *select_count += 1;
+
+            for table in &select.from {
+                collect_table_with_joins_tables(table, references, select_count);
+            }
+        }
+        SetExpr::Query(query) => collect_query_tables(query, references, select_count),
+        SetExpr::SetOperation { left, right, .. } => {
+            collect_set_expr_tables(left, references, select_count);
+            collect_set_expr_tables(right, references, select_count);
+        }
+        _ => references.complete = false,
+    }
+}
+
+fn collect_table_with_joins_tables(
+    table: &TableWithJoins,
+    references: &mut TableReferences,
+    select_count: &mut usize,
+) {
+    let factors =
+        std::iter::once(&table.relation).chain(table.joins.iter().map(|join| &join.relation));
+
+    for factor in factors {
+        match factor {
+            TableFactor::Table { name, .. } => references.tables.push(name.to_string()),
+            TableFactor::Derived { subquery, .. } => {
+                collect_query_tables(subquery, references, select_count)
+            }
+            TableFactor::NestedJoin(table) => {
+                collect_table_with_joins_tables(table, references, select_count)
+            }
+            _ => references.complete = false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_keyword() {
+        assert_eq!("SELECT", first_keyword(" (select 1)"));
+        assert_eq!(
+            "WITH",
+            first_keyword("with t AS (SELECT 1) SELECT * FROM t")
+        );
+        assert_eq!("", first_keyword(""));
+    }
+
+    #[test]
+    fn test_parameter_count() {
+        assert_eq!(0, parameter_count("SELECT 1"));
+        assert_eq!(
+            2,
+            parameter_count("SELECT * FROM t WHERE a = $2 AND b = '$3' AND c = $1")
+        );
+    }
+
+    #[test]
+    fn test_is_read_only() {
+        assert!(is_read_only("SELECT id FROM contacts"));
+        assert!(is_read_only(" with t AS (SELECT 1) SELECT * FROM t"));
+        assert!(is_read_only("SELECT 1; SELECT 2"));
+        assert!(is_read_only("SHOW TABLES"));
+        assert!(!is_read_only("UPDATE contacts SET name = 'a'"));
+        assert!(!is_read_only("INSERT INTO t VALUES (1)"));
+        assert!(!is_read_only("SELECT 1; DELETE FROM contacts"));
+        assert!(!is_read_only("SET search_path = public"));
+        assert!(!is_read_only("EXPLAIN ANALYZE DELETE FROM contacts"));
+        assert!(!is_read_only(""));
+    }
+
+    fn references_of(query: &str) -> TableReferences {
+        let statement = Parser::parse_sql(&PostgreSqlDialect {}, query)
+            .unwrap()
+            .remove(0);
+
+        table_references(&statement)
+    }
+
+    #[test]
+    fn test_table_references() {
+        assert_eq!(
+            TableReferences {
+                tables: vec!["public.users".to_string(), "events".to_string()],
+                complete: true,
+            },
+            references_of(
+                "SELECT * FROM public.users u JOIN (SELECT * FROM events) e ON u.id = e.user_id"
+            )
+        );
+        assert_eq!(
+            vec!["users".to_string(), "admins".to_string()],
+            references_of("SELECT name FROM users UNION SELECT name FROM admins").tables
+        );
+
+        let references =
+            references_of("SELECT * FROM users WHERE id IN (SELECT user_id FROM secrets)");
+        assert_eq!(vec!["users".to_string()], references.tables);
+        assert!(!references.complete);
+
+        let references = references_of("WITH recent AS (SELECT * FROM users) SELECT * FROM recent");
+        assert_eq!(
+            vec!["users".to_string(), "recent".to_string()],
+            references.tables
+        );
+        assert!(!references.complete);
+
+        assert_eq!(
+            TableReferences::default(),
+            references_of("DELETE FROM users")
+        );
+        assert_eq!("users", unqualified_table_name("public.\"Users\""));
+    }
+}
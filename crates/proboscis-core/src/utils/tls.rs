@@ -0,0 +1,101 @@
+use crate::ProboscisError;
+use native_tls::Identity;
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+use tracing::{info, warn};
+
+pub type TlsAcceptor = tokio_native_tls::TlsAcceptor;
+pub type TlsStream = tokio_native_tls::TlsStream<tokio::net::TcpStream>;
+
+/// A tls acceptor for a pkcs12 identity file, which is reloaded once the file changes.
+///
+/// Renewed certificates, e.g. written by an ACME client, are picked up by the next
+/// connection without restarting the proxy.
+pub struct ReloadingTlsAcceptor {
+    path: PathBuf,
+    password: String,
+    modified: Option<SystemTime>,
+    acceptor: TlsAcceptor,
+}
+
+fn load_acceptor(path: &Path, password: &str) -> Result<TlsAcceptor, ProboscisError> {
+    let identity = fs::read(path)?;
+    let certificate = Identity::from_pkcs12(&identity, password)?;
+
+    Ok(TlsAcceptor::from(
+        native_tls::TlsAcceptor::builder(certificate).build()?,
+    ))
+}
+
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path)
+        .and_then(|metadata| metadata.modified())
+        .ok()
+}
+
+impl ReloadingTlsAcceptor {
+    pub fn load(path: &str, password: &str) -> Result<ReloadingTlsAcceptor, ProboscisError> {
+        let path = PathBuf::from(path);
+
+        Ok(ReloadingTlsAcceptor {
+            modified: modified(&path),
+            acceptor: load_acceptor(&path, password)?,
+            password: password.to_string(),
+            path,
+        })
+    }
+
+    /// Returns the acceptor for the current identity file
+    pub fn acceptor(&mut self) -> TlsAcceptor {
+        let modified = modified(&self.path);
+
+        if modified != self.modified {
+            match load_acceptor(&self.path, &self.password) {
+                Ok(acceptor) => {
+                    info!(path = ?self.path, "reloaded tls identity");
+                    self.acceptor = acceptor;
+                    self.modified = modified;
+                }
+                // The file may be incomplete while it is written, the previous
+                // identity is used until it can be loaded
+                Err(err) => warn!(path = ?self.path, error = %err, "couldn't reload tls identity"),
+            }
+        }
+
+        self.acceptor.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_reloads_modified_identity() {
+        let identity = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("../../examples/resources/openssl/identity.p12");
+        let path =
+            std::env::temp_dir().join(format!("proboscis-tls-test-{}.p12", std::process::id()));
+        fs::copy(&identity, &path).unwrap();
+
+        let mut acceptor = ReloadingTlsAcceptor::load(path.to_str().unwrap(), "password").unwrap();
+        let loaded = acceptor.modified;
+
+        // A broken identity keeps the previous one
+        std::thread::sleep(Duration::from_millis(10));
+        fs::write(&path, b"incomplete").unwrap();
+        acceptor.acceptor();
+        assert_eq!(loaded, acceptor.modified);
+
+        std::thread::sleep(Duration::from_millis(10));
+        fs::copy(&identity, &path).unwrap();
+        acceptor.acceptor();
+        assert_ne!(loaded, acceptor.modified);
+
+        fs::remove_file(&path).unwrap();
+    }
+}
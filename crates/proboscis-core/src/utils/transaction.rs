@@ -0,0 +1,245 @@
+use proboscis_postgres_protocol::message::ReadyForQueryTransactionStatus;
+
+/// A statement changing the transaction state of a session
+#[derive(Debug, PartialEq)]
+pub enum TransactionStatement {
+    Begin,
+    Savepoint(String),
+    Release(String),
+    RollbackTo(String),
+    Commit,
+    Rollback,
+    /// Ends the transaction and immediately starts a new one
+    CommitAndChain,
+    RollbackAndChain,
+}
+
+// Unquoted identifiers are case insensitive
+fn identifier(word: &str) -> String {
+    match word
+        .strip_prefix('"')
+        .and_then(|word| word.strip_suffix('"'))
+    {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => word.to_lowercase(),
+    }
+}
+
+/// Recognizes transaction control statements by their keywords, other statements return none
+pub fn parse_transaction_statement(statement: &str) -> Option<TransactionStatement> {
+    let statement = statement.trim().trim_end_matches(';');
+    let words: Vec<&str> = statement.split_whitespace().collect();
+    let keyword = |index: usize| {
+        words
+            .get(index)
+            .map(|word| word.to_uppercase())
+            .unwrap_or_default()
+    };
+    // The name of a savepoint comes last, after the optional SAVEPOINT keyword
+    let savepoint = || words.last().map(|word| identifier(word));
+    let chain =
+        words.len() > 2 && keyword(words.len() - 2) == "AND" && keyword(words.len() - 1) == "CHAIN";
+
+    match keyword(0).as_str() {
+        "BEGIN" => Some(TransactionStatement::Begin),
+        "START" if keyword(1) == "TRANSACTION" => Some(TransactionStatement::Begin),
+        "SAVEPOINT" if words.len() == 2 => savepoint().map(TransactionStatement::Savepoint),
+        "RELEASE" if words.len() > 1 => savepoint().map(TransactionStatement::Release),
+        "ROLLBACK" | "ABORT" if keyword(1) == "TO" || keyword(2) == "TO" => {
+            savepoint().map(TransactionStatement::RollbackTo)
+        }
+        // Two-phase commits finish a prepared transaction, not the one of the session
+        "COMMIT" | "ROLLBACK" if keyword(1) == "PREPARED" => None,
+        "COMMIT" | "END" if chain => Some(TransactionStatement::CommitAndChain),
+        "COMMIT" | "END" => Some(TransactionStatement::Commit),
+        "ROLLBACK" | "ABORT" if chain => Some(TransactionStatement::RollbackAndChain),
+        "ROLLBACK" | "ABORT" => Some(TransactionStatement::Rollback),
+        _ => None,
+    }
+}
+
+/// Splits a query of multiple statements at semicolons outside of literals and identifiers
+pub fn split_statements(query: &str) -> Vec<&str> {
+    let mut statements = vec![];
+    let mut start = 0;
+    let mut quote = None;
+
+    for (index, c) in query.char_indices() {
+        match (quote, c) {
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (Some(open), c) if c == open => quote = None,
+            (None, ';') => {
+                statements.push(&query[start..index]);
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+    statements.push(&query[start..]);
+
+    statements
+        .into_iter()
+        .filter(|statement| !statement.trim().is_empty())
+        .collect()
+}
+
+/// The transaction state of a client's session, including its savepoints,
+/// as the target would report it
+#[derive(Debug, Default, Clone)]
+pub struct TransactionState {
+    in_transaction: bool,
+    failed: bool,
+    // Open savepoints, the most recent last
+    savepoints: Vec<String>,
+}
+
+impl TransactionState {
+    /// Updates the state with the statements of a query which succeeded
+    pub fn apply(&mut self, query: &str) {
+        for statement in split_statements(query) {
+            match parse_transaction_statement(statement) {
+                Some(TransactionStatement::Begin) => self.in_transaction = true,
+                Some(TransactionStatement::Savepoint(name)) if self.in_transaction => {
+                    self.savepoints.push(name)
+                }
+                // Releasing a savepoint releases every savepoint established after it
+                Some(TransactionStatement::Release(name)) => {
+                    if let Some(position) = self.savepoints.iter().rposition(|s| *s == name) {
+                        self.savepoints.truncate(position);
+                    }
+                }
+                // The savepoint stays established and the transaction recovers from errors
+                Some(TransactionStatement::RollbackTo(name)) => {
+                    if let Some(position) = self.savepoints.iter().rposition(|s| *s == name) {
+                        self.savepoints.truncate(position + 1);
+                        self.failed = false;
+                    }
+                }
+                Some(TransactionStatement::Commit) | Some(TransactionStatement::Rollback) => {
+                    *self = TransactionState::default()
+                }
+                Some(TransactionStatement::CommitAndChain)
+                | Some(TransactionStatement::RollbackAndChain) => {
+                    *self = TransactionState {
+                        in_transaction: true,
+                        ..TransactionState::default()
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Marks the transaction as failed after an error, until it is rolled back
+    pub fn fail(&mut self) {
+        if self.in_transaction {
+            self.failed = true;
+        }
+    }
+
+    pub fn savepoints(&self) -> &[String] {
+        &self.savepoints
+    }
+
+    pub fn status(&self) -> ReadyForQueryTransactionStatus {
+        match (self.in_transaction, self.failed) {
+            (false, _) => ReadyForQueryTransactionStatus::NotInTransaction,
+            (true, false) => ReadyForQueryTransactionStatus::InTransaction,
+            (true, true) => ReadyForQueryTransactionStatus::InFailedTransaction,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_transaction_statement() {
+        assert_eq!(
+            Some(TransactionStatement::RollbackTo("a".to_string())),
+            parse_transaction_statement("rollback to savepoint A;")
+        );
+        assert_eq!(
+            Some(TransactionStatement::Release("My Savepoint".to_string())),
+            parse_transaction_statement("RELEASE \"My Savepoint\"")
+        );
+        assert_eq!(
+            Some(TransactionStatement::Rollback),
+            parse_transaction_statement("ROLLBACK")
+        );
+        assert_eq!(
+            Some(TransactionStatement::CommitAndChain),
+            parse_transaction_statement("commit work and chain")
+        );
+        assert_eq!(
+            Some(TransactionStatement::RollbackAndChain),
+            parse_transaction_statement("ABORT AND CHAIN;")
+        );
+        assert_eq!(
+            Some(TransactionStatement::Commit),
+            parse_transaction_statement("COMMIT AND NO CHAIN")
+        );
+        assert_eq!(None, parse_transaction_statement("COMMIT PREPARED 'tx'"));
+        assert_eq!(None, parse_transaction_statement("SELECT 1"));
+    }
+
+    #[test]
+    fn test_savepoints() {
+        let mut state = TransactionState::default();
+        state.apply("BEGIN; SAVEPOINT a; SAVEPOINT b; SAVEPOINT c");
+        assert_eq!(["a", "b", "c"], state.savepoints());
+
+        state.fail();
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InFailedTransaction,
+            state.status()
+        );
+
+        state.apply("ROLLBACK TO b");
+        assert_eq!(["a", "b"], state.savepoints());
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InTransaction,
+            state.status()
+        );
+
+        state.apply("RELEASE SAVEPOINT a");
+        assert!(state.savepoints().is_empty());
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InTransaction,
+            state.status()
+        );
+
+        state.apply("INSERT INTO t VALUES (';'); COMMIT");
+        assert_eq!(
+            ReadyForQueryTransactionStatus::NotInTransaction,
+            state.status()
+        );
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut state = TransactionState::default();
+        state.apply("BEGIN; SAVEPOINT a");
+        state.apply("COMMIT AND CHAIN");
+        assert!(state.savepoints().is_empty());
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InTransaction,
+            state.status()
+        );
+
+        // The chained transaction starts without the failure of the previous one
+        state.fail();
+        state.apply("ROLLBACK AND CHAIN");
+        assert_eq!(
+            ReadyForQueryTransactionStatus::InTransaction,
+            state.status()
+        );
+
+        state.apply("COMMIT AND NO CHAIN");
+        assert_eq!(
+            ReadyForQueryTransactionStatus::NotInTransaction,
+            state.status()
+        );
+    }
+}
@@ -24,6 +24,13 @@ pub enum CharTag {
     CloseComplete,
     NoData,
     PortalSuspended,
+    FlushOrCopyOutResponse,
+    NotificationResponse,
+    NoticeResponse,
+    CopyInResponse,
+    CopyData,
+    CopyDone,
+    CopyFail,
 }
 
 impl From<CharTag> for u8 {
@@ -49,6 +56,13 @@ impl From<CharTag> for u8 {
             CharTag::CloseComplete => b'3',
             CharTag::NoData => b'n',
             CharTag::PortalSuspended => b's',
+            CharTag::FlushOrCopyOutResponse => b'H',
+            CharTag::NotificationResponse => b'A',
+            CharTag::NoticeResponse => b'N',
+            CharTag::CopyInResponse => b'G',
+            CharTag::CopyData => b'd',
+            CharTag::CopyDone => b'c',
+            CharTag::CopyFail => b'f',
         }
     }
 }
@@ -78,6 +92,13 @@ impl TryFrom<u8> for CharTag {
             b'3' => Ok(CharTag::CloseComplete),
             b'n' => Ok(CharTag::NoData),
             b's' => Ok(CharTag::PortalSuspended),
+            b'H' => Ok(CharTag::FlushOrCopyOutResponse),
+            b'A' => Ok(CharTag::NotificationResponse),
+            b'N' => Ok(CharTag::NoticeResponse),
+            b'G' => Ok(CharTag::CopyInResponse),
+            b'd' => Ok(CharTag::CopyData),
+            b'c' => Ok(CharTag::CopyDone),
+            b'f' => Ok(CharTag::CopyFail),
             _ => Err(ParseError::UnknownCharTag {
                 char: value as char,
             }),
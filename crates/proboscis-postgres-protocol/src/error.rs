@@ -11,6 +11,9 @@ pub enum ParseError {
     #[error("unknown char tag: {char}")]
     UnknownCharTag { char: char },
 
+    #[error("unexpected message: {char}")]
+    UnexpectedMessage { char: char },
+
     #[error("invalid describe kind: {char}")]
     InvalidDescribeKind { char: char },
 
@@ -20,6 +23,15 @@ pub enum ParseError {
     #[error("invalid close kind: {tag}")]
     InvalidCloseKind { tag: String },
 
+    #[error("expected a password message, received: {char}")]
+    ExpectedPasswordMessage { char: char },
+
     #[error("invalid bind parameter format")]
     InvalidBindParameterFormat,
+
+    #[error("invalid startup message length: {length}")]
+    InvalidStartupMessageLength { length: u32 },
+
+    #[error("field of {length} bytes exceeds the limit")]
+    FieldTooLarge { length: usize },
 }
@@ -1,6 +1,8 @@
 use crate::ParseError;
 
-use super::util::{read_until_zero, write_message_with_prefixed_message_len};
+use super::util::{
+    encode_message_with_prefixed_message_len, put_cstring, put_fields, read_fields, read_until_zero,
+};
 use super::CharTag;
 use std::convert::TryFrom;
 use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
@@ -74,12 +76,37 @@ pub struct MD5Hash(pub String);
 
 pub struct MD5Salt(pub Vec<u8>);
 
+/// The first message of the client in a SASL exchange, choosing one of the server's mechanisms
+#[derive(Debug, PartialEq, Clone)]
+pub struct SASLInitialResponse {
+    pub mechanism: String,
+    pub data: Option<Vec<u8>>,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct ParameterStatus {
     pub key: String,
     pub value: String,
 }
 
+/// Starts the copy-in or copy-out mode of a `COPY ... FROM STDIN` or `COPY ... TO STDOUT`
+#[derive(Debug, PartialEq, Clone)]
+pub struct CopyResponse {
+    /// 0 for text or csv data, 1 for binary data
+    pub format: i8,
+    /// The format of each column, all 0 for text or csv data
+    pub column_formats: Vec<i16>,
+}
+
+/// A notification on a channel the session listens on, sent by `NOTIFY`
+#[derive(Debug, PartialEq, Clone)]
+pub struct NotificationResponse {
+    /// The process id of the notifying backend
+    pub process_id: u32,
+    pub channel: String,
+    pub payload: String,
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub struct Parse {
     pub statement_name: String,
@@ -129,6 +156,58 @@ pub struct Error {
     pub messages: Vec<(u8, String)>,
 }
 
+impl Error {
+    /// The value of the field with the type, e.g. `b'M'` for the message
+    pub fn field(&self, field_type: u8) -> Option<&str> {
+        self.messages
+            .iter()
+            .find(|(field, _)| *field == field_type)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// The SQLSTATE code of the error, e.g. 42P01 if a table doesn't exist
+    pub fn code(&self) -> Option<&str> {
+        self.field(b'C')
+    }
+
+    /// The severity, e.g. `ERROR`, `FATAL` or `WARNING` for notices
+    pub fn severity(&self) -> Option<&str> {
+        self.field(b'S')
+    }
+
+    pub fn message(&self) -> Option<&str> {
+        self.field(b'M')
+    }
+
+    /// The secondary message, with more details of the problem
+    pub fn detail(&self) -> Option<&str> {
+        self.field(b'D')
+    }
+
+    /// Sets the field, replacing an earlier value
+    pub fn with_field(mut self, field_type: u8, value: String) -> Self {
+        match self
+            .messages
+            .iter_mut()
+            .find(|(field, _)| *field == field_type)
+        {
+            Some((_, existing)) => *existing = value,
+            None => self.messages.push((field_type, value)),
+        }
+        self
+    }
+
+    pub fn with_detail(self, detail: String) -> Self {
+        self.with_field(b'D', detail)
+    }
+
+    /// Sets the localized and the non-localized severity
+    pub fn with_severity(self, severity: &str) -> Self {
+        self.with_field(b'S', severity.to_string())
+            .with_field(b'V', severity.to_string())
+    }
+}
+
 #[derive(Debug, PartialEq, Clone)]
 pub enum ReadyForQueryTransactionStatus {
     NotInTransaction,
@@ -164,6 +243,10 @@ impl From<ReadyForQueryTransactionStatus> for u8 {
 #[derive(Debug, PartialEq, Clone)]
 pub enum BackendMessage {
     AuthenticationRequestMD5Password(MD5Salt),
+    /// Requests a SASL exchange with one of the listed mechanisms, e.g. `SCRAM-SHA-256`
+    AuthenticationSASL(Vec<String>),
+    AuthenticationSASLContinue(Vec<u8>),
+    AuthenticationSASLFinal(Vec<u8>),
     AuthenticationOk,
     ReadyForQuery(ReadyForQueryTransactionStatus),
     ParameterStatus(ParameterStatus),
@@ -179,11 +262,23 @@ pub enum BackendMessage {
     NoData,
     EmptyQueryResponse,
     PortalSuspended,
+    NotificationResponse(NotificationResponse),
+    /// A warning or other message that doesn't end the request, with the fields of an error
+    NoticeResponse(Error),
+    CopyInResponse(CopyResponse),
+    CopyOutResponse(CopyResponse),
+    /// A chunk of the data of a `COPY ... TO STDOUT`, postgres sends one per row
+    CopyData(Vec<u8>),
+    CopyDone,
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum FrontendMessage {
     MD5HashedPassword(MD5Hash),
+    /// Password messages can't be told apart by their tag,
+    /// these are read with `read_sasl_initial_response` and `read_sasl_response`
+    SASLInitialResponse(SASLInitialResponse),
+    SASLResponse(Vec<u8>),
     SimpleQuery(String),
     Terminate,
     Parse(Parse),
@@ -192,6 +287,12 @@ pub enum FrontendMessage {
     Execute(Execute),
     Close(Close),
     Sync,
+    Flush,
+    /// A chunk of the data of a `COPY ... FROM STDIN`, not necessarily aligned with rows
+    CopyData(Vec<u8>),
+    CopyDone,
+    /// Aborts a `COPY ... FROM STDIN` with the reason
+    CopyFail(String),
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -201,6 +302,14 @@ pub enum Message {
 }
 
 impl Message {
+    /// Appends the encoded message to the buffer, returning the number of bytes appended
+    pub fn encode(&self, buf: &mut Vec<u8>) -> usize {
+        match self {
+            Self::Backend(message) => message.encode(buf),
+            Self::Frontend(message) => message.encode(buf),
+        }
+    }
+
     pub async fn write<T: AsyncWrite + std::marker::Unpin>(
         self,
         buf: &mut T,
@@ -224,6 +333,31 @@ impl From<BackendMessage> for Message {
     }
 }
 
+fn put_copy_response(body: &mut Vec<u8>, response: &CopyResponse) {
+    body.push(response.format as u8);
+    body.extend_from_slice(&(response.column_formats.len() as i16).to_be_bytes());
+    for format in &response.column_formats {
+        body.extend_from_slice(&format.to_be_bytes());
+    }
+}
+
+async fn read_copy_response<T: AsyncRead + Unpin>(
+    stream: &mut T,
+) -> Result<CopyResponse, ParseError> {
+    let format = AsyncReadExt::read_i8(stream).await?;
+
+    let mut column_formats = vec![];
+    let num_columns: u16 = AsyncReadExt::read_u16(stream).await?;
+    while column_formats.len() < num_columns as usize {
+        column_formats.push(AsyncReadExt::read_i16(stream).await?);
+    }
+
+    Ok(CopyResponse {
+        format,
+        column_formats,
+    })
+}
+
 async fn read_meta_async<T: AsyncRead + Unpin>(
     stream: &mut T,
 ) -> Result<(CharTag, u32), ParseError> {
@@ -234,133 +368,151 @@ async fn read_meta_async<T: AsyncRead + Unpin>(
 }
 
 impl FrontendMessage {
-    pub async fn write<T: AsyncWrite + std::marker::Unpin>(
-        self,
-        buf: &mut T,
-    ) -> tokio::io::Result<usize> {
+    /// Appends the encoded message to the buffer, returning the number of bytes appended
+    pub fn encode(&self, buf: &mut Vec<u8>) -> usize {
         match self {
             Self::MD5HashedPassword(MD5Hash(hash)) => {
-                let mut body = vec![];
-                body.extend_from_slice(hash.as_bytes());
-                body.push(0);
+                encode_message_with_prefixed_message_len(buf, CharTag::Password, |body| {
+                    put_cstring(body, hash);
+                })
+            }
+            Self::SASLInitialResponse(SASLInitialResponse { mechanism, data }) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::Password, |body| {
+                    put_cstring(body, mechanism);
 
-                write_message_with_prefixed_message_len(buf, CharTag::Password, &body).await
+                    match data {
+                        Some(data) => {
+                            body.extend_from_slice(&(data.len() as i32).to_be_bytes());
+                            body.extend_from_slice(data);
+                        }
+                        None => body.extend_from_slice(&(-1_i32).to_be_bytes()),
+                    }
+                })
+            }
+            Self::SASLResponse(data) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::Password, |body| {
+                    body.extend_from_slice(data);
+                })
             }
             Self::SimpleQuery(query) => {
-                let mut body = vec![];
-                body.extend_from_slice(query.as_bytes());
-                body.push(0);
-
-                write_message_with_prefixed_message_len(buf, CharTag::Query, &body).await
+                encode_message_with_prefixed_message_len(buf, CharTag::Query, |body| {
+                    put_cstring(body, query);
+                })
             }
             Self::Terminate => {
-                let mut body = vec![];
-                body.write_i32(0).await?;
-
-                write_message_with_prefixed_message_len(buf, CharTag::Terminate, &body).await
+                encode_message_with_prefixed_message_len(buf, CharTag::Terminate, |body| {
+                    body.extend_from_slice(&0_i32.to_be_bytes());
+                })
             }
-
             Self::Parse(Parse {
                 statement_name: statement,
                 query,
                 param_types,
-            }) => {
-                let mut body = vec![];
-
-                body.extend_from_slice(statement.as_bytes());
-                body.push(0);
-
-                body.extend_from_slice(query.as_bytes());
-                body.push(0);
-
-                body.write_i16(param_types.clone().len() as i16).await?;
+            }) => encode_message_with_prefixed_message_len(buf, CharTag::Parse, |body| {
+                put_cstring(body, statement);
+                put_cstring(body, query);
 
-                for param in &param_types {
-                    body.write_u32(*param).await?;
+                body.extend_from_slice(&(param_types.len() as i16).to_be_bytes());
+                for param in param_types {
+                    body.extend_from_slice(&param.to_be_bytes());
                 }
-
-                write_message_with_prefixed_message_len(buf, CharTag::Parse, &body).await
-            }
+            }),
             Self::Describe(Describe { name, kind }) => {
-                let mut body = vec![kind.into()];
-
-                body.extend_from_slice(name.as_bytes());
-                body.push(0);
-
-                write_message_with_prefixed_message_len(buf, CharTag::DataRowOrDescribe, &body)
-                    .await
+                encode_message_with_prefixed_message_len(buf, CharTag::DataRowOrDescribe, |body| {
+                    body.push((*kind).into());
+                    put_cstring(body, name);
+                })
             }
             Self::Execute(Execute { portal, row_limit }) => {
-                let mut body = vec![];
-
-                body.extend_from_slice(portal.as_bytes());
-                body.push(0);
-
-                body.write_i32(row_limit).await?;
-
-                write_message_with_prefixed_message_len(buf, CharTag::ExecuteOrError, &body).await
+                encode_message_with_prefixed_message_len(buf, CharTag::ExecuteOrError, |body| {
+                    put_cstring(body, portal);
+                    body.extend_from_slice(&row_limit.to_be_bytes());
+                })
             }
-            Self::Sync => {
-                write_message_with_prefixed_message_len(buf, CharTag::ParameterStatusOrSync, &[])
-                    .await
+            Self::Sync => encode_message_with_prefixed_message_len(
+                buf,
+                CharTag::ParameterStatusOrSync,
+                |_| {},
+            ),
+            Self::Flush => encode_message_with_prefixed_message_len(
+                buf,
+                CharTag::FlushOrCopyOutResponse,
+                |_| {},
+            ),
+            Self::CopyData(data) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::CopyData, |body| {
+                    body.extend_from_slice(data);
+                })
+            }
+            Self::CopyDone => {
+                encode_message_with_prefixed_message_len(buf, CharTag::CopyDone, |_| {})
+            }
+            Self::CopyFail(message) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::CopyFail, |body| {
+                    put_cstring(body, message);
+                })
             }
             Self::Bind(Bind {
                 portal,
                 statement,
                 params,
                 results,
-            }) => {
-                let mut body = vec![];
-
-                body.extend_from_slice(portal.as_bytes());
-                body.push(0);
-
-                body.extend_from_slice(statement.as_bytes());
-                body.push(0);
+            }) => encode_message_with_prefixed_message_len(buf, CharTag::Bind, |body| {
+                put_cstring(body, portal);
+                put_cstring(body, statement);
 
                 let are_all_parameters_text =
                     params.iter().all(|p| matches!(p, BindParameter::Text(_)));
 
                 if params.is_empty() || are_all_parameters_text {
-                    body.write_i16(0_i16).await?;
+                    body.extend_from_slice(&0_i16.to_be_bytes());
                 } else {
-                    body.write_i16(params.len() as i16).await?;
-                    for param in &params {
-                        body.write_i16(match param {
-                            BindParameter::Text(_) => 0_i16,
-                            BindParameter::Binary(_) => 1_i16,
-                        })
-                        .await?;
+                    body.extend_from_slice(&(params.len() as i16).to_be_bytes());
+                    for param in params {
+                        let format: i16 = match param {
+                            BindParameter::Text(_) => 0,
+                            BindParameter::Binary(_) => 1,
+                        };
+                        body.extend_from_slice(&format.to_be_bytes());
                     }
                 };
 
-                body.write_i16(params.len() as i16).await?;
-                for param in &params {
+                body.extend_from_slice(&(params.len() as i16).to_be_bytes());
+                for param in params {
                     let bytes = match param {
                         BindParameter::Text(string) => string.as_bytes(),
                         BindParameter::Binary(data) => data,
                     };
 
-                    body.write_i32(bytes.len() as i32).await?;
+                    body.extend_from_slice(&(bytes.len() as i32).to_be_bytes());
                     body.extend_from_slice(bytes);
                 }
 
-                body.write_i16(results.len() as i16).await?;
-                for result_format in &results {
-                    body.write_i16(*result_format).await?;
+                body.extend_from_slice(&(results.len() as i16).to_be_bytes());
+                for result_format in results {
+                    body.extend_from_slice(&result_format.to_be_bytes());
                 }
+            }),
+            Self::Close(Close { kind, name }) => encode_message_with_prefixed_message_len(
+                buf,
+                CharTag::CommandCompleteOrClose,
+                |body| {
+                    body.push((*kind).into());
+                    body.extend_from_slice(name.as_bytes());
+                },
+            ),
+        }
+    }
 
-                write_message_with_prefixed_message_len(buf, CharTag::Bind, &body).await
-            }
-            Self::Close(Close { kind, name }) => {
-                let mut body = vec![kind.into()];
-
-                body.extend_from_slice(name.as_bytes());
+    pub async fn write<T: AsyncWrite + std::marker::Unpin>(
+        self,
+        buf: &mut T,
+    ) -> tokio::io::Result<usize> {
+        let mut bytes = vec![];
+        let written_bytes_count = self.encode(&mut bytes);
+        buf.write_all(&bytes).await?;
 
-                write_message_with_prefixed_message_len(buf, CharTag::CommandCompleteOrClose, &body)
-                    .await
-            }
-        }
+        Ok(written_bytes_count)
     }
 
     pub async fn read<T: AsyncRead + Unpin>(stream: &mut T) -> Result<Self, ParseError> {
@@ -368,6 +520,49 @@ impl FrontendMessage {
         Self::read_body(stream, tag, message_length - 4).await
     }
 
+    async fn read_password_message<T: AsyncRead + Unpin>(
+        stream: &mut T,
+    ) -> Result<Vec<u8>, ParseError> {
+        let (tag, message_length) = read_meta_async(stream).await?;
+        if tag != CharTag::Password {
+            return Err(ParseError::ExpectedPasswordMessage {
+                char: u8::from(tag) as char,
+            });
+        }
+
+        let mut bytes = vec![0; message_length as usize - 4];
+        stream.read_exact(&mut bytes).await?;
+        Ok(bytes)
+    }
+
+    /// Reads the response of the client to an `AuthenticationSASL` request
+    pub async fn read_sasl_initial_response<T: AsyncRead + Unpin>(
+        stream: &mut T,
+    ) -> Result<Self, ParseError> {
+        let bytes = Self::read_password_message(stream).await?;
+        let mut cursor = std::io::Cursor::new(&bytes[..]);
+
+        let mechanism = String::from_utf8(read_until_zero(&mut cursor).await?)?;
+        let data = match AsyncReadExt::read_i32(&mut cursor).await? {
+            -1 => None,
+            _ => Some(bytes[cursor.position() as usize..].to_vec()),
+        };
+
+        Ok(Self::SASLInitialResponse(SASLInitialResponse {
+            mechanism,
+            data,
+        }))
+    }
+
+    /// Reads the response of the client to an `AuthenticationSASLContinue` request
+    pub async fn read_sasl_response<T: AsyncRead + Unpin>(
+        stream: &mut T,
+    ) -> Result<Self, ParseError> {
+        Ok(Self::SASLResponse(
+            Self::read_password_message(stream).await?,
+        ))
+    }
+
     async fn read_body<T: AsyncRead + Unpin>(
         stream: &mut T,
         tag: CharTag,
@@ -387,6 +582,17 @@ impl FrontendMessage {
                 Ok(Self::MD5HashedPassword(MD5Hash(hash)))
             }
             CharTag::ParameterStatusOrSync => Ok(Self::Sync),
+            CharTag::FlushOrCopyOutResponse => Ok(Self::Flush),
+            CharTag::CopyData => {
+                let mut data = vec![0; remaining_bytes_len as usize];
+                stream.read_exact(&mut data).await?;
+
+                Ok(Self::CopyData(data))
+            }
+            CharTag::CopyDone => Ok(Self::CopyDone),
+            CharTag::CopyFail => Ok(Self::CopyFail(String::from_utf8(
+                read_until_zero(stream).await?,
+            )?)),
             CharTag::DataRowOrDescribe => {
                 let mut bytes: Vec<u8> = vec![0; remaining_bytes_len as usize];
                 bytes = stream.read_exact(&mut bytes).await.map(|_| bytes)?;
@@ -506,148 +712,250 @@ impl FrontendMessage {
                     results,
                 }))
             }
-            _ => todo!(),
+            // Like a backend message, or a message sent outside the exchange it belongs to
+            tag => Err(ParseError::UnexpectedMessage {
+                char: u8::from(tag) as char,
+            }),
         }
     }
 }
 
 impl BackendMessage {
-    pub async fn write<T: AsyncWrite + std::marker::Unpin>(
-        self,
-        buf: &mut T,
-    ) -> tokio::io::Result<usize> {
+    /// Appends the encoded message to the buffer, returning the number of bytes appended
+    pub fn encode(&self, buf: &mut Vec<u8>) -> usize {
         match self {
             Self::AuthenticationOk => {
-                let vec = vec![CharTag::Authentication.into(), 0, 0, 0, 8, 0, 0, 0, 0];
-                buf.write(&vec[..]).await
+                encode_message_with_prefixed_message_len(buf, CharTag::Authentication, |body| {
+                    body.extend_from_slice(&0_i32.to_be_bytes());
+                })
             }
             Self::ReadyForQuery(status) => {
-                write_message_with_prefixed_message_len(
-                    buf,
-                    CharTag::ReadyForQuery,
-                    &[status.into()],
-                )
-                .await
+                encode_message_with_prefixed_message_len(buf, CharTag::ReadyForQuery, |body| {
+                    body.push(status.clone().into());
+                })
             }
             Self::AuthenticationRequestMD5Password(MD5Salt(salt)) => {
-                let mut body = vec![];
-                body.write_i32(5_i32).await?;
-                body.write_all(&salt[..]).await?;
-
-                write_message_with_prefixed_message_len(buf, CharTag::Authentication, &body).await
+                encode_message_with_prefixed_message_len(buf, CharTag::Authentication, |body| {
+                    body.extend_from_slice(&5_i32.to_be_bytes());
+                    body.extend_from_slice(salt);
+                })
             }
-            Self::RowDescription(RowDescription { fields }) => {
-                let mut body = vec![];
-
-                body.write_i16(fields.len() as i16).await?;
-
-                for field in &fields {
-                    body.write_all(field.name.as_bytes()).await?;
+            Self::AuthenticationSASL(mechanisms) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::Authentication, |body| {
+                    body.extend_from_slice(&10_i32.to_be_bytes());
+                    for mechanism in mechanisms {
+                        put_cstring(body, mechanism);
+                    }
                     body.push(0);
-
-                    body.write_i32(field.table_oid).await?;
-                    body.write_i16(field.column_number).await?;
-                    body.write_u32(field.type_oid).await?;
-                    body.write_i16(field.type_length).await?;
-                    body.write_i32(field.type_modifier).await?;
-                    body.write_i16(field.format).await?;
-                }
-
-                write_message_with_prefixed_message_len(buf, CharTag::RowDescription, &body).await
+                })
+            }
+            Self::AuthenticationSASLContinue(data) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::Authentication, |body| {
+                    body.extend_from_slice(&11_i32.to_be_bytes());
+                    body.extend_from_slice(data);
+                })
+            }
+            Self::AuthenticationSASLFinal(data) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::Authentication, |body| {
+                    body.extend_from_slice(&12_i32.to_be_bytes());
+                    body.extend_from_slice(data);
+                })
+            }
+            Self::RowDescription(RowDescription { fields }) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::RowDescription, |body| {
+                    body.extend_from_slice(&(fields.len() as i16).to_be_bytes());
+
+                    for field in fields {
+                        put_cstring(body, &field.name);
+                        body.extend_from_slice(&field.table_oid.to_be_bytes());
+                        body.extend_from_slice(&field.column_number.to_be_bytes());
+                        body.extend_from_slice(&field.type_oid.to_be_bytes());
+                        body.extend_from_slice(&field.type_length.to_be_bytes());
+                        body.extend_from_slice(&field.type_modifier.to_be_bytes());
+                        body.extend_from_slice(&field.format.to_be_bytes());
+                    }
+                })
             }
             Self::DataRow(DataRow { field_data }) => {
-                let mut body = vec![];
-
-                body.write_i16(field_data.len() as i16).await?;
-
-                for data in &field_data {
-                    match data {
-                        Some(data) => {
-                            body.write_i32(data.len() as i32).await?;
-                            body.extend_from_slice(&data[..]);
-                        }
-                        None => {
-                            body.write_i32(-1_i32).await?;
+                encode_message_with_prefixed_message_len(buf, CharTag::DataRowOrDescribe, |body| {
+                    body.extend_from_slice(&(field_data.len() as i16).to_be_bytes());
+
+                    for data in field_data {
+                        match data {
+                            Some(data) => {
+                                body.extend_from_slice(&(data.len() as i32).to_be_bytes());
+                                body.extend_from_slice(data);
+                            }
+                            None => body.extend_from_slice(&(-1_i32).to_be_bytes()),
                         }
                     }
-                }
-
-                write_message_with_prefixed_message_len(buf, CharTag::DataRowOrDescribe, &body)
-                    .await
+                })
             }
             Self::CommandComplete(CommandCompleteTag(tag)) => {
-                let mut body = vec![];
-                body.extend_from_slice(tag.as_bytes());
-                body.push(0);
-
-                write_message_with_prefixed_message_len(buf, CharTag::CommandCompleteOrClose, &body)
-                    .await
+                encode_message_with_prefixed_message_len(
+                    buf,
+                    CharTag::CommandCompleteOrClose,
+                    |body| put_cstring(body, tag),
+                )
             }
             Self::ParameterStatus(ParameterStatus { key, value }) => {
-                let mut body = vec![];
-
-                body.extend_from_slice(key.as_bytes());
-                body.push(0);
-
-                body.extend_from_slice(value.as_bytes());
-                body.push(0);
-
-                write_message_with_prefixed_message_len(buf, CharTag::ParameterStatusOrSync, &body)
-                    .await
+                encode_message_with_prefixed_message_len(
+                    buf,
+                    CharTag::ParameterStatusOrSync,
+                    |body| {
+                        put_cstring(body, key);
+                        put_cstring(body, value);
+                    },
+                )
             }
             Self::BackendKeyData(BackendKeyData {
                 process_id,
                 secret_key,
                 additional,
-            }) => {
-                let mut body = vec![];
-                body.write_i32(process_id as i32).await?;
-                body.write_i32(secret_key as i32).await?;
-                body.extend_from_slice(&additional[..]);
-
-                write_message_with_prefixed_message_len(buf, CharTag::BackendKeyData, &body).await
-            }
+            }) => encode_message_with_prefixed_message_len(buf, CharTag::BackendKeyData, |body| {
+                body.extend_from_slice(&(*process_id as i32).to_be_bytes());
+                body.extend_from_slice(&(*secret_key as i32).to_be_bytes());
+                body.extend_from_slice(additional);
+            }),
             Self::ParseComplete => {
-                write_message_with_prefixed_message_len(buf, CharTag::ParseComplete, &[]).await
+                encode_message_with_prefixed_message_len(buf, CharTag::ParseComplete, |_| {})
             }
             Self::BindComplete => {
-                write_message_with_prefixed_message_len(buf, CharTag::BindComplete, &[]).await
+                encode_message_with_prefixed_message_len(buf, CharTag::BindComplete, |_| {})
             }
             Self::ParameterDescription(ParameterDescription { types }) => {
-                let mut body = vec![];
-
-                body.write_i16((&types).len() as i16).await?;
-
-                for param in &types {
-                    body.write_u32(*param).await?;
-                }
-
-                write_message_with_prefixed_message_len(buf, CharTag::ParameterDescription, &body)
-                    .await
+                encode_message_with_prefixed_message_len(
+                    buf,
+                    CharTag::ParameterDescription,
+                    |body| {
+                        body.extend_from_slice(&(types.len() as i16).to_be_bytes());
+                        for param in types {
+                            body.extend_from_slice(&param.to_be_bytes());
+                        }
+                    },
+                )
             }
             Self::CloseComplete => {
-                write_message_with_prefixed_message_len(buf, CharTag::CloseComplete, &[]).await
-            }
-            Self::NoData => {
-                write_message_with_prefixed_message_len(buf, CharTag::NoData, &[]).await
+                encode_message_with_prefixed_message_len(buf, CharTag::CloseComplete, |_| {})
             }
+            Self::NoData => encode_message_with_prefixed_message_len(buf, CharTag::NoData, |_| {}),
             Self::EmptyQueryResponse => {
-                write_message_with_prefixed_message_len(buf, CharTag::EmptyQueryResponse, &[]).await
+                encode_message_with_prefixed_message_len(buf, CharTag::EmptyQueryResponse, |_| {})
             }
             Self::PortalSuspended => {
-                write_message_with_prefixed_message_len(buf, CharTag::PortalSuspended, &[]).await
+                encode_message_with_prefixed_message_len(buf, CharTag::PortalSuspended, |_| {})
+            }
+            Self::NotificationResponse(NotificationResponse {
+                process_id,
+                channel,
+                payload,
+            }) => encode_message_with_prefixed_message_len(
+                buf,
+                CharTag::NotificationResponse,
+                |body| {
+                    body.extend_from_slice(&(*process_id as i32).to_be_bytes());
+                    put_cstring(body, channel);
+                    put_cstring(body, payload);
+                },
+            ),
+            Self::Error(Error { messages }) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::ExecuteOrError, |body| {
+                    put_fields(body, messages)
+                })
+            }
+            Self::NoticeResponse(Error { messages }) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::NoticeResponse, |body| {
+                    put_fields(body, messages)
+                })
             }
-            Self::Error(_) => {
-                unimplemented!()
+            Self::CopyInResponse(response) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::CopyInResponse, |body| {
+                    put_copy_response(body, response)
+                })
+            }
+            Self::CopyOutResponse(response) => encode_message_with_prefixed_message_len(
+                buf,
+                CharTag::FlushOrCopyOutResponse,
+                |body| put_copy_response(body, response),
+            ),
+            Self::CopyData(data) => {
+                encode_message_with_prefixed_message_len(buf, CharTag::CopyData, |body| {
+                    body.extend_from_slice(data);
+                })
+            }
+            Self::CopyDone => {
+                encode_message_with_prefixed_message_len(buf, CharTag::CopyDone, |_| {})
             }
         }
     }
 
+    pub async fn write<T: AsyncWrite + std::marker::Unpin>(
+        self,
+        buf: &mut T,
+    ) -> tokio::io::Result<usize> {
+        let mut bytes = vec![];
+        let written_bytes_count = self.encode(&mut bytes);
+        buf.write_all(&bytes).await?;
+
+        Ok(written_bytes_count)
+    }
+
     pub async fn read<T: AsyncRead + Unpin>(stream: &mut T) -> Result<Self, ParseError> {
         let (tag, message_length) = read_meta_async(stream).await?;
         Self::read_body(stream, tag, message_length - 4).await
     }
 
+    /// Like `read`, but fields of data rows longer than the limit are skipped instead of being
+    /// buffered. The rest of the message is still read, before failing with `FieldTooLarge`.
+    pub async fn read_with_field_limit<T: AsyncRead + Unpin>(
+        stream: &mut T,
+        field_limit: usize,
+    ) -> Result<Self, ParseError> {
+        let (tag, message_length) = read_meta_async(stream).await?;
+        match tag {
+            CharTag::DataRowOrDescribe => Self::read_data_row(stream, Some(field_limit)).await,
+            _ => Self::read_body(stream, tag, message_length - 4).await,
+        }
+    }
+
+    async fn read_data_row<T: AsyncRead + Unpin>(
+        stream: &mut T,
+        field_limit: Option<usize>,
+    ) -> Result<Self, ParseError> {
+        let num_fields: u16 = AsyncReadExt::read_u16(stream).await?;
+
+        let mut field_data = vec![];
+        let mut too_large = None;
+        for _ in 0..num_fields {
+            let field_len: i32 = AsyncReadExt::read_i32(stream).await?;
+
+            let field_bytes = match field_len {
+                -1 => None,
+                _ if field_limit.map_or(false, |limit| field_len as usize > limit) => {
+                    tokio::io::copy(
+                        &mut (&mut *stream).take(field_len as u64),
+                        &mut tokio::io::sink(),
+                    )
+                    .await?;
+                    too_large = Some(field_len as usize);
+                    None
+                }
+                _ => {
+                    let mut field_bytes = vec![0; field_len as usize];
+                    stream.read_exact(&mut field_bytes).await?;
+                    Some(field_bytes)
+                }
+            };
+
+            field_data.push(field_bytes)
+        }
+
+        match too_large {
+            Some(length) => Err(ParseError::FieldTooLarge { length }),
+            None => Ok(Self::DataRow(DataRow { field_data })),
+        }
+    }
+
     async fn read_body<T: AsyncRead + Unpin>(
         stream: &mut T,
         tag: CharTag,
@@ -667,6 +975,27 @@ impl BackendMessage {
                     return Ok(Self::AuthenticationOk);
                 }
 
+                let mut bytes = vec![0_u8; remaining_bytes_len as usize - 4];
+                bytes = stream.read_exact(&mut bytes).await.map(|_| bytes)?;
+
+                if method == 10 {
+                    // The list of mechanisms ends with an empty one
+                    let mechanisms = bytes
+                        .split(|byte| *byte == 0)
+                        .take_while(|mechanism| !mechanism.is_empty())
+                        .map(|mechanism| String::from_utf8(mechanism.to_vec()))
+                        .collect::<Result<Vec<String>, _>>()?;
+                    return Ok(Self::AuthenticationSASL(mechanisms));
+                }
+
+                if method == 11 {
+                    return Ok(Self::AuthenticationSASLContinue(bytes));
+                }
+
+                if method == 12 {
+                    return Ok(Self::AuthenticationSASLFinal(bytes));
+                }
+
                 unimplemented!();
             }
             CharTag::ParameterStatusOrSync => {
@@ -729,47 +1058,19 @@ impl BackendMessage {
 
                 Ok(Self::RowDescription(RowDescription { fields }))
             }
-            CharTag::DataRowOrDescribe => {
-                let num_fields: u16 = AsyncReadExt::read_u16(stream).await?;
-
-                let mut field_data = vec![];
-                for _ in 0..num_fields {
-                    let field_len: i32 = AsyncReadExt::read_i32(stream).await?;
-
-                    let field_bytes = if field_len != -1 {
-                        let mut field_bytes = vec![0; field_len as usize];
-                        stream.read_exact(&mut field_bytes).await?;
-                        Some(field_bytes)
-                    } else {
-                        None
-                    };
-
-                    field_data.push(field_bytes)
-                }
-
-                Ok(Self::DataRow(DataRow { field_data }))
-            }
+            CharTag::DataRowOrDescribe => Self::read_data_row(stream, None).await,
             CharTag::CommandCompleteOrClose => {
                 let tag_bytes = read_until_zero(stream).await?;
                 let tag = String::from_utf8(tag_bytes)?;
 
                 Ok(Self::CommandComplete(CommandCompleteTag(tag)))
             }
-            CharTag::ExecuteOrError => {
-                let mut messages = vec![];
-                while let Ok(identifier) = AsyncReadExt::read_u8(stream).await {
-                    match identifier {
-                        0 => break,
-                        _ => {
-                            let message_bytes = read_until_zero(stream).await?;
-                            let message = String::from_utf8(message_bytes)?;
-                            messages.push((identifier, message))
-                        }
-                    }
-                }
-
-                Ok(Self::Error(Error { messages }))
-            }
+            CharTag::ExecuteOrError => Ok(Self::Error(Error {
+                messages: read_fields(stream).await?,
+            })),
+            CharTag::NoticeResponse => Ok(Self::NoticeResponse(Error {
+                messages: read_fields(stream).await?,
+            })),
             CharTag::ParseComplete => Ok(Self::ParseComplete),
             CharTag::BindComplete => Ok(Self::BindComplete),
             CharTag::ParameterDescription => {
@@ -788,7 +1089,31 @@ impl BackendMessage {
             CharTag::EmptyQueryResponse => Ok(Self::EmptyQueryResponse),
             CharTag::PortalSuspended => Ok(Self::PortalSuspended),
             CharTag::NoData => Ok(Self::NoData),
-            _ => todo!(),
+            CharTag::CopyInResponse => Ok(Self::CopyInResponse(read_copy_response(stream).await?)),
+            CharTag::FlushOrCopyOutResponse => {
+                Ok(Self::CopyOutResponse(read_copy_response(stream).await?))
+            }
+            CharTag::CopyData => {
+                let mut data = vec![0; remaining_bytes_len as usize];
+                stream.read_exact(&mut data).await?;
+
+                Ok(Self::CopyData(data))
+            }
+            CharTag::CopyDone => Ok(Self::CopyDone),
+            CharTag::NotificationResponse => {
+                let process_id = AsyncReadExt::read_u32(stream).await?;
+                let channel = String::from_utf8(read_until_zero(stream).await?)?;
+                let payload = String::from_utf8(read_until_zero(stream).await?)?;
+
+                Ok(Self::NotificationResponse(NotificationResponse {
+                    process_id,
+                    channel,
+                    payload,
+                }))
+            }
+            tag => Err(ParseError::UnexpectedMessage {
+                char: u8::from(tag) as char,
+            }),
         }
     }
 }
@@ -839,6 +1164,110 @@ mod tests {
         test_backend_symmetric_serialization_deserialization(message.into());
     }
 
+    #[test]
+    fn data_row_field_limit() {
+        let mut buf = vec![];
+        let mut cursor = std::io::Cursor::new(&mut buf);
+        for field in [vec![0; 16], vec![0; 4]] {
+            let message: Message = BackendMessage::DataRow(DataRow {
+                field_data: vec![Some(vec![1, 2]), Some(field)],
+            })
+            .into();
+            tokio_test::block_on(message.write(&mut cursor)).unwrap();
+        }
+
+        cursor.set_position(0);
+        assert!(matches!(
+            tokio_test::block_on(BackendMessage::read_with_field_limit(&mut cursor, 8)),
+            Err(ParseError::FieldTooLarge { length: 16 })
+        ));
+        // The stream continues after the skipped message
+        assert_eq!(
+            BackendMessage::DataRow(DataRow {
+                field_data: vec![Some(vec![1, 2]), Some(vec![0; 4])],
+            }),
+            tokio_test::block_on(BackendMessage::read_with_field_limit(&mut cursor, 8)).unwrap()
+        );
+    }
+
+    #[test]
+    fn unexpected_message() {
+        // A ReadyForQuery sent by a client
+        let mut cursor = std::io::Cursor::new(vec![b'Z', 0, 0, 0, 5, b'I']);
+        assert!(matches!(
+            tokio_test::block_on(FrontendMessage::read(&mut cursor)),
+            Err(ParseError::UnexpectedMessage { char: 'Z' })
+        ));
+    }
+
+    #[test]
+    fn error() {
+        let message = BackendMessage::Error(Error {
+            messages: vec![
+                (b'S', "FATAL".to_string()),
+                (b'C', "0A000".to_string()),
+                (b'M', "Test Message".to_string()),
+            ],
+        });
+
+        test_backend_symmetric_serialization_deserialization(message.into());
+    }
+
+    #[test]
+    fn error_fields() {
+        let error = Error {
+            messages: vec![
+                (b'S', "ERROR".to_string()),
+                (b'C', "42P01".to_string()),
+                (b'M', "relation \"contacts\" does not exist".to_string()),
+            ],
+        }
+        .with_severity("FATAL")
+        .with_detail("the proxy ends the connection".to_string());
+
+        assert_eq!(Some("FATAL"), error.severity());
+        assert_eq!(Some("FATAL"), error.field(b'V'));
+        assert_eq!(Some("42P01"), error.code());
+        assert_eq!(Some("the proxy ends the connection"), error.detail());
+
+        test_backend_symmetric_serialization_deserialization(BackendMessage::Error(error).into());
+    }
+
+    #[test]
+    fn authentication_sasl() {
+        let message = BackendMessage::AuthenticationSASL(vec![
+            "SCRAM-SHA-256-PLUS".to_string(),
+            "SCRAM-SHA-256".to_string(),
+        ]);
+
+        test_backend_symmetric_serialization_deserialization(message.into());
+    }
+
+    #[test]
+    fn authentication_sasl_continue() {
+        let message = BackendMessage::AuthenticationSASLContinue(b"r=nonce,s=salt,i=4096".to_vec());
+
+        test_backend_symmetric_serialization_deserialization(message.into());
+    }
+
+    #[test]
+    fn sasl_initial_response() {
+        let message = FrontendMessage::SASLInitialResponse(SASLInitialResponse {
+            mechanism: "SCRAM-SHA-256".to_string(),
+            data: Some(b"n,,n=,r=nonce".to_vec()),
+        });
+
+        let mut buf = vec![];
+        let mut cursor = std::io::Cursor::new(&mut buf);
+        tokio_test::block_on(message.clone().write(&mut cursor)).unwrap();
+
+        cursor.set_position(0);
+        let parsed =
+            tokio_test::block_on(FrontendMessage::read_sasl_initial_response(&mut cursor)).unwrap();
+
+        assert_eq!(parsed, message);
+    }
+
     #[test]
     fn empty_backend_key_data() {
         let message = BackendMessage::BackendKeyData(BackendKeyData {
@@ -850,6 +1279,30 @@ mod tests {
         test_backend_symmetric_serialization_deserialization(message.into());
     }
 
+    #[test]
+    fn notice_response() {
+        let message = BackendMessage::NoticeResponse(Error {
+            messages: vec![
+                (b'S', "WARNING".to_string()),
+                (b'C', "01000".to_string()),
+                (b'M', "the result was truncated".to_string()),
+            ],
+        });
+
+        test_backend_symmetric_serialization_deserialization(message.into());
+    }
+
+    #[test]
+    fn notification_response() {
+        let message = BackendMessage::NotificationResponse(NotificationResponse {
+            process_id: 42,
+            channel: "ddl".to_string(),
+            payload: "ALTER TABLE".to_string(),
+        });
+
+        test_backend_symmetric_serialization_deserialization(message.into());
+    }
+
     #[test]
     fn ready_for_query() {
         let message =
@@ -939,4 +1392,39 @@ mod tests {
 
         test_frontend_symmetric_serialization_deserialization(message.into());
     }
+
+    #[test]
+    fn flush() {
+        test_frontend_symmetric_serialization_deserialization(FrontendMessage::Flush.into());
+    }
+
+    #[test]
+    fn copy_in() {
+        test_frontend_symmetric_serialization_deserialization(
+            FrontendMessage::CopyData(b"1\tMax\n2\tAnna\n".to_vec()).into(),
+        );
+        test_frontend_symmetric_serialization_deserialization(FrontendMessage::CopyDone.into());
+        test_frontend_symmetric_serialization_deserialization(
+            FrontendMessage::CopyFail("canceled by the user".to_string()).into(),
+        );
+    }
+
+    #[test]
+    fn copy_out() {
+        let response = CopyResponse {
+            format: 0,
+            column_formats: vec![0, 0],
+        };
+
+        test_backend_symmetric_serialization_deserialization(
+            BackendMessage::CopyInResponse(response.clone()).into(),
+        );
+        test_backend_symmetric_serialization_deserialization(
+            BackendMessage::CopyOutResponse(response).into(),
+        );
+        test_backend_symmetric_serialization_deserialization(
+            BackendMessage::CopyData(b"1\tMax\n".to_vec()).into(),
+        );
+        test_backend_symmetric_serialization_deserialization(BackendMessage::CopyDone.into());
+    }
 }
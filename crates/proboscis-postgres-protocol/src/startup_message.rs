@@ -11,6 +11,9 @@ pub const CODE_STARTUP_SSL_REQUEST: i32 = 80877103;
 pub const CODE_STARTUP_GSSENC_REQUEST: i32 = 80877104;
 pub const CODE_STARTUP_POSTGRESQLV3: i32 = 0x00_03_00_00; // postgres protocol version 3.0(196608)
 
+/// Like postgres, longer startup messages are rejected before allocating a buffer for them
+pub const MAX_STARTUP_MESSAGE_LENGTH: u32 = 10_000;
+
 #[derive(Debug, std::cmp::PartialEq)]
 pub enum StartupMessage {
     CancelRequest { connection_id: u32, secret_key: u32 },
@@ -44,13 +47,42 @@ impl StartupMessage {
 
                 Ok(())
             }
-            _ => unimplemented!(),
+            Self::CancelRequest {
+                connection_id,
+                secret_key,
+            } => {
+                buf.write_u32(16).await?;
+                buf.write_i32(CODE_STARTUP_CANCEL).await?;
+                buf.write_u32(*connection_id).await?;
+                buf.write_u32(*secret_key).await?;
+
+                Ok(())
+            }
+            Self::SslRequest => {
+                buf.write_u32(8).await?;
+                buf.write_i32(CODE_STARTUP_SSL_REQUEST).await?;
+
+                Ok(())
+            }
+            Self::GssEncRequest => {
+                buf.write_u32(8).await?;
+                buf.write_i32(CODE_STARTUP_GSSENC_REQUEST).await?;
+
+                Ok(())
+            }
         }
     }
 
     pub async fn read<T: AsyncRead + Unpin>(stream: &mut T) -> Result<Self, ParseError> {
         let message_length = AsyncReadExt::read_u32(stream).await?;
 
+        // Every startup message contains at least the length and a code
+        if !(8..=MAX_STARTUP_MESSAGE_LENGTH).contains(&message_length) {
+            return Err(ParseError::InvalidStartupMessageLength {
+                length: message_length,
+            });
+        }
+
         let mut body_bytes = vec![0; message_length as usize - 4];
         stream.read_exact(&mut body_bytes).await?;
 
@@ -148,4 +180,37 @@ mod tests {
 
         assert_eq!(parsed, StartupMessage::Startup { params })
     }
+
+    #[test]
+    fn startup_message_length() {
+        for length in [4_u32, MAX_STARTUP_MESSAGE_LENGTH + 1] {
+            let buf = length.to_be_bytes().to_vec();
+            let mut cursor = std::io::Cursor::new(buf);
+
+            assert!(matches!(
+                tokio_test::block_on(StartupMessage::read(&mut cursor)),
+                Err(ParseError::InvalidStartupMessageLength { .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn requests() {
+        for message in [
+            StartupMessage::SslRequest,
+            StartupMessage::GssEncRequest,
+            StartupMessage::CancelRequest {
+                connection_id: 1,
+                secret_key: 2,
+            },
+        ] {
+            let mut buf = vec![];
+            tokio_test::block_on(message.write(&mut buf)).unwrap();
+
+            let mut cursor = std::io::Cursor::new(&mut buf);
+            let parsed = tokio_test::block_on(StartupMessage::read(&mut cursor)).unwrap();
+
+            assert_eq!(parsed, message)
+        }
+    }
 }
@@ -1,36 +1,89 @@
 use super::char_tag::CharTag;
-use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use crate::ParseError;
+use tokio::io::{AsyncRead, AsyncReadExt};
 
 pub async fn read_until_zero<T: AsyncRead + Unpin>(stream: &mut T) -> tokio::io::Result<Vec<u8>> {
     let mut result = vec![];
 
     loop {
-        let mut bytes = vec![0; 1];
-        bytes = stream.read_exact(&mut bytes).await.map(|_| bytes)?;
-
-        match bytes[..] {
-            [0] => break,
-            _ => result.extend_from_slice(&bytes[..]),
+        match stream.read_u8().await? {
+            0 => break,
+            byte => result.push(byte),
         }
     }
 
     Ok(result)
 }
 
-/// Higher order function to write a message with some arbitraty number bytes to a buffer
-/// and automatically prefix it the char_tag and with the message length as a BigEndian 32 bit integer
-pub async fn write_message_with_prefixed_message_len<T: AsyncWrite + std::marker::Unpin>(
-    buf: &mut T,
+/// Appends the string to the buffer, terminated by a zero byte
+pub fn put_cstring(buf: &mut Vec<u8>, value: &str) {
+    buf.extend_from_slice(value.as_bytes());
+    buf.push(0);
+}
+
+/// Reads the fields of an ErrorResponse or NoticeResponse, each a type byte followed by
+/// a string, up to the terminating zero byte
+pub async fn read_fields<T: AsyncRead + Unpin>(
+    stream: &mut T,
+) -> Result<Vec<(u8, String)>, ParseError> {
+    let mut fields = vec![];
+
+    while let Ok(identifier) = stream.read_u8().await {
+        match identifier {
+            0 => break,
+            _ => {
+                let value = String::from_utf8(read_until_zero(stream).await?)?;
+                fields.push((identifier, value))
+            }
+        }
+    }
+
+    Ok(fields)
+}
+
+pub fn put_fields(buf: &mut Vec<u8>, fields: &[(u8, String)]) {
+    for (field, value) in fields {
+        buf.push(*field);
+        put_cstring(buf, value);
+    }
+    buf.push(0);
+}
+
+/// Higher order function to append a message with some arbitrary number of bytes to a buffer,
+/// prefixed with the char_tag and the message length as a BigEndian 32 bit integer.
+/// The body is encoded into the buffer directly, the length is filled in afterwards.
+pub fn encode_message_with_prefixed_message_len<F: FnOnce(&mut Vec<u8>)>(
+    buf: &mut Vec<u8>,
     char_tag: CharTag,
-    body: &[u8],
-) -> tokio::io::Result<usize> {
-    let mut written_bytes_count = 0;
-    written_bytes_count += buf.write(&[char_tag.into()]).await?;
+    encode_body: F,
+) -> usize {
+    let start = buf.len();
+    buf.push(char_tag.into());
+    buf.extend_from_slice(&[0; 4]);
 
-    buf.write_i32(body.len() as i32 + 4).await?;
-    written_bytes_count += 4;
+    encode_body(buf);
 
-    written_bytes_count += buf.write(body).await?;
+    // The length includes itself, but not the tag
+    let message_len = (buf.len() - start - 1) as i32;
+    buf[start + 1..start + 5].copy_from_slice(&message_len.to_be_bytes());
+
+    buf.len() - start
+}
 
-    Ok(written_bytes_count)
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_message_with_prefixed_message_len() {
+        let mut buf = vec![1, 2];
+
+        let written = encode_message_with_prefixed_message_len(&mut buf, CharTag::Query, |body| {
+            put_cstring(body, "SELECT 1")
+        });
+
+        assert_eq!(14, written);
+        assert_eq!(&[1, 2, b'Q', 0, 0, 0, 13], &buf[..7]);
+        assert_eq!(b"SELECT 1\0", &buf[7..]);
+    }
 }
@@ -0,0 +1,89 @@
+use crate::cache::QueryCache;
+use arrow::array::Int64Array;
+use arrow::datatypes::{DataType, Schema};
+use arrow::error::ArrowError;
+use arrow::record_batch::RecordBatch;
+use proboscis_core::data::field::{Field, TEXT_FORMAT};
+use std::sync::Arc;
+
+#[derive(Debug, PartialEq)]
+pub enum AdminCommand {
+    /// Returns the size and statistics of the cache
+    ShowCache,
+    /// Removes every cached entry
+    FlushCache,
+}
+
+impl AdminCommand {
+    /// Recognizes admin commands, compared ignoring case, whitespace and a trailing semicolon
+    pub fn parse(query: &str) -> Option<AdminCommand> {
+        let words: Vec<String> = query
+            .trim_end()
+            .trim_end_matches(';')
+            .split_whitespace()
+            .map(|word| word.to_uppercase())
+            .collect();
+
+        match words.iter().map(String::as_str).collect::<Vec<&str>>()[..] {
+            ["SHOW", "CACHE"] => Some(AdminCommand::ShowCache),
+            ["FLUSH", "CACHE"] => Some(AdminCommand::FlushCache),
+            _ => None,
+        }
+    }
+}
+
+fn int8_field(name: &str) -> arrow::datatypes::Field {
+    (&Field {
+        name: name.to_string(),
+        table_oid: 0,
+        column_number: 0,
+        data_type: DataType::Int64,
+        extension: None,
+        format: TEXT_FORMAT,
+        original_type: None,
+    })
+        .into()
+}
+
+/// Describes the cache as a single row, the way SHOW commands of postgres return their results
+pub fn cache_statistics_record_batch(cache: &QueryCache) -> Result<RecordBatch, ArrowError> {
+    let statistics = cache.statistics();
+
+    let columns = vec![
+        ("entries", cache.len() as i64),
+        ("bytes", cache.size() as i64),
+        ("hits", statistics.hits as i64),
+        ("misses", statistics.misses as i64),
+        ("evictions", statistics.evictions as i64),
+        ("spills", statistics.spills as i64),
+        ("invalidations", statistics.invalidations as i64),
+    ];
+
+    let schema = Schema::new(columns.iter().map(|(name, _)| int8_field(name)).collect());
+
+    RecordBatch::try_new(
+        Arc::new(schema),
+        columns
+            .into_iter()
+            .map(|(_, value)| Arc::new(Int64Array::from(vec![value])) as _)
+            .collect(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_admin_command() {
+        assert_eq!(
+            Some(AdminCommand::ShowCache),
+            AdminCommand::parse("SHOW CACHE")
+        );
+        assert_eq!(
+            Some(AdminCommand::FlushCache),
+            AdminCommand::parse("flush  cache;\n")
+        );
+        assert_eq!(None, AdminCommand::parse("SHOW search_path"));
+    }
+}
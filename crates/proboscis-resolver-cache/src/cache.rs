@@ -0,0 +1,526 @@
+use crate::spill::{
+    read_spill_file, read_spill_file_metadata, spill_files, spill_path, write_spill_file,
+    SpillEncryptionKey, SpilledEntry,
+};
+use arrow::array::Array;
+use arrow::record_batch::RecordBatch;
+use proboscis_core::utils::sql::unqualified_table_name;
+use proboscis_postgres_protocol::message::Error;
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+#[derive(Clone)]
+pub struct CacheEntry {
+    pub data: Vec<RecordBatch>,
+    /// Tables the cached query reads from
+    pub tables: Vec<String>,
+    pub expires_at: Instant,
+    /// Memory used by the arrays of the cached data
+    pub size: usize,
+    // Position of the entry in the usage order
+    last_used: u64,
+}
+
+struct CachedError {
+    error: Error,
+    tables: Vec<String>,
+    expires_at: Instant,
+}
+
+/// Counters describing how effective the cache is since it was created
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CacheStatistics {
+    pub hits: u64,
+    pub misses: u64,
+    /// Entries removed to stay below the memory limit
+    pub evictions: u64,
+    /// Evicted entries that were written to the spill directory
+    pub spills: u64,
+    /// Entries removed because their tables were written to, or the cache was flushed
+    pub invalidations: u64,
+}
+
+/// Stores query results by a key identifying the query and its parameters.
+/// If a memory limit is set, the least recently used entries are evicted to stay below it.
+///
+/// If a spill directory is set, evicted entries are written to it as arrow ipc files and
+/// moved back into memory when they are requested again. Files left in the directory
+/// by a previous run are picked up as well, so spilled entries survive restarts.
+/// With an encryption key, the files are encrypted with AES-256-GCM.
+#[derive(Default)]
+pub struct QueryCache {
+    entries: HashMap<String, CacheEntry>,
+    // Maps the last usage of an entry to its key, oldest first
+    usage_order: BTreeMap<u64, String>,
+    usage_counter: u64,
+    size: usize,
+    memory_limit: Option<usize>,
+    spill_directory: Option<PathBuf>,
+    spill_encryption_key: Option<SpillEncryptionKey>,
+    spilled: HashMap<String, SpilledEntry>,
+    // Errors are small and short-lived, so they are neither evicted nor spilled
+    errors: HashMap<String, CachedError>,
+    statistics: CacheStatistics,
+}
+
+fn batches_size(data: &[RecordBatch]) -> usize {
+    data.iter()
+        .flat_map(|batch| batch.columns())
+        .map(|column| column.get_array_memory_size())
+        .sum()
+}
+
+fn remove_spill_file(spilled: &SpilledEntry) {
+    if let Err(err) = std::fs::remove_file(&spilled.path) {
+        tracing::warn!(path = ?spilled.path, error = %err, "failed to remove cache spill file");
+    }
+}
+
+fn overlaps(entry_tables: &[String], tables: &[String]) -> bool {
+    entry_tables
+        .iter()
+        .any(|table| tables.contains(&unqualified_table_name(table)))
+}
+
+impl QueryCache {
+    pub fn with_memory_limit(mut self, memory_limit: usize) -> QueryCache {
+        self.memory_limit = Some(memory_limit);
+        self
+    }
+
+    /// Encrypts spill files with the key, it has to be set before the spill directory,
+    /// so the files already in it can be read
+    pub fn with_spill_encryption_key(mut self, key: SpillEncryptionKey) -> QueryCache {
+        self.spill_encryption_key = Some(key);
+        self
+    }
+
+    /// Spills evicted entries into the directory, loading the entries already in it
+    pub fn with_spill_directory(mut self, directory: PathBuf) -> std::io::Result<QueryCache> {
+        std::fs::create_dir_all(&directory)?;
+
+        for path in spill_files(&directory)? {
+            match read_spill_file_metadata(&path, self.spill_encryption_key.as_ref()) {
+                Ok((_, spilled)) if spilled.expires_at <= Instant::now() => {
+                    remove_spill_file(&spilled)
+                }
+                Ok((key, spilled)) => {
+                    self.spilled.insert(key, spilled);
+                }
+                Err(err) => {
+                    tracing::warn!(path = ?path, error = %err, "ignoring invalid cache spill file")
+                }
+            }
+        }
+
+        tracing::debug!(entries = self.spilled.len(), "loaded spilled cache entries");
+
+        self.spill_directory = Some(directory);
+        Ok(self)
+    }
+
+    /// Memory used by all cached entries
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Number of cached entries, in memory or spilled to disk
+    pub fn len(&self) -> usize {
+        self.entries.len() + self.spilled.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub fn statistics(&self) -> &CacheStatistics {
+        &self.statistics
+    }
+
+    pub fn get(&mut self, key: &str) -> Option<Vec<RecordBatch>> {
+        let data = if self.entries.contains_key(key) {
+            self.get_from_memory(key)
+        } else {
+            self.get_from_spill_file(key)
+        };
+
+        match data {
+            Some(_) => self.statistics.hits += 1,
+            None => self.statistics.misses += 1,
+        }
+
+        data
+    }
+
+    /// Returns the error a query failed with, if it is cached. Doesn't count as a miss
+    /// otherwise, as the result of the query is looked up afterwards.
+    pub fn get_error(&mut self, key: &str) -> Option<Error> {
+        let cached = self.errors.get(key)?;
+
+        if cached.expires_at <= Instant::now() {
+            self.errors.remove(key);
+            return None;
+        }
+
+        self.statistics.hits += 1;
+        Some(cached.error.clone())
+    }
+
+    pub fn insert_error(&mut self, key: String, error: Error, tables: Vec<String>, ttl: Duration) {
+        self.remove(&key);
+
+        let now = Instant::now();
+        self.errors.retain(|_, cached| cached.expires_at > now);
+
+        self.errors.insert(
+            key,
+            CachedError {
+                error,
+                tables,
+                expires_at: now + ttl,
+            },
+        );
+    }
+
+    fn get_from_memory(&mut self, key: &str) -> Option<Vec<RecordBatch>> {
+        if self.entries.get(key)?.expires_at <= Instant::now() {
+            self.remove(key);
+            return None;
+        }
+
+        self.usage_counter += 1;
+        let last_used = self.usage_counter;
+
+        let entry = self.entries.get_mut(key)?;
+        self.usage_order.remove(&entry.last_used);
+        self.usage_order.insert(last_used, key.to_string());
+        entry.last_used = last_used;
+
+        Some(entry.data.clone())
+    }
+
+    fn get_from_spill_file(&mut self, key: &str) -> Option<Vec<RecordBatch>> {
+        let spilled = self.spilled.remove(key)?;
+
+        let now = Instant::now();
+        if spilled.expires_at <= now {
+            remove_spill_file(&spilled);
+            return None;
+        }
+
+        let data = match read_spill_file(&spilled.path, key, self.spill_encryption_key.as_ref()) {
+            Ok(data) => data,
+            Err(err) => {
+                tracing::warn!(path = ?spilled.path, error = %err, "failed to read cache spill file");
+                remove_spill_file(&spilled);
+                return None;
+            }
+        };
+
+        tracing::debug!(key = %key, "loading spilled cache entry");
+        remove_spill_file(&spilled);
+
+        self.insert(
+            key.to_string(),
+            data.clone(),
+            spilled.tables,
+            spilled.expires_at - now,
+        );
+
+        Some(data)
+    }
+
+    pub fn insert(
+        &mut self,
+        key: String,
+        data: Vec<RecordBatch>,
+        tables: Vec<String>,
+        ttl: Duration,
+    ) {
+        self.remove(&key);
+
+        let size = batches_size(&data);
+        if let Some(memory_limit) = self.memory_limit {
+            // Results larger than the whole cache would only evict everything else
+            if size > memory_limit {
+                return;
+            }
+
+            while self.size + size > memory_limit {
+                if !self.evict_least_recently_used() {
+                    break;
+                }
+            }
+        }
+
+        self.usage_counter += 1;
+        self.usage_order.insert(self.usage_counter, key.clone());
+        self.size += size;
+
+        self.entries.insert(
+            key,
+            CacheEntry {
+                data,
+                tables,
+                expires_at: Instant::now() + ttl,
+                size,
+                last_used: self.usage_counter,
+            },
+        );
+    }
+
+    pub fn remove(&mut self, key: &str) -> Option<CacheEntry> {
+        self.errors.remove(key);
+
+        if let Some(spilled) = self.spilled.remove(key) {
+            remove_spill_file(&spilled);
+        }
+
+        self.remove_from_memory(key)
+    }
+
+    fn remove_from_memory(&mut self, key: &str) -> Option<CacheEntry> {
+        let entry = self.entries.remove(key)?;
+        self.usage_order.remove(&entry.last_used);
+        self.size -= entry.size;
+        Some(entry)
+    }
+
+    /// Removes the entries reading from any of the tables, returning how many were removed
+    pub fn invalidate_tables(&mut self, tables: &[String]) -> usize {
+        let tables: Vec<String> = tables
+            .iter()
+            .map(|table| unqualified_table_name(table))
+            .collect();
+
+        let keys: Vec<String> = self
+            .entries
+            .iter()
+            .filter(|(_, entry)| overlaps(&entry.tables, &tables))
+            .map(|(key, _)| key)
+            .chain(
+                self.spilled
+                    .iter()
+                    .filter(|(_, spilled)| overlaps(&spilled.tables, &tables))
+                    .map(|(key, _)| key),
+            )
+            .chain(
+                self.errors
+                    .iter()
+                    .filter(|(_, cached)| overlaps(&cached.tables, &tables))
+                    .map(|(key, _)| key),
+            )
+            .cloned()
+            .collect();
+
+        for key in &keys {
+            self.remove(key);
+        }
+
+        self.statistics.invalidations += keys.len() as u64;
+        keys.len()
+    }
+
+    pub fn clear(&mut self) {
+        self.statistics.invalidations += (self.len() + self.errors.len()) as u64;
+        self.errors.clear();
+
+        for (_, spilled) in self.spilled.drain() {
+            remove_spill_file(&spilled);
+        }
+
+        self.entries.clear();
+        self.usage_order.clear();
+        self.size = 0;
+    }
+
+    fn evict_least_recently_used(&mut self) -> bool {
+        let key = match self.usage_order.values().next() {
+            Some(key) => key.clone(),
+            None => return false,
+        };
+
+        tracing::debug!(key = %key, "evicting cache entry");
+        self.statistics.evictions += 1;
+
+        let entry = match self.remove_from_memory(&key) {
+            Some(entry) => entry,
+            None => return false,
+        };
+
+        if let Some(directory) = &self.spill_directory {
+            let path = spill_path(directory, &key);
+
+            match write_spill_file(
+                &path,
+                &key,
+                &entry.data,
+                &entry.tables,
+                entry.expires_at,
+                self.spill_encryption_key.as_ref(),
+            ) {
+                Ok(()) => {
+                    self.statistics.spills += 1;
+                    self.spilled.insert(
+                        key,
+                        SpilledEntry {
+                            path,
+                            tables: entry.tables,
+                            expires_at: entry.expires_at,
+                        },
+                    );
+                }
+                Err(err) => {
+                    tracing::warn!(key = %key, error = %err, "failed to spill cache entry")
+                }
+            }
+        }
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::Int64Array;
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    fn batch(rows: i64) -> Vec<RecordBatch> {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+        vec![RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(Int64Array::from((0..rows).collect::<Vec<i64>>()))],
+        )
+        .unwrap()]
+    }
+
+    #[test]
+    fn test_evicts_least_recently_used() {
+        let entry_size = batches_size(&batch(100));
+        let mut cache = QueryCache::default().with_memory_limit(entry_size * 2);
+        let ttl = Duration::from_secs(60);
+
+        cache.insert("a".to_string(), batch(100), vec![], ttl);
+        cache.insert("b".to_string(), batch(100), vec![], ttl);
+        assert!(cache.get("a").is_some());
+
+        cache.insert("c".to_string(), batch(100), vec![], ttl);
+
+        assert!(cache.get("a").is_some());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+        assert_eq!(entry_size * 2, cache.size());
+
+        // Too large to be cached at all
+        cache.insert("d".to_string(), batch(1000), vec![], ttl);
+        assert!(cache.get("d").is_none());
+        assert!(cache.get("a").is_some());
+
+        assert_eq!(
+            &CacheStatistics {
+                hits: 4,
+                misses: 2,
+                evictions: 1,
+                spills: 0,
+                invalidations: 0,
+            },
+            cache.statistics()
+        );
+    }
+
+    #[test]
+    fn test_spills_evicted_entries() {
+        let directory =
+            std::env::temp_dir().join(format!("proboscis-cache-test-{}", std::process::id()));
+        let entry_size = batches_size(&batch(100));
+        let ttl = Duration::from_secs(60);
+
+        let mut cache = QueryCache::default()
+            .with_memory_limit(entry_size)
+            .with_spill_directory(directory.clone())
+            .unwrap();
+
+        cache.insert("a".to_string(), batch(100), vec![], ttl);
+        cache.insert("b".to_string(), batch(100), vec![], ttl);
+        assert_eq!(1, cache.statistics().spills);
+
+        // Loading a evicts b to disk in turn
+        assert_eq!(100, cache.get("a").unwrap()[0].num_rows());
+        assert_eq!(2, cache.statistics().spills);
+        assert_eq!(2, cache.len());
+
+        // A new cache picks up the entries spilled by the previous one
+        drop(cache);
+        let mut cache = QueryCache::default()
+            .with_spill_directory(directory.clone())
+            .unwrap();
+        assert_eq!(100, cache.get("b").unwrap()[0].num_rows());
+        assert!(cache.get("a").is_none());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_encrypts_spilled_entries() {
+        let directory = std::env::temp_dir().join(format!(
+            "proboscis-cache-encryption-test-{}",
+            std::process::id()
+        ));
+        let entry_size = batches_size(&batch(100));
+        let ttl = Duration::from_secs(60);
+
+        let mut cache = QueryCache::default()
+            .with_memory_limit(entry_size)
+            .with_spill_encryption_key([7; 32])
+            .with_spill_directory(directory.clone())
+            .unwrap();
+
+        cache.insert("a".to_string(), batch(100), vec![], ttl);
+        cache.insert("b".to_string(), batch(100), vec![], ttl);
+        assert_eq!(1, cache.statistics().spills);
+
+        // The arrow file can't be read without the key
+        let path = spill_path(&directory, "a");
+        assert!(read_spill_file(&path, "a", None).is_err());
+        assert!(read_spill_file(&path, "a", Some(&[8; 32])).is_err());
+        drop(cache);
+
+        // Spill files which can't be decrypted are ignored
+        let cache = QueryCache::default()
+            .with_spill_encryption_key([8; 32])
+            .with_spill_directory(directory.clone())
+            .unwrap();
+        assert_eq!(0, cache.len());
+
+        let mut cache = QueryCache::default()
+            .with_spill_encryption_key([7; 32])
+            .with_spill_directory(directory.clone())
+            .unwrap();
+        assert_eq!(100, cache.get("a").unwrap()[0].num_rows());
+
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+
+    #[test]
+    fn test_invalidate_tables() {
+        let mut cache = QueryCache::default();
+        let ttl = Duration::from_secs(60);
+
+        cache.insert("a".to_string(), batch(1), vec!["users".to_string()], ttl);
+        cache.insert(
+            "b".to_string(),
+            batch(1),
+            vec!["contacts".to_string(), "public.users".to_string()],
+            ttl,
+        );
+        cache.insert("c".to_string(), batch(1), vec!["orders".to_string()], ttl);
+
+        assert_eq!(2, cache.invalidate_tables(&["public.Users".to_string()]));
+        assert!(cache.get("a").is_none());
+        assert!(cache.get("b").is_none());
+        assert!(cache.get("c").is_some());
+    }
+}
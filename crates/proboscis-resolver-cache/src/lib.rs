@@ -0,0 +1,11 @@
+mod admin;
+mod cache;
+mod resolver;
+mod rule;
+mod spill;
+mod tables;
+
+pub use cache::CacheStatistics;
+pub use resolver::CachingResolver;
+pub use rule::{CacheRule, NegativeCaching};
+pub use spill::SpillEncryptionKey;
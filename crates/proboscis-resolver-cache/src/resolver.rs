@@ -0,0 +1,1056 @@
+use crate::{
+    admin::{cache_statistics_record_batch, AdminCommand},
+    cache::{CacheStatistics, QueryCache},
+    rule::{normalize_query, policy_for_query, CachePolicy, CacheRule},
+    spill::SpillEncryptionKey,
+    tables::{ends_transaction, referenced_tables, written_tables, WrittenTables},
+};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use proboscis_core::{
+    resolver::{
+        sync_error, Bind, ClientContext, ClientId, Close, CopyOutStream, CopyResponse, Describe,
+        Execute, Parse, ResolveError, Resolver, SyncResponse,
+    },
+    utils::{
+        connection::Connection,
+        role::{parse_role_changes, RoleChange},
+        transaction::{split_statements, TransactionState},
+    },
+};
+use proboscis_postgres_protocol::message::{
+    BindParameter, CloseKind, CommandCompleteTag, Error, NotificationResponse, ParameterStatus,
+    ReadyForQueryTransactionStatus,
+};
+use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+/// Everything needed to store the result of a query in the cache
+#[derive(Clone)]
+struct Cacheable {
+    key: String,
+    tables: Vec<String>,
+    policy: CachePolicy,
+}
+
+struct Portal {
+    query: String,
+    cacheable: Option<Cacheable>,
+}
+
+enum Operation {
+    Parse,
+    Bind,
+    Describe,
+    Execute {
+        query: String,
+        cacheable: Option<Cacheable>,
+    },
+    Cached {
+        data: Vec<RecordBatch>,
+        query: String,
+    },
+}
+
+/// The role the statements of a client run as
+#[derive(Clone, Debug, PartialEq)]
+enum ClientRole {
+    /// The role the client authenticated as
+    Session,
+    Set(String),
+    /// The role changed in a way that isn't known, e.g. by `set_config` or within a transaction
+    /// which may revert it. Nothing is cached for the client until it sets its role again.
+    Unknown,
+}
+
+impl Default for ClientRole {
+    fn default() -> Self {
+        ClientRole::Session
+    }
+}
+
+#[derive(Default)]
+struct ClientState {
+    // Results may be transformed differently depending on the user and role
+    user: Option<String>,
+    role: ClientRole,
+    transaction: TransactionState,
+    // Maps a statement to an sql string
+    statements: HashMap<String, String>,
+    portals: HashMap<String, Portal>,
+    // Operations since the last sync, in order
+    pending: Vec<Operation>,
+    // Tables written since the end of the last transaction
+    transaction_writes: WrittenTables,
+    // The query of the copy in progress, its table is invalidated once the copy is done
+    copying: Option<String>,
+}
+
+/// Serves the results of SELECT queries matching one of the rules from a cache.
+/// Results are cached separately for every user and role clients switch to.
+///
+/// Cached results are invalidated when a statement writes to one of the tables they
+/// were read from, and once more when the transaction containing the write ends,
+/// as results cached in between may have been read before the write was committed.
+/// Results a client reads within its transaction after writing aren't cached at all.
+///
+/// The cache can be inspected and emptied by sending `SHOW CACHE` and `FLUSH CACHE`
+/// as simple queries, which are answered without involving the inner resolver.
+///
+/// Parse, Describe and Bind messages are always forwarded, so the inner resolver
+/// knows every statement and portal. Only executions of cached portals are
+/// answered without involving the inner resolver.
+pub struct CachingResolver {
+    resolver: Box<dyn Resolver>,
+    rules: Vec<CacheRule>,
+    cache: QueryCache,
+    clients: HashMap<ClientId, ClientState>,
+}
+
+impl CachingResolver {
+    pub fn new(resolver: Box<dyn Resolver>) -> CachingResolver {
+        CachingResolver {
+            resolver,
+            rules: Vec::new(),
+            cache: QueryCache::default(),
+            clients: HashMap::new(),
+        }
+    }
+
+    pub fn add_rule(mut self, rule: CacheRule) -> CachingResolver {
+        self.rules.push(rule);
+        self
+    }
+
+    /// Limits the memory used by cached results, evicting the least recently used ones
+    pub fn with_memory_limit(mut self, memory_limit: usize) -> CachingResolver {
+        self.cache = std::mem::take(&mut self.cache).with_memory_limit(memory_limit);
+        self
+    }
+
+    /// Encrypts the results written into the spill directory with the AES-256 key,
+    /// it has to be set before the directory
+    pub fn with_spill_encryption_key(mut self, key: SpillEncryptionKey) -> CachingResolver {
+        self.cache = std::mem::take(&mut self.cache).with_spill_encryption_key(key);
+        self
+    }
+
+    /// Writes evicted results into the directory instead of discarding them.
+    /// Results spilled by previous runs, which haven't expired yet, are served again.
+    pub fn with_spill_directory(mut self, directory: PathBuf) -> std::io::Result<CachingResolver> {
+        self.cache = std::mem::take(&mut self.cache).with_spill_directory(directory)?;
+        Ok(self)
+    }
+
+    pub fn statistics(&self) -> &CacheStatistics {
+        self.cache.statistics()
+    }
+
+    fn run_admin_command(
+        &mut self,
+        command: AdminCommand,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        match command {
+            AdminCommand::ShowCache => Ok(vec![cache_statistics_record_batch(&self.cache)?]),
+            AdminCommand::FlushCache => {
+                tracing::info!(entries = self.cache.len(), "flushing cache");
+                self.cache.clear();
+                Ok(vec![])
+            }
+        }
+    }
+
+    /// Invalidates the cached results which may be changed by the query
+    fn invalidate_writes(&mut self, client_id: ClientId, query: &str) {
+        // Without rules nothing is ever cached
+        if self.rules.is_empty() {
+            return;
+        }
+
+        let written = written_tables(query);
+
+        let state = self.clients.entry(client_id).or_default();
+        let invalidated = if ends_transaction(query) {
+            let mut transaction_writes = std::mem::take(&mut state.transaction_writes);
+            transaction_writes.merge(written);
+            transaction_writes
+        } else {
+            state.transaction_writes.merge(written.clone());
+            written
+        };
+
+        match invalidated {
+            WrittenTables::None => {}
+            WrittenTables::Tables(tables) => {
+                let count = self.cache.invalidate_tables(&tables);
+                tracing::debug!(tables = ?tables, count, "invalidated cache entries");
+            }
+            WrittenTables::Unknown => {
+                tracing::debug!(query = %query, "invalidating cache after unknown write");
+                self.cache.clear();
+            }
+        }
+    }
+
+    /// Tracks the role changes and transactions of a query which succeeded
+    fn apply_role_changes(&mut self, client_id: ClientId, query: &str) {
+        let state = self.clients.entry(client_id).or_default();
+
+        for statement in split_statements(query) {
+            let in_transaction = !matches!(
+                state.transaction.status(),
+                ReadyForQueryTransactionStatus::NotInTransaction
+            );
+
+            for change in parse_role_changes(statement) {
+                state.role = match change {
+                    // Rolling back the transaction reverts the change, committing it reverts
+                    // `SET LOCAL ROLE`
+                    _ if in_transaction => ClientRole::Unknown,
+                    RoleChange::Set(role) => ClientRole::Set(role),
+                    RoleChange::Reset => ClientRole::Session,
+                    RoleChange::Opaque => ClientRole::Unknown,
+                };
+            }
+
+            state.transaction.apply(statement);
+        }
+    }
+
+    // Results the client reads after writing within its open transaction may contain its
+    // uncommitted rows, which other clients must not get and which don't exist after a rollback
+    fn reads_uncommitted(&self, client_id: ClientId) -> bool {
+        self.clients.get(&client_id).map_or(false, |state| {
+            !matches!(
+                state.transaction.status(),
+                ReadyForQueryTransactionStatus::NotInTransaction
+            ) && state.transaction_writes != WrittenTables::None
+        })
+    }
+
+    /// A failed query may have changed the role before its error
+    fn fail_role_changes(&mut self, client_id: ClientId, query: &str) {
+        if !parse_role_changes(query).is_empty() {
+            self.clients.entry(client_id).or_default().role = ClientRole::Unknown;
+        }
+    }
+
+    fn cacheable(
+        &self,
+        client_id: ClientId,
+        query: &str,
+        parameters: &[BindParameter],
+        result_formats: &[i16],
+    ) -> Option<Cacheable> {
+        if self.rules.is_empty() || !parse_role_changes(query).is_empty() {
+            return None;
+        }
+
+        let state = self.clients.get(&client_id);
+        let role = match state.map(|state| &state.role) {
+            Some(ClientRole::Unknown) => return None,
+            Some(ClientRole::Set(role)) => Some(role.as_str()),
+            Some(ClientRole::Session) | None => None,
+        };
+
+        let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, query).ok()?;
+        if statements.len() != 1 {
+            return None;
+        }
+
+        let tables = referenced_tables(&statements.pop()?)?;
+        let policy = policy_for_query(&self.rules, query, &tables)?;
+
+        Some(Cacheable {
+            key: cache_key(
+                state.and_then(|state| state.user.as_deref()),
+                role,
+                query,
+                parameters,
+                result_formats,
+            ),
+            tables,
+            policy,
+        })
+    }
+}
+
+/// Results depend on the user, the role, the query, the bound parameters and the requested
+/// result formats
+fn cache_key(
+    user: Option<&str>,
+    role: Option<&str>,
+    query: &str,
+    parameters: &[BindParameter],
+    result_formats: &[i16],
+) -> String {
+    format!(
+        "{}\0{:?}\0{}\0{:?}\0{:?}",
+        user.unwrap_or_default(),
+        role,
+        normalize_query(query),
+        parameters,
+        result_formats
+    )
+}
+
+fn select_tag(data: &[RecordBatch]) -> CommandCompleteTag {
+    let rows: usize = data.iter().map(|batch| batch.num_rows()).sum();
+    CommandCompleteTag(format!("SELECT {}", rows))
+}
+
+impl CachingResolver {
+    /// Attributes the responses of the inner resolver to the pending operations,
+    /// answering cached executions in the position they were requested in
+    fn answer_pending(
+        &mut self,
+        client_id: ClientId,
+        result: Result<Vec<SyncResponse>, ResolveError>,
+    ) -> Result<Vec<SyncResponse>, ResolveError> {
+        let pending = std::mem::take(&mut self.clients.entry(client_id).or_default().pending);
+
+        let failed = match &result {
+            Ok(responses) => sync_error(responses).is_some(),
+            Err(_) => true,
+        };
+        if failed {
+            // The executions may have written data before the sync failed
+            for operation in &pending {
+                if let Operation::Execute { query, .. } = operation {
+                    self.invalidate_writes(client_id, query);
+
+                    // Without responses it isn't known which executions completed
+                    if result.is_err() {
+                        self.fail_role_changes(client_id, query);
+                    }
+                }
+            }
+        }
+
+        let mut responses = result?.into_iter();
+        let mut result = vec![];
+
+        for operation in pending {
+            // The target skips the operations after an error
+            if matches!(result.last(), Some(SyncResponse::Error(_))) {
+                break;
+            }
+
+            match operation {
+                Operation::Parse | Operation::Bind => result.extend(responses.next()),
+                Operation::Describe => {
+                    for response in responses.by_ref() {
+                        let is_last = matches!(
+                            response,
+                            SyncResponse::Schema { .. }
+                                | SyncResponse::NoData
+                                | SyncResponse::Error(_)
+                        );
+                        result.push(response);
+
+                        if is_last {
+                            break;
+                        }
+                    }
+                }
+                Operation::Execute { query, cacheable } => {
+                    let mut records = None;
+                    let mut completed = false;
+
+                    for response in responses.by_ref() {
+                        if let SyncResponse::Records { data, query: _ } = &response {
+                            records = Some(data.clone());
+                        }
+
+                        let is_last = matches!(
+                            response,
+                            SyncResponse::CommandComplete(_)
+                                | SyncResponse::PortalSuspended
+                                | SyncResponse::EmptyQueryResponse
+                                | SyncResponse::Error(_)
+                        );
+                        completed = matches!(response, SyncResponse::CommandComplete(_));
+                        result.push(response);
+
+                        if is_last {
+                            break;
+                        }
+                    }
+
+                    if completed {
+                        self.apply_role_changes(client_id, &query);
+                    }
+                    let uncommitted = self.reads_uncommitted(client_id);
+
+                    match (completed, cacheable, records) {
+                        (
+                            true,
+                            Some(Cacheable {
+                                key,
+                                tables,
+                                policy,
+                            }),
+                            Some(data),
+                        ) if !uncommitted => {
+                            let ttl = policy.ttl_for_result(&data);
+                            self.cache.insert(key, data, tables, ttl)
+                        }
+                        (_, None, _) => self.invalidate_writes(client_id, &query),
+                        _ => {}
+                    }
+                }
+                Operation::Cached { data, query } => {
+                    let tag = select_tag(&data);
+                    result.push(SyncResponse::Records { data, query });
+                    result.push(SyncResponse::CommandComplete(tag));
+                }
+            }
+        }
+
+        result.extend(responses);
+
+        Ok(result)
+    }
+}
+
+#[async_trait]
+impl Resolver for CachingResolver {
+    async fn authenticate(
+        &mut self,
+        client_id: ClientId,
+        frontend: &mut Connection,
+    ) -> Result<(), ResolveError> {
+        self.resolver.authenticate(client_id, frontend).await
+    }
+
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        self.clients.insert(
+            client_id,
+            ClientState {
+                user: context.user().map(str::to_string),
+                ..ClientState::default()
+            },
+        );
+        self.resolver.initialize(client_id, context).await
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        if let Some(command) = AdminCommand::parse(&query) {
+            return self.run_admin_command(command);
+        }
+
+        let cacheable = self.cacheable(client_id, &query, &[], &[]);
+
+        if let Some(cacheable) = &cacheable {
+            if let Some(error) = self.cache.get_error(&cacheable.key) {
+                tracing::debug!(query = %query, "serving error from cache");
+                return Err(ResolveError::Target(error));
+            }
+
+            if let Some(data) = self.cache.get(&cacheable.key) {
+                tracing::debug!(query = %query, "serving query from cache");
+                return Ok(data);
+            }
+        }
+
+        let result = self.resolver.query(client_id, query.clone()).await;
+        match &result {
+            Ok(_) => self.apply_role_changes(client_id, &query),
+            Err(_) => self.fail_role_changes(client_id, &query),
+        }
+
+        let Cacheable {
+            key,
+            tables,
+            policy,
+        } = match cacheable {
+            Some(cacheable) => cacheable,
+            None => {
+                // A failed query may still have written data before the error occurred
+                self.invalidate_writes(client_id, &query);
+                return result;
+            }
+        };
+
+        if self.reads_uncommitted(client_id) {
+            return result;
+        }
+
+        // Errors are only cached in the simple query protocol, the extended protocol
+        // doesn't report them per execution
+        match result {
+            Ok(data) => {
+                let ttl = policy.ttl_for_result(&data);
+                self.cache.insert(key, data.clone(), tables, ttl);
+                Ok(data)
+            }
+            Err(ResolveError::Target(error)) => {
+                if let Some(ttl) = policy.ttl_for_error(error.code()) {
+                    self.cache.insert_error(key, error.clone(), tables, ttl);
+                }
+                Err(ResolveError::Target(error))
+            }
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        let state = self.clients.entry(client_id).or_default();
+        state
+            .statements
+            .insert(parse.statement_name.clone(), parse.query.clone());
+        state.pending.push(Operation::Parse);
+
+        self.resolver.parse(client_id, parse).await
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        let state = self.clients.entry(client_id).or_default();
+        state.pending.push(Operation::Describe);
+
+        self.resolver.describe(client_id, describe).await
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        let query = self
+            .clients
+            .entry(client_id)
+            .or_default()
+            .statements
+            .get(&bind.statement)
+            .cloned()
+            .unwrap_or_default();
+
+        let cacheable = self.cacheable(client_id, &query, &bind.params, &bind.results);
+
+        let state = self.clients.entry(client_id).or_default();
+        state
+            .portals
+            .insert(bind.portal.clone(), Portal { query, cacheable });
+        state.pending.push(Operation::Bind);
+
+        self.resolver.bind(client_id, bind).await
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        let state = self.clients.entry(client_id).or_default();
+
+        // Partial executions can't be answered from a complete result
+        let (query, cacheable) = match state.portals.get(&execute.portal) {
+            Some(portal) => (
+                portal.query.clone(),
+                portal.cacheable.clone().filter(|_| execute.row_limit == 0),
+            ),
+            None => (String::new(), None),
+        };
+
+        if let Some(cacheable) = &cacheable {
+            if let Some(data) = self.cache.get(&cacheable.key) {
+                tracing::debug!(query = %query, "serving execution from cache");
+
+                self.clients
+                    .entry(client_id)
+                    .or_default()
+                    .pending
+                    .push(Operation::Cached { data, query });
+
+                return Ok(());
+            }
+        }
+
+        self.clients
+            .entry(client_id)
+            .or_default()
+            .pending
+            .push(Operation::Execute { query, cacheable });
+
+        self.resolver.execute(client_id, execute).await
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let result = self.resolver.sync(client_id).await;
+        self.answer_pending(client_id, result)
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let result = self.resolver.flush(client_id).await;
+        self.answer_pending(client_id, result)
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let state = self.clients.entry(client_id).or_default();
+        match close.kind {
+            CloseKind::Statement => state.statements.remove(&close.name),
+            CloseKind::Portal => state.portals.remove(&close.name).map(|portal| portal.query),
+        };
+
+        self.resolver.close(client_id, close).await
+    }
+
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        let response = self.resolver.copy_in(client_id, query.clone()).await?;
+        self.clients.entry(client_id).or_default().copying = Some(query);
+
+        Ok(response)
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        self.resolver.copy_data(client_id, data).await
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        let result = self.resolver.copy_done(client_id).await;
+        let copying = self.clients.entry(client_id).or_default().copying.take();
+        if let Some(query) = copying {
+            self.invalidate_writes(client_id, &query);
+        }
+
+        result
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        self.clients.entry(client_id).or_default().copying = None;
+        self.resolver.copy_fail(client_id, message).await
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        self.resolver.copy_out(client_id, query).await
+    }
+
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        self.resolver.take_notices(client_id)
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.resolver.take_parameter_changes(client_id)
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.resolver.take_notifications(client_id)
+    }
+
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.wait_for_idle_message(client_id).await
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.read_idle_messages(client_id).await
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.clients.remove(&client_id);
+        self.resolver.terminate(client_id).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rule::NegativeCaching;
+    use arrow::array::{Int32Array, Int64Array};
+    use arrow::datatypes::{DataType, Field, Schema};
+    use proboscis_postgres_protocol::message::Error;
+    use std::sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc,
+    };
+    use std::time::Duration;
+
+    struct CountingResolver {
+        queries: Arc<AtomicUsize>,
+    }
+
+    fn batch() -> RecordBatch {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap()
+    }
+
+    #[async_trait]
+    impl Resolver for CountingResolver {
+        async fn initialize(
+            &mut self,
+            _client_id: ClientId,
+            _context: &ClientContext,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn query(
+            &mut self,
+            _client_id: ClientId,
+            query: String,
+        ) -> Result<Vec<RecordBatch>, ResolveError> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+
+            if query.contains("missing") {
+                return Err(ResolveError::Target(Error {
+                    messages: vec![(b'C', "42P01".to_string())],
+                }));
+            }
+
+            Ok(vec![batch()])
+        }
+
+        async fn parse(&mut self, _client_id: ClientId, _parse: Parse) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn describe(
+            &mut self,
+            _client_id: ClientId,
+            _describe: Describe,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn bind(&mut self, _client_id: ClientId, _bind: Bind) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn execute(
+            &mut self,
+            _client_id: ClientId,
+            _execute: Execute,
+        ) -> Result<(), ResolveError> {
+            self.queries.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        async fn sync(&mut self, _client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+            Ok(vec![SyncResponse::ReadyForQuery])
+        }
+
+        async fn close(&mut self, _client_id: ClientId, _close: Close) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn terminate(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_caches_matching_queries() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let mut resolver = CachingResolver::new(Box::new(CountingResolver {
+            queries: queries.clone(),
+        }))
+        .add_rule(CacheRule::Table {
+            table: "users".to_string(),
+            ttl: Duration::from_secs(60),
+            negative: NegativeCaching::default(),
+        });
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        for _ in 0..3 {
+            let data = resolver
+                .query(client_id, "SELECT id FROM users".to_string())
+                .await
+                .unwrap();
+            assert_eq!(3, data[0].num_rows());
+        }
+        assert_eq!(1, queries.load(Ordering::SeqCst));
+
+        for _ in 0..2 {
+            resolver
+                .query(client_id, "SELECT id FROM orders".to_string())
+                .await
+                .unwrap();
+        }
+        assert_eq!(3, queries.load(Ordering::SeqCst));
+
+        let statistics = resolver
+            .query(client_id, "SHOW CACHE".to_string())
+            .await
+            .unwrap();
+        let hits = statistics[0].column(2);
+        assert_eq!(
+            &Int64Array::from(vec![2]),
+            hits.as_any().downcast_ref::<Int64Array>().unwrap()
+        );
+        assert_eq!(3, queries.load(Ordering::SeqCst));
+
+        resolver
+            .query(client_id, "FLUSH CACHE".to_string())
+            .await
+            .unwrap();
+        resolver
+            .query(client_id, "SELECT id FROM users".to_string())
+            .await
+            .unwrap();
+        assert_eq!(4, queries.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_caches_per_role() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let mut resolver = CachingResolver::new(Box::new(CountingResolver {
+            queries: queries.clone(),
+        }))
+        .add_rule(CacheRule::Table {
+            table: "users".to_string(),
+            ttl: Duration::from_secs(60),
+            negative: NegativeCaching::default(),
+        });
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        resolver
+            .query(client_id, "SELECT id FROM users".to_string())
+            .await
+            .unwrap();
+        assert_eq!(1, queries.load(Ordering::SeqCst));
+
+        // The result of the session's role isn't served to the restricted role
+        resolver
+            .query(client_id, "SET ROLE analyst".to_string())
+            .await
+            .unwrap();
+        resolver
+            .query(client_id, "SELECT id FROM users".to_string())
+            .await
+            .unwrap();
+        assert_eq!(3, queries.load(Ordering::SeqCst));
+        resolver
+            .query(client_id, "SELECT id FROM users".to_string())
+            .await
+            .unwrap();
+        assert_eq!(3, queries.load(Ordering::SeqCst));
+
+        resolver
+            .query(client_id, "RESET ROLE".to_string())
+            .await
+            .unwrap();
+        resolver
+            .query(client_id, "SELECT id FROM users".to_string())
+            .await
+            .unwrap();
+        assert_eq!(4, queries.load(Ordering::SeqCst));
+
+        // The role changed within a transaction isn't known once it ends
+        resolver
+            .query(client_id, "BEGIN; SET ROLE analyst; COMMIT".to_string())
+            .await
+            .unwrap();
+        resolver
+            .query(client_id, "SELECT id FROM users".to_string())
+            .await
+            .unwrap();
+        resolver
+            .query(client_id, "SELECT id FROM users".to_string())
+            .await
+            .unwrap();
+        assert_eq!(7, queries.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_invalidates_on_write() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let mut resolver = CachingResolver::new(Box::new(CountingResolver {
+            queries: queries.clone(),
+        }))
+        .add_rule(CacheRule::Table {
+            table: "users".to_string(),
+            ttl: Duration::from_secs(60),
+            negative: NegativeCaching::default(),
+        });
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        let query = "SELECT id FROM users".to_string();
+
+        resolver.query(client_id, query.clone()).await.unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        assert_eq!(1, queries.load(Ordering::SeqCst));
+
+        resolver
+            .query(client_id, "DELETE FROM orders".to_string())
+            .await
+            .unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        assert_eq!(2, queries.load(Ordering::SeqCst));
+
+        resolver
+            .query(
+                client_id,
+                "UPDATE users SET id = 4 WHERE id = 3".to_string(),
+            )
+            .await
+            .unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        assert_eq!(4, queries.load(Ordering::SeqCst));
+
+        // Results cached within the transaction may predate the committed write
+        resolver
+            .query(client_id, "COMMIT".to_string())
+            .await
+            .unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        assert_eq!(6, queries.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_skips_uncommitted_reads() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let mut resolver = CachingResolver::new(Box::new(CountingResolver {
+            queries: queries.clone(),
+        }))
+        .add_rule(CacheRule::Table {
+            table: "users".to_string(),
+            ttl: Duration::from_secs(60),
+            negative: NegativeCaching::default(),
+        });
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        let query = "SELECT id FROM users".to_string();
+
+        resolver
+            .query(client_id, "BEGIN".to_string())
+            .await
+            .unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        assert_eq!(2, queries.load(Ordering::SeqCst));
+
+        // The rows read after the write may never be committed
+        resolver
+            .query(client_id, "INSERT INTO users VALUES (4)".to_string())
+            .await
+            .unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        assert_eq!(5, queries.load(Ordering::SeqCst));
+
+        resolver
+            .query(client_id, "ROLLBACK".to_string())
+            .await
+            .unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        resolver.query(client_id, query.clone()).await.unwrap();
+        assert_eq!(7, queries.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_caches_errors() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let mut resolver = CachingResolver::new(Box::new(CountingResolver {
+            queries: queries.clone(),
+        }))
+        .add_rule(CacheRule::Table {
+            table: "missing".to_string(),
+            ttl: Duration::from_secs(60),
+            negative: NegativeCaching {
+                empty_result_ttl: None,
+                error_codes: vec!["42P01".to_string()],
+                error_ttl: Duration::from_secs(5),
+            },
+        });
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            let result = resolver
+                .query(client_id, "SELECT id FROM missing".to_string())
+                .await;
+            assert!(matches!(result, Err(ResolveError::Target(_))));
+        }
+        assert_eq!(1, queries.load(Ordering::SeqCst));
+
+        // Creating the table invalidates the cached error
+        resolver
+            .query(client_id, "CREATE TABLE missing (id INT)".to_string())
+            .await
+            .unwrap_err();
+        resolver
+            .query(client_id, "SELECT id FROM missing".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(3, queries.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_caches_per_user() {
+        let queries = Arc::new(AtomicUsize::new(0));
+        let mut resolver = CachingResolver::new(Box::new(CountingResolver {
+            queries: queries.clone(),
+        }))
+        .add_rule(CacheRule::Table {
+            table: "users".to_string(),
+            ttl: Duration::from_secs(60),
+            negative: NegativeCaching::default(),
+        });
+
+        let context = |user: &str| {
+            ClientContext::new(
+                vec![("user".to_string(), user.to_string())]
+                    .into_iter()
+                    .collect(),
+            )
+        };
+
+        // Transformers in front of the cache may return different results for each user
+        for user in &["analyst", "admin", "analyst"] {
+            let client_id = ClientId::new_v4();
+            resolver
+                .initialize(client_id, &context(user))
+                .await
+                .unwrap();
+            resolver
+                .query(client_id, "SELECT id FROM users".to_string())
+                .await
+                .unwrap();
+        }
+        assert_eq!(2, queries.load(Ordering::SeqCst));
+    }
+}
@@ -0,0 +1,204 @@
+use arrow::record_batch::RecordBatch;
+use std::time::Duration;
+
+/// Short-lived caching of results without data, to shield the target from
+/// repeated queries, e.g. for a table that doesn't exist yet during a migration
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct NegativeCaching {
+    /// Ttl for empty results, which are otherwise cached with the ttl of the rule
+    pub empty_result_ttl: Option<Duration>,
+    /// SQLSTATE codes of the errors to cache, e.g. 42P01 for an undefined table
+    pub error_codes: Vec<String>,
+    pub error_ttl: Duration,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum CacheRule {
+    /// Caches queries reading from the table. A query reading from multiple
+    /// tables is only cached if every table has a rule, using the shortest ttl.
+    Table {
+        table: String,
+        ttl: Duration,
+        negative: NegativeCaching,
+    },
+
+    /// Caches a specific query, compared ignoring differences in whitespace
+    Query {
+        query: String,
+        ttl: Duration,
+        negative: NegativeCaching,
+    },
+}
+
+/// How the results of a query are cached, combined from the rules matching the query
+#[derive(Clone, Debug, PartialEq)]
+pub struct CachePolicy {
+    pub ttl: Duration,
+    pub empty_result_ttl: Duration,
+    pub error_codes: Vec<String>,
+    pub error_ttl: Duration,
+}
+
+impl CachePolicy {
+    fn new(ttl: Duration, negative: &NegativeCaching) -> CachePolicy {
+        CachePolicy {
+            ttl,
+            empty_result_ttl: negative.empty_result_ttl.unwrap_or(ttl),
+            error_codes: negative.error_codes.clone(),
+            error_ttl: negative.error_ttl,
+        }
+    }
+
+    /// Combines the policies of multiple tables, only caching what every policy allows
+    fn intersect(self, other: CachePolicy) -> CachePolicy {
+        CachePolicy {
+            ttl: self.ttl.min(other.ttl),
+            empty_result_ttl: self.empty_result_ttl.min(other.empty_result_ttl),
+            error_codes: self
+                .error_codes
+                .into_iter()
+                .filter(|code| other.error_codes.contains(code))
+                .collect(),
+            error_ttl: self.error_ttl.min(other.error_ttl),
+        }
+    }
+
+    pub fn ttl_for_result(&self, data: &[RecordBatch]) -> Duration {
+        if data.iter().all(|batch| batch.num_rows() == 0) {
+            self.empty_result_ttl
+        } else {
+            self.ttl
+        }
+    }
+
+    /// The ttl for an error with the given SQLSTATE code, if it should be cached at all
+    pub fn ttl_for_error(&self, code: Option<&str>) -> Option<Duration> {
+        let code = code?;
+        if self.error_codes.iter().any(|error_code| error_code == code) {
+            Some(self.error_ttl)
+        } else {
+            None
+        }
+    }
+}
+
+/// Collapses whitespace and removes a trailing semicolon, so equivalent queries share cache entries
+pub fn normalize_query(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .trim_end_matches(';')
+        .trim_end()
+        .to_string()
+}
+
+/// Determines how the results of a query are cached, if any of the rules allow caching it
+pub fn policy_for_query(
+    rules: &[CacheRule],
+    query: &str,
+    tables: &[String],
+) -> Option<CachePolicy> {
+    let normalized_query = normalize_query(query);
+
+    let query_policy = rules.iter().find_map(|rule| match rule {
+        CacheRule::Query {
+            query,
+            ttl,
+            negative,
+        } if normalize_query(query) == normalized_query => Some(CachePolicy::new(*ttl, negative)),
+        _ => None,
+    });
+
+    if query_policy.is_some() {
+        return query_policy;
+    }
+
+    if tables.is_empty() {
+        return None;
+    }
+
+    let mut policy: Option<CachePolicy> = None;
+    for table in tables {
+        let table_policy = rules.iter().find_map(|rule| match rule {
+            CacheRule::Table {
+                table: name,
+                ttl,
+                negative,
+            } if name == table => Some(CachePolicy::new(*ttl, negative)),
+            _ => None,
+        })?;
+
+        policy = Some(match policy {
+            Some(policy) => policy.intersect(table_policy),
+            None => table_policy,
+        });
+    }
+
+    policy
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_policy_for_query() {
+        let rules = vec![
+            CacheRule::Table {
+                table: "users".to_string(),
+                ttl: Duration::from_secs(60),
+                negative: NegativeCaching {
+                    empty_result_ttl: Some(Duration::from_secs(1)),
+                    error_codes: vec!["42P01".to_string()],
+                    error_ttl: Duration::from_secs(2),
+                },
+            },
+            CacheRule::Table {
+                table: "contacts".to_string(),
+                ttl: Duration::from_secs(10),
+                negative: NegativeCaching::default(),
+            },
+            CacheRule::Query {
+                query: "SELECT count(*) FROM orders".to_string(),
+                ttl: Duration::from_secs(5),
+                negative: NegativeCaching::default(),
+            },
+        ];
+        let ttl_for_query = |query: &str, tables: &[String]| {
+            policy_for_query(&rules, query, tables).map(|policy| policy.ttl)
+        };
+
+        let users = vec!["users".to_string()];
+        let joined = vec!["contacts".to_string(), "users".to_string()];
+        let orders = vec!["orders".to_string()];
+
+        assert_eq!(
+            Some(Duration::from_secs(60)),
+            ttl_for_query("SELECT * FROM users", &users)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(10)),
+            ttl_for_query("SELECT * FROM users JOIN contacts", &joined)
+        );
+        assert_eq!(
+            Some(Duration::from_secs(5)),
+            ttl_for_query("SELECT count(*)\n  FROM orders;", &orders)
+        );
+        assert_eq!(None, ttl_for_query("SELECT * FROM orders", &orders));
+
+        let users_policy = policy_for_query(&rules, "SELECT * FROM users", &users).unwrap();
+        assert_eq!(Duration::from_secs(1), users_policy.empty_result_ttl);
+        assert_eq!(
+            Some(Duration::from_secs(2)),
+            users_policy.ttl_for_error(Some("42P01"))
+        );
+        assert_eq!(None, users_policy.ttl_for_error(Some("42501")));
+
+        // Errors are only cached if every table allows it
+        let joined_policy =
+            policy_for_query(&rules, "SELECT * FROM users JOIN contacts", &joined).unwrap();
+        assert_eq!(Duration::from_secs(1), joined_policy.empty_result_ttl);
+        assert_eq!(None, joined_policy.ttl_for_error(Some("42P01")));
+    }
+}
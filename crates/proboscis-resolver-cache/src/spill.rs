@@ -0,0 +1,257 @@
+use arrow::datatypes::Schema;
+use arrow::error::{ArrowError, Result};
+use arrow::ipc::{reader::FileReader, writer::FileWriter};
+use arrow::record_batch::RecordBatch;
+use openssl::error::ErrorStack;
+use openssl::rand::rand_bytes;
+use openssl::symm::{decrypt_aead, encrypt_aead, Cipher};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::fs::File;
+use std::hash::{Hash, Hasher};
+use std::io::{Cursor, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+const SPILL_FILE_EXTENSION: &str = "arrow";
+
+// The cache metadata is stored in the schema of the arrow file
+const KEY_METADATA_KEY: &str = "proboscis.cache.key";
+const TABLES_METADATA_KEY: &str = "proboscis.cache.tables";
+const EXPIRES_AT_METADATA_KEY: &str = "proboscis.cache.expires_at";
+
+const NONCE_LENGTH: usize = 12;
+const TAG_LENGTH: usize = 16;
+
+/// Key of the AES-256-GCM encryption of spill files
+pub type SpillEncryptionKey = [u8; 32];
+
+/// A cache entry that was moved out of memory into an arrow file
+pub struct SpilledEntry {
+    pub path: PathBuf,
+    pub tables: Vec<String>,
+    pub expires_at: Instant,
+}
+
+/// Location of the spill file for a cache key, within the spill directory
+pub fn spill_path(directory: &Path, key: &str) -> PathBuf {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+
+    directory.join(format!("{:016x}.{}", hasher.finish(), SPILL_FILE_EXTENSION))
+}
+
+fn metadata_error(message: &str) -> ArrowError {
+    ArrowError::IoError(format!("invalid cache spill file: {}", message))
+}
+
+// Encrypted spill files consist of the nonce, the authentication tag and the encrypted arrow file
+fn encrypt(key: &SpillEncryptionKey, plaintext: &[u8]) -> Result<Vec<u8>> {
+    let encryption_error =
+        |err: ErrorStack| ArrowError::IoError(format!("couldn't encrypt: {}", err));
+
+    let mut nonce = [0; NONCE_LENGTH];
+    rand_bytes(&mut nonce).map_err(encryption_error)?;
+
+    let mut tag = [0; TAG_LENGTH];
+    let ciphertext = encrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(&nonce),
+        &[],
+        plaintext,
+        &mut tag,
+    )
+    .map_err(encryption_error)?;
+
+    Ok([&nonce[..], &tag[..], &ciphertext[..]].concat())
+}
+
+fn decrypt(key: &SpillEncryptionKey, contents: &[u8]) -> Result<Vec<u8>> {
+    if contents.len() < NONCE_LENGTH + TAG_LENGTH {
+        return Err(metadata_error("too short to be encrypted"));
+    }
+
+    let (nonce, rest) = contents.split_at(NONCE_LENGTH);
+    let (tag, ciphertext) = rest.split_at(TAG_LENGTH);
+
+    // Fails for files written with another key or without encryption as well
+    decrypt_aead(
+        Cipher::aes_256_gcm(),
+        key,
+        Some(nonce),
+        &[],
+        ciphertext,
+        tag,
+    )
+    .map_err(|_| metadata_error("couldn't decrypt"))
+}
+
+fn open_spill_file(
+    path: &Path,
+    encryption_key: Option<&SpillEncryptionKey>,
+) -> Result<FileReader<Cursor<Vec<u8>>>> {
+    let mut contents = vec![];
+    File::open(path)?.read_to_end(&mut contents)?;
+
+    if let Some(encryption_key) = encryption_key {
+        contents = decrypt(encryption_key, &contents)?;
+    }
+
+    FileReader::try_new(Cursor::new(contents))
+}
+
+// Instants can't be persisted, the expiry is stored as seconds since the unix epoch instead
+fn instant_to_unix_seconds(instant: Instant) -> u64 {
+    let remaining = instant.saturating_duration_since(Instant::now());
+    (SystemTime::now() + remaining)
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+fn unix_seconds_to_instant(seconds: u64) -> Instant {
+    let remaining = (UNIX_EPOCH + Duration::from_secs(seconds))
+        .duration_since(SystemTime::now())
+        .unwrap_or_default();
+    Instant::now() + remaining
+}
+
+/// Writes the data of a cache entry, along with its key, tables and expiry, to an arrow ipc file.
+/// With an encryption key, the whole file is encrypted, including the metadata.
+pub fn write_spill_file(
+    path: &Path,
+    key: &str,
+    data: &[RecordBatch],
+    tables: &[String],
+    expires_at: Instant,
+    encryption_key: Option<&SpillEncryptionKey>,
+) -> Result<()> {
+    let schema = match data.first() {
+        Some(batch) => batch.schema(),
+        None => {
+            return Err(ArrowError::InvalidArgumentError(
+                "no data to spill".to_string(),
+            ))
+        }
+    };
+
+    let mut metadata = schema.metadata().clone();
+    metadata.insert(KEY_METADATA_KEY.to_string(), key.to_string());
+    metadata.insert(TABLES_METADATA_KEY.to_string(), tables.join("\n"));
+    metadata.insert(
+        EXPIRES_AT_METADATA_KEY.to_string(),
+        instant_to_unix_seconds(expires_at).to_string(),
+    );
+
+    let schema = Arc::new(Schema::new_with_metadata(schema.fields().clone(), metadata));
+
+    // The file is written in one go, so the cleartext never touches the disk
+    let mut contents = vec![];
+    {
+        let mut writer = FileWriter::try_new(&mut contents, &schema)?;
+        for batch in data {
+            writer.write(&RecordBatch::try_new(
+                schema.clone(),
+                batch.columns().to_vec(),
+            )?)?;
+        }
+        writer.finish()?;
+    }
+
+    if let Some(encryption_key) = encryption_key {
+        contents = encrypt(encryption_key, &contents)?;
+    }
+
+    File::create(path)?.write_all(&contents)?;
+    Ok(())
+}
+
+fn read_metadata(
+    path: &Path,
+    metadata: &HashMap<String, String>,
+) -> Result<(String, SpilledEntry)> {
+    let key = metadata
+        .get(KEY_METADATA_KEY)
+        .ok_or_else(|| metadata_error("missing key"))?;
+
+    let tables = metadata
+        .get(TABLES_METADATA_KEY)
+        .ok_or_else(|| metadata_error("missing tables"))?
+        .split('\n')
+        .filter(|table| !table.is_empty())
+        .map(|table| table.to_string())
+        .collect();
+
+    let expires_at = metadata
+        .get(EXPIRES_AT_METADATA_KEY)
+        .and_then(|seconds| seconds.parse::<u64>().ok())
+        .ok_or_else(|| metadata_error("missing expiry"))?;
+
+    Ok((
+        key.clone(),
+        SpilledEntry {
+            path: path.to_path_buf(),
+            tables,
+            expires_at: unix_seconds_to_instant(expires_at),
+        },
+    ))
+}
+
+/// Reads the key, tables and expiry of a spill file without decoding its data
+pub fn read_spill_file_metadata(
+    path: &Path,
+    encryption_key: Option<&SpillEncryptionKey>,
+) -> Result<(String, SpilledEntry)> {
+    let reader = open_spill_file(path, encryption_key)?;
+    read_metadata(path, reader.schema().metadata())
+}
+
+/// Reads the data of a spill file, restoring the schema the data had before it was spilled
+pub fn read_spill_file(
+    path: &Path,
+    key: &str,
+    encryption_key: Option<&SpillEncryptionKey>,
+) -> Result<Vec<RecordBatch>> {
+    let reader = open_spill_file(path, encryption_key)?;
+
+    // Keys with colliding hashes share a file, which only holds the last spilled one
+    if reader
+        .schema()
+        .metadata()
+        .get(KEY_METADATA_KEY)
+        .map(String::as_str)
+        != Some(key)
+    {
+        return Err(metadata_error("key doesn't match"));
+    }
+
+    let mut metadata = reader.schema().metadata().clone();
+    metadata.remove(KEY_METADATA_KEY);
+    metadata.remove(TABLES_METADATA_KEY);
+    metadata.remove(EXPIRES_AT_METADATA_KEY);
+
+    let schema = Arc::new(Schema::new_with_metadata(
+        reader.schema().fields().clone(),
+        metadata,
+    ));
+
+    reader
+        .map(|batch| RecordBatch::try_new(schema.clone(), batch?.columns().to_vec()))
+        .collect()
+}
+
+/// Finds the spill files left in the directory, e.g. by a previous run of the proxy
+pub fn spill_files(directory: &Path) -> std::io::Result<Vec<PathBuf>> {
+    let mut paths = vec![];
+
+    for entry in std::fs::read_dir(directory)? {
+        let path = entry?.path();
+        if path.extension().and_then(|extension| extension.to_str()) == Some(SPILL_FILE_EXTENSION) {
+            paths.push(path);
+        }
+    }
+
+    Ok(paths)
+}
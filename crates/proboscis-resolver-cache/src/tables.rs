@@ -0,0 +1,228 @@
+use proboscis_core::utils::{
+    sql::{first_keyword, table_references, TableReferences},
+    transaction::{parse_transaction_statement, split_statements, TransactionStatement},
+};
+use sqlparser::ast::Statement;
+use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+
+/// Tables whose data may be changed by a statement
+#[derive(Clone, Debug, PartialEq)]
+pub enum WrittenTables {
+    None,
+    Tables(Vec<String>),
+    /// The statement may change any table, e.g. because it couldn't be parsed
+    Unknown,
+}
+
+impl Default for WrittenTables {
+    fn default() -> Self {
+        WrittenTables::None
+    }
+}
+
+impl WrittenTables {
+    pub fn merge(&mut self, other: WrittenTables) {
+        *self = match (std::mem::replace(self, WrittenTables::None), other) {
+            (WrittenTables::Unknown, _) | (_, WrittenTables::Unknown) => WrittenTables::Unknown,
+            (WrittenTables::None, other) => other,
+            (this, WrittenTables::None) => this,
+            (WrittenTables::Tables(mut tables), WrittenTables::Tables(other)) => {
+                tables.extend(other);
+                tables.sort();
+                tables.dedup();
+                WrittenTables::Tables(tables)
+            }
+        }
+    }
+}
+
+// Statements which are known not to change any data, if they can't be parsed
+const READ_ONLY_KEYWORDS: &[&str] = &[
+    "SELECT", "SHOW", "SET", "RESET", "BEGIN", "START", "COMMIT", "END", "ROLLBACK",
+];
+
+/// Whether the query ends a transaction, after which its writes become visible to others.
+/// Rolling back to a savepoint or releasing it doesn't.
+pub fn ends_transaction(query: &str) -> bool {
+    split_statements(query).into_iter().any(|statement| {
+        matches!(
+            parse_transaction_statement(statement),
+            Some(TransactionStatement::Commit)
+                | Some(TransactionStatement::Rollback)
+                | Some(TransactionStatement::CommitAndChain)
+                | Some(TransactionStatement::RollbackAndChain)
+        )
+    })
+}
+
+/// Collects the tables that may be changed by the statements of a query.
+/// Statements that can't be parsed are treated as changing every table,
+/// unless they are known to be read-only.
+pub fn written_tables(query: &str) -> WrittenTables {
+    let keyword = first_keyword(query);
+
+    // Not every TRUNCATE form is supported by the parser, so the table names are read directly
+    if keyword == "TRUNCATE" {
+        return truncated_tables(query);
+    }
+
+    let statements = match Parser::parse_sql(&PostgreSqlDialect {}, query) {
+        Ok(statements) => statements,
+        Err(_) if READ_ONLY_KEYWORDS.contains(&keyword.as_str()) => return WrittenTables::None,
+        Err(_) => return WrittenTables::Unknown,
+    };
+
+    let mut written = WrittenTables::None;
+    for statement in &statements {
+        written.merge(statement_written_tables(statement));
+    }
+    written
+}
+
+fn statement_written_tables(statement: &Statement) -> WrittenTables {
+    let tables = match statement {
+        Statement::Query(_)
+        | Statement::SetVariable { .. }
+        | Statement::ShowVariable { .. }
+        | Statement::StartTransaction { .. }
+        | Statement::SetTransaction { .. }
+        | Statement::Commit { .. }
+        | Statement::Rollback { .. } => return WrittenTables::None,
+        Statement::Insert { table_name, .. }
+        | Statement::Update { table_name, .. }
+        | Statement::Delete { table_name, .. }
+        | Statement::Copy { table_name, .. }
+        | Statement::CreateIndex { table_name, .. } => vec![table_name.to_string()],
+        Statement::CreateTable { name, .. }
+        | Statement::CreateView { name, .. }
+        | Statement::AlterTable { name, .. } => vec![name.to_string()],
+        Statement::Drop { names, .. } => names.iter().map(|name| name.to_string()).collect(),
+        _ => return WrittenTables::Unknown,
+    };
+
+    WrittenTables::Tables(tables)
+}
+
+fn truncated_tables(query: &str) -> WrittenTables {
+    let mut tables = vec![];
+
+    for word in query
+        .trim_end_matches(|c: char| c == ';' || c.is_whitespace())
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|word| !word.is_empty())
+        .skip(1)
+    {
+        match word.to_lowercase().as_str() {
+            "table" | "only" => continue,
+            "restart" | "continue" | "cascade" | "restrict" => break,
+            _ => tables.push(word.trim_end_matches('*').to_string()),
+        }
+    }
+
+    if tables.is_empty() {
+        return WrittenTables::Unknown;
+    }
+
+    WrittenTables::Tables(tables)
+}
+
+/// Collects the tables a query reads from, or `None` if that can't be determined reliably.
+/// Only plain SELECT queries, including set operations and subqueries in the FROM clause,
+/// are supported. Subqueries within expressions make the query unsupported.
+pub fn referenced_tables(statement: &Statement) -> Option<Vec<String>> {
+    let TableReferences {
+        mut tables,
+        complete,
+    } = table_references(statement);
+
+    if !complete {
+        return None;
+    }
+
+    tables.sort();
+    tables.dedup();
+
+    Some(tables)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tables_of(query: &str) -> Option<Vec<String>> {
+        let statement = Parser::parse_sql(&PostgreSqlDialect {}, query)
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        referenced_tables(&statement)
+    }
+
+    #[test]
+    fn test_referenced_tables() {
+        assert_eq!(
+            Some(vec!["users".to_string()]),
+            tables_of("SELECT * FROM users")
+        );
+        assert_eq!(
+            Some(vec!["contacts".to_string(), "users".to_string()]),
+            tables_of("SELECT u.id FROM users u JOIN contacts c ON c.user_id = u.id")
+        );
+        assert_eq!(
+            Some(vec!["orders".to_string()]),
+            tables_of("SELECT x.total FROM (SELECT total FROM orders) AS x")
+        );
+        assert_eq!(
+            Some(vec!["admins".to_string(), "users".to_string()]),
+            tables_of("SELECT name FROM users UNION SELECT name FROM admins")
+        );
+    }
+
+    #[test]
+    fn test_unsupported_queries() {
+        assert_eq!(
+            None,
+            tables_of("SELECT * FROM users WHERE id IN (SELECT user_id FROM secrets)")
+        );
+        assert_eq!(
+            None,
+            tables_of("WITH recent AS (SELECT * FROM users) SELECT * FROM recent")
+        );
+        assert_eq!(None, tables_of("DELETE FROM users"));
+    }
+
+    #[test]
+    fn test_ends_transaction() {
+        assert!(ends_transaction("COMMIT"));
+        assert!(ends_transaction("UPDATE users SET id = 4; END"));
+        assert!(ends_transaction("COMMIT AND CHAIN"));
+        assert!(!ends_transaction("ROLLBACK TO SAVEPOINT before_update"));
+        assert!(!ends_transaction("RELEASE before_update"));
+    }
+
+    #[test]
+    fn test_written_tables() {
+        let tables = |tables: &[&str]| {
+            WrittenTables::Tables(tables.iter().map(|table| table.to_string()).collect())
+        };
+
+        assert_eq!(WrittenTables::None, written_tables("SELECT * FROM users"));
+        assert_eq!(
+            tables(&["users"]),
+            written_tables("UPDATE users SET name = 'Max' WHERE id = 1")
+        );
+        assert_eq!(
+            tables(&["public.users"]),
+            written_tables("INSERT INTO public.users (id) VALUES (1)")
+        );
+        assert_eq!(
+            tables(&["orders", "users"]),
+            written_tables("DELETE FROM users; DROP TABLE orders")
+        );
+        assert_eq!(
+            tables(&["orders", "users"]),
+            written_tables("TRUNCATE TABLE users, orders RESTART IDENTITY;")
+        );
+        assert_eq!(WrittenTables::Unknown, written_tables("VACUUM FULL users"));
+    }
+}
@@ -0,0 +1,5 @@
+mod config;
+mod resolver;
+
+pub use config::ClickHouseConfig;
+pub use resolver::ClickHouseResolver;
@@ -0,0 +1,466 @@
+use crate::config::ClickHouseConfig;
+use anyhow::anyhow;
+use arrow::{
+    array::ArrayRef,
+    compute::kernels::cast::cast,
+    datatypes::{DataType, Field as ArrowField, Schema, TimeUnit},
+    ipc::reader::StreamReader,
+    record_batch::RecordBatch,
+};
+use async_trait::async_trait;
+use hyper::{body::to_bytes, client::HttpConnector, Body, Client as HttpClient, Request};
+use proboscis_core::{
+    data::field::with_field_metadata,
+    resolver::{
+        answer_operations, AnswerOperations, Bind, ClientContext, ClientId, Close, Describe,
+        Execute, Operation, Parse, ResolveError, Resolver, SyncResponse,
+    },
+    utils::sql::{first_keyword, is_read_only, parameter_count},
+};
+use proboscis_postgres_protocol::message::{
+    BindParameter, CloseKind, CommandCompleteTag, DescribeKind, ParameterDescription,
+};
+use std::{collections::HashMap, io::Cursor, sync::Arc};
+
+// Parameters are inlined as string literals, ClickHouse converts them where they are compared
+const TEXT_OID: u32 = 25;
+
+fn quote_literal(value: &str) -> String {
+    format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'"))
+}
+
+/// Replaces parameters like `$2` outside of string literals with the literal of their value
+fn inline_parameters(query: &str, params: &[Option<String>]) -> Result<String, ResolveError> {
+    let chars: Vec<char> = query.chars().collect();
+    let mut inlined = String::with_capacity(query.len());
+    let mut in_literal = false;
+    let mut index = 0;
+
+    while index < chars.len() {
+        let c = chars[index];
+        index += 1;
+
+        match c {
+            '\'' => in_literal = !in_literal,
+            '$' if !in_literal => {
+                let digits: String = chars[index..]
+                    .iter()
+                    .take_while(|c| c.is_ascii_digit())
+                    .collect();
+
+                if let Ok(number) = digits.parse::<usize>() {
+                    let param = number
+                        .checked_sub(1)
+                        .and_then(|position| params.get(position))
+                        .ok_or_else(|| anyhow!("the parameter ${} isn't bound", number))?;
+
+                    inlined.push_str(&match param {
+                        Some(value) => quote_literal(value),
+                        None => "NULL".to_string(),
+                    });
+                    index += digits.len();
+                    continue;
+                }
+            }
+            _ => {}
+        }
+
+        inlined.push(c);
+    }
+
+    Ok(inlined)
+}
+
+fn parameter_value(parameter: BindParameter) -> Result<Option<String>, ResolveError> {
+    match parameter {
+        BindParameter::Text(text) => Ok(Some(text)),
+        BindParameter::Binary(_) => {
+            Err("ClickHouse parameters have to be bound in the text format".into())
+        }
+    }
+}
+
+/// Converts columns of types the proxy can't encode, e.g. the timestamps in seconds of DateTime
+fn normalized_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Timestamp(_, timezone) => {
+            DataType::Timestamp(TimeUnit::Microsecond, timezone.clone())
+        }
+        DataType::Date64 => DataType::Date32,
+        DataType::LargeBinary => DataType::Binary,
+        data_type => data_type.clone(),
+    }
+}
+
+fn normalized_schema(schema: &Schema) -> Schema {
+    Schema::new(
+        schema
+            .fields()
+            .iter()
+            .map(|field| {
+                ArrowField::new(
+                    field.name(),
+                    normalized_type(field.data_type()),
+                    field.is_nullable(),
+                )
+            })
+            .collect(),
+    )
+}
+
+/// Reads the Arrow stream ClickHouse responded with, in the schema the proxy expects
+fn read_arrow_stream(bytes: &[u8]) -> Result<(Schema, Vec<RecordBatch>), ResolveError> {
+    // ClickHouse may respond without a stream if there are no rows
+    if bytes.is_empty() {
+        return Ok((Schema::empty(), vec![]));
+    }
+
+    let reader = StreamReader::try_new(Cursor::new(bytes))?;
+    let schema = Arc::new(with_field_metadata(&normalized_schema(&reader.schema())));
+
+    let mut data = vec![];
+    for batch in reader {
+        let columns = batch?
+            .columns()
+            .iter()
+            .zip(schema.fields())
+            .map(|(column, field)| {
+                if column.data_type() == field.data_type() {
+                    Ok(column.clone())
+                } else {
+                    cast(column, field.data_type())
+                }
+            })
+            .collect::<Result<Vec<ArrayRef>, _>>()?;
+
+        data.push(RecordBatch::try_new(schema.clone(), columns)?);
+    }
+
+    Ok((schema.as_ref().clone(), data))
+}
+
+struct Portal {
+    query: String,
+    params: Vec<Option<String>>,
+}
+
+#[derive(Default)]
+struct Client {
+    // Maps a statement to an sql string
+    statements: HashMap<String, String>,
+    portals: HashMap<String, Portal>,
+    // Operations since the last sync, in order
+    pending: Vec<Operation>,
+}
+
+/// Forwards queries to the HTTP interface of ClickHouse, which returns their results as Arrow
+/// streams, so analytical queries can be answered without loading the primary database.
+/// Only statements which read are forwarded, as ClickHouse is used as a replica.
+///
+/// In the extended protocol, parameters are inlined as string literals and executions
+/// always return all rows of the portal.
+pub struct ClickHouseResolver {
+    config: ClickHouseConfig,
+    http: HttpClient<HttpConnector>,
+    clients: HashMap<ClientId, Client>,
+}
+
+impl ClickHouseResolver {
+    pub fn new(config: ClickHouseConfig) -> ClickHouseResolver {
+        ClickHouseResolver {
+            config,
+            http: HttpClient::new(),
+            clients: HashMap::new(),
+        }
+    }
+
+    async fn run_query(&self, query: &str) -> Result<(Schema, Vec<RecordBatch>), ResolveError> {
+        if !is_read_only(query) {
+            return Err(anyhow!(
+                "only statements which read are forwarded to ClickHouse, not {}",
+                first_keyword(query)
+            )
+            .into());
+        }
+
+        let mut request = Request::post(self.config.endpoint());
+        if let Some(user) = &self.config.user {
+            request = request.header("X-ClickHouse-User", user);
+        }
+        if let Some(password) = &self.config.password {
+            request = request.header("X-ClickHouse-Key", password);
+        }
+
+        let body = format!(
+            "{} FORMAT ArrowStream",
+            query.trim_end().trim_end_matches(';')
+        );
+        let request = request
+            .body(Body::from(body))
+            .map_err(|err: hyper::http::Error| anyhow!(err))?;
+
+        let response = self
+            .http
+            .request(request)
+            .await
+            .map_err(|err: hyper::Error| anyhow!("couldn't reach ClickHouse: {}", err))?;
+        let status = response.status();
+        let bytes = to_bytes(response.into_body())
+            .await
+            .map_err(|err: hyper::Error| anyhow!(err))?;
+
+        if !status.is_success() {
+            return Err(anyhow!(
+                "ClickHouse returned an error: {}",
+                String::from_utf8_lossy(&bytes).trim()
+            )
+            .into());
+        }
+
+        read_arrow_stream(&bytes)
+    }
+
+    fn client(&mut self, client_id: ClientId) -> &mut Client {
+        self.clients.entry(client_id).or_default()
+    }
+}
+
+#[async_trait]
+impl AnswerOperations for ClickHouseResolver {
+    async fn answer_describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        let (query, params) = match describe.kind {
+            DescribeKind::Statement => {
+                let query = client
+                    .statements
+                    .get(&describe.name)
+                    .cloned()
+                    .unwrap_or_default();
+
+                // The values aren't known yet, the schema doesn't depend on them
+                let count = parameter_count(&query);
+                responses.push(SyncResponse::ParameterDescription(ParameterDescription {
+                    types: vec![TEXT_OID; count],
+                }));
+
+                (query, vec![None; count])
+            }
+            DescribeKind::Portal => match client.portals.get(&describe.name) {
+                Some(portal) => (portal.query.clone(), portal.params.clone()),
+                None => (String::new(), vec![]),
+            },
+        };
+
+        if !is_read_only(&query) {
+            responses.push(SyncResponse::NoData);
+            return Ok(());
+        }
+
+        let (schema, _) = self
+            .run_query(&format!(
+                "SELECT * FROM ({}) AS described LIMIT 0",
+                inline_parameters(&query, &params)?
+            ))
+            .await?;
+
+        responses.push(SyncResponse::Schema { schema, query });
+        Ok(())
+    }
+
+    async fn answer_execute(
+        &mut self,
+        client_id: ClientId,
+        execute: Execute,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError> {
+        let (query, params) = match self.client(client_id).portals.get(&execute.portal) {
+            Some(portal) => (portal.query.clone(), portal.params.clone()),
+            None => (String::new(), vec![]),
+        };
+
+        if query.trim().is_empty() {
+            responses.push(SyncResponse::EmptyQueryResponse);
+            return Ok(());
+        }
+
+        let (_, data) = self.run_query(&inline_parameters(&query, &params)?).await?;
+        let rows: usize = data.iter().map(|batch| batch.num_rows()).sum();
+
+        responses.push(SyncResponse::Records { data, query });
+        responses.push(SyncResponse::CommandComplete(CommandCompleteTag(format!(
+            "SELECT {}",
+            rows
+        ))));
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Resolver for ClickHouseResolver {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        _context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id);
+        Ok(())
+    }
+
+    async fn query(
+        &mut self,
+        _client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let (_, data) = self.run_query(&query).await?;
+        Ok(data)
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        client.statements.insert(parse.statement_name, parse.query);
+        client.pending.push(Operation::Parse);
+
+        Ok(())
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id)
+            .pending
+            .push(Operation::Describe(describe));
+
+        Ok(())
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        let params = bind
+            .params
+            .into_iter()
+            .map(parameter_value)
+            .collect::<Result<Vec<Option<String>>, ResolveError>>()?;
+
+        let client = self.client(client_id);
+        let query = client
+            .statements
+            .get(&bind.statement)
+            .cloned()
+            .unwrap_or_default();
+
+        client.portals.insert(bind.portal, Portal { query, params });
+        client.pending.push(Operation::Bind);
+
+        Ok(())
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        self.client(client_id)
+            .pending
+            .push(Operation::Execute(execute));
+
+        Ok(())
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let pending = std::mem::take(&mut self.client(client_id).pending);
+
+        let mut responses = answer_operations(self, client_id, pending).await;
+        responses.push(SyncResponse::ReadyForQuery);
+
+        Ok(responses)
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        match close.kind {
+            CloseKind::Statement => client.statements.remove(&close.name).map(|_| ()),
+            CloseKind::Portal => client.portals.remove(&close.name).map(|_| ()),
+        };
+
+        Ok(())
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.clients.remove(&client_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Int64Array, TimestampSecondArray},
+        ipc::writer::StreamWriter,
+    };
+
+    #[test]
+    fn test_inline_parameters() {
+        assert_eq!(
+            "SELECT * FROM t WHERE a = '1' AND b = '$1' AND c = 'it\\'s' AND d = NULL",
+            inline_parameters(
+                "SELECT * FROM t WHERE a = $1 AND b = '$1' AND c = $2 AND d = $3",
+                &[Some("1".to_string()), Some("it's".to_string()), None]
+            )
+            .unwrap()
+        );
+        assert!(inline_parameters("SELECT $2", &[Some("1".to_string())]).is_err());
+    }
+
+    #[test]
+    fn test_read_arrow_stream() {
+        let schema = Arc::new(Schema::new(vec![
+            ArrowField::new("id", DataType::Int64, false),
+            ArrowField::new(
+                "seen_at",
+                DataType::Timestamp(TimeUnit::Second, None),
+                false,
+            ),
+        ]));
+        let batch = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(TimestampSecondArray::from(vec![
+                    1_600_000_000,
+                    1_600_000_060,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let mut bytes = vec![];
+        {
+            let mut writer = StreamWriter::try_new(&mut bytes, &schema).unwrap();
+            writer.write(&batch).unwrap();
+            writer.finish().unwrap();
+        }
+
+        let (schema, data) = read_arrow_stream(&bytes).unwrap();
+        assert_eq!(
+            &DataType::Timestamp(TimeUnit::Microsecond, None),
+            schema.field(1).data_type()
+        );
+        assert!(schema.field(0).metadata().is_some());
+        assert_eq!(2, data[0].num_rows());
+        assert_eq!(
+            1_600_000_060_000_000,
+            data[0]
+                .column(1)
+                .as_any()
+                .downcast_ref::<arrow::array::TimestampMicrosecondArray>()
+                .unwrap()
+                .value(1)
+        );
+
+        assert!(read_arrow_stream(&[]).unwrap().1.is_empty());
+    }
+}
@@ -0,0 +1,477 @@
+use arrow::{datatypes::Schema, record_batch::RecordBatch};
+use async_trait::async_trait;
+use duckdb::{params_from_iter, types::Value, Connection};
+use proboscis_core::{
+    data::field::with_field_metadata,
+    resolver::{
+        answer_operations, AnswerOperations, Bind, ClientContext, ClientId, Close, Describe,
+        Execute, Operation, Parse, ResolveError, Resolver, SyncResponse,
+    },
+    utils::sql::{first_keyword, parameter_count},
+};
+use proboscis_postgres_protocol::message::{
+    BindParameter, CloseKind, CommandCompleteTag, DescribeKind, ParameterDescription,
+};
+use std::{collections::HashMap, path::Path, sync::Arc, sync::Mutex};
+
+// Parameters are bound in their text representation, DuckDB casts them where they are used
+const TEXT_OID: u32 = 25;
+
+fn duckdb_error(err: duckdb::Error) -> ResolveError {
+    ResolveError::Other(anyhow::anyhow!(err))
+}
+
+/// Whether the statement returns rows, judged by its first keyword
+fn returns_rows(query: &str) -> bool {
+    matches!(
+        first_keyword(query).as_str(),
+        "SELECT" | "WITH" | "VALUES" | "TABLE" | "FROM" | "SHOW" | "DESCRIBE" | "SUMMARIZE"
+    )
+}
+
+fn command_tag(query: &str, rows: usize) -> CommandCompleteTag {
+    let keyword = first_keyword(query);
+
+    CommandCompleteTag(match keyword.as_str() {
+        "INSERT" => format!("INSERT 0 {}", rows),
+        "UPDATE" | "DELETE" => format!("{} {}", keyword, rows),
+        _ => keyword,
+    })
+}
+
+fn parameter_value(parameter: BindParameter) -> Value {
+    match parameter {
+        BindParameter::Text(text) => Value::Text(text),
+        BindParameter::Binary(bytes) => Value::Blob(bytes),
+    }
+}
+
+/// Runs a query returning rows, the records are converted to the schema the proxy expects
+fn run_query(
+    connection: &Connection,
+    query: &str,
+    params: &[Value],
+) -> Result<(Schema, Vec<RecordBatch>), ResolveError> {
+    let mut statement = connection.prepare(query).map_err(duckdb_error)?;
+    let batches = statement
+        .query_arrow(params_from_iter(params.iter()))
+        .map_err(duckdb_error)?;
+
+    let schema = Arc::new(with_field_metadata(&batches.get_schema()));
+    let data = batches
+        .map(|batch| RecordBatch::try_new(schema.clone(), batch.columns().to_vec()))
+        .collect::<Result<Vec<RecordBatch>, _>>()?;
+
+    Ok((schema.as_ref().clone(), data))
+}
+
+struct Portal {
+    query: String,
+    params: Vec<Value>,
+}
+
+struct Client {
+    // Each client has its own connection to the database, so settings and temporary tables
+    // of one client aren't visible to others
+    connection: Mutex<Connection>,
+    // Maps a statement to an sql string
+    statements: HashMap<String, String>,
+    portals: HashMap<String, Portal>,
+    // Operations since the last sync, in order
+    pending: Vec<Operation>,
+}
+
+/// Answers queries with an embedded DuckDB database, e.g. to query local Parquet and CSV files
+/// with analytical SQL. Queries run synchronously on the task of the proxy.
+///
+/// In the extended protocol, parameters are bound as text and executions always return
+/// all rows of the portal.
+pub struct DuckDBResolver {
+    database: Mutex<Connection>,
+    clients: HashMap<ClientId, Client>,
+}
+
+impl DuckDBResolver {
+    pub fn new(connection: Connection) -> DuckDBResolver {
+        DuckDBResolver {
+            database: Mutex::new(connection),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Opens the database file, creating it if it doesn't exist
+    pub fn open(path: &Path) -> Result<DuckDBResolver, ResolveError> {
+        Ok(DuckDBResolver::new(
+            Connection::open(path).map_err(duckdb_error)?,
+        ))
+    }
+
+    pub fn open_in_memory() -> Result<DuckDBResolver, ResolveError> {
+        Ok(DuckDBResolver::new(
+            Connection::open_in_memory().map_err(duckdb_error)?,
+        ))
+    }
+
+    /// Makes a Parquet or CSV file queryable as a view, DuckDB infers the format from the extension
+    pub fn with_file_view(self, name: &str, path: &Path) -> Result<DuckDBResolver, ResolveError> {
+        self.database
+            .lock()
+            .expect("DuckDB connection lock poisoned")
+            .execute_batch(&format!(
+                "CREATE OR REPLACE VIEW \"{}\" AS SELECT * FROM '{}'",
+                name.replace('"', "\"\""),
+                path.display().to_string().replace('\'', "''")
+            ))
+            .map_err(duckdb_error)?;
+
+        Ok(self)
+    }
+
+    fn client(&mut self, client_id: ClientId) -> Result<&mut Client, ResolveError> {
+        if !self.clients.contains_key(&client_id) {
+            let connection = self
+                .database
+                .lock()
+                .expect("DuckDB connection lock poisoned")
+                .try_clone()
+                .map_err(duckdb_error)?;
+
+            self.clients.insert(
+                client_id,
+                Client {
+                    connection: Mutex::new(connection),
+                    statements: HashMap::new(),
+                    portals: HashMap::new(),
+                    pending: vec![],
+                },
+            );
+        }
+
+        Ok(self
+            .clients
+            .get_mut(&client_id)
+            .expect("Client was just inserted"))
+    }
+}
+
+#[async_trait]
+impl AnswerOperations for Client {
+    async fn answer_describe(
+        &mut self,
+        _client_id: ClientId,
+        describe: Describe,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError> {
+        let (query, params) = match describe.kind {
+            DescribeKind::Statement => {
+                let query = self
+                    .statements
+                    .get(&describe.name)
+                    .cloned()
+                    .unwrap_or_default();
+
+                // The values aren't known yet, the schema doesn't depend on them
+                let count = parameter_count(&query);
+                responses.push(SyncResponse::ParameterDescription(ParameterDescription {
+                    types: vec![TEXT_OID; count],
+                }));
+
+                (query, vec![Value::Null; count])
+            }
+            DescribeKind::Portal => match self.portals.get(&describe.name) {
+                Some(portal) => (portal.query.clone(), portal.params.clone()),
+                None => (String::new(), vec![]),
+            },
+        };
+
+        if !returns_rows(&query) {
+            responses.push(SyncResponse::NoData);
+            return Ok(());
+        }
+
+        let connection = self
+            .connection
+            .lock()
+            .expect("DuckDB connection lock poisoned");
+        let (schema, _) = run_query(
+            &connection,
+            &format!("SELECT * FROM ({}) AS described LIMIT 0", query),
+            &params,
+        )?;
+
+        responses.push(SyncResponse::Schema { schema, query });
+        Ok(())
+    }
+
+    async fn answer_execute(
+        &mut self,
+        _client_id: ClientId,
+        execute: Execute,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError> {
+        let (query, params) = match self.portals.get(&execute.portal) {
+            Some(portal) => (portal.query.clone(), portal.params.clone()),
+            None => (String::new(), vec![]),
+        };
+
+        if query.trim().is_empty() {
+            responses.push(SyncResponse::EmptyQueryResponse);
+            return Ok(());
+        }
+
+        let connection = self
+            .connection
+            .lock()
+            .expect("DuckDB connection lock poisoned");
+
+        if returns_rows(&query) {
+            let (_, data) = run_query(&connection, &query, &params)?;
+            let rows: usize = data.iter().map(|batch| batch.num_rows()).sum();
+
+            responses.push(SyncResponse::Records { data, query });
+            responses.push(SyncResponse::CommandComplete(CommandCompleteTag(format!(
+                "SELECT {}",
+                rows
+            ))));
+        } else {
+            let rows = connection
+                .prepare(&query)
+                .and_then(|mut statement| statement.execute(params_from_iter(params.iter())))
+                .map_err(duckdb_error)?;
+
+            responses.push(SyncResponse::CommandComplete(command_tag(&query, rows)));
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Resolver for DuckDBResolver {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        _context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id)?;
+        Ok(())
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let client = self.client(client_id)?;
+        let connection = client
+            .connection
+            .lock()
+            .expect("DuckDB connection lock poisoned");
+
+        if returns_rows(&query) {
+            let (_, data) = run_query(&connection, &query, &[])?;
+            return Ok(data);
+        }
+
+        connection.execute_batch(&query).map_err(duckdb_error)?;
+        Ok(vec![])
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        let client = self.client(client_id)?;
+        client.statements.insert(parse.statement_name, parse.query);
+        client.pending.push(Operation::Parse);
+
+        Ok(())
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id)?
+            .pending
+            .push(Operation::Describe(describe));
+
+        Ok(())
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        let client = self.client(client_id)?;
+        let query = client
+            .statements
+            .get(&bind.statement)
+            .cloned()
+            .unwrap_or_default();
+
+        client.portals.insert(
+            bind.portal,
+            Portal {
+                query,
+                params: bind.params.into_iter().map(parameter_value).collect(),
+            },
+        );
+        client.pending.push(Operation::Bind);
+
+        Ok(())
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        self.client(client_id)?
+            .pending
+            .push(Operation::Execute(execute));
+
+        Ok(())
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let client = self.client(client_id)?;
+        let pending = std::mem::take(&mut client.pending);
+
+        let mut responses = answer_operations(client, client_id, pending).await;
+        responses.push(SyncResponse::ReadyForQuery);
+
+        Ok(responses)
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let client = self.client(client_id)?;
+        match close.kind {
+            CloseKind::Statement => client.statements.remove(&close.name).map(|_| ()),
+            CloseKind::Portal => client.portals.remove(&close.name).map(|_| ()),
+        };
+
+        Ok(())
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.clients.remove(&client_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::array::{Int32Array, StringArray};
+
+    #[tokio::test]
+    async fn test_query_csv_file() {
+        let path =
+            std::env::temp_dir().join(format!("proboscis-duckdb-test-{}.csv", std::process::id()));
+        std::fs::write(&path, "id,name\n1,Alice\n2,Bob\n").unwrap();
+
+        let mut resolver = DuckDBResolver::open_in_memory()
+            .unwrap()
+            .with_file_view("contacts", &path)
+            .unwrap();
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        let data = resolver
+            .query(
+                client_id,
+                "SELECT CAST(id AS INTEGER) AS id, name FROM contacts ORDER BY id".to_string(),
+            )
+            .await
+            .unwrap();
+
+        let ids = data[0]
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int32Array>()
+            .unwrap();
+        let names = data[0]
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(vec![1, 2], ids.values().to_vec());
+        assert_eq!("Bob", names.value(1));
+
+        // The proxy describes the columns with the metadata of the fields
+        assert!(data[0].schema().field(0).metadata().is_some());
+
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_extended_query() {
+        let mut resolver = DuckDBResolver::open_in_memory().unwrap();
+        let client_id = ClientId::new_v4();
+
+        resolver
+            .query(
+                client_id,
+                "CREATE TABLE numbers AS SELECT CAST(range AS INTEGER) AS n FROM range(10)"
+                    .to_string(),
+            )
+            .await
+            .unwrap();
+
+        let query = "SELECT n FROM numbers WHERE n < $1".to_string();
+        resolver
+            .parse(
+                client_id,
+                Parse {
+                    statement_name: "s".to_string(),
+                    query: query.clone(),
+                    param_types: vec![],
+                },
+            )
+            .await
+            .unwrap();
+        resolver
+            .bind(
+                client_id,
+                Bind {
+                    statement: "s".to_string(),
+                    portal: "p".to_string(),
+                    params: vec![BindParameter::Text("3".to_string())],
+                    results: vec![],
+                },
+            )
+            .await
+            .unwrap();
+        resolver
+            .describe(
+                client_id,
+                Describe {
+                    kind: DescribeKind::Portal,
+                    name: "p".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        resolver
+            .execute(
+                client_id,
+                Execute {
+                    portal: "p".to_string(),
+                    row_limit: 0,
+                },
+            )
+            .await
+            .unwrap();
+
+        let responses = resolver.sync(client_id).await.unwrap();
+        assert_eq!(6, responses.len());
+        assert!(matches!(responses[0], SyncResponse::ParseComplete));
+        assert!(matches!(responses[1], SyncResponse::BindComplete));
+        assert!(
+            matches!(&responses[2], SyncResponse::Schema { schema, .. } if schema.fields().len() == 1)
+        );
+        match &responses[3] {
+            SyncResponse::Records { data, .. } => assert_eq!(3, data[0].num_rows()),
+            _ => panic!("Expected records"),
+        }
+        assert!(
+            matches!(&responses[4], SyncResponse::CommandComplete(CommandCompleteTag(tag)) if tag == "SELECT 3")
+        );
+        assert!(matches!(responses[5], SyncResponse::ReadyForQuery));
+    }
+}
@@ -0,0 +1,3 @@
+mod resolver;
+
+pub use resolver::{FallbackResolver, LayerMetrics};
@@ -0,0 +1,652 @@
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use proboscis_core::resolver::{
+    sync_error, wait_for_any_idle_message, Bind, ClientContext, ClientId, Close,
+    CommandCompleteTag, CopyOutStream, CopyResponse, Describe, Execute, Parse, ResolveError,
+    Resolver, SyncResponse,
+};
+use proboscis_postgres_protocol::message::{
+    CloseKind, DescribeKind, Error, NotificationResponse, ParameterStatus,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+use tracing::warn;
+
+/// Counters of a layer, shared with whatever reports them
+#[derive(Debug, Default)]
+pub struct LayerMetrics {
+    /// Queries and syncs the layer answered
+    pub answered: AtomicU64,
+    /// Queries and syncs which fell through to the next layer, or failed if it was the last one
+    pub failed: AtomicU64,
+}
+
+impl LayerMetrics {
+    pub fn answered(&self) -> u64 {
+        self.answered.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+}
+
+struct Layer {
+    name: String,
+    resolver: Box<dyn Resolver>,
+    metrics: Arc<LayerMetrics>,
+}
+
+#[derive(Clone)]
+enum Operation {
+    Parse(Parse),
+    Bind(Bind),
+    Describe(Describe),
+    Execute(Execute),
+}
+
+struct Client {
+    context: ClientContext,
+    // Whether each layer has initialized the client
+    initialized: Vec<bool>,
+    // Every prepared statement of the client, so layers answering a later sync can prepare it
+    statements: HashMap<String, Parse>,
+    // The statements each layer has prepared
+    prepared: Vec<HashSet<String>>,
+    // Operations since the last sync, in order
+    pending: Vec<Operation>,
+    // The layer of the copy in progress
+    copying: Option<usize>,
+}
+
+/// Tries its layers in order, e.g. a cache, a snapshot and the live database, answering with
+/// the first one that doesn't fail, so clients are served while a layer is unavailable.
+/// Queries fall through on any error, the error of the last layer is returned.
+///
+/// In the extended protocol the operations are held back until the sync,
+/// all operations of a sync are answered by the same layer.
+pub struct FallbackResolver {
+    layers: Vec<Layer>,
+    clients: HashMap<ClientId, Client>,
+}
+
+impl Default for FallbackResolver {
+    fn default() -> Self {
+        FallbackResolver::new()
+    }
+}
+
+impl FallbackResolver {
+    pub fn new() -> FallbackResolver {
+        FallbackResolver {
+            layers: vec![],
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Adds a layer, which is tried after every layer added before it
+    pub fn add_layer(mut self, name: &str, resolver: Box<dyn Resolver>) -> FallbackResolver {
+        self.layers.push(Layer {
+            name: name.to_string(),
+            resolver,
+            metrics: Arc::new(LayerMetrics::default()),
+        });
+
+        for client in self.clients.values_mut() {
+            client.initialized.push(false);
+            client.prepared.push(HashSet::new());
+        }
+
+        self
+    }
+
+    /// The metrics of every layer by its name, in order
+    pub fn metrics(&self) -> Vec<(String, Arc<LayerMetrics>)> {
+        self.layers
+            .iter()
+            .map(|layer| (layer.name.clone(), layer.metrics.clone()))
+            .collect()
+    }
+
+    fn client(&mut self, client_id: ClientId) -> Result<&mut Client, ResolveError> {
+        self.clients
+            .get_mut(&client_id)
+            .ok_or_else(|| "the client isn't initialized".into())
+    }
+
+    // Layers that were unavailable when the client connected are initialized once they are tried
+    async fn ensure_initialized(
+        &mut self,
+        client_id: ClientId,
+        index: usize,
+    ) -> Result<(), ResolveError> {
+        let client = self
+            .clients
+            .get_mut(&client_id)
+            .ok_or("the client isn't initialized")?;
+
+        if !client.initialized[index] {
+            self.layers[index]
+                .resolver
+                .initialize(client_id, &client.context)
+                .await?;
+            client.initialized[index] = true;
+        }
+
+        Ok(())
+    }
+
+    /// The first layer available for a copy, which can't fall through once its data is sent
+    async fn copy_layer(&mut self, client_id: ClientId) -> Result<usize, ResolveError> {
+        let mut last_error = None;
+        for index in 0..self.layers.len() {
+            match self.ensure_initialized(client_id, index).await {
+                Ok(()) => return Ok(index),
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| "the fallback resolver has no layers".into()))
+    }
+
+    fn copying_layer(&mut self, client_id: ClientId) -> Result<usize, ResolveError> {
+        self.client(client_id)?
+            .copying
+            .ok_or_else(|| "the client has no copy in progress".into())
+    }
+
+    fn record(&self, index: usize, result: &Result<impl Sized, ResolveError>) {
+        let layer = &self.layers[index];
+
+        match result {
+            Ok(_) => {
+                layer.metrics.answered.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(err) => {
+                layer.metrics.failed.fetch_add(1, Ordering::Relaxed);
+                warn!("layer {} failed: {}", layer.name, err);
+            }
+        }
+    }
+
+    /// Sends the operations to the layer and syncs it, statements the operations use but the
+    /// layer hasn't prepared are parsed first and their responses are left out
+    async fn sync_layer(
+        &mut self,
+        client_id: ClientId,
+        index: usize,
+        operations: &[Operation],
+    ) -> Result<Vec<SyncResponse>, ResolveError> {
+        self.ensure_initialized(client_id, index).await?;
+
+        let client = self.client(client_id)?;
+        let mut missing = vec![];
+        let mut parsed = HashSet::new();
+        for operation in operations {
+            let statement = match operation {
+                Operation::Parse(parse) => {
+                    parsed.insert(parse.statement_name.clone());
+                    continue;
+                }
+                Operation::Bind(bind) => &bind.statement,
+                Operation::Describe(Describe {
+                    kind: DescribeKind::Statement,
+                    name,
+                }) => name,
+                _ => continue,
+            };
+
+            if !parsed.contains(statement)
+                && !client.prepared[index].contains(statement)
+                && !missing
+                    .iter()
+                    .any(|parse: &Parse| &parse.statement_name == statement)
+            {
+                if let Some(parse) = client.statements.get(statement) {
+                    missing.push(parse.clone());
+                }
+            }
+        }
+        let injected = missing.len();
+        parsed.extend(missing.iter().map(|parse| parse.statement_name.clone()));
+
+        let resolver = &mut self.layers[index].resolver;
+        let forwarded = async {
+            for parse in missing {
+                resolver.parse(client_id, parse).await?;
+            }
+
+            for operation in operations.iter().cloned() {
+                match operation {
+                    Operation::Parse(parse) => resolver.parse(client_id, parse).await?,
+                    Operation::Bind(bind) => resolver.bind(client_id, bind).await?,
+                    Operation::Describe(describe) => resolver.describe(client_id, describe).await?,
+                    Operation::Execute(execute) => resolver.execute(client_id, execute).await?,
+                }
+            }
+
+            Ok::<(), ResolveError>(())
+        }
+        .await;
+
+        if let Err(err) = forwarded {
+            // Operations the layer queued before failing are discarded
+            let _ = resolver.sync(client_id).await;
+            return Err(err);
+        }
+
+        let mut responses = resolver.sync(client_id).await?;
+        // An error of an injected parse is kept, the statements after it weren't prepared
+        let injected = responses
+            .iter()
+            .take(injected)
+            .take_while(|response| !matches!(response, SyncResponse::Error(_)))
+            .count();
+        responses.drain(..injected);
+
+        if sync_error(&responses).is_none() {
+            let client = self.client(client_id)?;
+            client.prepared[index].extend(parsed);
+        }
+
+        Ok(responses)
+    }
+}
+
+#[async_trait]
+impl Resolver for FallbackResolver {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        let mut initialized = vec![];
+        let mut last_error = None;
+        for layer in &mut self.layers {
+            match layer.resolver.initialize(client_id, context).await {
+                Ok(()) => initialized.push(true),
+                Err(err) => {
+                    warn!(
+                        "layer {} couldn't initialize the client: {}",
+                        layer.name, err
+                    );
+                    initialized.push(false);
+                    last_error = Some(err);
+                }
+            }
+        }
+
+        if !initialized.contains(&true) {
+            return Err(last_error.unwrap_or_else(|| "the fallback resolver has no layers".into()));
+        }
+
+        self.clients.insert(
+            client_id,
+            Client {
+                context: context.clone(),
+                prepared: vec![HashSet::new(); initialized.len()],
+                initialized,
+                statements: HashMap::new(),
+                pending: vec![],
+                copying: None,
+            },
+        );
+
+        Ok(())
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let mut last_error = None;
+        for index in 0..self.layers.len() {
+            let result = match self.ensure_initialized(client_id, index).await {
+                Ok(()) => {
+                    self.layers[index]
+                        .resolver
+                        .query(client_id, query.clone())
+                        .await
+                }
+                Err(err) => Err(err),
+            };
+            self.record(index, &result);
+
+            match result {
+                Ok(data) => return Ok(data),
+                Err(err) => last_error = Some(err),
+            }
+        }
+
+        Err(last_error.unwrap_or_else(|| "the fallback resolver has no layers".into()))
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        let client = self.client(client_id)?;
+        client
+            .statements
+            .insert(parse.statement_name.clone(), parse.clone());
+        for prepared in &mut client.prepared {
+            prepared.remove(&parse.statement_name);
+        }
+        client.pending.push(Operation::Parse(parse));
+
+        Ok(())
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id)?
+            .pending
+            .push(Operation::Describe(describe));
+
+        Ok(())
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        self.client(client_id)?.pending.push(Operation::Bind(bind));
+
+        Ok(())
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        self.client(client_id)?
+            .pending
+            .push(Operation::Execute(execute));
+
+        Ok(())
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let operations = std::mem::take(&mut self.client(client_id)?.pending);
+
+        let mut last_failure = None;
+        for index in 0..self.layers.len() {
+            let result = self.sync_layer(client_id, index, &operations).await;
+
+            // Errors the layer answered in place fall through like failed syncs
+            let error = match &result {
+                Ok(responses) => sync_error(responses).cloned().map(ResolveError::Target),
+                Err(_) => None,
+            };
+            match error {
+                Some(error) => self.record(index, &Err::<(), _>(error)),
+                None => self.record(index, &result),
+            }
+
+            match result {
+                Ok(responses) if sync_error(&responses).is_none() => return Ok(responses),
+                result => last_failure = Some(result),
+            }
+        }
+
+        last_failure.unwrap_or_else(|| Err("the fallback resolver has no layers".into()))
+    }
+
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        let index = self.copy_layer(client_id).await?;
+        let result = self.layers[index].resolver.copy_in(client_id, query).await;
+        self.record(index, &result);
+
+        let response = result?;
+        self.client(client_id)?.copying = Some(index);
+
+        Ok(response)
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        let index = self.copying_layer(client_id)?;
+        self.layers[index].resolver.copy_data(client_id, data).await
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        let index = self.copying_layer(client_id)?;
+        self.client(client_id)?.copying = None;
+        self.layers[index].resolver.copy_done(client_id).await
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        let index = self.copying_layer(client_id)?;
+        self.client(client_id)?.copying = None;
+        self.layers[index]
+            .resolver
+            .copy_fail(client_id, message)
+            .await
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        let index = self.copy_layer(client_id).await?;
+        self.layers[index].resolver.copy_out(client_id, query).await
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let client = self.client(client_id)?;
+        if close.kind == CloseKind::Statement {
+            client.statements.remove(&close.name);
+        }
+
+        let initialized = client.initialized.clone();
+        for (layer, initialized) in self.layers.iter_mut().zip(initialized) {
+            if initialized {
+                if let Err(err) = layer.resolver.close(client_id, close.clone()).await {
+                    warn!(
+                        "layer {} couldn't close {}: {}",
+                        layer.name, close.name, err
+                    );
+                }
+            }
+        }
+
+        if close.kind == CloseKind::Statement {
+            for prepared in &mut self.client(client_id)?.prepared {
+                prepared.remove(&close.name);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Layers the request fell through leave their notices as well
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.resolver.take_notices(client_id))
+            .collect()
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.resolver.take_parameter_changes(client_id))
+            .collect()
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.layers
+            .iter_mut()
+            .flat_map(|layer| layer.resolver.take_notifications(client_id))
+            .collect()
+    }
+
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        wait_for_any_idle_message(
+            self.layers.iter_mut().map(|layer| &mut layer.resolver),
+            client_id,
+        )
+        .await
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        for layer in self.layers.iter_mut() {
+            layer.resolver.read_idle_messages(client_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        let client = match self.clients.remove(&client_id) {
+            Some(client) => client,
+            None => return Ok(()),
+        };
+
+        for (layer, initialized) in self.layers.iter_mut().zip(client.initialized) {
+            if initialized {
+                if let Err(err) = layer.resolver.terminate(client_id).await {
+                    warn!(
+                        "layer {} couldn't terminate the client: {}",
+                        layer.name, err
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+    };
+    use proboscis_resolver_mock::{MockResolver, MockResponse};
+
+    fn batch() -> RecordBatch {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(Int32Array::from(vec![1]))]).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_to_next_layer() {
+        let mut resolver = FallbackResolver::new()
+            .add_layer(
+                "primary",
+                Box::new(
+                    MockResolver::new().with_fallback(MockResponse::error("connection refused")),
+                ),
+            )
+            .add_layer(
+                "snapshot",
+                Box::new(
+                    MockResolver::new()
+                        .on_query("SELECT id FROM contacts", MockResponse::rows(vec![batch()])),
+                ),
+            );
+        let metrics = resolver.metrics();
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        let data = resolver
+            .query(client_id, "SELECT id FROM contacts".to_string())
+            .await
+            .unwrap();
+        assert_eq!(1, data[0].num_rows());
+
+        let err = resolver
+            .query(client_id, "SELECT id FROM orders".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            "the mock has no response for 'SELECT id FROM orders'",
+            err.to_string()
+        );
+
+        assert_eq!("primary", metrics[0].0);
+        assert_eq!(0, metrics[0].1.answered());
+        assert_eq!(2, metrics[0].1.failed());
+        assert_eq!(1, metrics[1].1.answered());
+        assert_eq!(1, metrics[1].1.failed());
+    }
+
+    #[tokio::test]
+    async fn test_sync_prepares_statements_on_fallback_layer() {
+        let mut resolver = FallbackResolver::new()
+            .add_layer(
+                "primary",
+                Box::new(MockResolver::new().on_query(
+                    "SELECT id FROM contacts WHERE id = $1",
+                    MockResponse::error("connection lost"),
+                )),
+            )
+            .add_layer(
+                "snapshot",
+                Box::new(MockResolver::new().with_fallback(MockResponse::rows(vec![batch()]))),
+            );
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        resolver
+            .parse(
+                client_id,
+                Parse {
+                    statement_name: "by_id".to_string(),
+                    query: "SELECT id FROM contacts WHERE id = $1".to_string(),
+                    param_types: vec![],
+                },
+            )
+            .await
+            .unwrap();
+        let responses = resolver.sync(client_id).await.unwrap();
+        assert!(matches!(responses[0], SyncResponse::ParseComplete));
+
+        // The statement was prepared by the primary, which fails executing it
+        resolver
+            .bind(
+                client_id,
+                Bind {
+                    statement: "by_id".to_string(),
+                    portal: "".to_string(),
+                    params: vec![],
+                    results: vec![],
+                },
+            )
+            .await
+            .unwrap();
+        resolver
+            .execute(
+                client_id,
+                Execute {
+                    portal: "".to_string(),
+                    row_limit: 0,
+                },
+            )
+            .await
+            .unwrap();
+
+        let responses = resolver.sync(client_id).await.unwrap();
+        assert_eq!(4, responses.len());
+        assert!(matches!(responses[0], SyncResponse::BindComplete));
+        assert!(matches!(responses[1], SyncResponse::Records { .. }));
+    }
+}
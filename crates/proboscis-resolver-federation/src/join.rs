@@ -0,0 +1,295 @@
+use crate::plan::{ColumnReference, JoinPlan, Projection};
+use arrow::{
+    array::{ArrayRef, UInt32Array},
+    compute::kernels::{concat::concat, take::take},
+    datatypes::{Field, Schema},
+    error::{ArrowError, Result},
+    record_batch::RecordBatch,
+    util::display::array_value_to_string,
+};
+use std::{collections::HashMap, sync::Arc};
+
+fn plan_error(message: String) -> ArrowError {
+    ArrowError::InvalidArgumentError(message)
+}
+
+/// The rows of one or more joined tables, with the qualifier of the table of each column
+pub struct Relation {
+    qualifiers: Vec<String>,
+    fields: Vec<Field>,
+    columns: Vec<ArrayRef>,
+    rows: usize,
+}
+
+impl Relation {
+    /// The rows of a table, the batches have to share a schema
+    pub fn new(qualifier: &str, batches: &[RecordBatch]) -> Result<Relation> {
+        let schema = batches
+            .first()
+            .map(|batch| batch.schema())
+            .ok_or_else(|| plan_error(format!("{} returned no columns", qualifier)))?;
+
+        let columns = (0..schema.fields().len())
+            .map(|index| {
+                let arrays: Vec<&dyn arrow::array::Array> = batches
+                    .iter()
+                    .map(|batch| batch.column(index).as_ref())
+                    .collect();
+                concat(&arrays)
+            })
+            .collect::<Result<Vec<ArrayRef>>>()?;
+
+        Ok(Relation {
+            qualifiers: vec![qualifier.to_string(); schema.fields().len()],
+            fields: schema.fields().clone(),
+            columns,
+            rows: batches.iter().map(|batch| batch.num_rows()).sum(),
+        })
+    }
+
+    fn find(&self, column: &ColumnReference) -> Option<usize> {
+        let mut matches = self
+            .fields
+            .iter()
+            .zip(self.qualifiers.iter())
+            .enumerate()
+            .filter(|(_, (field, qualifier))| {
+                field.name().eq_ignore_ascii_case(&column.name)
+                    && column
+                        .qualifier
+                        .as_ref()
+                        .map_or(true, |expected| expected.eq_ignore_ascii_case(qualifier))
+            })
+            .map(|(index, _)| index);
+
+        match (matches.next(), matches.next()) {
+            (Some(index), None) => Some(index),
+            _ => None,
+        }
+    }
+
+    // Rows with a null in any key column don't match any other row
+    fn key(&self, columns: &[usize], row: usize) -> Result<Option<Vec<String>>> {
+        let mut key = vec![];
+        for column in columns {
+            if self.columns[*column].is_null(row) {
+                return Ok(None);
+            }
+            key.push(array_value_to_string(&self.columns[*column], row)?);
+        }
+        Ok(Some(key))
+    }
+
+    fn take(&self, indices: &UInt32Array) -> Result<Vec<ArrayRef>> {
+        self.columns
+            .iter()
+            .map(|column| take(column.as_ref(), indices, None))
+            .collect()
+    }
+
+    /// Inner join of the relations on equal values of the column pairs, by hashing the right one
+    pub fn join(
+        self,
+        right: Relation,
+        conditions: &[(ColumnReference, ColumnReference)],
+    ) -> Result<Relation> {
+        let mut left_columns = vec![];
+        let mut right_columns = vec![];
+        for (a, b) in conditions {
+            let (left_column, right_column) = match (self.find(a), right.find(b)) {
+                (Some(left_column), Some(right_column)) => (left_column, right_column),
+                _ => match (self.find(b), right.find(a)) {
+                    (Some(left_column), Some(right_column)) => (left_column, right_column),
+                    _ => {
+                        return Err(plan_error(format!(
+                            "the join condition of {} and {} doesn't refer to unique columns of both sides",
+                            a.name, b.name
+                        )))
+                    }
+                },
+            };
+            left_columns.push(left_column);
+            right_columns.push(right_column);
+        }
+
+        let mut right_rows: HashMap<Vec<String>, Vec<u32>> = HashMap::new();
+        for row in 0..right.rows {
+            if let Some(key) = right.key(&right_columns, row)? {
+                right_rows.entry(key).or_default().push(row as u32);
+            }
+        }
+
+        let mut left_indices = vec![];
+        let mut right_indices = vec![];
+        for row in 0..self.rows {
+            let matching = match self.key(&left_columns, row)? {
+                Some(key) => right_rows.get(&key),
+                None => None,
+            };
+
+            for right_row in matching.into_iter().flatten() {
+                left_indices.push(row as u32);
+                right_indices.push(*right_row);
+            }
+        }
+
+        let mut columns = self.take(&UInt32Array::from(left_indices.clone()))?;
+        columns.extend(right.take(&UInt32Array::from(right_indices))?);
+
+        Ok(Relation {
+            qualifiers: [self.qualifiers, right.qualifiers].concat(),
+            fields: [self.fields, right.fields].concat(),
+            columns,
+            rows: left_indices.len(),
+        })
+    }
+
+    /// The selected columns as a batch, the fields keep their metadata when they are renamed
+    pub fn project(self, projection: &[Projection]) -> Result<RecordBatch> {
+        let mut indices = vec![];
+        for item in projection {
+            match item {
+                Projection::Wildcard => {
+                    indices.extend((0..self.fields.len()).map(|index| (index, None)))
+                }
+                Projection::QualifiedWildcard(qualifier) => indices.extend(
+                    self.qualifiers
+                        .iter()
+                        .enumerate()
+                        .filter(|(_, candidate)| candidate.eq_ignore_ascii_case(qualifier))
+                        .map(|(index, _)| (index, None)),
+                ),
+                Projection::Column { column, alias } => {
+                    let index = self.find(column).ok_or_else(|| {
+                        plan_error(format!(
+                            "the column {} is unknown or ambiguous",
+                            column.name
+                        ))
+                    })?;
+                    indices.push((index, alias.clone()));
+                }
+            }
+        }
+
+        let fields = indices
+            .iter()
+            .map(|(index, alias)| {
+                let field = &self.fields[*index];
+                match alias {
+                    Some(alias) => {
+                        let mut renamed =
+                            Field::new(alias, field.data_type().clone(), field.is_nullable());
+                        renamed.set_metadata(field.metadata().clone());
+                        renamed
+                    }
+                    None => field.clone(),
+                }
+            })
+            .collect();
+        let columns = indices
+            .iter()
+            .map(|(index, _)| self.columns[*index].clone())
+            .collect();
+
+        RecordBatch::try_new(Arc::new(Schema::new(fields)), columns)
+    }
+}
+
+/// Joins the rows of the tables of the plan, in the order of the plan, and selects its columns
+pub fn execute_join(plan: &JoinPlan, tables: Vec<Vec<RecordBatch>>) -> Result<RecordBatch> {
+    let mut relations = plan
+        .tables
+        .iter()
+        .zip(tables.iter())
+        .map(|(table, batches)| Relation::new(&table.qualifier, batches));
+
+    let mut joined = relations
+        .next()
+        .ok_or_else(|| plan_error("the join has no tables".to_string()))??;
+    for (relation, conditions) in relations.zip(plan.conditions.iter()) {
+        joined = joined.join(relation?, conditions)?;
+    }
+
+    joined.project(&plan.projection)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::{join_plan, JoinedTable};
+    use arrow::{
+        array::{Int32Array, StringArray},
+        datatypes::DataType,
+    };
+    use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+
+    fn users() -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2, 3])),
+                Arc::new(StringArray::from(vec!["Max", "Erika", "Anna"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn events() -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("user_id", DataType::Int32, true),
+            Field::new("kind", DataType::Utf8, false),
+        ]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![Some(2), Some(1), Some(2), None])),
+                Arc::new(StringArray::from(vec!["login", "login", "logout", "login"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_execute_join() {
+        let statement = Parser::parse_sql(
+            &PostgreSqlDialect {},
+            "SELECT u.name, e.kind AS event FROM users u JOIN events e ON e.user_id = u.id",
+        )
+        .unwrap()
+        .remove(0);
+        let plan = join_plan(&statement).unwrap();
+        assert_eq!(
+            JoinedTable {
+                name: "events".to_string(),
+                qualifier: "e".to_string()
+            },
+            plan.tables[1]
+        );
+
+        let batch = execute_join(&plan, vec![vec![users()], vec![events()]]).unwrap();
+
+        assert_eq!("event", batch.schema().field(1).name());
+        let names = batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let kinds = batch
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        let rows: Vec<(&str, &str)> = (0..batch.num_rows())
+            .map(|row| (names.value(row), kinds.value(row)))
+            .collect();
+
+        assert_eq!(
+            vec![("Max", "login"), ("Erika", "login"), ("Erika", "logout")],
+            rows
+        );
+    }
+}
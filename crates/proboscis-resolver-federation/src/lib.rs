@@ -0,0 +1,5 @@
+mod join;
+mod plan;
+mod resolver;
+
+pub use resolver::FederatedResolver;
@@ -0,0 +1,251 @@
+use sqlparser::ast::{
+    BinaryOperator, Expr, Ident, JoinConstraint, JoinOperator, SelectItem, SetExpr, Statement,
+    TableFactor,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnReference {
+    /// The alias or name of the table, if the column is qualified
+    pub qualifier: Option<String>,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Projection {
+    Wildcard,
+    QualifiedWildcard(String),
+    Column {
+        column: ColumnReference,
+        alias: Option<String>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinedTable {
+    /// The name as written in the query
+    pub name: String,
+    /// The name its columns are qualified with, its alias if it has one
+    pub qualifier: String,
+}
+
+/// A join of tables of different sources, which is executed by the proxy.
+/// Only inner joins on equal columns, without any filtering, grouping or sorting, are supported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JoinPlan {
+    pub tables: Vec<JoinedTable>,
+    /// The equal columns joining each table after the first one to the ones before it
+    pub conditions: Vec<Vec<(ColumnReference, ColumnReference)>>,
+    pub projection: Vec<Projection>,
+}
+
+// Unquoted identifiers are case insensitive
+fn identifier(ident: &Ident) -> String {
+    match ident.quote_style {
+        Some(_) => ident.value.clone(),
+        None => ident.value.to_lowercase(),
+    }
+}
+
+fn column_reference(expr: &Expr) -> Result<ColumnReference, String> {
+    match expr {
+        Expr::Identifier(ident) => Ok(ColumnReference {
+            qualifier: None,
+            name: identifier(ident),
+        }),
+        Expr::CompoundIdentifier(idents) if idents.len() >= 2 => Ok(ColumnReference {
+            qualifier: Some(identifier(&idents[idents.len() - 2])),
+            name: identifier(&idents[idents.len() - 1]),
+        }),
+        _ => Err(format!("'{}' isn't a column", expr)),
+    }
+}
+
+fn equalities(
+    expr: &Expr,
+    conditions: &mut Vec<(ColumnReference, ColumnReference)>,
+) -> Result<(), String> {
+    match expr {
+        Expr::Nested(expr) => equalities(expr, conditions),
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::And,
+            right,
+        } => {
+            equalities(left, conditions)?;
+            equalities(right, conditions)
+        }
+        Expr::BinaryOp {
+            left,
+            op: BinaryOperator::Eq,
+            right,
+        } => {
+            conditions.push((column_reference(left)?, column_reference(right)?));
+            Ok(())
+        }
+        _ => Err(format!(
+            "only equal columns can join tables of different sources, not '{}'",
+            expr
+        )),
+    }
+}
+
+fn joined_table(factor: &TableFactor) -> Result<JoinedTable, String> {
+    match factor {
+        TableFactor::Table { name, alias, .. } => Ok(JoinedTable {
+            name: name.to_string(),
+            qualifier: match alias {
+                Some(alias) => identifier(&alias.name),
+                None => name.0.last().map(identifier).unwrap_or_default(),
+            },
+        }),
+        _ => Err(format!(
+            "only tables can be joined across sources, not '{}'",
+            factor
+        )),
+    }
+}
+
+pub fn join_plan(statement: &Statement) -> Result<JoinPlan, String> {
+    let unsupported = |clause: &str| Err(format!("{} isn't supported across sources", clause));
+
+    let query = match statement {
+        Statement::Query(query) => query,
+        _ => return unsupported("a statement other than SELECT"),
+    };
+
+    if query.with.is_some() {
+        return unsupported("WITH");
+    }
+    if !query.order_by.is_empty() {
+        return unsupported("ORDER BY");
+    }
+    if query.limit.is_some() || query.offset.is_some() || query.fetch.is_some() {
+        return unsupported("LIMIT");
+    }
+
+    let select = match &query.body {
+        SetExpr::Select(select) => select,
+        _ => return unsupported("a set operation"),
+    };
+
+    if select.distinct {
+        return unsupported("DISTINCT");
+    }
+    if select.selection.is_some() {
+        return unsupported("WHERE");
+    }
+    if !select.group_by.is_empty() || select.having.is_some() {
+        return unsupported("GROUP BY");
+    }
+
+    let from = match select.from.as_slice() {
+        [from] => from,
+        _ => return unsupported("a FROM clause without JOIN ... ON"),
+    };
+
+    let mut tables = vec![joined_table(&from.relation)?];
+    let mut conditions = vec![];
+    for join in &from.joins {
+        let constraint = match &join.join_operator {
+            JoinOperator::Inner(JoinConstraint::On(constraint)) => constraint,
+            _ => return unsupported("a join other than INNER JOIN ... ON"),
+        };
+
+        let mut join_conditions = vec![];
+        equalities(constraint, &mut join_conditions)?;
+
+        tables.push(joined_table(&join.relation)?);
+        conditions.push(join_conditions);
+    }
+
+    let projection = select
+        .projection
+        .iter()
+        .map(|item| match item {
+            SelectItem::Wildcard => Ok(Projection::Wildcard),
+            SelectItem::QualifiedWildcard(name) => Ok(Projection::QualifiedWildcard(
+                name.0.last().map(identifier).unwrap_or_default(),
+            )),
+            SelectItem::UnnamedExpr(expr) => Ok(Projection::Column {
+                column: column_reference(expr)?,
+                alias: None,
+            }),
+            SelectItem::ExprWithAlias { expr, alias } => Ok(Projection::Column {
+                column: column_reference(expr)?,
+                alias: Some(identifier(alias)),
+            }),
+        })
+        .collect::<Result<Vec<Projection>, String>>()?;
+
+    Ok(JoinPlan {
+        tables,
+        conditions,
+        projection,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+
+    fn parse(query: &str) -> Statement {
+        Parser::parse_sql(&PostgreSqlDialect {}, query)
+            .unwrap()
+            .remove(0)
+    }
+
+    #[test]
+    fn test_join_plan() {
+        let plan = join_plan(&parse(
+            "SELECT u.name, e.kind AS event FROM users u JOIN events e ON u.id = e.user_id",
+        ))
+        .unwrap();
+
+        assert_eq!(
+            vec![
+                JoinedTable {
+                    name: "users".to_string(),
+                    qualifier: "u".to_string()
+                },
+                JoinedTable {
+                    name: "events".to_string(),
+                    qualifier: "e".to_string()
+                }
+            ],
+            plan.tables
+        );
+        assert_eq!(
+            vec![vec![(
+                ColumnReference {
+                    qualifier: Some("u".to_string()),
+                    name: "id".to_string()
+                },
+                ColumnReference {
+                    qualifier: Some("e".to_string()),
+                    name: "user_id".to_string()
+                }
+            )]],
+            plan.conditions
+        );
+        assert_eq!(
+            Projection::Column {
+                column: ColumnReference {
+                    qualifier: Some("e".to_string()),
+                    name: "kind".to_string()
+                },
+                alias: Some("event".to_string())
+            },
+            plan.projection[1]
+        );
+
+        assert!(join_plan(&parse(
+            "SELECT * FROM users u JOIN events e ON u.id = e.user_id WHERE e.kind = 'login'"
+        ))
+        .is_err());
+        assert!(join_plan(&parse(
+            "SELECT * FROM users u LEFT JOIN events e ON u.id = e.user_id"
+        ))
+        .is_err());
+    }
+}
@@ -0,0 +1,395 @@
+use crate::{
+    join::execute_join,
+    plan::{join_plan, JoinPlan},
+};
+use anyhow::anyhow;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use proboscis_core::{
+    resolver::{
+        sync_error, wait_for_any_idle_message, Bind, ClientContext, ClientId, Close,
+        CommandCompleteTag, CopyOutStream, CopyResponse, Describe, Execute, Parse, ResolveError,
+        Resolver, SyncResponse,
+    },
+    utils::sql::{table_references, unqualified_table_name},
+};
+use proboscis_postgres_protocol::message::{
+    CloseKind, DescribeKind, Error, NotificationResponse, ParameterStatus,
+};
+use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+use std::collections::HashMap;
+
+const DEFAULT_SOURCE: usize = 0;
+
+enum Route {
+    Source(usize),
+    /// The plan with the source of each of its tables
+    Join(JoinPlan, Vec<usize>),
+}
+
+#[derive(Default)]
+struct Client {
+    // Maps statements and portals to the source they were sent to
+    statements: HashMap<String, usize>,
+    portals: HashMap<String, usize>,
+    // Sources with operations since the last sync, in the order of their first operation
+    pending: Vec<usize>,
+    // The source of the copy in progress
+    copying: Option<usize>,
+}
+
+impl Client {
+    fn touch(&mut self, source: usize) {
+        if !self.pending.contains(&source) {
+            self.pending.push(source);
+        }
+    }
+}
+
+/// Routes queries to the resolver of the tables they reference, e.g. users to a postgres
+/// resolver and events to one of parquet files. Tables which aren't assigned to a source
+/// are read from the default source, as are queries which can't be parsed.
+///
+/// Simple queries joining tables of different sources are executed by pulling every table
+/// and joining them in the proxy, which is limited to inner joins on equal columns
+/// without filtering, grouping or sorting.
+pub struct FederatedResolver {
+    // The default source comes first
+    sources: Vec<Box<dyn Resolver>>,
+    // Maps unqualified table names to the index of their source
+    tables: HashMap<String, usize>,
+    clients: HashMap<ClientId, Client>,
+}
+
+impl FederatedResolver {
+    pub fn new(default_source: Box<dyn Resolver>) -> FederatedResolver {
+        FederatedResolver {
+            sources: vec![default_source],
+            tables: HashMap::new(),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Reads the tables from the resolver
+    pub fn add_source(mut self, tables: &[&str], resolver: Box<dyn Resolver>) -> FederatedResolver {
+        self.sources.push(resolver);
+        let source = self.sources.len() - 1;
+
+        for table in tables {
+            self.tables.insert(unqualified_table_name(table), source);
+        }
+
+        self
+    }
+
+    fn source_of_table(&self, table: &str) -> usize {
+        *self
+            .tables
+            .get(&unqualified_table_name(table))
+            .unwrap_or(&DEFAULT_SOURCE)
+    }
+
+    fn route(&self, query: &str) -> Result<Route, ResolveError> {
+        let statements = match Parser::parse_sql(&PostgreSqlDialect {}, query) {
+            Ok(statements) => statements,
+            Err(_) => return Ok(Route::Source(DEFAULT_SOURCE)),
+        };
+
+        let mut sources = vec![];
+        for statement in &statements {
+            for table in table_references(statement).tables {
+                let source = self.source_of_table(&table);
+                if !sources.contains(&source) {
+                    sources.push(source);
+                }
+            }
+        }
+
+        let statement = match (sources.as_slice(), statements.as_slice()) {
+            ([], _) => return Ok(Route::Source(DEFAULT_SOURCE)),
+            ([source], _) => return Ok(Route::Source(*source)),
+            (_, [statement]) => statement,
+            _ => {
+                return Err(
+                    "a query of multiple statements can't read tables of different sources".into(),
+                )
+            }
+        };
+
+        let plan = join_plan(statement).map_err(|message| {
+            anyhow!(
+                "the query reads tables of different sources, but {}",
+                message
+            )
+        })?;
+        let table_sources = plan
+            .tables
+            .iter()
+            .map(|table| self.source_of_table(&table.name))
+            .collect();
+
+        Ok(Route::Join(plan, table_sources))
+    }
+
+    /// The source of the table a copy reads or writes, copies of a query go to the source of
+    /// the query's tables
+    fn copy_source(&self, query: &str) -> Result<usize, ResolveError> {
+        let target = query.trim_start().get(4..).unwrap_or_default().trim_start();
+
+        if let Some(inner) = target.strip_prefix('(') {
+            let end = inner.rfind(')').unwrap_or_else(|| inner.len());
+            return match self.route(&inner[..end])? {
+                Route::Source(source) => Ok(source),
+                Route::Join(_, _) => Err(cross_source_statement()),
+            };
+        }
+
+        let table = target
+            .split(|c: char| c.is_whitespace() || c == '(')
+            .next()
+            .unwrap_or_default();
+        Ok(self.source_of_table(table))
+    }
+
+    fn copying_source(&mut self, client_id: ClientId) -> Result<usize, ResolveError> {
+        self.client(client_id)
+            .copying
+            .ok_or_else(|| "the client has no copy in progress".into())
+    }
+
+    fn client(&mut self, client_id: ClientId) -> &mut Client {
+        self.clients.entry(client_id).or_default()
+    }
+}
+
+fn cross_source_statement() -> ResolveError {
+    "queries joining tables of different sources are only supported as simple queries".into()
+}
+
+#[async_trait]
+impl Resolver for FederatedResolver {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        for source in &mut self.sources {
+            source.initialize(client_id, context).await?;
+        }
+
+        self.client(client_id);
+        Ok(())
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let (plan, table_sources) = match self.route(&query)? {
+            Route::Source(source) => return self.sources[source].query(client_id, query).await,
+            Route::Join(plan, table_sources) => (plan, table_sources),
+        };
+
+        let mut tables = vec![];
+        for (table, source) in plan.tables.iter().zip(table_sources) {
+            tables.push(
+                self.sources[source]
+                    .query(client_id, format!("SELECT * FROM {}", table.name))
+                    .await?,
+            );
+        }
+
+        Ok(vec![execute_join(&plan, tables)?])
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        let source = match self.route(&parse.query)? {
+            Route::Source(source) => source,
+            Route::Join(_, _) => return Err(cross_source_statement()),
+        };
+
+        let client = self.client(client_id);
+        client
+            .statements
+            .insert(parse.statement_name.clone(), source);
+        client.touch(source);
+
+        self.sources[source].parse(client_id, parse).await
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        let sources = match describe.kind {
+            DescribeKind::Statement => &client.statements,
+            DescribeKind::Portal => &client.portals,
+        };
+        let source = *sources.get(&describe.name).unwrap_or(&DEFAULT_SOURCE);
+        client.touch(source);
+
+        self.sources[source].describe(client_id, describe).await
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        let source = *client
+            .statements
+            .get(&bind.statement)
+            .unwrap_or(&DEFAULT_SOURCE);
+        client.portals.insert(bind.portal.clone(), source);
+        client.touch(source);
+
+        self.sources[source].bind(client_id, bind).await
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        let source = *client
+            .portals
+            .get(&execute.portal)
+            .unwrap_or(&DEFAULT_SOURCE);
+        client.touch(source);
+
+        self.sources[source].execute(client_id, execute).await
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let mut pending = std::mem::take(&mut self.client(client_id).pending);
+        if pending.is_empty() {
+            pending.push(DEFAULT_SOURCE);
+        }
+
+        // Every source ends its responses with ReadyForQuery, only the last one is kept
+        let mut responses = vec![];
+        for source in pending {
+            if matches!(responses.last(), Some(SyncResponse::ReadyForQuery)) {
+                responses.pop();
+            }
+
+            let answered = self.sources[source].sync(client_id).await?;
+            // The operations after an error are skipped, the later sources are only synced
+            match sync_error(&responses) {
+                Some(_) => responses.extend(
+                    answered
+                        .into_iter()
+                        .filter(|response| matches!(response, SyncResponse::ReadyForQuery)),
+                ),
+                None => responses.extend(answered),
+            }
+        }
+
+        Ok(responses)
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        // The sources still expect a sync, so they stay pending
+        let pending = self.client(client_id).pending.clone();
+
+        let mut responses = vec![];
+        for source in pending {
+            responses.extend(self.sources[source].flush(client_id).await?);
+        }
+
+        Ok(responses)
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        let sources = match close.kind {
+            CloseKind::Statement => &mut client.statements,
+            CloseKind::Portal => &mut client.portals,
+        };
+        let source = sources.remove(&close.name).unwrap_or(DEFAULT_SOURCE);
+        client.touch(source);
+
+        self.sources[source].close(client_id, close).await
+    }
+
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        let source = self.copy_source(&query)?;
+        let response = self.sources[source].copy_in(client_id, query).await?;
+        self.client(client_id).copying = Some(source);
+
+        Ok(response)
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        let source = self.copying_source(client_id)?;
+        self.sources[source].copy_data(client_id, data).await
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        let source = self.copying_source(client_id)?;
+        self.client(client_id).copying = None;
+        self.sources[source].copy_done(client_id).await
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        let source = self.copying_source(client_id)?;
+        self.client(client_id).copying = None;
+        self.sources[source].copy_fail(client_id, message).await
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        let source = self.copy_source(&query)?;
+        self.sources[source].copy_out(client_id, query).await
+    }
+
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        self.sources
+            .iter_mut()
+            .flat_map(|source| source.take_notices(client_id))
+            .collect()
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.sources
+            .iter_mut()
+            .flat_map(|source| source.take_parameter_changes(client_id))
+            .collect()
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.sources
+            .iter_mut()
+            .flat_map(|source| source.take_notifications(client_id))
+            .collect()
+    }
+
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        wait_for_any_idle_message(self.sources.iter_mut(), client_id).await
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        for source in self.sources.iter_mut() {
+            source.read_idle_messages(client_id).await?;
+        }
+        Ok(())
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.clients.remove(&client_id);
+
+        for source in &mut self.sources {
+            source.terminate(client_id).await?;
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,833 @@
+use arrow::{datatypes::Schema, record_batch::RecordBatch};
+use async_trait::async_trait;
+use proboscis_core::{
+    data::{copy::serialize_record_batch_to_copy_text, field::with_field_metadata},
+    resolver::{
+        answer_operations, copy_out_messages, AnswerOperations, Bind, ClientContext, ClientId,
+        Close, CopyOutMessage, CopyOutStream, Describe, Execute, Operation, Parse, ResolveError,
+        Resolver, SyncResponse,
+    },
+    utils::sql::parameter_count,
+};
+use proboscis_postgres_protocol::message::{
+    CloseKind, CommandCompleteTag, CopyResponse, DescribeKind, NotificationResponse,
+    ParameterDescription, ParameterStatus,
+};
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+};
+
+// Parameters are described as text, the mock doesn't look at their values
+const TEXT_OID: u32 = 25;
+
+/// What the mock answers a matching query with
+#[derive(Debug, Clone)]
+pub enum MockResponse {
+    Rows {
+        schema: Schema,
+        data: Vec<RecordBatch>,
+    },
+    CommandComplete(String),
+    Error(String),
+}
+
+impl MockResponse {
+    /// Rows of the batches, which have to share a schema
+    pub fn rows(data: Vec<RecordBatch>) -> MockResponse {
+        let schema = data
+            .first()
+            .map(|batch| batch.schema().as_ref().clone())
+            .unwrap_or_else(Schema::empty);
+
+        MockResponse::Rows {
+            schema: with_field_metadata(&schema),
+            data: data
+                .iter()
+                .map(|batch| {
+                    RecordBatch::try_new(
+                        Arc::new(with_field_metadata(&schema)),
+                        batch.columns().to_vec(),
+                    )
+                    .expect("Batches have to share a schema")
+                })
+                .collect(),
+        }
+    }
+
+    /// A result without rows, which still describes its columns
+    pub fn empty(schema: Schema) -> MockResponse {
+        MockResponse::Rows {
+            schema: with_field_metadata(&schema),
+            data: vec![],
+        }
+    }
+
+    /// A statement without rows, like an `INSERT` with the tag `INSERT 0 1`
+    pub fn command_complete(tag: &str) -> MockResponse {
+        MockResponse::CommandComplete(tag.to_string())
+    }
+
+    pub fn error(message: &str) -> MockResponse {
+        MockResponse::Error(message.to_string())
+    }
+}
+
+fn normalize(query: &str) -> String {
+    query
+        .split_whitespace()
+        .collect::<Vec<&str>>()
+        .join(" ")
+        .trim_end_matches(';')
+        .trim_end()
+        .to_lowercase()
+}
+
+type QueryMatcher = Box<dyn Fn(&str) -> bool + Send + Sync>;
+
+struct Rule {
+    matches: QueryMatcher,
+    response: MockResponse,
+}
+
+#[derive(Default)]
+struct Client {
+    // Maps a statement to an sql string
+    statements: HashMap<String, String>,
+    // Maps a portal to the statement it was bound to
+    portals: HashMap<String, String>,
+    // Operations since the last sync, in order
+    pending: Vec<Operation>,
+    // Parameters reported as changed, until they are taken
+    parameter_changes: Vec<ParameterStatus>,
+    // Notifications of the client's channels, until they are taken
+    notifications: Vec<NotificationResponse>,
+    // The data of the copy in progress
+    copy: Option<Vec<u8>>,
+}
+
+/// Answers queries with canned responses instead of a database, e.g. to test transformer
+/// configurations or clients without one. The response of the first registered pattern
+/// matching a query is returned, queries without one fail.
+/// Bound parameters aren't considered when matching prepared statements.
+pub struct MockResolver {
+    rules: Vec<Rule>,
+    fallback: Option<MockResponse>,
+    // Parameters reported as changed by queries containing the fragment
+    parameter_rules: Vec<(String, ParameterStatus)>,
+    // Notifications sent after queries containing the fragment
+    notification_rules: Vec<(String, NotificationResponse)>,
+    // Every query received, in order
+    history: Arc<Mutex<Vec<String>>>,
+    // The data of every completed copy in, in order
+    copied: Arc<Mutex<Vec<Vec<u8>>>>,
+    clients: HashMap<ClientId, Client>,
+}
+
+impl Default for MockResolver {
+    fn default() -> Self {
+        MockResolver::new()
+    }
+}
+
+impl MockResolver {
+    pub fn new() -> MockResolver {
+        MockResolver {
+            rules: vec![],
+            fallback: None,
+            parameter_rules: vec![],
+            notification_rules: vec![],
+            history: Arc::new(Mutex::new(vec![])),
+            copied: Arc::new(Mutex::new(vec![])),
+            clients: HashMap::new(),
+        }
+    }
+
+    /// Answers the query, compared ignoring case, whitespace and a trailing semicolon
+    pub fn on_query(self, query: &str, response: MockResponse) -> MockResolver {
+        let query = normalize(query);
+        self.on_query_matching(move |candidate| normalize(candidate) == query, response)
+    }
+
+    /// Answers queries containing the fragment, compared ignoring case and whitespace
+    pub fn on_query_containing(self, fragment: &str, response: MockResponse) -> MockResolver {
+        let fragment = normalize(fragment);
+        self.on_query_matching(
+            move |candidate| normalize(candidate).contains(&fragment),
+            response,
+        )
+    }
+
+    pub fn on_query_matching<F>(mut self, matches: F, response: MockResponse) -> MockResolver
+    where
+        F: Fn(&str) -> bool + Send + Sync + 'static,
+    {
+        self.rules.push(Rule {
+            matches: Box::new(matches),
+            response,
+        });
+        self
+    }
+
+    /// Answers queries no pattern matches, instead of failing them
+    pub fn with_fallback(mut self, response: MockResponse) -> MockResolver {
+        self.fallback = Some(response);
+        self
+    }
+
+    /// Reports the parameter as changed to the value after queries containing the fragment,
+    /// like postgres does after a `SET`
+    pub fn with_parameter_change(mut self, fragment: &str, key: &str, value: &str) -> MockResolver {
+        self.parameter_rules.push((
+            normalize(fragment),
+            ParameterStatus {
+                key: key.to_string(),
+                value: value.to_string(),
+            },
+        ));
+        self
+    }
+
+    /// Notifies the client on the channel after queries containing the fragment, like postgres
+    /// does after a `NOTIFY` on a channel the client listens on
+    pub fn with_notification(
+        mut self,
+        fragment: &str,
+        channel: &str,
+        payload: &str,
+    ) -> MockResolver {
+        self.notification_rules.push((
+            normalize(fragment),
+            NotificationResponse {
+                process_id: 0,
+                channel: channel.to_string(),
+                payload: payload.to_string(),
+            },
+        ));
+        self
+    }
+
+    /// The queries received so far, which can be read after the resolver was handed to the proxy
+    pub fn history(&self) -> Arc<Mutex<Vec<String>>> {
+        self.history.clone()
+    }
+
+    /// The data of the copies in completed so far. A copy in is accepted if its query has a
+    /// response which isn't an error, a copy out is answered with the rows of its response.
+    pub fn copied(&self) -> Arc<Mutex<Vec<Vec<u8>>>> {
+        self.copied.clone()
+    }
+
+    fn respond(&self, query: &str) -> Result<MockResponse, ResolveError> {
+        self.history
+            .lock()
+            .expect("Mock history lock poisoned")
+            .push(query.to_string());
+
+        self.rules
+            .iter()
+            .find(|rule| (rule.matches)(query))
+            .map(|rule| &rule.response)
+            .or_else(|| self.fallback.as_ref())
+            .cloned()
+            .ok_or_else(|| {
+                format!("the mock has no response for '{}'", query)
+                    .as_str()
+                    .into()
+            })
+    }
+
+    fn report_changes(&mut self, client_id: ClientId, query: &str) {
+        let query = normalize(query);
+        let changes: Vec<ParameterStatus> = self
+            .parameter_rules
+            .iter()
+            .filter(|(fragment, _)| query.contains(fragment))
+            .map(|(_, status)| status.clone())
+            .collect();
+        let notifications: Vec<NotificationResponse> = self
+            .notification_rules
+            .iter()
+            .filter(|(fragment, _)| query.contains(fragment))
+            .map(|(_, notification)| notification.clone())
+            .collect();
+
+        let client = self.client(client_id);
+        client.parameter_changes.extend(changes);
+        client.notifications.extend(notifications);
+    }
+
+    async fn answer_pending(&mut self, client_id: ClientId) -> Vec<SyncResponse> {
+        let pending = std::mem::take(&mut self.client(client_id).pending);
+        answer_operations(self, client_id, pending).await
+    }
+
+    fn client(&mut self, client_id: ClientId) -> &mut Client {
+        self.clients.entry(client_id).or_default()
+    }
+
+    fn portal_query(&mut self, client_id: ClientId, portal: &str) -> String {
+        let client = self.client(client_id);
+        client
+            .portals
+            .get(portal)
+            .and_then(|statement| client.statements.get(statement))
+            .cloned()
+            .unwrap_or_default()
+    }
+}
+
+#[async_trait]
+impl AnswerOperations for MockResolver {
+    async fn answer_describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError> {
+        let query = match describe.kind {
+            DescribeKind::Statement => {
+                let query = self
+                    .client(client_id)
+                    .statements
+                    .get(&describe.name)
+                    .cloned()
+                    .unwrap_or_default();
+
+                responses.push(SyncResponse::ParameterDescription(ParameterDescription {
+                    types: vec![TEXT_OID; parameter_count(&query)],
+                }));
+
+                query
+            }
+            DescribeKind::Portal => self.portal_query(client_id, &describe.name),
+        };
+
+        match self.respond(&query)? {
+            MockResponse::Rows { schema, data: _ } => {
+                responses.push(SyncResponse::Schema { schema, query })
+            }
+            MockResponse::CommandComplete(_) => responses.push(SyncResponse::NoData),
+            MockResponse::Error(message) => return Err(message.as_str().into()),
+        }
+
+        Ok(())
+    }
+
+    async fn answer_execute(
+        &mut self,
+        client_id: ClientId,
+        execute: Execute,
+        responses: &mut Vec<SyncResponse>,
+    ) -> Result<(), ResolveError> {
+        let query = self.portal_query(client_id, &execute.portal);
+
+        if query.trim().is_empty() {
+            responses.push(SyncResponse::EmptyQueryResponse);
+            return Ok(());
+        }
+
+        match self.respond(&query)? {
+            MockResponse::Rows { schema: _, data } => {
+                let rows: usize = data.iter().map(|batch| batch.num_rows()).sum();
+
+                responses.push(SyncResponse::Records { data, query });
+                responses.push(SyncResponse::CommandComplete(CommandCompleteTag(format!(
+                    "SELECT {}",
+                    rows
+                ))));
+            }
+            MockResponse::CommandComplete(tag) => {
+                responses.push(SyncResponse::CommandComplete(CommandCompleteTag(tag)))
+            }
+            MockResponse::Error(message) => return Err(message.as_str().into()),
+        }
+
+        self.report_changes(client_id, &query);
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl Resolver for MockResolver {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        _context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id);
+        Ok(())
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let data = match self.respond(&query)? {
+            MockResponse::Rows { schema: _, data } => data,
+            MockResponse::CommandComplete(_) => vec![],
+            MockResponse::Error(message) => return Err(message.as_str().into()),
+        };
+
+        self.report_changes(client_id, &query);
+        Ok(data)
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        client.statements.insert(parse.statement_name, parse.query);
+        client.pending.push(Operation::Parse);
+
+        Ok(())
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id)
+            .pending
+            .push(Operation::Describe(describe));
+
+        Ok(())
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        client.portals.insert(bind.portal, bind.statement);
+        client.pending.push(Operation::Bind);
+
+        Ok(())
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        self.client(client_id)
+            .pending
+            .push(Operation::Execute(execute));
+
+        Ok(())
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let mut responses = self.answer_pending(client_id).await;
+        responses.push(SyncResponse::ReadyForQuery);
+
+        Ok(responses)
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        Ok(self.answer_pending(client_id).await)
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let client = self.client(client_id);
+        match close.kind {
+            CloseKind::Statement => client.statements.remove(&close.name),
+            CloseKind::Portal => client.portals.remove(&close.name),
+        };
+
+        Ok(())
+    }
+
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        if let MockResponse::Error(message) = self.respond(&query)? {
+            return Err(message.as_str().into());
+        }
+
+        self.client(client_id).copy = Some(vec![]);
+        Ok(CopyResponse {
+            format: 0,
+            column_formats: vec![],
+        })
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        self.client(client_id)
+            .copy
+            .as_mut()
+            .ok_or("the client has no copy in progress")?
+            .extend(data);
+
+        Ok(())
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        let data = self
+            .client(client_id)
+            .copy
+            .take()
+            .ok_or("the client has no copy in progress")?;
+
+        let rows = data.iter().filter(|byte| **byte == b'\n').count();
+        self.copied
+            .lock()
+            .expect("Mock copied lock poisoned")
+            .push(data);
+
+        Ok(CommandCompleteTag(format!("COPY {}", rows)))
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        _message: String,
+    ) -> Result<(), ResolveError> {
+        self.client(client_id).copy = None;
+        Ok(())
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        _client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        let (schema, data) = match self.respond(&query)? {
+            MockResponse::Rows { schema, data } => (schema, data),
+            MockResponse::CommandComplete(_) => {
+                return Err("the query of the copy doesn't return rows".into())
+            }
+            MockResponse::Error(message) => return Err(message.as_str().into()),
+        };
+
+        let mut messages = vec![];
+        for batch in &data {
+            for line in serialize_record_batch_to_copy_text(batch)? {
+                messages.push(CopyOutMessage::Data(line));
+            }
+        }
+        let rows = messages.len();
+        messages.push(CopyOutMessage::Complete(CommandCompleteTag(format!(
+            "COPY {}",
+            rows
+        ))));
+
+        let response = CopyResponse {
+            format: 0,
+            column_formats: vec![0; schema.fields().len()],
+        };
+
+        Ok((response, copy_out_messages(messages)))
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        std::mem::take(&mut self.client(client_id).parameter_changes)
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        std::mem::take(&mut self.client(client_id).notifications)
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.clients.remove(&client_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Int32Array, StringArray},
+        datatypes::{DataType, Field},
+    };
+    use futures::TryStreamExt;
+    use proboscis_postgres_protocol::message::BindParameter;
+    use std::convert::TryFrom;
+
+    fn contacts() -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, false),
+        ]);
+
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["Max", "Erika"])),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn resolver() -> MockResolver {
+        MockResolver::new()
+            .on_query(
+                "SELECT id, name FROM contacts",
+                MockResponse::rows(vec![contacts()]),
+            )
+            .on_query_containing(
+                "INSERT INTO contacts",
+                MockResponse::command_complete("INSERT 0 1"),
+            )
+    }
+
+    #[tokio::test]
+    async fn test_simple_query() {
+        let mut resolver = resolver();
+        let history = resolver.history();
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        let data = resolver
+            .query(client_id, "select id,  name\nfrom contacts;".to_string())
+            .await
+            .unwrap();
+        assert_eq!(2, data[0].num_rows());
+        assert!(proboscis_core::data::field::Field::try_from(data[0].schema().field(0)).is_ok());
+
+        assert!(resolver
+            .query(client_id, "SELECT * FROM orders".to_string())
+            .await
+            .is_err());
+
+        assert_eq!(
+            vec![
+                "select id,  name\nfrom contacts;".to_string(),
+                "SELECT * FROM orders".to_string()
+            ],
+            *history.lock().unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_query_stream() {
+        let mut resolver = resolver();
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        let batches: Vec<RecordBatch> = resolver
+            .query_stream(client_id, "SELECT id, name FROM contacts".to_string())
+            .await
+            .unwrap()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(1, batches.len());
+        assert_eq!(2, batches[0].num_rows());
+
+        assert!(resolver
+            .query_stream(client_id, "SELECT * FROM orders".to_string())
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extended_query() {
+        let mut resolver = resolver();
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        resolver
+            .parse(
+                client_id,
+                Parse {
+                    statement_name: "insert".to_string(),
+                    query: "INSERT INTO contacts (name) VALUES ($1)".to_string(),
+                    param_types: vec![],
+                },
+            )
+            .await
+            .unwrap();
+        resolver
+            .describe(
+                client_id,
+                Describe {
+                    kind: DescribeKind::Statement,
+                    name: "insert".to_string(),
+                },
+            )
+            .await
+            .unwrap();
+        resolver
+            .bind(
+                client_id,
+                Bind {
+                    statement: "insert".to_string(),
+                    portal: "".to_string(),
+                    params: vec![BindParameter::Text("Max".to_string())],
+                    results: vec![],
+                },
+            )
+            .await
+            .unwrap();
+        resolver
+            .execute(
+                client_id,
+                Execute {
+                    portal: "".to_string(),
+                    row_limit: 0,
+                },
+            )
+            .await
+            .unwrap();
+
+        let responses = resolver.sync(client_id).await.unwrap();
+        assert_eq!(6, responses.len());
+        assert!(matches!(responses[0], SyncResponse::ParseComplete));
+        assert!(
+            matches!(&responses[1], SyncResponse::ParameterDescription(description) if description.types.len() == 1)
+        );
+        assert!(matches!(responses[2], SyncResponse::NoData));
+        assert!(matches!(responses[3], SyncResponse::BindComplete));
+        assert!(
+            matches!(&responses[4], SyncResponse::CommandComplete(CommandCompleteTag(tag)) if tag == "INSERT 0 1")
+        );
+        assert!(matches!(responses[5], SyncResponse::ReadyForQuery));
+    }
+
+    #[tokio::test]
+    async fn test_extended_query_error() {
+        let mut resolver = resolver();
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        for query in ["SELECT * FROM orders", "SELECT id, name FROM contacts"] {
+            resolver
+                .parse(
+                    client_id,
+                    Parse {
+                        statement_name: "".to_string(),
+                        query: query.to_string(),
+                        param_types: vec![],
+                    },
+                )
+                .await
+                .unwrap();
+            resolver
+                .bind(
+                    client_id,
+                    Bind {
+                        statement: "".to_string(),
+                        portal: "".to_string(),
+                        params: vec![],
+                        results: vec![],
+                    },
+                )
+                .await
+                .unwrap();
+            resolver
+                .execute(
+                    client_id,
+                    Execute {
+                        portal: "".to_string(),
+                        row_limit: 0,
+                    },
+                )
+                .await
+                .unwrap();
+        }
+
+        // The failed execute is answered in place, the query after it is skipped
+        let responses = resolver.sync(client_id).await.unwrap();
+        assert_eq!(4, responses.len());
+        assert!(matches!(responses[0], SyncResponse::ParseComplete));
+        assert!(matches!(responses[1], SyncResponse::BindComplete));
+        assert!(
+            matches!(&responses[2], SyncResponse::Error(error) if error.message().unwrap().contains("orders"))
+        );
+        assert!(matches!(responses[3], SyncResponse::ReadyForQuery));
+    }
+
+    #[tokio::test]
+    async fn test_flush_answers_operations_before_sync() {
+        let mut resolver = resolver();
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        resolver
+            .parse(
+                client_id,
+                Parse {
+                    statement_name: "".to_string(),
+                    query: "SELECT id, name FROM contacts".to_string(),
+                    param_types: vec![],
+                },
+            )
+            .await
+            .unwrap();
+
+        let responses = resolver.flush(client_id).await.unwrap();
+        assert_eq!(1, responses.len());
+        assert!(matches!(responses[0], SyncResponse::ParseComplete));
+
+        let responses = resolver.sync(client_id).await.unwrap();
+        assert_eq!(1, responses.len());
+        assert!(matches!(responses[0], SyncResponse::ReadyForQuery));
+    }
+
+    #[tokio::test]
+    async fn test_copy() {
+        let mut resolver = resolver()
+            .on_query(
+                "COPY contacts FROM STDIN",
+                MockResponse::command_complete("COPY 0"),
+            )
+            .on_query(
+                "COPY (SELECT id, name FROM contacts) TO STDOUT",
+                MockResponse::rows(vec![contacts()]),
+            );
+        let copied = resolver.copied();
+        let client_id = ClientId::new_v4();
+
+        resolver
+            .copy_in(client_id, "COPY contacts FROM STDIN".to_string())
+            .await
+            .unwrap();
+        resolver
+            .copy_data(client_id, b"3\tAnna\n4\t".to_vec())
+            .await
+            .unwrap();
+        resolver
+            .copy_data(client_id, b"Paul\n".to_vec())
+            .await
+            .unwrap();
+        let tag = resolver.copy_done(client_id).await.unwrap();
+
+        assert_eq!(CommandCompleteTag("COPY 2".to_string()), tag);
+        assert_eq!(
+            vec![b"3\tAnna\n4\tPaul\n".to_vec()],
+            *copied.lock().unwrap()
+        );
+
+        let (response, messages) = resolver
+            .copy_out(
+                client_id,
+                "COPY (SELECT id, name FROM contacts) TO STDOUT".to_string(),
+            )
+            .await
+            .unwrap();
+        let messages: Vec<CopyOutMessage> = messages.try_collect().await.unwrap();
+
+        assert_eq!(vec![0, 0], response.column_formats);
+        assert_eq!(
+            vec![
+                CopyOutMessage::Data(b"1\tMax\n".to_vec()),
+                CopyOutMessage::Data(b"2\tErika\n".to_vec()),
+                CopyOutMessage::Complete(CommandCompleteTag("COPY 2".to_string())),
+            ],
+            messages
+        );
+    }
+}
@@ -0,0 +1,169 @@
+use crate::pool::establish_connection;
+use crate::target_config::TargetConfig;
+use proboscis_core::{
+    data::field::is_builtin_type_oid, data::registry::mapping_for_oid, resolver::ResolveError,
+    utils::connection::Connection,
+};
+use proboscis_postgres_protocol::message::{BackendMessage, DataRow, Field, FrontendMessage};
+use std::collections::HashMap;
+
+// Oids of the builtin types custom types fall back to
+const TEXT_OID: u32 = 25;
+const TEXT_ARRAY_OID: u32 = 1009;
+
+// Oids from here on belong to objects created in the database, the ones before are built in
+const FIRST_NORMAL_OBJECT_ID: u32 = 16384;
+
+/// Resolves types that are not built into postgres, like enums and domains,
+/// to builtin types by looking them up in the catalog of the target.
+///
+/// Lookups use a dedicated connection, so they can happen while the pooled
+/// connection of a client is in the middle of an extended query.
+pub struct TypeCatalog {
+    target_config: TargetConfig,
+    connection: Option<Connection>,
+
+    // Maps the oid of a custom type to the oid of the builtin type it is represented as
+    resolved_types: HashMap<u32, u32>,
+}
+
+impl TypeCatalog {
+    pub fn new(target_config: TargetConfig) -> TypeCatalog {
+        TypeCatalog {
+            target_config,
+            connection: None,
+            resolved_types: HashMap::new(),
+        }
+    }
+
+    /// Replaces the type of every field that has a custom type with its builtin equivalent
+    pub async fn resolve_fields(&mut self, fields: &mut [Field]) -> Result<(), ResolveError> {
+        for field in fields.iter_mut() {
+            // Types with a registered mapping are converted as they are, builtin types
+            // without a conversion are kept in their text representation
+            if field.type_oid >= FIRST_NORMAL_OBJECT_ID && mapping_for_oid(field.type_oid).is_none()
+            {
+                field.type_oid = self.resolve(field.type_oid).await?;
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn resolve(&mut self, type_oid: u32) -> Result<u32, ResolveError> {
+        if let Some(resolved) = self.resolved_types.get(&type_oid) {
+            return Ok(*resolved);
+        }
+
+        let mut oid = type_oid;
+        let resolved = loop {
+            if is_builtin_type_oid(oid) {
+                break oid;
+            }
+
+            let row = self
+                .query_row(&format!(
+                    "SELECT typtype, typbasetype, typelem, typcategory FROM pg_type WHERE oid = {}",
+                    oid
+                ))
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Unknown type oid {}", oid))?;
+
+            match (row[0].as_deref(), row[3].as_deref()) {
+                // Domains are represented like the type they are based on
+                (Some("d"), _) => oid = parse_oid(&row[1])?,
+                // Arrays of custom types become arrays of the type of their elements
+                (_, Some("A")) => {
+                    let element_oid = self.resolve_element(parse_oid(&row[2])?).await?;
+                    break self.array_type_of(element_oid).await?;
+                }
+                // Enums and every other type are forwarded in their text representation
+                _ => break TEXT_OID,
+            }
+        };
+
+        self.resolved_types.insert(type_oid, resolved);
+
+        Ok(resolved)
+    }
+
+    async fn resolve_element(&mut self, type_oid: u32) -> Result<u32, ResolveError> {
+        let mut oid = type_oid;
+        loop {
+            if is_builtin_type_oid(oid) {
+                return Ok(oid);
+            }
+
+            let row = self
+                .query_row(&format!(
+                    "SELECT typtype, typbasetype FROM pg_type WHERE oid = {}",
+                    oid
+                ))
+                .await?
+                .ok_or_else(|| anyhow::anyhow!("Unknown type oid {}", oid))?;
+
+            match row[0].as_deref() {
+                Some("d") => oid = parse_oid(&row[1])?,
+                _ => return Ok(TEXT_OID),
+            }
+        }
+    }
+
+    async fn array_type_of(&mut self, element_oid: u32) -> Result<u32, ResolveError> {
+        if element_oid == TEXT_OID {
+            return Ok(TEXT_ARRAY_OID);
+        }
+
+        let row = self
+            .query_row(&format!(
+                "SELECT typarray FROM pg_type WHERE oid = {}",
+                element_oid
+            ))
+            .await?
+            .ok_or_else(|| anyhow::anyhow!("Unknown type oid {}", element_oid))?;
+
+        parse_oid(&row[0])
+    }
+
+    async fn query_row(
+        &mut self,
+        query: &str,
+    ) -> Result<Option<Vec<Option<String>>>, ResolveError> {
+        if self.connection.is_none() {
+            self.connection = Some(establish_connection(&self.target_config).await?);
+        }
+
+        let connection = self.connection.as_mut().unwrap();
+
+        connection
+            .write_message(FrontendMessage::SimpleQuery(query.to_string()).into())
+            .await?;
+
+        let mut row = None;
+        loop {
+            match connection.read_backend_message().await? {
+                BackendMessage::ReadyForQuery(_) => break,
+                BackendMessage::DataRow(DataRow { field_data }) => {
+                    row = Some(
+                        field_data
+                            .into_iter()
+                            .map(|value| {
+                                value.map(|value| String::from_utf8_lossy(&value).to_string())
+                            })
+                            .collect(),
+                    )
+                }
+                _ => {}
+            }
+        }
+
+        Ok(row)
+    }
+}
+
+fn parse_oid(value: &Option<String>) -> Result<u32, ResolveError> {
+    value
+        .as_ref()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| ResolveError::from("Invalid oid in pg_type"))
+}
@@ -0,0 +1,88 @@
+use async_trait::async_trait;
+use proboscis_core::resolver::ResolveError;
+use std::{
+    fmt::Debug,
+    time::{Duration, Instant},
+};
+use tokio::sync::Mutex;
+
+/// Source of the password of the target's user, e.g. a secret store rotating it.
+/// New connections of the pool authenticate with the password it returns.
+#[async_trait]
+pub trait CredentialsProvider: Send + Sync + Debug {
+    async fn password(&self) -> Result<String, ResolveError>;
+}
+
+/// Caches the password of a provider and fetches it again once it is older than the interval,
+/// so opening connections doesn't hit the provider every time
+#[derive(Debug)]
+pub struct RefreshingCredentials {
+    provider: Box<dyn CredentialsProvider>,
+    refresh_interval: Duration,
+    cached: Mutex<Option<(Instant, String)>>,
+}
+
+impl RefreshingCredentials {
+    pub fn new(
+        provider: Box<dyn CredentialsProvider>,
+        refresh_interval: Duration,
+    ) -> RefreshingCredentials {
+        RefreshingCredentials {
+            provider,
+            refresh_interval,
+            cached: Mutex::new(None),
+        }
+    }
+}
+
+#[async_trait]
+impl CredentialsProvider for RefreshingCredentials {
+    async fn password(&self) -> Result<String, ResolveError> {
+        let mut cached = self.cached.lock().await;
+
+        if let Some((fetched, password)) = cached.as_ref() {
+            if fetched.elapsed() < self.refresh_interval {
+                return Ok(password.clone());
+            }
+        }
+
+        let password = self.provider.password().await?;
+        *cached = Some((Instant::now(), password.clone()));
+
+        Ok(password)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[derive(Debug, Default)]
+    struct CountingProvider {
+        fetches: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl CredentialsProvider for CountingProvider {
+        async fn password(&self) -> Result<String, ResolveError> {
+            let fetches = self.fetches.fetch_add(1, Ordering::SeqCst) + 1;
+            Ok(format!("password-{}", fetches))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_refreshing_credentials() {
+        let cached = RefreshingCredentials::new(
+            Box::new(CountingProvider::default()),
+            Duration::from_secs(60),
+        );
+        assert_eq!("password-1", cached.password().await.unwrap());
+        assert_eq!("password-1", cached.password().await.unwrap());
+
+        let refreshed =
+            RefreshingCredentials::new(Box::new(CountingProvider::default()), Duration::ZERO);
+        assert_eq!("password-1", refreshed.password().await.unwrap());
+        assert_eq!("password-2", refreshed.password().await.unwrap());
+    }
+}
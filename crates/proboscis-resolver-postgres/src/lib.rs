@@ -1,49 +1,133 @@
+mod catalog;
+mod credentials;
 mod pool;
+mod schema_catalog;
 mod target_config;
 
+use crate::catalog::TypeCatalog;
 use crate::pool::Manager;
 use crate::pool::Pool;
+use crate::pool::{establish_relayed_connection, UpstreamConnection};
 use arrow::{datatypes::Schema, record_batch::RecordBatch};
 use async_trait::async_trait;
-use deadpool::managed::BuildError;
+use deadpool::Runtime;
+use futures::{
+    future,
+    stream::{self, StreamExt},
+    TryStreamExt,
+};
 use proboscis_core::resolver::ResolveError;
 use proboscis_core::{
     data::arrow::{
         protocol_fields_to_schema, serialize_record_batch_schema_to_row_description,
-        simple_query_response_to_record_batch,
+        simple_query_response_to_record_batches, DEFAULT_BATCH_SIZE,
     },
+    metrics::{observe_stage, Stage},
     resolver::Resolver,
-    resolver::{ClientId, SyncResponse},
+    resolver::{
+        sync_error, ClientContext, ClientId, CopyOutMessage, CopyOutStream, RecordBatchStream,
+        SyncResponse,
+    },
+    sessions, sqlstate,
+    utils::connection::Connection,
 };
-use proboscis_postgres_protocol::message::{
-    BackendMessage, Bind, Close, CommandCompleteTag, DataRow, Describe, Execute, FrontendMessage,
-    Parse, RowDescription,
+use proboscis_postgres_protocol::{
+    message::{
+        BackendMessage, Bind, Close, CommandCompleteTag, CopyResponse, DataRow, Describe,
+        DescribeKind, Error, Execute, Field, FrontendMessage, NotificationResponse,
+        ParameterStatus, Parse, ReadyForQueryTransactionStatus, RowDescription,
+    },
+    ParseError,
 };
 use std::collections::hash_map::Entry::Occupied;
 use std::collections::hash_map::Entry::Vacant;
 use std::collections::{HashMap, VecDeque};
+use std::time::{Duration, Instant};
 
+pub use credentials::{CredentialsProvider, RefreshingCredentials};
+pub use pool::{PoolConfig, PoolMode};
+pub use schema_catalog::{listen_for_catalog_changes, PostgresCatalogSource};
 pub use target_config::TargetConfig;
 
 #[derive(Debug)]
 enum ClientOperation {
     Parse,
-    Bind { statement: String, portal: String },
-    Describe { statement: String },
-    Execute { portal: String },
+    Bind {
+        statement: String,
+        portal: String,
+        result_formats: Vec<i16>,
+    },
+    Describe {
+        kind: DescribeKind,
+        name: String,
+    },
+    Execute {
+        portal: String,
+    },
+    // The proxy answers closes itself, the response of the target is discarded
+    Close,
 }
 
 #[derive(Debug)]
 struct ActiveConnection {
-    connection: deadpool::managed::Object<Manager>,
+    connection: UpstreamConnection,
     requested_ops: VecDeque<ClientOperation>,
+    // The client listens on channels, so the connection is kept until it disconnects
+    listening: bool,
+    // Notifications of the channels, until they are sent to the client
+    notifications: Vec<NotificationResponse>,
+    // Notices of the target, e.g. of `RAISE NOTICE`, until they are sent to the client
+    notices: Vec<Error>,
 }
 
 impl ActiveConnection {
-    fn new(connection: deadpool::managed::Object<Manager>) -> ActiveConnection {
+    fn new(connection: UpstreamConnection) -> ActiveConnection {
         ActiveConnection {
             connection,
             requested_ops: VecDeque::new(),
+            listening: false,
+            notifications: vec![],
+            notices: vec![],
+        }
+    }
+
+    /// Keeps the connection for the client if a statement of the query is a `LISTEN`
+    fn track_listen(&mut self, query: &str) {
+        let listens = query.split(';').any(|statement| {
+            statement
+                .split_whitespace()
+                .next()
+                .map_or(false, |word| word.eq_ignore_ascii_case("LISTEN"))
+        });
+
+        if listens {
+            self.listening = true;
+            self.connection.mark_listening();
+        }
+    }
+
+    async fn read_message(&mut self) -> Result<BackendMessage, ParseError> {
+        self.read_message_with_field_limit(None).await
+    }
+
+    /// Reads the next message, notifications and notices are kept for the client as the target
+    /// sends them between any two messages
+    async fn read_message_with_field_limit(
+        &mut self,
+        field_limit: Option<usize>,
+    ) -> Result<BackendMessage, ParseError> {
+        loop {
+            match self
+                .connection
+                .read_backend_message_with_field_limit(field_limit)
+                .await?
+            {
+                BackendMessage::NotificationResponse(notification) => {
+                    self.notifications.push(notification)
+                }
+                BackendMessage::NoticeResponse(notice) => self.notices.push(notice),
+                message => return Ok(message),
+            }
         }
     }
 }
@@ -53,36 +137,635 @@ pub struct PostgresResolver {
     // To add them back to the pool, drop them.
     active_connections: HashMap<ClientId, ActiveConnection>,
     pool: Pool,
+    pool_mode: PoolMode,
+    target_config: TargetConfig,
+
+    // Resolves custom types of the target to builtin types
+    type_catalog: TypeCatalog,
+
+    // Maximum number of rows per record batch
+    batch_size: usize,
+
+    // Size in bytes above which values are rejected instead of being buffered
+    max_value_size: Option<usize>,
 
     // Maps a statement to a schema
     statement_schema_cache: HashMap<String, Schema>,
 
+    // Maps a described portal to a schema
+    portal_schema_cache: HashMap<String, Schema>,
+
     // Maps a statement to an sql string
     statement_query_cache: HashMap<String, String>,
 
     // Maps a portal to a statement
     portal_cache: HashMap<String, String>,
+
+    // Maps a portal to the result formats requested in its bind message
+    portal_result_formats: HashMap<String, Vec<i16>>,
+
+    // Prefix of the application name connections are tagged with while a client uses them
+    application_name_prefix: Option<String>,
+
+    // Maps a client to the application name of the connections it uses
+    application_names: HashMap<ClientId, String>,
+
+    // Parameters the target reported as changed, until they are sent to the client
+    parameter_changes: HashMap<ClientId, Vec<ParameterStatus>>,
 }
 
 impl PostgresResolver {
     pub async fn create(
         target_config: TargetConfig,
         max_pool_size: usize,
-    ) -> Result<PostgresResolver, BuildError<ResolveError>> {
-        let manager = Manager::new(target_config);
-        let pool = Pool::builder(manager).max_size(max_pool_size).build()?;
+    ) -> Result<PostgresResolver, ResolveError> {
+        PostgresResolver::create_with_pool_config(target_config, PoolConfig::new(max_pool_size))
+            .await
+    }
+
+    pub async fn create_with_pool_config(
+        target_config: TargetConfig,
+        pool_config: PoolConfig,
+    ) -> Result<PostgresResolver, ResolveError> {
+        let type_catalog = TypeCatalog::new(target_config.clone());
+        let manager = Manager::new(target_config.clone(), pool_config.max_lifetime);
+        let pool = Pool::builder(manager)
+            .max_size(pool_config.max_size)
+            .wait_timeout(pool_config.acquisition_timeout)
+            .create_timeout(pool_config.acquisition_timeout)
+            .runtime(Runtime::Tokio1)
+            .build()
+            .map_err(|err| ResolveError::Other(anyhow::anyhow!(err)))?;
+
+        // The connections are returned to the pool once they are dropped
+        let mut connections = vec![];
+        for _ in 0..pool_config.min_size.min(pool_config.max_size) {
+            connections.push(
+                pool.get()
+                    .await
+                    .map_err(|err| ResolveError::Other(anyhow::anyhow!(err)))?,
+            );
+        }
+        drop(connections);
 
         Ok(PostgresResolver {
             active_connections: HashMap::new(),
             pool,
+            pool_mode: pool_config.mode,
+            target_config,
+            type_catalog,
+            batch_size: DEFAULT_BATCH_SIZE,
+            max_value_size: None,
             statement_schema_cache: HashMap::new(),
+            portal_schema_cache: HashMap::new(),
             portal_cache: HashMap::new(),
+            portal_result_formats: HashMap::new(),
             statement_query_cache: HashMap::new(),
+            application_name_prefix: None,
+            application_names: HashMap::new(),
+            parameter_changes: HashMap::new(),
         })
     }
 
+    /// Sets the application name of connections to `prefix:user:session` while a client uses them,
+    /// so they can be told apart in `pg_stat_activity`. The session is the start of the client id.
+    pub fn with_application_name_prefix(mut self, prefix: &str) -> PostgresResolver {
+        self.application_name_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Sets the maximum number of rows the resolver puts into a single record batch
+    pub fn with_batch_size(mut self, batch_size: usize) -> PostgresResolver {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Rejects results containing a value larger than the size in bytes, e.g. a huge bytea.
+    /// The value is skipped while reading it, so it is never held in memory.
+    pub fn with_max_value_size(mut self, max_value_size: usize) -> PostgresResolver {
+        self.max_value_size = Some(max_value_size);
+        self
+    }
+
+    /// Reads the responses to the requested operations, in the order they were requested in.
+    /// After an error the target skips the remaining operations until the next sync.
+    async fn read_responses(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<Vec<SyncResponse>, ResolveError> {
+        let connection = self
+            .active_connections
+            .get_mut(&client_id)
+            .ok_or("the client has no connection")?;
+
+        let mut responses = vec![];
+        // The remaining operations are still read after a value was too large,
+        // as the target doesn't skip them
+        let mut too_large = None;
+        let mut failed_at = None;
+        while let Some(operation) = connection.requested_ops.pop_front() {
+            let result = match operation {
+                ClientOperation::Parse => match connection.read_message().await? {
+                    BackendMessage::ParseComplete => {
+                        responses.push(SyncResponse::ParseComplete);
+                        Ok(())
+                    }
+                    message => Err(message),
+                },
+                ClientOperation::Bind {
+                    statement,
+                    portal,
+                    result_formats,
+                } => match connection.read_message().await? {
+                    BackendMessage::BindComplete => {
+                        self.portal_schema_cache.remove(&portal);
+                        self.portal_cache.insert(portal.clone(), statement);
+                        self.portal_result_formats.insert(portal, result_formats);
+
+                        responses.push(SyncResponse::BindComplete);
+                        Ok(())
+                    }
+                    message => Err(message),
+                },
+                ClientOperation::Describe { kind, name } => loop {
+                    match connection.read_message().await? {
+                        BackendMessage::RowDescription(RowDescription { mut fields }) => {
+                            self.type_catalog.resolve_fields(&mut fields).await?;
+
+                            let schema = protocol_fields_to_schema(&fields)?;
+                            let statement = match kind {
+                                DescribeKind::Statement => name.clone(),
+                                DescribeKind::Portal => {
+                                    self.portal_cache.get(&name).cloned().unwrap_or_default()
+                                }
+                            };
+
+                            responses.push(SyncResponse::Schema {
+                                schema: schema.clone(),
+                                query: self
+                                    .statement_query_cache
+                                    .get(&statement)
+                                    .cloned()
+                                    .unwrap_or_default(),
+                            });
+
+                            match kind {
+                                DescribeKind::Statement => {
+                                    self.statement_schema_cache.insert(name, schema)
+                                }
+                                DescribeKind::Portal => {
+                                    self.portal_schema_cache.insert(name, schema)
+                                }
+                            };
+
+                            break Ok(());
+                        }
+                        BackendMessage::ParameterDescription(parameter_description) => responses
+                            .push(SyncResponse::ParameterDescription(parameter_description)),
+                        BackendMessage::NoData => {
+                            responses.push(SyncResponse::NoData);
+                            break Ok(());
+                        }
+                        message => break Err(message),
+                    }
+                },
+                ClientOperation::Execute { portal } => {
+                    let mut data_rows: Vec<DataRow> = vec![];
+                    let command_complete_tag = loop {
+                        let message = match connection
+                            .read_message_with_field_limit(self.max_value_size)
+                            .await
+                        {
+                            Err(ParseError::FieldTooLarge { length }) => {
+                                too_large = Some(length);
+                                continue;
+                            }
+                            message => message?,
+                        };
+
+                        match message {
+                            BackendMessage::DataRow(data_row) => data_rows.push(data_row),
+                            BackendMessage::ParameterStatus(status) => record_parameter_change(
+                                &mut self.parameter_changes,
+                                client_id,
+                                connection,
+                                status,
+                            ),
+                            BackendMessage::CommandComplete(tag) => break Ok(Some(tag)),
+                            BackendMessage::PortalSuspended => break Ok(None),
+                            message => break Err(message),
+                        }
+                    };
+
+                    match command_complete_tag {
+                        // The responses after it are still read, the client receives the error
+                        // in their place like for an error of the target
+                        Ok(_) if too_large.is_some() => {
+                            failed_at.get_or_insert(responses.len());
+                            Ok(())
+                        }
+                        Ok(command_complete_tag) => {
+                            let statement =
+                                self.portal_cache.get(&portal).cloned().unwrap_or_default();
+                            let schema = self
+                                .portal_schema_cache
+                                .get(&portal)
+                                .or_else(|| self.statement_schema_cache.get(&statement))
+                                .ok_or_else(|| {
+                                    anyhow::anyhow!("the portal '{}' wasn't described", portal)
+                                })?;
+
+                            let RowDescription { mut fields } =
+                                serialize_record_batch_schema_to_row_description(schema);
+
+                            if let Some(result_formats) = self.portal_result_formats.get(&portal) {
+                                apply_result_formats(&mut fields, result_formats);
+                            }
+
+                            let record_batches = simple_query_response_to_record_batches(
+                                &fields,
+                                &data_rows,
+                                self.batch_size,
+                            )?;
+
+                            responses.push(SyncResponse::Records {
+                                data: record_batches,
+                                query: self
+                                    .statement_query_cache
+                                    .get(&statement)
+                                    .cloned()
+                                    .unwrap_or_default(),
+                            });
+
+                            match command_complete_tag {
+                                Some(tag) => responses.push(SyncResponse::CommandComplete(tag)),
+                                None => responses.push(SyncResponse::PortalSuspended),
+                            }
+
+                            Ok(())
+                        }
+                        Err(BackendMessage::EmptyQueryResponse) => {
+                            responses.push(SyncResponse::EmptyQueryResponse);
+                            Ok(())
+                        }
+                        Err(message) => Err(message),
+                    }
+                }
+                ClientOperation::Close => match connection.read_message().await? {
+                    BackendMessage::CloseComplete => Ok(()),
+                    message => Err(message),
+                },
+            };
+
+            match result {
+                Ok(()) => {}
+                Err(BackendMessage::Error(error)) => {
+                    connection.requested_ops.clear();
+                    responses.push(SyncResponse::Error(error));
+                    return Ok(responses);
+                }
+                Err(message) => {
+                    return Err(anyhow::anyhow!(
+                        "unexpected message from the target: {:?}",
+                        message
+                    )
+                    .into())
+                }
+            }
+        }
+
+        if let (Some(length), Some(position)) = (too_large, failed_at) {
+            let error = value_too_large(length, self.max_value_size);
+            responses.truncate(position);
+            responses.push(SyncResponse::Error(error.to_error_response()));
+        }
+
+        Ok(responses)
+    }
+
+    async fn read_ready_for_query(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<ReadyForQueryTransactionStatus, ResolveError> {
+        let connection = self
+            .active_connections
+            .get_mut(&client_id)
+            .ok_or("the client has no connection")?;
+
+        // Targets report changed parameters once the statements of the batch ran
+        loop {
+            match connection.read_message().await? {
+                BackendMessage::ReadyForQuery(status) => return Ok(status),
+                BackendMessage::ParameterStatus(status) => record_parameter_change(
+                    &mut self.parameter_changes,
+                    client_id,
+                    connection,
+                    status,
+                ),
+                message => {
+                    return Err(anyhow::anyhow!(
+                        "unexpected message from the target: {:?}",
+                        message
+                    )
+                    .into())
+                }
+            }
+        }
+    }
+
+    /// Reads the target's response to the query starting a copy. A copy the target rejects,
+    /// e.g. of an unknown table, leaves it ready for the next query.
+    async fn read_copy_response(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<BackendMessage, ResolveError> {
+        let connection = self
+            .active_connections
+            .get_mut(&client_id)
+            .ok_or("the client has no connection")?;
+
+        loop {
+            match connection.read_message().await? {
+                BackendMessage::ParameterStatus(status) => record_parameter_change(
+                    &mut self.parameter_changes,
+                    client_id,
+                    connection,
+                    status,
+                ),
+                BackendMessage::Error(error) => {
+                    let status = self.read_ready_for_query(client_id).await?;
+                    self.release_connection(client_id, status);
+                    return Err(ResolveError::Target(error));
+                }
+                message => return Ok(message),
+            }
+        }
+    }
+
+    /// Reads the end of a copy in after the client finished or aborted it
+    async fn read_copy_in_result(
+        &mut self,
+        client_id: ClientId,
+    ) -> Result<CommandCompleteTag, ResolveError> {
+        let connection = self
+            .active_connections
+            .get_mut(&client_id)
+            .ok_or("the client has no connection")?;
+
+        let result = match connection.read_message().await? {
+            BackendMessage::CommandComplete(tag) => Ok(tag),
+            BackendMessage::Error(error) => Err(ResolveError::Target(error)),
+            message => {
+                self.discard_connection(client_id);
+                return Err(
+                    anyhow::anyhow!("unexpected message from the target: {:?}", message).into(),
+                );
+            }
+        };
+
+        let status = self.read_ready_for_query(client_id).await?;
+        self.release_connection(client_id, status);
+
+        result
+    }
+
+    /// Closes the connection of the client instead of returning it to the pool,
+    /// as it is in an unknown state
+    fn discard_connection(&mut self, client_id: ClientId) {
+        if let Some(active) = self.active_connections.remove(&client_id) {
+            active.connection.discard();
+            sessions::set_backend_pid(&client_id, None);
+        }
+    }
+
     fn terminate_connection(&mut self, client_id: ClientId) {
         self.active_connections.remove(&client_id);
+        self.application_names.remove(&client_id);
+        self.parameter_changes.remove(&client_id);
+        sessions::set_backend_pid(&client_id, None);
+    }
+
+    /// Returns the connection of the client to the pool in transaction mode,
+    /// if no transaction is open and no operations are outstanding
+    fn release_connection(&mut self, client_id: ClientId, status: ReadyForQueryTransactionStatus) {
+        if self.pool_mode != PoolMode::Transaction
+            || status != ReadyForQueryTransactionStatus::NotInTransaction
+        {
+            return;
+        }
+
+        // Connections the client authenticated itself can't be used by other clients
+        let is_idle = self
+            .active_connections
+            .get(&client_id)
+            .map_or(false, |connection| {
+                connection.requested_ops.is_empty()
+                    && !connection.listening
+                    && matches!(connection.connection, UpstreamConnection::Pooled(_))
+            });
+
+        if is_idle {
+            self.active_connections.remove(&client_id);
+            sessions::set_backend_pid(&client_id, None);
+        }
+    }
+}
+
+/// The result of a simple query, which is read from the target one batch at a time
+struct StreamedResult<'a> {
+    resolver: &'a mut PostgresResolver,
+    client_id: ClientId,
+    // Time spent waiting for the target, without the time the consumer takes per batch
+    upstream: Duration,
+    fields: Vec<Field>,
+    data_rows: Vec<DataRow>,
+    error: Option<Error>,
+    too_large: Option<usize>,
+    has_batch: bool,
+    // Whether the target is ready for the next query
+    finished: bool,
+}
+
+impl StreamedResult<'_> {
+    /// Reads the rows of the next batch, none once the whole result was read
+    async fn next_batch(&mut self) -> Result<Option<RecordBatch>, ResolveError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let started = Instant::now();
+        let batch_size = self.resolver.batch_size.max(1);
+        let status = loop {
+            let connection = self
+                .resolver
+                .active_connections
+                .get_mut(&self.client_id)
+                .ok_or("the client has no connection")?;
+
+            let response = match connection
+                .read_message_with_field_limit(self.resolver.max_value_size)
+                .await
+            {
+                Err(ParseError::FieldTooLarge { length }) => {
+                    self.too_large = Some(length);
+                    continue;
+                }
+                response => response?,
+            };
+            match response {
+                BackendMessage::ReadyForQuery(status) => break status,
+                // The server still sends ReadyForQuery after an error
+                BackendMessage::Error(message) => self.error = Some(message),
+                BackendMessage::RowDescription(RowDescription {
+                    fields: mut message_fields,
+                }) => {
+                    self.resolver
+                        .type_catalog
+                        .resolve_fields(&mut message_fields)
+                        .await?;
+                    self.fields.append(&mut message_fields);
+                }
+                // Rows after an error are discarded, the result fails once it was read
+                BackendMessage::DataRow(_) if self.error.is_some() || self.too_large.is_some() => {}
+                BackendMessage::DataRow(data_row) => {
+                    self.data_rows.push(data_row);
+
+                    if self.data_rows.len() >= batch_size {
+                        self.upstream += started.elapsed();
+                        return self.take_batch().map(Some);
+                    }
+                }
+                BackendMessage::CommandComplete(CommandCompleteTag(_)) => {
+                    // TODO: Handle this
+                }
+                // Copies are relayed by `copy_in` and `copy_out`, the target aborts the copy
+                // in and answers with an error
+                BackendMessage::CopyInResponse(_) => {
+                    connection
+                        .connection
+                        .write_message(
+                            FrontendMessage::CopyFail(COPY_WITHIN_QUERY.to_string()).into(),
+                        )
+                        .await?;
+                }
+                // The data of a copy out is discarded
+                BackendMessage::CopyOutResponse(_) => {
+                    self.error = Some(sqlstate::error_response(
+                        sqlstate::ERROR,
+                        sqlstate::FEATURE_NOT_SUPPORTED,
+                        COPY_WITHIN_QUERY.to_string(),
+                    ))
+                }
+                BackendMessage::CopyData(_) | BackendMessage::CopyDone => {}
+                BackendMessage::ParameterStatus(status) => record_parameter_change(
+                    &mut self.resolver.parameter_changes,
+                    self.client_id,
+                    connection,
+                    status,
+                ),
+                _ => unimplemented!(""),
+            }
+        };
+
+        self.finished = true;
+        self.upstream += started.elapsed();
+        observe_stage(Stage::Upstream, self.upstream);
+
+        self.resolver.release_connection(self.client_id, status);
+
+        if let Some(error) = self.error.take() {
+            return Err(ResolveError::Target(error));
+        }
+
+        if let Some(length) = self.too_large {
+            return Err(value_too_large(length, self.resolver.max_value_size));
+        }
+
+        // Results without rows still have a batch, which carries their schema
+        if self.has_batch && self.data_rows.is_empty() {
+            return Ok(None);
+        }
+
+        self.take_batch().map(Some)
+    }
+
+    fn take_batch(&mut self) -> Result<RecordBatch, ResolveError> {
+        let data_rows = std::mem::take(&mut self.data_rows);
+        self.has_batch = true;
+
+        simple_query_response_to_record_batches(&self.fields, &data_rows, data_rows.len())?
+            .pop()
+            .ok_or_else(|| "the result has no batch".into())
+    }
+}
+
+impl Drop for StreamedResult<'_> {
+    fn drop(&mut self) {
+        // The rest of the result can't be read without awaiting, so the connection
+        // is closed instead of being used for the client's next query
+        if !self.finished {
+            self.resolver.discard_connection(self.client_id);
+        }
+    }
+}
+
+/// The data of a copy out, which is read from the target one message at a time
+struct StreamedCopyOut<'a> {
+    resolver: &'a mut PostgresResolver,
+    client_id: ClientId,
+    // Whether the target is ready for the next query
+    finished: bool,
+}
+
+impl StreamedCopyOut<'_> {
+    /// Reads the next chunk of the data, the command complete ends it
+    async fn next_message(&mut self) -> Result<Option<CopyOutMessage>, ResolveError> {
+        if self.finished {
+            return Ok(None);
+        }
+
+        let connection = self
+            .resolver
+            .active_connections
+            .get_mut(&self.client_id)
+            .ok_or("the client has no connection")?;
+
+        let result = loop {
+            match connection.read_message().await? {
+                BackendMessage::CopyData(data) => return Ok(Some(CopyOutMessage::Data(data))),
+                BackendMessage::CopyDone => {}
+                BackendMessage::CommandComplete(tag) => break Ok(tag),
+                // The server still sends ReadyForQuery after an error
+                BackendMessage::Error(error) => break Err(ResolveError::Target(error)),
+                BackendMessage::ParameterStatus(status) => record_parameter_change(
+                    &mut self.resolver.parameter_changes,
+                    self.client_id,
+                    connection,
+                    status,
+                ),
+                message => {
+                    return Err(anyhow::anyhow!(
+                        "unexpected message from the target: {:?}",
+                        message
+                    )
+                    .into())
+                }
+            }
+        };
+
+        let status = self.resolver.read_ready_for_query(self.client_id).await?;
+        self.finished = true;
+        self.resolver.release_connection(self.client_id, status);
+
+        result.map(|tag| Some(CopyOutMessage::Complete(tag)))
+    }
+}
+
+impl Drop for StreamedCopyOut<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            self.resolver.discard_connection(self.client_id);
+        }
     }
 }
 
@@ -90,13 +773,20 @@ macro_rules! get_connection {
     ($resolver:ident, $client_id:ident) => {
         match $resolver.active_connections.entry($client_id) {
             Vacant(entry) => {
-                let connection = $resolver
+                let mut connection = $resolver
                     .pool
                     .get()
                     .await
                     .map_err(|err| ResolveError::Other(anyhow::anyhow!(err)))?;
 
-                let value = ActiveConnection::new(connection);
+                if let Some(application_name) = $resolver.application_names.get(&$client_id) {
+                    set_application_name(&mut connection, application_name).await?;
+                }
+
+                tracing::debug!(backend.pid = ?connection.backend_pid, "acquired upstream connection");
+                sessions::set_backend_pid(&$client_id, connection.backend_pid);
+
+                let value = ActiveConnection::new(UpstreamConnection::Pooled(connection));
 
                 entry.insert(value)
             }
@@ -105,42 +795,117 @@ macro_rules! get_connection {
     };
 }
 
+/// Keeps a parameter the target reported as changed to send it to the client. A pooled
+/// connection is reset before it serves another client, which expects the initial parameters.
+fn record_parameter_change(
+    parameter_changes: &mut HashMap<ClientId, Vec<ParameterStatus>>,
+    client_id: ClientId,
+    connection: &mut ActiveConnection,
+    status: ParameterStatus,
+) {
+    tracing::debug!(parameter = %status.key, value = %status.value, "target changed a parameter");
+    connection.connection.mark_parameters_changed();
+    parameter_changes.entry(client_id).or_default().push(status);
+}
+
+/// Sets the application name of the connection, which postgres truncates to 63 bytes
+async fn set_application_name(
+    connection: &mut Connection,
+    application_name: &str,
+) -> Result<(), ResolveError> {
+    connection
+        .write_message(
+            FrontendMessage::SimpleQuery(format!(
+                "SET application_name = '{}'",
+                application_name.replace('\'', "''")
+            ))
+            .into(),
+        )
+        .await?;
+
+    let mut error = None;
+    loop {
+        match connection.read_backend_message().await? {
+            BackendMessage::ReadyForQuery(_) => break,
+            BackendMessage::Error(message) => error = Some(message),
+            _ => {}
+        }
+    }
+
+    match error {
+        Some(error) => Err(ResolveError::Target(error)),
+        None => Ok(()),
+    }
+}
+
+const COPY_WITHIN_QUERY: &str = "COPY is only supported as a single statement of a simple query";
+
+fn value_too_large(length: usize, max_value_size: Option<usize>) -> ResolveError {
+    anyhow::anyhow!(
+        "the result contains a value of {} bytes, exceeding the maximum value size of {} bytes",
+        length,
+        max_value_size.unwrap_or_default()
+    )
+    .into()
+}
+
+/// Applies the result formats of a bind message to the fields of the portal.
+/// No formats mean text, a single format applies to all columns.
+fn apply_result_formats(fields: &mut [Field], result_formats: &[i16]) {
+    match result_formats {
+        [] => {}
+        [format] => fields.iter_mut().for_each(|field| field.format = *format),
+        formats => fields
+            .iter_mut()
+            .zip(formats.iter())
+            .for_each(|(field, format)| field.format = *format),
+    }
+}
+
 #[async_trait]
 impl Resolver for PostgresResolver {
     async fn query(
         &mut self,
         client_id: ClientId,
         query: String,
-    ) -> Result<RecordBatch, ResolveError> {
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        self.query_stream(client_id, query)
+            .await?
+            .try_collect()
+            .await
+    }
+
+    async fn query_stream<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<RecordBatchStream<'a>, ResolveError> {
         let connection = get_connection!(self, client_id);
+        connection.track_listen(&query);
 
+        let started = Instant::now();
         connection
             .connection
             .write_message(FrontendMessage::SimpleQuery(query).into())
             .await?;
 
-        let mut fields = vec![];
-        let mut data_rows = vec![];
-        loop {
-            let response = connection.connection.read_backend_message().await?;
-            match response {
-                BackendMessage::ReadyForQuery(_) => break,
-                BackendMessage::RowDescription(RowDescription {
-                    fields: mut message_fields,
-                }) => fields.append(&mut message_fields),
-                BackendMessage::DataRow(data_row) => {
-                    data_rows.push(data_row);
-                }
-                BackendMessage::CommandComplete(CommandCompleteTag(_)) => {
-                    // TODO: Handle this
-                }
-                _ => unimplemented!(""),
-            }
-        }
-
-        let data = simple_query_response_to_record_batch(&fields, &data_rows)?;
+        let result = StreamedResult {
+            resolver: self,
+            client_id,
+            upstream: started.elapsed(),
+            fields: vec![],
+            data_rows: vec![],
+            error: None,
+            too_large: None,
+            has_batch: false,
+            finished: false,
+        };
 
-        Ok(data)
+        Ok(stream::try_unfold(result, |mut result| async move {
+            let batch = result.next_batch().await?;
+            Ok(batch.map(|batch| (batch, result)))
+        })
+        .boxed())
     }
 
     async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
@@ -148,6 +913,7 @@ impl Resolver for PostgresResolver {
 
         let statement_name = parse.statement_name.clone();
         let query = parse.query.clone();
+        connection.track_listen(&query);
 
         connection
             .connection
@@ -168,7 +934,8 @@ impl Resolver for PostgresResolver {
     ) -> Result<(), ResolveError> {
         let connection = get_connection!(self, client_id);
 
-        let statement = describe.name.clone();
+        let kind = describe.kind;
+        let name = describe.name.clone();
 
         connection
             .connection
@@ -177,7 +944,7 @@ impl Resolver for PostgresResolver {
 
         connection
             .requested_ops
-            .push_back(ClientOperation::Describe { statement });
+            .push_back(ClientOperation::Describe { kind, name });
 
         Ok(())
     }
@@ -187,15 +954,18 @@ impl Resolver for PostgresResolver {
 
         let statement = bind.statement.clone();
         let portal = bind.portal.clone();
+        let result_formats = bind.results.clone();
 
         connection
             .connection
             .write_message(FrontendMessage::Bind(bind).into())
             .await?;
 
-        connection
-            .requested_ops
-            .push_back(ClientOperation::Bind { statement, portal });
+        connection.requested_ops.push_back(ClientOperation::Bind {
+            statement,
+            portal,
+            result_formats,
+        });
 
         Ok(())
     }
@@ -220,131 +990,269 @@ impl Resolver for PostgresResolver {
     async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
         let connection = get_connection!(self, client_id);
 
+        // Includes converting the rows of the responses to records
+        let started = Instant::now();
         connection
             .connection
             .write_message(FrontendMessage::Sync.into())
             .await?;
 
-        let mut responses = vec![];
-        'client_request: while let Some(operation) = &connection.requested_ops.pop_front() {
-            match operation {
-                ClientOperation::Parse => {
-                    let read_message = connection.connection.read_backend_message().await?;
-
-                    match read_message {
-                        BackendMessage::ParseComplete => {
-                            responses.push(SyncResponse::ParseComplete)
-                        }
-                        _ => todo!(),
-                    }
-                }
-                ClientOperation::Describe { statement } => loop {
-                    let read_message = connection.connection.read_backend_message().await?;
+        // After an error the target skips the remaining operations, but still answers the sync
+        let mut responses = self.read_responses(client_id).await?;
 
-                    match read_message {
-                        BackendMessage::RowDescription(RowDescription { fields }) => {
-                            let schema = protocol_fields_to_schema(&fields)?;
+        let status = self.read_ready_for_query(client_id).await?;
+        responses.push(SyncResponse::ReadyForQuery);
+        observe_stage(Stage::Upstream, started.elapsed());
 
-                            responses.push(SyncResponse::Schema {
-                                schema: schema.clone(),
-                                query: self.statement_query_cache.get(statement).unwrap().clone(),
-                            });
+        self.release_connection(client_id, status);
 
-                            self.statement_schema_cache
-                                .insert(statement.clone(), schema.clone());
+        Ok(responses)
+    }
 
-                            break;
-                        }
-                        BackendMessage::ParameterDescription(parameter_description) => responses
-                            .push(SyncResponse::ParameterDescription(parameter_description)),
-                        BackendMessage::NoData => {
-                            responses.push(SyncResponse::NoData);
-                            break;
-                        }
-                        _ => todo!(),
-                    }
-                },
-                ClientOperation::Bind { statement, portal } => {
-                    let read_message = connection.connection.read_backend_message().await?;
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let connection = get_connection!(self, client_id);
 
-                    self.portal_cache.insert(portal.clone(), statement.clone());
+        let started = Instant::now();
+        connection
+            .connection
+            .write_message(FrontendMessage::Flush.into())
+            .await?;
 
-                    match read_message {
-                        BackendMessage::BindComplete => responses.push(SyncResponse::BindComplete),
-                        _ => todo!(),
-                    }
-                }
-                ClientOperation::Execute { portal } => {
-                    let mut data_rows: Vec<DataRow> = vec![];
-                    let command_complete_tag;
+        let responses = self.read_responses(client_id).await?;
+        observe_stage(Stage::Upstream, started.elapsed());
 
-                    loop {
-                        let read_message = connection.connection.read_backend_message().await?;
+        Ok(responses)
+    }
 
-                        match read_message {
-                            BackendMessage::DataRow(data_row) => data_rows.push(data_row),
-                            BackendMessage::CommandComplete(tag) => {
-                                command_complete_tag = Some(tag);
-                                break;
-                            }
-                            BackendMessage::PortalSuspended => {
-                                command_complete_tag = None;
-                                break;
-                            }
-                            BackendMessage::EmptyQueryResponse => {
-                                responses.push(SyncResponse::EmptyQueryResponse);
-                                break 'client_request;
-                            }
-                            _ => todo!(),
-                        }
-                    }
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let connection = get_connection!(self, client_id);
+
+        let is_idle = connection.requested_ops.is_empty();
 
-                    let statement = self.portal_cache.get(portal).unwrap();
-                    let schema = self.statement_schema_cache.get(statement).unwrap();
+        connection
+            .connection
+            .write_message(FrontendMessage::Close(close).into())
+            .await?;
+        connection.requested_ops.push_back(ClientOperation::Close);
+
+        // Otherwise the response is read with those of the outstanding operations
+        if is_idle {
+            connection
+                .connection
+                .write_message(FrontendMessage::Flush.into())
+                .await?;
+            let responses = self.read_responses(client_id).await?;
+            if let Some(error) = sync_error(&responses) {
+                return Err(ResolveError::Target(error.clone()));
+            }
+        }
 
-                    let RowDescription { fields } =
-                        serialize_record_batch_schema_to_row_description(schema);
+        Ok(())
+    }
 
-                    let record_batch = simple_query_response_to_record_batch(&fields, &data_rows)?;
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        let connection = get_connection!(self, client_id);
 
-                    responses.push(SyncResponse::Records {
-                        data: record_batch,
-                        query: self.statement_query_cache.get(statement).unwrap().clone(),
-                    });
+        connection
+            .connection
+            .write_message(FrontendMessage::SimpleQuery(query).into())
+            .await?;
 
-                    match command_complete_tag {
-                        Some(tag) => responses.push(SyncResponse::CommandComplete(tag)),
-                        None => responses.push(SyncResponse::PortalSuspended),
-                    }
-                }
+        match self.read_copy_response(client_id).await? {
+            BackendMessage::CopyInResponse(response) => Ok(response),
+            message => {
+                self.discard_connection(client_id);
+                Err(anyhow::anyhow!("unexpected message from the target: {:?}", message).into())
             }
         }
+    }
 
-        let read_message = connection.connection.read_backend_message().await?;
-        match read_message {
-            BackendMessage::ReadyForQuery(_) => SyncResponse::ReadyForQuery,
-            _ => todo!(),
-        };
-        responses.push(SyncResponse::ReadyForQuery);
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        let connection = self
+            .active_connections
+            .get_mut(&client_id)
+            .ok_or("the client has no connection")?;
 
-        Ok(responses)
+        connection
+            .connection
+            .write_message(FrontendMessage::CopyData(data).into())
+            .await?;
+
+        Ok(())
     }
 
-    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        let connection = self
+            .active_connections
+            .get_mut(&client_id)
+            .ok_or("the client has no connection")?;
+
+        connection
+            .connection
+            .write_message(FrontendMessage::CopyDone.into())
+            .await?;
+
+        self.read_copy_in_result(client_id).await
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        let connection = self
+            .active_connections
+            .get_mut(&client_id)
+            .ok_or("the client has no connection")?;
+
+        connection
+            .connection
+            .write_message(FrontendMessage::CopyFail(message).into())
+            .await?;
+
+        self.read_copy_in_result(client_id).await.map(|_| ())
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
         let connection = get_connection!(self, client_id);
 
         connection
             .connection
-            .write_message(FrontendMessage::Close(close).into())
+            .write_message(FrontendMessage::SimpleQuery(query).into())
             .await?;
 
-        let _read_message = connection.connection.read_backend_message().await?;
-        // TODO: Handle response
+        let response = match self.read_copy_response(client_id).await? {
+            BackendMessage::CopyOutResponse(response) => response,
+            message => {
+                self.discard_connection(client_id);
+                return Err(
+                    anyhow::anyhow!("unexpected message from the target: {:?}", message).into(),
+                );
+            }
+        };
+
+        let copy_out = StreamedCopyOut {
+            resolver: self,
+            client_id,
+            finished: false,
+        };
+
+        let messages = stream::try_unfold(copy_out, |mut copy_out| async move {
+            let message = copy_out.next_message().await?;
+            Ok(message.map(|message| (message, copy_out)))
+        })
+        .boxed();
+
+        Ok((response, messages))
+    }
+
+    async fn authenticate(
+        &mut self,
+        client_id: ClientId,
+        frontend: &mut Connection,
+    ) -> Result<(), ResolveError> {
+        let connection = establish_relayed_connection(&self.target_config, frontend).await?;
+
+        tracing::debug!(backend.pid = ?connection.backend_pid, "opened upstream connection");
+        sessions::set_backend_pid(&client_id, connection.backend_pid);
+
+        self.active_connections.insert(
+            client_id,
+            ActiveConnection::new(UpstreamConnection::Dedicated(connection)),
+        );
 
         Ok(())
     }
 
-    async fn initialize(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        if let Some(prefix) = &self.application_name_prefix {
+            let application_name = format!(
+                "{}:{}:{}",
+                prefix,
+                context.user().unwrap_or_default(),
+                sessions::correlation_id(&client_id)
+            );
+
+            // Connections opened by `authenticate` are already in use by the client
+            if let Some(connection) = self.active_connections.get_mut(&client_id) {
+                set_application_name(&mut connection.connection, &application_name).await?;
+            }
+
+            self.application_names.insert(client_id, application_name);
+        }
+
+        Ok(())
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.parameter_changes
+            .remove(&client_id)
+            .unwrap_or_default()
+    }
+
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        self.active_connections
+            .get_mut(&client_id)
+            .map(|connection| std::mem::take(&mut connection.notices))
+            .unwrap_or_default()
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.active_connections
+            .get_mut(&client_id)
+            .map(|connection| std::mem::take(&mut connection.notifications))
+            .unwrap_or_default()
+    }
+
+    // Only connections of listening clients are notified while they are idle
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        match self.active_connections.get_mut(&client_id) {
+            Some(connection) if connection.listening => {
+                Ok(connection.connection.wait_for_message().await?)
+            }
+            _ => future::pending().await,
+        }
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        let connection = match self.active_connections.get_mut(&client_id) {
+            Some(connection) if connection.connection.has_pending_message() => connection,
+            _ => return Ok(()),
+        };
+
+        match connection.connection.read_backend_message().await? {
+            BackendMessage::NotificationResponse(notification) => {
+                connection.notifications.push(notification)
+            }
+            BackendMessage::NoticeResponse(notice) => connection.notices.push(notice),
+            BackendMessage::ParameterStatus(status) => {
+                record_parameter_change(&mut self.parameter_changes, client_id, connection, status)
+            }
+            // E.g. the backend was terminated
+            BackendMessage::Error(error) => {
+                self.discard_connection(client_id);
+                return Err(ResolveError::Target(error));
+            }
+            message => {
+                self.discard_connection(client_id);
+                return Err(
+                    anyhow::anyhow!("unexpected message from the target: {:?}", message).into(),
+                );
+            }
+        }
+
         Ok(())
     }
 
@@ -1,6 +1,6 @@
 use crate::target_config::TargetConfig;
 use async_trait::async_trait;
-use deadpool::managed::RecycleResult;
+use deadpool::managed::{RecycleError, RecycleResult};
 use proboscis_core::{
     resolver::ResolveError,
     utils::connection::{Connection, MaybeTlsStream},
@@ -10,35 +10,214 @@ use proboscis_postgres_protocol::{
     message::{BackendMessage, FrontendMessage, MD5Hash, MD5Salt},
     StartupMessage,
 };
-use std::collections::HashMap;
+use std::{
+    collections::HashMap,
+    ops::{Deref, DerefMut},
+    time::{Duration, Instant},
+};
 
 pub type Pool = deadpool::managed::Pool<Manager>;
 
+/// When a client gives its connection back to the pool
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PoolMode {
+    /// The connection stays with the client until it disconnects
+    Session,
+    /// The connection is returned once no transaction is open, so clients can't rely on
+    /// session state like named prepared statements or `SET` between transactions
+    Transaction,
+}
+
+impl Default for PoolMode {
+    fn default() -> Self {
+        PoolMode::Session
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct PoolConfig {
+    pub max_size: usize,
+    /// Connections opened when the resolver is created
+    pub min_size: usize,
+    /// Maximum time to wait for a free connection and to open a new one
+    pub acquisition_timeout: Option<Duration>,
+    /// Connections older than this are closed instead of being reused
+    pub max_lifetime: Option<Duration>,
+    pub mode: PoolMode,
+}
+
+impl PoolConfig {
+    pub fn new(max_size: usize) -> PoolConfig {
+        PoolConfig {
+            max_size,
+            min_size: 0,
+            acquisition_timeout: None,
+            max_lifetime: None,
+            mode: PoolMode::Session,
+        }
+    }
+}
+
+/// A connection of the pool, which knows when it was opened
+#[derive(Debug)]
+pub struct PooledConnection {
+    connection: Connection,
+    created: Instant,
+    // A client changed parameters of the session, e.g. by `SET TimeZone`,
+    // which are reset before the connection serves the next client
+    parameters_changed: bool,
+    // A client listened on channels, which are unlistened before the connection serves
+    // the next client
+    listening: bool,
+}
+
+impl Deref for PooledConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        &self.connection
+    }
+}
+
+impl DerefMut for PooledConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        &mut self.connection
+    }
+}
+
+/// The connection a client uses, either one of the pool or one opened for the client alone
+#[derive(Debug)]
+pub enum UpstreamConnection {
+    Pooled(deadpool::managed::Object<Manager>),
+    /// Authenticated as the client itself, closed once the client disconnects
+    Dedicated(Connection),
+}
+
+impl UpstreamConnection {
+    /// Notes that the target reported a changed parameter, so a pooled connection isn't
+    /// shared with other clients before its parameters are reset
+    pub fn mark_parameters_changed(&mut self) {
+        if let UpstreamConnection::Pooled(connection) = self {
+            connection.parameters_changed = true;
+        }
+    }
+
+    /// Notes that the client listens on channels, so a pooled connection isn't shared with
+    /// other clients before it stops listening
+    pub fn mark_listening(&mut self) {
+        if let UpstreamConnection::Pooled(connection) = self {
+            connection.listening = true;
+        }
+    }
+
+    /// Closes the connection, without returning it to the pool, e.g. if it is in
+    /// the middle of a response
+    pub fn discard(self) {
+        if let UpstreamConnection::Pooled(connection) = self {
+            deadpool::managed::Object::take(connection);
+        }
+    }
+}
+
+impl Deref for UpstreamConnection {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        match self {
+            UpstreamConnection::Pooled(connection) => connection,
+            UpstreamConnection::Dedicated(connection) => connection,
+        }
+    }
+}
+
+impl DerefMut for UpstreamConnection {
+    fn deref_mut(&mut self) -> &mut Connection {
+        match self {
+            UpstreamConnection::Pooled(connection) => connection,
+            UpstreamConnection::Dedicated(connection) => connection,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct Manager {
     target_config: TargetConfig,
+    max_lifetime: Option<Duration>,
 }
 
 impl Manager {
-    pub fn new(target_config: TargetConfig) -> Self {
-        Self { target_config }
+    pub fn new(target_config: TargetConfig, max_lifetime: Option<Duration>) -> Self {
+        Self {
+            target_config,
+            max_lifetime,
+        }
     }
 }
 
 #[async_trait]
 impl deadpool::managed::Manager for Manager {
-    type Type = Connection;
+    type Type = PooledConnection;
     type Error = ResolveError;
 
-    async fn create(&self) -> Result<Connection, ResolveError> {
-        establish_connection(&self.target_config).await
+    async fn create(&self) -> Result<PooledConnection, ResolveError> {
+        Ok(PooledConnection {
+            connection: establish_connection(&self.target_config).await?,
+            created: Instant::now(),
+            parameters_changed: false,
+            listening: false,
+        })
     }
 
-    async fn recycle(&self, _conn: &mut Connection) -> RecycleResult<ResolveError> {
+    async fn recycle(&self, conn: &mut PooledConnection) -> RecycleResult<ResolveError> {
+        match self.max_lifetime {
+            Some(max_lifetime) if conn.created.elapsed() > max_lifetime => {
+                return Err(RecycleError::Message(
+                    "Connection exceeded its max lifetime".to_string(),
+                ))
+            }
+            _ => {}
+        }
+
+        if conn.parameters_changed {
+            reset_session(&mut conn.connection, "RESET ALL")
+                .await
+                .map_err(RecycleError::Backend)?;
+            conn.parameters_changed = false;
+        }
+
+        if conn.listening {
+            reset_session(&mut conn.connection, "UNLISTEN *")
+                .await
+                .map_err(RecycleError::Backend)?;
+            conn.listening = false;
+        }
+
         Ok(())
     }
 }
 
+/// Resets the state a client left in the session, e.g. its parameters with `RESET ALL`.
+/// The changes and notifications the target reports on the way are discarded.
+async fn reset_session(connection: &mut Connection, statement: &str) -> Result<(), ResolveError> {
+    connection
+        .write_message(FrontendMessage::SimpleQuery(statement.to_string()).into())
+        .await?;
+
+    let mut error = None;
+    loop {
+        match connection.read_backend_message().await? {
+            BackendMessage::ReadyForQuery(_) => break,
+            BackendMessage::Error(message) => error = Some(message),
+            _ => {}
+        }
+    }
+
+    match error {
+        Some(error) => Err(ResolveError::Target(error)),
+        None => Ok(()),
+    }
+}
+
 pub async fn establish_connection(
     target_config: &TargetConfig,
 ) -> Result<Connection, ResolveError> {
@@ -63,15 +242,20 @@ pub async fn establish_connection(
     let response = connection.read_backend_message().await?;
     match response {
         BackendMessage::AuthenticationRequestMD5Password(MD5Salt(salt)) => {
+            let password = match &target_config.credentials_provider {
+                Some(credentials_provider) => credentials_provider.password().await?,
+                None => target_config
+                    .password
+                    .clone()
+                    .expect("Missing password in target_config"),
+            };
+
             let hash = encode_md5_password_hash(
                 target_config
                     .user
                     .as_ref()
                     .expect("Missing username in target_config"),
-                target_config
-                    .password
-                    .as_ref()
-                    .expect("Missing password in target_config"),
+                &password,
                 &salt[..],
             );
 
@@ -102,12 +286,109 @@ pub async fn establish_connection(
             BackendMessage::ParameterStatus(_) => {
                 // TODO: Handle this
             }
-            BackendMessage::BackendKeyData(_) => {
-                // TODO: Handle this
+            BackendMessage::BackendKeyData(key_data) => {
+                connection.backend_pid = Some(key_data.process_id);
             }
+            // No client is connected yet to receive the notice
+            BackendMessage::NoticeResponse(_) => {}
             _ => unimplemented!("Unexpected message"),
         }
     }
 
     Ok(connection)
 }
+
+/// Opens a connection as the user of the client and relays the authentication exchange
+/// between the client and the target, e.g. SCRAM, so the proxy never learns the password.
+/// The parameters the target reports are forwarded to the client, followed by `ReadyForQuery`.
+pub async fn establish_relayed_connection(
+    target_config: &TargetConfig,
+    frontend: &mut Connection,
+) -> Result<Connection, ResolveError> {
+    let stream =
+        tokio::net::TcpStream::connect(&format!("{}:{}", target_config.host, target_config.port))
+            .await?;
+
+    let mut params: HashMap<String, String> = HashMap::new();
+
+    if let Some(user) = frontend.parameters.get("user") {
+        params.insert("user".to_string(), user.to_string());
+    }
+
+    if let Some(database) = target_config
+        .database
+        .as_ref()
+        .or_else(|| frontend.parameters.get("database"))
+    {
+        params.insert("database".to_string(), database.to_string());
+    }
+
+    params.insert("client_encoding".to_string(), "UTF8".to_string());
+
+    let mut connection = Connection::new(MaybeTlsStream::Left(stream), params.clone());
+
+    connection
+        .write_startup_message(StartupMessage::Startup { params })
+        .await?;
+
+    loop {
+        match connection.read_backend_message().await? {
+            BackendMessage::AuthenticationOk => {
+                frontend
+                    .write_message(BackendMessage::AuthenticationOk.into())
+                    .await?;
+                break;
+            }
+            request @ BackendMessage::AuthenticationRequestMD5Password(_) => {
+                frontend.write_message(request.into()).await?;
+                let response = frontend.read_frontend_message().await?;
+                connection.write_message(response.into()).await?;
+            }
+            request @ BackendMessage::AuthenticationSASL(_) => {
+                frontend.write_message(request.into()).await?;
+                let response = frontend.read_sasl_initial_response().await?;
+                connection.write_message(response.into()).await?;
+            }
+            request @ BackendMessage::AuthenticationSASLContinue(_) => {
+                frontend.write_message(request.into()).await?;
+                let response = frontend.read_sasl_response().await?;
+                connection.write_message(response.into()).await?;
+            }
+            message @ BackendMessage::AuthenticationSASLFinal(_) => {
+                frontend.write_message(message.into()).await?;
+            }
+            BackendMessage::Error(error) => return Err(ResolveError::Target(error)),
+            message => {
+                return Err(ResolveError::Other(anyhow::anyhow!(
+                    "Unexpected message during authentication: {:?}",
+                    message
+                )))
+            }
+        }
+    }
+
+    loop {
+        match connection.read_backend_message().await? {
+            message @ BackendMessage::ParameterStatus(_)
+            | message @ BackendMessage::NoticeResponse(_) => {
+                frontend.write_message(message.into()).await?;
+            }
+            BackendMessage::BackendKeyData(key_data) => {
+                connection.backend_pid = Some(key_data.process_id);
+            }
+            message @ BackendMessage::ReadyForQuery(_) => {
+                frontend.write_message(message.into()).await?;
+                break;
+            }
+            BackendMessage::Error(error) => return Err(ResolveError::Target(error)),
+            message => {
+                return Err(ResolveError::Other(anyhow::anyhow!(
+                    "Unexpected message during startup: {:?}",
+                    message
+                )))
+            }
+        }
+    }
+
+    Ok(connection)
+}
@@ -0,0 +1,263 @@
+use crate::pool::establish_connection;
+use crate::target_config::TargetConfig;
+use async_trait::async_trait;
+use proboscis_core::{
+    catalog::{Catalog, CatalogCache, CatalogColumn, CatalogRelation, CatalogSource, RelationKind},
+    resolver::ResolveError,
+};
+use proboscis_postgres_protocol::message::{
+    BackendMessage, DataRow, FrontendMessage, NotificationResponse,
+};
+use std::{sync::Arc, time::Duration};
+use tokio::task::JoinHandle;
+
+// One row per column, relations without columns have a single row of nulls for them
+const RELATIONS_QUERY: &str = "SELECT c.oid, n.nspname, c.relname, c.relkind, \
+    a.attname, a.attnum, a.atttypid, format_type(a.atttypid, NULL), \
+    CASE WHEN c.relkind IN ('v', 'm') THEN pg_get_viewdef(c.oid) END \
+    FROM pg_class c \
+    JOIN pg_namespace n ON n.oid = c.relnamespace \
+    LEFT JOIN pg_attribute a ON a.attrelid = c.oid AND a.attnum > 0 AND NOT a.attisdropped \
+    WHERE c.relkind IN ('r', 'v', 'm', 'f', 'p') \
+    AND n.nspname NOT IN ('pg_catalog', 'information_schema') \
+    AND n.nspname NOT LIKE 'pg_toast%' \
+    ORDER BY c.oid, a.attnum";
+
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+type Row = Vec<Option<String>>;
+
+/// Loads the tables, views and their columns from the system catalogs of the target
+pub struct PostgresCatalogSource {
+    target_config: TargetConfig,
+}
+
+impl PostgresCatalogSource {
+    pub fn new(target_config: TargetConfig) -> PostgresCatalogSource {
+        PostgresCatalogSource { target_config }
+    }
+}
+
+#[async_trait]
+impl CatalogSource for PostgresCatalogSource {
+    async fn load(&self) -> Result<Catalog, ResolveError> {
+        let mut connection = establish_connection(&self.target_config).await?;
+
+        connection
+            .write_message(FrontendMessage::SimpleQuery(RELATIONS_QUERY.to_string()).into())
+            .await?;
+
+        let mut rows = vec![];
+        let mut error = None;
+        loop {
+            match connection.read_backend_message().await? {
+                BackendMessage::ReadyForQuery(_) => break,
+                BackendMessage::DataRow(DataRow { field_data }) => rows.push(
+                    field_data
+                        .into_iter()
+                        .map(|value| value.map(|value| String::from_utf8_lossy(&value).to_string()))
+                        .collect(),
+                ),
+                BackendMessage::Error(err) => error = Some(err),
+                _ => {}
+            }
+        }
+
+        connection
+            .write_message(FrontendMessage::Terminate.into())
+            .await?;
+
+        if let Some(error) = error {
+            return Err(ResolveError::Target(error));
+        }
+
+        Ok(Catalog {
+            relations: relations_from_rows(rows)?,
+        })
+    }
+}
+
+fn value<'a>(row: &'a Row, index: usize) -> Result<&'a str, ResolveError> {
+    row.get(index)
+        .and_then(|value| value.as_deref())
+        .ok_or_else(|| ResolveError::from("Missing value in the catalog"))
+}
+
+fn parse<T: std::str::FromStr>(row: &Row, index: usize) -> Result<T, ResolveError> {
+    value(row, index)?
+        .parse()
+        .map_err(|_| ResolveError::from("Invalid number in the catalog"))
+}
+
+fn relation_kind(relkind: &str) -> Result<RelationKind, ResolveError> {
+    match relkind {
+        "r" => Ok(RelationKind::Table),
+        "v" => Ok(RelationKind::View),
+        "m" => Ok(RelationKind::MaterializedView),
+        "f" => Ok(RelationKind::ForeignTable),
+        "p" => Ok(RelationKind::PartitionedTable),
+        _ => Err(ResolveError::from("Unknown relation kind in the catalog")),
+    }
+}
+
+fn relations_from_rows(rows: Vec<Row>) -> Result<Vec<CatalogRelation>, ResolveError> {
+    let mut relations: Vec<CatalogRelation> = vec![];
+
+    for row in rows {
+        let oid: u32 = parse(&row, 0)?;
+
+        if relations.last().map(|relation| relation.oid) != Some(oid) {
+            relations.push(CatalogRelation {
+                oid,
+                schema: value(&row, 1)?.to_string(),
+                name: value(&row, 2)?.to_string(),
+                kind: relation_kind(value(&row, 3)?)?,
+                columns: vec![],
+                // Definitions end with a semicolon, which would end the query they're nested in
+                definition: row[8]
+                    .as_deref()
+                    .map(|definition| definition.trim().trim_end_matches(';').to_string()),
+            });
+        }
+
+        if row[4].is_none() {
+            continue;
+        }
+
+        relations.last_mut().unwrap().columns.push(CatalogColumn {
+            name: value(&row, 4)?.to_string(),
+            number: parse(&row, 5)?,
+            type_oid: parse(&row, 6)?,
+            type_name: value(&row, 7)?.to_string(),
+        });
+    }
+
+    Ok(relations)
+}
+
+fn quote_identifier(identifier: &str) -> String {
+    format!("\"{}\"", identifier.replace('"', "\"\""))
+}
+
+/// Listens on the channel in the background and invalidates the cache on every notification,
+/// e.g. those an event trigger sends for DDL statements run by other clients of the target.
+/// The cache is invalidated after reconnecting as well, notifications might have been missed.
+pub fn listen_for_catalog_changes(
+    target_config: TargetConfig,
+    channel: String,
+    cache: Arc<CatalogCache>,
+) -> JoinHandle<()> {
+    tokio::spawn(async move {
+        loop {
+            if let Err(err) = listen(&target_config, &channel, &cache).await {
+                tracing::warn!(channel = %channel, "listening for catalog changes failed: {}", err);
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    })
+}
+
+async fn listen(
+    target_config: &TargetConfig,
+    channel: &str,
+    cache: &CatalogCache,
+) -> Result<(), ResolveError> {
+    let mut connection = establish_connection(target_config).await?;
+
+    connection
+        .write_message(
+            FrontendMessage::SimpleQuery(format!("LISTEN {}", quote_identifier(channel))).into(),
+        )
+        .await?;
+
+    cache.invalidate();
+
+    loop {
+        match connection.read_backend_message().await? {
+            BackendMessage::NotificationResponse(NotificationResponse {
+                process_id: _,
+                channel: notified_channel,
+                payload,
+            }) if notified_channel == channel => {
+                tracing::debug!(payload = %payload, "catalog change notified");
+                cache.invalidate();
+            }
+            BackendMessage::Error(err) => return Err(ResolveError::Target(err)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(values: &[Option<&str>]) -> Row {
+        values
+            .iter()
+            .map(|value| value.map(|value| value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_relations_from_rows() {
+        let relations = relations_from_rows(vec![
+            row(&[
+                Some("16384"),
+                Some("public"),
+                Some("contacts"),
+                Some("r"),
+                Some("id"),
+                Some("1"),
+                Some("23"),
+                Some("integer"),
+                None,
+            ]),
+            row(&[
+                Some("16384"),
+                Some("public"),
+                Some("contacts"),
+                Some("r"),
+                Some("name"),
+                Some("2"),
+                Some("25"),
+                Some("text"),
+                None,
+            ]),
+            row(&[
+                Some("16390"),
+                Some("public"),
+                Some("names"),
+                Some("v"),
+                Some("name"),
+                Some("1"),
+                Some("25"),
+                Some("text"),
+                Some(" SELECT contacts.name\n   FROM contacts;"),
+            ]),
+            row(&[
+                Some("16395"),
+                Some("public"),
+                Some("empty"),
+                Some("r"),
+                None,
+                None,
+                None,
+                None,
+                None,
+            ]),
+        ])
+        .unwrap();
+
+        assert_eq!(3, relations.len());
+        assert_eq!(2, relations[0].columns.len());
+        assert_eq!("text", relations[0].columns[1].type_name);
+        assert_eq!(RelationKind::View, relations[1].kind);
+        assert_eq!(
+            Some("SELECT contacts.name\n   FROM contacts"),
+            relations[1].definition.as_deref()
+        );
+        assert!(relations[2].columns.is_empty());
+    }
+}
@@ -1,3 +1,5 @@
+use crate::credentials::CredentialsProvider;
+use std::sync::Arc;
 use url::Url;
 
 #[derive(Clone, Debug)]
@@ -7,6 +9,8 @@ pub struct TargetConfig {
     pub database: Option<String>,
     pub user: Option<String>,
     pub password: Option<String>,
+    /// Takes precedence over `password`
+    pub credentials_provider: Option<Arc<dyn CredentialsProvider>>,
 }
 
 impl TargetConfig {
@@ -47,10 +51,20 @@ impl TargetConfig {
             database,
             user,
             password,
+            credentials_provider: None,
         };
 
         Ok(config)
     }
+
+    /// Authenticates new connections with the password of the provider
+    pub fn with_credentials_provider(
+        mut self,
+        credentials_provider: Arc<dyn CredentialsProvider>,
+    ) -> TargetConfig {
+        self.credentials_provider = Some(credentials_provider);
+        self
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,422 @@
+use arrow::{
+    datatypes::Schema,
+    error::Result as ArrowResult,
+    ipc::{reader::StreamReader, writer::StreamWriter},
+    record_batch::RecordBatch,
+};
+use proboscis_core::resolver::{ClientId, ResolveError, SyncResponse};
+use proboscis_postgres_protocol::message::{
+    CommandCompleteTag, Error, FrontendMessage, ParameterDescription,
+};
+use std::{collections::HashMap, io::Cursor};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+// A recording is a sequence of entries, each consisting of the client id,
+// the call and the outcome. Strings, frontend messages and arrow ipc streams
+// are prefixed with their length.
+
+const CALL_INITIALIZE: u8 = 0;
+const CALL_MESSAGE: u8 = 1;
+
+const OUTCOME_DONE: u8 = 0;
+const OUTCOME_BATCHES: u8 = 1;
+const OUTCOME_RESPONSES: u8 = 2;
+const OUTCOME_FAILED: u8 = 3;
+
+const RESPONSE_SCHEMA: u8 = 0;
+const RESPONSE_RECORDS: u8 = 1;
+const RESPONSE_COMMAND_COMPLETE: u8 = 2;
+const RESPONSE_BIND_COMPLETE: u8 = 3;
+const RESPONSE_PARSE_COMPLETE: u8 = 4;
+const RESPONSE_READY_FOR_QUERY: u8 = 5;
+const RESPONSE_PARAMETER_DESCRIPTION: u8 = 6;
+const RESPONSE_NO_DATA: u8 = 7;
+const RESPONSE_EMPTY_QUERY_RESPONSE: u8 = 8;
+const RESPONSE_PORTAL_SUSPENDED: u8 = 9;
+const RESPONSE_ERROR: u8 = 10;
+
+/// A call of a resolver method, the methods taking a frontend message are recorded as the message,
+/// `query` as a simple query, `sync` and `terminate` as their messages
+#[derive(Debug, Clone, PartialEq)]
+pub enum Call {
+    Initialize(HashMap<String, String>),
+    Message(FrontendMessage),
+}
+
+/// What a resolver method returned
+pub enum Outcome {
+    Done,
+    Batches(Vec<RecordBatch>),
+    Responses(Vec<SyncResponse>),
+    Failed(String),
+}
+
+fn unexpected_outcome() -> ResolveError {
+    "the recorded outcome doesn't match the call".into()
+}
+
+impl Outcome {
+    pub fn into_done(self) -> Result<(), ResolveError> {
+        match self {
+            Outcome::Done => Ok(()),
+            Outcome::Failed(message) => Err(message.as_str().into()),
+            _ => Err(unexpected_outcome()),
+        }
+    }
+
+    pub fn into_batches(self) -> Result<Vec<RecordBatch>, ResolveError> {
+        match self {
+            Outcome::Batches(batches) => Ok(batches),
+            Outcome::Failed(message) => Err(message.as_str().into()),
+            _ => Err(unexpected_outcome()),
+        }
+    }
+
+    pub fn into_responses(self) -> Result<Vec<SyncResponse>, ResolveError> {
+        match self {
+            Outcome::Responses(responses) => Ok(responses),
+            Outcome::Failed(message) => Err(message.as_str().into()),
+            _ => Err(unexpected_outcome()),
+        }
+    }
+}
+
+pub struct Entry {
+    pub client_id: ClientId,
+    pub call: Call,
+    pub outcome: Outcome,
+}
+
+// Results without batches have no schema, they are written as an empty stream
+fn encode_batches(schema: Option<&Schema>, batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+    let mut buffer = vec![];
+
+    if let Some(schema) = schema {
+        let mut writer = StreamWriter::try_new(&mut buffer, schema)?;
+        for batch in batches {
+            writer.write(batch)?;
+        }
+        writer.finish()?;
+    }
+
+    Ok(buffer)
+}
+
+fn decode_batches(bytes: &[u8]) -> ArrowResult<Option<(Schema, Vec<RecordBatch>)>> {
+    if bytes.is_empty() {
+        return Ok(None);
+    }
+
+    let reader = StreamReader::try_new(Cursor::new(bytes))?;
+    let schema = reader.schema().as_ref().clone();
+    let batches = reader.collect::<ArrowResult<Vec<RecordBatch>>>()?;
+
+    Ok(Some((schema, batches)))
+}
+
+fn encode_record_batches(batches: &[RecordBatch]) -> ArrowResult<Vec<u8>> {
+    let schema = batches.first().map(|batch| batch.schema());
+    encode_batches(schema.as_deref(), batches)
+}
+
+async fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) -> Result<(), ResolveError> {
+    buffer.write_u32(bytes.len() as u32).await?;
+    buffer.extend_from_slice(bytes);
+    Ok(())
+}
+
+async fn read_bytes(cursor: &mut Cursor<&[u8]>) -> Result<Vec<u8>, ResolveError> {
+    let length = cursor.read_u32().await?;
+    let mut bytes = vec![0; length as usize];
+    cursor.read_exact(&mut bytes).await?;
+    Ok(bytes)
+}
+
+async fn read_string(cursor: &mut Cursor<&[u8]>) -> Result<String, ResolveError> {
+    String::from_utf8(read_bytes(cursor).await?)
+        .map_err(|_| "the recording contains invalid utf-8".into())
+}
+
+async fn write_response(buffer: &mut Vec<u8>, response: &SyncResponse) -> Result<(), ResolveError> {
+    match response {
+        SyncResponse::Schema { schema, query } => {
+            buffer.push(RESPONSE_SCHEMA);
+            write_bytes(buffer, query.as_bytes()).await?;
+            write_bytes(buffer, &encode_batches(Some(schema), &[])?).await?;
+        }
+        SyncResponse::Records { data, query } => {
+            buffer.push(RESPONSE_RECORDS);
+            write_bytes(buffer, query.as_bytes()).await?;
+            write_bytes(buffer, &encode_record_batches(data)?).await?;
+        }
+        SyncResponse::CommandComplete(CommandCompleteTag(tag)) => {
+            buffer.push(RESPONSE_COMMAND_COMPLETE);
+            write_bytes(buffer, tag.as_bytes()).await?;
+        }
+        SyncResponse::BindComplete => buffer.push(RESPONSE_BIND_COMPLETE),
+        SyncResponse::ParseComplete => buffer.push(RESPONSE_PARSE_COMPLETE),
+        SyncResponse::ReadyForQuery => buffer.push(RESPONSE_READY_FOR_QUERY),
+        SyncResponse::ParameterDescription(ParameterDescription { types }) => {
+            buffer.push(RESPONSE_PARAMETER_DESCRIPTION);
+            buffer.write_u32(types.len() as u32).await?;
+            for oid in types {
+                buffer.write_u32(*oid).await?;
+            }
+        }
+        SyncResponse::NoData => buffer.push(RESPONSE_NO_DATA),
+        SyncResponse::EmptyQueryResponse => buffer.push(RESPONSE_EMPTY_QUERY_RESPONSE),
+        SyncResponse::PortalSuspended => buffer.push(RESPONSE_PORTAL_SUSPENDED),
+        SyncResponse::Error(Error { messages }) => {
+            buffer.push(RESPONSE_ERROR);
+            buffer.write_u32(messages.len() as u32).await?;
+            for (field, value) in messages {
+                buffer.push(*field);
+                write_bytes(buffer, value.as_bytes()).await?;
+            }
+        }
+    }
+
+    Ok(())
+}
+
+async fn read_response(cursor: &mut Cursor<&[u8]>) -> Result<SyncResponse, ResolveError> {
+    Ok(match cursor.read_u8().await? {
+        RESPONSE_SCHEMA => {
+            let query = read_string(cursor).await?;
+            let (schema, _) = decode_batches(&read_bytes(cursor).await?)?
+                .ok_or("the recorded schema is missing")?;
+            SyncResponse::Schema { schema, query }
+        }
+        RESPONSE_RECORDS => {
+            let query = read_string(cursor).await?;
+            let data = decode_batches(&read_bytes(cursor).await?)?
+                .map(|(_, batches)| batches)
+                .unwrap_or_default();
+            SyncResponse::Records { data, query }
+        }
+        RESPONSE_COMMAND_COMPLETE => {
+            SyncResponse::CommandComplete(CommandCompleteTag(read_string(cursor).await?))
+        }
+        RESPONSE_BIND_COMPLETE => SyncResponse::BindComplete,
+        RESPONSE_PARSE_COMPLETE => SyncResponse::ParseComplete,
+        RESPONSE_READY_FOR_QUERY => SyncResponse::ReadyForQuery,
+        RESPONSE_PARAMETER_DESCRIPTION => {
+            let count = cursor.read_u32().await?;
+            let mut types = vec![];
+            for _ in 0..count {
+                types.push(cursor.read_u32().await?);
+            }
+            SyncResponse::ParameterDescription(ParameterDescription { types })
+        }
+        RESPONSE_NO_DATA => SyncResponse::NoData,
+        RESPONSE_EMPTY_QUERY_RESPONSE => SyncResponse::EmptyQueryResponse,
+        RESPONSE_PORTAL_SUSPENDED => SyncResponse::PortalSuspended,
+        RESPONSE_ERROR => {
+            let count = cursor.read_u32().await?;
+            let mut messages = vec![];
+            for _ in 0..count {
+                let field = cursor.read_u8().await?;
+                messages.push((field, read_string(cursor).await?));
+            }
+            SyncResponse::Error(Error { messages })
+        }
+        _ => return Err("the recording contains an unknown sync response".into()),
+    })
+}
+
+pub async fn write_entry(
+    buffer: &mut Vec<u8>,
+    client_id: ClientId,
+    call: &Call,
+    outcome: &Outcome,
+) -> Result<(), ResolveError> {
+    buffer.extend_from_slice(client_id.as_bytes());
+
+    match call {
+        Call::Initialize(parameters) => {
+            buffer.push(CALL_INITIALIZE);
+            buffer.write_u32(parameters.len() as u32).await?;
+            for (key, value) in parameters {
+                write_bytes(buffer, key.as_bytes()).await?;
+                write_bytes(buffer, value.as_bytes()).await?;
+            }
+        }
+        Call::Message(message) => {
+            buffer.push(CALL_MESSAGE);
+            let mut bytes = vec![];
+            message.clone().write(&mut bytes).await?;
+            write_bytes(buffer, &bytes).await?;
+        }
+    }
+
+    match outcome {
+        Outcome::Done => buffer.push(OUTCOME_DONE),
+        Outcome::Batches(batches) => {
+            buffer.push(OUTCOME_BATCHES);
+            write_bytes(buffer, &encode_record_batches(batches)?).await?;
+        }
+        Outcome::Responses(responses) => {
+            buffer.push(OUTCOME_RESPONSES);
+            buffer.write_u32(responses.len() as u32).await?;
+            for response in responses {
+                write_response(buffer, response).await?;
+            }
+        }
+        Outcome::Failed(message) => {
+            buffer.push(OUTCOME_FAILED);
+            write_bytes(buffer, message.as_bytes()).await?;
+        }
+    }
+
+    Ok(())
+}
+
+async fn read_entry(cursor: &mut Cursor<&[u8]>) -> Result<Entry, ResolveError> {
+    let mut client_id = [0; 16];
+    cursor.read_exact(&mut client_id).await?;
+    let client_id = ClientId::from_bytes(client_id);
+
+    let call = match cursor.read_u8().await? {
+        CALL_INITIALIZE => {
+            let count = cursor.read_u32().await?;
+            let mut parameters = HashMap::new();
+            for _ in 0..count {
+                let key = read_string(cursor).await?;
+                parameters.insert(key, read_string(cursor).await?);
+            }
+            Call::Initialize(parameters)
+        }
+        CALL_MESSAGE => {
+            let bytes = read_bytes(cursor).await?;
+            Call::Message(FrontendMessage::read(&mut Cursor::new(&bytes[..])).await?)
+        }
+        _ => return Err("the recording contains an unknown call".into()),
+    };
+
+    let outcome = match cursor.read_u8().await? {
+        OUTCOME_DONE => Outcome::Done,
+        OUTCOME_BATCHES => Outcome::Batches(
+            decode_batches(&read_bytes(cursor).await?)?
+                .map(|(_, batches)| batches)
+                .unwrap_or_default(),
+        ),
+        OUTCOME_RESPONSES => {
+            let count = cursor.read_u32().await?;
+            let mut responses = vec![];
+            for _ in 0..count {
+                responses.push(read_response(cursor).await?);
+            }
+            Outcome::Responses(responses)
+        }
+        OUTCOME_FAILED => Outcome::Failed(read_string(cursor).await?),
+        _ => return Err("the recording contains an unknown outcome".into()),
+    };
+
+    Ok(Entry {
+        client_id,
+        call,
+        outcome,
+    })
+}
+
+pub async fn read_entries(bytes: &[u8]) -> Result<Vec<Entry>, ResolveError> {
+    let mut cursor = Cursor::new(bytes);
+    let mut entries = vec![];
+
+    while (cursor.position() as usize) < bytes.len() {
+        entries.push(read_entry(&mut cursor).await?);
+    }
+
+    Ok(entries)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field},
+    };
+    use proboscis_postgres_protocol::message::Parse;
+    use std::sync::Arc;
+
+    fn batch() -> RecordBatch {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(Int32Array::from(vec![1, 2, 3]))],
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_entries_roundtrip() {
+        let client_id = ClientId::new_v4();
+        let parse = Call::Message(FrontendMessage::Parse(Parse {
+            statement_name: "".to_string(),
+            query: "SELECT id FROM contacts WHERE id = $1".to_string(),
+            param_types: vec![23],
+        }));
+        let query = Call::Message(FrontendMessage::SimpleQuery(
+            "SELECT id FROM contacts".to_string(),
+        ));
+        let sync = Call::Message(FrontendMessage::Sync);
+
+        let mut buffer = vec![];
+        write_entry(&mut buffer, client_id, &parse, &Outcome::Done)
+            .await
+            .unwrap();
+        write_entry(
+            &mut buffer,
+            client_id,
+            &query,
+            &Outcome::Batches(vec![batch()]),
+        )
+        .await
+        .unwrap();
+        write_entry(
+            &mut buffer,
+            client_id,
+            &sync,
+            &Outcome::Responses(vec![
+                SyncResponse::ParseComplete,
+                SyncResponse::Schema {
+                    schema: batch().schema().as_ref().clone(),
+                    query: "SELECT id FROM contacts".to_string(),
+                },
+                SyncResponse::CommandComplete(CommandCompleteTag("SELECT 3".to_string())),
+                SyncResponse::Error(Error {
+                    messages: vec![(b'C', "23505".to_string()), (b'M', "duplicate".to_string())],
+                }),
+            ]),
+        )
+        .await
+        .unwrap();
+
+        let entries = read_entries(&buffer).await.unwrap();
+        assert_eq!(3, entries.len());
+        assert!(entries.iter().all(|entry| entry.client_id == client_id));
+        assert_eq!(parse, entries[0].call);
+        assert_eq!(query, entries[1].call);
+        assert_eq!(sync, entries[2].call);
+
+        match &entries[1].outcome {
+            Outcome::Batches(batches) => assert_eq!(vec![batch()], *batches),
+            _ => panic!("expected batches"),
+        }
+
+        match &entries[2].outcome {
+            Outcome::Responses(responses) => {
+                assert_eq!(4, responses.len());
+                assert!(matches!(responses[0], SyncResponse::ParseComplete));
+                assert!(
+                    matches!(&responses[2], SyncResponse::CommandComplete(CommandCompleteTag(tag)) if tag == "SELECT 3")
+                );
+                assert!(
+                    matches!(&responses[3], SyncResponse::Error(error) if error.code() == Some("23505"))
+                );
+            }
+            _ => panic!("expected sync responses"),
+        }
+    }
+}
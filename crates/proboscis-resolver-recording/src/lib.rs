@@ -0,0 +1,6 @@
+mod format;
+mod recorder;
+mod replay;
+
+pub use recorder::RecordingResolver;
+pub use replay::ReplayResolver;
@@ -0,0 +1,220 @@
+use crate::format::{write_entry, Call, Outcome};
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use proboscis_core::resolver::{
+    Bind, ClientContext, ClientId, Close, CommandCompleteTag, CopyOutStream, CopyResponse,
+    Describe, Execute, Parse, ResolveError, Resolver, SyncResponse,
+};
+use proboscis_postgres_protocol::message::{
+    Error, FrontendMessage, NotificationResponse, ParameterStatus,
+};
+use std::{
+    fs::{File, OpenOptions},
+    io::Write,
+    path::Path,
+};
+
+/// Forwards every call to the inner resolver and appends the call with its outcome
+/// to a recording, which a `ReplayResolver` can answer the same calls from later
+pub struct RecordingResolver {
+    resolver: Box<dyn Resolver>,
+    file: File,
+}
+
+impl RecordingResolver {
+    /// Appends to the recording at the path, it is created if it doesn't exist
+    pub fn new(
+        resolver: Box<dyn Resolver>,
+        path: &Path,
+    ) -> Result<RecordingResolver, ResolveError> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+
+        Ok(RecordingResolver { resolver, file })
+    }
+
+    // Errors are recorded as their message, the caller still receives the original error
+    async fn record<T>(
+        &mut self,
+        client_id: ClientId,
+        call: Call,
+        result: Result<T, ResolveError>,
+        into_outcome: fn(T) -> Outcome,
+        from_outcome: fn(Outcome) -> Result<T, ResolveError>,
+    ) -> Result<T, ResolveError> {
+        let (outcome, err) = match result {
+            Ok(value) => (into_outcome(value), None),
+            Err(err) => (Outcome::Failed(err.to_string()), Some(err)),
+        };
+
+        // Entries are written at once, so a crash can't leave a partial one behind
+        let mut entry = vec![];
+        write_entry(&mut entry, client_id, &call, &outcome).await?;
+        self.file.write_all(&entry)?;
+
+        match err {
+            Some(err) => Err(err),
+            None => from_outcome(outcome),
+        }
+    }
+}
+
+fn done(_: ()) -> Outcome {
+    Outcome::Done
+}
+
+#[async_trait]
+impl Resolver for RecordingResolver {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        let result = self.resolver.initialize(client_id, context).await;
+        let call = Call::Initialize(context.parameters.clone());
+        self.record(client_id, call, result, done, Outcome::into_done)
+            .await
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let call = Call::Message(FrontendMessage::SimpleQuery(query.clone()));
+        let result = self.resolver.query(client_id, query).await;
+        self.record(
+            client_id,
+            call,
+            result,
+            Outcome::Batches,
+            Outcome::into_batches,
+        )
+        .await
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        let call = Call::Message(FrontendMessage::Parse(parse.clone()));
+        let result = self.resolver.parse(client_id, parse).await;
+        self.record(client_id, call, result, done, Outcome::into_done)
+            .await
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        let call = Call::Message(FrontendMessage::Describe(describe.clone()));
+        let result = self.resolver.describe(client_id, describe).await;
+        self.record(client_id, call, result, done, Outcome::into_done)
+            .await
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        let call = Call::Message(FrontendMessage::Bind(bind.clone()));
+        let result = self.resolver.bind(client_id, bind).await;
+        self.record(client_id, call, result, done, Outcome::into_done)
+            .await
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        let call = Call::Message(FrontendMessage::Execute(execute.clone()));
+        let result = self.resolver.execute(client_id, execute).await;
+        self.record(client_id, call, result, done, Outcome::into_done)
+            .await
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let result = self.resolver.sync(client_id).await;
+        let call = Call::Message(FrontendMessage::Sync);
+        self.record(
+            client_id,
+            call,
+            result,
+            Outcome::Responses,
+            Outcome::into_responses,
+        )
+        .await
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let result = self.resolver.flush(client_id).await;
+        let call = Call::Message(FrontendMessage::Flush);
+        self.record(
+            client_id,
+            call,
+            result,
+            Outcome::Responses,
+            Outcome::into_responses,
+        )
+        .await
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        let call = Call::Message(FrontendMessage::Close(close.clone()));
+        let result = self.resolver.close(client_id, close).await;
+        self.record(client_id, call, result, done, Outcome::into_done)
+            .await
+    }
+
+    // Copies aren't recorded, replays reject them as unsupported
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        self.resolver.copy_in(client_id, query).await
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        self.resolver.copy_data(client_id, data).await
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        self.resolver.copy_done(client_id).await
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        self.resolver.copy_fail(client_id, message).await
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        self.resolver.copy_out(client_id, query).await
+    }
+
+    // Notices aren't recorded, replays answer without them
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        self.resolver.take_notices(client_id)
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.resolver.take_parameter_changes(client_id)
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.resolver.take_notifications(client_id)
+    }
+
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.wait_for_idle_message(client_id).await
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.read_idle_messages(client_id).await
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        let result = self.resolver.terminate(client_id).await;
+        let call = Call::Message(FrontendMessage::Terminate);
+        self.record(client_id, call, result, done, Outcome::into_done)
+            .await
+    }
+}
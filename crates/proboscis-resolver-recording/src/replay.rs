@@ -0,0 +1,277 @@
+use crate::format::{read_entries, Call, Outcome};
+use anyhow::anyhow;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use proboscis_core::resolver::{
+    Bind, ClientContext, ClientId, Close, Describe, Execute, Parse, ResolveError, Resolver,
+    SyncResponse,
+};
+use proboscis_postgres_protocol::message::FrontendMessage;
+use std::{
+    collections::{HashMap, VecDeque},
+    path::Path,
+};
+
+type Exchange = VecDeque<(Call, Outcome)>;
+
+/// Answers calls with the outcomes of a recording of a `RecordingResolver`, without an upstream.
+/// Clients are matched to the recorded clients in the order they first appeared,
+/// every call has to match the next recorded call of its client.
+pub struct ReplayResolver {
+    recorded: VecDeque<Exchange>,
+    clients: HashMap<ClientId, Exchange>,
+}
+
+impl ReplayResolver {
+    pub async fn open(path: &Path) -> Result<ReplayResolver, ResolveError> {
+        let bytes = std::fs::read(path)?;
+
+        let mut order = vec![];
+        let mut exchanges: HashMap<ClientId, Exchange> = HashMap::new();
+        for entry in read_entries(&bytes).await? {
+            if !exchanges.contains_key(&entry.client_id) {
+                order.push(entry.client_id);
+            }
+
+            exchanges
+                .entry(entry.client_id)
+                .or_default()
+                .push_back((entry.call, entry.outcome));
+        }
+
+        let recorded = order
+            .iter()
+            .filter_map(|client_id| exchanges.remove(client_id))
+            .collect();
+
+        Ok(ReplayResolver {
+            recorded,
+            clients: HashMap::new(),
+        })
+    }
+
+    fn replay(&mut self, client_id: ClientId, call: Call) -> Result<Outcome, ResolveError> {
+        if !self.clients.contains_key(&client_id) {
+            let exchange = self
+                .recorded
+                .pop_front()
+                .ok_or("the recording has no further clients")?;
+            self.clients.insert(client_id, exchange);
+        }
+
+        let exchange = self
+            .clients
+            .get_mut(&client_id)
+            .expect("Client was inserted above");
+
+        match exchange.pop_front() {
+            Some((expected, outcome)) if expected == call => Ok(outcome),
+            Some((expected, _)) => Err(anyhow!(
+                "the call {:?} differs from the recorded call {:?}",
+                call,
+                expected
+            )
+            .into()),
+            None => Err(anyhow!("the recording has no further calls for {:?}", call).into()),
+        }
+    }
+}
+
+#[async_trait]
+impl Resolver for ReplayResolver {
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        self.replay(client_id, Call::Initialize(context.parameters.clone()))?
+            .into_done()
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        self.replay(
+            client_id,
+            Call::Message(FrontendMessage::SimpleQuery(query)),
+        )?
+        .into_batches()
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        self.replay(client_id, Call::Message(FrontendMessage::Parse(parse)))?
+            .into_done()
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        self.replay(
+            client_id,
+            Call::Message(FrontendMessage::Describe(describe)),
+        )?
+        .into_done()
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        self.replay(client_id, Call::Message(FrontendMessage::Bind(bind)))?
+            .into_done()
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        self.replay(client_id, Call::Message(FrontendMessage::Execute(execute)))?
+            .into_done()
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        self.replay(client_id, Call::Message(FrontendMessage::Sync))?
+            .into_responses()
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        self.replay(client_id, Call::Message(FrontendMessage::Flush))?
+            .into_responses()
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        self.replay(client_id, Call::Message(FrontendMessage::Close(close)))?
+            .into_done()
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        let outcome = self.replay(client_id, Call::Message(FrontendMessage::Terminate));
+        self.clients.remove(&client_id);
+        outcome?.into_done()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RecordingResolver;
+    use arrow::{
+        array::StringArray,
+        datatypes::{DataType, Field, Schema},
+    };
+    use std::sync::Arc;
+
+    struct StaticResolver;
+
+    fn batch() -> RecordBatch {
+        let schema = Schema::new(vec![Field::new("name", DataType::Utf8, false)]);
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![Arc::new(StringArray::from(vec!["Max", "Erika"]))],
+        )
+        .unwrap()
+    }
+
+    #[async_trait]
+    impl Resolver for StaticResolver {
+        async fn initialize(
+            &mut self,
+            _client_id: ClientId,
+            _context: &ClientContext,
+        ) -> Result<(), ResolveError> {
+            Ok(())
+        }
+
+        async fn query(
+            &mut self,
+            _client_id: ClientId,
+            query: String,
+        ) -> Result<Vec<RecordBatch>, ResolveError> {
+            match query.as_str() {
+                "SELECT name FROM contacts" => Ok(vec![batch()]),
+                _ => Err("relation doesn't exist".into()),
+            }
+        }
+
+        async fn parse(&mut self, _client_id: ClientId, _parse: Parse) -> Result<(), ResolveError> {
+            unimplemented!()
+        }
+
+        async fn describe(
+            &mut self,
+            _client_id: ClientId,
+            _describe: Describe,
+        ) -> Result<(), ResolveError> {
+            unimplemented!()
+        }
+
+        async fn bind(&mut self, _client_id: ClientId, _bind: Bind) -> Result<(), ResolveError> {
+            unimplemented!()
+        }
+
+        async fn execute(
+            &mut self,
+            _client_id: ClientId,
+            _execute: Execute,
+        ) -> Result<(), ResolveError> {
+            unimplemented!()
+        }
+
+        async fn sync(&mut self, _client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+            unimplemented!()
+        }
+
+        async fn close(&mut self, _client_id: ClientId, _close: Close) -> Result<(), ResolveError> {
+            unimplemented!()
+        }
+
+        async fn terminate(&mut self, _client_id: ClientId) -> Result<(), ResolveError> {
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_replays_recorded_session() {
+        let path = std::env::temp_dir().join(format!("proboscis-recording-{}", ClientId::new_v4()));
+        let context = ClientContext::default();
+
+        let mut recorder = RecordingResolver::new(Box::new(StaticResolver), &path).unwrap();
+        let client_id = ClientId::new_v4();
+        recorder.initialize(client_id, &context).await.unwrap();
+        recorder
+            .query(client_id, "SELECT name FROM contacts".to_string())
+            .await
+            .unwrap();
+        assert!(recorder
+            .query(client_id, "SELECT name FROM missing".to_string())
+            .await
+            .is_err());
+        recorder.terminate(client_id).await.unwrap();
+        drop(recorder);
+
+        let mut replay = ReplayResolver::open(&path).await.unwrap();
+        let client_id = ClientId::new_v4();
+        replay.initialize(client_id, &context).await.unwrap();
+        assert_eq!(
+            vec![batch()],
+            replay
+                .query(client_id, "SELECT name FROM contacts".to_string())
+                .await
+                .unwrap()
+        );
+        let err = replay
+            .query(client_id, "SELECT name FROM missing".to_string())
+            .await
+            .unwrap_err();
+        assert_eq!("relation doesn't exist", err.to_string());
+        assert!(replay.terminate(client_id).await.is_ok());
+
+        let mut diverging = ReplayResolver::open(&path).await.unwrap();
+        let client_id = ClientId::new_v4();
+        diverging.initialize(client_id, &context).await.unwrap();
+        assert!(diverging
+            .query(client_id, "SELECT 1".to_string())
+            .await
+            .is_err());
+
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -0,0 +1,5 @@
+mod resolver;
+mod summary;
+
+pub use resolver::{Divergence, ShadowMetrics, ShadowResolver};
+pub use summary::ResultSummary;
@@ -0,0 +1,483 @@
+use crate::summary::ResultSummary;
+use arrow::record_batch::RecordBatch;
+use async_trait::async_trait;
+use futures::stream::{self, StreamExt};
+use proboscis_core::{
+    resolver::{
+        Bind, ClientContext, ClientId, Close, CommandCompleteTag, CopyOutStream, CopyResponse,
+        Describe, Execute, Parse, RecordBatchStream, ResolveError, Resolver, SyncResponse,
+    },
+    utils::{connection::Connection, fingerprint::fingerprint, sql::is_read_only},
+};
+use proboscis_postgres_protocol::message::{Error, NotificationResponse, ParameterStatus};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc, Mutex,
+    },
+};
+use tokio::sync::mpsc;
+use tracing::warn;
+
+// Queries waiting for the shadow target, further ones are skipped until it catches up
+const QUEUE_SIZE: usize = 1024;
+
+// Divergences kept for reporting, older ones are dropped
+const RECENT_DIVERGENCES: usize = 100;
+
+/// A query whose result on the shadow target differs from the one on the primary target
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub query: String,
+    pub primary: ResultSummary,
+    pub shadow: ResultSummary,
+}
+
+/// Counters of the mirrored queries, shared with whatever reports them
+#[derive(Debug, Default)]
+pub struct ShadowMetrics {
+    /// Queries whose results matched on both targets
+    pub matched: AtomicU64,
+    /// Queries whose results differ in their rows
+    pub diverged: AtomicU64,
+    /// Queries which failed on the shadow target only
+    pub failed: AtomicU64,
+    /// Queries which weren't mirrored, as the shadow target fell too far behind
+    pub skipped: AtomicU64,
+    recent_divergences: Mutex<VecDeque<Divergence>>,
+}
+
+impl ShadowMetrics {
+    pub fn matched(&self) -> u64 {
+        self.matched.load(Ordering::Relaxed)
+    }
+
+    pub fn diverged(&self) -> u64 {
+        self.diverged.load(Ordering::Relaxed)
+    }
+
+    pub fn failed(&self) -> u64 {
+        self.failed.load(Ordering::Relaxed)
+    }
+
+    pub fn skipped(&self) -> u64 {
+        self.skipped.load(Ordering::Relaxed)
+    }
+
+    /// The last divergences, oldest first
+    pub fn recent_divergences(&self) -> Vec<Divergence> {
+        let divergences = self
+            .recent_divergences
+            .lock()
+            .expect("Divergences lock poisoned");
+
+        divergences.iter().cloned().collect()
+    }
+
+    fn record_divergence(&self, divergence: Divergence) {
+        self.diverged.fetch_add(1, Ordering::Relaxed);
+
+        let mut divergences = self
+            .recent_divergences
+            .lock()
+            .expect("Divergences lock poisoned");
+
+        if divergences.len() == RECENT_DIVERGENCES {
+            divergences.pop_front();
+        }
+        divergences.push_back(divergence);
+    }
+}
+
+enum Job {
+    Initialize(ClientId, ClientContext),
+    Query {
+        client_id: ClientId,
+        query: String,
+        primary: Vec<RecordBatch>,
+    },
+    Terminate(ClientId),
+}
+
+/// Answers every request with the primary resolver and runs the read-only simple queries on a
+/// shadow resolver as well, e.g. a new postgres version or a snapshot, to validate a migration
+/// with real traffic. The shadow runs in the background, clients never wait for it. Its results
+/// are compared to those of the primary by their row counts and checksums, divergences are logged
+/// and counted in the metrics.
+///
+/// Queries of the extended protocol aren't mirrored. Neither are statements changing the state of
+/// a session, so the results of clients relying on e.g. `SET search_path` might diverge.
+pub struct ShadowResolver {
+    primary: Box<dyn Resolver>,
+    jobs: mpsc::Sender<Job>,
+    metrics: Arc<ShadowMetrics>,
+}
+
+impl ShadowResolver {
+    /// Spawns the task running the mirrored queries on the shadow resolver,
+    /// which ends once the `ShadowResolver` is dropped
+    pub fn new(primary: Box<dyn Resolver>, shadow: Box<dyn Resolver>) -> ShadowResolver {
+        let (jobs, receiver) = mpsc::channel(QUEUE_SIZE);
+        let metrics = Arc::new(ShadowMetrics::default());
+
+        tokio::spawn(run_shadow(shadow, receiver, metrics.clone()));
+
+        ShadowResolver {
+            primary,
+            jobs,
+            metrics,
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<ShadowMetrics> {
+        self.metrics.clone()
+    }
+}
+
+fn mirror(jobs: &mpsc::Sender<Job>, metrics: &ShadowMetrics, job: Job) {
+    if jobs.try_send(job).is_err() {
+        metrics.skipped.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+async fn run_shadow(
+    mut shadow: Box<dyn Resolver>,
+    mut jobs: mpsc::Receiver<Job>,
+    metrics: Arc<ShadowMetrics>,
+) {
+    while let Some(job) = jobs.recv().await {
+        match job {
+            Job::Initialize(client_id, context) => {
+                if let Err(err) = shadow.initialize(client_id, &context).await {
+                    warn!("the shadow target couldn't initialize a client: {}", err);
+                }
+            }
+            Job::Query {
+                client_id,
+                query,
+                primary,
+            } => compare(&mut shadow, &metrics, client_id, query, primary).await,
+            Job::Terminate(client_id) => {
+                if let Err(err) = shadow.terminate(client_id).await {
+                    warn!("the shadow target couldn't terminate a client: {}", err);
+                }
+            }
+        }
+    }
+}
+
+async fn compare(
+    shadow: &mut Box<dyn Resolver>,
+    metrics: &ShadowMetrics,
+    client_id: ClientId,
+    query: String,
+    primary: Vec<RecordBatch>,
+) {
+    let shadow_result = match shadow.query(client_id, query.clone()).await {
+        Ok(data) => ResultSummary::of(&data),
+        Err(err) => {
+            metrics.failed.fetch_add(1, Ordering::Relaxed);
+            warn!(fingerprint = %fingerprint(&query), "the query failed on the shadow target: {}", err);
+            return;
+        }
+    };
+
+    let (primary, shadow) = match (ResultSummary::of(&primary), shadow_result) {
+        (Ok(primary), Ok(shadow)) => (primary, shadow),
+        (Err(err), _) | (_, Err(err)) => {
+            metrics.failed.fetch_add(1, Ordering::Relaxed);
+            warn!(fingerprint = %fingerprint(&query), "couldn't summarize a mirrored result: {}", err);
+            return;
+        }
+    };
+
+    if primary == shadow {
+        metrics.matched.fetch_add(1, Ordering::Relaxed);
+        return;
+    }
+
+    warn!(
+        fingerprint = %fingerprint(&query),
+        primary_rows = primary.rows,
+        shadow_rows = shadow.rows,
+        "the result of the shadow target diverged"
+    );
+    metrics.record_divergence(Divergence {
+        query,
+        primary,
+        shadow,
+    });
+}
+
+#[async_trait]
+impl Resolver for ShadowResolver {
+    async fn authenticate(
+        &mut self,
+        client_id: ClientId,
+        frontend: &mut Connection,
+    ) -> Result<(), ResolveError> {
+        self.primary.authenticate(client_id, frontend).await
+    }
+
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        self.primary.initialize(client_id, context).await?;
+
+        // Queries of the client can't be mirrored without it
+        let _ = self
+            .jobs
+            .send(Job::Initialize(client_id, context.clone()))
+            .await;
+
+        Ok(())
+    }
+
+    async fn query(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        if !is_read_only(&query) {
+            return self.primary.query(client_id, query).await;
+        }
+
+        let data = self.primary.query(client_id, query.clone()).await?;
+
+        let job = Job::Query {
+            client_id,
+            query,
+            primary: data.clone(),
+        };
+        mirror(&self.jobs, &self.metrics, job);
+
+        Ok(data)
+    }
+
+    async fn query_stream<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<RecordBatchStream<'a>, ResolveError> {
+        if !is_read_only(&query) {
+            return self.primary.query_stream(client_id, query).await;
+        }
+
+        let jobs = self.jobs.clone();
+        let metrics = self.metrics.clone();
+        let batches = self.primary.query_stream(client_id, query.clone()).await?;
+
+        // The batches are collected while they are streamed, failed or abandoned results
+        // aren't mirrored
+        let mirrored = stream::unfold(
+            (batches, Some((query, vec![]))),
+            move |(mut batches, mut mirrored)| {
+                let jobs = jobs.clone();
+                let metrics = metrics.clone();
+
+                async move {
+                    match batches.next().await {
+                        Some(Ok(batch)) => {
+                            if let Some((_, primary)) = &mut mirrored {
+                                primary.push(batch.clone());
+                            }
+                            Some((Ok(batch), (batches, mirrored)))
+                        }
+                        Some(Err(err)) => Some((Err(err), (batches, None))),
+                        None => {
+                            if let Some((query, primary)) = mirrored {
+                                let job = Job::Query {
+                                    client_id,
+                                    query,
+                                    primary,
+                                };
+                                mirror(&jobs, &metrics, job);
+                            }
+                            None
+                        }
+                    }
+                }
+            },
+        );
+
+        Ok(mirrored.boxed())
+    }
+
+    async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
+        self.primary.parse(client_id, parse).await
+    }
+
+    async fn describe(
+        &mut self,
+        client_id: ClientId,
+        describe: Describe,
+    ) -> Result<(), ResolveError> {
+        self.primary.describe(client_id, describe).await
+    }
+
+    async fn bind(&mut self, client_id: ClientId, bind: Bind) -> Result<(), ResolveError> {
+        self.primary.bind(client_id, bind).await
+    }
+
+    async fn execute(&mut self, client_id: ClientId, execute: Execute) -> Result<(), ResolveError> {
+        self.primary.execute(client_id, execute).await
+    }
+
+    async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        self.primary.sync(client_id).await
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        self.primary.flush(client_id).await
+    }
+
+    async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        self.primary.close(client_id, close).await
+    }
+
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        self.primary.copy_in(client_id, query).await
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        self.primary.copy_data(client_id, data).await
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        self.primary.copy_done(client_id).await
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        self.primary.copy_fail(client_id, message).await
+    }
+
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        self.primary.copy_out(client_id, query).await
+    }
+
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        self.primary.take_notices(client_id)
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.primary.take_parameter_changes(client_id)
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.primary.take_notifications(client_id)
+    }
+
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.primary.wait_for_idle_message(client_id).await
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.primary.read_idle_messages(client_id).await
+    }
+
+    async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        let result = self.primary.terminate(client_id).await;
+        let _ = self.jobs.send(Job::Terminate(client_id)).await;
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::Int32Array,
+        datatypes::{DataType, Field, Schema},
+    };
+    use proboscis_resolver_mock::{MockResolver, MockResponse};
+    use std::time::Duration;
+
+    fn batch(ids: Vec<i32>) -> RecordBatch {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        RecordBatch::try_new(Arc::new(schema), vec![Arc::new(Int32Array::from(ids))]).unwrap()
+    }
+
+    // The shadow compares in the background
+    async fn compared(metrics: &ShadowMetrics, count: u64) {
+        for _ in 0..100 {
+            if metrics.matched() + metrics.diverged() + metrics.failed() >= count {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        panic!("the shadow didn't compare {} results", count);
+    }
+
+    #[tokio::test]
+    async fn test_reports_divergences() {
+        let primary = MockResolver::new()
+            .on_query(
+                "SELECT id FROM contacts",
+                MockResponse::rows(vec![batch(vec![1, 2])]),
+            )
+            .on_query(
+                "SELECT id FROM orders",
+                MockResponse::rows(vec![batch(vec![3])]),
+            )
+            .with_fallback(MockResponse::command_complete("UPDATE 1"));
+        let shadow = MockResolver::new()
+            .on_query(
+                "SELECT id FROM contacts",
+                MockResponse::rows(vec![batch(vec![2, 1])]),
+            )
+            .on_query(
+                "SELECT id FROM orders",
+                MockResponse::rows(vec![batch(vec![3, 4])]),
+            );
+        let shadow_history = shadow.history();
+
+        let mut resolver = ShadowResolver::new(Box::new(primary), Box::new(shadow));
+        let metrics = resolver.metrics();
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        for query in &[
+            "SELECT id FROM contacts",
+            "UPDATE contacts SET id = 3",
+            "SELECT id FROM orders",
+        ] {
+            resolver.query(client_id, query.to_string()).await.unwrap();
+        }
+
+        compared(&metrics, 2).await;
+        assert_eq!(1, metrics.matched());
+        assert_eq!(1, metrics.diverged());
+
+        let divergences = metrics.recent_divergences();
+        assert_eq!("SELECT id FROM orders", divergences[0].query);
+        assert_eq!(1, divergences[0].primary.rows);
+        assert_eq!(2, divergences[0].shadow.rows);
+
+        // Writes only reach the primary
+        assert!(!shadow_history
+            .lock()
+            .unwrap()
+            .iter()
+            .any(|query| query.starts_with("UPDATE")));
+    }
+}
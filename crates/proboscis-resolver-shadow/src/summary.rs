@@ -0,0 +1,107 @@
+use arrow::{array::Array, error::ArrowError, record_batch::RecordBatch, util::display};
+use std::{
+    collections::hash_map::DefaultHasher,
+    hash::{Hash, Hasher},
+};
+
+/// The rows of a result condensed to compare it with the result of another target
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct ResultSummary {
+    pub rows: u64,
+    /// Sum of the hashes of the rows, so results with the same rows in another order match.
+    /// Values are hashed by their text, so e.g. an `int4` matches the same `int8`.
+    pub checksum: u64,
+}
+
+impl ResultSummary {
+    pub fn of(batches: &[RecordBatch]) -> Result<ResultSummary, ArrowError> {
+        let mut summary = ResultSummary::default();
+
+        for batch in batches {
+            for row in 0..batch.num_rows() {
+                let mut hasher = DefaultHasher::new();
+                for column in batch.columns() {
+                    match column.is_null(row) {
+                        true => None,
+                        false => Some(display::array_value_to_string(column, row)?),
+                    }
+                    .hash(&mut hasher);
+                }
+
+                summary.rows += 1;
+                summary.checksum = summary.checksum.wrapping_add(hasher.finish());
+            }
+        }
+
+        Ok(summary)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Int32Array, Int64Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+    };
+    use std::sync::Arc;
+
+    fn batch(ids: Vec<i32>, names: Vec<Option<&str>>) -> RecordBatch {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+
+        RecordBatch::try_new(
+            Arc::new(schema),
+            vec![
+                Arc::new(Int32Array::from(ids)),
+                Arc::new(StringArray::from(names)),
+            ],
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_summary_ignores_order_and_batches() {
+        let summary = ResultSummary::of(&[batch(
+            vec![1, 2, 3],
+            vec![Some("alice"), None, Some("carol")],
+        )])
+        .unwrap();
+
+        let reordered = ResultSummary::of(&[
+            batch(vec![3], vec![Some("carol")]),
+            batch(vec![1, 2], vec![Some("alice"), None]),
+        ])
+        .unwrap();
+        assert_eq!(summary, reordered);
+        assert_eq!(3, summary.rows);
+
+        let changed = ResultSummary::of(&[batch(
+            vec![1, 2, 3],
+            vec![Some("alice"), Some(""), Some("carol")],
+        )])
+        .unwrap();
+        assert_eq!(3, changed.rows);
+        assert_ne!(summary.checksum, changed.checksum);
+    }
+
+    #[test]
+    fn test_summary_compares_values_by_text() {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+        let widened =
+            RecordBatch::try_new(Arc::new(schema), vec![Arc::new(Int64Array::from(vec![7]))])
+                .unwrap();
+
+        let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        let original =
+            RecordBatch::try_new(Arc::new(schema), vec![Arc::new(Int32Array::from(vec![7]))])
+                .unwrap();
+
+        assert_eq!(
+            ResultSummary::of(&[original]).unwrap(),
+            ResultSummary::of(&[widened]).unwrap()
+        );
+    }
+}
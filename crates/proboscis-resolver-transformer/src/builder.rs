@@ -0,0 +1,20 @@
+use crate::{interface::Transformer, resolver::TransformingResolver};
+use proboscis_core::ProxyBuilder;
+
+/// Transforms the results of the default resolver of a proxy
+pub trait ProxyBuilderExt {
+    /// Wraps the resolver stack built so far in a single transforming resolver,
+    /// which applies the transformers in order
+    fn transformers(self, transformers: Vec<Box<dyn Transformer>>) -> ProxyBuilder;
+}
+
+impl ProxyBuilderExt for ProxyBuilder {
+    fn transformers(self, transformers: Vec<Box<dyn Transformer>>) -> ProxyBuilder {
+        self.wrap_resolver(move |resolver| {
+            Box::new(transformers.into_iter().fold(
+                TransformingResolver::new(resolver),
+                |resolver, transformer| resolver.add_transformer(transformer),
+            ))
+        })
+    }
+}
@@ -0,0 +1,122 @@
+/// What a statement might return, judged by its first keyword without parsing it
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StatementKind {
+    /// Statements which might return rows of tables, like queries, DML with `RETURNING`,
+    /// cursors and everything that isn't known
+    Data,
+    /// Statements which don't return rows of tables, like DDL, `SET`, `SHOW` or `BEGIN`
+    Utility,
+}
+
+// First keywords of statements never returning rows of tables
+const UTILITY_KEYWORDS: &[&str] = &[
+    "ABORT",
+    "ALTER",
+    "ANALYZE",
+    "BEGIN",
+    "CHECKPOINT",
+    "CLUSTER",
+    "COMMENT",
+    "COMMIT",
+    "CREATE",
+    "DEALLOCATE",
+    "DISCARD",
+    "DROP",
+    "END",
+    "EXPLAIN",
+    "GRANT",
+    "LISTEN",
+    "LOAD",
+    "LOCK",
+    "NOTIFY",
+    "REASSIGN",
+    "REFRESH",
+    "REINDEX",
+    "RELEASE",
+    "RESET",
+    "REVOKE",
+    "ROLLBACK",
+    "SAVEPOINT",
+    "SECURITY",
+    "SET",
+    "SHOW",
+    "START",
+    "TRUNCATE",
+    "UNLISTEN",
+    "VACUUM",
+];
+
+/// The statement without leading whitespace, comments and parentheses
+fn skip_prefix(mut statement: &str) -> &str {
+    loop {
+        let trimmed = statement.trim_start_matches(|c: char| c.is_whitespace() || c == '(');
+
+        statement = if let Some(comment) = trimmed.strip_prefix("--") {
+            comment.find('\n').map_or("", |end| &comment[end..])
+        } else if let Some(comment) = trimmed.strip_prefix("/*") {
+            // Nested block comments end the skipping, the statement is classified as data
+            match comment.find("*/") {
+                Some(end) if !comment[..end].contains("/*") => &comment[end + 2..],
+                _ => return "",
+            }
+        } else {
+            return trimmed;
+        };
+    }
+}
+
+/// Classifies the statement by its first keyword. Only single statements can be utility
+/// statements, every statement of a batch like `SET x = 1; SELECT ...` would have to be.
+pub fn classify_statement(statement: &str) -> StatementKind {
+    let statement = skip_prefix(statement);
+    let statement = statement.trim_end().trim_end_matches(';');
+
+    // Semicolons within literals or comments make the statement count as a batch as well
+    if statement.contains(';') {
+        return StatementKind::Data;
+    }
+
+    let keyword_length = statement
+        .find(|c: char| !c.is_ascii_alphabetic())
+        .unwrap_or_else(|| statement.len());
+    let keyword = statement[..keyword_length].to_ascii_uppercase();
+
+    match UTILITY_KEYWORDS.contains(&keyword.as_str()) {
+        true => StatementKind::Utility,
+        false => StatementKind::Data,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_classify_statement() {
+        for statement in &[
+            "SET search_path TO public",
+            "show server_version;",
+            "BEGIN",
+            "commit;",
+            "  -- create the table\nCREATE TABLE contacts (id int)",
+            "/* migration */ ALTER TABLE contacts ADD COLUMN ssn text",
+            "START TRANSACTION ISOLATION LEVEL SERIALIZABLE",
+        ] {
+            assert_eq!(StatementKind::Utility, classify_statement(statement));
+        }
+
+        for statement in &[
+            "SELECT name FROM contacts",
+            "(SELECT 1) UNION (SELECT 2)",
+            "WITH c AS (SELECT * FROM contacts) SELECT * FROM c",
+            "DELETE FROM contacts RETURNING ssn",
+            "FETCH 10 FROM c",
+            "SET search_path TO public; SELECT ssn FROM contacts",
+            "/* /* nested */ SET */ SELECT 1",
+            "SETTLE",
+            "",
+        ] {
+            assert_eq!(StatementKind::Data, classify_statement(statement));
+        }
+    }
+}
@@ -0,0 +1,241 @@
+use proboscis_core::data::copy::CopyOptions;
+use sqlparser::{
+    dialect::PostgreSqlDialect,
+    tokenizer::{Token, Tokenizer},
+};
+use std::iter::Peekable;
+
+/// A `COPY ... TO STDOUT`, which copies the rows of a query
+#[derive(Debug, PartialEq)]
+pub struct CopyOut {
+    /// The query whose rows are copied, e.g. `SELECT id, name FROM contacts` for
+    /// `COPY contacts (id, name) TO STDOUT`
+    pub query: String,
+    pub options: CopyOptions,
+}
+
+/// The index of the parenthesis closing the one the text starts with
+fn closing_parenthesis(text: &str) -> Option<usize> {
+    let mut depth = 0;
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (Some(open), c) if c == open => quote = None,
+            (Some(_), _) => {}
+            (None, '\'') | (None, '"') => quote = Some(c),
+            (None, '(') => depth += 1,
+            (None, ')') => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The index after the table name the text starts with, which can be quoted
+fn table_end(text: &str) -> usize {
+    let mut quoted = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => quoted = !quoted,
+            c if !quoted && (c.is_whitespace() || c == '(') => return index,
+            _ => {}
+        }
+    }
+    text.len()
+}
+
+fn is_keyword(token: Option<&Token>, keyword: &str) -> bool {
+    matches!(token, Some(Token::Word(word)) if word.value.eq_ignore_ascii_case(keyword))
+}
+
+fn expect_word<I: Iterator<Item = Token>>(tokens: &mut I, expected: &str) -> Result<(), String> {
+    match tokens.next() {
+        Some(token) if is_keyword(Some(&token), expected) => Ok(()),
+        _ => Err(format!("expected {}", expected)),
+    }
+}
+
+/// The string of an option, e.g. of `DELIMITER ','` or `NULL AS ''`
+fn literal<I: Iterator<Item = Token>>(
+    tokens: &mut Peekable<I>,
+    option: &str,
+) -> Result<String, String> {
+    if is_keyword(tokens.peek(), "AS") {
+        tokens.next();
+    }
+
+    match tokens.next() {
+        Some(Token::SingleQuotedString(literal)) => Ok(literal),
+        _ => Err(format!("expected a string after {}", option)),
+    }
+}
+
+/// The single character of an option, e.g. of `DELIMITER ','`
+fn character<I: Iterator<Item = Token>>(
+    tokens: &mut Peekable<I>,
+    option: &str,
+) -> Result<u8, String> {
+    match literal(tokens, option)?.as_bytes() {
+        [byte] => Ok(*byte),
+        _ => Err(format!("{} must be a single character", option)),
+    }
+}
+
+/// Parses a `COPY ... TO STDOUT` of a table or query, with the options of either syntax,
+/// e.g. `WITH (FORMAT csv, HEADER)` or `WITH CSV HEADER`
+pub fn parse_copy_out(statement: &str) -> Result<CopyOut, String> {
+    let statement = statement.trim().trim_end_matches(';').trim_end();
+    let rest = match statement.get(..4) {
+        Some(keyword) if keyword.eq_ignore_ascii_case("COPY") => statement[4..].trim_start(),
+        _ => return Err("the statement isn't a COPY".to_string()),
+    };
+
+    let (query, rest) = if rest.starts_with('(') {
+        let end = closing_parenthesis(rest).ok_or("the query isn't closed")?;
+        (rest[1..end].trim().to_string(), &rest[end + 1..])
+    } else {
+        let end = table_end(rest);
+        let table = &rest[..end];
+        let rest = rest[end..].trim_start();
+        if rest.starts_with('(') {
+            let columns_end = closing_parenthesis(rest).ok_or("the columns aren't closed")?;
+            let columns = rest[1..columns_end].trim();
+            (
+                format!("SELECT {} FROM {}", columns, table),
+                &rest[columns_end + 1..],
+            )
+        } else {
+            (format!("SELECT * FROM {}", table), rest)
+        }
+    };
+
+    let mut tokens = Tokenizer::new(&PostgreSqlDialect {}, rest)
+        .tokenize()
+        .map_err(|_| "the options can't be parsed".to_string())?
+        .into_iter()
+        .filter(|token| !matches!(token, Token::Whitespace(_)))
+        .peekable();
+    expect_word(&mut tokens, "TO")?;
+    expect_word(&mut tokens, "STDOUT")?;
+
+    let mut csv = false;
+    let mut header = false;
+    let mut delimiter = None;
+    let mut null = None;
+    let mut quote = None;
+    let mut escape = None;
+    while let Some(token) = tokens.next() {
+        let option = match token {
+            Token::Word(word) => word.value.to_uppercase(),
+            Token::LParen | Token::RParen | Token::Comma => continue,
+            token => return Err(format!("unexpected {}", token)),
+        };
+
+        match option.as_str() {
+            "WITH" => {}
+            "FORMAT" => match tokens.next() {
+                format if is_keyword(format.as_ref(), "TEXT") => csv = false,
+                format if is_keyword(format.as_ref(), "CSV") => csv = true,
+                format if is_keyword(format.as_ref(), "BINARY") => {
+                    return Err("the binary format isn't supported".to_string())
+                }
+                _ => return Err("expected TEXT or CSV after FORMAT".to_string()),
+            },
+            "CSV" => csv = true,
+            "BINARY" => return Err("the binary format isn't supported".to_string()),
+            "HEADER" => {
+                header = if ["TRUE", "ON"]
+                    .iter()
+                    .any(|value| is_keyword(tokens.peek(), value))
+                {
+                    tokens.next();
+                    true
+                } else if ["FALSE", "OFF"]
+                    .iter()
+                    .any(|value| is_keyword(tokens.peek(), value))
+                {
+                    tokens.next();
+                    false
+                } else {
+                    true
+                }
+            }
+            "DELIMITER" => delimiter = Some(character(&mut tokens, &option)?),
+            "NULL" => null = Some(literal(&mut tokens, &option)?),
+            "QUOTE" => quote = Some(character(&mut tokens, &option)?),
+            "ESCAPE" => escape = Some(character(&mut tokens, &option)?),
+            option => return Err(format!("the option {} isn't supported", option)),
+        }
+    }
+
+    let defaults = if csv {
+        CopyOptions::csv()
+    } else {
+        CopyOptions::text()
+    };
+    let quote = quote.unwrap_or(defaults.quote);
+    let options = CopyOptions {
+        header,
+        delimiter: delimiter.unwrap_or(defaults.delimiter),
+        null: null.unwrap_or(defaults.null),
+        quote,
+        // Quotes are escaped by doubling them unless an escape is given
+        escape: escape.unwrap_or(quote),
+        ..defaults
+    };
+
+    Ok(CopyOut { query, options })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_copy_out() {
+        assert_eq!(
+            Ok(CopyOut {
+                query: "SELECT * FROM contacts".to_string(),
+                options: CopyOptions::text(),
+            }),
+            parse_copy_out("COPY contacts TO STDOUT;")
+        );
+
+        assert_eq!(
+            Ok(CopyOut {
+                query: "SELECT id, \"last name\" FROM public.contacts".to_string(),
+                options: CopyOptions {
+                    header: true,
+                    ..CopyOptions::csv()
+                },
+            }),
+            parse_copy_out("copy public.contacts(id, \"last name\") to stdout with csv header")
+        );
+
+        assert_eq!(
+            Ok(CopyOut {
+                query: "SELECT age FROM contacts WHERE name = 'a)'".to_string(),
+                options: CopyOptions {
+                    delimiter: b';',
+                    null: "NULL".to_string(),
+                    quote: b'\'',
+                    escape: b'\'',
+                    ..CopyOptions::csv()
+                },
+            }),
+            parse_copy_out(
+                "COPY (SELECT age FROM contacts WHERE name = 'a)') TO STDOUT \
+                 WITH (FORMAT csv, DELIMITER ';', NULL 'NULL', QUOTE '''', HEADER false)"
+            )
+        );
+
+        assert!(parse_copy_out("COPY contacts TO STDOUT (FORMAT binary)").is_err());
+        assert!(parse_copy_out("COPY contacts TO '/tmp/contacts'").is_err());
+        assert!(parse_copy_out("COPY contacts TO STDOUT (FORCE_QUOTE *)").is_err());
+    }
+}
@@ -0,0 +1,126 @@
+/// A statement managing a server-side cursor, which sqlparser doesn't parse
+#[derive(Debug, PartialEq)]
+pub enum CursorStatement {
+    Declare {
+        name: String,
+        query: String,
+    },
+    /// Returns rows of the cursor's query
+    Fetch {
+        name: String,
+    },
+    /// No name means all cursors are closed
+    Close {
+        name: Option<String>,
+    },
+}
+
+/// The words of the statement with the offset after each, quoted identifiers are a single word
+fn words(statement: &str) -> Vec<(&str, usize)> {
+    let mut words = vec![];
+    let mut start = None;
+    let mut in_quotes = false;
+
+    for (index, c) in statement.char_indices() {
+        match (start, c) {
+            (_, '"') => {
+                in_quotes = !in_quotes;
+                start.get_or_insert(index);
+            }
+            (Some(word_start), c) if c.is_whitespace() && !in_quotes => {
+                words.push((&statement[word_start..index], index));
+                start = None;
+            }
+            (None, c) if !c.is_whitespace() => start = Some(index),
+            _ => {}
+        }
+    }
+
+    if let Some(word_start) = start {
+        words.push((&statement[word_start..], statement.len()));
+    }
+
+    words
+}
+
+// Unquoted identifiers are case insensitive
+fn identifier(word: &str) -> String {
+    match word
+        .strip_prefix('"')
+        .and_then(|word| word.strip_suffix('"'))
+    {
+        Some(quoted) => quoted.replace("\"\"", "\""),
+        None => word.to_lowercase(),
+    }
+}
+
+/// Parses `DECLARE`, `FETCH` and `CLOSE` statements, other statements return none
+pub fn parse_cursor_statement(statement: &str) -> Option<CursorStatement> {
+    let statement = statement.trim().trim_end_matches(';').trim_end();
+    let words = words(statement);
+    let keyword = |index: usize| {
+        words
+            .get(index)
+            .map(|(word, _)| word.to_uppercase())
+            .unwrap_or_default()
+    };
+
+    match keyword(0).as_str() {
+        "DECLARE" => {
+            let name = identifier(words.get(1)?.0);
+
+            // Options like SCROLL come before CURSOR, WITH HOLD comes before FOR
+            let cursor = (2..words.len()).find(|index| keyword(*index) == "CURSOR")?;
+            let for_index = (cursor + 1..words.len()).find(|index| keyword(*index) == "FOR")?;
+            let query = statement[words[for_index].1..].trim().to_string();
+
+            Some(CursorStatement::Declare { name, query })
+        }
+        // The name comes last, after the direction and an optional FROM or IN
+        "FETCH" if words.len() > 1 => Some(CursorStatement::Fetch {
+            name: identifier(words.last()?.0),
+        }),
+        "CLOSE" if words.len() == 2 => Some(CursorStatement::Close {
+            name: match keyword(1).as_str() {
+                "ALL" => None,
+                _ => Some(identifier(words[1].0)),
+            },
+        }),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_cursor_statement() {
+        assert_eq!(
+            Some(CursorStatement::Declare {
+                name: "contacts_cursor".to_string(),
+                query: "SELECT name FROM contacts".to_string()
+            }),
+            parse_cursor_statement(
+                "declare Contacts_Cursor NO SCROLL CURSOR WITH HOLD FOR SELECT name FROM contacts;"
+            )
+        );
+        assert_eq!(
+            Some(CursorStatement::Fetch {
+                name: "My Cursor".to_string()
+            }),
+            parse_cursor_statement("FETCH FORWARD 100 FROM \"My Cursor\"")
+        );
+        assert_eq!(
+            Some(CursorStatement::Fetch {
+                name: "c".to_string()
+            }),
+            parse_cursor_statement("fetch c")
+        );
+        assert_eq!(
+            Some(CursorStatement::Close { name: None }),
+            parse_cursor_statement("CLOSE ALL")
+        );
+        assert_eq!(None, parse_cursor_statement("SELECT 1"));
+    }
+}
@@ -0,0 +1,85 @@
+use crate::projection::TableColumn;
+use proboscis_core::{resolver::ResolveError, sqlstate};
+use sqlparser::{
+    dialect::PostgreSqlDialect,
+    tokenizer::{Token, Tokenizer, TokenizerError, Word},
+};
+
+// Unquoted identifiers are case insensitive
+fn identifier(word: &Word) -> String {
+    match word.quote_style {
+        Some(_) => word.value.clone(),
+        None => word.value.to_lowercase(),
+    }
+}
+
+/// Finds the first denied column the query references in any clause, without tracing it.
+/// A column counts as referenced if the query mentions its table and either its name or a
+/// wildcard, so queries are rather rejected than a denied column being missed, e.g. if
+/// another table of a join has a column of the same name.
+pub fn find_denied_column<'a>(
+    query: &str,
+    denied_columns: &'a [TableColumn],
+) -> Result<Option<&'a TableColumn>, TokenizerError> {
+    if denied_columns.is_empty() {
+        return Ok(None);
+    }
+
+    let tokens = Tokenizer::new(&PostgreSqlDialect {}, query).tokenize()?;
+
+    let mut words = vec![];
+    let mut has_wildcard = false;
+    let mut previous = None;
+    for token in &tokens {
+        match token {
+            Token::Word(word) => words.push(identifier(word)),
+            // `count(*)` doesn't project any column
+            Token::Mul if previous != Some(&Token::LParen) => has_wildcard = true,
+            _ => {}
+        }
+
+        if !matches!(token, Token::Whitespace(_)) {
+            previous = Some(token);
+        }
+    }
+
+    Ok(denied_columns.iter().find(|TableColumn { table, column }| {
+        words.contains(table) && (has_wildcard || words.contains(column))
+    }))
+}
+
+/// The error postgres returns for a column the user lacks the privilege to read
+pub fn permission_denied(TableColumn { table, column }: &TableColumn) -> ResolveError {
+    ResolveError::Target(sqlstate::error_response(
+        sqlstate::ERROR,
+        sqlstate::INSUFFICIENT_PRIVILEGE,
+        format!(
+            "permission denied for column {} of relation {}",
+            column, table
+        ),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_denied_column() {
+        let denied = vec![TableColumn {
+            table: "contacts".to_string(),
+            column: "ssn".to_string(),
+        }];
+
+        let is_denied = |query: &str| find_denied_column(query, &denied).unwrap().is_some();
+
+        assert!(is_denied("SELECT SSN FROM contacts"));
+        assert!(is_denied(
+            "SELECT id FROM public.contacts WHERE ssn LIKE '123%'"
+        ));
+        assert!(is_denied("SELECT c.* FROM contacts c"));
+        assert!(!is_denied("SELECT count(*) FROM contacts"));
+        assert!(!is_denied("SELECT id FROM contacts WHERE name = 'ssn'"));
+        assert!(!is_denied("SELECT ssn FROM employees"));
+    }
+}
@@ -6,6 +6,10 @@ pub enum TransformerError {
     #[error(transparent)]
     Arrow(#[from] arrow::error::ArrowError),
 
+    /// The transformer can't handle the values, e.g. of a type it doesn't support
+    #[error("{0}")]
+    Unsupported(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
@@ -14,7 +18,15 @@ impl From<TransformerError> for ResolveError {
     fn from(error: TransformerError) -> Self {
         match error {
             TransformerError::Arrow(err) => ResolveError::Arrow(err),
-            _ => ResolveError::Other(anyhow::anyhow!(error)),
+            TransformerError::Unsupported(message) => ResolveError::Unsupported(message),
+            TransformerError::Other(err) => ResolveError::Transform(err),
         }
     }
 }
+
+impl TransformerError {
+    /// The ErrorResponse a client receives when transforming its result fails
+    pub fn to_error_response(self) -> proboscis_postgres_protocol::message::Error {
+        ResolveError::from(self).to_error_response()
+    }
+}
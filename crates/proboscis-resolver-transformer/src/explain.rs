@@ -0,0 +1,178 @@
+use crate::{
+    interface::Transformer,
+    projection::{trace_projection_origin, Aggregate, ProjectedOrigin, TableColumn},
+    TransformerError,
+};
+use arrow::datatypes::Schema;
+use sqlparser::{dialect::PostgreSqlDialect, parser::Parser};
+use std::convert::TryFrom;
+
+/// How the transformers would change a single column of a result
+#[derive(Debug, PartialEq)]
+pub struct ColumnExplanation {
+    pub name: String,
+    pub origin: ProjectedOrigin,
+    /// Descriptions of the changes, in the order the transformers are applied
+    pub transformations: Vec<String>,
+}
+
+impl ColumnExplanation {
+    /// The transformations joined in the order they are applied, or `unchanged`
+    pub fn describe_transformations(&self) -> String {
+        if self.transformations.is_empty() {
+            "unchanged".to_string()
+        } else {
+            self.transformations.join(", then ")
+        }
+    }
+}
+
+/// Describes where the values of a column come from, e.g. `contacts.age`
+pub fn describe_origin(origin: &ProjectedOrigin) -> String {
+    match origin {
+        ProjectedOrigin::TableColumn(TableColumn { table, column }) => {
+            format!("{}.{}", table, column)
+        }
+        ProjectedOrigin::AmbiguousTableColumn(candidates) => candidates
+            .iter()
+            .map(|TableColumn { table, column }| format!("{}.{}", table, column))
+            .collect::<Vec<String>>()
+            .join(" or "),
+        ProjectedOrigin::Value => "value".to_string(),
+        ProjectedOrigin::Function => "function".to_string(),
+        ProjectedOrigin::Aggregate(Aggregate { function, tables }) => {
+            format!("{} over {}", function, tables.join(", "))
+        }
+    }
+}
+
+/// Describes how the transformers would change the result of a query, without running it.
+/// The schema is the one of the untransformed result, e.g. from a target that was asked
+/// to describe the query.
+pub fn explain_query(
+    query: &str,
+    schema: &Schema,
+    transformers: &[&dyn Transformer],
+) -> Result<Vec<ColumnExplanation>, TransformerError> {
+    let statement = Parser::parse_sql(&PostgreSqlDialect {}, query)
+        .map_err(|err| anyhow::anyhow!("couldn't parse the query: {}", err))?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow::anyhow!("the query doesn't contain a statement"))?;
+
+    let mut fields = vec![];
+    for field in schema.fields() {
+        fields.push(
+            proboscis_core::data::field::Field::try_from(field)
+                .map_err(|err| anyhow::anyhow!(err))?,
+        );
+    }
+
+    let origins = trace_projection_origin(&statement, &fields)
+        .map_err(|err| anyhow::anyhow!("couldn't trace the projected columns: {}", err))?;
+
+    let mut explanations: Vec<ColumnExplanation> = schema
+        .fields()
+        .iter()
+        .zip(origins.iter())
+        .map(|(field, origin)| ColumnExplanation {
+            name: field.name().clone(),
+            origin: origin.clone(),
+            transformations: vec![],
+        })
+        .collect();
+
+    for transformer in transformers {
+        let descriptions = transformer.explain_schema(schema, &origins)?;
+
+        for (explanation, description) in explanations.iter_mut().zip(descriptions) {
+            explanation.transformations.extend(description);
+        }
+    }
+
+    Ok(explanations)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{datatypes::DataType, record_batch::RecordBatch};
+
+    struct DropColumn(&'static str);
+
+    impl Transformer for DropColumn {
+        fn transform_schema(
+            &self,
+            schema: &Schema,
+            _origins: &[ProjectedOrigin],
+        ) -> Result<Schema, TransformerError> {
+            Ok(schema.clone())
+        }
+
+        fn transform_records(
+            &self,
+            data: &RecordBatch,
+            _origins: &[ProjectedOrigin],
+        ) -> Result<RecordBatch, TransformerError> {
+            Ok(data.clone())
+        }
+
+        fn explain_schema(
+            &self,
+            schema: &Schema,
+            _origins: &[ProjectedOrigin],
+        ) -> Result<Vec<Option<String>>, TransformerError> {
+            Ok(schema
+                .fields()
+                .iter()
+                .map(|field| {
+                    Some(format!("dropped {}", field.name())).filter(|_| field.name() == self.0)
+                })
+                .collect())
+        }
+    }
+
+    fn field(name: &str) -> arrow::datatypes::Field {
+        (&proboscis_core::data::field::Field {
+            name: name.to_string(),
+            table_oid: 0,
+            column_number: 0,
+            data_type: DataType::Int32,
+            extension: None,
+            format: 0,
+            original_type: None,
+        })
+            .into()
+    }
+
+    #[test]
+    fn test_explain_query() {
+        let schema = Schema::new(vec![field("id"), field("age")]);
+
+        let explanations =
+            explain_query("SELECT id, age FROM users", &schema, &[&DropColumn("age")]).unwrap();
+
+        let origin = |column: &str| {
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: "users".to_string(),
+                column: column.to_string(),
+            })
+        };
+
+        assert_eq!(
+            vec![
+                ColumnExplanation {
+                    name: "id".to_string(),
+                    origin: origin("id"),
+                    transformations: vec![],
+                },
+                ColumnExplanation {
+                    name: "age".to_string(),
+                    origin: origin("age"),
+                    transformations: vec!["dropped age".to_string()],
+                },
+            ],
+            explanations
+        );
+    }
+}
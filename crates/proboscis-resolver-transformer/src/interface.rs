@@ -12,4 +12,44 @@ pub trait Transformer: Send + Sync {
         data: &RecordBatch,
         origins: &[ProjectedOrigin],
     ) -> Result<RecordBatch, TransformerError>;
+
+    /// Transforms all record batches of a result.
+    /// Transformers that need to see every row at once, like anonymization, can override this.
+    fn transform_batches(
+        &self,
+        data: &[RecordBatch],
+        origins: &[ProjectedOrigin],
+    ) -> Result<Vec<RecordBatch>, TransformerError> {
+        data.iter()
+            .map(|batch| self.transform_records(batch, origins))
+            .collect()
+    }
+
+    /// Describes how each column of a result with the schema would be changed,
+    /// `None` for columns the transformer leaves as they are
+    fn explain_schema(
+        &self,
+        schema: &Schema,
+        _origins: &[ProjectedOrigin],
+    ) -> Result<Vec<Option<String>>, TransformerError> {
+        Ok(vec![None; schema.fields().len()])
+    }
+
+    /// The query the target runs instead of the client's, none to run it as it is. Transformers
+    /// which need the target to compute a result differently rewrite it, e.g. to bound the
+    /// values an aggregate adds up.
+    fn rewrite_query(&self, _query: &str) -> Result<Option<String>, TransformerError> {
+        Ok(None)
+    }
+
+    /// The types of the parameters of the statement as clients have to send them, given the
+    /// types the target expects. Transformers which rewrite parameters before the target
+    /// receives them, e.g. hashing an integer into text, return the types they accept instead.
+    fn transform_parameter_types(
+        &self,
+        _query: &str,
+        types: &[u32],
+    ) -> Result<Vec<u32>, TransformerError> {
+        Ok(types.to_vec())
+    }
 }
@@ -1,8 +1,28 @@
+mod builder;
+mod classification;
+mod copy;
+mod cursor;
+mod denial;
 mod error;
+mod explain;
 mod interface;
+mod masking;
+mod parameters;
 pub mod projection;
 mod resolver;
+mod retention;
+#[cfg(any(test, feature = "testing"))]
+mod testing;
 
+pub use builder::ProxyBuilderExt;
+pub use classification::{classify_statement, StatementKind};
 pub use error::TransformerError;
+pub use explain::{describe_origin, explain_query, ColumnExplanation};
 pub use interface::Transformer;
-pub use resolver::TransformingResolver;
+pub use masking::DescribeMasking;
+pub use parameters::{parameterized_columns, replace_parameters};
+pub use proboscis_core::utils::role::{parse_role_changes, RoleChange};
+pub use resolver::{TransformingResolver, UnparseableQueryPolicy};
+pub use retention::RetentionRule;
+#[cfg(any(test, feature = "testing"))]
+pub use testing::{sample_batch, PolicyTest, PolicyTestResult};
@@ -0,0 +1,100 @@
+use arrow::datatypes::{Field, Schema};
+
+// Metadata restoring the type the target described a field with
+const TYPE_METADATA_KEYS: &[&str] = &["type_oid", "type_length", "type_modifier"];
+
+/// How columns a transformer changes are described to clients, so their names and
+/// types don't claim the values are the original ones
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DescribeMasking {
+    /// Appended to the names of the columns, e.g. `_masked` for `email_masked`
+    pub name_suffix: Option<String>,
+    /// Describes the columns with the type of their values and without the table they come
+    /// from, like computed columns, instead of the type the target described them with
+    pub declare_value_types: bool,
+}
+
+impl DescribeMasking {
+    fn mask_field(&self, field: &Field) -> Field {
+        let name = match &self.name_suffix {
+            Some(suffix) => format!("{}{}", field.name(), suffix),
+            None => field.name().clone(),
+        };
+
+        let mut masked = Field::new(&name, field.data_type().clone(), field.is_nullable());
+
+        let mut metadata = field.metadata().clone();
+        if self.declare_value_types {
+            if let Some(metadata) = &mut metadata {
+                for key in TYPE_METADATA_KEYS {
+                    metadata.remove(*key);
+                }
+                metadata.insert("table_oid".to_string(), "0".to_string());
+                metadata.insert("column_number".to_string(), "0".to_string());
+            }
+        }
+        masked.set_metadata(metadata);
+
+        masked
+    }
+
+    /// Masks the fields of the schema with the names of transformed columns
+    pub fn mask_schema(&self, schema: &Schema, transformed: &[String]) -> Schema {
+        Schema::new_with_metadata(
+            schema
+                .fields()
+                .iter()
+                .map(|field| {
+                    if transformed.contains(field.name()) {
+                        self.mask_field(field)
+                    } else {
+                        field.clone()
+                    }
+                })
+                .collect(),
+            schema.metadata().clone(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::datatypes::DataType;
+    use proboscis_core::data::field::PostgresTypeInfo;
+    use std::convert::TryFrom;
+
+    #[test]
+    fn test_mask_schema() {
+        let field = |name: &str| -> Field {
+            (&proboscis_core::data::field::Field {
+                name: name.to_string(),
+                table_oid: 16384,
+                column_number: 2,
+                data_type: DataType::Utf8,
+                extension: None,
+                format: 0,
+                original_type: Some(PostgresTypeInfo {
+                    oid: 1043,
+                    length: -1,
+                    modifier: 259,
+                }),
+            })
+                .into()
+        };
+        let schema = Schema::new(vec![field("id"), field("email")]);
+
+        let masking = DescribeMasking {
+            name_suffix: Some("_masked".to_string()),
+            declare_value_types: true,
+        };
+        let masked = masking.mask_schema(&schema, &["email".to_string()]);
+
+        assert_eq!(schema.field(0), masked.field(0));
+
+        let email = proboscis_core::data::field::Field::try_from(masked.field(1)).unwrap();
+        assert_eq!("email_masked", email.name);
+        assert_eq!(0, email.table_oid);
+        assert_eq!(None, email.original_type);
+    }
+}
@@ -0,0 +1,196 @@
+use crate::projection::{relations, resolve_column, ProjectedOrigin, Relation, TableColumn};
+use sqlparser::{
+    ast::{BinaryOperator, Expr, Ident, SetExpr, Statement, Value},
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+
+/// The query with its parameters like `$1` replaced by `NULL`, so it can be parsed.
+/// Parameters within string literals, quoted identifiers and comments are left as they are.
+pub fn replace_parameters(query: &str) -> String {
+    let chars: Vec<char> = query.chars().collect();
+    let mut result = String::with_capacity(query.len());
+    let mut index = 0;
+
+    // Copies the chars up to and including the closing delimiter
+    let copy_until = |result: &mut String, start: usize, end: &str| -> usize {
+        let rest: String = chars[start..].iter().collect();
+        let length = match rest.find(end) {
+            Some(position) => rest[..position + end.len()].chars().count(),
+            None => rest.chars().count(),
+        };
+        result.extend(&chars[start..start + length]);
+        start + length
+    };
+
+    while index < chars.len() {
+        match chars[index] {
+            // Doubled quotes within literals are copied as two adjacent literals
+            '\'' | '"' => {
+                let quote = chars[index];
+                result.push(quote);
+                index = copy_until(&mut result, index + 1, &quote.to_string());
+            }
+            '-' if chars.get(index + 1) == Some(&'-') => {
+                index = copy_until(&mut result, index, "\n");
+            }
+            '/' if chars.get(index + 1) == Some(&'*') => {
+                index = copy_until(&mut result, index, "*/");
+            }
+            '$' if chars.get(index + 1).map_or(false, char::is_ascii_digit)
+                && !(index > 0
+                    && (chars[index - 1].is_alphanumeric() || chars[index - 1] == '_')) =>
+            {
+                index += 1;
+                while chars.get(index).map_or(false, char::is_ascii_digit) {
+                    index += 1;
+                }
+                result.push_str("NULL");
+            }
+            c => {
+                result.push(c);
+                index += 1;
+            }
+        }
+    }
+
+    result
+}
+
+fn is_parameter(expr: &Expr) -> bool {
+    match expr {
+        Expr::Value(Value::Null) => true,
+        Expr::Cast { expr, .. } | Expr::Nested(expr) => is_parameter(expr),
+        _ => false,
+    }
+}
+
+fn column(expr: &Expr, relations: &[Relation]) -> Vec<TableColumn> {
+    let identifiers: Vec<String> = match expr {
+        Expr::Identifier(Ident { value, .. }) => vec![value.clone()],
+        Expr::CompoundIdentifier(identifiers) => identifiers
+            .iter()
+            .map(|identifier| identifier.value.clone())
+            .collect(),
+        Expr::Cast { expr, .. } | Expr::Nested(expr) => return column(expr, relations),
+        _ => return vec![],
+    };
+
+    match resolve_column(relations, &identifiers) {
+        Ok(ProjectedOrigin::TableColumn(column)) => vec![column],
+        Ok(ProjectedOrigin::AmbiguousTableColumn(candidates)) => candidates,
+        _ => vec![],
+    }
+}
+
+fn collect_columns(expr: &Expr, relations: &[Relation], result: &mut Vec<TableColumn>) {
+    match expr {
+        Expr::BinaryOp { left, op, right }
+            if matches!(op, BinaryOperator::And | BinaryOperator::Or) =>
+        {
+            collect_columns(left, relations, result);
+            collect_columns(right, relations, result);
+        }
+        Expr::BinaryOp { left, op: _, right } => {
+            if is_parameter(right) {
+                result.append(&mut column(left, relations));
+            }
+            if is_parameter(left) {
+                result.append(&mut column(right, relations));
+            }
+        }
+        Expr::InList { expr, list, .. } if list.iter().any(is_parameter) => {
+            result.append(&mut column(expr, relations))
+        }
+        Expr::Between {
+            expr, low, high, ..
+        } if is_parameter(low) || is_parameter(high) => result.append(&mut column(expr, relations)),
+        Expr::Nested(expr) | Expr::UnaryOp { expr, .. } => collect_columns(expr, relations, result),
+        _ => {}
+    }
+}
+
+/// The table columns the WHERE clause of the query compares with its parameters,
+/// e.g. `contacts.zip` of `SELECT age FROM contacts WHERE zip = $1`. Their values narrow
+/// the rows of the result down to a population the client chooses when it binds them.
+pub fn parameterized_columns(query: &str) -> Vec<TableColumn> {
+    let dialect = PostgreSqlDialect {};
+    let statements = match Parser::parse_sql(&dialect, &replace_parameters(query)) {
+        Ok(statements) => statements,
+        Err(_) => return vec![],
+    };
+
+    let mut result = vec![];
+    for statement in &statements {
+        let select = match statement {
+            Statement::Query(query) => match &query.body {
+                SetExpr::Select(select) => select,
+                _ => continue,
+            },
+            _ => continue,
+        };
+
+        let relations = match relations(select) {
+            Ok(relations) => relations,
+            Err(_) => continue,
+        };
+
+        if let Some(selection) = &select.selection {
+            collect_columns(selection, &relations, &mut result);
+        }
+    }
+
+    let mut unique = vec![];
+    for column in result {
+        if !unique.contains(&column) {
+            unique.push(column);
+        }
+    }
+
+    unique
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn column(table: &str, column: &str) -> TableColumn {
+        TableColumn {
+            table: table.to_string(),
+            column: column.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_replace_parameters() {
+        assert_eq!(
+            "SELECT a FROM t WHERE b = NULL AND c = '$2' AND \"$3\" = NULL::int -- $4",
+            replace_parameters(
+                "SELECT a FROM t WHERE b = $1 AND c = '$2' AND \"$3\" = $12::int -- $4"
+            )
+        );
+        assert_eq!("SELECT $$x$$", replace_parameters("SELECT $$x$$"));
+    }
+
+    #[test]
+    fn test_parameterized_columns() {
+        assert_eq!(
+            vec![column("contacts", "zip")],
+            parameterized_columns("SELECT age FROM contacts WHERE zip = $1")
+        );
+        assert_eq!(
+            vec![column("contacts", "zip"), column("contacts", "age")],
+            parameterized_columns(
+                "SELECT c.id FROM contacts c WHERE (c.zip IN ($1, $2) OR age BETWEEN 18 AND $3) AND c.id > 10"
+            )
+        );
+        assert_eq!(
+            vec![column("contacts", "zip"), column("orders", "zip")],
+            parameterized_columns(
+                "SELECT total FROM contacts JOIN orders ON contacts.id = orders.contact_id WHERE $1 = zip"
+            )
+        );
+        assert!(parameterized_columns("SELECT age FROM contacts WHERE zip = '10115'").is_empty());
+        assert!(parameterized_columns("SELECT age FROM contacts WHERE zip IS NULL").is_empty());
+    }
+}
@@ -1,8 +1,15 @@
-use proboscis_core::data::field::Field;
-use sqlparser::ast::{
-    Expr, Ident, SelectItem, SetExpr, Statement, TableAlias, TableFactor, TableWithJoins,
+use proboscis_core::{
+    catalog::{Catalog, RelationKind},
+    data::field::Field,
 };
-use std::collections::VecDeque;
+use sqlparser::{
+    ast::{
+        Expr, Ident, ObjectName, Select, SelectItem, SetExpr, Statement, TableAlias, TableFactor,
+    },
+    dialect::PostgreSqlDialect,
+    parser::Parser,
+};
+use std::collections::{HashMap, VecDeque};
 
 #[derive(Clone, Debug, PartialEq)]
 pub struct TableColumn {
@@ -10,195 +17,388 @@ pub struct TableColumn {
     pub column: String,
 }
 
+/// An aggregate function summarizing the rows of the queried tables, e.g. `count(*)`
+#[derive(Clone, Debug, PartialEq)]
+pub struct Aggregate {
+    /// The lowercase name of the function
+    pub function: String,
+    /// The tables of the FROM clause, whose rows are aggregated
+    pub tables: Vec<String>,
+}
+
 #[derive(Clone, Debug, PartialEq)]
 pub enum ProjectedOrigin {
     TableColumn(TableColumn),
+    /// An unqualified column of a query joining multiple tables,
+    /// which could originate from any of the candidates
+    AmbiguousTableColumn(Vec<TableColumn>),
     Value,
     Function,
+    Aggregate(Aggregate),
+}
+
+const AGGREGATE_FUNCTIONS: [&str; 5] = ["count", "sum", "avg", "min", "max"];
+
+pub(crate) struct Relation {
+    name: String,
+    alias: Option<String>,
+}
+
+impl Relation {
+    fn matches(&self, identifier: &str) -> bool {
+        self.name == identifier || self.alias.as_deref() == Some(identifier)
+    }
+}
+
+/// Flattens the tables of the FROM clause including all joined tables in their order of appearance
+pub(crate) fn relations(select: &Select) -> Result<Vec<Relation>, &'static str> {
+    let mut result = vec![];
+
+    for table in &select.from {
+        let factors =
+            std::iter::once(&table.relation).chain(table.joins.iter().map(|join| &join.relation));
+
+        for factor in factors {
+            match factor {
+                TableFactor::Table {
+                    name,
+                    alias,
+                    args: _,
+                    with_hints: _,
+                } => result.push(Relation {
+                    name: name.to_string(),
+                    alias: alias
+                        .as_ref()
+                        .map(|TableAlias { name, columns: _ }| name.to_string()),
+                }),
+                _ => return Err("projection tracing error"),
+            }
+        }
+    }
+
+    Ok(result)
+}
+
+pub(crate) fn resolve_column(
+    relations: &[Relation],
+    identifiers: &[String],
+) -> Result<ProjectedOrigin, &'static str> {
+    match identifiers {
+        [column] => match relations {
+            [] => Err("projection tracing error"),
+            [relation] => Ok(ProjectedOrigin::TableColumn(TableColumn {
+                table: relation.name.clone(),
+                column: column.clone(),
+            })),
+            _ => Ok(ProjectedOrigin::AmbiguousTableColumn(
+                relations
+                    .iter()
+                    .map(|relation| TableColumn {
+                        table: relation.name.clone(),
+                        column: column.clone(),
+                    })
+                    .collect(),
+            )),
+        },
+        [table, column] => relations
+            .iter()
+            .find(|relation| relation.matches(table))
+            .map(|relation| {
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: relation.name.clone(),
+                    column: column.clone(),
+                })
+            })
+            .ok_or("projection tracing error"),
+        _ => Err("projection tracing error"),
+    }
+}
+
+fn resolve_select_item(
+    item: &SelectItem,
+    relations: &[Relation],
+) -> Result<ProjectedOrigin, &'static str> {
+    let expr = match item {
+        SelectItem::UnnamedExpr(expr) => expr,
+        SelectItem::ExprWithAlias { expr, alias: _ } => expr,
+        _ => return Err("projection tracing error"),
+    };
+
+    match expr {
+        Expr::Identifier(Ident {
+            value,
+            quote_style: _,
+        }) => resolve_column(relations, &[value.clone()]),
+        Expr::CompoundIdentifier(identifiers) => {
+            let identifiers: Vec<String> = identifiers
+                .iter()
+                .map(|ident| ident.value.to_string())
+                .collect();
+
+            resolve_column(relations, &identifiers)
+        }
+        Expr::Function(function) => {
+            let name = function.name.to_string().to_lowercase();
+
+            if AGGREGATE_FUNCTIONS.contains(&name.as_str()) {
+                Ok(ProjectedOrigin::Aggregate(Aggregate {
+                    function: name,
+                    tables: relations
+                        .iter()
+                        .map(|relation| relation.name.clone())
+                        .collect(),
+                }))
+            } else {
+                Ok(ProjectedOrigin::Function)
+            }
+        }
+        Expr::Value(_) => Ok(ProjectedOrigin::Value),
+        _ => Err("projection tracing error"),
+    }
+}
+
+/// Pops the fields produced by a wildcard, assigning them to the relations in order.
+/// A new relation starts whenever the table oid of the fields changes, unless the
+/// catalog knows the relation of the oid.
+fn resolve_wildcard<'a>(
+    fields: &mut VecDeque<&'a Field>,
+    field_count: usize,
+    relations: &[&Relation],
+    catalog: Option<&Catalog>,
+) -> Result<Vec<(&'a Field, ProjectedOrigin)>, &'static str> {
+    let mut result = vec![];
+    let mut relation_index = 0;
+    let mut last_table_oid: Option<i32> = None;
+
+    for _ in 0..field_count {
+        let field = fields.pop_front().ok_or("projection tracing error")?;
+
+        if let Some(oid) = last_table_oid {
+            if oid != field.table_oid {
+                relation_index += 1;
+            }
+        }
+
+        let cataloged_relation = catalog
+            .and_then(|catalog| catalog.relation_by_oid(field.table_oid as u32))
+            .and_then(|cataloged| {
+                relations
+                    .iter()
+                    .find(|relation| cataloged.matches(&relation.name))
+            });
+
+        let relation = match cataloged_relation {
+            Some(relation) => relation,
+            None => relations
+                .get(relation_index)
+                .ok_or("projection tracing error")?,
+        };
+
+        result.push((
+            field,
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: relation.name.clone(),
+                column: field.name.clone(),
+            }),
+        ));
+
+        last_table_oid = Some(field.table_oid);
+    }
+
+    Ok(result)
+}
+
+fn trace_select(
+    select: &Select,
+    fields: &[Field],
+    catalog: Option<&Catalog>,
+) -> Result<Vec<ProjectedOrigin>, &'static str> {
+    let relations = relations(select)?;
+    let mut remaining_fields = fields.iter().collect::<VecDeque<_>>();
+    let mut traced: Vec<(&Field, ProjectedOrigin)> = vec![];
+
+    for (index, item) in select.projection.iter().enumerate() {
+        match item {
+            SelectItem::Wildcard => {
+                // Every item following the wildcard projects exactly one field
+                let items_after = select.projection.len() - index - 1;
+                let field_count = remaining_fields
+                    .len()
+                    .checked_sub(items_after)
+                    .ok_or("projection tracing error")?;
+
+                let all_relations: Vec<&Relation> = relations.iter().collect();
+                traced.append(&mut resolve_wildcard(
+                    &mut remaining_fields,
+                    field_count,
+                    &all_relations,
+                    catalog,
+                )?);
+            }
+            SelectItem::QualifiedWildcard(ObjectName(identifiers)) => {
+                let identifier = identifiers
+                    .iter()
+                    .map(|ident| ident.value.clone())
+                    .collect::<Vec<String>>()
+                    .join(".");
+
+                let relation = relations
+                    .iter()
+                    .find(|relation| relation.matches(&identifier))
+                    .ok_or("projection tracing error")?;
+
+                let table_oid = remaining_fields
+                    .front()
+                    .ok_or("projection tracing error")?
+                    .table_oid;
+
+                let field_count = remaining_fields
+                    .iter()
+                    .take_while(|field| field.table_oid == table_oid)
+                    .count();
+
+                traced.append(&mut resolve_wildcard(
+                    &mut remaining_fields,
+                    field_count,
+                    &[relation],
+                    catalog,
+                )?);
+            }
+            _ => {
+                let field = remaining_fields
+                    .pop_front()
+                    .ok_or("projection tracing error")?;
+
+                traced.push((field, resolve_select_item(item, &relations)?));
+            }
+        }
+    }
+
+    // Columns of the same table share the table oid of their fields, which
+    // allows attributing unqualified columns of joins once the table is known
+    let mut tables_by_oid: HashMap<i32, String> = HashMap::new();
+    for (field, origin) in &traced {
+        if let ProjectedOrigin::TableColumn(TableColumn { table, column: _ }) = origin {
+            if field.table_oid > 0 {
+                tables_by_oid.insert(field.table_oid, table.clone());
+            }
+        }
+    }
+
+    let result = traced
+        .into_iter()
+        .map(|(field, origin)| match origin {
+            ProjectedOrigin::AmbiguousTableColumn(candidates) => {
+                match tables_by_oid.get(&field.table_oid) {
+                    Some(table) => candidates
+                        .into_iter()
+                        .find(|candidate| &candidate.table == table)
+                        .map(ProjectedOrigin::TableColumn)
+                        .ok_or("projection tracing error"),
+                    None => Ok(ProjectedOrigin::AmbiguousTableColumn(candidates)),
+                }
+            }
+            origin => Ok(origin),
+        })
+        .collect::<Result<Vec<ProjectedOrigin>, &'static str>>()?;
+
+    Ok(result)
 }
 
 pub fn trace_projection_origin(
     ast: &Statement,
     fields: &[Field],
+) -> Result<Vec<ProjectedOrigin>, &'static str> {
+    trace_projection_origin_with_catalog(ast, fields, None)
+}
+
+/// Traces the projection like `trace_projection_origin`, attributing the fields of
+/// wildcards to the tables the catalog knows their table oids of
+pub fn trace_projection_origin_with_catalog(
+    ast: &Statement,
+    fields: &[Field],
+    catalog: Option<&Catalog>,
 ) -> Result<Vec<ProjectedOrigin>, &'static str> {
     match ast {
         Statement::Query(query) => match &query.body {
-            SetExpr::Select(select) => {
-                let mut result = vec![];
-                let mut remaining_fields = fields.iter().collect::<VecDeque<_>>();
-
-                let get_table_column =
-                    |identifiers: &[String]| -> Result<TableColumn, &'static str> {
-                        if identifiers.is_empty() {
-                            // Wildcard
-                            return Err("projection tracing error");
-                        }
-
-                        if identifiers.len() == 1 {
-                            let identifier = identifiers[0].clone();
-                            match &select.from.as_slice() {
-                                [TableWithJoins {
-                                    relation:
-                                        TableFactor::Table {
-                                            name,
-                                            alias: _,
-                                            args: _,
-                                            with_hints: _,
-                                        },
-                                    joins: _,
-                                }] => {
-                                    return Ok(TableColumn {
-                                        table: name.to_string(),
-                                        column: identifier,
-                                    })
-                                }
-                                _ => return Err("projection tracing error"),
-                            }
-                        }
-
-                        if identifiers.len() == 2 {
-                            let table_identifier = identifiers[0].clone();
-                            let column_identifier = identifiers[1].clone();
-
-                            for table in &select.from {
-                                for factor in vec![
-                                    vec![&table.relation],
-                                    table.joins.iter().map(|join| &join.relation).collect(),
-                                ]
-                                .concat()
-                                {
-                                    if let TableFactor::Table {
-                                        name,
-                                        alias,
-                                        args: _,
-                                        with_hints: _,
-                                    } = factor
-                                    {
-                                        let alias_name = alias.as_ref().map(
-                                            |TableAlias { name, columns: _ }| name.to_string(),
-                                        );
-
-                                        if name.to_string() == table_identifier
-                                            || alias_name == Some(table_identifier.clone())
-                                        {
-                                            return Ok(TableColumn {
-                                                table: name.to_string(),
-                                                column: column_identifier,
-                                            });
-                                        }
-                                    };
-                                }
-                            }
-                        }
-
-                        Err("projection tracing error")
-                    };
-
-                for item in &select.projection {
-                    match item {
-                        SelectItem::Wildcard => {
-                            let mut table_index = 0;
-                            let mut last_table_oid: Option<i32> = None;
-                            while let Some(field) = remaining_fields.pop_front() {
-                                let current_table_oid = field.table_oid;
-
-                                if let Some(oid) = &last_table_oid {
-                                    if *oid != current_table_oid {
-                                        table_index += 1;
-                                    }
-                                }
-
-                                let table_name = match &select.from.get(table_index) {
-                                    Some(TableWithJoins {
-                                        relation:
-                                            TableFactor::Table {
-                                                name,
-                                                alias: _,
-                                                args: _,
-                                                with_hints: _,
-                                            },
-                                        joins: _,
-                                    }) => name.to_string(),
-                                    _ => return Err("projection tracing error"),
-                                };
-
-                                result.push(ProjectedOrigin::TableColumn(TableColumn {
-                                    column: field.name.clone(),
-                                    table: table_name,
-                                }));
-
-                                last_table_oid = Some(current_table_oid);
-                            }
-                        }
-
-                        SelectItem::ExprWithAlias {
-                            expr:
-                                Expr::Identifier(Ident {
-                                    value,
-                                    quote_style: _,
-                                }),
-                            alias: _,
-                        } => {
-                            let column = value.clone();
-                            let table_column = get_table_column(&[column])?;
-                            result.push(ProjectedOrigin::TableColumn(table_column))
-                        }
-                        SelectItem::ExprWithAlias {
-                            expr: Expr::Value(_),
-                            alias: _,
-                        } => {
-                            remaining_fields.pop_front();
-                            result.push(ProjectedOrigin::Value)
-                        }
-                        SelectItem::ExprWithAlias {
-                            expr: Expr::Function(_),
-                            alias: _,
-                        } => {
-                            remaining_fields.pop_front();
-                            result.push(ProjectedOrigin::Function)
-                        }
-
-                        SelectItem::UnnamedExpr(Expr::Identifier(Ident {
-                            value,
-                            quote_style: _,
-                        })) => {
-                            let column = value.clone();
-                            let table_column = get_table_column(&[column])?;
-                            result.push(ProjectedOrigin::TableColumn(table_column))
-                        }
-                        SelectItem::UnnamedExpr(Expr::CompoundIdentifier(identifiers)) => {
-                            if identifiers.len() > 2 {
-                                return Err("projection tracing error");
-                            }
-
-                            let identifiers: Vec<String> = identifiers
-                                .iter()
-                                .map(|ident| ident.value.to_string())
-                                .collect();
-
-                            let table_column = get_table_column(&identifiers)?;
-                            result.push(ProjectedOrigin::TableColumn(table_column))
-                        }
-                        SelectItem::UnnamedExpr(Expr::Function(_)) => {
-                            remaining_fields.pop_front();
-                            result.push(ProjectedOrigin::Function)
-                        }
-                        SelectItem::UnnamedExpr(Expr::Value(_)) => {
-                            remaining_fields.pop_front();
-                            result.push(ProjectedOrigin::Value)
-                        }
-                        _ => return Err("projection tracing error"),
-                    }
-                }
-
-                Ok(result)
-            }
+            SetExpr::Select(select) => trace_select(select, fields, catalog),
             _ => Err("projection tracing error"),
         },
         _ => Err("projection tracing error"),
     }
 }
 
+// Views defined on views are expanded up to this depth
+const MAX_VIEW_DEPTH: usize = 8;
+
+/// The origin of the column of a view within the tables of its definition
+fn view_column_origin(
+    catalog: &Catalog,
+    TableColumn { table, column }: &TableColumn,
+    depth: usize,
+) -> Option<ProjectedOrigin> {
+    let view = catalog.relation(table).filter(|relation| {
+        matches!(
+            relation.kind,
+            RelationKind::View | RelationKind::MaterializedView
+        )
+    })?;
+
+    let position = view
+        .columns
+        .iter()
+        .position(|candidate| &candidate.name == column)?;
+
+    let definition = Parser::parse_sql(&PostgreSqlDialect {}, view.definition.as_ref()?)
+        .ok()?
+        .pop()?;
+
+    let fields: Vec<Field> = view
+        .columns
+        .iter()
+        .map(|column| Field {
+            name: column.name.clone(),
+            table_oid: 0,
+            column_number: column.number,
+            data_type: arrow::datatypes::DataType::Utf8,
+            extension: None,
+            format: 0,
+            original_type: None,
+        })
+        .collect();
+
+    let origin = trace_projection_origin_with_catalog(&definition, &fields, Some(catalog))
+        .ok()?
+        .into_iter()
+        .nth(position)?;
+
+    Some(expand_view_origin(catalog, origin, depth + 1))
+}
+
+fn expand_view_origin(catalog: &Catalog, origin: ProjectedOrigin, depth: usize) -> ProjectedOrigin {
+    if depth > MAX_VIEW_DEPTH {
+        return origin;
+    }
+
+    match origin {
+        ProjectedOrigin::TableColumn(table_column) => {
+            view_column_origin(catalog, &table_column, depth)
+                .unwrap_or(ProjectedOrigin::TableColumn(table_column))
+        }
+        origin => origin,
+    }
+}
+
+/// Replaces the columns of views with the columns of the tables they are defined on,
+/// so the rules of the tables apply to the views as well. Columns whose definition
+/// can't be traced keep the view as their origin.
+pub fn expand_views(origins: Vec<ProjectedOrigin>, catalog: &Catalog) -> Vec<ProjectedOrigin> {
+    origins
+        .into_iter()
+        .map(|origin| expand_view_origin(catalog, origin, 0))
+        .collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -219,6 +419,9 @@ mod tests {
                 table_oid: 0,
                 column_number: 0,
                 data_type: arrow::datatypes::DataType::Int64,
+                extension: None,
+                format: 0,
+                original_type: None,
             }],
         )
         .unwrap();
@@ -248,12 +451,18 @@ mod tests {
                     table_oid: 0,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
                 Field {
                     name: "name".to_string(),
                     table_oid: 0,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
             ],
         )
@@ -289,6 +498,9 @@ mod tests {
                 table_oid: 0,
                 column_number: 0,
                 data_type: arrow::datatypes::DataType::Int64,
+                extension: None,
+                format: 0,
+                original_type: None,
             }],
         )
         .unwrap();
@@ -317,6 +529,9 @@ mod tests {
                 table_oid: 0,
                 column_number: 0,
                 data_type: arrow::datatypes::DataType::Int64,
+                extension: None,
+                format: 0,
+                original_type: None,
             }],
         )
         .unwrap();
@@ -349,18 +564,27 @@ mod tests {
                     table_oid: 0,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
                 Field {
                     name: "id".to_string(),
                     table_oid: 0,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
                 Field {
                     name: "title".to_string(),
                     table_oid: 0,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
             ],
         )
@@ -400,6 +624,9 @@ mod tests {
                 table_oid: -1,
                 column_number: 0,
                 data_type: arrow::datatypes::DataType::Int64,
+                extension: None,
+                format: 0,
+                original_type: None,
             }],
         )
         .unwrap();
@@ -407,6 +634,45 @@ mod tests {
         assert_eq!(unnested_fields, vec![ProjectedOrigin::Function])
     }
 
+    #[test]
+    fn test_aggregate() {
+        let dialect = PostgreSqlDialect {};
+        let query_ast = Parser::parse_sql(
+            &dialect,
+            "SELECT city, COUNT(*) FROM contacts c GROUP BY city",
+        )
+        .unwrap()
+        .pop()
+        .unwrap();
+
+        let field = |name: &str| Field {
+            name: name.to_string(),
+            table_oid: 0,
+            column_number: 0,
+            data_type: arrow::datatypes::DataType::Int64,
+            extension: None,
+            format: 0,
+            original_type: None,
+        };
+
+        let unnested_fields =
+            trace_projection_origin(&query_ast, &[field("city"), field("count")]).unwrap();
+
+        assert_eq!(
+            unnested_fields,
+            vec![
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: String::from("contacts"),
+                    column: String::from("city"),
+                }),
+                ProjectedOrigin::Aggregate(Aggregate {
+                    function: String::from("count"),
+                    tables: vec![String::from("contacts")],
+                })
+            ]
+        )
+    }
+
     #[test]
     fn test_value() {
         let dialect = PostgreSqlDialect {};
@@ -422,6 +688,9 @@ mod tests {
                 table_oid: 0,
                 column_number: 0,
                 data_type: arrow::datatypes::DataType::Utf8,
+                extension: None,
+                format: 0,
+                original_type: None,
             }],
         )
         .unwrap();
@@ -445,30 +714,45 @@ mod tests {
                     table_oid: 1,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
                 Field {
                     name: "name".to_string(),
                     table_oid: 1,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
                 Field {
                     name: "id".to_string(),
                     table_oid: 2,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
                 Field {
                     name: "author".to_string(),
                     table_oid: 2,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
                 Field {
                     name: "text".to_string(),
                     table_oid: 2,
                     column_number: 0,
                     data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
                 },
             ],
         )
@@ -501,6 +785,256 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_unqualified_fields_with_join() {
+        let dialect = PostgreSqlDialect {};
+        let query_ast = Parser::parse_sql(
+            &dialect,
+            "SELECT u.id, name, title FROM users u JOIN posts p ON p.author = u.id",
+        )
+        .unwrap()
+        .pop()
+        .unwrap();
+
+        let unnested_fields = trace_projection_origin(
+            &query_ast,
+            &[
+                Field {
+                    name: "id".to_string(),
+                    table_oid: 1,
+                    column_number: 1,
+                    data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
+                },
+                Field {
+                    name: "name".to_string(),
+                    table_oid: 1,
+                    column_number: 2,
+                    data_type: arrow::datatypes::DataType::Utf8,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
+                },
+                Field {
+                    name: "title".to_string(),
+                    table_oid: 2,
+                    column_number: 2,
+                    data_type: arrow::datatypes::DataType::Utf8,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
+                },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            unnested_fields,
+            vec![
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: String::from("users"),
+                    column: String::from("id"),
+                }),
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: String::from("users"),
+                    column: String::from("name"),
+                }),
+                ProjectedOrigin::AmbiguousTableColumn(vec![
+                    TableColumn {
+                        table: String::from("users"),
+                        column: String::from("title"),
+                    },
+                    TableColumn {
+                        table: String::from("posts"),
+                        column: String::from("title"),
+                    }
+                ]),
+            ]
+        )
+    }
+
+    #[test]
+    fn test_wildcard_with_join() {
+        let dialect = PostgreSqlDialect {};
+        let query_ast = Parser::parse_sql(
+            &dialect,
+            "SELECT p.*, u.name FROM posts p JOIN users u ON p.author = u.id",
+        )
+        .unwrap()
+        .pop()
+        .unwrap();
+
+        let unnested_fields = trace_projection_origin(
+            &query_ast,
+            &[
+                Field {
+                    name: "id".to_string(),
+                    table_oid: 2,
+                    column_number: 1,
+                    data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
+                },
+                Field {
+                    name: "author".to_string(),
+                    table_oid: 2,
+                    column_number: 2,
+                    data_type: arrow::datatypes::DataType::Int64,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
+                },
+                Field {
+                    name: "name".to_string(),
+                    table_oid: 1,
+                    column_number: 2,
+                    data_type: arrow::datatypes::DataType::Utf8,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
+                },
+            ],
+        )
+        .unwrap();
+
+        assert_eq!(
+            unnested_fields,
+            vec![
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: String::from("posts"),
+                    column: String::from("id"),
+                }),
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: String::from("posts"),
+                    column: String::from("author"),
+                }),
+                ProjectedOrigin::TableColumn(TableColumn {
+                    table: String::from("users"),
+                    column: String::from("name"),
+                }),
+            ]
+        )
+    }
+
+    fn cataloged_relation(
+        oid: u32,
+        name: &str,
+        kind: RelationKind,
+        columns: &[&str],
+        definition: Option<&str>,
+    ) -> proboscis_core::catalog::CatalogRelation {
+        proboscis_core::catalog::CatalogRelation {
+            oid,
+            schema: "public".to_string(),
+            name: name.to_string(),
+            kind,
+            columns: columns
+                .iter()
+                .enumerate()
+                .map(|(index, column)| proboscis_core::catalog::CatalogColumn {
+                    name: column.to_string(),
+                    number: index as i16 + 1,
+                    type_oid: 25,
+                    type_name: "text".to_string(),
+                })
+                .collect(),
+            definition: definition.map(|definition| definition.to_string()),
+        }
+    }
+
+    #[test]
+    fn test_wildcard_with_catalog() {
+        let catalog = Catalog {
+            relations: vec![
+                cataloged_relation(1, "tags", RelationKind::Table, &[], None),
+                cataloged_relation(2, "users", RelationKind::Table, &["name"], None),
+            ],
+        };
+
+        let query_ast = Parser::parse_sql(&PostgreSqlDialect {}, "SELECT * FROM tags, users")
+            .unwrap()
+            .pop()
+            .unwrap();
+
+        let fields = [Field {
+            name: "name".to_string(),
+            table_oid: 2,
+            column_number: 1,
+            data_type: arrow::datatypes::DataType::Utf8,
+            extension: None,
+            format: 0,
+            original_type: None,
+        }];
+
+        // Without the catalog, the fields are attributed to the table without columns
+        assert_eq!(
+            Ok(vec![ProjectedOrigin::TableColumn(TableColumn {
+                table: String::from("tags"),
+                column: String::from("name"),
+            })]),
+            trace_projection_origin(&query_ast, &fields)
+        );
+
+        assert_eq!(
+            Ok(vec![ProjectedOrigin::TableColumn(TableColumn {
+                table: String::from("users"),
+                column: String::from("name"),
+            })]),
+            trace_projection_origin_with_catalog(&query_ast, &fields, Some(&catalog))
+        );
+    }
+
+    #[test]
+    fn test_expand_views() {
+        let catalog = Catalog {
+            relations: vec![
+                cataloged_relation(1, "users", RelationKind::Table, &["id", "name"], None),
+                cataloged_relation(
+                    2,
+                    "names",
+                    RelationKind::View,
+                    &["id", "full_name"],
+                    Some("SELECT users.id, users.name AS full_name FROM users"),
+                ),
+                cataloged_relation(
+                    3,
+                    "short_names",
+                    RelationKind::View,
+                    &["short_name"],
+                    Some("SELECT names.full_name AS short_name FROM names"),
+                ),
+            ],
+        };
+
+        let origin = |table: &str, column: &str| {
+            ProjectedOrigin::TableColumn(TableColumn {
+                table: table.to_string(),
+                column: column.to_string(),
+            })
+        };
+
+        assert_eq!(
+            vec![
+                origin("users", "name"),
+                origin("users", "name"),
+                origin("users", "id"),
+                ProjectedOrigin::Value,
+            ],
+            expand_views(
+                vec![
+                    origin("names", "full_name"),
+                    origin("short_names", "short_name"),
+                    origin("users", "id"),
+                    ProjectedOrigin::Value,
+                ],
+                &catalog
+            )
+        );
+    }
+
     // #[test]
     // fn test_aggregation_sum() {
     //     let dialect = PostgreSqlDialect {};
@@ -1,11 +1,44 @@
 use crate::{
+    classification::{classify_statement, StatementKind},
+    copy::parse_copy_out,
+    cursor::{parse_cursor_statement, CursorStatement},
+    denial::{find_denied_column, permission_denied},
+    explain::{describe_origin, explain_query},
     interface::Transformer,
-    projection::{trace_projection_origin, ProjectedOrigin},
+    masking::DescribeMasking,
+    parameters::parameterized_columns,
+    projection::{
+        expand_views, trace_projection_origin_with_catalog, ProjectedOrigin, TableColumn,
+    },
+    retention::{apply_retention_rules, RetentionRule},
+};
+use arrow::{
+    array::{Array, ArrayRef, StringArray},
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
 };
-use arrow::{datatypes::Schema, record_batch::RecordBatch};
 use async_trait::async_trait;
-use proboscis_core::resolver::{
-    Bind, ClientId, Close, Describe, Execute, Parse, ResolveError, Resolver, SyncResponse,
+use proboscis_core::{
+    catalog::{is_ddl, Catalog, CatalogCache},
+    data::{
+        arrow::text_rows_to_record_batch,
+        copy::{parse_copy_text, serialize_copy_header, serialize_record_batch_to_copy},
+    },
+    metrics::{record_truncation, time_stage, Stage},
+    resolver::{
+        collect_copy_out, copy_out_messages, sync_error, Bind, ClientContext, ClientId, Close,
+        CommandCompleteTag, CopyOutMessage, CopyOutStream, CopyResponse, Describe, Execute, Parse,
+        ResolveError, Resolver, SyncResponse,
+    },
+    sqlstate,
+    utils::{
+        connection::Connection,
+        fingerprint::fingerprint,
+        role::{parse_role_changes, RoleChange},
+    },
+};
+use proboscis_postgres_protocol::message::{
+    CloseKind, DescribeKind, Error, NotificationResponse, ParameterDescription, ParameterStatus,
 };
 use sqlparser::{
     ast::Statement,
@@ -13,40 +46,800 @@ use sqlparser::{
     parser::{Parser, ParserError},
 };
 use std::{
-    collections::{BTreeMap, HashMap},
+    collections::{BTreeMap, HashMap, HashSet, VecDeque},
     convert::TryFrom,
     sync::Arc,
     vec,
 };
 
+/// What happens to queries the parser can't handle, e.g. because of syntax it doesn't know,
+/// and to queries whose projected columns can't be traced to their tables
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum UnparseableQueryPolicy {
+    /// Their results are returned without being transformed. A warning with the fingerprint
+    /// of the query is logged, so the rules can be reviewed for the queries they don't cover.
+    PassthroughWithAudit,
+    /// They fail with a `feature_not_supported` error, the client can continue
+    Reject,
+}
+
+impl Default for UnparseableQueryPolicy {
+    fn default() -> Self {
+        UnparseableQueryPolicy::PassthroughWithAudit
+    }
+}
+
 pub struct TransformingResolver {
     resolver: Box<dyn Resolver>,
     transformers: Vec<Box<dyn Transformer>>,
-    skip_if_cannot_parse: bool,
-    skip_if_cannot_trace: bool,
+    // Transformers applied only to the results of clients authenticated as the user
+    user_transformers: HashMap<String, Vec<Box<dyn Transformer>>>,
+    client_users: HashMap<ClientId, String>,
+    // Transformers applied to the results of clients which switched to the role
+    role_transformers: HashMap<String, Vec<Box<dyn Transformer>>>,
+    // The roles clients switched to with `SET ROLE` or `SET SESSION AUTHORIZATION`
+    client_roles: HashMap<ClientId, String>,
+    // Role changes of prepared statements, applied once the client syncs
+    pending_role_changes: HashMap<ClientId, Vec<RoleChange>>,
+    // Maps users to the only roles their clients may switch to
+    allowed_roles: HashMap<String, Vec<String>>,
+    // Queries referencing these columns are rejected instead of having their results transformed
+    denied_columns: Vec<TableColumn>,
+    user_denied_columns: HashMap<String, Vec<TableColumn>>,
+    role_denied_columns: HashMap<String, Vec<TableColumn>>,
+    retention_rules: Vec<RetentionRule>,
+    // Maps the prepared statements of each client which were rewritten, e.g. by retention
+    // rules, to the statements the clients sent, whose projection the results have
+    retained_statements: HashMap<ClientId, HashMap<String, String>>,
+    unparseable_queries: UnparseableQueryPolicy,
+    // Queries starting with the prefix are explained instead of being run
+    explain_prefix: Option<String>,
+    describe_masking: Option<DescribeMasking>,
+    // Maps the cursors each client declared to their queries, whose projection applies to
+    // the rows fetched from them
+    cursors: HashMap<ClientId, HashMap<String, String>>,
+    catalog: Option<Arc<CatalogCache>>,
+    // Clients which parsed a DDL statement, the catalog is invalidated once they sync
+    pending_ddl: HashSet<ClientId>,
+    // The queries of the prepared statements of each client by their names
+    statements: HashMap<ClientId, HashMap<String, String>>,
+    // Queries of the statements each client described, whose parameter descriptions are pending
+    described_statements: HashMap<ClientId, VecDeque<String>>,
+    // The most rows results of clients authenticated as the user or switched to the role have
+    user_row_caps: HashMap<String, usize>,
+    role_row_caps: HashMap<String, usize>,
+    // The fewest rows results of queries comparing the column with a parameter must have
+    parameterized_min_rows: Vec<(TableColumn, usize)>,
+    // Notices for each client raised while answering its last request
+    notices: HashMap<ClientId, Vec<Error>>,
 }
 
 impl TransformingResolver {
     pub fn new(resolver: Box<dyn Resolver>) -> TransformingResolver {
         TransformingResolver {
             resolver,
-            skip_if_cannot_parse: true,
-            skip_if_cannot_trace: true,
+            unparseable_queries: UnparseableQueryPolicy::default(),
             transformers: Vec::new(),
+            user_transformers: HashMap::new(),
+            client_users: HashMap::new(),
+            role_transformers: HashMap::new(),
+            client_roles: HashMap::new(),
+            pending_role_changes: HashMap::new(),
+            allowed_roles: HashMap::new(),
+            denied_columns: Vec::new(),
+            user_denied_columns: HashMap::new(),
+            role_denied_columns: HashMap::new(),
+            retention_rules: Vec::new(),
+            retained_statements: HashMap::new(),
+            explain_prefix: None,
+            describe_masking: None,
+            cursors: HashMap::new(),
+            catalog: None,
+            pending_ddl: HashSet::new(),
+            statements: HashMap::new(),
+            described_statements: HashMap::new(),
+            user_row_caps: HashMap::new(),
+            role_row_caps: HashMap::new(),
+            parameterized_min_rows: Vec::new(),
+            notices: HashMap::new(),
         }
     }
 
+    /// Answers queries starting with the prefix, e.g. a comment like `/*explain*/`, with a single
+    /// row describing how the transformers would change each column of the query's result
+    pub fn with_explain_prefix(mut self, prefix: &str) -> TransformingResolver {
+        self.explain_prefix = Some(prefix.to_string());
+        self
+    }
+
+    /// Renames or retypes the columns the transformers change, in described schemas
+    /// as well as in the schemas of results
+    pub fn with_describe_masking(mut self, masking: DescribeMasking) -> TransformingResolver {
+        self.describe_masking = Some(masking);
+        self
+    }
+
+    /// Rejects queries which can't be parsed or traced, instead of returning their results
+    /// untransformed
+    pub fn with_unparseable_query_policy(
+        mut self,
+        policy: UnparseableQueryPolicy,
+    ) -> TransformingResolver {
+        self.unparseable_queries = policy;
+        self
+    }
+
+    /// Traces the fields of wildcards and the columns of views with the catalog of the target.
+    /// DDL statements of the clients invalidate the catalog.
+    pub fn with_catalog(mut self, catalog: Arc<CatalogCache>) -> TransformingResolver {
+        self.catalog = Some(catalog);
+        self
+    }
+
     pub fn add_transformer(mut self, transformer: Box<dyn Transformer>) -> TransformingResolver {
         self.transformers.push(transformer);
         self
     }
+
+    /// Adds a transformer which is only applied for clients authenticated as the user,
+    /// after the transformers applied for every client
+    pub fn add_user_transformer(
+        mut self,
+        user: &str,
+        transformer: Box<dyn Transformer>,
+    ) -> TransformingResolver {
+        self.user_transformers
+            .entry(user.to_string())
+            .or_default()
+            .push(transformer);
+        self
+    }
+
+    /// Adds a transformer which is applied for clients which switched to the role, after
+    /// the transformers of their user
+    pub fn add_role_transformer(
+        mut self,
+        role: &str,
+        transformer: Box<dyn Transformer>,
+    ) -> TransformingResolver {
+        self.role_transformers
+            .entry(role.to_string())
+            .or_default()
+            .push(transformer);
+        self
+    }
+
+    /// Clients authenticated as the user may only switch to the roles, e.g. so they can't
+    /// switch to a role the rules of their user don't anticipate
+    pub fn restrict_roles(mut self, user: &str, roles: &[&str]) -> TransformingResolver {
+        self.allowed_roles.insert(
+            user.to_string(),
+            roles.iter().map(|role| role.to_string()).collect(),
+        );
+        self
+    }
+
+    /// Rejects queries projecting or filtering on the column with a `permission denied` error
+    pub fn deny_column(mut self, table: &str, column: &str) -> TransformingResolver {
+        self.denied_columns.push(TableColumn {
+            table: table.to_string(),
+            column: column.to_string(),
+        });
+        self
+    }
+
+    /// Denies the column only to clients authenticated as the user
+    pub fn deny_user_column(
+        mut self,
+        user: &str,
+        table: &str,
+        column: &str,
+    ) -> TransformingResolver {
+        self.user_denied_columns
+            .entry(user.to_string())
+            .or_default()
+            .push(TableColumn {
+                table: table.to_string(),
+                column: column.to_string(),
+            });
+        self
+    }
+
+    /// Denies the column to clients which switched to the role
+    pub fn deny_role_column(
+        mut self,
+        role: &str,
+        table: &str,
+        column: &str,
+    ) -> TransformingResolver {
+        self.role_denied_columns
+            .entry(role.to_string())
+            .or_default()
+            .push(TableColumn {
+                table: table.to_string(),
+                column: column.to_string(),
+            });
+        self
+    }
+
+    /// Cuts off the results of clients authenticated as the user after the number of rows,
+    /// the client is warned with a notice that the result is incomplete
+    pub fn limit_user_rows(mut self, user: &str, max_rows: usize) -> TransformingResolver {
+        self.user_row_caps.insert(user.to_string(), max_rows);
+        self
+    }
+
+    /// Cuts off the results of clients which switched to the role after the number of rows.
+    /// The lowest cap of the user and role of a client applies.
+    pub fn limit_role_rows(mut self, role: &str, max_rows: usize) -> TransformingResolver {
+        self.role_row_caps.insert(role.to_string(), max_rows);
+        self
+    }
+
+    /// Suppresses the results of queries whose WHERE clause compares the column with a
+    /// parameter, e.g. `WHERE zip = $1`, if they have fewer rows than the minimum. The client
+    /// chooses the population such results describe when binding the parameters, so they
+    /// could single out a group smaller than the anonymization of its rows assumes.
+    pub fn require_parameterized_rows(
+        mut self,
+        table: &str,
+        column: &str,
+        min_rows: usize,
+    ) -> TransformingResolver {
+        self.parameterized_min_rows.push((
+            TableColumn {
+                table: table.to_string(),
+                column: column.to_string(),
+            },
+            min_rows,
+        ));
+        self
+    }
+
+    /// Restricts the rows every query reads from the table to those within the retention window
+    pub fn add_retention_rule(mut self, rule: RetentionRule) -> TransformingResolver {
+        self.retention_rules.push(rule);
+        self
+    }
+
+    /// The query with the retention rules applied, as it is sent to the target
+    fn retained_query(&self, query: &str) -> Result<String, ResolveError> {
+        if classify_statement(query) == StatementKind::Utility {
+            return Ok(query.to_string());
+        }
+
+        match apply_retention_rules(query, &self.retention_rules) {
+            Ok(Some(retained_query)) => Ok(retained_query),
+            Ok(None) => Ok(query.to_string()),
+            // Queries which can't be parsed are rejected if they might read a restricted table
+            Err(err)
+                if self
+                    .retention_rules
+                    .iter()
+                    .any(|rule| query.to_lowercase().contains(&rule.table.to_lowercase())) =>
+            {
+                Err(ResolveError::Other(anyhow::anyhow!(
+                    "couldn't apply the retention rules: {}",
+                    err
+                )))
+            }
+            Err(_) => Ok(query.to_string()),
+        }
+    }
+
+    /// The query the target runs, with the retention rules applied and rewritten by the
+    /// transformers of the client
+    fn rewritten_query(&self, client_id: ClientId, query: &str) -> Result<String, ResolveError> {
+        let mut rewritten = self.retained_query(query)?;
+        if classify_statement(query) == StatementKind::Utility {
+            return Ok(rewritten);
+        }
+
+        for transformer in self.client_transformers(client_id) {
+            if let Some(query) = transformer.rewrite_query(&rewritten)? {
+                rewritten = query;
+            }
+        }
+
+        Ok(rewritten)
+    }
+
+    /// Loads the catalog unless it is cached. Queries are still transformed if it can't be
+    /// loaded, without tracing their projection through the catalog.
+    async fn load_catalog(&self) {
+        if let Some(catalog) = &self.catalog {
+            if let Err(err) = catalog.get().await {
+                tracing::warn!("couldn't load the catalog: {}", err);
+            }
+        }
+    }
+
+    fn cached_catalog(&self) -> Option<Arc<Catalog>> {
+        self.catalog.as_ref().and_then(|catalog| catalog.current())
+    }
+
+    fn invalidate_catalog(&self) {
+        if let Some(catalog) = &self.catalog {
+            catalog.invalidate();
+        }
+    }
+
+    /// Rejects role changes the user of the client isn't allowed to make
+    fn check_role_changes(
+        &self,
+        client_id: ClientId,
+        changes: &[RoleChange],
+    ) -> Result<(), ResolveError> {
+        let user = match self.client_users.get(&client_id) {
+            Some(user) => user,
+            None => return Ok(()),
+        };
+
+        let allowed_roles = match self.allowed_roles.get(user) {
+            Some(allowed_roles) => allowed_roles,
+            None => return Ok(()),
+        };
+
+        for change in changes {
+            let role = match change {
+                RoleChange::Set(role) if allowed_roles.contains(role) => continue,
+                RoleChange::Reset => continue,
+                RoleChange::Set(role) => role.as_str(),
+                RoleChange::Opaque => "via set_config",
+            };
+
+            tracing::warn!(user = %user, role, "rejecting a role change");
+            return Err(ResolveError::Target(sqlstate::error_response(
+                sqlstate::ERROR,
+                sqlstate::INSUFFICIENT_PRIVILEGE,
+                format!("permission denied to set role \"{}\"", role),
+            )));
+        }
+
+        Ok(())
+    }
+
+    fn apply_role_changes(&mut self, client_id: ClientId, changes: Vec<RoleChange>) {
+        for change in changes {
+            let user = self.client_users.get(&client_id).map(String::as_str);
+
+            match change {
+                RoleChange::Set(role) => {
+                    tracing::info!(user = ?user, role = %role, "client switched its role");
+                    self.client_roles.insert(client_id, role);
+                }
+                RoleChange::Reset => {
+                    if let Some(role) = self.client_roles.remove(&client_id) {
+                        tracing::info!(user = ?user, role = %role, "client reset its role");
+                    }
+                }
+                // The role stays as it is known, rather than guessing it
+                RoleChange::Opaque => {
+                    tracing::warn!(user = ?user, "client switched its role to an unknown one")
+                }
+            }
+        }
+    }
+
+    fn row_cap(&self, client_id: ClientId) -> Option<usize> {
+        let user_cap = self
+            .client_users
+            .get(&client_id)
+            .and_then(|user| self.user_row_caps.get(user));
+
+        let role_cap = self
+            .client_roles
+            .get(&client_id)
+            .and_then(|role| self.role_row_caps.get(role));
+
+        user_cap.into_iter().chain(role_cap).min().copied()
+    }
+
+    /// The result cut off at the row cap of the client, which is warned if rows were dropped
+    fn cap_rows(
+        &mut self,
+        client_id: ClientId,
+        query: &str,
+        data: Vec<RecordBatch>,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let max_rows = match self.row_cap(client_id) {
+            Some(max_rows) => max_rows,
+            None => return Ok(data),
+        };
+
+        let rows: usize = data.iter().map(RecordBatch::num_rows).sum();
+        if rows <= max_rows {
+            return Ok(data);
+        }
+
+        let mut capped = vec![];
+        let mut remaining = max_rows;
+        for batch in data {
+            if remaining == 0 {
+                break;
+            }
+
+            let length = batch.num_rows().min(remaining);
+            remaining -= length;
+            capped.push(RecordBatch::try_new(
+                batch.schema(),
+                batch
+                    .columns()
+                    .iter()
+                    .map(|column| column.slice(0, length))
+                    .collect(),
+            )?);
+        }
+
+        tracing::warn!(
+            fingerprint = %fingerprint(query),
+            rows,
+            max_rows,
+            "truncated a result at the row cap of the client"
+        );
+        record_truncation();
+
+        self.notices
+            .entry(client_id)
+            .or_default()
+            .push(sqlstate::error_response(
+                sqlstate::WARNING_SEVERITY,
+                sqlstate::WARNING,
+                format!(
+                    "the result was truncated to {} of its {} rows",
+                    max_rows, rows
+                ),
+            ));
+
+        Ok(capped)
+    }
+
+    /// The fewest rows the result of the query must have, because of the columns its WHERE
+    /// clause compares with parameters
+    fn parameterized_min_rows(&self, query: &str) -> Option<usize> {
+        if self.parameterized_min_rows.is_empty() {
+            return None;
+        }
+
+        let columns = parameterized_columns(query);
+        self.parameterized_min_rows
+            .iter()
+            .filter(|(rule, _)| {
+                columns.iter().any(|column| {
+                    let table = column.table.rsplit('.').next().unwrap_or(&column.table);
+                    (column.table.eq_ignore_ascii_case(&rule.table)
+                        || table.eq_ignore_ascii_case(&rule.table))
+                        && column.column.eq_ignore_ascii_case(&rule.column)
+                })
+            })
+            .map(|(_, min_rows)| *min_rows)
+            .max()
+    }
+
+    /// The result without any rows if its query compares a column with a parameter and it
+    /// has fewer rows than required for the column, the client is warned with a notice
+    fn suppress_small_results(
+        &mut self,
+        client_id: ClientId,
+        query: &str,
+        data: Vec<RecordBatch>,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let min_rows = match self.parameterized_min_rows(query) {
+            Some(min_rows) => min_rows,
+            None => return Ok(data),
+        };
+
+        // Empty results don't describe any group
+        let rows: usize = data.iter().map(RecordBatch::num_rows).sum();
+        if rows == 0 || rows >= min_rows {
+            return Ok(data);
+        }
+
+        tracing::warn!(
+            fingerprint = %fingerprint(query),
+            rows,
+            min_rows,
+            "suppressed a result narrowed down by its parameters"
+        );
+
+        self.notices
+            .entry(client_id)
+            .or_default()
+            .push(sqlstate::error_response(
+                sqlstate::WARNING_SEVERITY,
+                sqlstate::WARNING,
+                format!(
+                    "the result was suppressed as it has fewer than {} rows for the parameters",
+                    min_rows
+                ),
+            ));
+
+        Ok(data
+            .first()
+            .map(|batch| vec![RecordBatch::new_empty(batch.schema())])
+            .unwrap_or_default())
+    }
+
+    fn check_denied_columns(&self, client_id: ClientId, query: &str) -> Result<(), ResolveError> {
+        let user_denied_columns = self
+            .client_users
+            .get(&client_id)
+            .and_then(|user| self.user_denied_columns.get(user));
+        let role_denied_columns = self
+            .client_roles
+            .get(&client_id)
+            .and_then(|role| self.role_denied_columns.get(role));
+
+        for denied_columns in std::iter::once(&self.denied_columns)
+            .chain(user_denied_columns)
+            .chain(role_denied_columns)
+        {
+            // Queries which can't be checked are rejected, the columns must never be returned
+            let denied_column = find_denied_column(query, denied_columns).map_err(|err| {
+                ResolveError::Other(anyhow::anyhow!(
+                    "couldn't check the query for denied columns: {:?}",
+                    err
+                ))
+            })?;
+
+            if let Some(denied_column) = denied_column {
+                return Err(permission_denied(denied_column));
+            }
+        }
+
+        Ok(())
+    }
+
+    fn client_transformers(&self, client_id: ClientId) -> impl Iterator<Item = &dyn Transformer> {
+        let user_transformers = self
+            .client_users
+            .get(&client_id)
+            .and_then(|user| self.user_transformers.get(user));
+
+        let role_transformers = self
+            .client_roles
+            .get(&client_id)
+            .and_then(|role| self.role_transformers.get(role));
+
+        self.transformers
+            .iter()
+            .chain(user_transformers.into_iter().flatten())
+            .chain(role_transformers.into_iter().flatten())
+            .map(|transformer| transformer.as_ref())
+    }
+
+    /// Masks the described columns the transformers of the client change, if masking is enabled.
+    /// The schema is the one of the untransformed result, the transformed one has to keep the
+    /// names of its columns.
+    fn mask_schema(
+        &self,
+        client_id: ClientId,
+        schema: &Schema,
+        transformed_schema: Schema,
+        origins: &[ProjectedOrigin],
+    ) -> Result<Schema, ResolveError> {
+        let masking = match &self.describe_masking {
+            Some(masking) => masking,
+            None => return Ok(transformed_schema),
+        };
+
+        let mut transformed = vec![false; schema.fields().len()];
+        for transformer in self.client_transformers(client_id) {
+            let descriptions = transformer.explain_schema(schema, origins)?;
+
+            for (is_transformed, description) in transformed.iter_mut().zip(descriptions) {
+                *is_transformed |= description.is_some();
+            }
+        }
+
+        let transformed_names: Vec<String> = schema
+            .fields()
+            .iter()
+            .zip(transformed)
+            .filter(|(_, is_transformed)| *is_transformed)
+            .map(|(field, _)| field.name().clone())
+            .collect();
+
+        Ok(masking.mask_schema(&transformed_schema, &transformed_names))
+    }
 }
 
 impl TransformingResolver {
+    async fn explain(
+        &mut self,
+        client_id: ClientId,
+        query: &str,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        // The target only describes the result, no rows are read
+        let described = self
+            .resolver
+            .query(
+                client_id,
+                format!("SELECT * FROM ({}) AS explained LIMIT 0", query),
+            )
+            .await?;
+
+        let schema = described
+            .first()
+            .ok_or_else(|| {
+                ResolveError::Other(anyhow::anyhow!("the target didn't describe the result"))
+            })?
+            .schema();
+
+        let transformers: Vec<&dyn Transformer> = self.client_transformers(client_id).collect();
+        let descriptions: Vec<String> = match explain_query(query, &schema, &transformers) {
+            Ok(explanations) => explanations
+                .iter()
+                .map(|explanation| {
+                    format!(
+                        "from {}: {}",
+                        describe_origin(&explanation.origin),
+                        explanation.describe_transformations()
+                    )
+                })
+                .collect(),
+            // Results of queries that can't be traced are returned without any changes
+            Err(err) => vec![format!("unchanged, {}", err); schema.fields().len()],
+        };
+
+        let mut fields = vec![];
+        let mut columns: Vec<ArrayRef> = vec![];
+        for (field, description) in schema.fields().iter().zip(descriptions) {
+            fields.push(
+                (&proboscis_core::data::field::Field {
+                    name: field.name().clone(),
+                    table_oid: 0,
+                    column_number: 0,
+                    data_type: DataType::Utf8,
+                    extension: None,
+                    format: 0,
+                    original_type: None,
+                })
+                    .into(),
+            );
+            columns.push(Arc::new(StringArray::from(vec![description])));
+        }
+
+        Ok(vec![RecordBatch::try_new(
+            Arc::new(Schema::new(fields)),
+            columns,
+        )?])
+    }
+
+    /// The rows copied by a `COPY` of the query, which are read from the target in the text
+    /// format and transformed like the result of the query
+    async fn copied_rows(
+        &mut self,
+        client_id: ClientId,
+        query: &str,
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        self.check_denied_columns(client_id, query)?;
+        self.check_parseable(query)?;
+        self.load_catalog().await;
+
+        let retained_query = self.rewritten_query(client_id, query)?;
+
+        // The copied values don't carry their types, the result of the query without rows does
+        let described = self
+            .resolver
+            .query(
+                client_id,
+                format!("SELECT * FROM ({}) AS copied LIMIT 0", retained_query),
+            )
+            .await?;
+        let schema = described
+            .first()
+            .map(RecordBatch::schema)
+            .ok_or("the columns of the copy couldn't be described")?;
+
+        let (_, messages) = self
+            .resolver
+            .copy_out(client_id, format!("COPY ({}) TO STDOUT", retained_query))
+            .await?;
+        let mut data = vec![];
+        for message in collect_copy_out(messages).await? {
+            if let CopyOutMessage::Data(chunk) = message {
+                data.extend(chunk);
+            }
+        }
+
+        let rows = parse_copy_text(&data);
+        if rows.iter().any(|row| row.len() != schema.fields().len()) {
+            return Err("the copied rows don't match the columns of the copy".into());
+        }
+        let records = vec![text_rows_to_record_batch(schema, rows)?];
+
+        let transformed = self.transform_records(client_id, query, &records)?;
+        let transformed = self.suppress_small_results(client_id, query, transformed)?;
+        self.cap_rows(client_id, query, transformed)
+    }
+
+    fn track_cursor(&mut self, client_id: ClientId, query: &str) {
+        match parse_cursor_statement(query) {
+            Some(CursorStatement::Declare { name, query }) => {
+                self.cursors
+                    .entry(client_id)
+                    .or_default()
+                    .insert(name, query);
+            }
+            Some(CursorStatement::Close { name: Some(name) }) => {
+                if let Some(cursors) = self.cursors.get_mut(&client_id) {
+                    cursors.remove(&name);
+                }
+            }
+            Some(CursorStatement::Close { name: None }) => {
+                self.cursors.remove(&client_id);
+            }
+            _ => {}
+        }
+    }
+
+    /// The query whose projection the result has, for fetches the query of their cursor
+    fn projected_query(&self, client_id: ClientId, query: &str) -> String {
+        if let Some(original) = self
+            .retained_statements
+            .get(&client_id)
+            .and_then(|statements| statements.get(query))
+        {
+            return original.clone();
+        }
+
+        match parse_cursor_statement(query) {
+            Some(CursorStatement::Fetch { name }) => self
+                .cursors
+                .get(&client_id)
+                .and_then(|cursors| cursors.get(&name))
+                .cloned()
+                .unwrap_or_else(|| query.to_string()),
+            _ => query.to_string(),
+        }
+    }
+
     fn parse_sql(&self, query: &str) -> Result<Vec<Statement>, ParserError> {
         let dialect = PostgreSqlDialect {};
         Parser::parse_sql(&dialect, query)
     }
+
+    /// Fails queries which won't be transformed because the parser can't handle them
+    /// before they are run, if such queries are rejected
+    fn check_parseable(&self, query: &str) -> Result<(), ResolveError> {
+        if self.unparseable_queries == UnparseableQueryPolicy::PassthroughWithAudit
+            || classify_statement(query) == StatementKind::Utility
+        {
+            return Ok(());
+        }
+
+        match self.parse_sql(query) {
+            Ok(statements) if !statements.is_empty() => Ok(()),
+            Ok(_) => self.unparseable(query, "it contains no statement"),
+            Err(err) => self.unparseable(query, &format!("couldn't parse the query: {}", err)),
+        }
+    }
+
+    /// Applies the policy to a query which isn't transformed for the reason
+    fn unparseable<T: Default>(&self, query: &str, reason: &str) -> Result<T, ResolveError> {
+        match self.unparseable_queries {
+            UnparseableQueryPolicy::PassthroughWithAudit => {
+                tracing::warn!(
+                    query.fingerprint = %fingerprint(query),
+                    reason,
+                    "returning the result of the query untransformed"
+                );
+                Ok(T::default())
+            }
+            UnparseableQueryPolicy::Reject => {
+                tracing::warn!(
+                    query.fingerprint = %fingerprint(query),
+                    reason,
+                    "rejecting the query, its result can't be transformed"
+                );
+                Err(ResolveError::Unsupported(format!(
+                    "the proxy can't transform the result of the query, {}",
+                    reason
+                )))
+            }
+        }
+    }
 }
 
 fn re_apply_metadata(original_schema: &Schema, new_schema: &Schema) -> Result<Schema, String> {
@@ -90,107 +883,331 @@ fn re_apply_metadata(original_schema: &Schema, new_schema: &Schema) -> Result<Sc
 }
 
 impl TransformingResolver {
-    fn with_traced_projection<T: Clone, F: Fn(Vec<ProjectedOrigin>) -> Result<T, ResolveError>>(
+    /// The origins of the columns of the query, none if the transformation is skipped
+    fn trace_origins(
         &self,
         query: &str,
         schema: &Schema,
-        fallback: &T,
-        transformation: F,
-    ) -> Result<T, ResolveError> {
+    ) -> Result<Option<Vec<ProjectedOrigin>>, ResolveError> {
         let query_ast: Vec<Statement> = match self.parse_sql(query) {
             Ok(ast) => ast,
             Err(err) => {
-                return if self.skip_if_cannot_parse {
-                    tracing::warn!("Could not parse query, skipping transformation");
-                    Ok(fallback.clone())
-                } else {
-                    return Err(ResolveError::Other(anyhow::anyhow!(err)));
-                }
+                return self.unparseable(query, &format!("couldn't parse the query: {}", err))
             }
         };
 
+        let statement = match query_ast.first() {
+            Some(statement) => statement,
+            None => return self.unparseable(query, "it contains no statement"),
+        };
+
         let mut fields = vec![];
         for f in schema.fields().iter() {
             let field = proboscis_core::data::field::Field::try_from(f)?;
             fields.push(field);
         }
 
-        let origins = match trace_projection_origin(query_ast.first().unwrap(), &fields) {
-            Ok(ast) => ast,
-            Err(err) => {
-                return if self.skip_if_cannot_trace {
-                    tracing::warn!(
-                        "Could not trace origin of projected columns, skipping transformation"
-                    );
-                    Ok(fallback.clone())
-                } else {
-                    return Err(ResolveError::Other(anyhow::anyhow!(err)));
-                }
-            }
-        };
+        let catalog = self.cached_catalog();
+        match trace_projection_origin_with_catalog(statement, &fields, catalog.as_deref()) {
+            Ok(origins) => Ok(Some(match &catalog {
+                Some(catalog) => expand_views(origins, catalog),
+                None => origins,
+            })),
+            Err(err) => self.unparseable(
+                query,
+                &format!("couldn't trace the projected columns: {}", err),
+            ),
+        }
+    }
 
-        transformation(origins)
+    fn with_traced_projection<T: Clone, F: Fn(Vec<ProjectedOrigin>) -> Result<T, ResolveError>>(
+        &self,
+        query: &str,
+        schema: &Schema,
+        fallback: &T,
+        transformation: F,
+    ) -> Result<T, ResolveError> {
+        // Results of utility statements don't contain rows of tables, so they aren't parsed
+        if classify_statement(query) == StatementKind::Utility {
+            return Ok(fallback.clone());
+        }
+
+        match time_stage(Stage::Parse, || self.trace_origins(query, schema))? {
+            Some(origins) => transformation(origins),
+            None => Ok(fallback.clone()),
+        }
     }
 
     fn transform_records(
         &self,
+        client_id: ClientId,
         query: &str,
-        data: &RecordBatch,
-    ) -> Result<RecordBatch, ResolveError> {
-        self.with_traced_projection(query, &data.schema(), data, |origins| {
-            let mut transformed = data.clone();
+        data: &[RecordBatch],
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let schema = match data.first() {
+            Some(batch) => batch.schema(),
+            None => return Ok(vec![]),
+        };
 
-            for transformer in &self.transformers {
-                transformed = transformer.transform_records(&transformed, &origins)?;
-            }
+        let fallback = data.to_vec();
+        self.with_traced_projection(query, &schema, &fallback, |origins| {
+            let transformed = time_stage(Stage::Anonymization, || {
+                let mut transformed = data.to_vec();
+
+                for transformer in self.client_transformers(client_id) {
+                    transformed = transformer.transform_batches(&transformed, &origins)?;
+                }
+
+                Ok::<_, ResolveError>(transformed)
+            })?;
 
-            let transformed_schema_with_metadata =
-                re_apply_metadata(&data.schema(), &transformed.schema())
+            let mut transformed_with_metadata = vec![];
+            for batch in transformed {
+                let transformed_schema_with_metadata = re_apply_metadata(&schema, &batch.schema())
                     .map_err(|err| ResolveError::Other(anyhow::anyhow!(err)))?;
+                let transformed_schema_with_metadata = self.mask_schema(
+                    client_id,
+                    &schema,
+                    transformed_schema_with_metadata,
+                    &origins,
+                )?;
 
-            let transformed_with_metadata = RecordBatch::try_new(
-                Arc::new(transformed_schema_with_metadata),
-                transformed.columns().to_vec(),
-            )?;
+                transformed_with_metadata.push(RecordBatch::try_new(
+                    Arc::new(transformed_schema_with_metadata),
+                    batch.columns().to_vec(),
+                )?);
+            }
 
             Ok(transformed_with_metadata)
         })
     }
 
-    fn transform_schema(&self, query: &str, schema: &Schema) -> Result<Schema, ResolveError> {
+    fn transform_schema(
+        &self,
+        client_id: ClientId,
+        query: &str,
+        schema: &Schema,
+    ) -> Result<Schema, ResolveError> {
         self.with_traced_projection(query, schema, schema, |origins| {
-            let mut transformed = schema.clone();
+            let transformed = time_stage(Stage::TransformSchema, || {
+                let mut transformed = schema.clone();
 
-            for transformer in &self.transformers {
-                transformed = transformer.transform_schema(&transformed, &origins)?;
-            }
+                for transformer in self.client_transformers(client_id) {
+                    transformed = transformer.transform_schema(&transformed, &origins)?;
+                }
+
+                Ok::<_, ResolveError>(transformed)
+            })?;
 
             let transformed_with_metadata = re_apply_metadata(schema, &transformed)
                 .map_err(|err| ResolveError::Other(anyhow::anyhow!(err)))?;
 
-            Ok(transformed_with_metadata)
+            self.mask_schema(client_id, schema, transformed_with_metadata, &origins)
         })
     }
+
+    /// The parameter types of the statement as the transformers of the client accept them
+    fn transform_parameter_types(
+        &self,
+        client_id: ClientId,
+        query: &str,
+        types: Vec<u32>,
+    ) -> Result<Vec<u32>, ResolveError> {
+        let mut transformed = types;
+
+        for transformer in self.client_transformers(client_id) {
+            let parameter_types = transformer.transform_parameter_types(query, &transformed)?;
+
+            if parameter_types.len() != transformed.len() {
+                return Err(ResolveError::Transform(anyhow::anyhow!(
+                    "a transformer changed the number of parameters from {} to {}",
+                    transformed.len(),
+                    parameter_types.len()
+                )));
+            }
+
+            transformed = parameter_types;
+        }
+
+        Ok(transformed)
+    }
+
+    /// Transforms the parameter descriptions, schemas and records among the responses of a
+    /// sync or flush
+    fn transform_responses(
+        &mut self,
+        client_id: ClientId,
+        responses: Vec<SyncResponse>,
+    ) -> Result<Vec<SyncResponse>, ResolveError> {
+        let mut transformed_responses = vec![];
+
+        for response in responses {
+            let transformed_response = match response {
+                // Parameter descriptions answer the described statements in their order
+                SyncResponse::ParameterDescription(ParameterDescription { types }) => {
+                    let query = self
+                        .described_statements
+                        .get_mut(&client_id)
+                        .and_then(VecDeque::pop_front);
+
+                    let types = match query {
+                        Some(query) => self.transform_parameter_types(client_id, &query, types)?,
+                        None => types,
+                    };
+
+                    SyncResponse::ParameterDescription(ParameterDescription { types })
+                }
+                SyncResponse::Schema { schema, query } => {
+                    let projected_query = self.projected_query(client_id, &query);
+                    let transformed_schema =
+                        self.transform_schema(client_id, &projected_query, &schema)?;
+
+                    SyncResponse::Schema {
+                        schema: transformed_schema,
+                        query,
+                    }
+                }
+                SyncResponse::Records { data, query } => {
+                    let projected_query = self.projected_query(client_id, &query);
+                    let transformed_data =
+                        self.transform_records(client_id, &projected_query, &data)?;
+                    let transformed_data =
+                        self.suppress_small_results(client_id, &projected_query, transformed_data)?;
+                    let transformed_data = self.cap_rows(client_id, &query, transformed_data)?;
+
+                    SyncResponse::Records {
+                        data: transformed_data,
+                        query,
+                    }
+                }
+                _ => response,
+            };
+
+            transformed_responses.push(transformed_response)
+        }
+
+        Ok(transformed_responses)
+    }
 }
 
 #[async_trait]
 impl Resolver for TransformingResolver {
-    async fn initialize(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
-        self.resolver.initialize(client_id).await
+    async fn authenticate(
+        &mut self,
+        client_id: ClientId,
+        frontend: &mut Connection,
+    ) -> Result<(), ResolveError> {
+        self.resolver.authenticate(client_id, frontend).await
+    }
+
+    async fn initialize(
+        &mut self,
+        client_id: ClientId,
+        context: &ClientContext,
+    ) -> Result<(), ResolveError> {
+        if let Some(user) = context.user() {
+            self.client_users.insert(client_id, user.to_string());
+        }
+
+        self.resolver.initialize(client_id, context).await
     }
 
     async fn query(
         &mut self,
         client_id: ClientId,
         query: String,
-    ) -> Result<arrow::record_batch::RecordBatch, ResolveError> {
-        let records = self.resolver.query(client_id, query.clone()).await?;
-        let transformed = self.transform_records(&query, &records)?;
-        Ok(transformed)
+    ) -> Result<Vec<RecordBatch>, ResolveError> {
+        let explained_query = self.explain_prefix.as_deref().and_then(|prefix| {
+            query
+                .trim_start()
+                .strip_prefix(prefix)
+                .map(|explained_query| explained_query.trim().trim_end_matches(';').to_string())
+        });
+
+        if let Some(explained_query) = explained_query {
+            self.check_denied_columns(client_id, &explained_query)?;
+            return self.explain(client_id, &explained_query).await;
+        }
+
+        self.check_denied_columns(client_id, &query)?;
+
+        let role_changes = parse_role_changes(&query);
+        self.check_role_changes(client_id, &role_changes)?;
+
+        // Utility statements skip the transformation, their denied columns are still checked,
+        // e.g. those of `CREATE TABLE copy AS SELECT ...`
+        if classify_statement(&query) == StatementKind::Utility {
+            let ddl = is_ddl(&query);
+            let result = self.resolver.query(client_id, query).await;
+
+            // Failed statements might have changed the schema as part of a batch
+            if ddl {
+                self.invalidate_catalog();
+            }
+
+            if result.is_ok() {
+                self.apply_role_changes(client_id, role_changes);
+            }
+
+            return result;
+        }
+
+        self.check_parseable(&query)?;
+        self.load_catalog().await;
+
+        let retained_query = self.rewritten_query(client_id, &query)?;
+        let records = self.resolver.query(client_id, retained_query).await?;
+        self.track_cursor(client_id, &query);
+        self.apply_role_changes(client_id, role_changes);
+
+        let projected_query = self.projected_query(client_id, &query);
+        let transformed = self.transform_records(client_id, &projected_query, &records)?;
+        let transformed = self.suppress_small_results(client_id, &projected_query, transformed)?;
+        self.cap_rows(client_id, &query, transformed)
     }
 
     async fn parse(&mut self, client_id: ClientId, parse: Parse) -> Result<(), ResolveError> {
-        self.resolver.parse(client_id, parse).await
+        self.check_denied_columns(client_id, &parse.query)?;
+        self.check_parseable(&parse.query)?;
+
+        let role_changes = parse_role_changes(&parse.query);
+        self.check_role_changes(client_id, &role_changes)?;
+        if !role_changes.is_empty() {
+            self.pending_role_changes
+                .entry(client_id)
+                .or_default()
+                .extend(role_changes);
+        }
+
+        // Cursors declared by prepared statements are tracked once they are parsed
+        self.track_cursor(client_id, &parse.query);
+
+        if is_ddl(&parse.query) {
+            self.pending_ddl.insert(client_id);
+        }
+
+        self.statements
+            .entry(client_id)
+            .or_default()
+            .insert(parse.statement_name.clone(), parse.query.clone());
+
+        let retained_query = self.rewritten_query(client_id, &parse.query)?;
+        if retained_query != parse.query {
+            self.retained_statements
+                .entry(client_id)
+                .or_default()
+                .insert(retained_query.clone(), parse.query.clone());
+        }
+
+        self.resolver
+            .parse(
+                client_id,
+                Parse {
+                    query: retained_query,
+                    ..parse
+                },
+            )
+            .await
     }
 
     async fn describe(
@@ -198,6 +1215,19 @@ impl Resolver for TransformingResolver {
         client_id: ClientId,
         describe: Describe,
     ) -> Result<(), ResolveError> {
+        if describe.kind == DescribeKind::Statement {
+            if let Some(query) = self
+                .statements
+                .get(&client_id)
+                .and_then(|statements| statements.get(&describe.name))
+            {
+                self.described_statements
+                    .entry(client_id)
+                    .or_default()
+                    .push_back(query.clone());
+            }
+        }
+
         self.resolver.describe(client_id, describe).await
     }
 
@@ -210,41 +1240,146 @@ impl Resolver for TransformingResolver {
     }
 
     async fn sync(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
-        let responses = self.resolver.sync(client_id).await?;
+        let responses = self.resolver.sync(client_id).await;
 
-        let mut transformed_responses = vec![];
-        for response in responses {
-            let transformed_response = match response {
-                SyncResponse::Schema { schema, query } => {
-                    let transformed_schema = self.transform_schema(&query, &schema)?;
+        if self.pending_ddl.remove(&client_id) {
+            self.invalidate_catalog();
+        }
 
-                    SyncResponse::Schema {
-                        schema: transformed_schema,
-                        query,
-                    }
-                }
-                SyncResponse::Records { data, query } => {
-                    let transformed_data = self.transform_records(&query, &data)?;
+        if let Some(role_changes) = self.pending_role_changes.remove(&client_id) {
+            // The statements after an error were skipped
+            if matches!(&responses, Ok(responses) if sync_error(responses).is_none()) {
+                self.apply_role_changes(client_id, role_changes);
+            }
+        }
 
-                    SyncResponse::Records {
-                        data: transformed_data,
-                        query,
-                    }
-                }
-                _ => response,
-            };
+        self.load_catalog().await;
+        let transformed =
+            responses.and_then(|responses| self.transform_responses(client_id, responses));
 
-            transformed_responses.push(transformed_response)
-        }
+        // Statements whose description failed don't have a parameter description
+        self.described_statements.remove(&client_id);
 
-        Ok(transformed_responses)
+        transformed
+    }
+
+    async fn flush(&mut self, client_id: ClientId) -> Result<Vec<SyncResponse>, ResolveError> {
+        let responses = self.resolver.flush(client_id).await?;
+        self.load_catalog().await;
+        self.transform_responses(client_id, responses)
     }
 
     async fn close(&mut self, client_id: ClientId, close: Close) -> Result<(), ResolveError> {
+        if close.kind == CloseKind::Statement {
+            if let Some(statements) = self.statements.get_mut(&client_id) {
+                statements.remove(&close.name);
+            }
+        }
+
         self.resolver.close(client_id, close).await
     }
 
+    async fn copy_in(
+        &mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<CopyResponse, ResolveError> {
+        self.resolver.copy_in(client_id, query).await
+    }
+
+    async fn copy_data(&mut self, client_id: ClientId, data: Vec<u8>) -> Result<(), ResolveError> {
+        self.resolver.copy_data(client_id, data).await
+    }
+
+    async fn copy_done(&mut self, client_id: ClientId) -> Result<CommandCompleteTag, ResolveError> {
+        self.resolver.copy_done(client_id).await
+    }
+
+    async fn copy_fail(
+        &mut self,
+        client_id: ClientId,
+        message: String,
+    ) -> Result<(), ResolveError> {
+        self.resolver.copy_fail(client_id, message).await
+    }
+
+    // The rows are copied in the client's format once they are transformed, the target's
+    // copy isn't streamed through
+    async fn copy_out<'a>(
+        &'a mut self,
+        client_id: ClientId,
+        query: String,
+    ) -> Result<(CopyResponse, CopyOutStream<'a>), ResolveError> {
+        let copy = parse_copy_out(&query).map_err(|reason| {
+            ResolveError::Unsupported(format!("the proxy can't transform the copy, {}", reason))
+        })?;
+        let data = self.copied_rows(client_id, &copy.query).await?;
+
+        let schema = match data.first() {
+            Some(batch) => batch.schema(),
+            None => return Err("the copy has no columns".into()),
+        };
+
+        let mut messages = vec![];
+        if copy.options.header {
+            messages.push(CopyOutMessage::Data(serialize_copy_header(
+                &schema,
+                &copy.options,
+            )));
+        }
+
+        let mut rows = 0;
+        for batch in &data {
+            rows += batch.num_rows();
+            for line in serialize_record_batch_to_copy(batch, &copy.options)? {
+                messages.push(CopyOutMessage::Data(line));
+            }
+        }
+        messages.push(CopyOutMessage::Complete(CommandCompleteTag(format!(
+            "COPY {}",
+            rows
+        ))));
+
+        let response = CopyResponse {
+            format: 0,
+            column_formats: vec![0; schema.fields().len()],
+        };
+
+        Ok((response, copy_out_messages(messages)))
+    }
+
+    fn take_notices(&mut self, client_id: ClientId) -> Vec<Error> {
+        let mut notices = self.notices.remove(&client_id).unwrap_or_default();
+        notices.extend(self.resolver.take_notices(client_id));
+        notices
+    }
+
+    fn take_parameter_changes(&mut self, client_id: ClientId) -> Vec<ParameterStatus> {
+        self.resolver.take_parameter_changes(client_id)
+    }
+
+    fn take_notifications(&mut self, client_id: ClientId) -> Vec<NotificationResponse> {
+        self.resolver.take_notifications(client_id)
+    }
+
+    async fn wait_for_idle_message(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.wait_for_idle_message(client_id).await
+    }
+
+    async fn read_idle_messages(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.resolver.read_idle_messages(client_id).await
+    }
+
     async fn terminate(&mut self, client_id: ClientId) -> Result<(), ResolveError> {
+        self.client_users.remove(&client_id);
+        self.cursors.remove(&client_id);
+        self.retained_statements.remove(&client_id);
+        self.pending_ddl.remove(&client_id);
+        self.statements.remove(&client_id);
+        self.described_statements.remove(&client_id);
+        self.client_roles.remove(&client_id);
+        self.pending_role_changes.remove(&client_id);
+        self.notices.remove(&client_id);
         self.resolver.terminate(client_id).await
     }
 }
@@ -0,0 +1,185 @@
+use sqlparser::{
+    ast::{Ident, Query, SetExpr, Statement, TableAlias, TableFactor, TableWithJoins},
+    dialect::PostgreSqlDialect,
+    parser::{Parser, ParserError},
+};
+
+/// Restricts the rows of a table to those whose timestamp column is within the window
+#[derive(Clone, Debug, PartialEq)]
+pub struct RetentionRule {
+    pub table: String,
+    pub column: String,
+    /// A postgres interval, like `2 years`
+    pub window: String,
+}
+
+impl RetentionRule {
+    fn matches(&self, name: &str) -> bool {
+        name == self.table || name.rsplit('.').next() == Some(self.table.as_str())
+    }
+
+    /// The rows of the table within the window, which replace the table in queries
+    fn retained_rows(&self) -> Result<Query, ParserError> {
+        let query = format!(
+            "SELECT * FROM {} WHERE {} > now() - interval '{}'",
+            self.table,
+            self.column,
+            self.window.replace('\'', "''")
+        );
+
+        match Parser::parse_sql(&PostgreSqlDialect {}, &query)?.pop() {
+            Some(Statement::Query(query)) => Ok(*query),
+            _ => Err(ParserError::ParserError(format!(
+                "invalid retention rule for {}",
+                self.table
+            ))),
+        }
+    }
+}
+
+fn apply_to_table_factor(
+    factor: &mut TableFactor,
+    rules: &[RetentionRule],
+) -> Result<bool, ParserError> {
+    match factor {
+        TableFactor::Table {
+            name,
+            alias,
+            args: _,
+            with_hints: _,
+        } => {
+            let name = name.to_string();
+            let rule = match rules.iter().find(|rule| rule.matches(&name)) {
+                Some(rule) => rule,
+                None => return Ok(false),
+            };
+
+            // Columns qualified with the table keep referring to it
+            let alias = alias.clone().unwrap_or_else(|| TableAlias {
+                name: Ident::new(name.rsplit('.').next().unwrap_or(&name)),
+                columns: vec![],
+            });
+
+            *factor = TableFactor::Derived {
+                lateral: false,
+                subquery: Box::new(rule.retained_rows()?),
+                alias: Some(alias),
+            };
+            Ok(true)
+        }
+        TableFactor::Derived { subquery, .. } => apply_to_query(subquery, rules),
+        TableFactor::NestedJoin(table) => apply_to_table(table, rules),
+        _ => Ok(false),
+    }
+}
+
+fn apply_to_table(
+    table: &mut TableWithJoins,
+    rules: &[RetentionRule],
+) -> Result<bool, ParserError> {
+    let mut applied = apply_to_table_factor(&mut table.relation, rules)?;
+
+    for join in &mut table.joins {
+        applied |= apply_to_table_factor(&mut join.relation, rules)?;
+    }
+
+    Ok(applied)
+}
+
+fn apply_to_set_expr(body: &mut SetExpr, rules: &[RetentionRule]) -> Result<bool, ParserError> {
+    match body {
+        SetExpr::Select(select) => {
+            let mut applied = false;
+            for table in &mut select.from {
+                applied |= apply_to_table(table, rules)?;
+            }
+            Ok(applied)
+        }
+        SetExpr::Query(query) => apply_to_query(query, rules),
+        SetExpr::SetOperation { left, right, .. } => {
+            Ok(apply_to_set_expr(left, rules)? | apply_to_set_expr(right, rules)?)
+        }
+        _ => Ok(false),
+    }
+}
+
+fn apply_to_query(query: &mut Query, rules: &[RetentionRule]) -> Result<bool, ParserError> {
+    let mut applied = false;
+
+    if let Some(with) = &mut query.with {
+        for cte in &mut with.cte_tables {
+            applied |= apply_to_query(&mut cte.query, rules)?;
+        }
+    }
+
+    Ok(apply_to_set_expr(&mut query.body, rules)? | applied)
+}
+
+/// Replaces the tables with retention rules in the FROM clauses of the query by their
+/// retained rows, none if the query doesn't read any of them. Tables of subqueries in
+/// expressions, like `WHERE id IN (SELECT ...)`, aren't restricted.
+pub fn apply_retention_rules(
+    query: &str,
+    rules: &[RetentionRule],
+) -> Result<Option<String>, ParserError> {
+    if rules.is_empty() {
+        return Ok(None);
+    }
+
+    let mut statements = Parser::parse_sql(&PostgreSqlDialect {}, query)?;
+
+    let mut applied = false;
+    for statement in &mut statements {
+        if let Statement::Query(query) = statement {
+            applied |= apply_to_query(query, rules)?;
+        }
+    }
+
+    if !applied {
+        return Ok(None);
+    }
+
+    Ok(Some(
+        statements
+            .iter()
+            .map(|statement| statement.to_string())
+            .collect::<Vec<String>>()
+            .join("; "),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply_retention_rules() {
+        let rules = vec![RetentionRule {
+            table: "events".to_string(),
+            column: "created_at".to_string(),
+            window: "2 years".to_string(),
+        }];
+
+        let rewritten = apply_retention_rules(
+            "SELECT e.id, users.name FROM events e JOIN users ON e.user_id = users.id",
+            &rules,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(rewritten.contains("(SELECT * FROM events WHERE created_at > now() - "));
+        assert!(rewritten.contains("'2 years') AS e JOIN users"));
+
+        let rewritten = apply_retention_rules(
+            "SELECT count(*) FROM (SELECT id FROM events) AS recent",
+            &rules,
+        )
+        .unwrap()
+        .unwrap();
+        assert!(rewritten.contains("'2 years') AS events) AS recent"));
+
+        assert_eq!(
+            None,
+            apply_retention_rules("SELECT id FROM users", &rules).unwrap()
+        );
+    }
+}
@@ -0,0 +1,502 @@
+use crate::resolver::TransformingResolver;
+use arrow::{
+    array::{
+        new_null_array, ArrayRef, BooleanArray, Float32Array, Float64Array, Int16Array, Int32Array,
+        Int64Array, LargeStringArray, StringArray,
+    },
+    datatypes::{DataType, Schema},
+    record_batch::RecordBatch,
+};
+use proboscis_core::resolver::{
+    ClientContext, ClientId, Describe, Parse, ResolveError, Resolver, SyncResponse,
+};
+use proboscis_postgres_protocol::message::{DescribeKind, Error};
+use proboscis_resolver_mock::{MockResolver, MockResponse};
+use std::{collections::HashMap, sync::Arc};
+
+// Rows generated for a schema without a sample
+const SAMPLE_ROWS: usize = 10;
+
+/// Values for a column of the type, which are distinct unless the type is boolean
+fn sample_column(data_type: &DataType, rows: usize) -> ArrayRef {
+    match data_type {
+        DataType::Int16 => Arc::new(Int16Array::from_iter_values((0..rows).map(|i| i as i16))),
+        DataType::Int32 => Arc::new(Int32Array::from_iter_values((0..rows).map(|i| i as i32))),
+        DataType::Int64 => Arc::new(Int64Array::from_iter_values((0..rows).map(|i| i as i64))),
+        DataType::Float32 => Arc::new(Float32Array::from_iter_values((0..rows).map(|i| i as f32))),
+        DataType::Float64 => Arc::new(Float64Array::from_iter_values((0..rows).map(|i| i as f64))),
+        DataType::Boolean => Arc::new(BooleanArray::from(
+            (0..rows).map(|i| i % 2 == 0).collect::<Vec<bool>>(),
+        )),
+        DataType::Utf8 => Arc::new(StringArray::from(
+            (0..rows)
+                .map(|i| format!("value {}", i))
+                .collect::<Vec<String>>(),
+        )),
+        DataType::LargeUtf8 => Arc::new(LargeStringArray::from(
+            (0..rows)
+                .map(|i| format!("value {}", i))
+                .collect::<Vec<String>>(),
+        )),
+        data_type => new_null_array(data_type, rows),
+    }
+}
+
+/// Rows with a distinct value in every column, columns of types without generated values are null
+pub fn sample_batch(schema: &Schema, rows: usize) -> Result<RecordBatch, ResolveError> {
+    Ok(RecordBatch::try_new(
+        Arc::new(schema.clone()),
+        schema
+            .fields()
+            .iter()
+            .map(|field| sample_column(field.data_type(), rows))
+            .collect(),
+    )?)
+}
+
+/// The result of a query as a client would receive it
+#[derive(Debug)]
+pub struct PolicyTestResult {
+    /// The schema the query is described with
+    pub schema: Schema,
+    /// The types of the parameters the query is described with
+    pub parameter_types: Vec<u32>,
+    pub data: RecordBatch,
+    /// The notices the client is sent along with the rows
+    pub notices: Vec<Error>,
+}
+
+/// Runs queries through a transforming resolver configured like in production, which is
+/// answered with sample rows instead of a database, e.g. to assert in CI that a column is
+/// always masked for some users. Every query is answered with the same rows, which should
+/// have the schema the target would return for it.
+pub struct PolicyTest {
+    sample: RecordBatch,
+    user: Option<String>,
+    statements: Vec<String>,
+}
+
+impl PolicyTest {
+    /// Answers queries with generated rows of the schema
+    pub fn new(schema: Schema) -> Result<PolicyTest, ResolveError> {
+        Ok(PolicyTest {
+            sample: sample_batch(&schema, SAMPLE_ROWS)?,
+            user: None,
+            statements: vec![],
+        })
+    }
+
+    /// Answers queries with the rows instead of generated ones
+    pub fn with_sample(mut self, sample: RecordBatch) -> PolicyTest {
+        self.sample = sample;
+        self
+    }
+
+    /// Runs queries as a client authenticated as the user
+    pub fn with_user(mut self, user: &str) -> PolicyTest {
+        self.user = Some(user.to_string());
+        self
+    }
+
+    /// Runs the statement before the query, e.g. `SET ROLE analyst`
+    pub fn with_statement(mut self, statement: &str) -> PolicyTest {
+        self.statements.push(statement.to_string());
+        self
+    }
+
+    /// Runs the query through the resolver the policy returns, which is given a resolver
+    /// without any transformers to add them to
+    pub async fn run<F>(&self, query: &str, policy: F) -> Result<PolicyTestResult, ResolveError>
+    where
+        F: FnOnce(TransformingResolver) -> TransformingResolver,
+    {
+        let mock = MockResolver::new().with_fallback(MockResponse::rows(vec![self.sample.clone()]));
+        let mut resolver = policy(TransformingResolver::new(Box::new(mock)));
+
+        let mut parameters = HashMap::new();
+        if let Some(user) = &self.user {
+            parameters.insert("user".to_string(), user.clone());
+        }
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::new(parameters))
+            .await?;
+
+        for statement in &self.statements {
+            resolver.query(client_id, statement.clone()).await?;
+        }
+
+        // The schema is transformed separately from the rows when a statement is described
+        resolver
+            .parse(
+                client_id,
+                Parse {
+                    statement_name: "".to_string(),
+                    query: query.to_string(),
+                    param_types: vec![],
+                },
+            )
+            .await?;
+        resolver
+            .describe(
+                client_id,
+                Describe {
+                    kind: DescribeKind::Statement,
+                    name: "".to_string(),
+                },
+            )
+            .await?;
+        let mut schema = None;
+        let mut parameter_types = vec![];
+        for response in resolver.sync(client_id).await? {
+            match response {
+                SyncResponse::Schema {
+                    schema: described,
+                    query: _,
+                } => schema = Some(described),
+                SyncResponse::ParameterDescription(description) => {
+                    parameter_types = description.types
+                }
+                _ => {}
+            }
+        }
+        let schema = schema.ok_or_else(|| anyhow::anyhow!("the query wasn't described"))?;
+
+        let batches = resolver.query(client_id, query.to_string()).await?;
+        let notices = resolver.take_notices(client_id);
+        resolver.terminate(client_id).await?;
+
+        let data = match batches.first() {
+            Some(batch) => RecordBatch::concat(&batch.schema(), &batches)?,
+            None => RecordBatch::new_empty(Arc::new(schema.clone())),
+        };
+
+        Ok(PolicyTestResult {
+            schema,
+            parameter_types,
+            data,
+            notices,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        projection::{ProjectedOrigin, TableColumn},
+        Transformer, TransformerError, UnparseableQueryPolicy,
+    };
+    use arrow::datatypes::Field;
+    use proboscis_core::resolver::{collect_copy_out, CommandCompleteTag, CopyOutMessage};
+
+    // Replaces the values of a column with asterisks
+    struct Mask(&'static str);
+
+    impl Mask {
+        fn is_masked(&self, origin: &ProjectedOrigin) -> bool {
+            matches!(origin, ProjectedOrigin::TableColumn(TableColumn { table: _, column }) if column == self.0)
+        }
+    }
+
+    impl Transformer for Mask {
+        fn transform_schema(
+            &self,
+            schema: &Schema,
+            _origins: &[ProjectedOrigin],
+        ) -> Result<Schema, TransformerError> {
+            Ok(schema.clone())
+        }
+
+        fn transform_records(
+            &self,
+            data: &RecordBatch,
+            origins: &[ProjectedOrigin],
+        ) -> Result<RecordBatch, TransformerError> {
+            let columns = data
+                .columns()
+                .iter()
+                .zip(origins)
+                .map(|(column, origin)| -> ArrayRef {
+                    if self.is_masked(origin) {
+                        Arc::new(StringArray::from(vec!["***"; column.len()]))
+                    } else {
+                        column.clone()
+                    }
+                })
+                .collect();
+
+            Ok(RecordBatch::try_new(data.schema(), columns)?)
+        }
+    }
+
+    // Accepts the parameters as integers, which it would hash before the target receives them
+    struct HashedParameters;
+
+    const INT8_OID: u32 = 20;
+
+    impl Transformer for HashedParameters {
+        fn transform_schema(
+            &self,
+            schema: &Schema,
+            _origins: &[ProjectedOrigin],
+        ) -> Result<Schema, TransformerError> {
+            Ok(schema.clone())
+        }
+
+        fn transform_records(
+            &self,
+            data: &RecordBatch,
+            _origins: &[ProjectedOrigin],
+        ) -> Result<RecordBatch, TransformerError> {
+            Ok(data.clone())
+        }
+
+        fn transform_parameter_types(
+            &self,
+            _query: &str,
+            types: &[u32],
+        ) -> Result<Vec<u32>, TransformerError> {
+            Ok(vec![INT8_OID; types.len()])
+        }
+    }
+
+    fn emails(result: &PolicyTestResult) -> Vec<String> {
+        let column = result
+            .data
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+
+        (0..column.len())
+            .map(|index| column.value(index).to_string())
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_policy() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("email", DataType::Utf8, false),
+        ]);
+        let test = |user: &str| PolicyTest::new(schema.clone()).unwrap().with_user(user);
+
+        let policy = |resolver: TransformingResolver| {
+            resolver
+                .add_user_transformer("analyst", Box::new(Mask("email")))
+                .deny_user_column("intern", "contacts", "email")
+        };
+        let query = "SELECT id, email FROM contacts";
+
+        let result = test("analyst").run(query, policy).await.unwrap();
+        assert_eq!(2, result.schema.fields().len());
+        assert_eq!(vec!["***"; SAMPLE_ROWS], emails(&result));
+
+        let result = test("admin").run(query, policy).await.unwrap();
+        assert_eq!("value 0", emails(&result)[0]);
+
+        assert!(test("intern").run(query, policy).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_parameter_types() {
+        let schema = Schema::new(vec![Field::new("email", DataType::Utf8, false)]);
+        let test = PolicyTest::new(schema).unwrap();
+        let query = "SELECT email FROM contacts WHERE id = $1";
+
+        let result = test.run(query, |resolver| resolver).await.unwrap();
+        assert_eq!(vec![25], result.parameter_types);
+
+        let result = test
+            .run(query, |resolver| {
+                resolver.add_transformer(Box::new(HashedParameters))
+            })
+            .await
+            .unwrap();
+        assert_eq!(vec![INT8_OID], result.parameter_types);
+    }
+
+    #[tokio::test]
+    async fn test_row_caps() {
+        let schema = Schema::new(vec![Field::new("id", DataType::Int32, false)]);
+        let test = |user: &str| PolicyTest::new(schema.clone()).unwrap().with_user(user);
+
+        let policy = |resolver: TransformingResolver| {
+            resolver
+                .limit_user_rows("analyst", 7)
+                .limit_role_rows("auditor", 3)
+        };
+        let query = "SELECT id FROM contacts";
+
+        let result = test("admin").run(query, policy).await.unwrap();
+        assert_eq!(SAMPLE_ROWS, result.data.num_rows());
+        assert!(result.notices.is_empty());
+
+        let result = test("analyst").run(query, policy).await.unwrap();
+        assert_eq!(7, result.data.num_rows());
+        assert_eq!(1, result.notices.len());
+        assert_eq!(
+            Some("01000"),
+            proboscis_core::sqlstate::code_of(&result.notices[0])
+        );
+
+        let result = test("analyst")
+            .with_statement("SET ROLE auditor")
+            .run(query, policy)
+            .await
+            .unwrap();
+        assert_eq!(3, result.data.num_rows());
+    }
+
+    #[tokio::test]
+    async fn test_parameterized_min_rows() {
+        let schema = Schema::new(vec![Field::new("age", DataType::Int32, false)]);
+        let test = PolicyTest::new(schema).unwrap();
+        let policy = |min_rows| {
+            move |resolver: TransformingResolver| {
+                resolver.require_parameterized_rows("contacts", "zip", min_rows)
+            }
+        };
+
+        let result = test
+            .run("SELECT age FROM contacts WHERE zip = $1", policy(20))
+            .await
+            .unwrap();
+        assert_eq!(0, result.data.num_rows());
+        assert_eq!(1, result.schema.fields().len());
+        assert_eq!(1, result.notices.len());
+
+        let result = test
+            .run("SELECT age FROM contacts WHERE zip = $1", policy(5))
+            .await
+            .unwrap();
+        assert_eq!(SAMPLE_ROWS, result.data.num_rows());
+
+        let result = test
+            .run("SELECT age FROM contacts WHERE id = $1", policy(20))
+            .await
+            .unwrap();
+        assert_eq!(SAMPLE_ROWS, result.data.num_rows());
+        assert!(result.notices.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_role_changes() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("email", DataType::Utf8, false),
+        ]);
+        let test = |user: &str| PolicyTest::new(schema.clone()).unwrap().with_user(user);
+
+        let policy = |resolver: TransformingResolver| {
+            resolver
+                .add_role_transformer("analyst", Box::new(Mask("email")))
+                .restrict_roles("intern", &["reader"])
+        };
+        let query = "SELECT id, email FROM contacts";
+
+        let result = test("admin")
+            .with_statement("SET ROLE analyst")
+            .run(query, policy)
+            .await
+            .unwrap();
+        assert_eq!(vec!["***"; SAMPLE_ROWS], emails(&result));
+
+        let result = test("admin")
+            .with_statement("SET ROLE analyst")
+            .with_statement("RESET ROLE")
+            .run(query, policy)
+            .await
+            .unwrap();
+        assert_eq!("value 0", emails(&result)[0]);
+
+        assert!(test("intern")
+            .with_statement("SET ROLE reader")
+            .run(query, policy)
+            .await
+            .is_ok());
+        assert!(test("intern")
+            .with_statement("SET ROLE analyst")
+            .run(query, policy)
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_unparseable_queries() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("email", DataType::Utf8, false),
+        ]);
+        let test = PolicyTest::new(schema).unwrap();
+        let query = "SELECT id, email FROM contacts TABLESAMPLE SYSTEM (10)";
+
+        let result = test
+            .run(query, |resolver| {
+                resolver.add_transformer(Box::new(Mask("email")))
+            })
+            .await
+            .unwrap();
+        assert_eq!("value 0", emails(&result)[0]);
+
+        let rejected = test
+            .run(query, |resolver| {
+                resolver
+                    .add_transformer(Box::new(Mask("email")))
+                    .with_unparseable_query_policy(UnparseableQueryPolicy::Reject)
+            })
+            .await;
+        assert!(matches!(rejected, Err(ResolveError::Unsupported(_))));
+    }
+
+    #[tokio::test]
+    async fn test_copy_out() {
+        let schema = Schema::new(vec![
+            Field::new("id", DataType::Int32, false),
+            Field::new("email", DataType::Utf8, false),
+        ]);
+        let mock = MockResolver::new()
+            .with_fallback(MockResponse::rows(vec![sample_batch(&schema, 2).unwrap()]));
+        let mut resolver = TransformingResolver::new(Box::new(mock))
+            .add_transformer(Box::new(Mask("email")))
+            .deny_user_column("intern", "contacts", "email");
+
+        let client_id = ClientId::new_v4();
+        resolver
+            .initialize(client_id, &ClientContext::default())
+            .await
+            .unwrap();
+
+        let (response, messages) = resolver
+            .copy_out(
+                client_id,
+                "COPY contacts (id, email) TO STDOUT WITH (FORMAT csv, HEADER)".to_string(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(vec![0, 0], response.column_formats);
+        assert_eq!(
+            vec![
+                CopyOutMessage::Data(b"id,email\n".to_vec()),
+                CopyOutMessage::Data(b"0,***\n".to_vec()),
+                CopyOutMessage::Data(b"1,***\n".to_vec()),
+                CopyOutMessage::Complete(CommandCompleteTag("COPY 2".to_string())),
+            ],
+            collect_copy_out(messages).await.unwrap()
+        );
+
+        let intern_id = ClientId::new_v4();
+        let mut parameters = HashMap::new();
+        parameters.insert("user".to_string(), "intern".to_string());
+        resolver
+            .initialize(intern_id, &ClientContext::new(parameters))
+            .await
+            .unwrap();
+        assert!(resolver
+            .copy_out(intern_id, "COPY contacts TO STDOUT".to_string())
+            .await
+            .is_err());
+    }
+}
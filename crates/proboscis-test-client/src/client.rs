@@ -0,0 +1,542 @@
+use crate::error::{ServerError, TestClientError};
+use proboscis_core::{
+    utils::{
+        connection::{Connection, MaybeTlsStream},
+        password::encode_md5_password_hash,
+        scram::{ScramClient, SCRAM_SHA_256},
+    },
+    Proxy,
+};
+use proboscis_postgres_protocol::{
+    message::{
+        BackendMessage, Bind, BindParameter, CommandCompleteTag, DataRow, Describe, DescribeKind,
+        Execute, FrontendMessage, MD5Hash, MD5Salt, NotificationResponse, Parse,
+        ReadyForQueryTransactionStatus, RowDescription, SASLInitialResponse,
+    },
+    StartupMessage,
+};
+use std::{collections::HashMap, net::SocketAddr};
+use tokio::net::{TcpListener, TcpStream};
+
+/// The result of a statement, its values in the text format
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<Option<String>>>,
+    /// The tag of the CommandComplete message, e.g. `INSERT 0 1`
+    pub tag: Option<String>,
+    /// The data of a `COPY ... TO STDOUT`
+    pub copied: Vec<u8>,
+}
+
+impl QueryResult {
+    /// The values of the column, none if the result doesn't have it
+    pub fn column(&self, name: &str) -> Option<Vec<Option<&str>>> {
+        let index = self.columns.iter().position(|column| column == name)?;
+        Some(self.rows.iter().map(|row| row[index].as_deref()).collect())
+    }
+}
+
+pub struct TestClient {
+    connection: Connection,
+    /// The parameters the server reported with ParameterStatus messages
+    pub parameters: HashMap<String, String>,
+    /// The notifications received on the channels the client listens on, in order
+    pub notifications: Vec<NotificationResponse>,
+    pub transaction_status: ReadyForQueryTransactionStatus,
+}
+
+impl TestClient {
+    /// Connects as the user, answering a request for its password with an md5 hash
+    /// or a SCRAM-SHA-256 exchange
+    pub async fn connect(
+        address: SocketAddr,
+        user: &str,
+        password: &str,
+    ) -> Result<TestClient, TestClientError> {
+        let parameters = vec![
+            ("user".to_string(), user.to_string()),
+            ("client_encoding".to_string(), "UTF8".to_string()),
+        ];
+
+        TestClient::connect_with_parameters(address, parameters.into_iter().collect(), password)
+            .await
+    }
+
+    /// Connects with the parameters of the startup message, e.g. `database` or `application_name`
+    pub async fn connect_with_parameters(
+        address: SocketAddr,
+        parameters: HashMap<String, String>,
+        password: &str,
+    ) -> Result<TestClient, TestClientError> {
+        let stream = TcpStream::connect(address).await?;
+        let mut connection = Connection::new(MaybeTlsStream::Left(stream), parameters.clone());
+        let user = parameters.get("user").cloned().unwrap_or_default();
+
+        connection
+            .write_startup_message(StartupMessage::Startup { params: parameters })
+            .await?;
+
+        let mut client = TestClient {
+            connection,
+            parameters: HashMap::new(),
+            notifications: vec![],
+            transaction_status: ReadyForQueryTransactionStatus::NotInTransaction,
+        };
+
+        let mut scram: Option<ScramClient> = None;
+
+        loop {
+            match client.connection.read_backend_message().await? {
+                BackendMessage::AuthenticationRequestMD5Password(MD5Salt(salt)) => {
+                    let hash = encode_md5_password_hash(&user, password, &salt);
+                    client
+                        .connection
+                        .write_message(FrontendMessage::MD5HashedPassword(MD5Hash(hash)).into())
+                        .await?;
+                }
+                BackendMessage::AuthenticationSASL(mechanisms) => {
+                    if !mechanisms
+                        .iter()
+                        .any(|mechanism| mechanism == SCRAM_SHA_256)
+                    {
+                        return Err(TestClientError::UnsupportedAuthentication);
+                    }
+
+                    let scram_client = ScramClient::new(password);
+                    client
+                        .connection
+                        .write_message(
+                            FrontendMessage::SASLInitialResponse(SASLInitialResponse {
+                                mechanism: SCRAM_SHA_256.to_string(),
+                                data: Some(scram_client.client_first()),
+                            })
+                            .into(),
+                        )
+                        .await?;
+                    scram = Some(scram_client);
+                }
+                BackendMessage::AuthenticationSASLContinue(data) => {
+                    let scram = scram.as_mut().ok_or_else(|| {
+                        TestClientError::UnexpectedMessage(
+                            BackendMessage::AuthenticationSASLContinue(data.clone()),
+                        )
+                    })?;
+                    let client_final = scram.handle_server_first(&data)?;
+                    client
+                        .connection
+                        .write_message(FrontendMessage::SASLResponse(client_final).into())
+                        .await?;
+                }
+                BackendMessage::AuthenticationSASLFinal(data) => match &scram {
+                    Some(scram) => scram.verify_server_final(&data)?,
+                    None => {
+                        return Err(TestClientError::UnexpectedMessage(
+                            BackendMessage::AuthenticationSASLFinal(data),
+                        ))
+                    }
+                },
+                BackendMessage::AuthenticationOk | BackendMessage::BackendKeyData(_) => {}
+                BackendMessage::ParameterStatus(status) => {
+                    client.parameters.insert(status.key, status.value);
+                }
+                BackendMessage::ReadyForQuery(status) => {
+                    client.transaction_status = status;
+                    return Ok(client);
+                }
+                BackendMessage::Error(error) => return Err(TestClientError::Server(error.into())),
+                message => return Err(TestClientError::UnexpectedMessage(message)),
+            }
+        }
+    }
+
+    /// Runs the statements of the query with the simple query protocol, returns a result per
+    /// statement. After an error of the server, the client can continue with the next query.
+    pub async fn simple_query(&mut self, query: &str) -> Result<Vec<QueryResult>, TestClientError> {
+        self.connection
+            .write_message(FrontendMessage::SimpleQuery(query.to_string()).into())
+            .await?;
+
+        self.read_results().await
+    }
+
+    /// Runs a `COPY ... FROM STDIN`, sending the chunks of data as they are
+    pub async fn copy_in(
+        &mut self,
+        query: &str,
+        data: &[&[u8]],
+    ) -> Result<QueryResult, TestClientError> {
+        self.connection
+            .write_message(FrontendMessage::SimpleQuery(query.to_string()).into())
+            .await?;
+
+        match self.connection.read_backend_message().await? {
+            BackendMessage::CopyInResponse(_) => {}
+            BackendMessage::Error(error) => {
+                self.read_results().await?;
+                return Err(TestClientError::Server(error.into()));
+            }
+            message => return Err(TestClientError::UnexpectedMessage(message)),
+        }
+
+        for chunk in data {
+            self.connection
+                .write_message(FrontendMessage::CopyData(chunk.to_vec()).into())
+                .await?;
+        }
+        self.connection
+            .write_message(FrontendMessage::CopyDone.into())
+            .await?;
+
+        Ok(self.read_results().await?.pop().unwrap_or_default())
+    }
+
+    /// Runs the query with the extended query protocol, binding the parameters as text
+    pub async fn query(
+        &mut self,
+        query: &str,
+        params: &[&str],
+    ) -> Result<QueryResult, TestClientError> {
+        let messages = vec![
+            FrontendMessage::Parse(Parse {
+                statement_name: "".to_string(),
+                query: query.to_string(),
+                param_types: vec![],
+            }),
+            FrontendMessage::Bind(Bind {
+                statement: "".to_string(),
+                portal: "".to_string(),
+                params: params
+                    .iter()
+                    .map(|param| BindParameter::Text(param.to_string()))
+                    .collect(),
+                results: vec![],
+            }),
+            FrontendMessage::Describe(Describe {
+                kind: DescribeKind::Portal,
+                name: "".to_string(),
+            }),
+            FrontendMessage::Execute(Execute {
+                portal: "".to_string(),
+                row_limit: 0,
+            }),
+            FrontendMessage::Sync,
+        ];
+
+        for message in messages {
+            self.connection.write_message(message.into()).await?;
+        }
+
+        Ok(self.read_results().await?.pop().unwrap_or_default())
+    }
+
+    /// Ends the session
+    pub async fn terminate(mut self) -> Result<(), TestClientError> {
+        self.connection
+            .write_message(FrontendMessage::Terminate.into())
+            .await?;
+        Ok(())
+    }
+
+    /// Reads the responses until the server is ready for the next query
+    async fn read_results(&mut self) -> Result<Vec<QueryResult>, TestClientError> {
+        let mut results = vec![];
+        let mut current = QueryResult::default();
+        let mut error = None;
+
+        loop {
+            match self.connection.read_backend_message().await? {
+                BackendMessage::RowDescription(RowDescription { fields }) => {
+                    current.columns = fields.into_iter().map(|field| field.name).collect();
+                }
+                BackendMessage::DataRow(DataRow { field_data }) => current.rows.push(
+                    field_data
+                        .into_iter()
+                        .map(|value| {
+                            value.map(|value| String::from_utf8_lossy(&value).into_owned())
+                        })
+                        .collect(),
+                ),
+                BackendMessage::CopyData(data) => current.copied.extend(data),
+                BackendMessage::CommandComplete(CommandCompleteTag(tag)) => {
+                    current.tag = Some(tag);
+                    results.push(std::mem::take(&mut current));
+                }
+                BackendMessage::EmptyQueryResponse | BackendMessage::PortalSuspended => {
+                    results.push(std::mem::take(&mut current));
+                }
+                BackendMessage::Error(message) => error = Some(ServerError::from(message)),
+                BackendMessage::ReadyForQuery(status) => {
+                    self.transaction_status = status;
+                    break;
+                }
+                BackendMessage::ParameterStatus(status) => {
+                    self.parameters.insert(status.key, status.value);
+                }
+                BackendMessage::NotificationResponse(notification) => {
+                    self.notifications.push(notification)
+                }
+                BackendMessage::ParseComplete
+                | BackendMessage::BindComplete
+                | BackendMessage::CloseComplete
+                | BackendMessage::NoData
+                | BackendMessage::ParameterDescription(_)
+                | BackendMessage::NoticeResponse(_)
+                | BackendMessage::CopyOutResponse(_)
+                | BackendMessage::CopyDone => {}
+                message => return Err(TestClientError::UnexpectedMessage(message)),
+            }
+        }
+
+        match error {
+            Some(error) => Err(TestClientError::Server(error)),
+            None => Ok(results),
+        }
+    }
+}
+
+/// Serves the clients of the proxy on a free port of localhost, in the background
+pub async fn spawn_proxy(mut proxy: Proxy) -> Result<SocketAddr, TestClientError> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let address = listener.local_addr()?;
+
+    tokio::spawn(async move { proxy.listen(listener).await });
+
+    Ok(address)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::{
+        array::{Int32Array, StringArray},
+        datatypes::{DataType, Field, Schema},
+        record_batch::RecordBatch,
+    };
+    use proboscis_core::{
+        utils::{password::md5_password_verifier, scram::ScramVerifier},
+        AuthenticationMethod, ProxyBuilder,
+    };
+    use proboscis_resolver_mock::{MockResolver, MockResponse};
+    use std::sync::Arc;
+
+    fn contacts() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int32, false),
+                Field::new("name", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(Int32Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec![Some("Max"), None])),
+            ],
+        )
+        .unwrap()
+    }
+
+    async fn proxy() -> SocketAddr {
+        let resolver = MockResolver::new()
+            .on_query_containing("FROM contacts", MockResponse::rows(vec![contacts()]))
+            .on_query_containing("FROM orders", MockResponse::error("relation doesn't exist"))
+            .on_query_containing("SET TimeZone", MockResponse::command_complete("SET"))
+            .on_query_containing("LISTEN", MockResponse::command_complete("LISTEN"))
+            .on_query_containing("NOTIFY", MockResponse::command_complete("NOTIFY"))
+            .on_query(
+                "COPY contacts FROM STDIN",
+                MockResponse::command_complete("COPY 0"),
+            )
+            .with_parameter_change("SET TimeZone", "TimeZone", "Europe/Berlin")
+            .with_notification("NOTIFY contacts", "contacts", "inserted");
+
+        let proxy = ProxyBuilder::new()
+            .credential("admin", "password")
+            .resolver(Box::new(resolver))
+            .build()
+            .unwrap();
+
+        spawn_proxy(proxy).await.unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_simple_query() {
+        let address = proxy().await;
+
+        assert!(matches!(
+            TestClient::connect(address, "admin", "wrong").await,
+            Err(TestClientError::Server(ServerError { code, .. })) if code == "28P01"
+        ));
+
+        let mut client = TestClient::connect(address, "admin", "password")
+            .await
+            .unwrap();
+        assert_eq!(
+            Some("UTF8"),
+            client.parameters.get("client_encoding").map(String::as_str)
+        );
+
+        let results = client
+            .simple_query("SELECT id, name FROM contacts")
+            .await
+            .unwrap();
+        assert_eq!(vec!["id", "name"], results[0].columns);
+        assert_eq!(Some(vec![Some("Max"), None]), results[0].column("name"));
+
+        // The client continues after an error
+        assert!(matches!(
+            client.simple_query("SELECT * FROM orders").await,
+            Err(TestClientError::Server(_))
+        ));
+        assert_eq!(
+            2,
+            client
+                .simple_query("SELECT id FROM contacts")
+                .await
+                .unwrap()[0]
+                .rows
+                .len()
+        );
+
+        // Also after an error of the extended query protocol
+        assert!(matches!(
+            client.query("SELECT * FROM orders WHERE id = $1", &["1"]).await,
+            Err(TestClientError::Server(ServerError { severity, .. })) if severity == "ERROR"
+        ));
+        assert_eq!(
+            2,
+            client
+                .query("SELECT id FROM contacts WHERE id > $1", &["0"])
+                .await
+                .unwrap()
+                .rows
+                .len()
+        );
+
+        client.terminate().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_scram_authentication() {
+        let verifier = ScramVerifier::from_password("secret").to_string();
+        let proxy = ProxyBuilder::new()
+            .credential("admin", "password")
+            .credential("analyst", &verifier)
+            .credential("auditor", &md5_password_verifier("auditor", "audit"))
+            .authentication_method(AuthenticationMethod::ScramSha256)
+            .resolver(Box::new(MockResolver::new()))
+            .build()
+            .unwrap();
+        let address = spawn_proxy(proxy).await.unwrap();
+
+        for (user, password) in [
+            ("admin", "password"),
+            ("analyst", "secret"),
+            ("auditor", "audit"),
+        ] {
+            TestClient::connect(address, user, password)
+                .await
+                .unwrap()
+                .terminate()
+                .await
+                .unwrap();
+        }
+
+        for (user, password) in [
+            ("admin", "wrong"),
+            ("analyst", "wrong"),
+            ("unknown", "password"),
+        ] {
+            assert!(matches!(
+                TestClient::connect(address, user, password).await,
+                Err(TestClientError::Server(ServerError { code, .. })) if code == "28P01"
+            ));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_extended_query() {
+        let mut client = TestClient::connect(proxy().await, "admin", "password")
+            .await
+            .unwrap();
+
+        let result = client
+            .query("SELECT id, name FROM contacts WHERE id > $1", &["0"])
+            .await
+            .unwrap();
+        assert_eq!(vec!["id", "name"], result.columns);
+        assert_eq!(Some(vec![Some("1"), Some("2")]), result.column("id"));
+    }
+
+    #[tokio::test]
+    async fn test_copy() {
+        let mut client = TestClient::connect(proxy().await, "admin", "password")
+            .await
+            .unwrap();
+
+        let result = client
+            .copy_in("COPY contacts FROM STDIN", &[b"3\tAnna\n4\t", b"Paul\n"])
+            .await
+            .unwrap();
+        assert_eq!(Some("COPY 2"), result.tag.as_deref());
+
+        let results = client
+            .simple_query("COPY (SELECT id, name FROM contacts) TO STDOUT")
+            .await
+            .unwrap();
+        assert_eq!(b"1\tMax\n2\t\\N\n".to_vec(), results[0].copied);
+        assert_eq!(Some("COPY 2"), results[0].tag.as_deref());
+
+        // Copies the resolver rejects leave the client ready for the next query
+        assert!(matches!(
+            client.copy_in("COPY orders FROM STDIN", &[]).await,
+            Err(TestClientError::Server(_))
+        ));
+        assert_eq!(
+            2,
+            client
+                .simple_query("SELECT id FROM contacts")
+                .await
+                .unwrap()[0]
+                .rows
+                .len()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_parameter_changes() {
+        let mut client = TestClient::connect(proxy().await, "admin", "password")
+            .await
+            .unwrap();
+
+        client
+            .simple_query("SET TimeZone = 'Europe/Berlin'")
+            .await
+            .unwrap();
+        assert_eq!(
+            Some("Europe/Berlin"),
+            client.parameters.get("TimeZone").map(String::as_str)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_notifications() {
+        let mut client = TestClient::connect(proxy().await, "admin", "password")
+            .await
+            .unwrap();
+
+        client.simple_query("LISTEN contacts").await.unwrap();
+        assert!(client.notifications.is_empty());
+
+        client
+            .simple_query("NOTIFY contacts, 'inserted'")
+            .await
+            .unwrap();
+        assert_eq!(
+            vec![("contacts", "inserted")],
+            client
+                .notifications
+                .iter()
+                .map(|n| (n.channel.as_str(), n.payload.as_str()))
+                .collect::<Vec<_>>()
+        );
+    }
+}
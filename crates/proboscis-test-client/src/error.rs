@@ -0,0 +1,45 @@
+use proboscis_postgres_protocol::message::{BackendMessage, Error};
+use thiserror::Error;
+
+/// An ErrorResponse of the server
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerError {
+    pub severity: String,
+    pub code: String,
+    pub message: String,
+    pub detail: Option<String>,
+}
+
+impl From<Error> for ServerError {
+    fn from(error: Error) -> Self {
+        let field = |tag: u8| error.field(tag).unwrap_or_default().to_string();
+
+        ServerError {
+            severity: field(b'S'),
+            code: field(b'C'),
+            message: field(b'M'),
+            detail: error.detail().map(String::from),
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum TestClientError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Parse(#[from] proboscis_postgres_protocol::ParseError),
+
+    #[error("the server returned an error: {} {}", .0.code, .0.message)]
+    Server(ServerError),
+
+    #[error("unexpected message: {0:?}")]
+    UnexpectedMessage(BackendMessage),
+
+    #[error("the server requested an unsupported authentication method")]
+    UnsupportedAuthentication,
+
+    #[error(transparent)]
+    Scram(#[from] proboscis_core::utils::scram::ScramError),
+}
@@ -0,0 +1,7 @@
+//! A minimal client for tests of proxies and resolvers, which doesn't need
+//! a postgres client library or a database running in docker
+mod client;
+mod error;
+
+pub use client::{spawn_proxy, QueryResult, TestClient};
+pub use error::{ServerError, TestClientError};
@@ -1,6 +1,7 @@
 use maplit::hashmap;
 use proboscis_anonymization::{
-    AnonymizationCriteria, AnonymizationTransformer, NumericAggregation, StringAggregation,
+    AnonymizationCriteria, AnonymizationTransformer, ColumnConcurrency, NumericAggregation,
+    StringAggregation,
 };
 use proboscis_core::{Config, Proxy};
 use proboscis_resolver_postgres::{PostgresResolver, TargetConfig};
@@ -67,7 +68,9 @@ async fn run_proxy(database_connection_url: String) -> String {
             .add_transformer(Box::new(AnonymizationTransformer {
                 identifier_columns,
                 quasi_identifier_columns,
-                criteria: AnonymizationCriteria::KAnonymous { k: 3 },
+                criteria: vec![AnonymizationCriteria::KAnonymous { k: 3 }],
+                table_criteria: hashmap! {},
+                concurrency: ColumnConcurrency::sequential(),
             })),
         ),
     );
@@ -1,9 +1,8 @@
 use arrow::{array::LargeStringArray, datatypes::Schema, record_batch::RecordBatch};
-use maplit::hashmap;
-use proboscis_core::{Config, Proxy};
+use proboscis_core::ProxyBuilder;
 use proboscis_resolver_postgres::{PostgresResolver, TargetConfig};
 use proboscis_resolver_transformer::{
-    projection::ProjectedOrigin, Transformer, TransformerError, TransformingResolver,
+    projection::ProjectedOrigin, ProxyBuilderExt, Transformer, TransformerError,
 };
 use std::sync::Arc;
 use testcontainers::clients;
@@ -54,25 +53,19 @@ async fn run_proxy(database_connection_url: String) -> String {
     let proxy_user = "admin";
     let proxy_password = "password";
 
-    let mut proxy = Proxy::new(
-        Config {
-            credentials: hashmap! {
-                proxy_user.to_string() => proxy_password.to_string(),
-            },
-            tls_config: None,
-        },
-        Box::new(
-            TransformingResolver::new(Box::new(
-                PostgresResolver::create(
-                    TargetConfig::from_uri(&database_connection_url).unwrap(),
-                    10,
-                )
-                .await
-                .unwrap(),
-            ))
-            .add_transformer(Box::new(ExampleTransformer)),
-        ),
-    );
+    let mut proxy = ProxyBuilder::new()
+        .credential(proxy_user, proxy_password)
+        .resolver(Box::new(
+            PostgresResolver::create(
+                TargetConfig::from_uri(&database_connection_url).unwrap(),
+                10,
+            )
+            .await
+            .unwrap(),
+        ))
+        .transformers(vec![Box::new(ExampleTransformer)])
+        .build()
+        .unwrap();
 
     let listener = TcpListener::bind("0.0.0.0:0").await.unwrap();
 
@@ -0,0 +1,36 @@
+//! Building blocks for PostgreSQL compatible network applications.
+//!
+//! Re-exports the crates of the workspace, the optional ones behind features
+//! of the same name, so applications depend on a single crate.
+
+pub use proboscis_core::*;
+
+/// Reading and writing the messages of the postgres protocol
+pub use proboscis_postgres_protocol as protocol;
+
+/// Resolvers answering the queries of clients, e.g. by forwarding them to postgres
+pub mod resolvers {
+    #[cfg(feature = "cache")]
+    pub use proboscis_resolver_cache as cache;
+    #[cfg(feature = "clickhouse")]
+    pub use proboscis_resolver_clickhouse as clickhouse;
+    #[cfg(feature = "duckdb")]
+    pub use proboscis_resolver_duckdb as duckdb;
+    #[cfg(feature = "fallback")]
+    pub use proboscis_resolver_fallback as fallback;
+    #[cfg(feature = "federation")]
+    pub use proboscis_resolver_federation as federation;
+    #[cfg(feature = "mock")]
+    pub use proboscis_resolver_mock as mock;
+    #[cfg(feature = "postgres")]
+    pub use proboscis_resolver_postgres as postgres;
+    #[cfg(feature = "recording")]
+    pub use proboscis_resolver_recording as recording;
+    #[cfg(feature = "shadow")]
+    pub use proboscis_resolver_shadow as shadow;
+    #[cfg(feature = "transformer")]
+    pub use proboscis_resolver_transformer as transformer;
+}
+
+#[cfg(feature = "anonymization")]
+pub use proboscis_anonymization as anonymization;